@@ -0,0 +1,136 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bytes sent on the simulated network, attributed to senders, receivers, kinds of messages,
+//! and kinds of content, e.g. record types.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::base_types::{Author, Duration};
+
+#[cfg(test)]
+#[path = "unit_tests/bandwidth_tests.rs"]
+mod bandwidth_tests;
+
+/// Number of bytes of each kind of content of a message. Kinds may repeat.
+pub type ByteCounts = Vec<(&'static str, usize)>;
+
+/// Messages whose size on the wire can be measured.
+pub trait MessageSize {
+    fn byte_counts(&self) -> ByteCounts;
+
+    /// Identifiers of the payloads that the message carries without encoding them, e.g. the
+    /// commands of its blocks, see `PayloadSizes`. None by default.
+    fn payloads(&self) -> Vec<u64> {
+        Vec::new()
+    }
+}
+
+/// Sizes of the payloads that simulated messages only identify, e.g. client commands. Sizes
+/// are derived from the identifiers rather than drawn at random, so that all the copies of a
+/// payload have the same size and runs with and without payloads follow the same delays.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "distribution")]
+pub enum PayloadSizes {
+    #[serde(rename = "fixed")]
+    Fixed { bytes: usize },
+    /// Spread over `[min, max]`, assuming well-mixed identifiers, e.g. hashes.
+    #[serde(rename = "uniform")]
+    Uniform { min: usize, max: usize },
+}
+
+impl PayloadSizes {
+    pub fn size(&self, payload: u64) -> usize {
+        match *self {
+            PayloadSizes::Fixed { bytes } => bytes,
+            PayloadSizes::Uniform { min, max } => min + (payload % (max - min + 1) as u64) as usize,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct BandwidthStats {
+    /// Length of the intervals of the time series.
+    pub interval: Duration,
+    /// Total number of bytes sent.
+    pub total_bytes: usize,
+    /// Bytes sent by each author.
+    pub sent: Vec<usize>,
+    /// Bytes sent to each author.
+    pub received: Vec<usize>,
+    /// Bytes sent per kind of message: "notify", "request", or "response".
+    pub by_message: BTreeMap<&'static str, usize>,
+    /// Bytes sent per kind of content.
+    pub by_content: BTreeMap<&'static str, usize>,
+    /// Bytes sent during each interval `[i * interval, (i + 1) * interval)`.
+    pub time_series: Vec<usize>,
+    /// Bytes that senders did not send because the receiver got the same content from them
+    /// before.
+    pub suppressed_bytes: usize,
+    /// Bytes not sent again, per kind of content.
+    pub suppressed_by_content: BTreeMap<&'static str, usize>,
+}
+
+impl BandwidthStats {
+    pub fn new(num_authors: usize, interval: Duration) -> Self {
+        assert!(interval > 0, "Intervals of time series must not be empty");
+        BandwidthStats {
+            interval,
+            sent: vec![0; num_authors],
+            received: vec![0; num_authors],
+            ..BandwidthStats::default()
+        }
+    }
+
+    /// Account for a message sent at the given global time.
+    pub fn record(
+        &mut self,
+        time: i64,
+        sender: Author,
+        receiver: Author,
+        message_kind: &'static str,
+        byte_counts: &[(&'static str, usize)],
+    ) {
+        let bytes: usize = byte_counts.iter().map(|(_, count)| count).sum();
+        self.total_bytes += bytes;
+        self.sent[sender.0] += bytes;
+        self.received[receiver.0] += bytes;
+        *self.by_message.entry(message_kind).or_insert(0) += bytes;
+        for (kind, count) in byte_counts {
+            *self.by_content.entry(kind).or_insert(0) += count;
+        }
+        let index = std::cmp::max(time, 0) as usize / self.interval as usize;
+        if self.time_series.len() <= index {
+            self.time_series.resize(index + 1, 0);
+        }
+        self.time_series[index] += bytes;
+    }
+
+    /// Account for a message that was reduced to `sent` before sending, instead of `original`.
+    /// The message itself must be recorded separately.
+    pub fn record_suppressed(
+        &mut self,
+        original: &[(&'static str, usize)],
+        sent: &[(&'static str, usize)],
+    ) {
+        let mut counts = BTreeMap::new();
+        for (kind, count) in original {
+            *counts.entry(*kind).or_insert(0) += *count as i64;
+        }
+        for (kind, count) in sent {
+            *counts.entry(*kind).or_insert(0) -= *count as i64;
+        }
+        for (kind, count) in counts {
+            if count > 0 {
+                self.suppressed_bytes += count as usize;
+                *self.suppressed_by_content.entry(kind).or_insert(0) += count as usize;
+            }
+        }
+    }
+
+    /// Largest number of bytes sent during an interval of the time series.
+    pub fn peak_bytes(&self) -> usize {
+        self.time_series.iter().cloned().max().unwrap_or(0)
+    }
+}
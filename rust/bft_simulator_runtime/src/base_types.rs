@@ -2,11 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use failure::Error;
-use std::{
-    collections::hash_map::DefaultHasher,
-    fmt,
-    hash::{Hash, Hasher},
-};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[cfg(test)]
 #[path = "unit_tests/base_type_tests.rs"]
@@ -14,17 +11,25 @@ mod base_type_tests;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct Round(pub usize);
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Serialize, Deserialize)]
 pub struct NodeTime(pub i64);
 pub type Duration = i64;
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct Author(pub usize);
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct Signature(pub u64);
 
+/// Simulated public key: the key of the given version of an author. Authors start with version
+/// 0, and each key rotation moves to the next version.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
+pub struct PublicKey {
+    pub author: Author,
+    pub version: usize,
+}
+
 impl fmt::Debug for NodeTime {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "@{}", self.0)
@@ -41,7 +46,7 @@ impl std::ops::Add<usize> for Round {
 
 impl NodeTime {
     pub fn never() -> Self {
-        NodeTime(std::i64::MAX)
+        NodeTime(i64::MAX)
     }
 }
 
@@ -53,23 +58,65 @@ impl std::ops::Add<Duration> for NodeTime {
     }
 }
 
+impl PublicKey {
+    /// The key that an author starts with.
+    pub fn initial(author: Author) -> Self {
+        PublicKey { author, version: 0 }
+    }
+
+    /// The key replacing this one after a rotation.
+    pub fn rotated(self) -> Self {
+        PublicKey {
+            author: self.author,
+            version: self.version + 1,
+        }
+    }
+}
+
+impl From<Author> for PublicKey {
+    fn from(author: Author) -> Self {
+        PublicKey::initial(author)
+    }
+}
+
 impl Signature {
+    /// Sign with the initial key of the author.
     pub fn sign(hash: u64, author: Author) -> Self {
-        let mut hasher = DefaultHasher::new();
-        hash.hash(&mut hasher);
-        author.hash(&mut hasher);
-        Signature(hasher.finish())
+        Self::sign_with_key(hash, PublicKey::initial(author))
+    }
+
+    pub fn sign_with_key(hash: u64, key: PublicKey) -> Self {
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&hash.to_le_bytes());
+        bytes.extend_from_slice(&(key.author.0 as u64).to_le_bytes());
+        // Initial keys sign as authors did before keys could be rotated.
+        if key.version > 0 {
+            bytes.extend_from_slice(&(key.version as u64).to_le_bytes());
+        }
+        Signature(stable_hash(&bytes))
     }
 
+    /// Check against the initial key of the author.
     pub fn check(&self, hash: u64, author: Author) -> Result<()> {
-        let mut hasher = DefaultHasher::new();
-        hash.hash(&mut hasher);
-        author.hash(&mut hasher);
-        ensure!(hasher.finish() == self.0, "Signatures must be valid.");
+        self.check_with_key(hash, PublicKey::initial(author))
+    }
+
+    pub fn check_with_key(&self, hash: u64, key: PublicKey) -> Result<()> {
+        ensure!(
+            Self::sign_with_key(hash, key) == *self,
+            "Signatures must be valid."
+        );
         Ok(())
     }
 }
 
+/// 64-bit FNV-1a, a hash function with a fixed specification, unlike `DefaultHasher`.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
 impl Round {
     pub fn max_update(&mut self, round: Round) {
         *self = std::cmp::max(*self, round);
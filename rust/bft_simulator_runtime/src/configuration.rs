@@ -2,21 +2,111 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
 
 #[cfg(test)]
 #[path = "unit_tests/configuration_tests.rs"]
 mod configuration_tests;
 
+/// Resilience of the classic BFT setting, where `n = 3f + 1`.
+pub const DEFAULT_RESILIENCE: usize = 3;
+
+/// Serialized form of configurations, without the total number of votes so that it cannot be
+/// inconsistent.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct VotingRights {
+    voting_rights: BTreeMap<Author, usize>,
+    resilience: usize,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    key_versions: BTreeMap<Author, usize>,
+}
+
+impl From<EpochConfiguration> for VotingRights {
+    fn from(configuration: EpochConfiguration) -> Self {
+        VotingRights {
+            voting_rights: configuration.voting_rights,
+            resilience: configuration.resilience,
+            key_versions: configuration.key_versions,
+        }
+    }
+}
+
+impl TryFrom<VotingRights> for EpochConfiguration {
+    type Error = String;
+
+    fn try_from(rights: VotingRights) -> std::result::Result<Self, String> {
+        if rights.resilience < 2 {
+            return Err(format!("Invalid resilience {}", rights.resilience));
+        }
+        Ok(
+            EpochConfiguration::with_resilience(rights.voting_rights, rights.resilience)
+                .with_key_versions(rights.key_versions),
+        )
+    }
+}
+
 impl EpochConfiguration {
     pub fn new(voting_rights: BTreeMap<Author, usize>) -> Self {
+        Self::with_resilience(voting_rights, DEFAULT_RESILIENCE)
+    }
+
+    /// Configuration tolerating `f` faulty votes out of `n = resilience * f + 1`, e.g. with a
+    /// resilience of 5 for protocols with a fast path. Quorums need `n - f` votes. A resilience
+    /// of 2 only tolerates crash faults, since quorums may then intersect in a single vote.
+    pub fn with_resilience(voting_rights: BTreeMap<Author, usize>, resilience: usize) -> Self {
+        assert!(resilience >= 2, "Quorums must intersect");
         let total_votes = voting_rights.iter().fold(0, |sum, (_, votes)| sum + *votes);
         EpochConfiguration {
             voting_rights,
             total_votes,
+            resilience,
+            key_versions: BTreeMap::new(),
         }
     }
 
+    /// The same configuration, where the given authors sign with the given versions of their
+    /// key instead of their initial one.
+    pub fn with_key_versions(mut self, key_versions: BTreeMap<Author, usize>) -> Self {
+        self.key_versions = key_versions
+            .into_iter()
+            .filter(|(_, version)| *version > 0)
+            .collect();
+        self
+    }
+
+    pub fn key_versions(&self) -> &BTreeMap<Author, usize> {
+        &self.key_versions
+    }
+
+    /// The key that signs the records of the author in this epoch.
+    pub fn public_key(&self, author: Author) -> PublicKey {
+        PublicKey {
+            author,
+            version: *self.key_versions.get(&author).unwrap_or(&0),
+        }
+    }
+
+    pub fn voting_rights(&self) -> &BTreeMap<Author, usize> {
+        &self.voting_rights
+    }
+
+    pub fn total_votes(&self) -> usize {
+        self.total_votes
+    }
+
+    pub fn resilience(&self) -> usize {
+        self.resilience
+    }
+
+    /// The maximal weight `f` of faulty votes, such that `n >= resilience * f + 1`.
+    pub fn max_faulty_votes(&self) -> usize {
+        self.total_votes.saturating_sub(1) / self.resilience
+    }
+
     pub fn weight(&self, author: &Author) -> usize {
         *self.voting_rights.get(author).unwrap_or(&0)
     }
@@ -31,15 +121,14 @@ impl EpochConfiguration {
     }
 
     pub fn quorum_threshold(&self) -> usize {
-        // If N = 3f + 1 + k (0 <= k < 3)
-        // then (2 N + 3) / 3 = 2f + 1 + (2k + 2)/3 = 2f + 1 + k = N - f
-        2 * self.total_votes / 3 + 1
+        // With the default resilience, if N = 3f + 1 + k (0 <= k < 3), this is 2f + 1 + k, that
+        // is, the classic 2N/3 + 1.
+        self.total_votes - self.max_faulty_votes()
     }
 
     pub fn validity_threshold(&self) -> usize {
-        // If N = 3f + 1 + k (0 <= k < 3)
-        // then (N + 2) / 3 = f + 1 + k/3 = f + 1
-        (self.total_votes + 2) / 3
+        // At least one honest vote.
+        self.max_faulty_votes() + 1
     }
 
     pub fn pick_author(&self, seed: u64) -> Author {
@@ -53,4 +142,23 @@ impl EpochConfiguration {
         }
         unreachable!()
     }
+
+    /// Same as `pick_author` but only among the authors that are not excluded, if any.
+    pub fn pick_author_excluding(&self, seed: u64, excluded: &BTreeSet<Author>) -> Option<Author> {
+        let eligible_votes = self.total_votes - self.count_votes(excluded);
+        if eligible_votes == 0 {
+            return None;
+        }
+        let mut target = seed as usize % eligible_votes;
+        for (author, votes) in &self.voting_rights {
+            if excluded.contains(author) {
+                continue;
+            }
+            if *votes > target {
+                return Some(*author);
+            }
+            target -= *votes;
+        }
+        unreachable!()
+    }
 }
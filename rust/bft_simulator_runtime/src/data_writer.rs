@@ -66,10 +66,7 @@ impl DataWriter {
 
         // CSV of the round switch
         let headers: Vec<_> = (0..self.nodes_len).collect();
-        let headers: Vec<String> = headers
-            .iter()
-            .map(|x| format!("node {}", x.to_string()))
-            .collect();
+        let headers: Vec<String> = headers.iter().map(|x| format!("node {}", x)).collect();
         wtr.serialize(&headers).expect("writing did not succeed");
 
         let max_round = *self.max_round_per_node.iter().max().unwrap() as i32;
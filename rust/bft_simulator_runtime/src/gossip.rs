@@ -0,0 +1,123 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Epidemic dissemination of broadcasts over a random regular overlay, as an alternative to
+//! direct all-to-all sends at large scales.
+
+use rand::Rng;
+use std::collections::BTreeSet;
+
+use crate::base_types::{Author, Duration};
+
+#[cfg(test)]
+#[path = "unit_tests/gossip_tests.rs"]
+mod gossip_tests;
+
+/// A random graph where every node has the same number of neighbors. Nodes forward each
+/// broadcast that they receive for the first time to all their neighbors.
+#[derive(Clone, Debug)]
+pub struct GossipOverlay {
+    neighbors: Vec<Vec<Author>>,
+    /// Time spent by a node before forwarding a broadcast, on top of the network delay.
+    relay_delay: Duration,
+}
+
+impl GossipOverlay {
+    /// Draw a random `degree`-regular graph over `num_nodes` nodes. Stubs are paired at random,
+    /// avoiding loops and multiple edges, and the pairing restarts whenever it gets stuck.
+    pub fn random_regular<R: Rng>(
+        num_nodes: usize,
+        degree: usize,
+        relay_delay: Duration,
+        rng: &mut R,
+    ) -> Self {
+        assert!(
+            degree > 0 && degree < num_nodes,
+            "The degree of the overlay must be in [1, num_nodes)"
+        );
+        assert!(
+            (num_nodes * degree).is_multiple_of(2),
+            "Regular graphs need an even number of stubs"
+        );
+        loop {
+            if let Some(edges) = Self::try_pairing(num_nodes, degree, rng) {
+                let mut neighbors = vec![Vec::new(); num_nodes];
+                for (a, b) in edges {
+                    neighbors[a].push(Author(b));
+                    neighbors[b].push(Author(a));
+                }
+                for list in &mut neighbors {
+                    list.sort();
+                }
+                return GossipOverlay {
+                    neighbors,
+                    relay_delay,
+                };
+            }
+        }
+    }
+
+    fn try_pairing<R: Rng>(
+        num_nodes: usize,
+        degree: usize,
+        rng: &mut R,
+    ) -> Option<BTreeSet<(usize, usize)>> {
+        let mut stubs: Vec<usize> = (0..num_nodes)
+            .flat_map(|node| std::iter::repeat_n(node, degree))
+            .collect();
+        let mut edges = BTreeSet::new();
+        while !stubs.is_empty() {
+            let mut paired = false;
+            for _ in 0..100 {
+                let i = rng.gen_range(0, stubs.len());
+                let j = rng.gen_range(0, stubs.len());
+                let edge = (
+                    std::cmp::min(stubs[i], stubs[j]),
+                    std::cmp::max(stubs[i], stubs[j]),
+                );
+                if edge.0 != edge.1 && !edges.contains(&edge) {
+                    edges.insert(edge);
+                    stubs.swap_remove(std::cmp::max(i, j));
+                    stubs.swap_remove(std::cmp::min(i, j));
+                    paired = true;
+                    break;
+                }
+            }
+            if !paired {
+                return None;
+            }
+        }
+        Some(edges)
+    }
+
+    pub fn neighbors(&self, author: Author) -> &[Author] {
+        &self.neighbors[author.0]
+    }
+
+    pub fn relay_delay(&self) -> Duration {
+        self.relay_delay
+    }
+
+    /// Maximal number of hops needed to reach every node from the given one, or `None` if the
+    /// overlay is not connected.
+    pub fn eccentricity(&self, author: Author) -> Option<usize> {
+        let mut reached = BTreeSet::new();
+        reached.insert(author);
+        let mut frontier = vec![author];
+        let mut hops = 0;
+        while reached.len() < self.neighbors.len() {
+            let next: Vec<_> = frontier
+                .iter()
+                .flat_map(|node| self.neighbors(*node))
+                .filter(|node| reached.insert(**node))
+                .cloned()
+                .collect();
+            if next.is_empty() {
+                return None;
+            }
+            frontier = next;
+            hops += 1;
+        }
+        Some(hops)
+    }
+}
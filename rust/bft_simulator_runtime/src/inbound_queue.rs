@@ -0,0 +1,168 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded queues of the network messages waiting for a busy node, served according to a
+//! scheduling policy.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    base_types::{Duration, Round},
+    simulator::GlobalTime,
+};
+
+#[cfg(test)]
+#[path = "unit_tests/inbound_queue_tests.rs"]
+mod inbound_queue_tests;
+
+/// What scheduling policies need to know about the content of messages.
+pub trait MessagePriority {
+    /// Urgency of the message, lower first, e.g. votes before blocks with payload.
+    fn priority(&self) -> usize;
+    /// Highest round of the content of the message.
+    fn round(&self) -> Round;
+}
+
+/// Order in which a node serves the messages of its queue.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum QueuePolicy {
+    #[default]
+    #[serde(rename = "fifo")]
+    Fifo,
+    /// Most urgent messages first, see `MessagePriority::priority`, then FIFO.
+    #[serde(rename = "priority")]
+    Priority,
+    /// Messages of the highest rounds first, then FIFO.
+    #[serde(rename = "newest_round_first")]
+    NewestRoundFirst,
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct QueueStats {
+    /// Number of messages that had to wait in the queue.
+    pub enqueued: usize,
+    /// Number of messages dropped because the queue was full.
+    pub dropped: usize,
+    /// Longest length of the queue.
+    pub max_length: usize,
+    /// Total time spent in the queue by the messages that were served.
+    pub total_wait: Duration,
+}
+
+impl QueueStats {
+    pub fn add(&mut self, other: &QueueStats) {
+        self.enqueued += other.enqueued;
+        self.dropped += other.dropped;
+        self.max_length = std::cmp::max(self.max_length, other.max_length);
+        self.total_wait += other.total_wait;
+    }
+}
+
+#[derive(Clone)]
+struct QueuedMessage<T> {
+    arrival: GlobalTime,
+    sequence_number: u64,
+    priority: usize,
+    round: Round,
+    item: T,
+}
+
+/// Messages received by a busy node, at most `capacity` of them. On overflow, the message that
+/// would be served last is dropped.
+#[derive(Clone)]
+pub struct InboundQueue<T> {
+    capacity: usize,
+    policy: QueuePolicy,
+    messages: Vec<QueuedMessage<T>>,
+    num_pushed: u64,
+    stats: QueueStats,
+}
+
+impl<T> InboundQueue<T> {
+    pub fn new(capacity: usize, policy: QueuePolicy) -> Self {
+        assert!(
+            capacity > 0,
+            "Inbound queues must hold at least one message"
+        );
+        InboundQueue {
+            capacity,
+            policy,
+            messages: Vec::new(),
+            num_pushed: 0,
+            stats: QueueStats::default(),
+        }
+    }
+
+    /// Position of a message in the service order, smallest first.
+    fn rank(&self, message: &QueuedMessage<T>) -> (usize, GlobalTime, u64) {
+        let key = match self.policy {
+            QueuePolicy::Fifo => 0,
+            QueuePolicy::Priority => message.priority,
+            QueuePolicy::NewestRoundFirst => usize::MAX - message.round.0,
+        };
+        (key, message.arrival, message.sequence_number)
+    }
+
+    /// Index of the first or the last message to serve.
+    fn position(&self, first: bool) -> Option<usize> {
+        let ranks = self
+            .messages
+            .iter()
+            .enumerate()
+            .map(|(index, message)| (self.rank(message), index));
+        let best = if first { ranks.min() } else { ranks.max() };
+        best.map(|(_, index)| index)
+    }
+
+    /// Add a message received at the given time, with the priority and round of its content.
+    /// Return the message dropped on overflow, if any, which may be the new one.
+    pub fn push(
+        &mut self,
+        arrival: GlobalTime,
+        priority: usize,
+        round: Round,
+        item: T,
+    ) -> Option<T> {
+        self.messages.push(QueuedMessage {
+            arrival,
+            sequence_number: self.num_pushed,
+            priority,
+            round,
+            item,
+        });
+        self.num_pushed += 1;
+        self.stats.enqueued += 1;
+        if self.messages.len() > self.capacity {
+            let index = self.position(false).unwrap();
+            self.stats.dropped += 1;
+            return Some(self.messages.remove(index).item);
+        }
+        self.stats.max_length = std::cmp::max(self.stats.max_length, self.messages.len());
+        None
+    }
+
+    /// Remove the next message to serve at the given time, if any.
+    pub fn pop(&mut self, clock: GlobalTime) -> Option<T> {
+        let index = self.position(true)?;
+        let message = self.messages.remove(index);
+        self.stats.total_wait += clock.0 - message.arrival.0;
+        Some(message.item)
+    }
+
+    /// The messages in the queue, in the order in which they were received.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.messages.iter().map(|message| &message.item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn stats(&self) -> &QueueStats {
+        &self.stats
+    }
+}
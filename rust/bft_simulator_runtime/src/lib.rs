@@ -9,7 +9,10 @@ extern crate rand;
 #[macro_use]
 extern crate log;
 extern crate env_logger;
+extern crate serde;
+extern crate serde_json;
 
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 // Comments in the following form are used for code-block generation in the consensus report:
@@ -17,12 +20,17 @@ use std::collections::BTreeMap;
 //    "// -- END FILE --"
 // Do not modify definitions without changing the report as well :)
 
+pub mod bandwidth;
 pub mod base_types;
 pub mod configuration;
 pub mod data_writer;
+pub mod gossip;
+pub mod inbound_queue;
+pub mod scheduler;
 pub mod simulator;
+pub mod transport;
 
-use crate::base_types::{Author, NodeTime, Round};
+use crate::base_types::{Author, Duration, NodeTime, PublicKey, Round};
 
 // -- BEGIN FILE node_update_actions --
 #[derive(Debug)]
@@ -49,6 +57,12 @@ impl NodeUpdateActions {
     }
 }
 
+impl Default for NodeUpdateActions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // -- BEGIN FILE consensus_node --
 pub trait ConsensusNode<Context> {
     fn update_node(&mut self, clock: NodeTime, context: &mut Context) -> NodeUpdateActions;
@@ -75,6 +89,15 @@ pub trait DataSyncNode<Context> {
     ) -> Option<Self::Request>;
     /// Receiver role: receive data.
     fn handle_response(&mut self, response: Self::Response, context: &mut Context, clock: NodeTime);
+    /// Sender role: the notification to send to a given receiver instead of the one created by
+    /// `create_notification`, if different, e.g. without the records sent to it before.
+    fn filter_notification(
+        &mut self,
+        _notification: &Self::Notification,
+        _receiver: Author,
+    ) -> Option<Self::Notification> {
+        None
+    }
 }
 // -- END FILE --
 
@@ -82,9 +105,63 @@ pub trait ActiveRound {
     fn active_round(&self) -> Round;
 }
 
+/// A block committed by a node and the state resulting from its execution.
 #[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Commit<Block, State> {
+    /// Index of the epoch of the block.
+    pub epoch: usize,
+    pub round: Round,
+    pub block: Block,
+    pub state: State,
+}
+
+/// The commits of each consensus instance of a node, by instance.
+pub type InstanceCommits<'a, Block, State> = Vec<(usize, &'a [Commit<Block, State>])>;
+
+/// Nodes that remember their commits, so that the simulator can check that honest nodes
+/// executed the same blocks to the same states.
+pub trait CommitLog {
+    type Block: Eq + std::fmt::Debug + Clone;
+    type State: Eq + std::fmt::Debug + Clone;
+
+    /// All the commits of the node, in order.
+    fn commits(&self) -> &[Commit<Self::Block, Self::State>];
+
+    /// The commits of each of the independent consensus instances run by the node, e.g. one
+    /// per shard, by instance. Commits are only compared within the same instance. Nodes run
+    /// a single instance by default.
+    fn instance_commits(&self) -> InstanceCommits<'_, Self::Block, Self::State> {
+        vec![(0, self.commits())]
+    }
+}
+
+/// Nodes that can describe what they know of a round, so that a divergence between the
+/// commits of honest nodes can be triaged from a single run.
+pub trait RoundTriage {
+    type Summary: Serialize;
+
+    /// Blocks, votes, certificates, and states known to the node at the given epoch and round.
+    fn round_summary(&self, epoch: usize, round: Round) -> Self::Summary;
+}
+
+/// Contexts that model the time spent by their node on CPU-bound work, such as verifying
+/// signatures or executing commands. The simulator delays the next events of a busy node.
+pub trait ProcessingTime {
+    /// Simulated time spent by the node since the last call.
+    fn take_processing_time(&mut self) -> Duration;
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(
+    into = "configuration::VotingRights",
+    try_from = "configuration::VotingRights"
+)]
 /// Hold voting rights for a give epoch.
 pub struct EpochConfiguration {
     voting_rights: BTreeMap<Author, usize>,
     total_votes: usize,
+    /// The epoch tolerates `f` faulty votes out of `n = resilience * f + 1` votes or more.
+    resilience: usize,
+    /// Versions of the keys of the authors who rotated their key in a previous epoch.
+    key_versions: BTreeMap<Author, usize>,
 }
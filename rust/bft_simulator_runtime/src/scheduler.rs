@@ -0,0 +1,34 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Strategies delaying network messages beyond the network model, e.g. to play an adversary
+//! that controls the schedule within the bounds of partial synchrony.
+
+use crate::{
+    base_types::Duration,
+    simulator::{Event, GlobalTime},
+};
+
+/// Decides an extra delay for each network message. The simulator caps extra delays at the
+/// asynchrony bound given with the scheduler, so that messages still arrive.
+pub trait Scheduler<Notification, Request, Response>: Send + Sync {
+    /// Extra delay of a message sent at `departure` and due at `arrival` under the network
+    /// model.
+    fn extra_delay(
+        &mut self,
+        departure: GlobalTime,
+        arrival: GlobalTime,
+        event: &Event<Notification, Request, Response>,
+    ) -> Duration;
+
+    /// A copy of the scheduler, for snapshots of the simulator.
+    fn clone_box(&self) -> Box<dyn Scheduler<Notification, Request, Response>>;
+}
+
+impl<Notification, Request, Response> Clone
+    for Box<dyn Scheduler<Notification, Request, Response>>
+{
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
@@ -1,13 +1,24 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
-use rand::distributions::{Distribution, LogNormal};
-use std::collections::{BinaryHeap, HashSet};
+use rand::{
+    distributions::{Distribution, LogNormal},
+    rngs::StdRng,
+    seq::index,
+    Rng, RngCore, SeedableRng,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
 
 use crate::{
+    bandwidth::{BandwidthStats, ByteCounts, MessageSize, PayloadSizes},
     base_types::{Author, Duration, NodeTime, Round},
     data_writer::*,
-    ActiveRound, ConsensusNode, DataSyncNode, NodeUpdateActions,
+    gossip::GossipOverlay,
+    inbound_queue::{InboundQueue, MessagePriority, QueuePolicy, QueueStats},
+    scheduler::Scheduler,
+    ActiveRound, Commit, CommitLog, ConsensusNode, DataSyncNode, EpochConfiguration,
+    NodeUpdateActions, ProcessingTime, RoundTriage,
 };
 
 #[cfg(test)]
@@ -15,7 +26,7 @@ use crate::{
 mod simulator_tests;
 
 // Simulated global clock
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct GlobalTime(pub i64);
 
 impl std::ops::Add<Duration> for GlobalTime {
@@ -40,24 +51,94 @@ impl RandomDelay {
             distribution: LogNormal::new(mu, sigma),
         }
     }
+
+    /// Draw a delay, e.g. to delay messages outside of the simulator.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        self.distribution.sample(rng) as Duration
+    }
+}
+
+/// The random generator of a simulation. It counts the 32-bit words drawn since it was seeded,
+/// so that saved snapshots can record its state.
+#[derive(Clone)]
+struct SimulationRng {
+    seed: u64,
+    draws: u64,
+    rng: StdRng,
+}
+
+impl SimulationRng {
+    fn new(seed: u64) -> Self {
+        SimulationRng {
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RngCore for SimulationRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 2;
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.draws += dest.len().div_ceil(4) as u64;
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.draws += dest.len().div_ceil(4) as u64;
+        self.rng.try_fill_bytes(dest)
+    }
 }
 
 impl GlobalTime {
-    fn add_delay(self, delay: RandomDelay) -> GlobalTime {
-        let v = delay.distribution.sample(&mut rand::thread_rng());
-        GlobalTime(self.0 + (v as i64))
+    pub fn never() -> Self {
+        GlobalTime(i64::MAX)
+    }
+
+    fn add_delay<R: Rng>(self, delay: RandomDelay, rng: &mut R) -> GlobalTime {
+        GlobalTime(self.0 + delay.sample(rng))
     }
 
-    fn to_node_time(self, startup_time: GlobalTime) -> NodeTime {
-        NodeTime(self.0 - startup_time.0)
+    fn to_node_time(self, startup_time: GlobalTime, skew: ClockSkew) -> NodeTime {
+        let elapsed = (self.0 - startup_time.0) as f64 * (1.0 + skew.drift);
+        NodeTime(skew.offset + elapsed.floor() as i64)
     }
 
-    fn from_node_time(node_time: NodeTime, startup_time: GlobalTime) -> GlobalTime {
-        GlobalTime(node_time.0 + startup_time.0)
+    /// The first time at which the clock of the node reads at least `node_time`.
+    fn from_node_time(
+        node_time: NodeTime,
+        startup_time: GlobalTime,
+        skew: ClockSkew,
+    ) -> GlobalTime {
+        let elapsed = (node_time.0 - skew.offset) as f64 / (1.0 + skew.drift);
+        let mut time = GlobalTime(startup_time.0 + elapsed.ceil() as i64);
+        // Compensate for rounding errors.
+        while time.to_node_time(startup_time, skew) < node_time {
+            time = time + 1;
+        }
+        time
     }
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Debug)]
+/// Deviation of the clock of a node from the global clock. The clock of a node reads `offset`
+/// when the node starts, then runs faster by a factor `1 + drift`, e.g. a drift of `-1e-4`
+/// for a clock that is 100 ppm slow.
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+pub struct ClockSkew {
+    pub offset: Duration,
+    pub drift: f64,
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
 pub enum Event<Notification, Request, Response> {
     DataSyncNotifyEvent {
         receiver: Author,
@@ -79,19 +160,491 @@ pub enum Event<Notification, Request, Response> {
     },
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd)]
-struct ScheduledEvent<Notification, Request, Response>(
-    std::cmp::Reverse<GlobalTime>,
-    Event<Notification, Request, Response>,
-);
+impl<Notification, Request, Response> Event<Notification, Request, Response> {
+    /// The node that will process this event.
+    pub fn target(&self) -> Author {
+        match self {
+            Event::DataSyncNotifyEvent { receiver, .. } => *receiver,
+            // Requests are handled by the node that will send the data.
+            Event::DataSyncRequestEvent { sender, .. } => *sender,
+            Event::DataSyncResponseEvent { receiver, .. } => *receiver,
+            Event::UpdateTimerEvent { author } => *author,
+        }
+    }
+
+    /// The two nodes exchanging a network message, if any.
+    pub fn endpoints(&self) -> Option<(Author, Author)> {
+        match self {
+            Event::DataSyncNotifyEvent {
+                receiver, sender, ..
+            }
+            | Event::DataSyncRequestEvent {
+                receiver, sender, ..
+            }
+            | Event::DataSyncResponseEvent {
+                receiver, sender, ..
+            } => Some((*sender, *receiver)),
+            Event::UpdateTimerEvent { .. } => None,
+        }
+    }
+
+    /// The node sending the network message and the node receiving it, if any.
+    fn transmission(&self) -> Option<(Author, Author)> {
+        match self {
+            Event::DataSyncNotifyEvent {
+                sender, receiver, ..
+            }
+            | Event::DataSyncResponseEvent {
+                sender, receiver, ..
+            } => Some((*sender, *receiver)),
+            // Requests travel from the receiver of the data to its sender.
+            Event::DataSyncRequestEvent {
+                sender, receiver, ..
+            } => Some((*receiver, *sender)),
+            Event::UpdateTimerEvent { .. } => None,
+        }
+    }
+}
+
+/// An event processed (or dropped) during a run.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct TraceEntry {
+    pub clock: GlobalTime,
+    /// One of "notify", "request", "response", or "timer".
+    pub kind: &'static str,
+    /// The node processing the event.
+    pub target: Author,
+    /// The other end of a network message, if any.
+    pub peer: Option<Author>,
+    /// Whether the target was crashed and ignored the event.
+    pub dropped: bool,
+}
+
+/// Same format as the traces printed by `librabft-sim replay`.
+impl std::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {:?}", self.clock.0, self.kind, self.target)?;
+        if let Some(peer) = self.peer {
+            write!(f, " {:?}", peer)?;
+        }
+        if self.dropped {
+            write!(f, " dropped")?;
+        }
+        Ok(())
+    }
+}
+
+impl<Notification, Request, Response> Event<Notification, Request, Response> {
+    /// One of "notify", "request", "response", or "timer".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::DataSyncNotifyEvent { .. } => "notify",
+            Event::DataSyncRequestEvent { .. } => "request",
+            Event::DataSyncResponseEvent { .. } => "response",
+            Event::UpdateTimerEvent { .. } => "timer",
+        }
+    }
+
+    /// The other end of the network message, if any, as seen by the target.
+    pub fn peer(&self) -> Option<Author> {
+        match self {
+            Event::DataSyncNotifyEvent { sender, .. } => Some(*sender),
+            Event::DataSyncRequestEvent { receiver, .. } => Some(*receiver),
+            Event::DataSyncResponseEvent { sender, .. } => Some(*sender),
+            Event::UpdateTimerEvent { .. } => None,
+        }
+    }
+
+    pub fn trace_entry(&self, clock: GlobalTime, dropped: bool) -> TraceEntry {
+        TraceEntry {
+            clock,
+            kind: self.kind(),
+            target: self.target(),
+            peer: self.peer(),
+            dropped,
+        }
+    }
+}
+
+impl<Notification, Request, Response> MessagePriority for Event<Notification, Request, Response>
+where
+    Notification: MessagePriority,
+    Request: MessagePriority,
+    Response: MessagePriority,
+{
+    fn priority(&self) -> usize {
+        match self {
+            Event::DataSyncNotifyEvent { notification, .. } => notification.priority(),
+            Event::DataSyncRequestEvent { request, .. } => request.priority(),
+            Event::DataSyncResponseEvent { response, .. } => response.priority(),
+            Event::UpdateTimerEvent { .. } => 0,
+        }
+    }
+
+    fn round(&self) -> Round {
+        match self {
+            Event::DataSyncNotifyEvent { notification, .. } => notification.round(),
+            Event::DataSyncRequestEvent { request, .. } => request.round(),
+            Event::DataSyncResponseEvent { response, .. } => response.round(),
+            Event::UpdateTimerEvent { .. } => Round(0),
+        }
+    }
+}
+
+/// Ordering guarantees of the messages that a replica sends to another one.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum LinkOrdering {
+    /// Messages are delayed independently of each other and may arrive in any order.
+    #[default]
+    #[serde(rename = "unordered")]
+    Unordered,
+    /// Messages arrive in the order in which they were sent.
+    #[serde(rename = "fifo")]
+    Fifo,
+    /// A message arrives at most `window` before the messages sent earlier on the link.
+    #[serde(rename = "bounded")]
+    Bounded { window: Duration },
+}
+
+impl LinkOrdering {
+    /// How much earlier than the messages sent before it a message may arrive, if bounded.
+    fn window(self) -> Option<Duration> {
+        match self {
+            LinkOrdering::Unordered => None,
+            LinkOrdering::Fifo => Some(0),
+            LinkOrdering::Bounded { window } => Some(window),
+        }
+    }
+}
+
+/// Extra delay applied to the messages between two groups of nodes during `[start, end)`.
+#[derive(Clone, Debug)]
+pub struct LinkDegradation {
+    pub group_a: BTreeSet<Author>,
+    pub group_b: BTreeSet<Author>,
+    pub start: GlobalTime,
+    pub end: GlobalTime,
+    pub extra_delay: Duration,
+}
+
+impl LinkDegradation {
+    fn applies(&self, clock: GlobalTime, author1: Author, author2: Author) -> bool {
+        self.start <= clock
+            && clock < self.end
+            && ((self.group_a.contains(&author1) && self.group_b.contains(&author2))
+                || (self.group_a.contains(&author2) && self.group_b.contains(&author1)))
+    }
+}
+
+/// During `[start, end)`, messages are only delivered between replicas of the same group.
+/// Replicas are numbered as explained in `Simulator::add_twin`.
+#[derive(Clone, Debug)]
+pub struct Partition {
+    pub groups: Vec<BTreeSet<usize>>,
+    pub start: GlobalTime,
+    pub end: GlobalTime,
+}
+
+impl Partition {
+    fn separates(&self, clock: GlobalTime, replica1: usize, replica2: usize) -> bool {
+        self.start <= clock
+            && clock < self.end
+            && !self
+                .groups
+                .iter()
+                .any(|group| group.contains(&replica1) && group.contains(&replica2))
+    }
+}
+
+/// The replica processing an event, the replica that caused it, and the gossiped broadcast
+/// that the event belongs to, if any.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+struct Route {
+    target: usize,
+    origin: usize,
+    broadcast: Option<u64>,
+}
+
+impl Route {
+    fn local(replica: usize) -> Self {
+        Route {
+            target: replica,
+            origin: replica,
+            broadcast: None,
+        }
+    }
+
+    fn reply(self) -> Self {
+        Route {
+            target: self.origin,
+            origin: self.target,
+            broadcast: None,
+        }
+    }
+}
+
+/// An event in the queue of the simulator. Events are processed by increasing time, then in
+/// the order in which they were scheduled, so that ties never compare the messages themselves.
+/// Events without content wake up a node to serve its inbound queue.
+#[derive(Clone)]
+struct ScheduledEvent<Notification, Request, Response> {
+    time: GlobalTime,
+    sequence_number: u64,
+    route: Route,
+    event: Option<Event<Notification, Request, Response>>,
+}
+
+impl<Notification, Request, Response> ScheduledEvent<Notification, Request, Response> {
+    fn key(&self) -> std::cmp::Reverse<(GlobalTime, u64)> {
+        std::cmp::Reverse((self.time, self.sequence_number))
+    }
+}
+
+impl<Notification, Request, Response> PartialEq
+    for ScheduledEvent<Notification, Request, Response>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<Notification, Request, Response> Eq for ScheduledEvent<Notification, Request, Response> {}
+
+impl<Notification, Request, Response> PartialOrd
+    for ScheduledEvent<Notification, Request, Response>
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Notification, Request, Response> Ord for ScheduledEvent<Notification, Request, Response> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
 
 type PendingEvents<Notification, Request, Response> =
     BinaryHeap<ScheduledEvent<Notification, Request, Response>>;
 
-#[derive(Debug)]
+type RouteQueue<Notification, Request, Response> =
+    InboundQueue<(Route, Event<Notification, Request, Response>)>;
+
+/// Commits of two honest replicas that differ at the same epoch and round.
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct Divergence {
+    epoch: usize,
+    round: Round,
+    description: String,
+}
+
+/// Instance, epoch and round of a commit.
+type CommitSlot = (usize, usize, Round);
+
+/// Compare the commits of honest replicas as they happen. Replicas of an author with twins are
+/// byzantine and not checked.
+struct CommitChecker<Block, State> {
+    /// Number of commits of each replica already checked, by consensus instance.
+    checked: HashMap<(usize, usize), usize>,
+    /// The first commit seen at each instance, epoch and round, and the replica that made it.
+    reference: HashMap<CommitSlot, (usize, Commit<Block, State>)>,
+}
+
+impl<Block, State> CommitChecker<Block, State>
+where
+    Block: Eq + std::fmt::Debug + Clone,
+    State: Eq + std::fmt::Debug + Clone,
+{
+    fn new() -> Self {
+        CommitChecker {
+            checked: HashMap::new(),
+            reference: HashMap::new(),
+        }
+    }
+
+    /// Describe the first commit of a replica in the given instance, if any, that differs from
+    /// the commit of another replica at the same round.
+    fn check(
+        &mut self,
+        replica: usize,
+        instance: usize,
+        commits: &[Commit<Block, State>],
+    ) -> std::result::Result<(), Divergence> {
+        let checked = self.checked.entry((replica, instance)).or_insert(0);
+        for commit in &commits[*checked..] {
+            let (other, expected) = self
+                .reference
+                .entry((instance, commit.epoch, commit.round))
+                .or_insert_with(|| (replica, commit.clone()));
+            if expected != commit {
+                let instance = if instance == 0 {
+                    String::new()
+                } else {
+                    format!(" of instance {}", instance)
+                };
+                return Err(Divergence {
+                    epoch: commit.epoch,
+                    round: commit.round,
+                    description: format!(
+                        "Execution diverged at epoch {} and {:?}{}: replica {} committed block \
+                         {:?} with state {:?} but replica {} committed block {:?} with state {:?}",
+                        commit.epoch,
+                        commit.round,
+                        instance,
+                        replica,
+                        commit.block,
+                        commit.state,
+                        other,
+                        expected.block,
+                        expected.state
+                    ),
+                });
+            }
+        }
+        *checked = commits.len();
+        Ok(())
+    }
+}
+
+/// Conditions to end a run before `max_clock`, see `run_until`.
+#[derive(Clone, Debug, Default)]
+pub struct StopConditions {
+    /// Stop as soon as an honest replica has committed this many blocks.
+    pub commits: Option<usize>,
+    /// Stop once the run has taken this much real time.
+    pub wall_clock: Option<std::time::Duration>,
+    /// Stop at the first divergence between the commits of honest replicas instead of
+    /// panicking.
+    pub stop_on_violation: bool,
+}
+
+/// Why a run ended.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The next event is after `max_clock`, the simulated-time budget.
+    SimulatedTime,
+    /// No event is left to process.
+    NoMoreEvents,
+    /// An honest replica committed the requested number of blocks.
+    Commits,
+    /// The run took the whole wall-clock budget.
+    WallClock,
+    /// Two honest replicas committed different blocks or states at the same round.
+    Violation(String),
+}
+
+/// How a run ended.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct RunOutcome {
+    pub reason: StopReason,
+    /// Simulated time at the end of the run.
+    pub clock: GlobalTime,
+    /// Number of events processed since the beginning of the simulation, including before a
+    /// snapshot or a previous run.
+    pub events: usize,
+}
+
+/// A snapshot of a run that can be saved to a file. Node states and messages cannot be decoded
+/// without their SMR context, so a snapshot is restored by replaying the run from its seed up
+/// to the same number of events, then checking that the result matches the rest of the
+/// snapshot: the state of the random generator, the events in flight and in inbound queues, and
+/// the round and commits of each replica.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct SimulationSnapshot {
+    /// Seed given to the simulator when it was created.
+    pub seed: u64,
+    /// Seeds given to `Simulator::reseed`, each with the number of events processed before.
+    pub reseeds: Vec<(usize, u64)>,
+    /// Number of events processed since the beginning of the simulation.
+    pub events: usize,
+    pub clock: GlobalTime,
+    /// State of the random generator: its latest seed and the number of 32-bit words drawn
+    /// since.
+    pub rng: (u64, u64),
+    /// Events scheduled and not processed yet, in the order in which they will be processed.
+    pub pending_events: Vec<EventSnapshot>,
+    /// Network events waiting in the inbound queue of each replica, if queues are bounded.
+    pub inbound_queues: Vec<Vec<EventSnapshot>>,
+    /// State of each replica, twins included.
+    pub replicas: Vec<ReplicaSnapshot>,
+}
+
+/// An event in a `SimulationSnapshot`.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EventSnapshot {
+    /// Time of delivery, or of arrival in an inbound queue.
+    pub time: GlobalTime,
+    /// Index of the replica processing the event.
+    pub replica: usize,
+    /// The event, in `Debug` format, or `None` for a replica serving its inbound queue.
+    pub event: Option<String>,
+}
+
+/// A replica in a `SimulationSnapshot`.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ReplicaSnapshot {
+    pub author: Author,
+    pub active_round: Round,
+    /// Time until which the replica is processing previous events.
+    pub busy_until: GlobalTime,
+    /// All the commits of the replica, in `Debug` format.
+    pub commits: Vec<String>,
+}
+
+impl SimulationSnapshot {
+    pub fn save(&self, path: &str) -> std::result::Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, json).map_err(|error| format!("Failed to write {}: {}", path, error))
+    }
+
+    pub fn load(path: &str) -> std::result::Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {}", path, error))?;
+        serde_json::from_str(&json).map_err(|error| format!("Invalid snapshot {}: {}", path, error))
+    }
+}
+
+/// What every replica knew of the round at which honest replicas first committed different
+/// blocks or states, captured when the divergence was detected.
+#[derive(Clone, Debug, Serialize)]
+pub struct TriageBundle<Summary> {
+    /// Description of the divergence.
+    pub violation: String,
+    pub epoch: usize,
+    pub round: Round,
+    /// Simulated time of the detection.
+    pub clock: GlobalTime,
+    pub replicas: Vec<ReplicaTriage<Summary>>,
+}
+
+/// The view of one replica in a `TriageBundle`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReplicaTriage<Summary> {
+    /// Index of the replica in the simulation, twins included.
+    pub replica: usize,
+    pub author: Author,
+    /// Whether the replica has no twin, i.e. whether its commits are checked.
+    pub honest: bool,
+    pub crashed: bool,
+    pub summary: Summary,
+}
+
+#[derive(Clone, Debug)]
 pub struct SimulatedNode<Node, Context> {
+    author: Author,
     startup_time: GlobalTime,
+    clock_skew: ClockSkew,
     ignore_scheduled_updates_until: GlobalTime,
+    /// Intervals `[start, end)` during which the node is crashed and drops all events.
+    crashes: Vec<(GlobalTime, GlobalTime)>,
+    /// Time until which the node is processing previous events. New events wait until then.
+    busy_until: GlobalTime,
+    /// Bytes that the node sends per unit of time, if its uplink is limited.
+    upload_throughput: Option<f64>,
+    /// Time until which the uplink is sending previous messages. New messages wait until then.
+    uplink_busy_until: GlobalTime,
     node: Node,
     context: Context,
 }
@@ -101,11 +654,66 @@ where
     Node: ConsensusNode<Context>,
 {
     fn update(&mut self, global_clock: GlobalTime) -> NodeUpdateActions {
-        let local_clock = global_clock.to_node_time(self.startup_time);
+        let local_clock = global_clock.to_node_time(self.startup_time, self.clock_skew);
         self.node.update_node(local_clock, &mut self.context)
     }
 }
 
+impl<Node, Context> SimulatedNode<Node, Context> {
+    /// Send a message of the given size through the uplink of the node, once the previous
+    /// messages are sent. Return the time at which the whole message has left.
+    fn upload(&mut self, departure: GlobalTime, bytes: usize) -> GlobalTime {
+        match self.upload_throughput {
+            Some(upload_throughput) => {
+                let start = std::cmp::max(departure, self.uplink_busy_until);
+                self.uplink_busy_until =
+                    start + (bytes as f64 / upload_throughput).ceil() as Duration;
+                self.uplink_busy_until
+            }
+            None => departure,
+        }
+    }
+
+    pub fn author(&self) -> Author {
+        self.author
+    }
+
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Mutable access to the node between two steps of the simulation, e.g. to subscribe to its
+    /// commits.
+    pub fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Mutable access to the context between two steps of the simulation, e.g. to submit the
+    /// commands of simulated clients.
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    /// Local time of the node at the given global time, given its clock skew.
+    pub fn local_time(&self, clock: GlobalTime) -> NodeTime {
+        clock.to_node_time(self.startup_time, self.clock_skew)
+    }
+
+    pub fn is_crashed(&self, clock: GlobalTime) -> bool {
+        self.crashes
+            .iter()
+            .any(|(start, end)| *start <= clock && clock < *end)
+    }
+
+    fn recovers_at(&self, clock: GlobalTime) -> bool {
+        self.crashes.iter().any(|(_, end)| *end == clock)
+    }
+}
+
 impl<Node, Context> SimulatedNode<Node, Context>
 where
     Node: ActiveRound,
@@ -115,11 +723,73 @@ where
     }
 }
 
+#[derive(Clone)]
 pub struct Simulator<Node, Context, Notification, Request, Response> {
     clock: GlobalTime,
     network_delay: RandomDelay,
+    /// Delays of the links between given pairs of nodes, in both directions, instead of
+    /// `network_delay`.
+    link_delays: HashMap<(Author, Author), RandomDelay>,
+    /// Ordering of the messages of the links without their own ordering.
+    link_ordering: LinkOrdering,
+    /// Ordering of the links between given pairs of nodes, in both directions.
+    link_orderings: HashMap<(Author, Author), LinkOrdering>,
+    /// Latest arrival time of the messages sent by each replica to each other replica, on the
+    /// links with ordering guarantees.
+    latest_arrivals: HashMap<(usize, usize), GlobalTime>,
+    /// Strategy delaying messages on top of the network model, if any.
+    scheduler: Option<Box<dyn Scheduler<Notification, Request, Response>>>,
+    /// Maximal extra delay of a message decided by the scheduler.
+    max_scheduled_delay: Duration,
+    /// Number of messages delayed by the scheduler.
+    scheduled_delays: usize,
+    /// Probability that a network message is lost.
+    loss_rate: f64,
+    /// Seed given to the simulator when it was created.
+    seed: u64,
+    /// Seeds given to `reseed`, each with the number of events processed before.
+    reseeds: Vec<(usize, u64)>,
+    /// Source of all the randomness of a run, so that runs can be replayed from their seed.
+    rng: SimulationRng,
     pending_events: PendingEvents<Notification, Request, Response>,
+    /// Number of events scheduled so far, used to break ties between events.
+    num_scheduled_events: u64,
+    /// Number of events processed so far.
+    num_processed_events: usize,
+    /// All replicas: first one per author, then the twins.
     nodes: Vec<SimulatedNode<Node, Context>>,
+    /// The replicas of each author.
+    replicas_by_author: Vec<Vec<usize>>,
+    /// The clock skew of each author, shared by its replicas.
+    clock_skews: Vec<ClockSkew>,
+    num_authors: usize,
+    link_degradations: Vec<LinkDegradation>,
+    partitions: Vec<Partition>,
+    trace: Option<Vec<TraceEntry>>,
+    /// File where to write the triage bundle of a divergence, if any.
+    triage_path: Option<String>,
+    /// Triage bundle of the divergence detected by the last run, in JSON.
+    triage: Option<serde_json::Value>,
+    bandwidth: Option<BandwidthStats>,
+    /// Sizes of the payloads carried by messages, if any.
+    payload_sizes: Option<PayloadSizes>,
+    /// Bytes that a link transmits per unit of time, if messages take longer to arrive as
+    /// they grow.
+    link_throughput: Option<f64>,
+    /// Capacity and policy of the inbound queues of the replicas, if they are bounded.
+    inbound_queue_parameters: Option<(usize, QueuePolicy)>,
+    /// Network events received by each replica while it was busy, if queues are bounded.
+    inbound_queues: Vec<RouteQueue<Notification, Request, Response>>,
+    /// Whether each replica is already scheduled to serve its inbound queue.
+    queue_wakeups: Vec<bool>,
+    /// Overlay disseminating broadcasts, if they are not sent directly to all nodes.
+    gossip: Option<GossipOverlay>,
+    /// Number of broadcasts gossiped so far, used to identify them.
+    num_broadcasts: u64,
+    /// The replicas that already received each gossiped broadcast.
+    gossip_receivers: HashMap<u64, BTreeSet<usize>>,
+    /// Number of nodes, drawn at random, that each query-all action reaches, if not all of them.
+    query_fanout: Option<usize>,
 }
 
 impl<Node, Context, Notification, Request, Response>
@@ -139,51 +809,421 @@ where
         F: Fn(Author, usize) -> Context,
         G: Fn(Author, &Context, NodeTime) -> Node,
     {
-        let clock = GlobalTime(0);
-        let mut pending_events = BinaryHeap::new();
-        let nodes = (0..num_nodes)
-            .map(|index| {
-                let author = Author(index);
-                let context = context_factory(author, num_nodes);
-                let startup_time = clock.add_delay(network_delay) + 1;
-                let node_time = NodeTime(0);
-                let deadline = GlobalTime::from_node_time(node_time, startup_time);
-                let event = Event::UpdateTimerEvent { author };
-                trace!(
-                    "Scheduling initial event {:?} for time {:?}",
-                    event,
-                    deadline
-                );
-                pending_events.push(ScheduledEvent(std::cmp::Reverse(deadline), event));
-                SimulatedNode {
-                    startup_time,
-                    ignore_scheduled_updates_until: startup_time + (-1),
-                    node: node_factory(author, &context, node_time),
-                    context,
-                }
-            })
-            .collect();
-        Simulator {
-            clock,
+        Self::with_seed(
+            rand::random(),
+            num_nodes,
+            network_delay,
+            context_factory,
+            node_factory,
+        )
+    }
+
+    /// Same as `new` but all the random network delays are drawn from the given seed.
+    pub fn with_seed<F, G>(
+        seed: u64,
+        num_nodes: usize,
+        network_delay: RandomDelay,
+        context_factory: F,
+        node_factory: G,
+    ) -> Simulator<Node, Context, Notification, Request, Response>
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        Self::with_clock_skews(
+            seed,
+            vec![ClockSkew::default(); num_nodes],
             network_delay,
-            pending_events,
-            nodes,
+            context_factory,
+            node_factory,
+        )
+    }
+
+    /// Same as `with_seed` for nodes whose clocks deviate from the global clock, one node per
+    /// given clock skew.
+    pub fn with_clock_skews<F, G>(
+        seed: u64,
+        clock_skews: Vec<ClockSkew>,
+        network_delay: RandomDelay,
+        context_factory: F,
+        node_factory: G,
+    ) -> Simulator<Node, Context, Notification, Request, Response>
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        let num_nodes = clock_skews.len();
+        let mut sim = Simulator {
+            clock: GlobalTime(0),
+            network_delay,
+            link_delays: HashMap::new(),
+            link_ordering: LinkOrdering::default(),
+            link_orderings: HashMap::new(),
+            latest_arrivals: HashMap::new(),
+            scheduler: None,
+            max_scheduled_delay: 0,
+            scheduled_delays: 0,
+            loss_rate: 0.0,
+            seed,
+            reseeds: Vec::new(),
+            rng: SimulationRng::new(seed),
+            pending_events: BinaryHeap::new(),
+            num_scheduled_events: 0,
+            num_processed_events: 0,
+            nodes: Vec::new(),
+            replicas_by_author: vec![Vec::new(); num_nodes],
+            clock_skews,
+            num_authors: num_nodes,
+            link_degradations: Vec::new(),
+            partitions: Vec::new(),
+            trace: None,
+            triage_path: None,
+            triage: None,
+            bandwidth: None,
+            payload_sizes: None,
+            link_throughput: None,
+            inbound_queue_parameters: None,
+            inbound_queues: Vec::new(),
+            queue_wakeups: Vec::new(),
+            gossip: None,
+            num_broadcasts: 0,
+            gossip_receivers: HashMap::new(),
+            query_fanout: None,
+        };
+        for index in 0..num_nodes {
+            sim.add_replica(Author(index), &context_factory, &node_factory);
+        }
+        sim
+    }
+
+    fn add_replica<F, G>(&mut self, author: Author, context_factory: &F, node_factory: &G) -> usize
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        let context = context_factory(author, self.num_authors);
+        let startup_time = self.clock.add_delay(self.network_delay, &mut self.rng) + 1;
+        let clock_skew = self.clock_skews[author.0];
+        let node_time = startup_time.to_node_time(startup_time, clock_skew);
+        let deadline = GlobalTime::from_node_time(node_time, startup_time, clock_skew);
+        let replica = self.nodes.len();
+        let event = Event::UpdateTimerEvent { author };
+        trace!(
+            "Scheduling initial event {:?} for time {:?}",
+            event,
+            deadline
+        );
+        self.schedule_event(deadline, Route::local(replica), event);
+        self.nodes.push(SimulatedNode {
+            author,
+            startup_time,
+            clock_skew,
+            ignore_scheduled_updates_until: startup_time + (-1),
+            crashes: Vec::new(),
+            busy_until: GlobalTime(0),
+            upload_throughput: None,
+            uplink_busy_until: GlobalTime(0),
+            node: node_factory(author, &context, node_time),
+            context,
+        });
+        self.replicas_by_author[author.0].push(replica);
+        if let Some((capacity, policy)) = self.inbound_queue_parameters {
+            self.inbound_queues
+                .push(InboundQueue::new(capacity, policy));
+            self.queue_wakeups.push(false);
         }
+        replica
+    }
+
+    /// Add another replica of an existing author, sharing its identity and thus its signing key,
+    /// to probe safety under equivocation as in the "Twins" methodology. Messages to an author
+    /// are delivered to all its replicas. The initial nodes are the replicas `0..num_nodes`, and
+    /// twins are numbered after them in order of creation. Return the new replica number.
+    pub fn add_twin<F, G>(&mut self, author: Author, context_factory: F, node_factory: G) -> usize
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        assert!(author.0 < self.num_authors, "Twins need an existing author");
+        self.add_replica(author, &context_factory, &node_factory)
     }
 
     fn schedule_event(
         &mut self,
         deadline: GlobalTime,
+        route: Route,
         event: Event<Notification, Request, Response>,
     ) {
         trace!("Scheduling event {:?} for {:?}", event, deadline);
-        self.pending_events
-            .push(ScheduledEvent(std::cmp::Reverse(deadline), event));
+        self.pending_events.push(ScheduledEvent {
+            time: deadline,
+            sequence_number: self.num_scheduled_events,
+            route,
+            event: Some(event),
+        });
+        self.num_scheduled_events += 1;
+    }
+
+    /// Make sure that the given replica serves its inbound queue once it is not busy anymore.
+    fn schedule_queue_wakeup(&mut self, replica: usize) {
+        if self.inbound_queues.is_empty()
+            || self.inbound_queues[replica].is_empty()
+            || self.queue_wakeups[replica]
+        {
+            return;
+        }
+        self.queue_wakeups[replica] = true;
+        self.pending_events.push(ScheduledEvent {
+            time: std::cmp::max(self.clock, self.nodes[replica].busy_until),
+            sequence_number: self.num_scheduled_events,
+            route: Route::local(replica),
+            event: None,
+        });
+        self.num_scheduled_events += 1;
+    }
+
+    /// Schedule the delivery of a message leaving its origin at the given time and taking
+    /// `transmission` to go through the link, on top of the network delay.
+    fn schedule_network_event(
+        &mut self,
+        departure: GlobalTime,
+        route: Route,
+        event: Event<Notification, Request, Response>,
+        transmission: Duration,
+    ) {
+        if self
+            .partitions
+            .iter()
+            .any(|partition| partition.separates(departure, route.origin, route.target))
+        {
+            debug!(
+                "@{:?} Dropping event across a partition {:?}",
+                departure, event
+            );
+            return;
+        }
+        // Only draw from the random generator when messages can be lost, so that the runs of
+        // a reliable network do not change.
+        if self.loss_rate > 0.0 && self.rng.gen::<f64>() < self.loss_rate {
+            debug!("@{:?} Losing event {:?}", departure, event);
+            return;
+        }
+        let network_delay = event
+            .endpoints()
+            .and_then(|endpoints| self.link_delays.get(&endpoints))
+            .cloned()
+            .unwrap_or(self.network_delay);
+        let mut deadline = departure.add_delay(network_delay, &mut self.rng) + transmission;
+        if let Some((author1, author2)) = event.endpoints() {
+            for degradation in &self.link_degradations {
+                if degradation.applies(departure, author1, author2) {
+                    deadline = deadline + degradation.extra_delay;
+                }
+            }
+            if let Some(scheduler) = &mut self.scheduler {
+                let extra_delay = scheduler.extra_delay(departure, deadline, &event);
+                let extra_delay =
+                    std::cmp::min(std::cmp::max(extra_delay, 0), self.max_scheduled_delay);
+                if extra_delay > 0 {
+                    self.scheduled_delays += 1;
+                    deadline = deadline + extra_delay;
+                }
+            }
+            let ordering = self
+                .link_orderings
+                .get(&(author1, author2))
+                .cloned()
+                .unwrap_or(self.link_ordering);
+            if let Some(window) = ordering.window() {
+                // Hold the message back until at most `window` before the latest arrival of
+                // the previous messages of the link. Ties are delivered in sending order.
+                let latest = self
+                    .latest_arrivals
+                    .entry((route.origin, route.target))
+                    .or_insert(deadline);
+                deadline = std::cmp::max(deadline, *latest + (-window));
+                *latest = std::cmp::max(*latest, deadline);
+            }
+        }
+        self.schedule_event(deadline, route, event);
+    }
+
+    /// Record the events processed from now on, see `trace`.
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// Write the triage bundle of the next divergence to the given file, see `triage`.
+    pub fn set_triage_path(&mut self, path: String) {
+        self.triage_path = Some(path);
     }
 
-    fn schedule_network_event(&mut self, event: Event<Notification, Request, Response>) {
-        let deadline = self.clock.add_delay(self.network_delay);
-        self.schedule_event(deadline, event);
+    /// Count the bytes of the network messages sent from now on, see `bandwidth`. Time series
+    /// use intervals of the given length.
+    pub fn enable_bandwidth_accounting(&mut self, interval: Duration) {
+        let num_authors = self.num_authors;
+        self.bandwidth
+            .get_or_insert_with(|| BandwidthStats::new(num_authors, interval));
+    }
+
+    /// Add payloads of the given sizes to the messages, as identified by `MessageSize::payloads`,
+    /// when counting bytes.
+    pub fn set_payload_sizes(&mut self, payload_sizes: PayloadSizes) {
+        self.payload_sizes = Some(payload_sizes);
+    }
+
+    /// Delay each network message by its size divided by the given number of bytes per unit of
+    /// time. Messages do not compete for links.
+    pub fn set_link_throughput(&mut self, link_throughput: f64) {
+        assert!(link_throughput > 0.0, "Invalid link throughput");
+        self.link_throughput = Some(link_throughput);
+    }
+
+    /// Limit the bytes that the replicas of an author send per unit of time, e.g. to model a
+    /// node with a slower network card. Messages leave one after the other, after the previous
+    /// ones are sent, then go through links as usual.
+    pub fn set_upload_throughput(&mut self, author: Author, upload_throughput: f64) {
+        assert!(upload_throughput > 0.0, "Invalid upload throughput");
+        for replica in self.replicas_of(author) {
+            self.nodes[replica].upload_throughput = Some(upload_throughput);
+        }
+    }
+
+    /// Draw the delays of the messages between two nodes from their own distribution, e.g. to
+    /// model the distance between the regions hosting them.
+    pub fn set_link_delay(&mut self, author1: Author, author2: Author, delay: RandomDelay) {
+        self.link_delays.insert((author1, author2), delay);
+        self.link_delays.insert((author2, author1), delay);
+    }
+
+    /// Deliver the messages of all links with the given ordering guarantees, except for the
+    /// links given their own ordering.
+    pub fn set_link_ordering(&mut self, ordering: LinkOrdering) {
+        if let Some(window) = ordering.window() {
+            assert!(window >= 0, "Invalid reordering window");
+        }
+        self.link_ordering = ordering;
+    }
+
+    /// Deliver the messages between two nodes with the given ordering guarantees, in both
+    /// directions.
+    pub fn set_link_ordering_between(
+        &mut self,
+        author1: Author,
+        author2: Author,
+        ordering: LinkOrdering,
+    ) {
+        if let Some(window) = ordering.window() {
+            assert!(window >= 0, "Invalid reordering window");
+        }
+        self.link_orderings.insert((author1, author2), ordering);
+        self.link_orderings.insert((author2, author1), ordering);
+    }
+
+    /// Let a scheduler delay each network message by up to `max_delay` on top of the network
+    /// model, see `Scheduler`.
+    pub fn set_scheduler(
+        &mut self,
+        scheduler: Box<dyn Scheduler<Notification, Request, Response>>,
+        max_delay: Duration,
+    ) {
+        assert!(max_delay >= 0, "Invalid asynchrony bound");
+        self.scheduler = Some(scheduler);
+        self.max_scheduled_delay = max_delay;
+    }
+
+    /// Number of network messages delayed by the scheduler so far.
+    pub fn scheduled_delays(&self) -> usize {
+        self.scheduled_delays
+    }
+
+    /// Lose each network message independently with the given probability.
+    pub fn set_loss_rate(&mut self, loss_rate: f64) {
+        assert!((0.0..1.0).contains(&loss_rate), "Invalid loss rate");
+        self.loss_rate = loss_rate;
+    }
+
+    /// Draw the network delays and losses from now on from the given seed, e.g. to explore
+    /// another schedule from a snapshot.
+    pub fn reseed(&mut self, seed: u64) {
+        self.reseeds.push((self.num_processed_events, seed));
+        self.rng = SimulationRng::new(seed);
+    }
+
+    /// Disseminate broadcasts by gossip over a random `degree`-regular overlay, where each hop
+    /// takes a network delay plus `relay_delay`, instead of sending them to all nodes directly.
+    pub fn enable_gossip(&mut self, degree: usize, relay_delay: Duration) {
+        self.gossip = Some(GossipOverlay::random_regular(
+            self.num_authors,
+            degree,
+            relay_delay,
+            &mut self.rng,
+        ));
+    }
+
+    /// Send the requests of each query-all action to `fanout` other nodes drawn at random rather
+    /// than to all of them, so that synchronization costs grow linearly with the number of
+    /// nodes.
+    pub fn sample_queries(&mut self, fanout: usize) {
+        self.query_fanout = Some(fanout);
+    }
+
+    /// Bound the number of network messages waiting for each busy replica. Messages are served
+    /// according to the given policy and the ones that would be served last are dropped on
+    /// overflow. By default, queues are unbounded and FIFO.
+    pub fn set_inbound_queues(&mut self, capacity: usize, policy: QueuePolicy) {
+        self.inbound_queue_parameters = Some((capacity, policy));
+        self.inbound_queues = (0..self.nodes.len())
+            .map(|_| InboundQueue::new(capacity, policy))
+            .collect();
+        self.queue_wakeups = vec![false; self.nodes.len()];
+    }
+
+    /// Stop the messages between some groups of replicas for some time.
+    pub fn add_partition(&mut self, partition: Partition) {
+        self.partitions.push(partition);
+    }
+
+    /// Slow down the messages between two groups of nodes for some time.
+    pub fn add_link_degradation(&mut self, degradation: LinkDegradation) {
+        self.link_degradations.push(degradation);
+    }
+
+    /// Crash the replicas of an author during the interval `[start, end)`. They keep their
+    /// state but drop all the events that they receive in the meantime. They are woken up again
+    /// at time `end`.
+    pub fn add_crash(&mut self, author: Author, start: GlobalTime, end: GlobalTime) {
+        for replica in self.replicas_of(author) {
+            self.nodes[replica].crashes.push((start, end));
+            if end < GlobalTime::never() {
+                self.schedule_event(
+                    end,
+                    Route::local(replica),
+                    Event::UpdateTimerEvent { author },
+                );
+            }
+        }
+    }
+
+    /// Deliver an event at the given time (at the earliest at the current time), regardless of
+    /// network delays and partitions, e.g. to replay a message by hand while debugging. The
+    /// event goes to all the replicas of its target, as if it came from the first replica of
+    /// the other end of the message.
+    pub fn inject_event(&mut self, time: GlobalTime, event: Event<Notification, Request, Response>)
+    where
+        Event<Notification, Request, Response>: Clone,
+    {
+        let time = std::cmp::max(time, self.clock);
+        let peer = event.peer();
+        for target in self.replicas_of(event.target()) {
+            let origin = peer.map_or(target, |peer| self.replicas_by_author[peer.0][0]);
+            let route = Route {
+                target,
+                origin,
+                broadcast: None,
+            };
+            self.schedule_event(time, route, event.clone());
+        }
     }
 }
 
@@ -194,42 +1234,331 @@ impl<Node, Context, Notification, Request, Response>
         self.nodes.get(author.0).unwrap()
     }
 
-    fn simulated_node_mut(&mut self, author: Author) -> &mut SimulatedNode<Node, Context> {
+    pub fn simulated_node_mut(&mut self, author: Author) -> &mut SimulatedNode<Node, Context> {
         self.nodes.get_mut(author.0).unwrap()
     }
+
+    /// All replicas, including twins.
+    pub fn replicas(&self) -> &[SimulatedNode<Node, Context>] {
+        &self.nodes
+    }
+
+    fn replicas_of(&self, author: Author) -> Vec<usize> {
+        self.replicas_by_author[author.0].clone()
+    }
+
+    /// Number of distinct authors, not counting twins.
+    pub fn num_nodes(&self) -> usize {
+        self.num_authors
+    }
+
+    pub fn clock(&self) -> GlobalTime {
+        self.clock
+    }
+
+    /// The next `count` pending events with their times, in the order in which they will be
+    /// processed. Wake-ups of inbound queues are not listed.
+    pub fn upcoming_events(
+        &self,
+        count: usize,
+    ) -> Vec<(GlobalTime, &Event<Notification, Request, Response>)> {
+        let mut events: Vec<_> = self.pending_events.iter().collect();
+        events.sort_by_key(|event| std::cmp::Reverse(event.key()));
+        events
+            .into_iter()
+            .filter_map(|scheduled| Some((scheduled.time, scheduled.event.as_ref()?)))
+            .take(count)
+            .collect()
+    }
+
+    /// The events recorded since `enable_trace` was called, if ever.
+    pub fn trace(&self) -> Option<&[TraceEntry]> {
+        self.trace.as_deref()
+    }
+
+    /// The triage bundle of the divergence that stopped the last run, if any, in JSON.
+    pub fn triage(&self) -> Option<&serde_json::Value> {
+        self.triage.as_ref()
+    }
+
+    /// The overlay used to gossip broadcasts, if any.
+    pub fn gossip_overlay(&self) -> Option<&GossipOverlay> {
+        self.gossip.as_ref()
+    }
+
+    /// Mark a gossiped broadcast as received by the given replica. Return false if it was
+    /// received before.
+    fn receive_broadcast(&mut self, broadcast: u64, replica: usize) -> bool {
+        self.gossip_receivers
+            .entry(broadcast)
+            .or_default()
+            .insert(replica)
+    }
+
+    /// The bytes sent since `enable_bandwidth_accounting` was called, if ever.
+    pub fn bandwidth(&self) -> Option<&BandwidthStats> {
+        self.bandwidth.as_ref()
+    }
+
+    /// Intervals `[start, end)` before `max_clock` during which the nodes that are not crashed
+    /// hold less than a quorum of votes in `configuration`. No commit can happen during these
+    /// intervals, whatever the protocol does.
+    pub fn quorum_impossible_intervals(
+        &self,
+        configuration: &EpochConfiguration,
+        max_clock: GlobalTime,
+    ) -> Vec<(GlobalTime, GlobalTime)> {
+        // The set of live nodes only changes when a crash starts or ends.
+        let boundaries: Vec<_> = self
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                node.crashes
+                    .iter()
+                    .flat_map(|(start, end)| vec![*start, *end])
+            })
+            .filter(|time| *time < max_clock)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut intervals: Vec<(GlobalTime, GlobalTime)> = Vec::new();
+        for (index, start) in boundaries.iter().enumerate() {
+            let end = boundaries.get(index + 1).cloned().unwrap_or(max_clock);
+            let live_authors: BTreeSet<_> = self
+                .nodes
+                .iter()
+                .filter(|node| !node.is_crashed(*start))
+                .map(|node| node.author)
+                .collect();
+            if configuration.count_votes(&live_authors) >= configuration.quorum_threshold() {
+                continue;
+            }
+            match intervals.last_mut() {
+                Some((_, last_end)) if last_end == start => *last_end = end,
+                _ => intervals.push((*start, end)),
+            }
+        }
+        intervals
+    }
+
+    /// Number of network events waiting to be processed by the given node, including the
+    /// ones in its inbound queues.
+    pub fn pending_network_events(&self, author: Author) -> usize {
+        let in_flight = self
+            .pending_events
+            .iter()
+            .filter(|ScheduledEvent { event, .. }| match event {
+                Some(Event::UpdateTimerEvent { .. }) | None => false,
+                Some(event) => event.target() == author,
+            })
+            .count();
+        let queued: usize = self.replicas_by_author[author.0]
+            .iter()
+            .filter_map(|replica| self.inbound_queues.get(*replica))
+            .map(InboundQueue::len)
+            .sum();
+        in_flight + queued
+    }
+
+    /// Statistics of the inbound queue of each replica, if queues are bounded.
+    pub fn queue_stats(&self) -> Option<Vec<&QueueStats>> {
+        self.inbound_queue_parameters?;
+        Some(
+            self.inbound_queues
+                .iter()
+                .map(InboundQueue::stats)
+                .collect(),
+        )
+    }
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+where
+    Node: Clone,
+    Context: Clone,
+    Notification: Clone,
+    Request: Clone,
+    Response: Clone,
+{
+    /// A copy of the whole simulation: the clock, node states and contexts, messages in flight
+    /// and in inbound queues, the random generator, and the statistics so far. A snapshot is a
+    /// simulator itself, so that it can be run to branch off the original run.
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    /// Go back to the state of a snapshot.
+    pub fn restore(&mut self, snapshot: &Self) {
+        *self = snapshot.clone();
+    }
 }
 
 impl<Node, Context, Notification, Request, Response>
     Simulator<Node, Context, Notification, Request, Response>
 where
-    Context: std::fmt::Debug,
+    Context: ProcessingTime + std::fmt::Debug,
     Node: ConsensusNode<Context>
-        + DataSyncNode<
-            Context,
-            Notification = Notification,
-            Request = Request,
-            Response = Response,
-        > + ActiveRound
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
+        + ActiveRound
+        + CommitLog
+        + RoundTriage
         + std::fmt::Debug,
-    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
-    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
-    Response: std::cmp::Ord + std::fmt::Debug,
+    Notification:
+        std::cmp::Ord + std::fmt::Debug + std::clone::Clone + MessageSize + MessagePriority,
+    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone + MessageSize + MessagePriority,
+    Response: std::cmp::Ord + std::fmt::Debug + MessageSize + MessagePriority,
 {
+    /// The size of the content of a message, including its payloads if their sizes are known.
+    fn byte_counts<M: MessageSize>(&self, message: &M) -> ByteCounts {
+        let mut byte_counts = message.byte_counts();
+        if let Some(payload_sizes) = &self.payload_sizes {
+            byte_counts.extend(
+                message
+                    .payloads()
+                    .into_iter()
+                    .map(|payload| ("payload", payload_sizes.size(payload))),
+            );
+        }
+        byte_counts
+    }
+
+    /// Same as `byte_counts` if sizes are needed, to account for bandwidth or to delay
+    /// messages by their size.
+    fn measure<M: MessageSize>(&self, message: &M) -> Option<ByteCounts> {
+        if self.bandwidth.is_some()
+            || self.link_throughput.is_some()
+            || self
+                .nodes
+                .iter()
+                .any(|node| node.upload_throughput.is_some())
+        {
+            Some(self.byte_counts(message))
+        } else {
+            None
+        }
+    }
+
+    /// The size of the content of a network message, if any and if needed.
+    fn measure_event(&self, event: &Event<Notification, Request, Response>) -> Option<ByteCounts> {
+        match event {
+            Event::DataSyncNotifyEvent { notification, .. } => self.measure(notification),
+            Event::DataSyncRequestEvent { request, .. } => self.measure(request),
+            Event::DataSyncResponseEvent { response, .. } => self.measure(response),
+            Event::UpdateTimerEvent { .. } => None,
+        }
+    }
+
+    /// Send a message once its origin is done processing the current event. Also account for
+    /// the bytes sent, including messages lost in a partition.
+    fn send_network_event(&mut self, route: Route, event: Event<Notification, Request, Response>) {
+        let byte_counts = self.measure_event(&event);
+        self.send_network_event_of_size(route, event, byte_counts.as_ref());
+    }
+
+    /// Same as `send_network_event` for a message of known size, e.g. one of the copies of a
+    /// notification, to avoid encoding the same content for each receiver.
+    fn send_network_event_of_size(
+        &mut self,
+        route: Route,
+        event: Event<Notification, Request, Response>,
+        byte_counts: Option<&ByteCounts>,
+    ) {
+        let departure = std::cmp::max(self.clock, self.nodes[route.origin].busy_until);
+        self.send_network_event_at(departure, route, event, byte_counts);
+    }
+
+    /// Same as `send_network_event_of_size` for a message leaving at the given time.
+    fn send_network_event_at(
+        &mut self,
+        departure: GlobalTime,
+        route: Route,
+        event: Event<Notification, Request, Response>,
+        byte_counts: Option<&ByteCounts>,
+    ) {
+        if let (Some(bandwidth), Some(byte_counts)) = (self.bandwidth.as_mut(), byte_counts) {
+            if let Some((sender, receiver)) = event.transmission() {
+                bandwidth.record(departure.0, sender, receiver, event.kind(), byte_counts);
+            }
+        }
+        let bytes: usize = byte_counts.map_or(0, |byte_counts| {
+            byte_counts.iter().map(|(_, count)| count).sum()
+        });
+        let departure = self.nodes[route.origin].upload(departure, bytes);
+        let transmission = match self.link_throughput {
+            Some(link_throughput) => (bytes as f64 / link_throughput).ceil() as Duration,
+            None => 0,
+        };
+        self.schedule_network_event(departure, route, event, transmission);
+    }
+
+    /// Forward a gossiped broadcast received for the first time to the other neighbors of the
+    /// receiver in the overlay.
+    fn relay_broadcast(&mut self, route: Route, broadcast: u64, notification: Notification) {
+        let (neighbors, relay_delay) = match &self.gossip {
+            Some(overlay) => (
+                overlay.neighbors(self.nodes[route.target].author).to_vec(),
+                overlay.relay_delay(),
+            ),
+            None => return,
+        };
+        let author = self.nodes[route.target].author;
+        let previous_hop = self.nodes[route.origin].author;
+        let departure =
+            std::cmp::max(self.clock, self.nodes[route.target].busy_until) + relay_delay;
+        let byte_counts = self.measure(&notification);
+        for receiver in neighbors {
+            if receiver == previous_hop {
+                continue;
+            }
+            for target in self.replicas_of(receiver) {
+                self.send_network_event_at(
+                    departure,
+                    Route {
+                        target,
+                        origin: route.target,
+                        broadcast: Some(broadcast),
+                    },
+                    Event::DataSyncNotifyEvent {
+                        sender: author,
+                        receiver,
+                        notification: notification.clone(),
+                    },
+                    byte_counts.as_ref(),
+                );
+            }
+        }
+    }
+
+    /// Keep a replica busy for the processing time reported by its context, e.g. to verify
+    /// signatures and execute commands during the current event.
+    fn charge_processing_time(&mut self, replica: usize) {
+        let clock = self.clock;
+        let node = &mut self.nodes[replica];
+        let processing_time = node.context.take_processing_time();
+        node.busy_until = std::cmp::max(node.busy_until, clock) + processing_time;
+    }
+
     fn process_node_actions(
         &mut self,
         clock: GlobalTime,
-        author: Author,
+        replica: usize,
         actions: NodeUpdateActions,
     ) {
+        let author = self.nodes[replica].author;
         debug!(
             "@{:?} Processing node actions for {:?}: {:?}",
             clock, author, actions
         );
         // Timers
         let new_deadline = {
-            let mut node = self.nodes.get_mut(author.0).unwrap();
+            let node = self.nodes.get_mut(replica).unwrap();
             let new_deadline = std::cmp::max(
-                GlobalTime::from_node_time(actions.next_scheduled_update, node.startup_time),
+                GlobalTime::from_node_time(
+                    actions.next_scheduled_update,
+                    node.startup_time,
+                    node.clock_skew,
+                ),
                 // Make sure we schedule the update strictly in the future so it does not get
                 // ignored by `ignore_scheduled_updates_until` below.
                 clock + 1,
@@ -240,137 +1569,535 @@ where
             // scoping the mutable 'node' for the borrow checker
         };
         let event = Event::UpdateTimerEvent { author };
-        self.schedule_event(new_deadline, event);
-        // Notifications
-        let mut receivers = HashSet::new();
+        self.schedule_event(new_deadline, Route::local(replica), event);
+        // Notifications, in a deterministic order so that runs can be replayed.
+        let mut receivers = BTreeSet::new();
         for node in actions.should_send {
             receivers.insert(node);
         }
+        // Broadcasts go either to all nodes or to our neighbors in the gossip overlay.
+        let mut gossip_receivers: BTreeSet<Author> = BTreeSet::new();
+        let mut broadcast = None;
         if actions.should_broadcast {
-            for index in 0..self.nodes.len() {
-                if index != author.0 {
-                    receivers.insert(Author(index));
+            match &self.gossip {
+                Some(overlay) => {
+                    gossip_receivers.extend(overlay.neighbors(author).iter().cloned());
+                    broadcast = Some(self.num_broadcasts);
+                    self.num_broadcasts += 1;
+                }
+                None => {
+                    for index in 0..self.num_authors {
+                        if index != author.0 {
+                            receivers.insert(Author(index));
+                        }
+                    }
                 }
             }
         }
-        let notification = self.simulated_node(author).node.create_notification();
-        for receiver in receivers {
-            self.schedule_network_event(Event::DataSyncNotifyEvent {
-                sender: author,
-                receiver,
-                notification: notification.clone(),
-            });
+        if let Some(broadcast) = broadcast {
+            for origin in self.replicas_of(author) {
+                self.receive_broadcast(broadcast, origin);
+            }
+        }
+        receivers.extend(gossip_receivers.iter().cloned());
+        if !receivers.is_empty() {
+            let notification = self.nodes[replica].node.create_notification();
+            let byte_counts = self.measure(&notification);
+            for receiver in receivers {
+                let is_gossip = gossip_receivers.contains(&receiver);
+                // Gossip neighbors relay what they receive, so only direct sends are filtered.
+                let filtered = if is_gossip {
+                    None
+                } else {
+                    self.nodes[replica]
+                        .node
+                        .filter_notification(&notification, receiver)
+                };
+                let filtered_byte_counts = match (&filtered, &byte_counts) {
+                    (Some(filtered), Some(byte_counts)) => {
+                        let filtered_byte_counts = self.byte_counts(filtered);
+                        if let Some(bandwidth) = self.bandwidth.as_mut() {
+                            bandwidth.record_suppressed(byte_counts, &filtered_byte_counts);
+                        }
+                        Some(filtered_byte_counts)
+                    }
+                    _ => None,
+                };
+                for target in self.replicas_of(receiver) {
+                    let route = Route {
+                        target,
+                        origin: replica,
+                        broadcast: if is_gossip { broadcast } else { None },
+                    };
+                    self.send_network_event_of_size(
+                        route,
+                        Event::DataSyncNotifyEvent {
+                            sender: author,
+                            receiver,
+                            notification: filtered.as_ref().unwrap_or(&notification).clone(),
+                        },
+                        filtered_byte_counts.as_ref().or(byte_counts.as_ref()),
+                    );
+                }
+            }
         }
         // Queries
-        let mut senders = HashSet::new();
+        let mut senders = BTreeSet::new();
         if actions.should_query_all {
-            for index in 0..self.nodes.len() {
-                if index != author.0 {
-                    senders.insert(Author(index));
+            match self.query_fanout {
+                Some(fanout) if fanout < self.num_authors - 1 => {
+                    // Draw among the other nodes, skipping our own index.
+                    let sample = index::sample(&mut self.rng, self.num_authors - 1, fanout);
+                    for index in sample.into_iter() {
+                        senders.insert(Author(if index < author.0 { index } else { index + 1 }));
+                    }
+                }
+                _ => {
+                    for index in 0..self.num_authors {
+                        if index != author.0 {
+                            senders.insert(Author(index));
+                        }
+                    }
                 }
             }
         }
-        let request = self.simulated_node(author).node.create_request();
-        for sender in senders {
-            self.schedule_network_event(Event::DataSyncRequestEvent {
-                receiver: author,
-                sender,
-                request: request.clone(),
-            });
+        if !senders.is_empty() {
+            let request = self.nodes[replica].node.create_request();
+            let byte_counts = self.measure(&request);
+            for sender in senders {
+                for target in self.replicas_of(sender) {
+                    let route = Route {
+                        target,
+                        origin: replica,
+                        broadcast: None,
+                    };
+                    self.send_network_event_of_size(
+                        route,
+                        Event::DataSyncRequestEvent {
+                            receiver: author,
+                            sender,
+                            request: request.clone(),
+                        },
+                        byte_counts.as_ref(),
+                    );
+                }
+            }
         }
     }
 
-    pub fn loop_until(&mut self, max_clock: GlobalTime, csv_path: Option<String>) -> Vec<&Context> {
-        let mut data_writer = {
-            match csv_path {
-                Some(path) => Some(DataWriter::new(self.nodes.len(), path)),
-                None => None,
+    /// Process an event popped from the queue. Return its trace entry if it reached its
+    /// replica, together with the replica if the replica processed it, i.e. was not crashed and
+    /// the event was not a cancelled timer. Return `None` if the event was postponed because the
+    /// replica was busy, or was a gossiped duplicate.
+    fn process_scheduled_event(
+        &mut self,
+        scheduled: ScheduledEvent<Notification, Request, Response>,
+        data_writer: Option<&mut DataWriter>,
+    ) -> Option<(TraceEntry, Option<usize>)> {
+        let ScheduledEvent {
+            time: clock,
+            route,
+            event,
+            ..
+        } = scheduled;
+        let (route, event) = match event {
+            Some(event) => (route, event),
+            None => {
+                // The node is done with its previous events: serve its inbound queue.
+                self.queue_wakeups[route.target] = false;
+                self.inbound_queues[route.target].pop(clock)?
             }
         };
-
-        while let Some(ScheduledEvent(std::cmp::Reverse(clock), event)) = self.pending_events.pop()
-        {
-            if clock > max_clock {
-                break;
+        let busy_until = self.nodes[route.target].busy_until;
+        if clock < busy_until && !self.nodes[route.target].is_crashed(clock) {
+            // Wait for the node to finish processing previous events, in its inbound queue
+            // if it is bounded.
+            match event {
+                Event::UpdateTimerEvent { .. } => self.schedule_event(busy_until, route, event),
+                _ if !self.inbound_queues.is_empty() => {
+                    let (priority, round) = (event.priority(), event.round());
+                    let queue = &mut self.inbound_queues[route.target];
+                    if let Some((_, dropped)) = queue.push(clock, priority, round, (route, event)) {
+                        debug!(
+                            "@{:?} Dropping event on queue overflow {:?}",
+                            clock, dropped
+                        );
+                    }
+                    self.schedule_queue_wakeup(route.target);
+                }
+                _ => self.schedule_event(busy_until, route, event),
             }
+            return None;
+        }
 
-            if let Some(data_writer_val) = data_writer.as_mut() {
-                data_writer_val.update_round_number(&self, &clock);
-                data_writer_val.add_message_counter(&event);
-            }
+        if let Some(data_writer_val) = data_writer {
+            data_writer_val.update_round_number(self, &clock);
+            data_writer_val.add_message_counter(&event);
+        }
 
-            // Events scheduled in the past are fine but they do not move the clock.
-            let clock = std::cmp::max(clock, self.clock);
-            self.clock = clock;
-            debug!("@{:?} Processing event {:?}", clock, event);
-            match event {
-                Event::UpdateTimerEvent { author } => {
-                    let actions = {
-                        let node = self.simulated_node_mut(author);
-                        if clock <= node.ignore_scheduled_updates_until {
-                            // This scheduled update was invalidated in the meantime.
-                            debug!("@{:?} Timer was cancelled: {:?}", clock, event);
-                            continue;
-                        }
-                        node.update(clock)
-                    };
-                    trace!("Node state: {:?}", self.simulated_node(author));
-                    self.process_node_actions(clock, author, actions);
-                }
-                Event::DataSyncNotifyEvent {
-                    receiver,
-                    sender,
-                    notification,
-                } => {
-                    let node = self.simulated_node_mut(receiver);
-                    let result = node
-                        .node
-                        .handle_notification(notification, &mut node.context);
-                    let actions = node.update(clock);
-                    if let Some(request) = result {
-                        self.schedule_network_event(Event::DataSyncRequestEvent {
+        // Events scheduled in the past are fine but they do not move the clock.
+        let clock = std::cmp::max(clock, self.clock);
+        self.clock = clock;
+        let dropped = self.nodes[route.target].is_crashed(clock);
+        if let Some(broadcast) = route.broadcast {
+            if !dropped && !self.receive_broadcast(broadcast, route.target) {
+                debug!("@{:?} Ignoring a gossiped duplicate {:?}", clock, event);
+                self.schedule_queue_wakeup(route.target);
+                return None;
+            }
+        }
+        let entry = event.trace_entry(clock, dropped);
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(entry.clone());
+        }
+        if dropped {
+            debug!("@{:?} Dropping event for crashed node {:?}", clock, event);
+            self.schedule_queue_wakeup(route.target);
+            return Some((entry, None));
+        }
+        debug!("@{:?} Processing event {:?}", clock, event);
+        match event {
+            Event::UpdateTimerEvent { .. } => {
+                let actions = {
+                    let node = &mut self.nodes[route.target];
+                    if clock <= node.ignore_scheduled_updates_until && !node.recovers_at(clock) {
+                        // This scheduled update was invalidated in the meantime.
+                        debug!("@{:?} Timer was cancelled: {:?}", clock, event);
+                        return Some((entry, None));
+                    }
+                    node.update(clock)
+                };
+                trace!("Node state: {:?}", self.nodes[route.target]);
+                self.charge_processing_time(route.target);
+                self.process_node_actions(clock, route.target, actions);
+            }
+            Event::DataSyncNotifyEvent {
+                receiver,
+                sender,
+                notification,
+            } => {
+                let relayed = route
+                    .broadcast
+                    .map(|broadcast| (broadcast, notification.clone()));
+                let node = &mut self.nodes[route.target];
+                let result = node
+                    .node
+                    .handle_notification(notification, &mut node.context);
+                let actions = node.update(clock);
+                self.charge_processing_time(route.target);
+                if let Some(request) = result {
+                    self.send_network_event(
+                        route.reply(),
+                        Event::DataSyncRequestEvent {
                             sender,
                             receiver,
                             request,
-                        });
-                    }
-                    trace!(
-                        "Node state: {:?}, node index: {:?}",
-                        self.simulated_node(receiver),
-                        receiver
+                        },
                     );
-                    self.process_node_actions(clock, receiver, actions);
                 }
-                Event::DataSyncRequestEvent {
-                    receiver,
-                    sender,
-                    request,
-                } => {
-                    let response = self.simulated_node_mut(sender).node.handle_request(request);
-                    self.schedule_network_event(Event::DataSyncResponseEvent {
+                trace!(
+                    "Node state: {:?}, node index: {:?}",
+                    self.nodes[route.target],
+                    receiver
+                );
+                self.process_node_actions(clock, route.target, actions);
+                if let Some((broadcast, notification)) = relayed {
+                    self.relay_broadcast(route, broadcast, notification);
+                }
+            }
+            Event::DataSyncRequestEvent {
+                receiver,
+                sender,
+                request,
+            } => {
+                let response = self.nodes[route.target].node.handle_request(request);
+                self.send_network_event(
+                    route.reply(),
+                    Event::DataSyncResponseEvent {
                         sender,
                         receiver,
                         response,
-                    });
-                }
-                Event::DataSyncResponseEvent {
-                    receiver, response, ..
-                } => {
-                    let node = self.simulated_node_mut(receiver);
-                    let local_clock = clock.to_node_time(node.startup_time);
-                    node.node
-                        .handle_response(response, &mut node.context, local_clock);
-                    let actions = node.update(clock);
-                    trace!("Node state: {:?}", node);
-                    self.process_node_actions(clock, receiver, actions);
+                    },
+                );
+            }
+            Event::DataSyncResponseEvent { response, .. } => {
+                let node = &mut self.nodes[route.target];
+                let local_clock = clock.to_node_time(node.startup_time, node.clock_skew);
+                node.node
+                    .handle_response(response, &mut node.context, local_clock);
+                let actions = node.update(clock);
+                trace!("Node state: {:?}", node);
+                self.charge_processing_time(route.target);
+                self.process_node_actions(clock, route.target, actions);
+            }
+        }
+        self.schedule_queue_wakeup(route.target);
+        Some((entry, Some(route.target)))
+    }
+
+    /// A snapshot of the run so far that can be saved to a file.
+    pub fn saved_snapshot(&self) -> SimulationSnapshot {
+        let event_snapshot = |time, replica, event: Option<&Event<_, _, _>>| EventSnapshot {
+            time,
+            replica,
+            event: event.map(|event| format!("{:?}", event)),
+        };
+        let mut pending_events: Vec<_> = self.pending_events.iter().collect();
+        pending_events.sort_by_key(|scheduled| std::cmp::Reverse(scheduled.key()));
+        SimulationSnapshot {
+            seed: self.seed,
+            reseeds: self.reseeds.clone(),
+            events: self.num_processed_events,
+            clock: self.clock,
+            rng: (self.rng.seed, self.rng.draws),
+            pending_events: pending_events
+                .into_iter()
+                .map(|scheduled| {
+                    event_snapshot(
+                        scheduled.time,
+                        scheduled.route.target,
+                        scheduled.event.as_ref(),
+                    )
+                })
+                .collect(),
+            inbound_queues: self
+                .inbound_queues
+                .iter()
+                .enumerate()
+                .map(|(replica, queue)| {
+                    queue
+                        .iter()
+                        .map(|(_, event)| event_snapshot(self.clock, replica, Some(event)))
+                        .collect()
+                })
+                .collect(),
+            replicas: self
+                .nodes
+                .iter()
+                .map(|node| ReplicaSnapshot {
+                    author: node.author,
+                    active_round: node.active_round(),
+                    busy_until: node.busy_until,
+                    commits: node
+                        .node
+                        .commits()
+                        .iter()
+                        .map(|commit| format!("{:?}", commit))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Bring a simulator that was just built like the one of a saved snapshot, e.g. from the
+    /// same scenario, to the state of the snapshot: replay its events, reseeding the random
+    /// generator at the same points, then check that the replayed run matches the snapshot.
+    pub fn restore_saved_snapshot(
+        &mut self,
+        snapshot: &SimulationSnapshot,
+    ) -> std::result::Result<(), String> {
+        if self.seed != snapshot.seed || self.num_processed_events > 0 {
+            return Err(format!(
+                "Snapshots of runs from seed {} must be restored in a new simulator with the same \
+                 seed",
+                snapshot.seed
+            ));
+        }
+        let mut reseeds = snapshot.reseeds.iter().peekable();
+        loop {
+            while let Some((_, seed)) =
+                reseeds.next_if(|(events, _)| *events == self.num_processed_events)
+            {
+                self.reseed(*seed);
+            }
+            if self.num_processed_events >= snapshot.events {
+                break;
+            }
+            match self.pending_events.pop() {
+                Some(scheduled) => {
+                    self.num_processed_events += 1;
+                    self.process_scheduled_event(scheduled, None);
                 }
+                None => break,
+            }
+        }
+        let replayed = self.saved_snapshot();
+        let difference = if replayed.events != snapshot.events || replayed.clock != snapshot.clock {
+            "events processed"
+        } else if replayed.rng != snapshot.rng {
+            "random generator"
+        } else if replayed.pending_events != snapshot.pending_events
+            || replayed.inbound_queues != snapshot.inbound_queues
+        {
+            "pending events"
+        } else if replayed.replicas != snapshot.replicas {
+            "replicas"
+        } else {
+            return Ok(());
+        };
+        Err(format!(
+            "Replaying the run from seed {} does not give the {} of the snapshot",
+            snapshot.seed, difference
+        ))
+    }
+
+    /// Process the pending events until one of them reaches a replica, whether the replica
+    /// processes it or ignores it, e.g. because it is crashed, regardless of any time limit.
+    /// Return the trace entry of this event, or `None` if no events are left. Unlike
+    /// `loop_until`, commits are not checked.
+    pub fn step(&mut self) -> Option<TraceEntry> {
+        while let Some(scheduled) = self.pending_events.pop() {
+            self.num_processed_events += 1;
+            if let Some((entry, _)) = self.process_scheduled_event(scheduled, None) {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    pub fn loop_until(&mut self, max_clock: GlobalTime, csv_path: Option<String>) -> Vec<&Context> {
+        self.loop_until_with_observer(max_clock, csv_path, |_, _| ())
+    }
+
+    /// Same as `loop_until` but calls `observer` after each processed event.
+    /// Panics as soon as two honest replicas commit different blocks or states at the same
+    /// round, e.g. because the execution of the SMR context is not deterministic.
+    pub fn loop_until_with_observer<F>(
+        &mut self,
+        max_clock: GlobalTime,
+        csv_path: Option<String>,
+        observer: F,
+    ) -> Vec<&Context>
+    where
+        F: FnMut(&Self, GlobalTime),
+    {
+        self.run_until_with_observer(max_clock, csv_path, &StopConditions::default(), observer);
+        self.nodes.iter().map(|node| &node.context).collect()
+    }
+
+    /// Capture the views of all replicas on the round of a divergence, write them to the
+    /// triage file if any, and return the description of the divergence.
+    fn record_divergence(&mut self, divergence: Divergence, honest: &[bool]) -> String {
+        let Divergence {
+            epoch,
+            round,
+            description,
+        } = divergence;
+        let bundle = TriageBundle {
+            violation: description,
+            epoch,
+            round,
+            clock: self.clock,
+            replicas: self
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(replica, node)| ReplicaTriage {
+                    replica,
+                    author: node.author,
+                    honest: honest[replica],
+                    crashed: node.is_crashed(self.clock),
+                    summary: node.node.round_summary(epoch, round),
+                })
+                .collect(),
+        };
+        let triage = serde_json::to_value(&bundle).expect("Failed to serialize triage bundle");
+        let mut violation = bundle.violation;
+        if let Some(path) = &self.triage_path {
+            match serde_json::to_string_pretty(&triage)
+                .map_err(|error| error.to_string())
+                .and_then(|json| std::fs::write(path, json).map_err(|error| error.to_string()))
+            {
+                Ok(()) => violation += &format!(" (triage bundle in {})", path),
+                Err(error) => error!("Failed to write triage bundle to {}: {}", path, error),
             }
         }
+        self.triage = Some(triage);
+        violation
+    }
+
+    /// Process the events until `max_clock`, or until one of the given conditions is met.
+    pub fn run_until(&mut self, max_clock: GlobalTime, conditions: &StopConditions) -> RunOutcome {
+        self.run_until_with_observer(max_clock, None, conditions, |_, _| ())
+    }
+
+    /// Same as `run_until` but calls `observer` after each processed event. Unless
+    /// `conditions.stop_on_violation` is set, panics as soon as two honest replicas commit
+    /// different blocks or states at the same round.
+    pub fn run_until_with_observer<F>(
+        &mut self,
+        max_clock: GlobalTime,
+        csv_path: Option<String>,
+        conditions: &StopConditions,
+        mut observer: F,
+    ) -> RunOutcome
+    where
+        F: FnMut(&Self, GlobalTime),
+    {
+        let start = std::time::Instant::now();
+        self.triage = None;
+        let mut data_writer = {
+            match csv_path {
+                Some(path) => Some(DataWriter::new(self.num_authors, path)),
+                None => None,
+            }
+        };
+        let honest: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| self.replicas_of(node.author).len() == 1)
+            .collect();
+        let mut commit_checker = CommitChecker::new();
+
+        // Leave the events after `max_clock` in the queue, so that the run may be resumed.
+        let reason = loop {
+            match self.pending_events.peek() {
+                None => break StopReason::NoMoreEvents,
+                Some(event) if event.time > max_clock => break StopReason::SimulatedTime,
+                Some(_) => (),
+            }
+            if conditions
+                .wall_clock
+                .is_some_and(|budget| start.elapsed() >= budget)
+            {
+                break StopReason::WallClock;
+            }
+            let scheduled = self.pending_events.pop().unwrap();
+            self.num_processed_events += 1;
+            if let Some((entry, Some(replica))) =
+                self.process_scheduled_event(scheduled, data_writer.as_mut())
+            {
+                if honest[replica] {
+                    let instance_commits = self.nodes[replica].node.instance_commits();
+                    let divergence = instance_commits.iter().find_map(|(instance, commits)| {
+                        commit_checker.check(replica, *instance, commits).err()
+                    });
+                    if let Some(divergence) = divergence {
+                        let violation = self.record_divergence(divergence, &honest);
+                        assert!(conditions.stop_on_violation, "{}", violation);
+                        break StopReason::Violation(violation);
+                    }
+                    if conditions.commits.is_some_and(|target| {
+                        instance_commits
+                            .iter()
+                            .any(|(_, commits)| commits.len() >= target)
+                    }) {
+                        observer(self, entry.clock);
+                        break StopReason::Commits;
+                    }
+                }
+                observer(self, entry.clock);
+            }
+        };
 
         if let Some(data_writer_val) = data_writer {
             data_writer_val.write_to_file();
         }
 
-        self.nodes.iter().map(|node| &node.context).collect()
+        RunOutcome {
+            reason,
+            clock: self.clock,
+            events: self.num_processed_events,
+        }
     }
 }
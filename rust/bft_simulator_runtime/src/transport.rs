@@ -0,0 +1,261 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Running nodes as real processes. The simulator delivers messages through its event queue;
+//! here a `NodeRunner` drives a single node from the messages of a `ProcessTransport` and from
+//! the wall clock, with the same handling of data-synchronization exchanges. Node times are
+//! milliseconds since the startup of the runner.
+
+use std::{
+    collections::BTreeSet,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    base_types::{Author, NodeTime},
+    ConsensusNode, DataSyncNode, NodeUpdateActions,
+};
+
+#[cfg(test)]
+#[path = "unit_tests/transport_tests.rs"]
+mod transport_tests;
+
+/// A network message of a data-synchronization exchange.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Message<Notification, Request, Response> {
+    Notification(Notification),
+    Request(Request),
+    Response(Response),
+}
+
+/// Best-effort delivery of messages between nodes running as processes, in real time. Simulated
+/// runs do not use it: the simulator schedules messages in its event queue, which models their
+/// delays, bandwidth and losses.
+pub trait ProcessTransport<Notification, Request, Response> {
+    /// Queue a message for the given receiver. Messages may be lost.
+    fn send(&mut self, receiver: Author, message: Message<Notification, Request, Response>);
+    /// Wait for the next message and its sender until the given deadline.
+    fn receive(
+        &mut self,
+        deadline: Instant,
+    ) -> Option<(Author, Message<Notification, Request, Response>)>;
+}
+
+/// A message together with its sender.
+type Envelope<Notification, Request, Response> = (Author, Message<Notification, Request, Response>);
+
+/// In-process transport over channels, mostly for testing.
+pub struct LocalTransport<Notification, Request, Response> {
+    author: Author,
+    peers: Vec<mpsc::Sender<Envelope<Notification, Request, Response>>>,
+    inbox: mpsc::Receiver<Envelope<Notification, Request, Response>>,
+}
+
+impl<Notification, Request, Response> LocalTransport<Notification, Request, Response> {
+    /// Create connected transports for the nodes `0..num_nodes`.
+    pub fn network(num_nodes: usize) -> Vec<Self> {
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..num_nodes).map(|_| mpsc::channel()).unzip();
+        receivers
+            .into_iter()
+            .enumerate()
+            .map(|(index, inbox)| LocalTransport {
+                author: Author(index),
+                peers: senders.clone(),
+                inbox,
+            })
+            .collect()
+    }
+}
+
+impl<Notification, Request, Response> ProcessTransport<Notification, Request, Response>
+    for LocalTransport<Notification, Request, Response>
+{
+    fn send(&mut self, receiver: Author, message: Message<Notification, Request, Response>) {
+        if let Some(peer) = self.peers.get(receiver.0) {
+            // The receiver may have stopped already.
+            let _ = peer.send((self.author, message));
+        }
+    }
+
+    fn receive(
+        &mut self,
+        deadline: Instant,
+    ) -> Option<(Author, Message<Notification, Request, Response>)> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.inbox.recv_timeout(timeout).ok()
+    }
+}
+
+/// Work submitted to a running node from another thread, e.g. to answer an introspection
+/// query. The returned actions are processed like those of `update_node`, except that an
+/// earlier scheduled update is kept.
+pub type NodeTask<Node, Context> =
+    Box<dyn FnOnce(&mut Node, &mut Context, NodeTime) -> NodeUpdateActions + Send>;
+
+/// Maximal delay, in milliseconds, before a submitted task is run.
+const TASK_POLL_INTERVAL: i64 = 10;
+
+/// Drive a node with the messages of a transport and the wall clock.
+pub struct NodeRunner<Node, Context, T> {
+    author: Author,
+    num_nodes: usize,
+    node: Node,
+    context: Context,
+    transport: T,
+    startup_time: Instant,
+    next_scheduled_update: NodeTime,
+    tasks: Option<mpsc::Receiver<NodeTask<Node, Context>>>,
+}
+
+impl<Node, Context, Notification, Request, Response, T> NodeRunner<Node, Context, T>
+where
+    Node: ConsensusNode<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>,
+    Notification: Clone,
+    Request: Clone,
+    T: ProcessTransport<Notification, Request, Response>,
+{
+    pub fn new<F, G>(
+        author: Author,
+        num_nodes: usize,
+        context_factory: F,
+        node_factory: G,
+        transport: T,
+    ) -> Self
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        let context = context_factory(author, num_nodes);
+        let node = node_factory(author, &context, NodeTime(0));
+        NodeRunner {
+            author,
+            num_nodes,
+            node,
+            context,
+            transport,
+            startup_time: Instant::now(),
+            next_scheduled_update: NodeTime(0),
+            tasks: None,
+        }
+    }
+
+    /// Create a queue to submit tasks to the node while it runs. Only the latest queue is used.
+    pub fn task_queue(&mut self) -> mpsc::Sender<NodeTask<Node, Context>> {
+        let (sender, receiver) = mpsc::channel();
+        self.tasks = Some(receiver);
+        sender
+    }
+
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Milliseconds since the startup of the runner.
+    pub fn clock(&self) -> NodeTime {
+        NodeTime(self.startup_time.elapsed().as_millis() as i64)
+    }
+
+    fn instant(&self, clock: NodeTime) -> Instant {
+        self.startup_time + Duration::from_millis(clock.0 as u64)
+    }
+
+    /// Process messages and scheduled updates until the given node time.
+    pub fn run_until(&mut self, max_clock: NodeTime) {
+        loop {
+            let clock = self.clock();
+            if clock >= max_clock {
+                return;
+            }
+            if clock >= self.next_scheduled_update {
+                let actions = self.node.update_node(clock, &mut self.context);
+                self.process_node_actions(actions);
+                continue;
+            }
+            let mut deadline = std::cmp::min(self.next_scheduled_update, max_clock);
+            if self.tasks.is_some() {
+                deadline = std::cmp::min(deadline, clock + TASK_POLL_INTERVAL);
+            }
+            if let Some((peer, message)) = self.transport.receive(self.instant(deadline)) {
+                self.handle_message(peer, message);
+            }
+            self.run_tasks();
+        }
+    }
+
+    fn run_tasks(&mut self) {
+        let tasks: Vec<_> = match &self.tasks {
+            Some(receiver) => receiver.try_iter().collect(),
+            None => return,
+        };
+        for task in tasks {
+            let clock = self.clock();
+            let mut actions = task(&mut self.node, &mut self.context, clock);
+            actions.next_scheduled_update =
+                std::cmp::min(actions.next_scheduled_update, self.next_scheduled_update);
+            self.process_node_actions(actions);
+        }
+    }
+
+    fn handle_message(&mut self, peer: Author, message: Message<Notification, Request, Response>) {
+        trace!("{:?} Received message from {:?}", self.author, peer);
+        match message {
+            Message::Notification(notification) => {
+                if let Some(request) = self
+                    .node
+                    .handle_notification(notification, &mut self.context)
+                {
+                    self.transport.send(peer, Message::Request(request));
+                }
+            }
+            Message::Request(request) => {
+                let response = self.node.handle_request(request);
+                self.transport.send(peer, Message::Response(response));
+                // Serving a request does not change the node.
+                return;
+            }
+            Message::Response(response) => {
+                let clock = self.clock();
+                self.node
+                    .handle_response(response, &mut self.context, clock);
+            }
+        }
+        let clock = self.clock();
+        let actions = self.node.update_node(clock, &mut self.context);
+        self.process_node_actions(actions);
+    }
+
+    fn process_node_actions(&mut self, actions: NodeUpdateActions) {
+        debug!("{:?} Processing node actions: {:?}", self.author, actions);
+        // Make sure that time moves forward between two scheduled updates.
+        self.next_scheduled_update = std::cmp::max(actions.next_scheduled_update, self.clock() + 1);
+        let local_author = self.author;
+        let others = (0..self.num_nodes)
+            .map(Author)
+            .filter(move |author| *author != local_author);
+        let mut receivers: BTreeSet<_> = actions.should_send.into_iter().collect();
+        if actions.should_broadcast {
+            receivers.extend(others.clone());
+        }
+        if !receivers.is_empty() {
+            let notification = self.node.create_notification();
+            for receiver in receivers {
+                self.transport
+                    .send(receiver, Message::Notification(notification.clone()));
+            }
+        }
+        if actions.should_query_all {
+            let request = self.node.create_request();
+            for sender in others {
+                self.transport
+                    .send(sender, Message::Request(request.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_record() {
+    let mut stats = BandwidthStats::new(3, 10);
+    stats.record(
+        5,
+        Author(0),
+        Author(2),
+        "notify",
+        &[("block", 40), ("vote", 20), ("vote", 20)],
+    );
+    stats.record(27, Author(2), Author(1), "response", &[("block", 40)]);
+    assert_eq!(stats.total_bytes, 120);
+    assert_eq!(stats.sent, vec![80, 0, 40]);
+    assert_eq!(stats.received, vec![0, 40, 80]);
+    assert_eq!(stats.by_message.get("notify"), Some(&80));
+    assert_eq!(stats.by_message.get("request"), None);
+    assert_eq!(stats.by_content.get("block"), Some(&80));
+    assert_eq!(stats.by_content.get("vote"), Some(&40));
+    assert_eq!(stats.time_series, vec![80, 0, 40]);
+    assert_eq!(stats.peak_bytes(), 80);
+}
+
+#[test]
+fn test_record_suppressed() {
+    let mut stats = BandwidthStats::new(2, 10);
+    stats.record_suppressed(
+        &[("header", 2), ("block", 40), ("vote", 20), ("vote", 20)],
+        &[("header", 2), ("vote", 20)],
+    );
+    assert_eq!(stats.suppressed_bytes, 60);
+    assert_eq!(stats.suppressed_by_content.get("block"), Some(&40));
+    assert_eq!(stats.suppressed_by_content.get("vote"), Some(&20));
+    assert_eq!(stats.suppressed_by_content.get("header"), None);
+    assert_eq!(stats.total_bytes, 0);
+}
+
+#[test]
+fn test_payload_sizes() {
+    assert_eq!(PayloadSizes::Fixed { bytes: 100 }.size(12345), 100);
+    let uniform = PayloadSizes::Uniform { min: 10, max: 13 };
+    let sizes: Vec<_> = (0..8).map(|payload| uniform.size(payload)).collect();
+    assert_eq!(sizes, vec![10, 11, 12, 13, 10, 11, 12, 13]);
+    assert_eq!(PayloadSizes::Uniform { min: 7, max: 7 }.size(u64::MAX), 7);
+}
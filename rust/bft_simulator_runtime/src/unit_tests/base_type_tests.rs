@@ -15,3 +15,30 @@ fn test_signature() {
     assert!(sig.check(32, Author(2)).is_err());
     assert!(sig.check(35, Author(1)).is_err());
 }
+
+#[test]
+fn test_rotated_keys() {
+    let key = PublicKey::initial(Author(2));
+    assert_eq!(
+        Signature::sign_with_key(35, key),
+        Signature::sign(35, Author(2))
+    );
+    let sig = Signature::sign_with_key(35, key.rotated());
+    assert!(sig.check_with_key(35, key.rotated()).is_ok());
+    assert!(sig.check(35, Author(2)).is_err());
+    assert!(sig.check_with_key(35, key.rotated().rotated()).is_err());
+}
+
+#[test]
+fn test_stable_hash() {
+    // Reference values of 64-bit FNV-1a.
+    assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    // Signatures only depend on the specification of the hash function.
+    let mut bytes = 35u64.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&2u64.to_le_bytes());
+    assert_eq!(
+        Signature::sign(35, Author(2)),
+        Signature(stable_hash(&bytes))
+    );
+}
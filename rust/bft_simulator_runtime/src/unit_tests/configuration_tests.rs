@@ -1,7 +1,7 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
-use std::iter::FromIterator;
+use std::{collections::BTreeSet, iter::FromIterator};
 
 use super::*;
 
@@ -36,6 +36,27 @@ fn test_pick_author() {
     assert_eq!(vec![1, 2, 5], results);
 }
 
+#[test]
+fn test_pick_author_excluding() {
+    let mut rights = BTreeMap::new();
+    rights.insert(Author(0), 1);
+    rights.insert(Author(1), 2);
+    rights.insert(Author(2), 5);
+    let config = EpochConfiguration::new(rights);
+
+    let excluded = BTreeSet::from_iter(vec![Author(2)]);
+    let mut hits = BTreeMap::new();
+    for seed in 10..13 {
+        let author = config.pick_author_excluding(seed, &excluded).unwrap();
+        *hits.entry(author).or_insert(0) += 1;
+    }
+    assert_eq!(hits[&Author(0)], 1);
+    assert_eq!(hits[&Author(1)], 2);
+
+    let everyone = BTreeSet::from_iter(vec![Author(0), Author(1), Author(2)]);
+    assert_eq!(config.pick_author_excluding(10, &everyone), None);
+}
+
 fn equal_configuration(num_nodes: usize) -> EpochConfiguration {
     let mut voting_rights = BTreeMap::new();
     for index in 0..num_nodes {
@@ -53,3 +74,37 @@ fn test_quorum() {
     assert_eq!(equal_configuration(5).quorum_threshold(), 4);
     assert_eq!(equal_configuration(6).quorum_threshold(), 5);
 }
+
+#[test]
+fn test_resilience() {
+    for num_nodes in 1..20 {
+        let configuration = equal_configuration(num_nodes);
+        assert_eq!(configuration.quorum_threshold(), 2 * num_nodes / 3 + 1);
+        assert_eq!(configuration.validity_threshold(), num_nodes.div_ceil(3));
+    }
+    let rights: BTreeMap<_, _> = (0..11).map(|index| (Author(index), 1)).collect();
+    let configuration = EpochConfiguration::with_resilience(rights, 5);
+    // n = 5f + 1 with f = 2.
+    assert_eq!(configuration.max_faulty_votes(), 2);
+    assert_eq!(configuration.quorum_threshold(), 9);
+    assert_eq!(configuration.validity_threshold(), 3);
+}
+
+#[test]
+fn test_key_versions() {
+    let rights = BTreeMap::from_iter((0..4).map(|index| (Author(index), 1)));
+    let versions = BTreeMap::from_iter(vec![(Author(1), 2), (Author(2), 0)]);
+    let config = EpochConfiguration::new(rights.clone()).with_key_versions(versions);
+    assert_eq!(config.public_key(Author(0)), PublicKey::initial(Author(0)));
+    assert_eq!(
+        config.public_key(Author(1)),
+        PublicKey::initial(Author(1)).rotated().rotated()
+    );
+    // Initial versions are left out, so that configurations compare as before rotations.
+    assert_eq!(config.key_versions().len(), 1);
+    assert_ne!(config, EpochConfiguration::new(rights.clone()));
+    assert_eq!(
+        EpochConfiguration::new(rights.clone()).with_key_versions(BTreeMap::new()),
+        EpochConfiguration::new(rights)
+    );
+}
@@ -0,0 +1,30 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn test_random_regular() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let overlay = GossipOverlay::random_regular(100, 8, 2, &mut rng);
+    assert_eq!(overlay.relay_delay(), 2);
+    for index in 0..100 {
+        let neighbors = overlay.neighbors(Author(index));
+        assert_eq!(neighbors.len(), 8);
+        assert!(!neighbors.contains(&Author(index)));
+        assert_eq!(
+            neighbors.iter().collect::<BTreeSet<_>>().len(),
+            neighbors.len()
+        );
+        for neighbor in neighbors {
+            assert!(overlay.neighbors(*neighbor).contains(&Author(index)));
+        }
+    }
+    // A few hops reach all the nodes.
+    let hops = overlay.eccentricity(Author(0)).unwrap();
+    assert!(hops > 1 && hops <= 5);
+
+    let complete = GossipOverlay::random_regular(4, 3, 0, &mut rng);
+    assert_eq!(complete.eccentricity(Author(2)), Some(1));
+}
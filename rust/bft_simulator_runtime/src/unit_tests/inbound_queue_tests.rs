@@ -0,0 +1,67 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+/// A message of the given priority and round.
+struct Message(usize, usize);
+
+impl MessagePriority for Message {
+    fn priority(&self) -> usize {
+        self.0
+    }
+
+    fn round(&self) -> Round {
+        Round(self.1)
+    }
+}
+
+fn fill(policy: QueuePolicy) -> (InboundQueue<&'static str>, Vec<&'static str>) {
+    let mut queue = InboundQueue::new(3, policy);
+    let mut dropped = Vec::new();
+    for (time, message, name) in [
+        (1, Message(2, 5), "block"),
+        (2, Message(0, 4), "old vote"),
+        (3, Message(1, 6), "qc"),
+        (4, Message(0, 7), "vote"),
+    ] {
+        dropped.extend(queue.push(GlobalTime(time), message.priority(), message.round(), name));
+    }
+    (queue, dropped)
+}
+
+fn drain(queue: &mut InboundQueue<&'static str>) -> Vec<&'static str> {
+    std::iter::from_fn(|| queue.pop(GlobalTime(10))).collect()
+}
+
+#[test]
+fn test_fifo() {
+    let (mut queue, dropped) = fill(QueuePolicy::Fifo);
+    assert_eq!(dropped, vec!["vote"]);
+    assert_eq!(queue.len(), 3);
+    assert_eq!(drain(&mut queue), vec!["block", "old vote", "qc"]);
+    assert!(queue.is_empty());
+    assert_eq!(
+        queue.stats(),
+        &QueueStats {
+            enqueued: 4,
+            dropped: 1,
+            max_length: 3,
+            total_wait: 9 + 8 + 7,
+        }
+    );
+}
+
+#[test]
+fn test_priority() {
+    let (mut queue, dropped) = fill(QueuePolicy::Priority);
+    assert_eq!(dropped, vec!["block"]);
+    assert_eq!(drain(&mut queue), vec!["old vote", "vote", "qc"]);
+}
+
+#[test]
+fn test_newest_round_first() {
+    let (mut queue, dropped) = fill(QueuePolicy::NewestRoundFirst);
+    assert_eq!(dropped, vec!["old vote"]);
+    assert_eq!(drain(&mut queue), vec!["vote", "qc", "block"]);
+}
@@ -2,11 +2,393 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::scheduler::Scheduler;
 
 #[test]
 fn test_time_conversion() {
     let x = GlobalTime(15);
     let start = GlobalTime(3);
-    assert_eq!(x.to_node_time(start), NodeTime(12));
-    assert_eq!(GlobalTime::from_node_time(NodeTime(12), start), x);
+    let no_skew = ClockSkew::default();
+    assert_eq!(x.to_node_time(start, no_skew), NodeTime(12));
+    assert_eq!(GlobalTime::from_node_time(NodeTime(12), start, no_skew), x);
+}
+
+#[test]
+fn test_clock_skew() {
+    let start = GlobalTime(3);
+    let skew = ClockSkew {
+        offset: 100,
+        drift: 0.5,
+    };
+    assert_eq!(start.to_node_time(start, skew), NodeTime(100));
+    assert_eq!(GlobalTime(13).to_node_time(start, skew), NodeTime(115));
+    assert_eq!(GlobalTime(14).to_node_time(start, skew), NodeTime(116));
+    assert_eq!(
+        GlobalTime::from_node_time(NodeTime(116), start, skew),
+        GlobalTime(14)
+    );
+    // Node times that the clock skips are reached at the next tick.
+    assert_eq!(
+        GlobalTime::from_node_time(NodeTime(117), start, skew),
+        GlobalTime(15)
+    );
+    let slow = ClockSkew {
+        offset: -10,
+        drift: -0.25,
+    };
+    assert_eq!(GlobalTime(7).to_node_time(start, slow), NodeTime(-7));
+    assert_eq!(
+        GlobalTime::from_node_time(NodeTime(-7), start, slow),
+        GlobalTime(7)
+    );
+    for time in 0..100 {
+        let node_time = GlobalTime(time).to_node_time(start, slow);
+        assert!(GlobalTime::from_node_time(node_time, start, slow) <= GlobalTime(time));
+    }
+}
+
+#[test]
+fn test_crash_interval() {
+    let node = SimulatedNode {
+        author: Author(0),
+        startup_time: GlobalTime(0),
+        clock_skew: ClockSkew::default(),
+        ignore_scheduled_updates_until: GlobalTime(-1),
+        crashes: vec![(GlobalTime(10), GlobalTime(20))],
+        busy_until: GlobalTime(0),
+        upload_throughput: None,
+        uplink_busy_until: GlobalTime(0),
+        node: (),
+        context: (),
+    };
+    assert!(!node.is_crashed(GlobalTime(9)));
+    assert!(node.is_crashed(GlobalTime(10)));
+    assert!(node.is_crashed(GlobalTime(19)));
+    assert!(!node.is_crashed(GlobalTime(20)));
+    assert!(node.recovers_at(GlobalTime(20)));
+}
+
+#[test]
+fn test_upload_throughput() {
+    let mut node = SimulatedNode {
+        author: Author(0),
+        startup_time: GlobalTime(0),
+        clock_skew: ClockSkew::default(),
+        ignore_scheduled_updates_until: GlobalTime(-1),
+        crashes: Vec::new(),
+        busy_until: GlobalTime(0),
+        upload_throughput: None,
+        uplink_busy_until: GlobalTime(0),
+        node: (),
+        context: (),
+    };
+    assert_eq!(node.upload(GlobalTime(10), 1000), GlobalTime(10));
+    node.upload_throughput = Some(100.0);
+    assert_eq!(node.upload(GlobalTime(10), 1000), GlobalTime(20));
+    // Messages leave one after the other.
+    assert_eq!(node.upload(GlobalTime(12), 150), GlobalTime(22));
+    assert_eq!(node.upload(GlobalTime(30), 100), GlobalTime(31));
+}
+
+#[test]
+fn test_link_degradation() {
+    let degradation = LinkDegradation {
+        group_a: vec![Author(0), Author(1)].into_iter().collect(),
+        group_b: vec![Author(2)].into_iter().collect(),
+        start: GlobalTime(10),
+        end: GlobalTime(20),
+        extra_delay: 100,
+    };
+    assert!(degradation.applies(GlobalTime(10), Author(0), Author(2)));
+    assert!(degradation.applies(GlobalTime(15), Author(2), Author(1)));
+    assert!(!degradation.applies(GlobalTime(15), Author(0), Author(1)));
+    assert!(!degradation.applies(GlobalTime(20), Author(0), Author(2)));
+}
+
+#[test]
+fn test_partition() {
+    let partition = Partition {
+        groups: vec![
+            vec![0, 1, 4].into_iter().collect(),
+            vec![1, 2, 3].into_iter().collect(),
+        ],
+        start: GlobalTime(10),
+        end: GlobalTime(20),
+    };
+    assert!(partition.separates(GlobalTime(10), 0, 2));
+    assert!(partition.separates(GlobalTime(15), 4, 3));
+    assert!(!partition.separates(GlobalTime(15), 0, 4));
+    assert!(!partition.separates(GlobalTime(15), 1, 3));
+    assert!(!partition.separates(GlobalTime(20), 0, 2));
+}
+
+#[test]
+fn test_twins() {
+    let mut sim: Simulator<(), (), u8, u8, u8> =
+        Simulator::new(4, RandomDelay::new(10.0, 4.0), |_, _| (), |_, _, _| ());
+    assert_eq!(sim.add_twin(Author(1), |_, _| (), |_, _, _| ()), 4);
+    assert_eq!(sim.num_nodes(), 4);
+    assert_eq!(sim.replicas().len(), 5);
+    assert_eq!(sim.replicas()[4].author(), Author(1));
+    assert_eq!(sim.replicas_of(Author(1)), vec![1, 4]);
+    sim.add_crash(Author(1), GlobalTime(10), GlobalTime(20));
+    assert!(sim.replicas()[4].is_crashed(GlobalTime(15)));
+    // Twins do not count twice in a quorum.
+    let configuration = EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect());
+    sim.add_crash(Author(2), GlobalTime(10), GlobalTime(20));
+    assert_eq!(
+        sim.quorum_impossible_intervals(&configuration, GlobalTime(100)),
+        vec![(GlobalTime(10), GlobalTime(20))]
+    );
+}
+
+#[test]
+fn test_event_queue_order() {
+    let mut sim: Simulator<(), (), u8, u8, u8> =
+        Simulator::new(0, RandomDelay::new(10.0, 4.0), |_, _| (), |_, _, _| ());
+    let notify = |notification| Event::DataSyncNotifyEvent {
+        sender: Author(0),
+        receiver: Author(1),
+        notification,
+    };
+    for (time, notification) in &[(5, 1), (3, 2), (5, 0), (3, 1)] {
+        sim.schedule_event(GlobalTime(*time), Route::local(1), notify(*notification));
+    }
+    // By time, then first scheduled first, whatever the content of the messages.
+    let mut order = Vec::new();
+    while let Some(ScheduledEvent { time, event, .. }) = sim.pending_events.pop() {
+        if let Some(Event::DataSyncNotifyEvent { notification, .. }) = event {
+            order.push((time.0, notification));
+        }
+    }
+    assert_eq!(order, vec![(3, 2), (3, 1), (5, 1), (5, 0)]);
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let mut sim: Simulator<(), (), u8, u8, u8> =
+        Simulator::with_seed(3, 4, RandomDelay::new(10.0, 4.0), |_, _| (), |_, _, _| ());
+    let snapshot = sim.snapshot();
+    let delay = GlobalTime(0).add_delay(sim.network_delay, &mut sim.rng);
+    sim.pending_events.clear();
+    sim.restore(&snapshot);
+    // The timers of the 4 nodes are pending again, and delays are drawn again.
+    assert_eq!(sim.pending_events.len(), 4);
+    assert_eq!(
+        GlobalTime(0).add_delay(sim.network_delay, &mut sim.rng),
+        delay
+    );
+    sim.restore(&snapshot);
+    sim.reseed(4);
+    assert_ne!(
+        GlobalTime(0).add_delay(sim.network_delay, &mut sim.rng),
+        delay
+    );
+}
+
+#[test]
+fn test_simulation_rng() {
+    let mut rng = SimulationRng::new(5);
+    let mut expected = StdRng::seed_from_u64(5);
+    assert_eq!(rng.next_u32(), expected.next_u32());
+    assert_eq!(rng.next_u64(), expected.next_u64());
+    let mut bytes = [0u8; 5];
+    rng.fill_bytes(&mut bytes);
+    let mut expected_bytes = [0u8; 5];
+    expected.fill_bytes(&mut expected_bytes);
+    assert_eq!(bytes, expected_bytes);
+    // 32-bit words drawn: 1 + 2 + 2.
+    assert_eq!((rng.seed, rng.draws), (5, 5));
+}
+
+#[test]
+fn test_quorum_impossible_intervals() {
+    let mut sim: Simulator<(), (), u8, u8, u8> =
+        Simulator::new(4, RandomDelay::new(10.0, 4.0), |_, _| (), |_, _, _| ());
+    let configuration = EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect());
+    sim.add_crash(Author(0), GlobalTime(10), GlobalTime(50));
+    assert!(sim
+        .quorum_impossible_intervals(&configuration, GlobalTime(100))
+        .is_empty());
+    sim.add_crash(Author(1), GlobalTime(20), GlobalTime(30));
+    sim.add_crash(Author(2), GlobalTime(30), GlobalTime(40));
+    sim.add_crash(Author(3), GlobalTime(90), GlobalTime::never());
+    sim.add_crash(Author(2), GlobalTime(95), GlobalTime::never());
+    assert_eq!(
+        sim.quorum_impossible_intervals(&configuration, GlobalTime(100)),
+        vec![
+            (GlobalTime(20), GlobalTime(40)),
+            (GlobalTime(95), GlobalTime(100))
+        ]
+    );
+}
+
+#[test]
+fn test_trace_entry() {
+    let event: Event<u8, u8, u8> = Event::DataSyncRequestEvent {
+        receiver: Author(1),
+        sender: Author(2),
+        request: 0,
+    };
+    assert_eq!(
+        event.trace_entry(GlobalTime(5), false),
+        TraceEntry {
+            clock: GlobalTime(5),
+            kind: "request",
+            target: Author(2),
+            peer: Some(Author(1)),
+            dropped: false,
+        }
+    );
+}
+
+fn commit(round: usize, block: u8, state: u8) -> Commit<u8, u8> {
+    Commit {
+        epoch: 0,
+        round: Round(round),
+        block,
+        state,
+    }
+}
+
+#[test]
+fn test_commit_checker() {
+    let mut checker = CommitChecker::new();
+    assert!(checker
+        .check(0, 0, &[commit(1, 1, 1), commit(2, 2, 2)])
+        .is_ok());
+    // Replicas may skip rounds and lag behind.
+    assert!(checker.check(1, 0, &[commit(2, 2, 2)]).is_ok());
+    assert!(checker
+        .check(1, 0, &[commit(2, 2, 2), commit(4, 4, 4)])
+        .is_ok());
+    assert!(checker
+        .check(0, 0, &[commit(1, 1, 1), commit(2, 2, 2), commit(4, 4, 4)])
+        .is_ok());
+}
+
+#[test]
+fn test_commit_checker_divergence() {
+    let mut checker = CommitChecker::new();
+    assert!(checker
+        .check(0, 0, &[commit(1, 1, 1), commit(2, 2, 2)])
+        .is_ok());
+    let divergence = checker
+        .check(1, 0, &[commit(1, 1, 1), commit(2, 2, 3)])
+        .unwrap_err();
+    assert_eq!((divergence.epoch, divergence.round), (0, Round(2)));
+    assert!(divergence
+        .description
+        .starts_with("Execution diverged at epoch 0 and Round(2): replica 1 committed block 2"));
+}
+
+#[test]
+fn test_commit_checker_instances() {
+    let mut checker = CommitChecker::new();
+    assert!(checker
+        .check(0, 0, &[commit(1, 1, 1), commit(2, 2, 2)])
+        .is_ok());
+    // Instances commit different blocks at the same rounds.
+    assert!(checker
+        .check(0, 1, &[commit(1, 5, 5), commit(2, 6, 6)])
+        .is_ok());
+    assert!(checker.check(1, 1, &[commit(1, 5, 5)]).is_ok());
+    let divergence = checker
+        .check(2, 1, &[commit(1, 5, 5), commit(2, 2, 2)])
+        .unwrap_err();
+    assert!(divergence
+        .description
+        .starts_with("Execution diverged at epoch 0 and Round(2) of instance 1: replica 2"));
+}
+
+type TestSimulator = Simulator<(), (), u8, u8, u8>;
+
+/// Arrival times of 100 notifications sent from replica 0 to replica 1, one per unit of time,
+/// in sending order, once the simulator is configured.
+fn arrival_times<F: FnOnce(&mut TestSimulator)>(configure: F) -> Vec<i64> {
+    let mut sim: TestSimulator =
+        Simulator::with_seed(1, 2, RandomDelay::new(10.0, 50.0), |_, _| (), |_, _, _| ());
+    configure(&mut sim);
+    for index in 0..100 {
+        let route = Route {
+            target: 1,
+            origin: 0,
+            broadcast: None,
+        };
+        let event = Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(1),
+            notification: index,
+        };
+        sim.schedule_network_event(GlobalTime(index as i64), route, event, 0);
+    }
+    let mut arrivals = vec![0; 100];
+    while let Some(ScheduledEvent { time, event, .. }) = sim.pending_events.pop() {
+        if let Some(Event::DataSyncNotifyEvent { notification, .. }) = event {
+            arrivals[notification as usize] = time.0;
+        }
+    }
+    arrivals
+}
+
+/// The largest amount of time by which a message arrives before a message sent earlier.
+fn max_overtaking(arrivals: &[i64]) -> i64 {
+    let mut latest = arrivals[0];
+    let mut result = 0;
+    for time in arrivals {
+        result = std::cmp::max(result, latest - time);
+        latest = std::cmp::max(latest, *time);
+    }
+    result
+}
+
+#[test]
+fn test_link_ordering() {
+    let ordered = |ordering| {
+        arrival_times(|sim| sim.set_link_ordering_between(Author(0), Author(1), ordering))
+    };
+    let unordered = arrival_times(|_| ());
+    assert!(max_overtaking(&unordered) > 5);
+    assert_eq!(ordered(LinkOrdering::Unordered), unordered);
+
+    let fifo = ordered(LinkOrdering::Fifo);
+    assert_eq!(max_overtaking(&fifo), 0);
+    // Messages are only held back, never sped up.
+    assert!(fifo
+        .iter()
+        .zip(&unordered)
+        .all(|(fifo, unordered)| fifo >= unordered));
+
+    let bounded = ordered(LinkOrdering::Bounded { window: 5 });
+    assert!(max_overtaking(&bounded) > 0);
+    assert!(max_overtaking(&bounded) <= 5);
+}
+
+/// Delays the notifications with even contents by more than the asynchrony bound.
+#[derive(Clone)]
+struct EvenDelayScheduler;
+
+impl Scheduler<u8, u8, u8> for EvenDelayScheduler {
+    fn extra_delay(&mut self, _: GlobalTime, _: GlobalTime, event: &Event<u8, u8, u8>) -> Duration {
+        match event {
+            Event::DataSyncNotifyEvent { notification, .. } if notification % 2 == 0 => 1000,
+            _ => 0,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Scheduler<u8, u8, u8>> {
+        Box::new(self.clone())
+    }
+}
+
+#[test]
+fn test_scheduler() {
+    // Extra delays are capped at the asynchrony bound, and do not change the delays drawn for
+    // the other messages.
+    let delayed = arrival_times(|sim| sim.set_scheduler(Box::new(EvenDelayScheduler), 30));
+    let undelayed = arrival_times(|_| ());
+    for (index, (delayed, undelayed)) in delayed.iter().zip(&undelayed).enumerate() {
+        let extra_delay = if index % 2 == 0 { 30 } else { 0 };
+        assert_eq!(*delayed, undelayed + extra_delay);
+    }
 }
@@ -0,0 +1,109 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::thread;
+
+/// Node 0 increments a counter; the other nodes catch up through data synchronization.
+struct CounterNode {
+    author: Author,
+    value: u64,
+}
+
+impl ConsensusNode<()> for CounterNode {
+    fn update_node(&mut self, clock: NodeTime, _context: &mut ()) -> NodeUpdateActions {
+        let mut actions = NodeUpdateActions::new();
+        if self.author == Author(0) {
+            self.value += 1;
+            actions.should_broadcast = true;
+            actions.next_scheduled_update = clock + 5;
+        }
+        actions
+    }
+}
+
+impl DataSyncNode<()> for CounterNode {
+    type Notification = u64;
+    type Request = ();
+    type Response = u64;
+
+    fn create_notification(&self) -> u64 {
+        self.value
+    }
+
+    fn create_request(&self) {}
+
+    fn handle_request(&self, _request: ()) -> u64 {
+        self.value
+    }
+
+    fn handle_notification(&mut self, notification: u64, _context: &mut ()) -> Option<()> {
+        if notification > self.value {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn handle_response(&mut self, response: u64, _context: &mut (), _clock: NodeTime) {
+        self.value = std::cmp::max(self.value, response);
+    }
+}
+
+#[test]
+fn test_local_transport() {
+    let handles: Vec<_> = LocalTransport::network(3)
+        .into_iter()
+        .enumerate()
+        .map(|(index, transport)| {
+            thread::spawn(move || {
+                let mut runner = NodeRunner::new(
+                    Author(index),
+                    3,
+                    |_, _| (),
+                    |author, _, _| CounterNode { author, value: 0 },
+                    transport,
+                );
+                runner.run_until(NodeTime(100));
+                runner.node().value
+            })
+        })
+        .collect();
+    let values: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert!(values[0] > 5);
+    assert!(values[1] > 0 && values[1] <= values[0]);
+    assert!(values[2] > 0 && values[2] <= values[0]);
+}
+
+#[test]
+fn test_tasks() {
+    let mut transports = LocalTransport::network(2);
+    // Node 1 never sends anything on its own.
+    let mut runner = NodeRunner::new(
+        Author(1),
+        2,
+        |_, _| (),
+        |author, _, _| CounterNode { author, value: 0 },
+        transports.pop().unwrap(),
+    );
+    let mut peer = transports.pop().unwrap();
+    let tasks = runner.task_queue();
+    let (sender, receiver) = mpsc::channel();
+    tasks
+        .send(Box::new(move |node: &mut CounterNode, _: &mut (), _| {
+            sender.send(node.value).unwrap();
+            let mut actions = NodeUpdateActions::new();
+            actions.should_send.push(Author(0));
+            actions
+        }))
+        .unwrap();
+    runner.run_until(NodeTime(20));
+    assert!(receiver.try_recv().is_ok());
+    assert_eq!(
+        peer.receive(Instant::now()),
+        Some((Author(1), Message::Notification(0)))
+    );
+}
@@ -0,0 +1,187 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Running all the nodes of a scenario in one process, each as its own task driven by an
+//! `AsyncNodeRunner`, and exchanging messages through a router task that delays and loses them
+//! like the network of the simulator. Unlike the simulator, which processes one event at a
+//! time, nodes run concurrently on the threads of a tokio runtime, so that messages, timers,
+//! and updates interleave in orders that the simulator never produces.
+
+use crate::driver::AsyncNodeRunner;
+use bft_simulator_runtime::{
+    base_types::{Author, NodeTime},
+    simulator::RandomDelay,
+    transport::Message,
+};
+use futures_util::{sink::Sink, stream::Stream};
+use librabft_simulator::{
+    data_sync::{DataSyncNotification, DataSyncRequest, DataSyncResponse},
+    follower::Participant,
+    scenario::Scenario,
+    simulated_context::SimulatedContext,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::{sleep, Duration},
+};
+
+#[cfg(test)]
+#[path = "unit_tests/actors_tests.rs"]
+mod actors_tests;
+
+type NetworkMessage = Message<DataSyncNotification, DataSyncRequest, DataSyncResponse>;
+
+/// A message in transit: its sender, its receiver, and its content.
+type Packet = (Author, Author, NetworkMessage);
+
+/// Messages delivered to a node, with their sender.
+pub struct Inbox {
+    receiver: UnboundedReceiver<(Author, NetworkMessage)>,
+}
+
+impl Stream for Inbox {
+    type Item = (Author, NetworkMessage);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// The router stopped, so messages cannot be sent any more.
+#[derive(Debug)]
+pub struct NetworkClosed;
+
+/// Messages sent by a node, with their receiver, to the router.
+pub struct Outbox {
+    author: Author,
+    router: UnboundedSender<Packet>,
+}
+
+impl Sink<(Author, NetworkMessage)> for Outbox {
+    type Error = NetworkClosed;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), NetworkClosed>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        (receiver, message): (Author, NetworkMessage),
+    ) -> Result<(), NetworkClosed> {
+        self.router
+            .send((self.author, receiver, message))
+            .map_err(|_| NetworkClosed)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), NetworkClosed>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), NetworkClosed>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Create the inbox and the outbox of the nodes `0..num_nodes`, and spawn the router task on
+/// the current tokio runtime. The router delivers each message after a delay drawn from
+/// `delay`, in milliseconds, unless it is lost, which happens with probability `loss_rate`.
+/// Messages may thus be reordered. The router stops when all the outboxes are dropped.
+pub fn network(
+    num_nodes: usize,
+    delay: RandomDelay,
+    loss_rate: f64,
+    seed: u64,
+) -> Vec<(Inbox, Outbox)> {
+    assert!((0.0..1.0).contains(&loss_rate), "Invalid loss rate");
+    let (router, packets) = unbounded_channel();
+    let (inboxes, receivers): (Vec<_>, Vec<_>) =
+        (0..num_nodes).map(|_| unbounded_channel()).unzip();
+    tokio::spawn(route(packets, inboxes, delay, loss_rate, seed));
+    receivers
+        .into_iter()
+        .enumerate()
+        .map(|(index, receiver)| {
+            let outbox = Outbox {
+                author: Author(index),
+                router: router.clone(),
+            };
+            (Inbox { receiver }, outbox)
+        })
+        .collect()
+}
+
+async fn route(
+    mut packets: UnboundedReceiver<Packet>,
+    inboxes: Vec<UnboundedSender<(Author, NetworkMessage)>>,
+    delay: RandomDelay,
+    loss_rate: f64,
+    seed: u64,
+) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    while let Some((sender, receiver, message)) = packets.recv().await {
+        let inbox = match inboxes.get(receiver.0) {
+            Some(inbox) => inbox.clone(),
+            None => {
+                warn!("{:?} Dropping message to unknown {:?}", sender, receiver);
+                continue;
+            }
+        };
+        if loss_rate > 0.0 && rng.gen::<f64>() < loss_rate {
+            continue;
+        }
+        let delay = Duration::from_millis(delay.sample(&mut rng).max(0) as u64);
+        tokio::spawn(async move {
+            sleep(delay).await;
+            // The receiver may have stopped already.
+            let _ = inbox.send((sender, message));
+        });
+    }
+}
+
+/// A node of a scenario run by `run_scenario`.
+pub type ActorNode = AsyncNodeRunner<Participant, SimulatedContext, Inbox, Outbox>;
+
+/// Run the nodes and followers of a scenario as concurrent tasks until the given node time,
+/// with the network delays and losses of the scenario taken as milliseconds. Node times are
+/// milliseconds as well. Return the nodes at the end of the run, by author.
+pub async fn run_scenario(scenario: &Scenario, max_clock: NodeTime) -> Vec<ActorNode> {
+    let num_nodes = scenario.nodes + scenario.followers;
+    let delay = RandomDelay::new(scenario.network.mean, scenario.network.variance);
+    let endpoints = network(
+        num_nodes,
+        delay,
+        scenario.network.loss_rate,
+        scenario.seed.unwrap_or(0),
+    );
+    let tasks: Vec<_> = endpoints
+        .into_iter()
+        .enumerate()
+        .map(|(index, (inbox, outbox))| {
+            let scenario = scenario.clone();
+            tokio::spawn(async move {
+                let mut node = AsyncNodeRunner::new(
+                    Author(index),
+                    num_nodes,
+                    |author, _| scenario.make_context(author),
+                    |author, context, clock| scenario.make_node(author, context, clock),
+                    inbox,
+                    outbox,
+                );
+                if node.run_until(max_clock).await.is_err() {
+                    warn!("{:?} Stopped early: the router stopped", Author(index));
+                }
+                node
+            })
+        })
+        .collect();
+    let mut nodes = Vec::new();
+    for task in tasks {
+        nodes.push(task.await.expect("Node tasks should not panic"));
+    }
+    nodes
+}
@@ -0,0 +1,188 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Running a node as a task of an async application. An `AsyncNodeRunner` does the same as a
+//! `NodeRunner` without blocking a thread: scheduled updates become timers, messages are read
+//! from a stream, and the notifications and requests that the node should send are written to a
+//! sink. Node times are milliseconds since the startup of the runner.
+
+use bft_simulator_runtime::{
+    base_types::{Author, NodeTime},
+    transport::Message,
+    ConsensusNode, DataSyncNode, NodeUpdateActions,
+};
+use futures_util::{
+    sink::{Sink, SinkExt},
+    stream::{Stream, StreamExt},
+};
+use std::collections::BTreeSet;
+use tokio::time::{timeout_at, Duration, Instant};
+
+#[cfg(test)]
+#[path = "unit_tests/driver_tests.rs"]
+mod driver_tests;
+
+/// Drive a node with the messages of a stream and with timers, and write the messages of the
+/// node to a sink. Incoming items carry their sender and outgoing items their receiver.
+pub struct AsyncNodeRunner<Node, Context, Incoming, Outgoing> {
+    author: Author,
+    num_nodes: usize,
+    node: Node,
+    context: Context,
+    incoming: Incoming,
+    outgoing: Outgoing,
+    startup_time: Instant,
+    next_scheduled_update: NodeTime,
+}
+
+impl<Node, Context, Notification, Request, Response, Incoming, Outgoing>
+    AsyncNodeRunner<Node, Context, Incoming, Outgoing>
+where
+    Node: ConsensusNode<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>,
+    Notification: Clone,
+    Request: Clone,
+    Incoming: Stream<Item = (Author, Message<Notification, Request, Response>)> + Unpin,
+    Outgoing: Sink<(Author, Message<Notification, Request, Response>)> + Unpin,
+{
+    pub fn new<F, G>(
+        author: Author,
+        num_nodes: usize,
+        context_factory: F,
+        node_factory: G,
+        incoming: Incoming,
+        outgoing: Outgoing,
+    ) -> Self
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        let context = context_factory(author, num_nodes);
+        let node = node_factory(author, &context, NodeTime(0));
+        AsyncNodeRunner {
+            author,
+            num_nodes,
+            node,
+            context,
+            incoming,
+            outgoing,
+            startup_time: Instant::now(),
+            next_scheduled_update: NodeTime(0),
+        }
+    }
+
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Milliseconds since the startup of the runner.
+    pub fn clock(&self) -> NodeTime {
+        NodeTime(self.startup_time.elapsed().as_millis() as i64)
+    }
+
+    fn instant(&self, clock: NodeTime) -> Instant {
+        self.startup_time + Duration::from_millis(clock.0 as u64)
+    }
+
+    /// Process messages and scheduled updates until the given node time, or until the stream
+    /// of incoming messages ends. Stop at the first error of the sink.
+    pub async fn run_until(&mut self, max_clock: NodeTime) -> Result<(), Outgoing::Error> {
+        loop {
+            let clock = self.clock();
+            if clock >= max_clock {
+                return Ok(());
+            }
+            if clock >= self.next_scheduled_update {
+                let actions = self.node.update_node(clock, &mut self.context);
+                self.process_node_actions(actions).await?;
+                continue;
+            }
+            let deadline = self.instant(std::cmp::min(self.next_scheduled_update, max_clock));
+            match timeout_at(deadline, self.incoming.next()).await {
+                Ok(Some((peer, message))) => self.handle_message(peer, message).await?,
+                Ok(None) => {
+                    debug!("{:?} Incoming messages ended", self.author);
+                    return Ok(());
+                }
+                // Time for the next scheduled update.
+                Err(_) => (),
+            }
+        }
+    }
+
+    async fn handle_message(
+        &mut self,
+        peer: Author,
+        message: Message<Notification, Request, Response>,
+    ) -> Result<(), Outgoing::Error> {
+        trace!("{:?} Received message from {:?}", self.author, peer);
+        match message {
+            Message::Notification(notification) => {
+                if let Some(request) = self
+                    .node
+                    .handle_notification(notification, &mut self.context)
+                {
+                    self.outgoing
+                        .send((peer, Message::Request(request)))
+                        .await?;
+                }
+            }
+            Message::Request(request) => {
+                let response = self.node.handle_request(request);
+                // Serving a request does not change the node.
+                return self
+                    .outgoing
+                    .send((peer, Message::Response(response)))
+                    .await;
+            }
+            Message::Response(response) => {
+                let clock = self.clock();
+                self.node
+                    .handle_response(response, &mut self.context, clock);
+            }
+        }
+        let clock = self.clock();
+        let actions = self.node.update_node(clock, &mut self.context);
+        self.process_node_actions(actions).await
+    }
+
+    async fn process_node_actions(
+        &mut self,
+        actions: NodeUpdateActions,
+    ) -> Result<(), Outgoing::Error> {
+        debug!("{:?} Processing node actions: {:?}", self.author, actions);
+        // Make sure that time moves forward between two scheduled updates.
+        self.next_scheduled_update = std::cmp::max(actions.next_scheduled_update, self.clock() + 1);
+        let local_author = self.author;
+        let others: Vec<_> = (0..self.num_nodes)
+            .map(Author)
+            .filter(|author| *author != local_author)
+            .collect();
+        let mut receivers: BTreeSet<_> = actions.should_send.into_iter().collect();
+        if actions.should_broadcast {
+            receivers.extend(others.iter().cloned());
+        }
+        if !receivers.is_empty() {
+            let notification = self.node.create_notification();
+            for receiver in receivers {
+                // Messages are flushed once, after the whole batch.
+                self.outgoing
+                    .feed((receiver, Message::Notification(notification.clone())))
+                    .await?;
+            }
+        }
+        if actions.should_query_all {
+            let request = self.node.create_request();
+            for sender in others {
+                self.outgoing
+                    .feed((sender, Message::Request(request.clone())))
+                    .await?;
+            }
+        }
+        self.outgoing.flush().await
+    }
+}
@@ -0,0 +1,365 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! gRPC service to inspect and control a running node, as specified in `proto/control.proto`.
+//! Like the messages of `librabft_simulator::proto`, the service is written by hand so that
+//! building the crate does not require `protoc`. Queries are submitted to the `NodeRunner` of
+//! the node as tasks, so they never race with the processing of network messages.
+
+use bft_simulator_runtime::{base_types::NodeTime, transport::NodeTask, NodeUpdateActions};
+use librabft_simulator::{
+    base_types::{BlockHash, EpochId, QuorumCertificateHash},
+    conformance::NodeVariables,
+    follower::Participant,
+    proto::Proto,
+    record::Record,
+    simulated_context::SimulatedContext,
+};
+use prost::Message;
+use std::{convert::Infallible, future::Future, marker::PhantomData, net::SocketAddr, sync::mpsc};
+use tokio::sync::oneshot;
+use tonic::{
+    body::BoxBody,
+    client::Grpc as GrpcClient,
+    codec::ProstCodec,
+    codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError},
+    server::{Grpc, NamedService, UnaryService},
+    transport::{Channel, Endpoint},
+    Status,
+};
+
+#[cfg(test)]
+#[path = "unit_tests/grpc_tests.rs"]
+mod grpc_tests;
+
+/// Protobuf messages of `proto/control.proto`.
+pub mod messages {
+    use librabft_simulator::proto::messages::{Command, Record};
+    use prost::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct GetStatusRequest {}
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct NodeStatus {
+        #[prost(uint64, tag = "1")]
+        pub author: u64,
+        #[prost(uint64, tag = "2")]
+        pub epoch: u64,
+        #[prost(uint64, tag = "3")]
+        pub current_round: u64,
+        #[prost(uint64, tag = "4")]
+        pub locked_round: u64,
+        #[prost(uint64, tag = "5")]
+        pub latest_voted_round: u64,
+        #[prost(uint64, tag = "6")]
+        pub committed_round: u64,
+        #[prost(uint64, tag = "7")]
+        pub highest_quorum_certificate_hash: u64,
+        #[prost(uint64, tag = "8")]
+        pub committed_commands: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct GetRecordRequest {
+        #[prost(uint64, tag = "1")]
+        pub hash: u64,
+        #[prost(uint64, optional, tag = "2")]
+        pub epoch_id: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct GetRecordResponse {
+        #[prost(message, optional, tag = "1")]
+        pub record: Option<Record>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct InjectTransactionRequest {}
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct InjectTransactionResponse {
+        #[prost(message, optional, tag = "1")]
+        pub command: Option<Command>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TriggerQueryAllRequest {}
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TriggerQueryAllResponse {}
+}
+
+const SERVICE_NAME: &str = "librabft.control.NodeControl";
+const GET_STATUS: &str = "/librabft.control.NodeControl/GetStatus";
+const GET_RECORD: &str = "/librabft.control.NodeControl/GetRecord";
+const INJECT_TRANSACTION: &str = "/librabft.control.NodeControl/InjectTransaction";
+const TRIGGER_QUERY_ALL: &str = "/librabft.control.NodeControl/TriggerQueryAll";
+
+/// Queue of tasks of a running node, see `NodeRunner::task_queue`.
+pub type TaskQueue = mpsc::Sender<NodeTask<Participant, SimulatedContext>>;
+
+#[derive(Clone)]
+pub struct NodeControlServer {
+    tasks: TaskQueue,
+}
+
+impl NodeControlServer {
+    pub fn new(tasks: TaskQueue) -> Self {
+        NodeControlServer { tasks }
+    }
+
+    /// Run `query` on the node and wait for its result.
+    fn submit<R, F>(&self, query: F) -> impl Future<Output = Result<R, Status>>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Participant, &mut SimulatedContext) -> (R, NodeUpdateActions)
+            + Send
+            + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let task: NodeTask<Participant, SimulatedContext> = Box::new(move |node, context, _| {
+            let (result, actions) = query(node, context);
+            // The client may have given up already.
+            let _ = sender.send(result);
+            actions
+        });
+        let submitted = self.tasks.send(task).is_ok();
+        async move {
+            if !submitted {
+                return Err(Status::unavailable("The node has stopped."));
+            }
+            receiver
+                .await
+                .map_err(|_| Status::unavailable("The node has stopped."))
+        }
+    }
+
+    fn get_status(&self) -> BoxFuture<tonic::Response<messages::NodeStatus>, Status> {
+        let status = self.submit(|node, context| {
+            let state = node.node_state();
+            let variables = NodeVariables::of(state);
+            let status = messages::NodeStatus {
+                author: state.local_author().0 as u64,
+                epoch: variables.epoch.0 as u64,
+                current_round: variables.current_round.0 as u64,
+                locked_round: variables.locked_round.0 as u64,
+                latest_voted_round: variables.latest_voted_round.0 as u64,
+                committed_round: variables.committed_round.0 as u64,
+                highest_quorum_certificate_hash: state
+                    .record_store()
+                    .highest_quorum_certificate_hash()
+                    .0,
+                committed_commands: context.committed_history().len() as u64,
+            };
+            (status, NodeUpdateActions::new())
+        });
+        Box::pin(async move { Ok(tonic::Response::new(status.await?)) })
+    }
+
+    fn get_record(
+        &self,
+        request: messages::GetRecordRequest,
+    ) -> BoxFuture<tonic::Response<messages::GetRecordResponse>, Status> {
+        let record = self.submit(move |node, _| {
+            let state = node.node_state();
+            let store = match request.epoch_id {
+                Some(epoch_id) => state.record_store_at(EpochId(epoch_id as usize)),
+                None => Some(state.record_store()),
+            };
+            let record = store.and_then(|store| match store.block(BlockHash(request.hash)) {
+                Some(block) => Some(Record::Block(block.clone())),
+                None => store
+                    .quorum_certificate(QuorumCertificateHash(request.hash))
+                    .map(|qc| Record::QuorumCertificate(qc.clone())),
+            });
+            (record, NodeUpdateActions::new())
+        });
+        Box::pin(async move {
+            match record.await? {
+                Some(record) => Ok(tonic::Response::new(messages::GetRecordResponse {
+                    record: Some(record.to_proto()),
+                })),
+                None => Err(Status::not_found("Unknown record.")),
+            }
+        })
+    }
+
+    fn inject_transaction(
+        &self,
+    ) -> BoxFuture<tonic::Response<messages::InjectTransactionResponse>, Status> {
+        let command = self.submit(|_, context| {
+            let command = context.submit();
+            // Let the node use the command right away.
+            let mut actions = NodeUpdateActions::new();
+            actions.next_scheduled_update = NodeTime(0);
+            (command, actions)
+        });
+        Box::pin(async move {
+            Ok(tonic::Response::new(messages::InjectTransactionResponse {
+                command: Some(command.await?.to_proto()),
+            }))
+        })
+    }
+
+    fn trigger_query_all(
+        &self,
+    ) -> BoxFuture<tonic::Response<messages::TriggerQueryAllResponse>, Status> {
+        let done = self.submit(|_, _| {
+            let mut actions = NodeUpdateActions::new();
+            actions.should_query_all = true;
+            ((), actions)
+        });
+        Box::pin(async move {
+            done.await?;
+            Ok(tonic::Response::new(messages::TriggerQueryAllResponse {}))
+        })
+    }
+}
+
+/// Adapt a handler to `UnaryService`.
+struct Unary<F, Request, Response>(F, PhantomData<fn(Request) -> Response>);
+
+impl<F, Request, Response> UnaryService<Request> for Unary<F, Request, Response>
+where
+    F: FnMut(Request) -> BoxFuture<tonic::Response<Response>, Status>,
+{
+    type Response = Response;
+    type Future = BoxFuture<tonic::Response<Response>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Request>) -> Self::Future {
+        (self.0)(request.into_inner())
+    }
+}
+
+fn unary<B, Request, Response, F>(
+    request: http::Request<B>,
+    handler: F,
+) -> BoxFuture<http::Response<BoxBody>, Infallible>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Request: Message + Default + Send + 'static,
+    Response: Message + Send + 'static,
+    F: FnMut(Request) -> BoxFuture<tonic::Response<Response>, Status> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::<Response, Request>::default());
+        Ok(grpc.unary(Unary(handler, PhantomData), request).await)
+    })
+}
+
+impl<B> Service<http::Request<B>> for NodeControlServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _context: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let server = self.clone();
+        match request.uri().path() {
+            GET_STATUS => unary(request, move |_: messages::GetStatusRequest| {
+                server.get_status()
+            }),
+            GET_RECORD => unary(request, move |request| server.get_record(request)),
+            INJECT_TRANSACTION => unary(request, move |_: messages::InjectTransactionRequest| {
+                server.inject_transaction()
+            }),
+            TRIGGER_QUERY_ALL => unary(request, move |_: messages::TriggerQueryAllRequest| {
+                server.trigger_query_all()
+            }),
+            _ => Box::pin(async { Ok(Status::unimplemented("Unknown method.").into_http()) }),
+        }
+    }
+}
+
+impl NamedService for NodeControlServer {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+/// Serve the control service of a node.
+pub async fn serve(address: SocketAddr, tasks: TaskQueue) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(NodeControlServer::new(tasks))
+        .serve(address)
+        .await
+}
+
+/// Client of the control service, for external orchestration.
+pub struct NodeControlClient {
+    grpc: GrpcClient<Channel>,
+}
+
+impl NodeControlClient {
+    /// Connect to a URI such as `http://127.0.0.1:9000`.
+    pub async fn connect(uri: String) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::new(uri)?.connect().await?;
+        Ok(NodeControlClient {
+            grpc: GrpcClient::new(channel),
+        })
+    }
+
+    async fn call<Request, Response>(
+        &mut self,
+        path: &'static str,
+        request: Request,
+    ) -> Result<Response, Status>
+    where
+        Request: Message + Send + 'static,
+        Response: Message + Default + Send + 'static,
+    {
+        self.grpc
+            .ready()
+            .await
+            .map_err(|error| Status::unavailable(error.to_string()))?;
+        let response = self
+            .grpc
+            .unary(
+                tonic::Request::new(request),
+                http::uri::PathAndQuery::from_static(path),
+                ProstCodec::<Request, Response>::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn get_status(&mut self) -> Result<messages::NodeStatus, Status> {
+        self.call(GET_STATUS, messages::GetStatusRequest {}).await
+    }
+
+    pub async fn get_record(&mut self, hash: u64, epoch_id: Option<u64>) -> Result<Record, Status> {
+        let response: messages::GetRecordResponse = self
+            .call(GET_RECORD, messages::GetRecordRequest { hash, epoch_id })
+            .await?;
+        let record = response
+            .record
+            .ok_or_else(|| Status::internal("Missing record."))?;
+        Record::from_proto(record).map_err(|error| Status::internal(error.to_string()))
+    }
+
+    pub async fn inject_transaction(
+        &mut self,
+    ) -> Result<librabft_simulator::base_types::Command, Status> {
+        let response: messages::InjectTransactionResponse = self
+            .call(INJECT_TRANSACTION, messages::InjectTransactionRequest {})
+            .await?;
+        let command = response
+            .command
+            .ok_or_else(|| Status::internal("Missing command."))?;
+        Proto::from_proto(command).map_err(|error| Status::internal(error.to_string()))
+    }
+
+    pub async fn trigger_query_all(&mut self) -> Result<(), Status> {
+        let _: messages::TriggerQueryAllResponse = self
+            .call(TRIGGER_QUERY_ALL, messages::TriggerQueryAllRequest {})
+            .await?;
+        Ok(())
+    }
+}
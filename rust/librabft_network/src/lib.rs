@@ -0,0 +1,15 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Networking for running LibraBFT nodes as separate processes instead of simulating them.
+
+#[macro_use]
+extern crate log;
+
+#[cfg(feature = "driver")]
+pub mod actors;
+#[cfg(feature = "driver")]
+pub mod driver;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod tcp;
@@ -0,0 +1,118 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use bft_simulator_runtime::{
+    base_types::{Author, NodeTime},
+    transport::NodeRunner,
+    ActiveRound,
+};
+use clap::{App, Arg};
+use librabft_network::tcp::TcpTransport;
+use librabft_simulator::scenario::Scenario;
+use serde::Serialize;
+use std::net::SocketAddr;
+
+/// Summary printed when the node stops.
+#[derive(Serialize)]
+struct NodeReport {
+    author: usize,
+    committed_commands: usize,
+    current_round: usize,
+}
+
+fn main() {
+    env_logger::init();
+    let app = App::new("librabft-node")
+        .about("Run one LibraBFT node as a separate process, connected to its peers over TCP")
+        .arg(
+            Arg::with_name("scenario")
+                .help("TOML file describing the nodes and the pacemaker parameters")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("author")
+                .long("author")
+                .help("Index of the local node")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("peers")
+                .long("peers")
+                .help("Comma-separated addresses of all the nodes, including the local one")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .help("Milliseconds to run before exiting")
+                .default_value("10000"),
+        );
+    #[cfg(feature = "grpc")]
+    let app = app.arg(
+        Arg::with_name("grpc")
+            .long("grpc")
+            .help("Address to serve the gRPC control service on")
+            .takes_value(true),
+    );
+    let matches = app.get_matches();
+
+    let mut scenario =
+        Scenario::load(matches.value_of("scenario").unwrap()).expect("Failed to load scenario");
+    scenario.deploy();
+    let author = Author(parse(matches.value_of("author").unwrap(), "author"));
+    let peers: Vec<SocketAddr> = matches
+        .value_of("peers")
+        .unwrap()
+        .split(',')
+        .map(|address| {
+            address
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid address: {}", address))
+        })
+        .collect();
+    assert_eq!(
+        peers.len(),
+        scenario.nodes + scenario.followers,
+        "There must be one address per node of the scenario"
+    );
+    let duration = parse(matches.value_of("duration").unwrap(), "duration");
+
+    let transport = TcpTransport::bind(author, &peers).expect("Failed to listen");
+    let mut runner = NodeRunner::new(
+        author,
+        peers.len(),
+        |author, _| scenario.make_context(author),
+        |author, context, clock| scenario.make_node(author, context, clock),
+        transport,
+    );
+    #[cfg(feature = "grpc")]
+    {
+        if let Some(address) = matches.value_of("grpc") {
+            let address = address
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid address: {}", address));
+            let tasks = runner.task_queue();
+            std::thread::spawn(move || {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to start runtime");
+                runtime
+                    .block_on(librabft_network::grpc::serve(address, tasks))
+                    .expect("Failed to run the gRPC service");
+            });
+        }
+    }
+    runner.run_until(NodeTime(duration as i64));
+    let report = NodeReport {
+        author: author.0,
+        committed_commands: runner.context().committed_history().len(),
+        current_round: runner.node().active_round().0,
+    };
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
+
+fn parse(value: &str, name: &str) -> usize {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value for {}: {}", name, value))
+}
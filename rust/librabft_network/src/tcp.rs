@@ -0,0 +1,177 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transport over TCP. Each message is sent as a frame made of its length, as a 4-byte
+//! big-endian integer, followed by its protobuf encoding. Senders are not authenticated, so
+//! this is only meant for trusted networks.
+
+use bft_simulator_runtime::{
+    base_types::Author,
+    transport::{Message, ProcessTransport},
+};
+use failure::Error;
+use librabft_simulator::{
+    data_sync::{DataSyncNotification, DataSyncRequest, DataSyncResponse, NetworkEnvelope},
+    proto::Proto,
+};
+use std::{net::SocketAddr, sync::mpsc, time::Instant};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::{timeout, Duration},
+};
+
+#[cfg(test)]
+#[path = "unit_tests/tcp_tests.rs"]
+mod tcp_tests;
+
+/// Larger frames are rejected and close the connection.
+const MAX_FRAME_SIZE: u32 = 64 << 20;
+/// Messages to an unreachable peer are dropped after this delay.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct TcpTransport {
+    author: Author,
+    local_address: SocketAddr,
+    /// Queues of frames to send, indexed by the receiver.
+    peers: Vec<UnboundedSender<Vec<u8>>>,
+    inbox: mpsc::Receiver<NetworkEnvelope>,
+    /// Runs the connections until the transport is dropped.
+    _runtime: Runtime,
+}
+
+impl TcpTransport {
+    /// Listen on the address of `author` in `addresses`, and send messages to the other ones.
+    pub fn bind(author: Author, addresses: &[SocketAddr]) -> Result<Self, Error> {
+        let address = addresses
+            .get(author.0)
+            .ok_or_else(|| failure::format_err!("No address for {:?}", author))?;
+        let listener = std::net::TcpListener::bind(address)?;
+        Self::new(author, listener, addresses)
+    }
+
+    /// Same as `bind` with an existing listener.
+    pub fn new(
+        author: Author,
+        listener: std::net::TcpListener,
+        addresses: &[SocketAddr],
+    ) -> Result<Self, Error> {
+        let runtime = Runtime::new()?;
+        let local_address = listener.local_addr()?;
+        listener.set_nonblocking(true)?;
+        let (inbox_sender, inbox) = mpsc::channel();
+        let listener = {
+            let _guard = runtime.enter();
+            TcpListener::from_std(listener)?
+        };
+        runtime.spawn(accept_connections(listener, inbox_sender));
+        let peers = addresses
+            .iter()
+            .map(|address| {
+                let (sender, receiver) = unbounded_channel();
+                runtime.spawn(write_frames(*address, receiver));
+                sender
+            })
+            .collect();
+        Ok(TcpTransport {
+            author,
+            local_address,
+            peers,
+            inbox,
+            _runtime: runtime,
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+}
+
+impl ProcessTransport<DataSyncNotification, DataSyncRequest, DataSyncResponse> for TcpTransport {
+    fn send(
+        &mut self,
+        receiver: Author,
+        message: Message<DataSyncNotification, DataSyncRequest, DataSyncResponse>,
+    ) {
+        let peer = match self.peers.get(receiver.0) {
+            Some(peer) => peer,
+            None => {
+                warn!(
+                    "{:?} Dropping message to unknown {:?}",
+                    self.author, receiver
+                );
+                return;
+            }
+        };
+        let bytes = (self.author, message).to_proto_bytes();
+        let mut frame = Vec::with_capacity(4 + bytes.len());
+        frame.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        frame.extend(bytes);
+        // The queue is only closed when the runtime shuts down.
+        let _ = peer.send(frame);
+    }
+
+    fn receive(&mut self, deadline: Instant) -> Option<NetworkEnvelope> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.inbox.recv_timeout(timeout).ok()
+    }
+}
+
+async fn accept_connections(listener: TcpListener, inbox: mpsc::Sender<NetworkEnvelope>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(read_frames(stream, inbox.clone()));
+            }
+            Err(error) => warn!("Failed to accept connection: {}", error),
+        }
+    }
+}
+
+async fn read_frames(mut stream: TcpStream, inbox: mpsc::Sender<NetworkEnvelope>) {
+    while let Ok(len) = stream.read_u32().await {
+        if len > MAX_FRAME_SIZE {
+            warn!("Closing connection after a frame of {} bytes", len);
+            return;
+        }
+        let mut bytes = vec![0; len as usize];
+        if stream.read_exact(&mut bytes).await.is_err() {
+            return;
+        }
+        match NetworkEnvelope::from_proto_bytes(&bytes) {
+            Ok(envelope) => {
+                if inbox.send(envelope).is_err() {
+                    // The transport was dropped.
+                    return;
+                }
+            }
+            Err(error) => warn!("Ignoring invalid message: {}", error),
+        }
+    }
+}
+
+async fn write_frames(address: SocketAddr, mut queue: UnboundedReceiver<Vec<u8>>) {
+    let mut stream = None;
+    while let Some(frame) = queue.recv().await {
+        if stream.is_none() {
+            stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+                Ok(Ok(stream)) => {
+                    let _ = stream.set_nodelay(true);
+                    Some(stream)
+                }
+                _ => {
+                    debug!("Dropping message to unreachable peer {}", address);
+                    continue;
+                }
+            };
+        }
+        if let Some(connection) = stream.as_mut() {
+            if connection.write_all(&frame).await.is_err() {
+                // Reconnect for the next message.
+                stream = None;
+            }
+        }
+    }
+}
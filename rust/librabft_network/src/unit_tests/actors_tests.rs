@@ -0,0 +1,57 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use librabft_simulator::{
+    data_sync::NetworkEnvelope,
+    proto::{messages, Proto},
+};
+use std::time::Instant;
+use tokio::runtime::Runtime;
+
+#[test]
+fn test_router() {
+    let runtime = Runtime::new().unwrap();
+    runtime.block_on(async {
+        let mut endpoints = network(2, RandomDelay::new(50.0, 1.0), 0.0, 0);
+        let (mut inbox, _) = endpoints.pop().unwrap();
+        let (_, mut outbox) = endpoints.pop().unwrap();
+        let request = DataSyncRequest::from_proto(messages::DataSyncRequest {
+            current_epoch: 1,
+            summary: Some(messages::RecordSummary::default()),
+            needs_snapshot: false,
+        })
+        .unwrap();
+        let message = Message::Request(request);
+        let start = Instant::now();
+        // Unknown receivers are ignored.
+        outbox.send((Author(7), message.clone())).await.unwrap();
+        outbox.send((Author(1), message.clone())).await.unwrap();
+        let envelope: NetworkEnvelope = inbox.next().await.unwrap();
+        assert_eq!(envelope, (Author(0), message));
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    });
+}
+
+#[test]
+fn test_concurrent_nodes() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1\nseed = 5").unwrap();
+    let runtime = Runtime::new().unwrap();
+    let nodes = runtime.block_on(run_scenario(&scenario, NodeTime(2000)));
+    assert_eq!(nodes.len(), 4);
+    let histories: Vec<_> = nodes
+        .iter()
+        .map(|node| node.context().committed_history())
+        .collect();
+    for history in &histories {
+        assert!(!history.is_empty());
+        // Honest nodes commit the same commands in the same order, however the messages and
+        // the timers of the nodes interleave.
+        let longest = histories
+            .iter()
+            .max_by_key(|history| history.len())
+            .unwrap();
+        assert_eq!(history[..], longest[..history.len()]);
+    }
+}
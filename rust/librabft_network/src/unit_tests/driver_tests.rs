@@ -0,0 +1,100 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use librabft_simulator::{
+    data_sync::{DataSyncNotification, DataSyncRequest, DataSyncResponse},
+    scenario::Scenario,
+};
+use tokio::runtime::Runtime;
+
+type Envelope = (
+    Author,
+    Message<DataSyncNotification, DataSyncRequest, DataSyncResponse>,
+);
+
+/// In-process network: the incoming stream and the outgoing sink of each node, with tasks
+/// routing the messages written to each sink to the stream of their receiver.
+fn local_network(
+    runtime: &Runtime,
+    num_nodes: usize,
+) -> Vec<(UnboundedReceiver<Envelope>, UnboundedSender<Envelope>)> {
+    let (inbox_senders, inboxes): (Vec<_>, Vec<_>) = (0..num_nodes).map(|_| unbounded()).unzip();
+    inboxes
+        .into_iter()
+        .enumerate()
+        .map(|(index, inbox)| {
+            let (outbox, mut queue) = unbounded::<Envelope>();
+            let peers = inbox_senders.clone();
+            runtime.spawn(async move {
+                while let Some((receiver, message)) = queue.next().await {
+                    if let Some(peer) = peers.get(receiver.0) {
+                        let _ = peer.unbounded_send((Author(index), message));
+                    }
+                }
+            });
+            (inbox, outbox)
+        })
+        .collect()
+}
+
+#[test]
+fn test_async_nodes() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1").unwrap();
+    let runtime = Runtime::new().unwrap();
+    let handles: Vec<_> = local_network(&runtime, scenario.nodes)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (incoming, outgoing))| {
+            let scenario = scenario.clone();
+            runtime.spawn(async move {
+                let mut runner = AsyncNodeRunner::new(
+                    Author(index),
+                    scenario.nodes,
+                    |author, _| scenario.make_context(author),
+                    |author, context, clock| scenario.make_node(author, context, clock),
+                    incoming,
+                    outgoing,
+                );
+                runner.run_until(NodeTime(2000)).await.unwrap();
+                runner.context().committed_history().len()
+            })
+        })
+        .collect();
+    runtime.block_on(async {
+        for handle in handles {
+            assert!(handle.await.unwrap() > 0);
+        }
+    });
+}
+
+#[test]
+fn test_closed_streams() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1").unwrap();
+    let runtime = Runtime::new().unwrap();
+    let runner = |incoming, outgoing| {
+        AsyncNodeRunner::new(
+            Author(0),
+            scenario.nodes,
+            |author, _| scenario.make_context(author),
+            |author, context, clock| scenario.make_node(author, context, clock),
+            incoming,
+            outgoing,
+        )
+    };
+    runtime.block_on(async {
+        // The runner stops when no more messages can arrive..
+        let (_, incoming) = unbounded::<Envelope>();
+        let (outgoing, _queue) = unbounded::<Envelope>();
+        let mut node = runner(incoming, outgoing);
+        assert!(node.run_until(NodeTime(2000)).await.is_ok());
+        assert!(node.clock() < NodeTime(2000));
+
+        // .. and when messages cannot be sent any more.
+        let (_sender, incoming) = unbounded::<Envelope>();
+        let (outgoing, _) = unbounded::<Envelope>();
+        let mut node = runner(incoming, outgoing);
+        assert!(node.run_until(NodeTime(2000)).await.is_err());
+    });
+}
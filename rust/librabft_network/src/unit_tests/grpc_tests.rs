@@ -0,0 +1,82 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::{
+    base_types::Author,
+    transport::{LocalTransport, NodeRunner},
+};
+use librabft_simulator::scenario::Scenario;
+use std::{thread, time::Duration};
+
+#[test]
+fn test_node_control() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1").unwrap();
+    let mut tasks = None;
+    let mut handles = Vec::new();
+    for (index, transport) in LocalTransport::network(scenario.nodes)
+        .into_iter()
+        .enumerate()
+    {
+        let scenario = scenario.clone();
+        let (sender, receiver) = mpsc::channel();
+        handles.push(thread::spawn(move || {
+            let mut runner = NodeRunner::new(
+                Author(index),
+                scenario.nodes,
+                |author, _| scenario.make_context(author),
+                |author, context, clock| scenario.make_node(author, context, clock),
+                transport,
+            );
+            if index == 0 {
+                sender.send(runner.task_queue()).unwrap();
+            }
+            runner.run_until(NodeTime(2000));
+        }));
+        if index == 0 {
+            tasks = Some(receiver.recv().unwrap());
+        }
+    }
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(serve(address, tasks.unwrap()));
+    runtime.block_on(async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let mut client = NodeControlClient::connect(format!("http://{}", address))
+            .await
+            .unwrap();
+        let status = client.get_status().await.unwrap();
+        assert_eq!(status.author, 0);
+        assert!(status.current_round > 1);
+        assert!(status.committed_commands > 0);
+
+        match client
+            .get_record(status.highest_quorum_certificate_hash, None)
+            .await
+            .unwrap()
+        {
+            Record::QuorumCertificate(qc) => assert!(qc.round.0 > 0),
+            record => panic!("Unexpected record {:?}", record),
+        }
+        let error = client.get_record(17, Some(0)).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::NotFound);
+
+        let command = client.inject_transaction().await.unwrap();
+        assert_eq!(command.proposer, Author(0));
+        client.trigger_query_all().await.unwrap();
+    });
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    // The node has stopped.
+    runtime.block_on(async {
+        let mut client = NodeControlClient::connect(format!("http://{}", address))
+            .await
+            .unwrap();
+        let error = client.get_status().await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unavailable);
+    });
+}
@@ -0,0 +1,73 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::{base_types::NodeTime, transport::NodeRunner};
+use librabft_simulator::{proto::messages, scenario::Scenario};
+use std::{net::TcpListener, thread};
+
+#[test]
+fn test_tcp_transport() {
+    let listeners: Vec<_> = (0..2)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect();
+    let addresses: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+    let mut transports: Vec<_> = listeners
+        .into_iter()
+        .enumerate()
+        .map(|(index, listener)| TcpTransport::new(Author(index), listener, &addresses).unwrap())
+        .collect();
+    let request = DataSyncRequest::from_proto(messages::DataSyncRequest {
+        current_epoch: 1,
+        summary: Some(messages::RecordSummary {
+            highest_committed_round: 3,
+            round_digests: vec![messages::RoundDigest {
+                round: 4,
+                digest: 17,
+            }],
+            sketch: None,
+        }),
+        needs_snapshot: false,
+    })
+    .unwrap();
+    // Unknown peers are ignored.
+    transports[0].send(Author(7), Message::Request(request.clone()));
+    transports[0].send(Author(1), Message::Request(request.clone()));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    assert_eq!(
+        transports[1].receive(deadline),
+        Some((Author(0), Message::Request(request)))
+    );
+}
+
+#[test]
+fn test_nodes_over_tcp() {
+    let mut scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1").unwrap();
+    scenario.deploy();
+    let listeners: Vec<_> = (0..scenario.nodes)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect();
+    let addresses: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+    let handles: Vec<_> = listeners
+        .into_iter()
+        .enumerate()
+        .map(|(index, listener)| {
+            let transport = TcpTransport::new(Author(index), listener, &addresses).unwrap();
+            let scenario = scenario.clone();
+            thread::spawn(move || {
+                let mut runner = NodeRunner::new(
+                    Author(index),
+                    scenario.nodes,
+                    |author, _| scenario.make_context(author),
+                    |author, context, clock| scenario.make_node(author, context, clock),
+                    transport,
+                );
+                runner.run_until(NodeTime(2000));
+                runner.context().committed_history().len()
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.join().unwrap() > 0);
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Benchmarks of the hot paths of a node, for record stores of increasing sizes. Run with
+//! `cargo bench -p librabft_simulator`.
+
+#[macro_use]
+extern crate criterion;
+extern crate bft_simulator_runtime;
+extern crate librabft_simulator;
+
+use bft_simulator_runtime::ConsensusNode;
+use criterion::{BatchSize, BenchmarkId, Criterion};
+use librabft_simulator::{
+    fixtures::{simulated_validator, ChainFixture},
+    record::Record,
+    record_store::RecordStore,
+    serialization::{from_canonical_bytes, to_canonical_bytes},
+};
+
+/// Certified rounds in the stores of the record-store benchmarks.
+const NUM_ROUNDS: &[usize] = &[100, 1000, 5000];
+
+/// Durations of the runs producing the nodes of the `update_node` benchmarks.
+const MAX_CLOCKS: &[i64] = &[1000, 10000, 50000];
+
+const NUM_NODES: usize = 4;
+
+fn insert_network_record(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_network_record");
+    for &num_rounds in NUM_ROUNDS {
+        let fixture = ChainFixture::new(NUM_NODES, num_rounds);
+        let records = fixture.next_round_records();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_rounds),
+            &records,
+            |b, records| {
+                b.iter_batched(
+                    || (fixture.store.clone(), fixture.contexts[0].clone()),
+                    |(mut store, mut context)| {
+                        for record in records.iter().cloned() {
+                            store.insert_network_record(record, &mut context).unwrap();
+                        }
+                        store
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn quorum_certificate_formation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("quorum_certificate_formation");
+    for &num_rounds in NUM_ROUNDS {
+        let mut fixture = ChainFixture::new(NUM_NODES, num_rounds);
+        fixture.propose_and_vote();
+        let leader = fixture.leader();
+        group.bench_function(BenchmarkId::from_parameter(num_rounds), |b| {
+            b.iter_batched(
+                || (fixture.store.clone(), fixture.contexts[leader.0].clone()),
+                |(mut store, mut context)| {
+                    assert!(store
+                        .check_for_new_quorum_certificate(leader, &mut context)
+                        .unwrap());
+                    store
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn update_node(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_node");
+    for &max_clock in MAX_CLOCKS {
+        let (node, context, clock) = simulated_validator(NUM_NODES, max_clock);
+        group.bench_function(BenchmarkId::from_parameter(max_clock), |b| {
+            b.iter_batched(
+                || (node.clone(), context.clone()),
+                |(mut node, mut context)| node.update_node(clock, &mut context),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn canonical_serialization(c: &mut Criterion) {
+    // A block, a vote, and a QC.
+    let records = ChainFixture::new(NUM_NODES, 10).next_round_records();
+    let mut group = c.benchmark_group("canonical_serialization");
+    for record in &[&records[0], &records[1], records.last().unwrap()] {
+        let bytes = to_canonical_bytes(*record);
+        group.bench_function(format!("encode_{}", record.kind()), |b| {
+            b.iter(|| to_canonical_bytes(*record))
+        });
+        group.bench_function(format!("decode_{}", record.kind()), |b| {
+            b.iter(|| from_canonical_bytes::<Record>(&bytes).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    insert_network_record,
+    quorum_certificate_formation,
+    update_node,
+    canonical_serialization
+);
+criterion_main!(benches);
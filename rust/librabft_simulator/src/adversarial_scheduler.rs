@@ -0,0 +1,92 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Schedulers playing an adversary that controls the network within the asynchrony bound, to
+//! probe the worst-case liveness of the protocol rather than its average case.
+
+use super::*;
+use bft_simulator_runtime::{
+    scheduler::Scheduler,
+    simulator::{Event, GlobalTime},
+};
+use data_sync::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(test)]
+#[path = "unit_tests/adversarial_scheduler_tests.rs"]
+mod adversarial_scheduler_tests;
+
+/// Number of rounds whose votes are tracked, the most recent ones.
+const ROUNDS_TRACKED: usize = 16;
+
+/// Authors of the votes of a round delivered without extra delay, by receiver and voted block.
+type DeliveredVotes = BTreeMap<(Author, BlockHash), BTreeSet<Author>>;
+
+/// Delays every vote that would complete a quorum at its receiver by the whole asynchrony
+/// bound, so that QCs form as late as the bound allows. Votes below the quorum, and copies of
+/// the votes already delivered, go through. Quorums are counted with the voting rights of the
+/// first epoch.
+#[derive(Clone, Debug)]
+pub struct QuorumDelayScheduler {
+    configuration: EpochConfiguration,
+    max_delay: Duration,
+    /// Votes delivered without extra delay, by epoch and round.
+    delivered_votes: BTreeMap<(EpochId, Round), DeliveredVotes>,
+}
+
+impl QuorumDelayScheduler {
+    pub fn new(configuration: EpochConfiguration, max_delay: Duration) -> Self {
+        QuorumDelayScheduler {
+            configuration,
+            max_delay,
+            delivered_votes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Scheduler<DataSyncNotification, DataSyncRequest, DataSyncResponse> for QuorumDelayScheduler {
+    fn extra_delay(
+        &mut self,
+        _departure: GlobalTime,
+        _arrival: GlobalTime,
+        event: &Event<DataSyncNotification, DataSyncRequest, DataSyncResponse>,
+    ) -> Duration {
+        let (receiver, vote) = match event {
+            Event::DataSyncNotifyEvent {
+                receiver,
+                notification,
+                ..
+            } => match notification.current_vote() {
+                Some(vote) => (*receiver, vote),
+                None => return 0,
+            },
+            _ => return 0,
+        };
+        let voters = self
+            .delivered_votes
+            .entry((vote.epoch_id, vote.round))
+            .or_default()
+            .entry((receiver, vote.certified_block_hash))
+            .or_default();
+        if voters.contains(&vote.author) {
+            return 0;
+        }
+        let weight =
+            self.configuration.count_votes(voters.iter()) + self.configuration.weight(&vote.author);
+        if weight >= self.configuration.quorum_threshold() {
+            return self.max_delay;
+        }
+        voters.insert(vote.author);
+        while self.delivered_votes.len() > ROUNDS_TRACKED {
+            let oldest = *self.delivered_votes.keys().next().unwrap();
+            self.delivered_votes.remove(&oldest);
+        }
+        0
+    }
+
+    fn clone_box(
+        &self,
+    ) -> Box<dyn Scheduler<DataSyncNotification, DataSyncRequest, DataSyncResponse>> {
+        Box::new(self.clone())
+    }
+}
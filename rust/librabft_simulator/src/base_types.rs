@@ -2,23 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::Author;
+use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
 #[path = "unit_tests/base_type_tests.rs"]
 mod base_type_tests;
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct EpochId(pub usize);
 
+/// Identifier of a network, e.g. a test network or a simulated run, signed by every record so
+/// that records cannot be replayed from one network to another.
+#[derive(
+    Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Default, Serialize, Deserialize,
+)]
+pub struct ChainId(pub u64);
+
 // The following types are simplified for simulation purposes.
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct BlockHash(pub u64);
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct QuorumCertificateHash(pub u64);
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct State(pub u64);
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Hash, Debug, Serialize, Deserialize)]
 pub struct Command {
     pub proposer: Author,
     pub index: usize,
@@ -33,7 +41,7 @@ impl EpochId {
         if self.0 == 0 {
             None
         } else {
-            Some(EpochId(self.0))
+            Some(EpochId(self.0 - 1))
         }
     }
 }
@@ -53,13 +61,14 @@ pub fn merge_sort<T: Eq, I: IntoIterator<Item = T>, F: Fn(&T, &T) -> std::cmp::O
     let mut head1 = iter1.next();
     let mut head2 = iter2.next();
     while let (Some(x1), Some(x2)) = (&head1, &head2) {
-        match cmp(&x1, &x2) {
+        match cmp(x1, x2) {
             std::cmp::Ordering::Less => {
                 result.push(head1.unwrap());
                 head1 = iter1.next();
             }
             std::cmp::Ordering::Equal => {
-                if head1 == head1 {
+                // Only drop duplicates: distinct elements that compare as equal are all kept.
+                if x1 == x2 {
                     result.push(head1.unwrap());
                 } else {
                     result.push(head1.unwrap());
@@ -0,0 +1,161 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Execution of the commands of a block as a single transaction, for SMR contexts backed by a
+//! database. Contexts implement `BatchExecutor` and call `execute_batch` from `compute`, while
+//! `compute_batch` runs a batch with the single-command `compute` of any context.
+
+use super::*;
+use base_types::{Command, State};
+use smr_context::StateComputer;
+
+#[cfg(test)]
+#[path = "unit_tests/batch_executor_tests.rs"]
+mod batch_executor_tests;
+
+/// Execution of a batch of commands as a transaction: after `begin_transaction`, either
+/// `commit_transaction` makes all the commands applied so far visible as a new state, or
+/// `abort_transaction` leaves no trace of them. At most one transaction is open at a time.
+pub trait BatchExecutor {
+    /// Open a transaction on top of `base_state`, with the arguments of `compute`. Return
+    /// `false` if the base state is not available.
+    fn begin_transaction(
+        &mut self,
+        base_state: &State,
+        time: NodeTime,
+        previous_author: Option<Author>,
+        previous_voters: Vec<Author>,
+    ) -> bool;
+
+    /// Apply a command in the open transaction. Return `false` if the command is rejected, in
+    /// which case the transaction must be aborted.
+    fn apply_command(&mut self, command: &Command) -> bool;
+
+    /// Close the open transaction and return the state after all its commands, or after an
+    /// empty block if there were none.
+    fn commit_transaction(&mut self) -> Option<State>;
+
+    /// Close the open transaction and drop its changes.
+    fn abort_transaction(&mut self);
+}
+
+/// Run the begin/commit/abort protocol of `executor` on a batch of commands.
+pub fn execute_batch<E: BatchExecutor + ?Sized>(
+    executor: &mut E,
+    base_state: &State,
+    commands: &[Command],
+    time: NodeTime,
+    previous_author: Option<Author>,
+    previous_voters: Vec<Author>,
+) -> Option<State> {
+    if !executor.begin_transaction(base_state, time, previous_author, previous_voters) {
+        return None;
+    }
+    for command in commands {
+        if !executor.apply_command(command) {
+            executor.abort_transaction();
+            return None;
+        }
+    }
+    executor.commit_transaction()
+}
+
+/// Adapter running transactions with the single-command `compute` of a context: each command
+/// is computed after the state of the previous one, and the intermediate states are left to
+/// the context like any other speculative state.
+pub struct ComputeTransaction<'a, C: StateComputer + ?Sized> {
+    context: &'a mut C,
+    /// Arguments of the open transaction, and the state after the commands applied so far.
+    time: NodeTime,
+    previous_author: Option<Author>,
+    previous_voters: Vec<Author>,
+    state: Option<State>,
+    applied: bool,
+}
+
+impl<'a, C: StateComputer + ?Sized> ComputeTransaction<'a, C> {
+    pub fn new(context: &'a mut C) -> Self {
+        ComputeTransaction {
+            context,
+            time: NodeTime(0),
+            previous_author: None,
+            previous_voters: Vec::new(),
+            state: None,
+            applied: false,
+        }
+    }
+}
+
+impl<'a, C: StateComputer + ?Sized> BatchExecutor for ComputeTransaction<'a, C> {
+    fn begin_transaction(
+        &mut self,
+        base_state: &State,
+        time: NodeTime,
+        previous_author: Option<Author>,
+        previous_voters: Vec<Author>,
+    ) -> bool {
+        self.time = time;
+        self.previous_author = previous_author;
+        self.previous_voters = previous_voters;
+        self.state = Some(base_state.clone());
+        self.applied = false;
+        true
+    }
+
+    fn apply_command(&mut self, command: &Command) -> bool {
+        let base_state = match &self.state {
+            Some(state) => state.clone(),
+            None => return false,
+        };
+        // Only the first command of the batch rewards the previous block.
+        self.state = self.context.compute(
+            &base_state,
+            Some(command.clone()),
+            self.time,
+            self.previous_author.take(),
+            std::mem::take(&mut self.previous_voters),
+        );
+        self.applied = true;
+        self.state.is_some()
+    }
+
+    fn commit_transaction(&mut self) -> Option<State> {
+        let state = self.state.take()?;
+        if self.applied {
+            return Some(state);
+        }
+        self.context.compute(
+            &state,
+            None,
+            self.time,
+            self.previous_author.take(),
+            std::mem::take(&mut self.previous_voters),
+        )
+    }
+
+    fn abort_transaction(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Execute a batch of commands as a single step with the `compute` of a context, e.g. all the
+/// commands of a block, and an empty batch as an empty block. Each command is computed after
+/// the state of the previous one.
+pub fn compute_batch<C: StateComputer + ?Sized>(
+    context: &mut C,
+    base_state: &State,
+    commands: &[Command],
+    time: NodeTime,
+    previous_author: Option<Author>,
+    previous_voters: Vec<Author>,
+) -> Option<State> {
+    let mut transaction = ComputeTransaction::new(context);
+    execute_batch(
+        &mut transaction,
+        base_state,
+        commands,
+        time,
+        previous_author,
+        previous_voters,
+    )
+}
@@ -0,0 +1,491 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+extern crate bft_simulator_runtime;
+extern crate clap;
+extern crate env_logger;
+extern crate librabft_simulator;
+extern crate serde;
+extern crate serde_json;
+
+use bft_simulator_runtime::base_types::Round;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use librabft_simulator::{
+    campaign, commit_chain,
+    comparison::{self, ReportFormat},
+    conformance, debugger,
+    experiment::{self, Sweep},
+    follower,
+    model_checker::{self, ModelCheckerConfig},
+    scenario::Scenario,
+    shards,
+    soak::{self, SoakParameters},
+};
+use serde::Serialize;
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+fn main() {
+    env_logger::init();
+    let scenario_arg = Arg::with_name("scenario")
+        .help("TOML file describing the scenario")
+        .required(true);
+    let seed_arg = Arg::with_name("seed")
+        .long("seed")
+        .help("Seed of the random network delays")
+        .takes_value(true);
+    let app = App::new("librabft-sim")
+        .about("Run, replay, and analyze simulations of the LibraBFT consensus protocol")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Run a scenario once and print a summary report, per shard if any")
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Run a scenario again from a seed and print the trace of all events")
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone().required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about(
+                    "Run a scenario step by step, with commands read from the standard input \
+                     (try help)",
+                )
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("branch")
+                .about(
+                    "Run a scenario until a given time, then finish it both as is and with \
+                     another seed, and print both reports",
+                )
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone().required(true))
+                .arg(
+                    Arg::with_name("at")
+                        .long("at")
+                        .help("Time of the snapshot")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("branch_seed")
+                        .long("branch_seed")
+                        .help("Seed of the network delays after the snapshot in the branch")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("Run a scenario until a given time and save a snapshot of the run")
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone().required(true))
+                .arg(
+                    Arg::with_name("at")
+                        .long("at")
+                        .help("Time of the snapshot")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .help("File where to save the snapshot")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("Finish a run of a scenario from a saved snapshot and print the report")
+                .arg(scenario_arg.clone())
+                .arg(
+                    Arg::with_name("snapshot")
+                        .long("snapshot")
+                        .help("File of the snapshot")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("propagation")
+                .about("Run a scenario and measure how fast commits reach the followers")
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("itf")
+                .about("Run a scenario from a seed and export its trace for the TLA+ specification")
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone().required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("certificates")
+                .about(
+                    "Run a scenario from a seed and export the chain of commit certificates of \
+                     a replica across epochs",
+                )
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone().required(true))
+                .arg(
+                    Arg::with_name("replica")
+                        .long("replica")
+                        .help("Replica whose records are exported")
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("analyze")
+                .about(
+                    "Run scenarios with consecutive seeds and aggregate the results, side by \
+                     side if there are several scenarios, sweeps, or a --format",
+                )
+                .arg(scenario_arg.clone().multiple(true))
+                .arg(seed_arg.clone().help("First seed").default_value("0"))
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .help("Number of runs")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("sweep")
+                        .long("sweep")
+                        .help("Values of a parameter to compare, e.g. pacemaker.delta=10,20,40")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("Format of the comparison: json, markdown, or csv")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("experiment")
+                .about("Run a grid of variants of a scenario with several seeds, in parallel")
+                .arg(scenario_arg.clone())
+                .arg(seed_arg.clone().help("First seed").default_value("0"))
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .help("Number of runs for each variant")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("sweep")
+                        .long("sweep")
+                        .help("Values of a parameter, e.g. pacemaker.delta=10,20,40")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .long("threads")
+                        .help("Number of threads (default: one per CPU)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fuzz")
+                .about("Run random variants of a scenario and report safety violations")
+                .arg(scenario_arg.clone())
+                .arg(
+                    seed_arg
+                        .clone()
+                        .help("Seed of the mutations")
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("iterations")
+                        .long("iterations")
+                        .help("Number of variants to run")
+                        .default_value("100"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("soak")
+                .about(
+                    "Run a scenario through many short epochs with reconfigurations and random \
+                     faults, check invariants along the way, and print a health report",
+                )
+                .arg(scenario_arg)
+                .arg(
+                    seed_arg
+                        .help("Seed of the network delays, faults, and voting rights")
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("epochs")
+                        .long("epochs")
+                        .help("Epoch to reach")
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::with_name("reconfiguration_period")
+                        .long("reconfiguration_period")
+                        .help("Number of epochs between two changes of the voting rights")
+                        .default_value("5"),
+                )
+                .arg(
+                    Arg::with_name("past_epochs_kept")
+                        .long("past_epochs_kept")
+                        .help("Number of past epochs whose records each replica keeps")
+                        .default_value("2"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Explore all the schedules of a small configuration and check invariants")
+                .arg(
+                    Arg::with_name("nodes")
+                        .long("nodes")
+                        .help("Number of nodes")
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::with_name("depth")
+                        .long("depth")
+                        .help("Maximal number of transitions in a schedule")
+                        .default_value("6"),
+                )
+                .arg(
+                    Arg::with_name("rounds")
+                        .long("rounds")
+                        .help("Maximal round to explore")
+                        .default_value("6"),
+                )
+                .arg(
+                    Arg::with_name("no_reduction")
+                        .long("no_reduction")
+                        .help("Disable the partial-order reduction"),
+                ),
+        );
+    #[cfg(feature = "server")]
+    let app = app.subcommand(
+        SubCommand::with_name("serve")
+            .about("Accept scenarios and serve their results through a REST API")
+            .arg(
+                Arg::with_name("address")
+                    .long("address")
+                    .help("Address to listen on")
+                    .default_value("127.0.0.1:8080"),
+            ),
+    );
+    let matches = app.get_matches();
+
+    match matches.subcommand() {
+        ("run", Some(args)) => {
+            let seed = args.value_of("seed").map(|x| parse(x, "seed"));
+            let scenario = load_scenario(args);
+            if scenario.shards.is_empty() {
+                print_report(&campaign::run(&scenario, seed));
+            } else {
+                print_report(&shards::run_shards(&scenario, seed));
+            }
+        }
+        ("replay", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let (report, trace) = campaign::replay(&load_scenario(args), seed);
+            for entry in trace {
+                println!("{}", entry);
+            }
+            print_report(&report);
+        }
+        ("debug", Some(args)) => {
+            let mut scenario = load_scenario(args);
+            if let Some(seed) = args.value_of("seed") {
+                scenario.seed = Some(parse(seed, "seed"));
+            }
+            let mut debugger = debugger::Debugger::new(&scenario);
+            let stdin = std::io::stdin();
+            loop {
+                print!("@{} > ", debugger.simulator().clock().0);
+                std::io::stdout().flush().unwrap();
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line).unwrap() == 0 || line.trim() == "quit" {
+                    break;
+                }
+                match debugger.execute(&line) {
+                    Ok(output) if output.is_empty() => (),
+                    Ok(output) => println!("{}", output),
+                    Err(error) => println!("{}", error),
+                }
+            }
+        }
+        ("branch", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let time = parse(args.value_of("at").unwrap(), "at");
+            let branch_seed = parse(args.value_of("branch_seed").unwrap(), "branch_seed");
+            print_report(&campaign::branch(
+                &load_scenario(args),
+                seed,
+                time as i64,
+                branch_seed,
+            ));
+        }
+        ("snapshot", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let time = parse(args.value_of("at").unwrap(), "at");
+            campaign::save_snapshot(
+                &load_scenario(args),
+                seed,
+                time as i64,
+                args.value_of("output").unwrap(),
+            )
+            .expect("Failed to save snapshot");
+        }
+        ("resume", Some(args)) => {
+            let report = campaign::resume(&load_scenario(args), args.value_of("snapshot").unwrap())
+                .expect("Failed to resume run");
+            print_report(&report);
+        }
+        ("propagation", Some(args)) => {
+            let mut scenario = load_scenario(args);
+            if let Some(seed) = args.value_of("seed") {
+                scenario.seed = Some(parse(seed, "seed"));
+            }
+            print_report(&follower::measure_propagation(&scenario));
+        }
+        ("itf", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            print_report(&conformance::export_itf(&load_scenario(args), seed));
+        }
+        ("certificates", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let replica = parse(args.value_of("replica").unwrap(), "replica");
+            print_report(&commit_chain::export_from_run(
+                &load_scenario(args),
+                seed,
+                replica as usize,
+            ));
+        }
+        ("analyze", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let runs = parse(args.value_of("runs").unwrap(), "runs");
+            let scenarios: Vec<_> = args
+                .values_of("scenario")
+                .unwrap()
+                .map(|path| {
+                    let name = Path::new(path)
+                        .file_stem()
+                        .map_or(path.to_string(), |stem| stem.to_string_lossy().into_owned());
+                    (name, Scenario::load(path).expect("Failed to load scenario"))
+                })
+                .collect();
+            let sweeps = parse_sweeps(args);
+            if scenarios.len() == 1 && sweeps.is_empty() && !args.is_present("format") {
+                print_report(&campaign::analyze(&scenarios[0].1, seed..seed + runs));
+                return;
+            }
+            let format = ReportFormat::parse(args.value_of("format").unwrap_or("json"))
+                .expect("Failed to parse format");
+            let variants =
+                comparison::variants(&scenarios, &sweeps).expect("Failed to apply sweeps");
+            let seeds: Vec<_> = (seed..seed + runs).collect();
+            let report =
+                comparison::compare(&variants, &seeds).expect("Failed to compare variants");
+            print!(
+                "{}",
+                report.render(format).expect("Failed to render report")
+            );
+        }
+        ("experiment", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let runs = parse(args.value_of("runs").unwrap(), "runs");
+            let sweeps = parse_sweeps(args);
+            let threads = args
+                .value_of("threads")
+                .map(|threads| parse(threads, "threads") as usize);
+            let seeds: Vec<_> = (seed..seed + runs).collect();
+            let report = experiment::run_experiment(&load_scenario(args), &sweeps, &seeds, threads)
+                .expect("Failed to run experiment");
+            print_report(&report);
+        }
+        ("fuzz", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let iterations = parse(args.value_of("iterations").unwrap(), "iterations");
+            let report = campaign::fuzz(&load_scenario(args), seed, iterations as usize);
+            print_report(&report);
+            if !report.failures.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        ("soak", Some(args)) => {
+            let seed = parse(args.value_of("seed").unwrap(), "seed");
+            let parameters = SoakParameters {
+                epochs: parse(args.value_of("epochs").unwrap(), "epochs") as usize,
+                reconfiguration_period: parse(
+                    args.value_of("reconfiguration_period").unwrap(),
+                    "reconfiguration_period",
+                ) as usize,
+                past_epochs_kept: parse(
+                    args.value_of("past_epochs_kept").unwrap(),
+                    "past_epochs_kept",
+                ) as usize,
+                ..SoakParameters::default()
+            };
+            let report = soak::soak(&load_scenario(args), seed, &parameters)
+                .expect("Failed to run soak test");
+            print_report(&report);
+            if report.violation.is_some() {
+                std::process::exit(1);
+            }
+        }
+        ("check", Some(args)) => {
+            let config = ModelCheckerConfig {
+                nodes: parse(args.value_of("nodes").unwrap(), "nodes") as usize,
+                max_depth: parse(args.value_of("depth").unwrap(), "depth") as usize,
+                max_round: Round(parse(args.value_of("rounds").unwrap(), "rounds") as usize),
+                partial_order_reduction: !args.is_present("no_reduction"),
+                ..ModelCheckerConfig::default()
+            };
+            let report = model_checker::check(&config);
+            print_report(&report);
+            if report.safety_violation.is_some() || report.liveness_violation.is_some() {
+                std::process::exit(1);
+            }
+        }
+        #[cfg(feature = "server")]
+        ("serve", Some(args)) => {
+            let server = librabft_simulator::server::CampaignServer::new();
+            server
+                .serve(args.value_of("address").unwrap())
+                .expect("Failed to run the server");
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn load_scenario(args: &ArgMatches) -> Scenario {
+    Scenario::load(args.value_of("scenario").unwrap()).expect("Failed to load scenario")
+}
+
+fn parse_sweeps(args: &ArgMatches) -> Vec<Sweep> {
+    args.values_of("sweep")
+        .into_iter()
+        .flatten()
+        .map(|sweep| Sweep::parse(sweep).expect("Failed to parse sweep"))
+        .collect()
+}
+
+fn parse(value: &str, name: &str) -> u64 {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value for {}: {}", name, value))
+}
+
+fn print_report<T: Serialize>(report: &T) {
+    println!("{}", serde_json::to_string_pretty(report).unwrap());
+}
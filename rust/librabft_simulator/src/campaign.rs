@@ -0,0 +1,589 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::{
+    bandwidth::{BandwidthStats, PayloadSizes},
+    inbound_queue::QueueStats,
+    simulator::{GlobalTime, RunOutcome, SimulationSnapshot, TraceEntry},
+};
+use checkpoints::CheckpointReport;
+use clients::{ClientRequestStats, SimulatedClients};
+use data_sync::payload_id;
+use orphan_buffer::OrphanStats;
+use proposal_limiter::ProposalLimiter;
+use qc_cache::QuorumCertificateCacheStats;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use reads::{ReadStats, SimulatedReads};
+use reconciliation::ReconciliationStats;
+use scenario::{quorum_impossible_intervals, CrashFault, NodeSimulator, Scenario};
+use serde::Serialize;
+use simulated_context::SimulatedContext;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[path = "unit_tests/campaign_tests.rs"]
+mod campaign_tests;
+
+/// Summary of a single simulation run.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct RunReport {
+    /// Seed from which the run can be replayed.
+    pub seed: u64,
+    /// Why and when the run ended.
+    pub outcome: RunOutcome,
+    /// Number of commands committed by each node.
+    pub commits: Vec<usize>,
+    /// Whether the committed histories of the nodes are prefixes of one another.
+    pub consistent: bool,
+    /// Intervals during which crashes made commits impossible.
+    pub quorum_impossible: Vec<(i64, i64)>,
+    /// Mean time between the proposals of two consecutive committed blocks, that is, the
+    /// latency of a round, if at least two blocks were committed.
+    pub mean_block_interval: Option<Duration>,
+    /// Blocks and QCs received by all replicas during data sync, and how many were missing.
+    pub reconciliation: ReconciliationStats,
+    /// Records received from the network and rejected by all replicas, by reason.
+    pub rejections: BTreeMap<&'static str, usize>,
+    /// Bytes sent on the network, per node, per kind of message and record, and over time.
+    pub bandwidth: BandwidthStats,
+    /// If validators learn `delta`, the successive values learned by each of them, with the
+    /// local time at which they were learned.
+    pub learned_deltas: Option<Vec<Vec<(NodeTime, Duration)>>>,
+    /// If the fast path is enabled, the number of blocks committed on the fast path by each
+    /// validator.
+    pub fast_path_commits: Option<Vec<usize>>,
+    /// If only committees vote, the number of proposals that each validator did not vote for
+    /// because it was not in the committee of their round.
+    pub committee_abstentions: Option<Vec<usize>>,
+    /// If inbound queues are bounded, their statistics for each replica.
+    pub queues: Option<Vec<QueueStats>>,
+    /// If replicas buffer records received before their parent, the statistics of all
+    /// replicas.
+    pub orphans: Option<OrphanStats>,
+    /// If commands have payloads, the commit latency of the validators by payload size.
+    pub payload_latencies: Option<Vec<PayloadLatency>>,
+    /// Largest number of uncommitted blocks seen by a validator in the chain of its highest QC.
+    pub deepest_uncommitted_chain: usize,
+    /// Mean time between the proposals of each validator and the creation of their QC, if
+    /// any was certified.
+    pub qc_latencies: Vec<Option<Duration>>,
+    /// If nodes are placed into regions, the mean time between the proposal of a block and its
+    /// commit as observed by the validators of each region.
+    pub region_latencies: Option<BTreeMap<String, Duration>>,
+    /// If there are several clients, the commit latency of the validators for each of them.
+    pub client_latencies: Option<Vec<ClientLatency>>,
+    /// If validators join during the run, how long each of them took to bootstrap.
+    pub joins: Option<Vec<JoinLatency>>,
+    /// If leaders wait between their proposals, the number of proposals that each validator
+    /// postponed.
+    pub deferred_proposals: Option<Vec<usize>>,
+    /// If replicas cache verified QCs, the statistics of the caches of all replicas.
+    pub qc_cache: Option<QuorumCertificateCacheStats>,
+    /// If an adversary schedules the network, the number of messages that it delayed.
+    pub delayed_messages: Option<usize>,
+    /// If clients wait for the commit of their requests, what they observed.
+    pub client_requests: Option<ClientRequestStats>,
+    /// If replicas take checkpoints, the comparison of the checkpoints of honest replicas.
+    pub checkpoints: Option<CheckpointReport>,
+    /// If reads are issued during the run, how stale they were at each level of consistency.
+    pub reads: Option<Vec<ReadStats>>,
+    /// If the run stopped at a divergence, what every replica knew of the offending round.
+    pub triage: Option<serde_json::Value>,
+}
+
+/// Commit latency of the blocks whose payloads have similar sizes.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct PayloadLatency {
+    /// Payloads have between `min_bytes` and `2 * min_bytes - 1` bytes, or none at all if
+    /// `min_bytes` is 0, e.g. in empty blocks.
+    pub min_bytes: usize,
+    /// Number of blocks committed by validators.
+    pub commits: usize,
+    /// Mean local time between the proposal and the commit of a block.
+    pub mean_latency: Duration,
+}
+
+/// Commit latency of the commands of a client, e.g. to tell censored clients from the others.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct ClientLatency {
+    pub client: usize,
+    /// Number of commands of the client committed by validators.
+    pub commits: usize,
+    /// Mean local time between the proposal and the commit of a command, or 0 if none.
+    pub mean_latency: Duration,
+    /// Largest local time between the proposal and the commit of a command, or 0 if none.
+    pub max_latency: Duration,
+    /// Number of commands of the client still waiting in the mempools of validators at the
+    /// end of the run.
+    pub pending: usize,
+}
+
+/// Bootstrap of a joining validator, measured from the time it comes online.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct JoinLatency {
+    pub node: usize,
+    /// Time until the validator installed a snapshot, if it bootstrapped from one.
+    pub snapshot: Option<Duration>,
+    /// Time until the first vote of the validator with voting rights, if any.
+    pub first_vote: Option<Duration>,
+}
+
+/// Aggregated results of the same scenario over several seeds.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct AnalysisReport {
+    pub runs: usize,
+    pub min_commits: usize,
+    pub mean_commits: f64,
+    pub max_commits: usize,
+    /// Seeds of the runs that broke safety, if any.
+    pub inconsistent_seeds: Vec<u64>,
+}
+
+/// Results of a fuzzing campaign.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct FuzzReport {
+    pub iterations: usize,
+    /// Mutated scenarios that broke safety, with their seed set.
+    pub failures: Vec<Scenario>,
+}
+
+/// Check that all committed histories are prefixes of the longest one.
+pub(crate) fn consistent_histories(contexts: &[&SimulatedContext]) -> bool {
+    let longest = match contexts
+        .iter()
+        .max_by_key(|context| context.committed_history().len())
+    {
+        Some(context) => context.committed_history(),
+        None => return true,
+    };
+    contexts.iter().all(|context| {
+        context
+            .committed_history()
+            .iter()
+            .zip(longest.iter())
+            .all(|((command1, _), (command2, _))| command1 == command2)
+    })
+}
+
+/// Mean interval between the proposal times of the blocks of the longest committed history.
+pub(crate) fn mean_block_interval(contexts: &[&SimulatedContext]) -> Option<Duration> {
+    let history = contexts
+        .iter()
+        .map(|context| context.committed_history())
+        .max_by_key(|history| history.len())?;
+    match (history.first(), history.last()) {
+        (Some((_, first)), Some((_, last))) if history.len() > 1 => {
+            Some((last.0 - first.0) / (history.len() - 1) as Duration)
+        }
+        _ => None,
+    }
+}
+
+/// Commit latency of the validators by payload size, from the smallest payloads to the
+/// largest.
+fn payload_latencies(
+    sim: &NodeSimulator,
+    nodes: usize,
+    payload_sizes: PayloadSizes,
+) -> Vec<PayloadLatency> {
+    let mut buckets = BTreeMap::new();
+    for replica in &sim.replicas()[..nodes] {
+        for (command, latency) in replica.node().node_state().commit_latencies() {
+            let bytes = command
+                .as_ref()
+                .map_or(0, |command| payload_sizes.size(payload_id(command)));
+            let min_bytes = (bytes + 1).next_power_of_two() / 2;
+            let (commits, total_latency) = buckets.entry(min_bytes).or_insert((0, 0));
+            *commits += 1;
+            *total_latency += latency;
+        }
+    }
+    buckets
+        .into_iter()
+        .map(|(min_bytes, (commits, total_latency))| PayloadLatency {
+            min_bytes,
+            commits,
+            mean_latency: total_latency / commits as Duration,
+        })
+        .collect()
+}
+
+/// Mean commit latency observed by the validators of each region, if they committed anything.
+fn region_latencies(sim: &NodeSimulator, scenario: &Scenario) -> BTreeMap<String, Duration> {
+    let mut latencies = BTreeMap::new();
+    for (region, nodes) in &scenario.topology.regions {
+        let region_latencies: Vec<_> = nodes
+            .iter()
+            .flat_map(|node| sim.replicas()[*node].node().node_state().commit_latencies())
+            .map(|(_, latency)| *latency)
+            .collect();
+        if !region_latencies.is_empty() {
+            latencies.insert(
+                region.clone(),
+                region_latencies.iter().sum::<Duration>() / region_latencies.len() as Duration,
+            );
+        }
+    }
+    latencies
+}
+
+/// Commit latency of the validators for each client, and commands left in their mempools.
+fn client_latencies(sim: &NodeSimulator, nodes: usize, clients: usize) -> Vec<ClientLatency> {
+    let mut latencies: Vec<_> = (0..clients)
+        .map(|client| ClientLatency {
+            client,
+            commits: 0,
+            mean_latency: 0,
+            max_latency: 0,
+            pending: 0,
+        })
+        .collect();
+    for replica in &sim.replicas()[..nodes] {
+        for (command, latency) in replica.node().node_state().commit_latencies() {
+            if let Some(command) = command {
+                let client = &mut latencies[SimulatedContext::client(command, clients)];
+                client.commits += 1;
+                // Sum the latencies until the mean is taken below.
+                client.mean_latency += latency;
+                client.max_latency = std::cmp::max(client.max_latency, *latency);
+            }
+        }
+        for command in replica.context().pending_commands() {
+            latencies[SimulatedContext::client(command, clients)].pending += 1;
+        }
+    }
+    for client in &mut latencies {
+        if client.commits > 0 {
+            client.mean_latency /= client.commits as Duration;
+        }
+    }
+    latencies
+}
+
+/// Cross-check the checkpoints of the replicas of the authors without twins.
+fn checkpoint_report(sim: &NodeSimulator) -> CheckpointReport {
+    let mut replicas = BTreeMap::new();
+    for replica in sim.replicas() {
+        *replicas.entry(replica.author()).or_insert(0) += 1;
+    }
+    let nodes: Vec<_> = sim
+        .replicas()
+        .iter()
+        .filter(|replica| replicas[&replica.author()] == 1)
+        .map(|replica| replica.node().node_state())
+        .collect();
+    checkpoints::cross_check(&nodes)
+}
+
+fn run_simulator(sim: &mut NodeSimulator, scenario: &Scenario, seed: u64) -> RunReport {
+    let quorum_impossible = quorum_impossible_intervals(sim, scenario.max_clock);
+    sim.enable_bandwidth_accounting(scenario.network.bandwidth_interval);
+    let conditions = scenario
+        .stop
+        .as_ref()
+        .map(|stop| stop.conditions())
+        .unwrap_or_default();
+    let mut clients = scenario
+        .client_requests
+        .as_ref()
+        .map(|parameters| SimulatedClients::new(parameters, scenario.nodes, seed));
+    let mut reads = scenario
+        .reads
+        .as_ref()
+        .map(|parameters| SimulatedReads::new(parameters, scenario.nodes, seed));
+    let observer = |sim: &NodeSimulator, clock| {
+        if let Some(reads) = &mut reads {
+            reads.observe(sim, clock);
+        }
+    };
+    let max_clock = GlobalTime(scenario.max_clock);
+    let outcome = match &mut clients {
+        Some(clients) => clients.run_with_observer(sim, max_clock, &conditions, observer),
+        None => sim.run_until_with_observer(max_clock, None, &conditions, observer),
+    };
+    let (commits, consistent, mean_block_interval) = {
+        let contexts: Vec<_> = sim.replicas().iter().map(|node| node.context()).collect();
+        (
+            contexts
+                .iter()
+                .map(|context| context.committed_history().len())
+                .collect(),
+            consistent_histories(&contexts),
+            mean_block_interval(&contexts),
+        )
+    };
+    let mut reconciliation = ReconciliationStats::default();
+    let mut rejections = BTreeMap::new();
+    for replica in sim.replicas() {
+        let node = replica.node().node_state();
+        reconciliation.add(node.reconciliation_stats());
+        for (reason, count) in node.rejections() {
+            *rejections.entry(*reason).or_insert(0) += count;
+        }
+    }
+    let learned_deltas = scenario.pacemaker.adaptive_delta.as_ref().map(|_| {
+        sim.replicas()[..scenario.nodes]
+            .iter()
+            .map(
+                |replica| match replica.node().node_state().adaptive_delta() {
+                    Some(adaptive_delta) => adaptive_delta.history().to_vec(),
+                    None => Vec::new(),
+                },
+            )
+            .collect()
+    });
+    let fast_path_commits = scenario.fast_path.as_ref().map(|_| {
+        sim.replicas()[..scenario.nodes]
+            .iter()
+            .map(|replica| replica.node().node_state().fast_path_commits())
+            .collect()
+    });
+    let committee_abstentions = scenario.committee.as_ref().map(|_| {
+        sim.replicas()[..scenario.nodes]
+            .iter()
+            .map(|replica| replica.node().node_state().committee_abstentions())
+            .collect()
+    });
+    let queues = sim
+        .queue_stats()
+        .map(|stats| stats.into_iter().cloned().collect());
+    let orphans = scenario.orphan_buffer.as_ref().map(|_| {
+        let mut orphans = OrphanStats::default();
+        for replica in sim.replicas() {
+            if let Some(stats) = replica.node().node_state().orphan_stats() {
+                orphans.add(stats);
+            }
+        }
+        orphans
+    });
+    let qc_cache = scenario.qc_cache.map(|_| {
+        let mut qc_cache = QuorumCertificateCacheStats::default();
+        for replica in sim.replicas() {
+            if let Some(stats) = replica.node().node_state().quorum_certificate_cache_stats() {
+                qc_cache.add(stats);
+            }
+        }
+        qc_cache
+    });
+    let payload_latencies = scenario
+        .workload
+        .payload
+        .map(|payload_sizes| payload_latencies(sim, scenario.nodes, payload_sizes));
+    let region_latencies = if scenario.topology.regions.is_empty() {
+        None
+    } else {
+        Some(region_latencies(sim, scenario))
+    };
+    let client_latencies = if scenario.workload.clients > 1 {
+        Some(client_latencies(
+            sim,
+            scenario.nodes,
+            scenario.workload.clients,
+        ))
+    } else {
+        None
+    };
+    let qc_latencies = sim.replicas()[..scenario.nodes]
+        .iter()
+        .map(|replica| {
+            let latencies = replica.node().node_state().quorum_certificate_latencies();
+            if latencies.is_empty() {
+                None
+            } else {
+                Some(latencies.iter().sum::<Duration>() / latencies.len() as Duration)
+            }
+        })
+        .collect();
+    let joins = if scenario.joins.is_empty() {
+        None
+    } else {
+        Some(
+            scenario
+                .joins
+                .iter()
+                .map(|join| {
+                    let node = sim.replicas()[join.node].node().node_state();
+                    JoinLatency {
+                        node: join.node,
+                        snapshot: node.snapshot_time().map(|time| time.0 - join.start),
+                        first_vote: node.first_vote_time().map(|time| time.0 - join.start),
+                    }
+                })
+                .collect(),
+        )
+    };
+    let deferred_proposals = scenario.pacemaker.min_proposal_interval.map(|_| {
+        sim.replicas()[..scenario.nodes]
+            .iter()
+            .map(|replica| {
+                replica
+                    .node()
+                    .node_state()
+                    .proposal_limiter()
+                    .map_or(0, ProposalLimiter::deferrals)
+            })
+            .collect()
+    });
+    let deepest_uncommitted_chain = sim.replicas()[..scenario.nodes]
+        .iter()
+        .map(|replica| replica.node().node_state().deepest_uncommitted_chain())
+        .max()
+        .unwrap_or(0);
+    RunReport {
+        seed,
+        outcome,
+        commits,
+        consistent,
+        quorum_impossible,
+        mean_block_interval,
+        reconciliation,
+        rejections,
+        bandwidth: sim.bandwidth().cloned().unwrap_or_default(),
+        learned_deltas,
+        fast_path_commits,
+        committee_abstentions,
+        queues,
+        orphans,
+        payload_latencies,
+        deepest_uncommitted_chain,
+        qc_latencies,
+        region_latencies,
+        client_latencies,
+        joins,
+        deferred_proposals,
+        qc_cache,
+        delayed_messages: scenario.scheduler.as_ref().map(|_| sim.scheduled_delays()),
+        client_requests: clients.map(|clients| clients.stats()),
+        checkpoints: scenario.checkpoint_interval.map(|_| checkpoint_report(sim)),
+        reads: reads.map(|reads| reads.stats().to_vec()),
+        triage: sim.triage().cloned(),
+    }
+}
+
+pub(crate) fn with_seed(scenario: &Scenario, seed: u64) -> Scenario {
+    let mut scenario = scenario.clone();
+    scenario.seed = Some(seed);
+    scenario
+}
+
+/// Run a scenario once. The seed of the scenario is used unless another one is given.
+pub fn run(scenario: &Scenario, seed: Option<u64>) -> RunReport {
+    let seed = seed.or(scenario.seed).unwrap_or_else(rand::random);
+    let scenario = with_seed(scenario, seed);
+    run_simulator(&mut scenario.build_simulator(), &scenario, seed)
+}
+
+/// Run a scenario again from a given seed and return the trace of all processed events.
+pub fn replay(scenario: &Scenario, seed: u64) -> (RunReport, Vec<TraceEntry>) {
+    let scenario = with_seed(scenario, seed);
+    let mut sim = scenario.build_simulator();
+    sim.enable_trace();
+    let report = run_simulator(&mut sim, &scenario, seed);
+    (report, sim.trace().unwrap_or(&[]).to_vec())
+}
+
+/// Run a scenario until `time`, then finish the run twice from a snapshot: as it was going,
+/// and with the network delays and losses drawn from `branch_seed` instead, e.g. to see if a
+/// liveness stall that follows depends on the schedule.
+pub fn branch(
+    scenario: &Scenario,
+    seed: u64,
+    time: i64,
+    branch_seed: u64,
+) -> (RunReport, RunReport) {
+    let scenario = with_seed(scenario, seed);
+    let mut sim = scenario.build_simulator();
+    sim.enable_bandwidth_accounting(scenario.network.bandwidth_interval);
+    sim.loop_until(GlobalTime(time), None);
+    let mut branch = sim.snapshot();
+    branch.reseed(branch_seed);
+    (
+        run_simulator(&mut sim, &scenario, seed),
+        run_simulator(&mut branch, &scenario, seed),
+    )
+}
+
+/// Run a scenario until `time` and save a snapshot of the run to a file, so that `resume` can
+/// finish it later, e.g. in another process.
+pub fn save_snapshot(scenario: &Scenario, seed: u64, time: i64, path: &str) -> Result<()> {
+    let scenario = with_seed(scenario, seed);
+    let mut sim = scenario.build_simulator();
+    sim.enable_bandwidth_accounting(scenario.network.bandwidth_interval);
+    sim.loop_until(GlobalTime(time), None);
+    sim.saved_snapshot()
+        .save(path)
+        .map_err(|error| format_err!("{}", error))
+}
+
+/// Finish a run of a scenario from a snapshot saved by `save_snapshot`. The report is the same
+/// as the one of the whole run.
+pub fn resume(scenario: &Scenario, path: &str) -> Result<RunReport> {
+    let snapshot = SimulationSnapshot::load(path).map_err(|error| format_err!("{}", error))?;
+    let scenario = with_seed(scenario, snapshot.seed);
+    let mut sim = scenario.build_simulator();
+    sim.enable_bandwidth_accounting(scenario.network.bandwidth_interval);
+    sim.restore_saved_snapshot(&snapshot)
+        .map_err(|error| format_err!("{}", error))?;
+    Ok(run_simulator(&mut sim, &scenario, snapshot.seed))
+}
+
+/// Run a scenario once for each of the given seeds.
+pub fn analyze<I>(scenario: &Scenario, seeds: I) -> AnalysisReport
+where
+    I: IntoIterator<Item = u64>,
+{
+    let reports: Vec<_> = seeds
+        .into_iter()
+        .map(|seed| run(scenario, Some(seed)))
+        .collect();
+    let commits: Vec<_> = reports
+        .iter()
+        .flat_map(|report| report.commits.iter().cloned())
+        .collect();
+    AnalysisReport {
+        runs: reports.len(),
+        min_commits: commits.iter().cloned().min().unwrap_or(0),
+        mean_commits: commits.iter().sum::<usize>() as f64 / std::cmp::max(commits.len(), 1) as f64,
+        max_commits: commits.iter().cloned().max().unwrap_or(0),
+        inconsistent_seeds: reports
+            .iter()
+            .filter(|report| !report.consistent)
+            .map(|report| report.seed)
+            .collect(),
+    }
+}
+
+/// Derive a random variant of `base`: other network parameters and arbitrary crash faults.
+/// Safety must hold whatever the number of crashes.
+fn mutate(base: &Scenario, rng: &mut StdRng) -> Scenario {
+    let mut scenario = with_seed(base, rng.gen());
+    scenario.network.mean = rng.gen_range(1.0, 4.0 * base.network.mean);
+    scenario.network.variance = rng.gen_range(0.0, 4.0 * base.network.variance);
+    for _ in 0..rng.gen_range(0, base.nodes + 1) {
+        let start = rng.gen_range(0, base.max_clock);
+        scenario.faults.push(CrashFault {
+            node: rng.gen_range(0, base.nodes),
+            start,
+            end: if rng.gen() {
+                Some(rng.gen_range(start + 1, base.max_clock + 1))
+            } else {
+                None
+            },
+        });
+    }
+    scenario
+}
+
+/// Run random variants of a scenario, derived from `seed`, and collect the safety violations.
+pub fn fuzz(base: &Scenario, seed: u64, iterations: usize) -> FuzzReport {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let failures = (0..iterations)
+        .map(|_| mutate(base, &mut rng))
+        .filter(|scenario| !run(scenario, None).consistent)
+        .collect();
+    FuzzReport {
+        iterations,
+        failures,
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checkpoints of the commits of a node: a rolling hash of all the committed states, signed
+//! every `interval` rounds, so that long runs can cheaply check that replicas agree on their
+//! whole history and not only on their latest commits.
+
+use super::*;
+use hashing::{HashFunction, Hasher};
+use node::NodeState;
+use serde::{Deserialize, Serialize};
+use serialization::CanonicalEncode;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[path = "unit_tests/checkpoints_tests.rs"]
+mod checkpoints_tests;
+
+/// Digest of all the states committed by a node up to a block, signed by the node.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Epoch and round of the latest committed block covered by the checkpoint.
+    pub epoch_id: EpochId,
+    pub round: Round,
+    /// Number of commits covered by the checkpoint.
+    pub height: usize,
+    /// Rolling hash of the committed states, see `CheckpointLog::add_commit`.
+    pub digest: u64,
+    pub author: Author,
+    pub signature: Signature,
+}
+
+impl Checkpoint {
+    /// Hash signed by the author of the checkpoint.
+    pub fn signing_hash(&self, chain_id: ChainId, hash_function: HashFunction) -> u64 {
+        let mut bytes = Vec::new();
+        chain_id.encode(&mut bytes);
+        self.epoch_id.encode(&mut bytes);
+        self.round.encode(&mut bytes);
+        self.height.encode(&mut bytes);
+        self.digest.encode(&mut bytes);
+        self.author.encode(&mut bytes);
+        hash_function.hash_in_domain("checkpoint", &bytes)
+    }
+
+    pub fn verify(
+        &self,
+        key: PublicKey,
+        chain_id: ChainId,
+        hash_function: HashFunction,
+    ) -> Result<()> {
+        ensure!(
+            key.author == self.author,
+            "Checkpoints must be signed by their author."
+        );
+        self.signature
+            .check_with_key(self.signing_hash(chain_id, hash_function), key)
+    }
+}
+
+/// Rolling hash of the states committed by a node, and the checkpoints taken so far.
+#[derive(Clone, Debug)]
+pub struct CheckpointLog {
+    /// Number of rounds between two checkpoints.
+    interval: usize,
+    digest: u64,
+    height: usize,
+    /// Epoch and multiple of `interval` of the latest checkpoint, if any.
+    latest_period: Option<(EpochId, usize)>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl CheckpointLog {
+    pub fn new(interval: usize) -> Self {
+        assert!(interval > 0, "Checkpoints need a positive interval");
+        CheckpointLog {
+            interval,
+            digest: 0,
+            height: 0,
+            latest_period: None,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Rolling hash of the states committed so far.
+    pub fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Fold the state of a new commit into the rolling hash. The first commit at or after each
+    /// multiple of `interval` rounds in an epoch is checkpointed and signed with `key`.
+    pub fn add_commit(
+        &mut self,
+        epoch_id: EpochId,
+        round: Round,
+        state: &State,
+        key: PublicKey,
+        chain_id: ChainId,
+        hash_function: HashFunction,
+    ) -> Option<&Checkpoint> {
+        let mut bytes = Vec::new();
+        self.digest.encode(&mut bytes);
+        state.encode(&mut bytes);
+        self.digest = hash_function.hash_in_domain("committed_state", &bytes);
+        self.height += 1;
+        let period = (epoch_id, round.0 / self.interval);
+        if self.latest_period.is_some_and(|latest| latest >= period) {
+            return None;
+        }
+        self.latest_period = Some(period);
+        let mut checkpoint = Checkpoint {
+            epoch_id,
+            round,
+            height: self.height,
+            digest: self.digest,
+            author: key.author,
+            signature: Signature(0),
+        };
+        checkpoint.signature =
+            Signature::sign_with_key(checkpoint.signing_hash(chain_id, hash_function), key);
+        self.checkpoints.push(checkpoint);
+        self.checkpoints.last()
+    }
+}
+
+/// Outcome of comparing the checkpoints of several replicas.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct CheckpointReport {
+    /// Number of checkpoints taken by all the replicas.
+    pub checkpoints: usize,
+    /// Number of checkpoints compared with the one of at least another replica at the same
+    /// epoch and round.
+    pub cross_checked: usize,
+    /// Number of checkpoints whose signature did not verify.
+    pub invalid_signatures: usize,
+    /// Number of checkpoints not verified because no replica knew the keys of their epoch any
+    /// more.
+    pub unverified: usize,
+    /// Number of replicas left out because they synchronized from a snapshot, so that their
+    /// digests only cover the commits after the snapshot.
+    pub skipped_replicas: usize,
+    /// Epoch and round of the checkpoints on which replicas disagree, either on the digest or
+    /// on the number of commits.
+    pub mismatches: Vec<(EpochId, Round)>,
+}
+
+/// Verify the signatures of the checkpoints of the given nodes and compare the checkpoints
+/// taken at the same epoch and round. Nodes that committed the same block committed the same
+/// history before it, unless they synchronized from a snapshot.
+pub fn cross_check(nodes: &[&NodeState]) -> CheckpointReport {
+    let mut report = CheckpointReport::default();
+    let mut digests = BTreeMap::new();
+    for node in nodes {
+        if node.snapshot_time().is_some() {
+            report.skipped_replicas += 1;
+            continue;
+        }
+        for checkpoint in node.checkpoints() {
+            report.checkpoints += 1;
+            let key = nodes.iter().find_map(|node| {
+                node.configuration_at(checkpoint.epoch_id)
+                    .map(|configuration| configuration.public_key(checkpoint.author))
+            });
+            match key {
+                Some(key) => {
+                    if checkpoint
+                        .verify(key, node.chain_id(), node.hash_function())
+                        .is_err()
+                    {
+                        report.invalid_signatures += 1;
+                    }
+                }
+                None => report.unverified += 1,
+            }
+            digests
+                .entry((checkpoint.epoch_id, checkpoint.round))
+                .or_insert_with(Vec::new)
+                .push((checkpoint.height, checkpoint.digest));
+        }
+    }
+    for (position, digests) in digests {
+        if digests.len() > 1 {
+            report.cross_checked += digests.len();
+        }
+        if digests.iter().any(|entry| *entry != digests[0]) {
+            report.mismatches.push(position);
+        }
+    }
+    report
+}
@@ -0,0 +1,380 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Clients sending requests to validators and waiting for their commit. Validators that are
+//! not the leader redirect requests to the leader they know, crashed validators never answer,
+//! and clients retry elsewhere after a timeout, so that the latency seen by clients includes
+//! leader discovery and failover. Validators answer with a `LeaderHint`, which clients use to
+//! route their next requests.
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, RunOutcome, StopConditions, StopReason};
+use introspection::LeaderHint;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use scenario::{ClientRequestParameters, LeaderDiscovery, NodeSimulator};
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "unit_tests/clients_tests.rs"]
+mod clients_tests;
+
+/// What the clients observed during a run.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct ClientRequestStats {
+    /// Number of requests started by all clients.
+    pub requests: usize,
+    /// Number of requests committed and answered.
+    pub completed: usize,
+    /// Number of requests given up after `max_attempts`.
+    pub failed: usize,
+    /// Number of times that requests were tried, i.e. the first tries and the retries after a
+    /// timeout.
+    pub attempts: usize,
+    /// Number of redirects received from validators that were not the leader.
+    pub redirects: usize,
+    /// Number of times that requests reached a validator that was up.
+    pub arrivals: usize,
+    /// Number of times that requests reached a validator that was up but expected another
+    /// leader.
+    pub misdirected: usize,
+    /// Number of hints ignored by clients because they already knew a later round.
+    pub stale_hints: usize,
+    /// Number of times that clients stopped waiting for a validator.
+    pub timeouts: usize,
+    /// Mean time between the start of a request and the response, if any was completed.
+    pub mean_latency: Option<Duration>,
+    pub max_latency: Option<Duration>,
+}
+
+impl ClientRequestStats {
+    /// Fraction of the requests reaching a validator that reached the wrong one, if any
+    /// reached a validator.
+    pub fn misdirection_rate(&self) -> Option<f64> {
+        if self.arrivals == 0 {
+            None
+        } else {
+            Some(self.misdirected as f64 / self.arrivals as f64)
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ClientState {
+    /// Waiting to start the next request.
+    Thinking,
+    /// The request is on its way to a validator, after a redirect or not.
+    Sending { node: Author, redirected: bool },
+    /// A redirect to the leader of the hint is on its way back to the client.
+    Redirected(LeaderHint),
+    /// Waiting for one of the validators that accepted the request to see it committed, or
+    /// for the timeout of the latest attempt.
+    Pending { node: Author, deadline: i64 },
+    /// The response is on its way back to the client, with the hint of the validator.
+    Responding(LeaderHint),
+    /// All the requests of the client are done.
+    Done,
+}
+
+#[derive(Debug)]
+struct Client {
+    state: ClientState,
+    /// Time of the next step of the client, if any.
+    next_time: Option<i64>,
+    remaining_requests: usize,
+    /// Latest hint received, if it was not invalidated by a timeout.
+    hint: Option<LeaderHint>,
+    /// Validator to send the next request to, with sticky leader discovery and no hint.
+    fallback: Author,
+    /// Start of the current request.
+    request_start: i64,
+    /// Number of times the current request was sent.
+    attempts: usize,
+    /// Commands submitted for the current request, with the validator that accepted them.
+    accepted: Vec<(Author, Command)>,
+}
+
+/// Clients driving a simulation, see `SimulatedClients::run`.
+#[derive(Debug)]
+pub struct SimulatedClients {
+    parameters: ClientRequestParameters,
+    num_nodes: usize,
+    clients: Vec<Client>,
+    rng: StdRng,
+    stats: ClientRequestStats,
+    total_latency: Duration,
+}
+
+impl SimulatedClients {
+    /// Clients sending requests to the first `num_nodes` replicas, starting at time 0.
+    pub fn new(parameters: &ClientRequestParameters, num_nodes: usize, seed: u64) -> Self {
+        let clients = (0..parameters.count)
+            .map(|index| Client {
+                state: ClientState::Thinking,
+                next_time: if parameters.requests > 0 {
+                    Some(0)
+                } else {
+                    None
+                },
+                remaining_requests: parameters.requests,
+                hint: None,
+                fallback: Author(index % num_nodes),
+                request_start: 0,
+                attempts: 0,
+                accepted: Vec::new(),
+            })
+            .collect();
+        SimulatedClients {
+            parameters: parameters.clone(),
+            num_nodes,
+            clients,
+            rng: StdRng::seed_from_u64(seed),
+            stats: ClientRequestStats::default(),
+            total_latency: 0,
+        }
+    }
+
+    /// What the clients observed so far.
+    pub fn stats(&self) -> ClientRequestStats {
+        let mut stats = self.stats.clone();
+        if stats.completed > 0 {
+            stats.mean_latency = Some(self.total_latency / stats.completed as Duration);
+        }
+        stats
+    }
+
+    /// Whether every client is done with all its requests.
+    pub fn is_done(&self) -> bool {
+        self.clients.iter().all(|client| client.next_time.is_none())
+    }
+
+    /// Same as `Simulator::run_until`, with the clients acting between the events of the
+    /// simulation.
+    pub fn run(
+        &mut self,
+        sim: &mut NodeSimulator,
+        max_clock: GlobalTime,
+        conditions: &StopConditions,
+    ) -> RunOutcome {
+        self.run_with_observer(sim, max_clock, conditions, |_, _| ())
+    }
+
+    /// Same as `run` but calls `observer` after each processed event, see
+    /// `Simulator::run_until_with_observer`.
+    pub fn run_with_observer<F>(
+        &mut self,
+        sim: &mut NodeSimulator,
+        max_clock: GlobalTime,
+        conditions: &StopConditions,
+        mut observer: F,
+    ) -> RunOutcome
+    where
+        F: FnMut(&NodeSimulator, GlobalTime),
+    {
+        let start = std::time::Instant::now();
+        loop {
+            let until = match self.next_time() {
+                Some(time) if time < max_clock.0 => GlobalTime(time),
+                _ => max_clock,
+            };
+            let mut conditions = conditions.clone();
+            conditions.wall_clock = conditions
+                .wall_clock
+                .map(|budget| budget.checked_sub(start.elapsed()).unwrap_or_default());
+            let outcome = sim.run_until_with_observer(until, None, &conditions, &mut observer);
+            match outcome.reason {
+                StopReason::SimulatedTime | StopReason::NoMoreEvents if until < max_clock => {
+                    self.step(sim, until.0)
+                }
+                _ => return outcome,
+            }
+        }
+    }
+
+    fn next_time(&self) -> Option<i64> {
+        self.clients
+            .iter()
+            .filter_map(|client| client.next_time)
+            .min()
+    }
+
+    /// Process every step of the clients due at `time`.
+    fn step(&mut self, sim: &mut NodeSimulator, time: i64) {
+        for index in 0..self.clients.len() {
+            if self.clients[index].next_time == Some(time) {
+                self.step_client(sim, index, time);
+            }
+        }
+    }
+
+    fn step_client(&mut self, sim: &mut NodeSimulator, index: usize, time: i64) {
+        let link_delay = self.parameters.link_delay;
+        let state = std::mem::replace(&mut self.clients[index].state, ClientState::Done);
+        match state {
+            ClientState::Thinking => {
+                self.stats.requests += 1;
+                let client = &mut self.clients[index];
+                client.remaining_requests -= 1;
+                client.request_start = time;
+                client.attempts = 0;
+                client.accepted.clear();
+                let target = self.choose_target(index, None);
+                self.send(index, target, time);
+            }
+            ClientState::Sending { node, redirected } => {
+                self.arrive(sim, index, node, redirected, time)
+            }
+            ClientState::Redirected(hint) => {
+                self.learn(index, hint);
+                let client = &mut self.clients[index];
+                client.state = ClientState::Sending {
+                    node: hint.leader.expect("Redirects name a leader"),
+                    redirected: true,
+                };
+                client.next_time = Some(time + link_delay);
+            }
+            ClientState::Pending { node, deadline } => {
+                if let Some(hint) = self.committed_hint(sim, index, time) {
+                    let client = &mut self.clients[index];
+                    client.state = ClientState::Responding(hint);
+                    client.next_time = Some(time + link_delay);
+                } else if time >= deadline {
+                    self.stats.timeouts += 1;
+                    let target = self.choose_target(index, Some(node));
+                    self.send(index, target, time);
+                } else {
+                    let client = &mut self.clients[index];
+                    client.state = ClientState::Pending { node, deadline };
+                    client.next_time =
+                        Some(std::cmp::min(time + self.parameters.poll_period, deadline));
+                }
+            }
+            ClientState::Responding(hint) => {
+                self.learn(index, hint);
+                let latency = time - self.clients[index].request_start;
+                self.stats.completed += 1;
+                self.total_latency += latency;
+                self.stats.max_latency = std::cmp::max(self.stats.max_latency, Some(latency));
+                self.next_request(index, time);
+            }
+            ClientState::Done => unreachable!("Clients that are done have no next step"),
+        }
+    }
+
+    /// Validator to send the current request to, after a timeout of `timed_out` if any.
+    fn choose_target(&mut self, index: usize, timed_out: Option<Author>) -> Author {
+        match self.parameters.discovery {
+            LeaderDiscovery::Sticky => {
+                let client = &mut self.clients[index];
+                if let Some(node) = timed_out {
+                    client.hint = None;
+                    client.fallback = Author((node.0 + 1) % self.num_nodes);
+                }
+                client
+                    .hint
+                    .and_then(|hint| hint.leader)
+                    .unwrap_or(client.fallback)
+            }
+            LeaderDiscovery::Random => Author(self.rng.gen_range(0, self.num_nodes)),
+        }
+    }
+
+    /// Send the current request to `node`, unless the client already used all its attempts.
+    fn send(&mut self, index: usize, node: Author, time: i64) {
+        if self.clients[index].attempts >= self.parameters.max_attempts {
+            self.stats.failed += 1;
+            self.next_request(index, time);
+            return;
+        }
+        self.stats.attempts += 1;
+        let client = &mut self.clients[index];
+        client.attempts += 1;
+        client.state = ClientState::Sending {
+            node,
+            redirected: false,
+        };
+        client.next_time = Some(time + self.parameters.link_delay);
+    }
+
+    /// The current request reaches `node`, which redirects it, accepts it, or never answers.
+    /// Requests are redirected at most once, since the leader may change again before they
+    /// reach the next one: the validator keeps them until it proposes.
+    fn arrive(
+        &mut self,
+        sim: &mut NodeSimulator,
+        index: usize,
+        node: Author,
+        redirected: bool,
+        time: i64,
+    ) {
+        // The timeout of the attempt runs from the time when the request was first sent.
+        let mut deadline = time - self.parameters.link_delay + self.parameters.retry_timeout;
+        if redirected {
+            deadline -= 2 * self.parameters.link_delay;
+        }
+        let replica = sim.simulated_node_mut(node);
+        if !replica.is_crashed(GlobalTime(time)) {
+            self.stats.arrivals += 1;
+            let hint = replica.node().node_state().leader_hint();
+            let misdirected = hint.leader.is_some_and(|leader| leader != node);
+            if misdirected {
+                self.stats.misdirected += 1;
+            }
+            if misdirected && !redirected {
+                self.stats.redirects += 1;
+                let client = &mut self.clients[index];
+                client.state = ClientState::Redirected(hint);
+                client.next_time = Some(time + self.parameters.link_delay);
+                return;
+            }
+            let command = replica.context_mut().submit();
+            self.clients[index].accepted.push((node, command));
+            self.learn(index, hint);
+        }
+        let client = &mut self.clients[index];
+        client.state = ClientState::Pending { node, deadline };
+        client.next_time = Some(std::cmp::min(time + self.parameters.poll_period, deadline));
+    }
+
+    /// The hint of a validator that accepted the current request, is still up, and committed
+    /// it, if any.
+    fn committed_hint(&self, sim: &NodeSimulator, index: usize, time: i64) -> Option<LeaderHint> {
+        self.clients[index]
+            .accepted
+            .iter()
+            .map(|(node, command)| (sim.simulated_node(*node), command))
+            .find(|(replica, command)| {
+                !replica.is_crashed(GlobalTime(time))
+                    && replica
+                        .context()
+                        .committed_history()
+                        .iter()
+                        .rev()
+                        .any(|(committed, _)| committed == *command)
+            })
+            .map(|(replica, _)| replica.node().node_state().leader_hint())
+    }
+
+    /// Keep the hint of a validator unless the client already knows a later round.
+    fn learn(&mut self, index: usize, hint: LeaderHint) {
+        let client = &mut self.clients[index];
+        match client.hint {
+            Some(known) if !hint.is_newer_than(&known) => {
+                if hint != known {
+                    self.stats.stale_hints += 1;
+                }
+            }
+            _ => client.hint = Some(hint),
+        }
+    }
+
+    fn next_request(&mut self, index: usize, time: i64) {
+        let client = &mut self.clients[index];
+        if client.remaining_requests > 0 {
+            client.state = ClientState::Thinking;
+            client.next_time = Some(time + self.parameters.think_time);
+        } else {
+            client.state = ClientState::Done;
+            client.next_time = None;
+        }
+    }
+}
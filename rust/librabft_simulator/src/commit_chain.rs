@@ -0,0 +1,339 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Self-contained proofs of the commits of a node, across epochs, for external auditing: the
+//! chain of certified blocks of each epoch, from its initial QC up to its highest commit
+//! certificate, and a verifier that checks them from the voting rights of the first epoch.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use hashing::{HashFunction, Hasher};
+use node::NodeState;
+use record::{verify_quorum_certificate, Block, QuorumCertificate, Record, VerifyError};
+use scenario::Scenario;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[cfg(test)]
+#[path = "unit_tests/commit_chain_tests.rs"]
+mod commit_chain_tests;
+
+/// A block and the QC that certifies it.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CertifiedBlock {
+    pub block: Block,
+    pub certificate: QuorumCertificate,
+}
+
+/// The chain of certified blocks of an epoch that ends with its highest commit certificate, by
+/// increasing rounds. The chain is empty if nothing was committed with a certificate in this
+/// epoch, e.g. under commit rules that do not certify commits.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EpochCommitChain {
+    pub epoch_id: EpochId,
+    /// Voting rights and keys in this epoch.
+    pub configuration: EpochConfiguration,
+    pub chain: Vec<CertifiedBlock>,
+    /// Round of the committed block whose state started the next epoch, if any.
+    pub end_round: Option<Round>,
+}
+
+/// The commit chains of all the epochs that a node went through.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CommitChain {
+    /// Hash function and network of the records of the run.
+    #[serde(default)]
+    pub hash_function: HashFunction,
+    #[serde(default)]
+    pub chain_id: ChainId,
+    pub epochs: Vec<EpochCommitChain>,
+}
+
+/// Reasons for rejecting a commit chain.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum ChainError {
+    /// Epochs must start with the first one, with the genesis configuration, and increase.
+    UnexpectedEpoch {
+        previous: Option<EpochId>,
+        found: EpochId,
+    },
+    UnexpectedGenesis,
+    /// The block of the given round does not extend the previous QC, or the QC does not
+    /// certify the block.
+    BrokenLink {
+        epoch_id: EpochId,
+        round: Round,
+    },
+    InvalidBlock {
+        epoch_id: EpochId,
+        round: Round,
+    },
+    InvalidCertificate {
+        epoch_id: EpochId,
+        round: Round,
+        error: VerifyError,
+    },
+    /// The chain does not end with a certificate committing one of its blocks.
+    UncommittedChain {
+        epoch_id: EpochId,
+    },
+    /// Another epoch follows although no committed state of this one starts a new epoch.
+    MissingEpochChange {
+        epoch_id: EpochId,
+    },
+    /// The block ending the epoch is not committed by the chain.
+    UncommittedEpochChange {
+        epoch_id: EpochId,
+        round: Round,
+    },
+    /// The voting rights of the epoch were not announced by the commit certificate ending the
+    /// previous one.
+    UncertifiedConfiguration {
+        epoch_id: EpochId,
+    },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::UnexpectedEpoch { previous, found } => {
+                write!(f, "{:?} cannot follow {:?}.", found, previous)
+            }
+            ChainError::UnexpectedGenesis => {
+                write!(
+                    f,
+                    "The first epoch does not have the genesis configuration."
+                )
+            }
+            ChainError::BrokenLink { epoch_id, round } => {
+                write!(f, "The chain of {:?} is broken at {:?}.", epoch_id, round)
+            }
+            ChainError::InvalidBlock { epoch_id, round } => {
+                write!(f, "Invalid block at {:?} in {:?}.", round, epoch_id)
+            }
+            ChainError::InvalidCertificate {
+                epoch_id,
+                round,
+                error,
+            } => write!(f, "Invalid QC at {:?} in {:?}: {}", round, epoch_id, error),
+            ChainError::UncommittedChain { epoch_id } => write!(
+                f,
+                "The chain of {:?} does not end with a commit certificate.",
+                epoch_id
+            ),
+            ChainError::MissingEpochChange { epoch_id } => {
+                write!(f, "No committed state ends {:?}.", epoch_id)
+            }
+            ChainError::UncommittedEpochChange { epoch_id, round } => write!(
+                f,
+                "The block of {:?} ending {:?} is not committed.",
+                round, epoch_id
+            ),
+            ChainError::UncertifiedConfiguration { epoch_id } => write!(
+                f,
+                "The voting rights of {:?} were not announced by the previous epoch.",
+                epoch_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// The certified chains of all the epochs of a node, ending with the highest commit
+/// certificate of each epoch.
+pub fn export_commit_chain(node: &NodeState) -> CommitChain {
+    let epochs = (0..=node.epoch_id().0)
+        .filter_map(|index| {
+            let epoch_id = EpochId(index);
+            let store = node.record_store_at(epoch_id)?;
+            let mut chain = Vec::new();
+            let mut certificate = store.highest_commit_certificate();
+            while let Some(qc) = certificate {
+                let block = store.block(qc.certified_block_hash).unwrap();
+                chain.push(CertifiedBlock {
+                    block: block.clone(),
+                    certificate: qc.clone(),
+                });
+                certificate = if block.previous_quorum_certificate_hash == epoch_id.initial_hash() {
+                    None
+                } else {
+                    store.quorum_certificate(block.previous_quorum_certificate_hash)
+                };
+            }
+            chain.reverse();
+            // The last commit of a past epoch started the next one.
+            let end_round = if epoch_id < node.epoch_id() {
+                node.commits()
+                    .iter()
+                    .rev()
+                    .find(|commit| commit.epoch == index)
+                    .map(|commit| commit.round)
+            } else {
+                None
+            };
+            Some(EpochCommitChain {
+                epoch_id,
+                configuration: node.configuration_at(epoch_id)?.clone(),
+                chain,
+                end_round,
+            })
+        })
+        .collect();
+    CommitChain {
+        hash_function: node.hash_function(),
+        chain_id: node.chain_id(),
+        epochs,
+    }
+}
+
+/// Run a scenario from a seed and export the commit chain of the given replica.
+pub fn export_from_run(scenario: &Scenario, seed: u64, replica: usize) -> CommitChain {
+    let mut scenario = scenario.clone();
+    scenario.seed = Some(seed);
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    export_commit_chain(sim.replicas()[replica].node().node_state())
+}
+
+/// Verify the links, signatures, and quorums of a commit chain, starting with the voting rights
+/// of the first epoch, and return the commits that it proves, as in the commit logs of nodes.
+/// The voting rights of later epochs are taken from the chain: their epoch change is certified
+/// by the previous epoch, but simulated states are opaque hashes that do not commit to voting
+/// rights, so that auditors must check them separately, e.g. against the stake changes of the
+/// scenario.
+pub fn verify_commit_chain(
+    commit_chain: &CommitChain,
+    genesis: &EpochConfiguration,
+) -> std::result::Result<Vec<Commit<BlockHash, State>>, ChainError> {
+    let mut commits = Vec::new();
+    let mut previous = None;
+    for (index, epoch) in commit_chain.epochs.iter().enumerate() {
+        let epoch_id = epoch.epoch_id;
+        let expected = previous.map_or(epoch_id == EpochId(0), |previous| epoch_id > previous);
+        if !expected {
+            return Err(ChainError::UnexpectedEpoch {
+                previous,
+                found: epoch_id,
+            });
+        }
+        if index == 0 && epoch.configuration != *genesis {
+            return Err(ChainError::UnexpectedGenesis);
+        }
+        if index > 0 {
+            check_epoch_change(&commit_chain.epochs[index - 1], epoch)?;
+        }
+        let mut committed =
+            verify_epoch_chain(epoch, commit_chain.chain_id, &commit_chain.hash_function)?;
+        match epoch.end_round {
+            Some(round) => {
+                // Blocks committed after the epoch change are ignored.
+                let end = committed
+                    .iter()
+                    .position(|commit| commit.round == round)
+                    .ok_or(ChainError::UncommittedEpochChange { epoch_id, round })?;
+                committed.truncate(end + 1);
+            }
+            None if index + 1 < commit_chain.epochs.len() => {
+                return Err(ChainError::MissingEpochChange { epoch_id });
+            }
+            None => (),
+        }
+        commits.extend(committed);
+        previous = Some(epoch_id);
+    }
+    Ok(commits)
+}
+
+/// Check the voting rights of `epoch` against the digest announced by the last certificate of
+/// `previous`, when the latter is the epoch just before and ended with a certified commit.
+fn check_epoch_change(
+    previous: &EpochCommitChain,
+    epoch: &EpochCommitChain,
+) -> std::result::Result<(), ChainError> {
+    let last = match previous.chain.last() {
+        Some(last) if previous.epoch_id.0 + 1 == epoch.epoch_id.0 => last,
+        _ => return Ok(()),
+    };
+    match last.certificate.next_epoch {
+        Some(next_epoch)
+            if next_epoch.epoch_id == epoch.epoch_id
+                && next_epoch.certifies(&epoch.configuration) =>
+        {
+            Ok(())
+        }
+        _ => Err(ChainError::UncertifiedConfiguration {
+            epoch_id: epoch.epoch_id,
+        }),
+    }
+}
+
+/// Verify the chain of an epoch and return the commits that it proves.
+fn verify_epoch_chain(
+    epoch: &EpochCommitChain,
+    chain_id: ChainId,
+    hasher: &Hasher,
+) -> std::result::Result<Vec<Commit<BlockHash, State>>, ChainError> {
+    let epoch_id = epoch.epoch_id;
+    let configuration = &epoch.configuration;
+    let mut previous_hash = epoch_id.initial_hash();
+    let mut previous_round = Round(0);
+    for CertifiedBlock { block, certificate } in &epoch.chain {
+        let round = block.round;
+        let block_record = Record::Block(block.clone());
+        if block.previous_quorum_certificate_hash != previous_hash
+            || certificate.certified_block_hash != BlockHash(block_record.digest(hasher))
+            || certificate.epoch_id != epoch_id
+            || certificate.round != round
+            || round <= previous_round
+        {
+            return Err(ChainError::BrokenLink { epoch_id, round });
+        }
+        if configuration.weight(&block.author) == 0
+            || block
+                .signature
+                .check_with_key(
+                    block_record.signing_hash(epoch_id, chain_id, hasher),
+                    configuration.public_key(block.author),
+                )
+                .is_err()
+        {
+            return Err(ChainError::InvalidBlock { epoch_id, round });
+        }
+        verify_quorum_certificate(certificate, configuration, chain_id, hasher).map_err(
+            |error| ChainError::InvalidCertificate {
+                epoch_id,
+                round,
+                error,
+            },
+        )?;
+        previous_hash =
+            QuorumCertificateHash(Record::QuorumCertificate(certificate.clone()).digest(hasher));
+        previous_round = round;
+    }
+    let last = match epoch.chain.last() {
+        Some(last) => last,
+        None => return Ok(Vec::new()),
+    };
+    // The highest commit certificate commits one of the blocks of the chain and its ancestors.
+    let committed_state = last
+        .certificate
+        .committed_state
+        .as_ref()
+        .ok_or(ChainError::UncommittedChain { epoch_id })?;
+    let length = epoch
+        .chain
+        .iter()
+        .position(|link| link.certificate.state == *committed_state)
+        .ok_or(ChainError::UncommittedChain { epoch_id })?
+        + 1;
+    Ok(epoch.chain[..length]
+        .iter()
+        .map(|link| Commit {
+            epoch: epoch_id.0,
+            round: link.certificate.round,
+            block: link.certificate.certified_block_hash,
+            state: link.certificate.state.clone(),
+        })
+        .collect())
+}
@@ -0,0 +1,164 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rules deciding when a chain of QCs commits a block, so that commit latencies and safety can
+//! be compared within the same simulations.
+
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[cfg(test)]
+#[path = "unit_tests/commit_rule_tests.rs"]
+mod commit_rule_tests;
+
+pub trait CommitRule: Debug + Send + Sync {
+    /// Number of QCs in a commit chain. The oldest QC certifies the committed block and the
+    /// newest one is the commit certificate.
+    fn chain_length(&self) -> usize;
+
+    /// Whether QCs at the given rounds, from the oldest to the newest, form a commit chain.
+    fn is_commit_chain(&self, rounds: &[Round]) -> bool;
+
+    /// Whether votes certify the state committed by the block they vote for, so that a single
+    /// QC proves a commit to clients.
+    fn certifies_commits(&self) -> bool;
+
+    /// Whether QCs at the given rounds form a commit chain on the fast path, that is, when the
+    /// newest QC gathers a super-quorum of votes. By default, chains of at least 3 QCs may be
+    /// one QC shorter. A single QC never commits its block since voting does not lock it.
+    fn is_fast_commit_chain(&self, rounds: &[Round]) -> bool {
+        self.chain_length() > 2 && rounds.len() == self.chain_length() - 1 && consecutive(rounds)
+    }
+
+    /// The round that a node locks on after voting for a block whose QC certifies a block at
+    /// `previous_round`, itself extending a QC at `second_previous_round`. By default, nodes lock
+    /// on the head of 2-chains.
+    fn locked_round(&self, _previous_round: Round, second_previous_round: Round) -> Round {
+        second_previous_round
+    }
+
+    /// Whether a node locked on `locked_round` may vote for a block at `block_round` extending a
+    /// QC at `previous_round`. `highest_timeout_certificate` is the round of the highest TC known
+    /// to the node together with the highest QC round reported in this TC.
+    fn is_safe_to_vote(
+        &self,
+        _block_round: Round,
+        previous_round: Round,
+        locked_round: Round,
+        _highest_timeout_certificate: (Round, Round),
+    ) -> bool {
+        previous_round >= locked_round
+    }
+}
+
+/// Whether the given rounds are consecutive.
+fn consecutive(rounds: &[Round]) -> bool {
+    rounds.windows(2).all(|pair| pair[1] == pair[0] + 1)
+}
+
+/// The rule of LibraBFT: three QCs at consecutive rounds, where the last QC also certifies the
+/// committed state.
+#[derive(Clone, Debug)]
+pub struct LibraBftCommitRule;
+
+impl CommitRule for LibraBftCommitRule {
+    fn chain_length(&self) -> usize {
+        3
+    }
+
+    fn is_commit_chain(&self, rounds: &[Round]) -> bool {
+        rounds.len() == 3 && consecutive(rounds)
+    }
+
+    fn certifies_commits(&self) -> bool {
+        true
+    }
+}
+
+/// The rule of chained HotStuff: three blocks linked to their direct parents, i.e. at
+/// consecutive rounds. Each node decides commits locally from its chain and votes carry no
+/// committed state, so commits cannot be proven with a single QC.
+#[derive(Clone, Debug)]
+pub struct ChainedHotStuffCommitRule;
+
+impl CommitRule for ChainedHotStuffCommitRule {
+    fn chain_length(&self) -> usize {
+        3
+    }
+
+    fn is_commit_chain(&self, rounds: &[Round]) -> bool {
+        rounds.len() == 3 && consecutive(rounds)
+    }
+
+    fn certifies_commits(&self) -> bool {
+        false
+    }
+}
+
+/// The 2-chain rule of Jolteon and DiemBFT v4: two QCs at consecutive rounds. Nodes lock on
+/// their highest QC, hence a block must either extend the QC of the previous round or follow a
+/// TC of the previous round and extend a QC at least as high as any QC reported in that TC.
+#[derive(Clone, Debug)]
+pub struct TwoChainCommitRule;
+
+impl CommitRule for TwoChainCommitRule {
+    fn chain_length(&self) -> usize {
+        2
+    }
+
+    fn is_commit_chain(&self, rounds: &[Round]) -> bool {
+        rounds.len() == 2 && consecutive(rounds)
+    }
+
+    fn certifies_commits(&self) -> bool {
+        true
+    }
+
+    fn locked_round(&self, previous_round: Round, _second_previous_round: Round) -> Round {
+        previous_round
+    }
+
+    fn is_safe_to_vote(
+        &self,
+        block_round: Round,
+        previous_round: Round,
+        _locked_round: Round,
+        highest_timeout_certificate: (Round, Round),
+    ) -> bool {
+        let (tc_round, tc_qc_round) = highest_timeout_certificate;
+        block_round == previous_round + 1
+            || (block_round == tc_round + 1 && previous_round >= tc_qc_round)
+    }
+}
+
+/// Optional fast path: a commit chain may be one QC shorter when its newest QC gathers all the
+/// votes but `missing_votes`. To keep this safe, nodes lock on the QC extended by the blocks
+/// they vote for, and leaders wait up to `wait` for the votes beyond the quorum.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct FastPath {
+    pub missing_votes: usize,
+    pub wait: Duration,
+}
+
+/// Choice of a commit rule in scenario files.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum CommitRuleKind {
+    #[default]
+    #[serde(rename = "librabft")]
+    LibraBft,
+    #[serde(rename = "chained_hotstuff")]
+    ChainedHotStuff,
+    #[serde(rename = "two_chain")]
+    TwoChain,
+}
+
+impl CommitRuleKind {
+    pub fn rule(self) -> Arc<CommitRule> {
+        match self {
+            CommitRuleKind::LibraBft => Arc::new(LibraBftCommitRule),
+            CommitRuleKind::ChainedHotStuff => Arc::new(ChainedHotStuffCommitRule),
+            CommitRuleKind::TwoChain => Arc::new(TwoChainCommitRule),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Subscriptions to the commits of a node, for components that only observe the committed
+//! blocks and would otherwise have to implement a full `SMRContext`.
+
+use super::*;
+use record::QuorumCertificate;
+use std::sync::mpsc;
+
+#[cfg(test)]
+#[path = "unit_tests/commit_stream_tests.rs"]
+mod commit_stream_tests;
+
+/// A block committed by a node, as delivered to the SMR layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitNotification {
+    pub epoch_id: EpochId,
+    pub round: Round,
+    pub block_hash: BlockHash,
+    /// The state after the execution of the block.
+    pub state: State,
+    /// The quorum time of the block (see `ChainQueries::quorum_time`).
+    pub time: NodeTime,
+    /// The commit certificate given to the SMR layer, if any. Only the last block of a batch
+    /// of commits comes with one.
+    pub certificate: Option<QuorumCertificate>,
+}
+
+/// The commits of a node, in order, from the time of the subscription. Dropping the stream
+/// ends the subscription.
+#[derive(Debug)]
+pub struct CommitStream {
+    receiver: mpsc::Receiver<CommitNotification>,
+}
+
+impl CommitStream {
+    /// The next commit, if any happened since the previous call.
+    pub fn try_next(&self) -> Option<CommitNotification> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// All the commits that happened since the previous call.
+    pub fn drain(&self) -> Vec<CommitNotification> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// The underlying channel, e.g. to block on the next commit from another thread.
+    pub fn into_receiver(self) -> mpsc::Receiver<CommitNotification> {
+        self.receiver
+    }
+}
+
+/// The sending ends of the subscriptions of a node. Cloning a node keeps its subscriptions,
+/// so subscribers receive the commits of all the copies.
+#[derive(Clone, Debug, Default)]
+pub struct CommitSubscribers {
+    senders: Vec<mpsc::Sender<CommitNotification>>,
+}
+
+impl CommitSubscribers {
+    pub fn subscribe(&mut self) -> CommitStream {
+        let (sender, receiver) = mpsc::channel();
+        self.senders.push(sender);
+        CommitStream { receiver }
+    }
+
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// Send a commit to every subscriber, and forget the ones whose stream was dropped.
+    pub fn notify(&mut self, notification: &CommitNotification) {
+        self.senders
+            .retain(|sender| sender.send(notification.clone()).is_ok());
+    }
+}
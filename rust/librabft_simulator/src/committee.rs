@@ -0,0 +1,85 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Voting committees, to explore large validator sets where flat all-to-all voting does not
+//! scale: only a stake-weighted sample of the validators votes on the blocks of each round, and
+//! QCs need a quorum of the committee rather than of the whole epoch. Only the committee times
+//! out as well, so that timeout certificates need the same quorum, and the number of votes and
+//! timeouts of a round does not grow with the number of validators. Proposals still reach all
+//! the validators.
+//!
+//! Committees are drawn independently of each other, so quorums of different committees may not
+//! intersect in an honest validator. Safety then only holds with high probability, when each
+//! committee has enough seats for its faulty fraction to stay close to the one of the epoch.
+
+use super::*;
+use serde::{Deserialize, Serialize};
+use serialization::{stable_hash, CanonicalEncode};
+
+#[cfg(test)]
+#[path = "unit_tests/committee_tests.rs"]
+mod committee_tests;
+
+/// How often a new committee is drawn.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitteeRotation {
+    /// A new committee votes at every round, so that an adversary cannot target it in advance.
+    #[default]
+    PerRound,
+    /// The same committee forms all the QCs of an epoch.
+    PerEpoch,
+}
+
+/// Selection of the voters of each round among the validators of an epoch.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct VotingCommittee {
+    /// Number of seats, each drawn with chances proportional to voting rights. Validators
+    /// drawn several times have as many votes in the committee.
+    pub seats: usize,
+    pub rotation: CommitteeRotation,
+}
+
+impl VotingCommittee {
+    pub fn new(seats: usize, rotation: CommitteeRotation) -> Self {
+        assert!(seats > 0, "Committees need at least one seat");
+        VotingCommittee { seats, rotation }
+    }
+
+    /// Voting rights of the committee of the given round, with the resilience and keys of the
+    /// epoch. Every replica draws the same committee, since the draw only depends on the
+    /// configuration, the epoch, and the round.
+    pub fn select(
+        &self,
+        configuration: &EpochConfiguration,
+        epoch_id: EpochId,
+        round: Round,
+    ) -> EpochConfiguration {
+        let round = match self.rotation {
+            CommitteeRotation::PerRound => round,
+            CommitteeRotation::PerEpoch => Round(0),
+        };
+        // Cumulative voting rights, so that each seat is drawn in logarithmic time.
+        let mut authors = Vec::new();
+        let mut cumulative_votes = Vec::new();
+        let mut total_votes = 0;
+        for (author, votes) in configuration.voting_rights() {
+            if *votes > 0 {
+                total_votes += *votes;
+                authors.push(*author);
+                cumulative_votes.push(total_votes);
+            }
+        }
+        let mut seats = BTreeMap::new();
+        for seat in 0..self.seats {
+            // Encoded without the version byte, so that the draws survive encoding changes.
+            let mut bytes = Vec::new();
+            (epoch_id, round, seat).encode(&mut bytes);
+            let target = (stable_hash(&bytes) % total_votes as u64) as usize;
+            let index = cumulative_votes.partition_point(|votes| *votes <= target);
+            *seats.entry(authors[index]).or_insert(0) += 1;
+        }
+        EpochConfiguration::with_resilience(seats, configuration.resilience())
+            .with_key_versions(configuration.key_versions().clone())
+    }
+}
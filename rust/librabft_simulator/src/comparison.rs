@@ -0,0 +1,235 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Side-by-side comparison of variants of a protocol configuration, e.g. different pacemaker
+//! policies, each run with the same seeds. Reports can be rendered as JSON, markdown, or CSV.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use campaign::consistent_histories;
+use experiment::{grid, with_parameters, Statistics, Sweep};
+use rayon::prelude::*;
+use scenario::Scenario;
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "unit_tests/comparison_tests.rs"]
+mod comparison_tests;
+
+/// Output formats of the comparison reports.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ReportFormat {
+    Json,
+    Markdown,
+    Csv,
+}
+
+impl ReportFormat {
+    pub fn parse(text: &str) -> Result<Self> {
+        match text {
+            "json" => Ok(ReportFormat::Json),
+            "markdown" => Ok(ReportFormat::Markdown),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => bail!("Unknown report format {}: try json, markdown, or csv", text),
+        }
+    }
+}
+
+/// Metrics of a single run of a variant.
+#[derive(PartialEq, Clone, Debug)]
+struct RunMetrics {
+    seed: u64,
+    consistent: bool,
+    /// Local time between the proposal and the commit of each command, for all validators.
+    commit_latencies: Vec<f64>,
+    /// Number of commands in the longest committed history.
+    commits: usize,
+    total_bytes: usize,
+}
+
+fn measure(scenario: &Scenario, seed: u64) -> RunMetrics {
+    let mut scenario = scenario.clone();
+    scenario.seed = Some(seed);
+    let mut sim = scenario.build_simulator();
+    sim.enable_bandwidth_accounting(scenario.network.bandwidth_interval);
+    let (consistent, commits) = {
+        let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+        (
+            consistent_histories(&contexts),
+            contexts
+                .iter()
+                .map(|context| context.committed_history().len())
+                .max()
+                .unwrap_or(0),
+        )
+    };
+    let commit_latencies = sim.replicas()[..scenario.nodes]
+        .iter()
+        .flat_map(|replica| replica.node().node_state().commit_latencies())
+        .filter(|(command, _)| command.is_some())
+        .map(|(_, latency)| *latency as f64)
+        .collect();
+    RunMetrics {
+        seed,
+        consistent,
+        commit_latencies,
+        commits,
+        total_bytes: sim.bandwidth().map_or(0, |stats| stats.total_bytes),
+    }
+}
+
+/// Aggregated metrics of a variant over all its runs.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct VariantSummary {
+    pub name: String,
+    pub runs: usize,
+    /// Seeds of the runs that broke safety, if any.
+    pub inconsistent_seeds: Vec<u64>,
+    /// Commit latency of the commands, over all validators and runs.
+    pub commit_latency: Option<Statistics>,
+    /// Commands committed per 1000 units of time in each run.
+    pub throughput: Option<Statistics>,
+    /// Bytes sent on the network per committed command in each run that committed anything.
+    pub bytes_per_commit: Option<Statistics>,
+}
+
+impl VariantSummary {
+    fn new(name: String, max_clock: i64, runs: &[RunMetrics]) -> Self {
+        VariantSummary {
+            name,
+            runs: runs.len(),
+            inconsistent_seeds: runs
+                .iter()
+                .filter(|run| !run.consistent)
+                .map(|run| run.seed)
+                .collect(),
+            commit_latency: Statistics::new(
+                runs.iter()
+                    .flat_map(|run| run.commit_latencies.iter().cloned())
+                    .collect(),
+            ),
+            throughput: Statistics::new(
+                runs.iter()
+                    .map(|run| 1000.0 * run.commits as f64 / max_clock as f64)
+                    .collect(),
+            ),
+            bytes_per_commit: Statistics::new(
+                runs.iter()
+                    .filter(|run| run.commits > 0)
+                    .map(|run| run.total_bytes as f64 / run.commits as f64)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Cells of the row of the variant in tables, see `ComparisonReport::COLUMNS`.
+    fn cells(&self) -> Vec<String> {
+        let format = |statistics: &Option<Statistics>, value: fn(&Statistics) -> f64| {
+            statistics.as_ref().map_or_else(String::new, |statistics| {
+                format!("{:.1}", value(statistics))
+            })
+        };
+        vec![
+            self.name.clone(),
+            self.runs.to_string(),
+            self.inconsistent_seeds.len().to_string(),
+            format(&self.commit_latency, |x| x.p50),
+            format(&self.commit_latency, |x| x.p90),
+            format(&self.commit_latency, |x| x.p99),
+            format(&self.throughput, |x| x.mean),
+            format(&self.bytes_per_commit, |x| x.mean),
+        ]
+    }
+}
+
+/// Summaries of the variants, in the order in which they were given.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct ComparisonReport {
+    pub variants: Vec<VariantSummary>,
+}
+
+impl ComparisonReport {
+    /// Columns of the markdown and CSV tables. Throughput and bytes per command are means over
+    /// runs.
+    pub const COLUMNS: [&'static str; 8] = [
+        "variant",
+        "runs",
+        "unsafe runs",
+        "latency p50",
+        "latency p90",
+        "latency p99",
+        "commands per 1000 time units",
+        "bytes per command",
+    ];
+
+    pub fn to_markdown(&self) -> String {
+        let row = |cells: &[String]| format!("| {} |\n", cells.join(" | "));
+        let mut out = row(&Self::COLUMNS
+            .iter()
+            .map(|column| column.to_string())
+            .collect::<Vec<_>>());
+        out += &row(&vec!["---".to_string(); Self::COLUMNS.len()]);
+        for variant in &self.variants {
+            out += &row(&variant.cells());
+        }
+        out
+    }
+
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(Self::COLUMNS.iter())?;
+        for variant in &self.variants {
+            writer.write_record(variant.cells())?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ReportFormat::Markdown => Ok(self.to_markdown()),
+            ReportFormat::Csv => self.to_csv(),
+        }
+    }
+}
+
+/// The variants of each named scenario over the grid defined by `sweeps`. Variants are named
+/// after their scenario and the values of the swept parameters.
+pub fn variants(
+    scenarios: &[(String, Scenario)],
+    sweeps: &[Sweep],
+) -> Result<Vec<(String, Scenario)>> {
+    let mut variants = Vec::new();
+    for (name, scenario) in scenarios {
+        for parameters in grid(sweeps) {
+            let mut variant_name = name.clone();
+            for (parameter, value) in &parameters {
+                variant_name += &format!(" {}={}", parameter, value);
+            }
+            variants.push((variant_name, with_parameters(scenario, &parameters)?));
+        }
+    }
+    Ok(variants)
+}
+
+/// Run each variant once per seed, in parallel, and summarize the runs of each variant.
+pub fn compare(variants: &[(String, Scenario)], seeds: &[u64]) -> Result<ComparisonReport> {
+    ensure!(!seeds.is_empty(), "Comparisons need at least one seed.");
+    let jobs: Vec<_> = variants
+        .iter()
+        .flat_map(|(_, scenario)| seeds.iter().map(move |seed| (scenario, *seed)))
+        .collect();
+    let runs: Vec<_> = jobs
+        .par_iter()
+        .map(|(scenario, seed)| measure(scenario, *seed))
+        .collect();
+    Ok(ComparisonReport {
+        variants: variants
+            .iter()
+            .zip(runs.chunks(seeds.len()))
+            .map(|((name, scenario), runs)| {
+                VariantSummary::new(name.clone(), scenario.max_clock, runs)
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,140 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Mapping from simulation runs to the variables of a TLA+ specification of LibraBFT, so that
+//! the traces of the implementation can be checked against the formal spec.
+//!
+//! Traces are exported in the Informal Trace Format (ITF) of Apalache: each state maps every
+//! node to the values of the variables `epoch`, `current_round`, `locked_round`,
+//! `latest_voted_round`, `qc_rounds`, and `committed_round`. Steps that change none of these
+//! variables are stuttering steps in the spec and are omitted.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use node::NodeState;
+use scenario::{NodeSimulator, Scenario};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+#[path = "unit_tests/conformance_tests.rs"]
+mod conformance_tests;
+
+/// Values of the specification variables for one node.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct NodeVariables {
+    pub epoch: EpochId,
+    pub current_round: Round,
+    pub locked_round: Round,
+    pub latest_voted_round: Round,
+    /// Rounds of the QCs known in the current epoch.
+    pub qc_rounds: BTreeSet<Round>,
+    pub committed_round: Round,
+}
+
+impl NodeVariables {
+    pub fn of(node: &NodeState) -> Self {
+        let store = node.record_store();
+        NodeVariables {
+            epoch: node.epoch_id(),
+            current_round: store.current_round(),
+            locked_round: node.locked_round(),
+            latest_voted_round: node.latest_voted_round(),
+            qc_rounds: store.known_quorum_certificate_rounds(),
+            committed_round: store.highest_committed_round(),
+        }
+    }
+}
+
+/// The state of all the nodes after an event of the simulation.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Step {
+    pub clock: GlobalTime,
+    pub nodes: Vec<NodeVariables>,
+}
+
+fn snapshot(sim: &NodeSimulator, clock: GlobalTime) -> Step {
+    Step {
+        clock,
+        nodes: (0..sim.num_nodes())
+            .map(|index| NodeVariables::of(sim.simulated_node(Author(index)).node().node_state()))
+            .collect(),
+    }
+}
+
+/// Run the simulation until `max_clock` and record the states of the nodes, without
+/// stuttering steps.
+pub fn record_steps(sim: &mut NodeSimulator, max_clock: GlobalTime) -> Vec<Step> {
+    let mut steps = vec![snapshot(sim, sim.clock())];
+    sim.loop_until_with_observer(max_clock, None, |sim, clock| {
+        let step = snapshot(sim, clock);
+        if steps.last().unwrap().nodes != step.nodes {
+            steps.push(step);
+        }
+    });
+    steps
+}
+
+fn itf_map<F>(step: &Step, value: F) -> Value
+where
+    F: Fn(&NodeVariables) -> Value,
+{
+    let pairs: Vec<_> = step
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| json!([index, value(node)]))
+        .collect();
+    json!({ "#map": pairs })
+}
+
+/// Encode steps in the Informal Trace Format.
+pub fn to_itf(steps: &[Step], seed: u64) -> Value {
+    let states: Vec<_> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            json!({
+                "#meta": { "index": index },
+                "clock": step.clock.0,
+                "epoch": itf_map(step, |node| json!(node.epoch.0)),
+                "current_round": itf_map(step, |node| json!(node.current_round.0)),
+                "locked_round": itf_map(step, |node| json!(node.locked_round.0)),
+                "latest_voted_round": itf_map(step, |node| json!(node.latest_voted_round.0)),
+                "qc_rounds": itf_map(step, |node| {
+                    let rounds: Vec<_> = node.qc_rounds.iter().map(|round| round.0).collect();
+                    json!({ "#set": rounds })
+                }),
+                "committed_round": itf_map(step, |node| json!(node.committed_round.0)),
+            })
+        })
+        .collect();
+    json!({
+        "#meta": {
+            "format": "ITF",
+            "source": "librabft_simulator",
+            "seed": seed,
+        },
+        "vars": [
+            "clock",
+            "epoch",
+            "current_round",
+            "locked_round",
+            "latest_voted_round",
+            "qc_rounds",
+            "committed_round",
+        ],
+        "states": states,
+    })
+}
+
+/// Run a scenario from a given seed and export its trace in the Informal Trace Format.
+pub fn export_itf(scenario: &Scenario, seed: u64) -> Value {
+    let mut scenario = scenario.clone();
+    scenario.seed = Some(seed);
+    let mut sim = scenario.build_simulator();
+    to_itf(
+        &record_steps(&mut sim, GlobalTime(scenario.max_clock)),
+        seed,
+    )
+}
@@ -0,0 +1,410 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Atomic transactions between two shards, with two-phase commit on top of their chains. A
+//! coordinator submits a prepare operation to both shards. Each shard decides on its chain
+//! whether it prepares the transaction, see `SimulatedContext::transactions`. Once both
+//! shards prepared it, the coordinator submits a commit to both; if one refuses, or either
+//! one takes longer than a timeout, it submits an abort to both instead. Since every phase is
+//! decided by consensus, the transaction commits on both chains or on neither, whatever the
+//! faults of the validators; the faults only delay it.
+//!
+//! The coordinator stands for a client that never fails: it reads the committed ledgers of
+//! the validators that are up, and submits each operation to all the members of its shard
+//! that are up, so that the next leader proposes it. Decisions that do not reach a chain
+//! within the timeout are submitted again. Operations are idempotent, and mempools keep them
+//! until they are committed but skip the ones carried by pending blocks, so that duplicates
+//! are rare and harmless.
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, RunOutcome, StopConditions, StopReason};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use scenario::{CrossShardParameters, Scenario};
+use serde::Serialize;
+use serialization::{stable_hash, to_canonical_bytes};
+use shards::ShardedSimulator;
+
+#[cfg(test)]
+#[path = "unit_tests/cross_shard_tests.rs"]
+mod cross_shard_tests;
+
+/// Commands with at least this index stand for the operations of cross-shard transactions.
+const CROSS_SHARD_COMMANDS: usize = 1 << 40;
+
+/// Phase of a cross-shard transaction, in the order of the protocol.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug, Serialize)]
+pub enum TransactionPhase {
+    Prepare,
+    Commit,
+    Abort,
+}
+
+/// An operation of a cross-shard transaction on the chain of one of its shards. Commands do
+/// not carry a payload, so operations are encoded in the index of their commands.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub struct CrossShardOperation {
+    pub transaction: usize,
+    pub phase: TransactionPhase,
+}
+
+impl CrossShardOperation {
+    /// The command of the operation, as submitted to the given validator.
+    pub fn command(self, proposer: Author) -> Command {
+        Command {
+            proposer,
+            index: CROSS_SHARD_COMMANDS + 3 * self.transaction + self.phase as usize,
+        }
+    }
+
+    /// The operation of a command, if it stands for one.
+    pub fn of(command: &Command) -> Option<Self> {
+        let offset = command.index.checked_sub(CROSS_SHARD_COMMANDS)?;
+        let phase = match offset % 3 {
+            0 => TransactionPhase::Prepare,
+            1 => TransactionPhase::Commit,
+            _ => TransactionPhase::Abort,
+        };
+        Some(CrossShardOperation {
+            transaction: offset / 3,
+            phase,
+        })
+    }
+}
+
+/// The key locked by a transaction, among `keys` keys.
+pub fn lock_key(transaction: usize, keys: u64) -> u64 {
+    stable_hash(&to_canonical_bytes(&transaction)) % keys
+}
+
+/// Status of a cross-shard transaction on the chain of one of its shards.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize)]
+pub enum TransactionStatus {
+    /// The shard voted for the transaction and waits for the decision.
+    Prepared,
+    /// The shard voted against the transaction, whose key was locked.
+    Refused,
+    Committed,
+    Aborted,
+}
+
+/// What the coordinator observed during a run.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct CrossShardStats {
+    /// Number of transactions started.
+    pub transactions: usize,
+    /// Number of transactions committed on both shards.
+    pub committed: usize,
+    /// Number of transactions aborted on both shards.
+    pub aborted: usize,
+    /// Number of transactions aborted because a shard refused to prepare them.
+    pub conflicts: usize,
+    /// Number of transactions aborted because a shard did not prepare them in time.
+    pub timeouts: usize,
+    /// Number of decisions submitted again because a shard did not execute them in time.
+    pub resubmissions: usize,
+    /// Mean time between the start of a committed transaction and the time when both shards
+    /// prepared it, if any was committed.
+    pub mean_prepare_latency: Option<Duration>,
+    /// Mean time between the start of a transaction and its decision on both chains, if any
+    /// was decided.
+    pub mean_latency: Option<Duration>,
+    pub max_latency: Option<Duration>,
+    /// Number of transactions that some validator saw committed and another one aborted at
+    /// the end of the run. Always 0 unless atomicity is broken.
+    pub atomicity_violations: usize,
+}
+
+#[derive(Debug)]
+struct Transaction {
+    /// The two shards of the transaction.
+    shards: [usize; 2],
+    start: i64,
+    /// Time when both shards prepared the transaction, if they did.
+    prepared: Option<i64>,
+    /// Decision of the coordinator, if any, and the time when it was last submitted.
+    decision: Option<(TransactionPhase, i64)>,
+    /// Operations to submit, kept until a member of their shard is up.
+    unsent: Vec<(usize, TransactionPhase)>,
+    done: bool,
+}
+
+/// Coordinator of the cross-shard transactions of a sharded simulation, see
+/// `CrossShardCoordinator::run`.
+#[derive(Debug)]
+pub struct CrossShardCoordinator {
+    parameters: CrossShardParameters,
+    /// Members of each shard.
+    members: Vec<Vec<usize>>,
+    transactions: Vec<Transaction>,
+    rng: StdRng,
+    stats: CrossShardStats,
+    total_prepare_latency: Duration,
+    total_latency: Duration,
+}
+
+impl CrossShardCoordinator {
+    /// Coordinator of the transactions of a sharded scenario, starting at time 0.
+    pub fn new(parameters: &CrossShardParameters, scenario: &Scenario, seed: u64) -> Self {
+        CrossShardCoordinator {
+            parameters: parameters.clone(),
+            members: scenario
+                .shards
+                .iter()
+                .map(|shard| shard.members.clone())
+                .collect(),
+            transactions: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            stats: CrossShardStats::default(),
+            total_prepare_latency: 0,
+            total_latency: 0,
+        }
+    }
+
+    /// What the coordinator observed so far.
+    pub fn stats(&self) -> CrossShardStats {
+        let mut stats = self.stats.clone();
+        if stats.committed > 0 {
+            stats.mean_prepare_latency =
+                Some(self.total_prepare_latency / stats.committed as Duration);
+        }
+        let decided = stats.committed + stats.aborted;
+        if decided > 0 {
+            stats.mean_latency = Some(self.total_latency / decided as Duration);
+        }
+        stats
+    }
+
+    /// Same as `Simulator::run_until`, with the coordinator acting between the events of the
+    /// simulation. Atomicity is checked at the end of the run.
+    pub fn run(
+        &mut self,
+        sim: &mut ShardedSimulator,
+        max_clock: GlobalTime,
+        conditions: &StopConditions,
+    ) -> RunOutcome {
+        let start = std::time::Instant::now();
+        let mut time = 0;
+        let outcome = loop {
+            let until = match self.next_time(time) {
+                Some(next) if next < max_clock.0 => GlobalTime(next),
+                _ => max_clock,
+            };
+            let mut conditions = conditions.clone();
+            conditions.wall_clock = conditions
+                .wall_clock
+                .map(|budget| budget.checked_sub(start.elapsed()).unwrap_or_default());
+            let outcome = sim.run_until(until, &conditions);
+            match outcome.reason {
+                StopReason::SimulatedTime | StopReason::NoMoreEvents if until < max_clock => {
+                    time = until.0;
+                    self.step(sim, time);
+                }
+                _ => break outcome,
+            }
+        };
+        self.stats.atomicity_violations = self.atomicity_violations(sim);
+        outcome
+    }
+
+    /// Time of the next step after `time`: the start of the next transaction, or the next
+    /// poll of the chains while a transaction is not done.
+    fn next_time(&self, time: i64) -> Option<i64> {
+        let next_start = if self.transactions.len() < self.parameters.transactions {
+            Some(self.transactions.len() as i64 * self.parameters.interval)
+        } else {
+            None
+        };
+        let next_poll = if self
+            .transactions
+            .iter()
+            .any(|transaction| !transaction.done)
+        {
+            Some(time + self.parameters.poll_period)
+        } else {
+            None
+        };
+        match (next_start, next_poll) {
+            (Some(start), Some(poll)) => Some(std::cmp::min(start, poll)),
+            (start, poll) => start.or(poll),
+        }
+    }
+
+    fn step(&mut self, sim: &mut ShardedSimulator, time: i64) {
+        while self.transactions.len() < self.parameters.transactions
+            && self.transactions.len() as i64 * self.parameters.interval <= time
+        {
+            self.start(time);
+        }
+        let statuses: Vec<_> = (0..self.members.len())
+            .map(|shard| self.committed_transactions(sim, shard, time))
+            .collect();
+        for index in 0..self.transactions.len() {
+            if !self.transactions[index].done {
+                self.advance(index, &statuses, time);
+            }
+        }
+        for index in 0..self.transactions.len() {
+            let unsent = std::mem::take(&mut self.transactions[index].unsent);
+            for (shard, phase) in unsent {
+                let operation = CrossShardOperation {
+                    transaction: index,
+                    phase,
+                };
+                if !self.submit(sim, shard, operation, time) {
+                    self.transactions[index].unsent.push((shard, phase));
+                }
+            }
+        }
+    }
+
+    /// Start a transaction between two distinct shards.
+    fn start(&mut self, time: i64) {
+        let num_shards = self.members.len();
+        let first = self.rng.gen_range(0, num_shards);
+        let second = (first + self.rng.gen_range(1, num_shards)) % num_shards;
+        self.stats.transactions += 1;
+        self.transactions.push(Transaction {
+            shards: [first, second],
+            start: time,
+            prepared: None,
+            decision: None,
+            unsent: vec![
+                (first, TransactionPhase::Prepare),
+                (second, TransactionPhase::Prepare),
+            ],
+            done: false,
+        });
+    }
+
+    /// Decide a transaction once both shards voted or the timeout expired, and finish it once
+    /// both shards executed the decision.
+    fn advance(
+        &mut self,
+        index: usize,
+        statuses: &[BTreeMap<usize, TransactionStatus>],
+        time: i64,
+    ) {
+        let transaction = &mut self.transactions[index];
+        let [first, second] = transaction.shards.map(|shard| statuses[shard].get(&index));
+        let timeout = self.parameters.timeout;
+        match transaction.decision {
+            None => {
+                let decision = match (first, second) {
+                    (Some(TransactionStatus::Prepared), Some(TransactionStatus::Prepared)) => {
+                        transaction.prepared = Some(time);
+                        TransactionPhase::Commit
+                    }
+                    (Some(TransactionStatus::Refused), _)
+                    | (_, Some(TransactionStatus::Refused)) => {
+                        self.stats.conflicts += 1;
+                        TransactionPhase::Abort
+                    }
+                    _ if time >= transaction.start + timeout => {
+                        self.stats.timeouts += 1;
+                        TransactionPhase::Abort
+                    }
+                    _ => return,
+                };
+                transaction.decision = Some((decision, time));
+                for shard in transaction.shards {
+                    transaction.unsent.push((shard, decision));
+                }
+            }
+            Some((decision, submitted)) => {
+                let expected = match decision {
+                    TransactionPhase::Commit => TransactionStatus::Committed,
+                    _ => TransactionStatus::Aborted,
+                };
+                if first != Some(&expected) || second != Some(&expected) {
+                    if time >= submitted + timeout && transaction.unsent.is_empty() {
+                        for (shard, status) in transaction.shards.iter().zip(&[first, second]) {
+                            if *status != Some(&expected) {
+                                self.stats.resubmissions += 1;
+                                transaction.unsent.push((*shard, decision));
+                            }
+                        }
+                        transaction.decision = Some((decision, time));
+                    }
+                    return;
+                }
+                transaction.done = true;
+                let latency = time - transaction.start;
+                self.total_latency += latency;
+                self.stats.max_latency = std::cmp::max(self.stats.max_latency, Some(latency));
+                if expected == TransactionStatus::Committed {
+                    self.stats.committed += 1;
+                    self.total_prepare_latency += transaction.prepared.unwrap() - transaction.start;
+                } else {
+                    self.stats.aborted += 1;
+                }
+            }
+        }
+    }
+
+    /// Status of the transactions in the longest committed ledger of the members of a shard
+    /// that are up.
+    fn committed_transactions(
+        &self,
+        sim: &ShardedSimulator,
+        shard: usize,
+        time: i64,
+    ) -> BTreeMap<usize, TransactionStatus> {
+        self.members[shard]
+            .iter()
+            .map(|member| sim.simulated_node(Author(*member)))
+            .filter(|replica| !replica.is_crashed(GlobalTime(time)))
+            .filter_map(|replica| replica.context().shard(shard))
+            .max_by_key(|context| context.committed_history().len())
+            .map(|context| context.transactions())
+            .unwrap_or_default()
+    }
+
+    /// Submit an operation to the members of a shard that are up. Return whether any was.
+    fn submit(
+        &mut self,
+        sim: &mut ShardedSimulator,
+        shard: usize,
+        operation: CrossShardOperation,
+        time: i64,
+    ) -> bool {
+        let mut submitted = false;
+        for member in &self.members[shard] {
+            let replica = sim.simulated_node_mut(Author(*member));
+            if !replica.is_crashed(GlobalTime(time)) {
+                replica
+                    .context_mut()
+                    .shard_mut(shard)
+                    .expect("Members run the contexts of their shards")
+                    .submit_operation(operation);
+                submitted = true;
+            }
+        }
+        submitted
+    }
+
+    /// Number of transactions committed by a member of one of their shards and aborted by a
+    /// member of the other one, or of the same one.
+    fn atomicity_violations(&self, sim: &ShardedSimulator) -> usize {
+        let statuses: Vec<Vec<_>> = (0..self.members.len())
+            .map(|shard| {
+                self.members[shard]
+                    .iter()
+                    .filter_map(|member| sim.simulated_node(Author(*member)).context().shard(shard))
+                    .map(|context| context.transactions())
+                    .collect()
+            })
+            .collect();
+        (0..self.transactions.len())
+            .filter(|index| {
+                let observed: Vec<_> = self.transactions[*index]
+                    .shards
+                    .iter()
+                    .flat_map(|shard| &statuses[*shard])
+                    .filter_map(|transactions| transactions.get(index))
+                    .collect();
+                observed.contains(&&TransactionStatus::Committed)
+                    && observed.contains(&&TransactionStatus::Aborted)
+            })
+            .count()
+    }
+}
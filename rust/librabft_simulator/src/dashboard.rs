@@ -0,0 +1,106 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, Simulator};
+use follower::Participant;
+use std::{
+    fmt::Write as FmtWrite,
+    io::{self, Write},
+    thread, time,
+};
+
+#[cfg(test)]
+#[path = "unit_tests/dashboard_tests.rs"]
+mod dashboard_tests;
+
+/// Summary of a node displayed on the dashboard.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct NodeRow {
+    author: Author,
+    epoch_id: EpochId,
+    active_round: Round,
+    locked_round: Round,
+    highest_committed_round: Round,
+    queue_depth: usize,
+}
+
+/// Terminal dashboard redrawn as simulated time advances.
+pub struct Dashboard {
+    /// Simulated time between two frames.
+    refresh_period: Duration,
+    /// Wall-clock pause after each frame, so that humans can follow.
+    frame_delay: time::Duration,
+    /// Simulated time of the next frame.
+    next_refresh: GlobalTime,
+}
+
+impl Dashboard {
+    pub fn new(refresh_period: Duration, frame_delay_ms: u64) -> Self {
+        Dashboard {
+            refresh_period,
+            frame_delay: time::Duration::from_millis(frame_delay_ms),
+            next_refresh: GlobalTime(0),
+        }
+    }
+
+    /// Redraw the dashboard if enough simulated time has passed since the last frame.
+    pub fn observe<Context, Notification, Request, Response>(
+        &mut self,
+        simulator: &Simulator<Participant, Context, Notification, Request, Response>,
+        clock: GlobalTime,
+    ) {
+        if clock < self.next_refresh {
+            return;
+        }
+        self.next_refresh = clock + self.refresh_period;
+        let rows: Vec<_> = (0..simulator.num_nodes())
+            .map(|index| {
+                let author = Author(index);
+                let node = simulator.simulated_node(author).node().node_state();
+                NodeRow {
+                    author,
+                    epoch_id: node.epoch_id(),
+                    active_round: node.active_round(),
+                    locked_round: node.locked_round(),
+                    highest_committed_round: node.record_store().highest_committed_round(),
+                    queue_depth: simulator.pending_network_events(author),
+                }
+            })
+            .collect();
+        // Clear the screen and move the cursor to the top-left corner.
+        let frame = format!("\x1b[2J\x1b[H{}", Self::render(clock, &rows));
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        handle
+            .write_all(frame.as_bytes())
+            .expect("could not draw dashboard");
+        handle.flush().expect("could not draw dashboard");
+        thread::sleep(self.frame_delay);
+    }
+
+    fn render(clock: GlobalTime, rows: &[NodeRow]) -> String {
+        let mut out = String::new();
+        writeln!(out, "Simulated time: {}", clock.0).unwrap();
+        writeln!(
+            out,
+            "{:>6} {:>6} {:>8} {:>8} {:>10} {:>6}",
+            "node", "epoch", "active", "locked", "committed", "queue"
+        )
+        .unwrap();
+        for row in rows {
+            writeln!(
+                out,
+                "{:>6} {:>6} {:>8} {:>8} {:>10} {:>6}",
+                row.author.0,
+                row.epoch_id.0,
+                row.active_round.0,
+                row.locked_round.0,
+                row.highest_committed_round.0,
+                row.queue_depth
+            )
+            .unwrap();
+        }
+        out
+    }
+}
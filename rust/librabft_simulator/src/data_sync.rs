@@ -3,8 +3,18 @@
 
 use super::*;
 use base_types::*;
+use bft_simulator_runtime::{
+    bandwidth::{ByteCounts, MessageSize},
+    inbound_queue::MessagePriority,
+    transport::Message,
+};
+use light_client::LightClient;
 use node::*;
+use proto::{messages, Proto};
+use reconciliation::{InvertibleBloomLookupTable, SketchCell};
 use record::*;
+use record_store::RecordSummary;
+use serialization::{encoded_len, from_canonical_bytes, stable_hash, to_canonical_bytes};
 use smr_context::SMRContext;
 use std::collections::BTreeSet;
 
@@ -33,11 +43,14 @@ pub struct DataSyncNotification {
 pub struct DataSyncRequest {
     /// Current epoch identifier.
     current_epoch: EpochId,
-    /// Selection of rounds for which the receiver already knows a QC.
-    known_quorum_certificates: BTreeSet<Round>,
+    /// Summary of the blocks and QCs that the receiver already knows in its current epoch.
+    summary: RecordSummary,
+    /// Whether the sender bootstraps from a snapshot of the receiver's current epoch rather
+    /// than from the records of all the epochs in between.
+    needs_snapshot: bool,
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone)]
 pub struct DataSyncResponse {
     /// Current epoch identifier.
     current_epoch: EpochId,
@@ -45,14 +58,168 @@ pub struct DataSyncResponse {
     /// Epochs older than the receiver's current epoch will be skipped, as well as chains
     /// of records ending with QC known to the receiver.
     records: Vec<(EpochId, Vec<Record>)>,
+    /// Snapshot of the state starting the sender's current epoch, if requested.
+    snapshot: Option<EpochSnapshot>,
 }
 // -- END FILE --
 
+/// A committed state starting an epoch, for nodes that cannot replay the commits leading to it.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone)]
+pub struct EpochSnapshot {
+    /// Commit certificate of the previous epoch committing the state and announcing the epoch.
+    pub(crate) certificate: QuorumCertificate,
+    /// Quorum time of the committed block, before which the blocks of the epoch are rejected.
+    pub(crate) time: NodeTime,
+    /// Encoding of the state by the SMR layer.
+    pub(crate) data: Vec<u8>,
+    /// How each epoch before the one of the certificate started, from the first one, so that
+    /// nodes that did not follow them can still verify the certificate.
+    pub(crate) proofs: Vec<EpochProof>,
+}
+
+/// A commit certificate announcing an epoch, and the configuration of that epoch.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct EpochProof {
+    pub(crate) certificate: QuorumCertificate,
+    pub(crate) configuration: EpochConfiguration,
+}
+
+impl EpochSnapshot {
+    pub(crate) fn new(
+        certificate: QuorumCertificate,
+        time: NodeTime,
+        data: Vec<u8>,
+        proofs: Vec<EpochProof>,
+    ) -> Self {
+        EpochSnapshot {
+            certificate,
+            time,
+            data,
+            proofs,
+        }
+    }
+
+    /// Verify the certificate of the snapshot with a light client trusting an earlier epoch,
+    /// entering the epochs in between with the proofs of the snapshot.
+    pub(crate) fn verify(
+        &self,
+        mut light_client: LightClient,
+    ) -> std::result::Result<CommittedState, VerifyError> {
+        for proof in &self.proofs {
+            if light_client.epoch_id() == self.certificate.epoch_id {
+                break;
+            }
+            if proof.certificate.epoch_id != light_client.epoch_id() {
+                continue;
+            }
+            let next_epoch = light_client
+                .verify(&proof.certificate)?
+                .next_epoch
+                .ok_or(VerifyError::InvalidEpochChange(light_client.epoch_id()))?;
+            light_client.enter_epoch(next_epoch.epoch_id, proof.configuration.clone())?;
+        }
+        light_client.verify(&self.certificate).cloned()
+    }
+}
+
+impl EpochProof {
+    pub(crate) fn new(certificate: QuorumCertificate, configuration: EpochConfiguration) -> Self {
+        EpochProof {
+            certificate,
+            configuration,
+        }
+    }
+}
+
+/// Configurations are not ordered, hence proofs are compared by their canonical encoding.
+impl Ord for EpochProof {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.certificate, to_canonical_bytes(&self.configuration))
+            .cmp(&(&other.certificate, to_canonical_bytes(&other.configuration)))
+    }
+}
+
+impl PartialOrd for EpochProof {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl DataSyncNotification {
+    /// The vote of the sender at its current round, if any.
+    pub(crate) fn current_vote(&self) -> Option<&Vote> {
+        self.current_vote.as_ref()
+    }
+
+    /// Notification carrying the given records and no certificate, e.g. the junk sent by a
+    /// flooding node.
+    pub(crate) fn with_records(
+        current_epoch: EpochId,
+        timeouts: Vec<Timeout>,
+        current_vote: Option<Vote>,
+        proposed_block: Option<Block>,
+    ) -> Self {
+        DataSyncNotification {
+            current_epoch,
+            highest_commit_certificate: None,
+            highest_quorum_certificate: None,
+            timeouts,
+            current_vote,
+            proposed_block,
+        }
+    }
+}
+
+impl DataSyncNotification {
+    /// Copy of the notification without the records of the given epoch, round, author, and kind
+    /// for which `keep` returns false.
+    fn retain<F>(&self, mut keep: F) -> Self
+    where
+        F: FnMut(EpochId, Round, Author, &'static str) -> bool,
+    {
+        let current_epoch = self.current_epoch;
+        let mut keep_qc =
+            |qc: &&QuorumCertificate| keep(qc.epoch_id, qc.round, qc.author, "quorum_certificate");
+        let highest_quorum_certificate = self
+            .highest_quorum_certificate
+            .as_ref()
+            .filter(&mut keep_qc)
+            .cloned();
+        let highest_commit_certificate = self
+            .highest_commit_certificate
+            .as_ref()
+            .filter(&mut keep_qc)
+            .cloned();
+        DataSyncNotification {
+            current_epoch,
+            highest_commit_certificate,
+            highest_quorum_certificate,
+            timeouts: self
+                .timeouts
+                .iter()
+                .filter(|timeout| keep(timeout.epoch_id, timeout.round, timeout.author, "timeout"))
+                .cloned()
+                .collect(),
+            current_vote: self
+                .current_vote
+                .as_ref()
+                .filter(|vote| keep(vote.epoch_id, vote.round, vote.author, "vote"))
+                .cloned(),
+            proposed_block: self
+                .proposed_block
+                .as_ref()
+                .filter(|block| keep(current_epoch, block.round, block.author, "block"))
+                .cloned(),
+        }
+    }
+}
+
 impl NodeState {
     fn create_request_internal(&self) -> DataSyncRequest {
         DataSyncRequest {
             current_epoch: self.epoch_id(),
-            known_quorum_certificates: self.record_store().known_quorum_certificate_rounds(),
+            summary: self.record_store().record_summary(self.sketch_cells()),
+            needs_snapshot: self.needs_snapshot(),
         }
     }
 }
@@ -115,7 +282,7 @@ where
 
         if let Some(highest_commit_certificate) = &notification.highest_commit_certificate {
             // Try to insert the QC just in case.
-            self.insert_network_record(
+            let _ = self.insert_network_record(
                 highest_commit_certificate.epoch_id,
                 Record::QuorumCertificate(highest_commit_certificate.clone()),
                 smr_context,
@@ -127,7 +294,7 @@ where
         }
         if let Some(highest_quorum_certificate) = &notification.highest_quorum_certificate {
             // Try to insert the QC.
-            self.insert_network_record(
+            let _ = self.insert_network_record(
                 highest_quorum_certificate.epoch_id,
                 Record::QuorumCertificate(highest_quorum_certificate.clone()),
                 smr_context,
@@ -140,7 +307,7 @@ where
         }
         // Try to insert the proposed block right away.
         if let Some(block) = notification.proposed_block {
-            self.insert_network_record(
+            let _ = self.insert_network_record(
                 notification.current_epoch,
                 Record::Block(block),
                 smr_context,
@@ -148,7 +315,7 @@ where
         }
         // Try to insert timeouts right away.
         for timeout in notification.timeouts {
-            self.insert_network_record(
+            let _ = self.insert_network_record(
                 notification.current_epoch,
                 Record::Timeout(timeout),
                 smr_context,
@@ -156,7 +323,11 @@ where
         }
         // Try to insert votes right away.
         if let Some(vote) = notification.current_vote {
-            self.insert_network_record(notification.current_epoch, Record::Vote(vote), smr_context);
+            let _ = self.insert_network_record(
+                notification.current_epoch,
+                Record::Vote(vote),
+                smr_context,
+            );
         }
         // Create a follow-up request if needed.
         if should_sync {
@@ -171,23 +342,58 @@ where
     }
 
     fn handle_request(&self, request: DataSyncRequest) -> DataSyncResponse {
+        // A snapshot replaces the records of the epochs before our current one.
+        if let Some(snapshot) = self.epoch_snapshot() {
+            if request.needs_snapshot && request.current_epoch < self.epoch_id() {
+                return DataSyncResponse {
+                    current_epoch: self.epoch_id(),
+                    records: vec![(
+                        self.epoch_id(),
+                        self.record_store().unknown_records(BTreeSet::new()),
+                    )],
+                    snapshot: Some(snapshot.clone()),
+                };
+            }
+        }
         let mut records = Vec::new();
         if let Some(store) = self.record_store_at(request.current_epoch) {
             records.push((
                 request.current_epoch,
-                store.unknown_records(request.known_quorum_certificates),
+                store.records_unknown_to(&request.summary),
             ));
         }
         for i in (request.current_epoch.0 + 1)..(self.epoch_id().0 + 1) {
             let epoch_id = EpochId(i);
-            let store = self
-                .record_store_at(epoch_id)
-                .expect("All record stores up to the current epoch should exist.");
-            records.push((epoch_id, store.unknown_records(BTreeSet::new())));
+            // The stores of old epochs may have been pruned, in which case the requester cannot
+            // catch up from us.
+            if let Some(store) = self.record_store_at(epoch_id) {
+                records.push((epoch_id, store.unknown_records(BTreeSet::new())));
+            }
         }
         DataSyncResponse {
             current_epoch: self.epoch_id(),
             records,
+            snapshot: None,
+        }
+    }
+
+    fn filter_notification(
+        &mut self,
+        notification: &DataSyncNotification,
+        receiver: Author,
+    ) -> Option<DataSyncNotification> {
+        let sent_records = self.sent_records_mut()?;
+        // The other records of a notification are at least as recent as its commit certificate.
+        if let Some(qc) = &notification.highest_commit_certificate {
+            sent_records.prune(qc.epoch_id, qc.round);
+        }
+        let filtered = notification.retain(|epoch_id, round, author, kind| {
+            sent_records.insert(receiver, epoch_id, round, author, kind)
+        });
+        if &filtered == notification {
+            None
+        } else {
+            Some(filtered)
         }
     }
 
@@ -197,6 +403,14 @@ where
         smr_context: &mut Context,
         clock: NodeTime,
     ) {
+        self.update_clock(clock);
+        // Bootstrap from the snapshot, if we asked for one, then sync its epoch.
+        if let Some(snapshot) = &response.snapshot {
+            if self.needs_snapshot() {
+                self.install_snapshot(snapshot, clock, smr_context);
+                self.update_tracker(clock);
+            }
+        }
         let num_records = response.records.len();
         // Insert all the records in order.
         // Process the commits so that new epochs are created along the way.
@@ -210,16 +424,420 @@ where
                 // This should not happen. Abort.
                 break;
             }
-            for record in records {
-                self.insert_network_record(epoch_id, record, smr_context);
+            let mut received = 0;
+            let mut missing = 0;
+            for record in &records {
+                if let Record::Block(_) | Record::QuorumCertificate(_) = record {
+                    received += 1;
+                    if !self.record_store().has_record(record) {
+                        missing += 1;
+                    }
+                }
             }
+            self.record_sync_stats(received, missing);
+            self.insert_network_records(epoch_id, records, smr_context);
             if i == num_records - 1 {
                 // Leave the latest epoch for the main handler to process.
                 break;
             }
             // Deliver commits and start the next epochs.
-            self.process_commits(smr_context);
+            self.process_commits(clock, smr_context);
             self.update_tracker(clock);
         }
     }
 }
+
+/// Size of a record in the canonical encoding, labelled with its type.
+fn record_size(record: &Record) -> (&'static str, usize) {
+    (record.kind(), encoded_len(record))
+}
+
+/// Identifier of the payload of a command. Commands do not carry their payload, whose size is
+/// only simulated, see `PayloadSizes`.
+pub fn payload_id(command: &Command) -> u64 {
+    stable_hash(&to_canonical_bytes(command))
+}
+
+/// Payloads of the commands of the given blocks.
+fn block_payloads<'a, I: Iterator<Item = &'a Block>>(blocks: I) -> Vec<u64> {
+    blocks
+        .filter_map(|block| block.command.as_ref())
+        .map(payload_id)
+        .collect()
+}
+
+impl MessageSize for DataSyncNotification {
+    fn byte_counts(&self) -> ByteCounts {
+        let records = self
+            .highest_commit_certificate
+            .iter()
+            .chain(&self.highest_quorum_certificate)
+            .cloned()
+            .map(Record::QuorumCertificate)
+            .chain(self.timeouts.iter().cloned().map(Record::Timeout))
+            .chain(self.current_vote.iter().cloned().map(Record::Vote))
+            .chain(self.proposed_block.iter().cloned().map(Record::Block));
+        let mut counts = vec![("header", encoded_len(&self.current_epoch))];
+        counts.extend(records.map(|record| record_size(&record)));
+        counts
+    }
+
+    fn payloads(&self) -> Vec<u64> {
+        block_payloads(self.proposed_block.iter())
+    }
+}
+
+impl MessageSize for DataSyncRequest {
+    fn byte_counts(&self) -> ByteCounts {
+        let summary = &self.summary;
+        let round_digests: Vec<_> = summary
+            .round_digests
+            .iter()
+            .map(|(round, digest)| (*round, *digest))
+            .collect();
+        let mut counts = vec![
+            ("header", encoded_len(&self.current_epoch)),
+            (
+                "summary",
+                encoded_len(&(summary.highest_committed_round, round_digests)),
+            ),
+        ];
+        if let Some(sketch) = &summary.sketch {
+            let cells: Vec<_> = sketch
+                .cells()
+                .iter()
+                .map(|cell| (cell.count, (cell.key_sum, cell.hash_sum)))
+                .collect();
+            counts.push(("sketch", encoded_len(&cells)));
+        }
+        counts
+    }
+}
+
+impl MessageSize for DataSyncResponse {
+    fn byte_counts(&self) -> ByteCounts {
+        let mut counts = vec![("header", encoded_len(&self.current_epoch))];
+        for (epoch_id, records) in &self.records {
+            counts.push(("header", encoded_len(epoch_id)));
+            counts.extend(records.iter().map(record_size));
+        }
+        if let Some(snapshot) = &self.snapshot {
+            counts.push((
+                "snapshot",
+                encoded_len(&snapshot.certificate)
+                    + encoded_len(&snapshot.time.0)
+                    + encoded_len(&snapshot.data)
+                    + snapshot
+                        .proofs
+                        .iter()
+                        .map(|proof| {
+                            encoded_len(&proof.certificate) + encoded_len(&proof.configuration)
+                        })
+                        .sum::<usize>(),
+            ));
+        }
+        counts
+    }
+
+    fn payloads(&self) -> Vec<u64> {
+        block_payloads(self.records.iter().flat_map(|(_, records)| {
+            records.iter().filter_map(|record| match record {
+                Record::Block(block) => Some(block),
+                _ => None,
+            })
+        }))
+    }
+}
+
+/// Votes come first, then certificates and timeouts, then proposals, then data sync.
+impl MessagePriority for DataSyncNotification {
+    fn priority(&self) -> usize {
+        if self.current_vote.is_some() {
+            0
+        } else if self.proposed_block.is_none() {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn round(&self) -> Round {
+        self.highest_quorum_certificate
+            .iter()
+            .map(|qc| qc.round)
+            .chain(self.timeouts.iter().map(|timeout| timeout.round))
+            .chain(self.current_vote.iter().map(|vote| vote.round))
+            .chain(self.proposed_block.iter().map(|block| block.round))
+            .max()
+            .unwrap_or(Round(0))
+    }
+}
+
+impl MessagePriority for DataSyncRequest {
+    fn priority(&self) -> usize {
+        3
+    }
+
+    fn round(&self) -> Round {
+        self.summary.highest_committed_round
+    }
+}
+
+impl MessagePriority for DataSyncResponse {
+    fn priority(&self) -> usize {
+        4
+    }
+
+    fn round(&self) -> Round {
+        self.records
+            .iter()
+            .flat_map(|(_, records)| records.iter().map(Record::round))
+            .max()
+            .unwrap_or(Round(0))
+    }
+}
+
+impl Proto for DataSyncNotification {
+    type Message = messages::DataSyncNotification;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::DataSyncNotification {
+            current_epoch: self.current_epoch.0 as u64,
+            highest_commit_certificate: self
+                .highest_commit_certificate
+                .as_ref()
+                .map(Proto::to_proto),
+            highest_quorum_certificate: self
+                .highest_quorum_certificate
+                .as_ref()
+                .map(Proto::to_proto),
+            timeouts: self.timeouts.iter().map(Proto::to_proto).collect(),
+            current_vote: self.current_vote.as_ref().map(Proto::to_proto),
+            proposed_block: self.proposed_block.as_ref().map(Proto::to_proto),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(DataSyncNotification {
+            current_epoch: EpochId(message.current_epoch as usize),
+            highest_commit_certificate: message
+                .highest_commit_certificate
+                .map(QuorumCertificate::from_proto)
+                .transpose()?,
+            highest_quorum_certificate: message
+                .highest_quorum_certificate
+                .map(QuorumCertificate::from_proto)
+                .transpose()?,
+            timeouts: message
+                .timeouts
+                .into_iter()
+                .map(Timeout::from_proto)
+                .collect::<Result<_>>()?,
+            current_vote: message.current_vote.map(Vote::from_proto).transpose()?,
+            proposed_block: message.proposed_block.map(Block::from_proto).transpose()?,
+        })
+    }
+}
+
+impl Proto for DataSyncRequest {
+    type Message = messages::DataSyncRequest;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::DataSyncRequest {
+            current_epoch: self.current_epoch.0 as u64,
+            summary: Some(self.summary.to_proto()),
+            needs_snapshot: self.needs_snapshot,
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(DataSyncRequest {
+            current_epoch: EpochId(message.current_epoch as usize),
+            summary: RecordSummary::from_proto(message.summary.unwrap_or_default())?,
+            needs_snapshot: message.needs_snapshot,
+        })
+    }
+}
+
+impl Proto for RecordSummary {
+    type Message = messages::RecordSummary;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::RecordSummary {
+            highest_committed_round: self.highest_committed_round.0 as u64,
+            round_digests: self
+                .round_digests
+                .iter()
+                .map(|(round, digest)| messages::RoundDigest {
+                    round: round.0 as u64,
+                    digest: *digest,
+                })
+                .collect(),
+            sketch: self.sketch.as_ref().map(|sketch| messages::RecordSketch {
+                cells: sketch
+                    .cells()
+                    .iter()
+                    .map(|cell| messages::SketchCell {
+                        count: cell.count,
+                        key_sum: cell.key_sum,
+                        hash_sum: cell.hash_sum,
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(RecordSummary {
+            highest_committed_round: Round(message.highest_committed_round as usize),
+            round_digests: message
+                .round_digests
+                .into_iter()
+                .map(|entry| (Round(entry.round as usize), entry.digest))
+                .collect(),
+            sketch: message.sketch.map(|sketch| {
+                InvertibleBloomLookupTable::from_cells(
+                    sketch
+                        .cells
+                        .into_iter()
+                        .map(|cell| SketchCell {
+                            count: cell.count,
+                            key_sum: cell.key_sum,
+                            hash_sum: cell.hash_sum,
+                        })
+                        .collect(),
+                )
+            }),
+        })
+    }
+}
+
+impl Proto for DataSyncResponse {
+    type Message = messages::DataSyncResponse;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::DataSyncResponse {
+            current_epoch: self.current_epoch.0 as u64,
+            records: self
+                .records
+                .iter()
+                .map(|(epoch_id, records)| messages::EpochRecords {
+                    epoch_id: epoch_id.0 as u64,
+                    records: records.iter().map(Proto::to_proto).collect(),
+                })
+                .collect(),
+            snapshot: self.snapshot.as_ref().map(Proto::to_proto),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(DataSyncResponse {
+            current_epoch: EpochId(message.current_epoch as usize),
+            records: message
+                .records
+                .into_iter()
+                .map(|epoch_records| {
+                    let records = epoch_records
+                        .records
+                        .into_iter()
+                        .map(Record::from_proto)
+                        .collect::<Result<_>>()?;
+                    Ok((EpochId(epoch_records.epoch_id as usize), records))
+                })
+                .collect::<Result<_>>()?,
+            snapshot: message
+                .snapshot
+                .map(EpochSnapshot::from_proto)
+                .transpose()?,
+        })
+    }
+}
+
+impl Proto for EpochSnapshot {
+    type Message = messages::EpochSnapshot;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::EpochSnapshot {
+            certificate: Some(self.certificate.to_proto()),
+            time: self.time.0,
+            data: self.data.clone(),
+            proofs: self.proofs.iter().map(Proto::to_proto).collect(),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        match message.certificate {
+            Some(certificate) => Ok(EpochSnapshot {
+                certificate: QuorumCertificate::from_proto(certificate)?,
+                time: NodeTime(message.time),
+                data: message.data,
+                proofs: message
+                    .proofs
+                    .into_iter()
+                    .map(EpochProof::from_proto)
+                    .collect::<Result<_>>()?,
+            }),
+            None => bail!("Snapshots must carry a commit certificate."),
+        }
+    }
+}
+
+impl Proto for EpochProof {
+    type Message = messages::EpochProof;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::EpochProof {
+            certificate: Some(self.certificate.to_proto()),
+            configuration: to_canonical_bytes(&self.configuration),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        match message.certificate {
+            Some(certificate) => Ok(EpochProof {
+                certificate: QuorumCertificate::from_proto(certificate)?,
+                configuration: from_canonical_bytes(&message.configuration)?,
+            }),
+            None => bail!("Epoch proofs must carry a commit certificate."),
+        }
+    }
+}
+
+/// A data-synchronization message together with its sender, as sent over the network.
+pub type NetworkEnvelope = (
+    Author,
+    Message<DataSyncNotification, DataSyncRequest, DataSyncResponse>,
+);
+
+impl Proto for NetworkEnvelope {
+    type Message = messages::NetworkMessage;
+
+    fn to_proto(&self) -> Self::Message {
+        let message = match &self.1 {
+            Message::Notification(x) => messages::NetworkMessageKind::Notification(x.to_proto()),
+            Message::Request(x) => messages::NetworkMessageKind::Request(x.to_proto()),
+            Message::Response(x) => messages::NetworkMessageKind::Response(x.to_proto()),
+        };
+        messages::NetworkMessage {
+            sender: self.0 .0 as u64,
+            message: Some(message),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        let sender = Author(message.sender as usize);
+        match message.message {
+            Some(messages::NetworkMessageKind::Notification(x)) => Ok((
+                sender,
+                Message::Notification(DataSyncNotification::from_proto(x)?),
+            )),
+            Some(messages::NetworkMessageKind::Request(x)) => {
+                Ok((sender, Message::Request(DataSyncRequest::from_proto(x)?)))
+            }
+            Some(messages::NetworkMessageKind::Response(x)) => {
+                Ok((sender, Message::Response(DataSyncResponse::from_proto(x)?)))
+            }
+            None => bail!("Unknown or missing kind of network message."),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Step-by-step execution of a scenario, driven by text commands, to follow the protocol one
+//! event at a time and poke at it.
+
+use super::*;
+use bft_simulator_runtime::simulator::{Event, GlobalTime, Partition};
+use scenario::{NodeSimulator, Scenario};
+use simulated_context::SimulatedContext;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+#[path = "unit_tests/debugger_tests.rs"]
+mod debugger_tests;
+
+pub const HELP: &str = "\
+step [<count>]                    process the next events (1 by default)
+continue <time>                   process the events until the given time
+next [<count>]                    list the next pending events (10 by default)
+show <replica>                    print the state of a replica
+inject <sender> <receiver>        deliver a notification of the sender to the receiver now
+crash <node> <start> <end>        crash a node during [start, end)
+partition <start> <end> <group>.. split the replicas into comma-separated groups, e.g. 0,1 2,3
+help                              print this message";
+
+/// A simulation of a scenario, with the trace enabled, run by commands.
+pub struct Debugger {
+    sim: NodeSimulator,
+}
+
+impl Debugger {
+    pub fn new(scenario: &Scenario) -> Self {
+        let mut sim = scenario.build_simulator();
+        sim.enable_trace();
+        Debugger { sim }
+    }
+
+    pub fn simulator(&self) -> &NodeSimulator {
+        &self.sim
+    }
+
+    /// Execute a command, see `HELP`, and return what to print.
+    pub fn execute(&mut self, line: &str) -> Result<String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(String::new()),
+        };
+        match command {
+            "step" => {
+                let count = optional_arg(args, 1)?;
+                let mut lines = Vec::new();
+                for _ in 0..count {
+                    match self.sim.step() {
+                        Some(entry) => lines.push(entry.to_string()),
+                        None => {
+                            lines.push("No events left".to_string());
+                            break;
+                        }
+                    }
+                }
+                Ok(lines.join("\n"))
+            }
+            "continue" => {
+                ensure!(args.len() == 1, "Usage: continue <time>");
+                let time = args[0].parse()?;
+                ensure!(time >= self.sim.clock().0, "Time {} is in the past", time);
+                self.sim.loop_until(GlobalTime(time), None);
+                Ok(format!("Clock is {}", self.sim.clock().0))
+            }
+            "next" => {
+                let count = optional_arg(args, 10)?;
+                let lines: Vec<_> = self
+                    .sim
+                    .upcoming_events(count)
+                    .into_iter()
+                    .map(|(time, event)| event.trace_entry(time, false).to_string())
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            "show" => {
+                ensure!(args.len() == 1, "Usage: show <replica>");
+                let replica = self.replica(args[0])?;
+                Ok(format!(
+                    "{:#?}",
+                    self.sim.replicas()[replica].node().node_state()
+                ))
+            }
+            "inject" => {
+                ensure!(args.len() == 2, "Usage: inject <sender> <receiver>");
+                let sender = self.replica(args[0])?;
+                let receiver = self.replica(args[1])?;
+                let sender = &self.sim.replicas()[sender];
+                let event = Event::DataSyncNotifyEvent {
+                    sender: sender.author(),
+                    receiver: self.sim.replicas()[receiver].author(),
+                    notification: DataSyncNode::<SimulatedContext>::create_notification(
+                        sender.node(),
+                    ),
+                };
+                let clock = self.sim.clock();
+                self.sim.inject_event(clock, event);
+                Ok(String::new())
+            }
+            "crash" => {
+                ensure!(args.len() == 3, "Usage: crash <node> <start> <end>");
+                let node: usize = args[0].parse()?;
+                ensure!(node < self.sim.num_nodes(), "Unknown node {}", node);
+                self.sim.add_crash(
+                    Author(node),
+                    GlobalTime(args[1].parse()?),
+                    GlobalTime(args[2].parse()?),
+                );
+                Ok(String::new())
+            }
+            "partition" => {
+                ensure!(args.len() >= 3, "Usage: partition <start> <end> <group>..");
+                let mut groups = Vec::new();
+                for group in &args[2..] {
+                    let group = group
+                        .split(',')
+                        .map(|replica| self.replica(replica))
+                        .collect::<Result<BTreeSet<_>>>()?;
+                    groups.push(group);
+                }
+                self.sim.add_partition(Partition {
+                    groups,
+                    start: GlobalTime(args[0].parse()?),
+                    end: GlobalTime(args[1].parse()?),
+                });
+                Ok(String::new())
+            }
+            "help" => Ok(HELP.to_string()),
+            _ => bail!("Unknown command {}, try help", command),
+        }
+    }
+
+    fn replica(&self, arg: &str) -> Result<usize> {
+        let replica = arg.parse()?;
+        ensure!(
+            replica < self.sim.replicas().len(),
+            "Unknown replica {}",
+            replica
+        );
+        Ok(replica)
+    }
+}
+
+fn optional_arg(args: &[&str], default: usize) -> Result<usize> {
+    match args {
+        [] => Ok(default),
+        [count] => Ok(count.parse()?),
+        _ => bail!("Too many arguments"),
+    }
+}
@@ -0,0 +1,431 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Record stores whose blocks and QCs are kept in a sled database, so that long runs and
+//! nodes of the real transport do not hold all their records in memory. Records are keyed by
+//! hash, and indexed by round so that the records of a range of rounds can be read in order.
+//! Each epoch has its own trees in the database of a node.
+
+use super::*;
+use commit_rule::CommitRule;
+use node_error::NodeResult;
+use pacemaker::Pacemaker;
+use record::*;
+use record_arena::RecordBackend;
+use record_store::{
+    ChainQueries, CommittedBlocks, InsertResult, Inserted, RecordQueries, RecordStore,
+    RecordStoreState, RecordSummary,
+};
+use serialization::{from_canonical_bytes, to_canonical_bytes, CanonicalDecode, CanonicalEncode};
+use smr_context::SMRContext;
+use std::{collections::BTreeSet, marker::PhantomData, ops::Range, path::Path};
+
+#[cfg(test)]
+#[path = "unit_tests/disk_store_tests.rs"]
+mod disk_store_tests;
+
+/// Blocks and QCs, as stored on disk.
+pub trait StoredRecord: CanonicalEncode + CanonicalDecode + Debug + Send + Sync {
+    type Hash: Copy;
+
+    /// Name of the trees holding records of this kind.
+    const KIND: &'static str;
+
+    fn round(&self) -> Round;
+
+    fn key(hash: &Self::Hash) -> u64;
+}
+
+impl StoredRecord for Block {
+    type Hash = BlockHash;
+
+    const KIND: &'static str = "blocks";
+
+    fn round(&self) -> Round {
+        self.round
+    }
+
+    fn key(hash: &BlockHash) -> u64 {
+        hash.0
+    }
+}
+
+impl StoredRecord for QuorumCertificate {
+    type Hash = QuorumCertificateHash;
+
+    const KIND: &'static str = "quorum_certificates";
+
+    fn round(&self) -> Round {
+        self.round
+    }
+
+    fn key(hash: &QuorumCertificateHash) -> u64 {
+        hash.0
+    }
+}
+
+/// The records of one kind of an epoch: their canonical bytes by hash, and the index of their
+/// hashes by round.
+#[derive(Debug)]
+pub struct DiskRecords<R> {
+    records: sled::Tree,
+    rounds: sled::Tree,
+    kind: PhantomData<fn() -> R>,
+}
+
+impl<R: StoredRecord> DiskRecords<R> {
+    /// Records of the given rounds, by increasing round, and by hash within a round.
+    pub fn records_in_rounds(&self, rounds: Range<Round>) -> Result<Vec<R>> {
+        let start = (rounds.start.0 as u64).to_be_bytes();
+        let end = (rounds.end.0 as u64).to_be_bytes();
+        let mut records = Vec::new();
+        for entry in self.rounds.range(start..end) {
+            let (key, _) = entry?;
+            let bytes = match self.records.get(&key[8..])? {
+                Some(bytes) => bytes,
+                None => bail!("Record indexed by round is missing"),
+            };
+            records.push(from_canonical_bytes(&bytes)?);
+        }
+        Ok(records)
+    }
+}
+
+impl<R: StoredRecord> RecordBackend<R::Hash, R> for DiskRecords<R> {
+    fn store(&self, hash: &R::Hash, record: &R) -> Result<()> {
+        let key = R::key(hash).to_be_bytes();
+        let mut round_key = (record.round().0 as u64).to_be_bytes().to_vec();
+        round_key.extend_from_slice(&key);
+        // Index the record only once it is written, so that the index never refers to a
+        // missing record.
+        self.records.insert(key, to_canonical_bytes(record))?;
+        self.rounds.insert(round_key, &[])?;
+        Ok(())
+    }
+
+    fn load(&self, hash: &R::Hash) -> Result<R> {
+        match self.records.get(R::key(hash).to_be_bytes())? {
+            Some(bytes) => from_canonical_bytes(&bytes),
+            None => bail!("Record {:x} is missing from disk", R::key(hash)),
+        }
+    }
+}
+
+/// The database of a node, and how many records of each kind its record stores keep in memory.
+#[derive(Clone, Debug)]
+pub struct DiskStorage {
+    db: sled::Db,
+    cache_capacity: usize,
+}
+
+impl DiskStorage {
+    /// Open the database at the given path, or create it.
+    pub fn open<P: AsRef<Path>>(path: P, cache_capacity: usize) -> Result<Self> {
+        ensure!(
+            cache_capacity > 0,
+            "Record caches must hold at least one record."
+        );
+        Ok(DiskStorage {
+            db: sled::open(path)?,
+            cache_capacity,
+        })
+    }
+
+    /// A database removed from disk when the last copy of the storage is dropped.
+    pub fn temporary(cache_capacity: usize) -> Result<Self> {
+        ensure!(
+            cache_capacity > 0,
+            "Record caches must hold at least one record."
+        );
+        Ok(DiskStorage {
+            db: sled::Config::new().temporary(true).open()?,
+            cache_capacity,
+        })
+    }
+
+    pub fn cache_capacity(&self) -> usize {
+        self.cache_capacity
+    }
+
+    /// The records of the given kind of an epoch. Records left by previous runs are kept:
+    /// hashes identify records, so they do not conflict with new ones.
+    pub fn records<R: StoredRecord>(&self, epoch_id: EpochId) -> Result<DiskRecords<R>> {
+        let tree = |suffix: &str| {
+            self.db
+                .open_tree(format!("epoch_{}/{}{}", epoch_id.0, R::KIND, suffix))
+        };
+        Ok(DiskRecords {
+            records: tree("")?,
+            rounds: tree("_by_round")?,
+            kind: PhantomData,
+        })
+    }
+
+    /// Blocks and QCs of an epoch at the given rounds, by increasing round, e.g. to inspect the
+    /// database of a node after a run.
+    pub fn records_in_rounds(
+        &self,
+        epoch_id: EpochId,
+        rounds: Range<Round>,
+    ) -> Result<Vec<Record>> {
+        let blocks = self
+            .records::<Block>(epoch_id)?
+            .records_in_rounds(rounds.clone())?;
+        let qcs = self
+            .records::<QuorumCertificate>(epoch_id)?
+            .records_in_rounds(rounds)?;
+        let mut records: Vec<_> = blocks
+            .into_iter()
+            .map(|block| (block.round, 0, Record::Block(block)))
+            .chain(
+                qcs.into_iter()
+                    .map(|qc| (qc.round, 1, Record::QuorumCertificate(qc))),
+            )
+            .collect();
+        records.sort_by_key(|(round, kind, _)| (*round, *kind));
+        Ok(records.into_iter().map(|(_, _, record)| record).collect())
+    }
+}
+
+/// A record store whose blocks and QCs are saved in the database of a node as they are
+/// inserted, with only the most recent ones kept in memory, and which can read back the records
+/// of a range of rounds from disk. Votes, timeouts, indexes, and the values computed from the
+/// records are the ones of the `RecordStoreState` that the store is built on.
+#[derive(Clone, Debug)]
+pub struct DiskRecordStore {
+    store: RecordStoreState,
+}
+
+impl DiskRecordStore {
+    /// Keep the records of an empty store in the given database.
+    pub fn open(mut store: RecordStoreState, storage: DiskStorage) -> Result<Self> {
+        store.set_disk_storage(storage)?;
+        Ok(DiskRecordStore { store })
+    }
+
+    pub fn storage(&self) -> &DiskStorage {
+        self.store
+            .disk_storage()
+            .expect("Disk record stores have a database")
+    }
+
+    /// Blocks and QCs of the store at the given rounds, by increasing round, read from disk.
+    pub fn records_in_rounds(&self, rounds: Range<Round>) -> Result<Vec<Record>> {
+        self.storage()
+            .records_in_rounds(self.store.epoch_id(), rounds)
+    }
+
+    /// Number of blocks and QCs currently in memory.
+    pub fn resident_records(&self) -> usize {
+        self.store.resident_records()
+    }
+
+    /// The store in memory, e.g. to configure it or to read the values that it computes.
+    pub fn state(&self) -> &RecordStoreState {
+        &self.store
+    }
+
+    pub fn state_mut(&mut self) -> &mut RecordStoreState {
+        &mut self.store
+    }
+}
+
+impl RecordStore for DiskRecordStore {
+    fn highest_quorum_certificate_hash(&self) -> QuorumCertificateHash {
+        self.store.highest_quorum_certificate_hash()
+    }
+
+    fn highest_quorum_certificate_round(&self) -> Round {
+        self.store.highest_quorum_certificate_round()
+    }
+
+    fn highest_quorum_certificate(&self) -> Option<&QuorumCertificate> {
+        self.store.highest_quorum_certificate()
+    }
+
+    fn highest_timeout_certificate_round(&self) -> Round {
+        self.store.highest_timeout_certificate_round()
+    }
+
+    fn highest_committed_round(&self) -> Round {
+        self.store.highest_committed_round()
+    }
+
+    fn highest_commit_certificate(&self) -> Option<&QuorumCertificate> {
+        self.store.highest_commit_certificate()
+    }
+
+    fn current_round(&self) -> Round {
+        self.store.current_round()
+    }
+
+    fn proposed_block(&self, pacemaker: &Pacemaker) -> Option<(BlockHash, Round, Author)> {
+        self.store.proposed_block(pacemaker)
+    }
+
+    fn has_timeout(&self, author: Author, round: Round) -> bool {
+        self.store.has_timeout(author, round)
+    }
+
+    fn create_timeout(
+        &mut self,
+        author: Author,
+        round: Round,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<()> {
+        self.store.create_timeout(author, round, smr_context)
+    }
+
+    fn propose_block(
+        &mut self,
+        local_author: Author,
+        previous_qc_hash: QuorumCertificateHash,
+        clock: NodeTime,
+        command: Option<Command>,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<()> {
+        self.store
+            .propose_block(local_author, previous_qc_hash, clock, command, smr_context)
+    }
+
+    fn create_vote(
+        &mut self,
+        local_author: Author,
+        block_hash: BlockHash,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<()> {
+        self.store
+            .create_vote(local_author, block_hash, clock, smr_context)
+    }
+
+    fn check_for_new_quorum_certificate(
+        &mut self,
+        local_author: Author,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<bool> {
+        self.store
+            .check_for_new_quorum_certificate(local_author, smr_context)
+    }
+
+    fn previous_round(&self, block_hash: BlockHash) -> Round {
+        self.store.previous_round(block_hash)
+    }
+
+    fn second_previous_round(&self, block_hash: BlockHash) -> Round {
+        self.store.second_previous_round(block_hash)
+    }
+
+    fn pick_author(&self, seed: u64) -> Author {
+        self.store.pick_author(seed)
+    }
+
+    fn timeouts(&self) -> Vec<Timeout> {
+        self.store.timeouts()
+    }
+
+    fn current_vote(&self, local_author: Author) -> Option<&Vote> {
+        self.store.current_vote(local_author)
+    }
+
+    fn block(&self, block_hash: BlockHash) -> Option<&Block> {
+        self.store.block(block_hash)
+    }
+
+    fn known_quorum_certificate_rounds(&self) -> BTreeSet<Round> {
+        self.store.known_quorum_certificate_rounds()
+    }
+
+    fn unknown_records(&self, known_qc_rounds: BTreeSet<Round>) -> Vec<Record> {
+        self.store.unknown_records(known_qc_rounds)
+    }
+
+    fn insert_network_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted> {
+        self.store.insert_network_record(record, smr_context)
+    }
+}
+
+impl ChainQueries for DiskRecordStore {
+    fn highest_timeout_certificate_qc_round(&self) -> Round {
+        self.store.highest_timeout_certificate_qc_round()
+    }
+
+    fn commit_rule(&self) -> &CommitRule {
+        self.store.commit_rule()
+    }
+
+    fn committed_blocks(&self, after_round: Round) -> CommittedBlocks<'_> {
+        self.store.committed_blocks(after_round)
+    }
+
+    fn quorum_time(&self, block_hash: BlockHash) -> NodeTime {
+        self.store.quorum_time(block_hash)
+    }
+
+    fn blocks_conflicting_with(&self, block_hash: BlockHash) -> Vec<BlockHash> {
+        self.store.blocks_conflicting_with(block_hash)
+    }
+
+    fn uncommitted_depth(&self, qc_hash: QuorumCertificateHash) -> usize {
+        self.store.uncommitted_depth(qc_hash)
+    }
+
+    fn oldest_quorum_certificate_hash(
+        &self,
+        qc_hash: QuorumCertificateHash,
+        min_round: Round,
+    ) -> QuorumCertificateHash {
+        self.store
+            .oldest_quorum_certificate_hash(qc_hash, min_round)
+    }
+
+    fn previous_state(&self, block_hash: BlockHash) -> &State {
+        self.store.previous_state(block_hash)
+    }
+
+    fn pick_author_excluding(&self, seed: u64, excluded: &BTreeSet<Author>) -> Author {
+        self.store.pick_author_excluding(seed, excluded)
+    }
+}
+
+impl RecordQueries for DiskRecordStore {
+    fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate> {
+        self.store.quorum_certificate(qc_hash)
+    }
+
+    fn record_summary(&self, sketch_cells: Option<usize>) -> RecordSummary {
+        self.store.record_summary(sketch_cells)
+    }
+
+    fn records_unknown_to(&self, peer_summary: &RecordSummary) -> Vec<Record> {
+        self.store.records_unknown_to(peer_summary)
+    }
+
+    fn has_record(&self, record: &Record) -> bool {
+        self.store.has_record(record)
+    }
+
+    fn blocks_proposed_by(&self, author: Author) -> Vec<BlockHash> {
+        self.store.blocks_proposed_by(author)
+    }
+
+    fn certified_rounds(&self) -> BTreeSet<Round> {
+        self.store.certified_rounds()
+    }
+
+    fn blocks_at(&self, round: Round) -> Vec<BlockHash> {
+        self.store.blocks_at(round)
+    }
+
+    fn quorum_certificates_at(&self, round: Round) -> Vec<QuorumCertificateHash> {
+        self.store.quorum_certificates_at(round)
+    }
+
+    fn export_dot(&self) -> String {
+        self.store.export_dot()
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Canned scenarios stressing the switch to a new epoch in `NodeState::process_commits`, and
+//! the safety properties that honest replicas must keep across epochs.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use campaign::consistent_histories;
+use scenario::{PartitionFault, Scenario};
+
+#[cfg(test)]
+#[path = "unit_tests/epoch_changes_tests.rs"]
+mod epoch_changes_tests;
+
+/// Number of commands per epoch in the canned scenarios, so that runs see many epoch changes.
+pub const SHORT_EPOCH: usize = 10;
+
+/// End epochs every `SHORT_EPOCH` commands and make network delays vary as much as their mean,
+/// so that commits of the last blocks of an epoch race with the first proposals of the next
+/// one.
+pub fn commit_race(base: &Scenario) -> Scenario {
+    let mut scenario = base.clone();
+    scenario.commands_per_epoch = SHORT_EPOCH;
+    scenario.network.variance = scenario.network.mean * scenario.network.mean;
+    scenario
+}
+
+/// End epochs every `SHORT_EPOCH` commands and cut `node` off from all other replicas every
+/// other `window`, so that it enters most epochs late, through data sync.
+pub fn staggered_entry(base: &Scenario, node: usize, window: i64) -> Scenario {
+    let mut scenario = base.clone();
+    scenario.commands_per_epoch = SHORT_EPOCH;
+    let replicas = base.nodes + base.followers + base.twins.len();
+    let others: Vec<_> = (0..replicas).filter(|replica| *replica != node).collect();
+    let mut start = window;
+    while start < base.max_clock {
+        // Replicas outside of the groups are isolated.
+        scenario.partitions.push(PartitionFault {
+            groups: vec![others.clone()],
+            start,
+            end: start + window,
+        });
+        start += 2 * window;
+    }
+    scenario
+}
+
+/// End epochs every `SHORT_EPOCH` commands and give `node` a twin that is cut off from all
+/// other replicas during the first two thirds of the run. The twin is stuck in the first epoch,
+/// then keeps broadcasting its stale timeouts, votes, and proposals to replicas that moved on.
+pub fn stale_epoch_leader(base: &Scenario, node: usize) -> Scenario {
+    let mut scenario = base.clone();
+    scenario.commands_per_epoch = SHORT_EPOCH;
+    let twin = base.nodes + base.followers + base.twins.len();
+    scenario.twins.push(node);
+    scenario.partitions.push(PartitionFault {
+        groups: vec![(0..twin).collect()],
+        start: 0,
+        end: 2 * base.max_clock / 3,
+    });
+    scenario
+}
+
+/// The canned epoch-change scenarios derived from `base`, where the last validator lags behind
+/// or has a Byzantine twin.
+pub fn epoch_change_library(base: &Scenario) -> Vec<(String, Scenario)> {
+    let node = base.nodes - 1;
+    let window = 10 * base.pacemaker.delta;
+    vec![
+        ("commit_race".to_string(), commit_race(base)),
+        (
+            "staggered_entry".to_string(),
+            staggered_entry(base, node, window),
+        ),
+        (
+            "stale_epoch_leader".to_string(),
+            stale_epoch_leader(base, node),
+        ),
+    ]
+}
+
+/// Check that the commit logs of honest replicas agree across epoch changes: each log must be a
+/// prefix of the longest one, with non-decreasing epochs and, within an epoch, increasing
+/// rounds.
+pub fn check_commit_logs(logs: &[&[Commit<BlockHash, State>]]) -> Result<()> {
+    for log in logs {
+        for (previous, commit) in log.iter().zip(log.iter().skip(1)) {
+            ensure!(
+                previous.epoch < commit.epoch
+                    || (previous.epoch == commit.epoch && previous.round < commit.round),
+                "Commit of round {:?} in epoch {} follows round {:?} in epoch {}",
+                commit.round,
+                commit.epoch,
+                previous.round,
+                previous.epoch
+            );
+        }
+    }
+    let longest = match logs.iter().max_by_key(|log| log.len()) {
+        Some(log) => log,
+        None => return Ok(()),
+    };
+    for log in logs {
+        if let Some((commit, other)) = log
+            .iter()
+            .zip(longest.iter())
+            .find(|(commit, other)| commit != other)
+        {
+            bail!(
+                "Conflicting commits in epoch {} at round {:?} and in epoch {} at round {:?}",
+                commit.epoch,
+                commit.round,
+                other.epoch,
+                other.round
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of a canned epoch-change scenario for the honest replicas.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct EpochChangeReport {
+    pub name: String,
+    /// Epoch reached by each honest replica.
+    pub epochs: Vec<usize>,
+    pub min_commits: usize,
+    pub max_commits: usize,
+    /// Records of past epochs received and rejected by honest replicas.
+    pub stale_records: usize,
+    /// Records of the previous epoch inserted by honest replicas during grace periods.
+    pub late_records: usize,
+    /// Why safety was broken, if it was.
+    pub violation: Option<String>,
+}
+
+/// Run the epoch-change library over `base`, and the baseline itself. Twins are not honest,
+/// nor are the nodes that they duplicate.
+pub fn evaluate_epoch_changes(base: &Scenario) -> Vec<EpochChangeReport> {
+    std::iter::once(("baseline".to_string(), base.clone()))
+        .chain(epoch_change_library(base))
+        .map(|(name, scenario)| {
+            let mut sim = scenario.build_simulator();
+            sim.loop_until(GlobalTime(scenario.max_clock), None);
+            let honest: Vec<_> = sim.replicas()[..scenario.nodes + scenario.followers]
+                .iter()
+                .filter(|replica| !scenario.twins.contains(&replica.author().0))
+                .collect();
+            let logs: Vec<_> = honest
+                .iter()
+                .map(|replica| replica.node().node_state().commits())
+                .collect();
+            let contexts: Vec<_> = honest.iter().map(|replica| replica.context()).collect();
+            let commits: Vec<_> = contexts
+                .iter()
+                .map(|context| context.committed_history().len())
+                .collect();
+            let violation = match check_commit_logs(&logs) {
+                Err(error) => Some(error.to_string()),
+                Ok(()) if !consistent_histories(&contexts) => {
+                    Some("Inconsistent committed histories".to_string())
+                }
+                Ok(()) => None,
+            };
+            EpochChangeReport {
+                name,
+                epochs: honest
+                    .iter()
+                    .map(|replica| replica.node().node_state().epoch_id().0)
+                    .collect(),
+                min_commits: commits.iter().cloned().min().unwrap_or(0),
+                max_commits: commits.iter().cloned().max().unwrap_or(0),
+                stale_records: honest
+                    .iter()
+                    .filter_map(|replica| {
+                        replica.node().node_state().rejections().get("past_epoch")
+                    })
+                    .sum(),
+                late_records: honest
+                    .iter()
+                    .map(|replica| replica.node().node_state().late_records())
+                    .sum(),
+                violation,
+            }
+        })
+        .collect()
+}
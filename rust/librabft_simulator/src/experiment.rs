@@ -0,0 +1,215 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Experiments run variants of a scenario over a grid of parameters, with many seeds each, on
+//! a pool of threads. Runs are independent and reproducible, so the results do not depend on
+//! the number of threads.
+
+use super::*;
+use campaign::{run, RunReport};
+use rayon::prelude::*;
+use scenario::Scenario;
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "unit_tests/experiment_tests.rs"]
+mod experiment_tests;
+
+/// Values to try for a parameter of a scenario, designated by its path in the TOML format,
+/// e.g. `pacemaker.delta` or `network.loss_rate`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Sweep {
+    pub parameter: String,
+    pub values: Vec<toml::Value>,
+}
+
+impl Sweep {
+    /// Parse a sweep given on the command line, e.g. `pacemaker.delta=10,20,40`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let (parameter, values) = match text.find('=') {
+            Some(index) => (&text[..index], &text[index + 1..]),
+            None => bail!("Sweeps must have the form parameter=value1,value2,..."),
+        };
+        let values = values
+            .split(',')
+            .map(|value| {
+                let mut document: toml::value::Table = toml::from_str(&format!("x = {}", value))?;
+                Ok(document.remove("x").unwrap())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Sweep {
+            parameter: parameter.to_string(),
+            values,
+        })
+    }
+}
+
+/// Distribution of a metric over runs.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct Statistics {
+    pub samples: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Statistics {
+    /// Compute statistics of the given samples, if any. Percentiles use the nearest rank.
+    pub fn new(mut samples: Vec<f64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_by(|x, y| x.partial_cmp(y).expect("Samples should be comparable"));
+        let n = samples.len();
+        let percentile = |p: f64| samples[((p * n as f64).ceil() as usize).clamp(1, n) - 1];
+        Some(Statistics {
+            samples: n,
+            mean: samples.iter().sum::<f64>() / n as f64,
+            min: samples[0],
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: samples[n - 1],
+        })
+    }
+}
+
+/// Aggregated results of the runs of one point of the grid.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct ExperimentPoint {
+    /// Value of each swept parameter.
+    pub parameters: BTreeMap<String, toml::Value>,
+    pub runs: usize,
+    /// Seeds of the runs that broke safety, if any.
+    pub inconsistent_seeds: Vec<u64>,
+    /// Number of commands committed by each node in each run.
+    pub commits: Option<Statistics>,
+    /// Mean time between two consecutive committed blocks in each run.
+    pub mean_block_interval: Option<Statistics>,
+    /// Bytes sent on the network in each run.
+    pub total_bytes: Option<Statistics>,
+}
+
+impl ExperimentPoint {
+    fn new(parameters: &[(String, toml::Value)], reports: &[RunReport]) -> Self {
+        ExperimentPoint {
+            parameters: parameters.iter().cloned().collect(),
+            runs: reports.len(),
+            inconsistent_seeds: reports
+                .iter()
+                .filter(|report| !report.consistent)
+                .map(|report| report.seed)
+                .collect(),
+            commits: Statistics::new(
+                reports
+                    .iter()
+                    .flat_map(|report| report.commits.iter().map(|commits| *commits as f64))
+                    .collect(),
+            ),
+            mean_block_interval: Statistics::new(
+                reports
+                    .iter()
+                    .filter_map(|report| report.mean_block_interval.map(|x| x as f64))
+                    .collect(),
+            ),
+            total_bytes: Statistics::new(
+                reports
+                    .iter()
+                    .map(|report| report.bandwidth.total_bytes as f64)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Results of an experiment, one point for each combination of the swept values.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct ExperimentReport {
+    pub points: Vec<ExperimentPoint>,
+}
+
+/// Set the given parameters of a scenario. Parameters left to their default value, such as
+/// optional ones, can be set as well.
+pub fn with_parameters(base: &Scenario, parameters: &[(String, toml::Value)]) -> Result<Scenario> {
+    let mut document = toml::Value::try_from(base)?;
+    for (path, value) in parameters {
+        let mut keys: Vec<_> = path.split('.').collect();
+        let last_key = keys.pop().unwrap();
+        let mut table = &mut document;
+        for key in keys {
+            table = match table.get_mut(key) {
+                Some(table) => table,
+                None => bail!("Unknown parameter {}", path),
+            };
+        }
+        let table = match table.as_table_mut() {
+            Some(table) => table,
+            None => bail!("Unknown parameter {}", path),
+        };
+        // Integers are fine for floating-point parameters.
+        let value = match (table.get(last_key), value) {
+            (Some(toml::Value::Float(_)), toml::Value::Integer(x)) => toml::Value::Float(*x as f64),
+            _ => value.clone(),
+        };
+        table.insert(last_key.to_string(), value);
+    }
+    Scenario::from_toml(&toml::to_string(&document)?)
+}
+
+/// All the combinations of the values of the sweeps, in lexicographic order.
+pub(crate) fn grid(sweeps: &[Sweep]) -> Vec<Vec<(String, toml::Value)>> {
+    let mut points = vec![Vec::new()];
+    for sweep in sweeps {
+        points = points
+            .into_iter()
+            .flat_map(|point| {
+                sweep.values.iter().map(move |value| {
+                    let mut point = point.clone();
+                    point.push((sweep.parameter.clone(), value.clone()));
+                    point
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+/// Run each point of the grid defined by `sweeps` once per seed, using the given number of
+/// threads, or one per CPU by default.
+pub fn run_experiment(
+    base: &Scenario,
+    sweeps: &[Sweep],
+    seeds: &[u64],
+    threads: Option<usize>,
+) -> Result<ExperimentReport> {
+    ensure!(!seeds.is_empty(), "Experiments need at least one seed.");
+    let points = grid(sweeps);
+    let scenarios = points
+        .iter()
+        .map(|parameters| with_parameters(base, parameters))
+        .collect::<Result<Vec<_>>>()?;
+    let jobs: Vec<_> = scenarios
+        .iter()
+        .flat_map(|scenario| seeds.iter().map(move |seed| (scenario, *seed)))
+        .collect();
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = threads {
+        builder = builder.num_threads(threads);
+    }
+    let pool = builder.build()?;
+    let reports: Vec<_> = pool.install(|| {
+        jobs.par_iter()
+            .map(|(scenario, seed)| run(scenario, Some(*seed)))
+            .collect()
+    });
+    Ok(ExperimentReport {
+        points: points
+            .iter()
+            .zip(reports.chunks(seeds.len()))
+            .map(|(parameters, reports)| ExperimentPoint::new(parameters, reports))
+            .collect(),
+    })
+}
@@ -0,0 +1,126 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Large record stores and nodes for benchmarks, built by the same code paths as in a run so
+//! that every record is correctly signed and executed.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use follower::Participant;
+use pacemaker::PacemakerState;
+use record::*;
+use record_store::*;
+use scenario::Scenario;
+use simulated_context::SimulatedContext;
+use smr_context::*;
+
+#[cfg(test)]
+#[path = "unit_tests/fixtures_tests.rs"]
+mod fixtures_tests;
+
+/// A record store holding a chain of certified rounds, one QC per round, together with the SMR
+/// contexts of all the nodes so that the chain can be extended.
+#[derive(Clone, Debug)]
+pub struct ChainFixture {
+    pub store: RecordStoreState,
+    pub contexts: Vec<SimulatedContext>,
+}
+
+impl ChainFixture {
+    /// Certify `num_rounds` blocks in a row, each voted by all the `num_nodes` nodes.
+    pub fn new(num_nodes: usize, num_rounds: usize) -> Self {
+        let contexts: Vec<_> = (0..num_nodes)
+            .map(|index| SimulatedContext::new(Author(index), num_nodes, usize::MAX))
+            .collect();
+        let state = contexts[0].last_committed_state();
+        let store = RecordStoreState::new(
+            QuorumCertificateHash(0),
+            state.clone(),
+            EpochId(0),
+            contexts[0].configuration(&state),
+        );
+        let mut fixture = ChainFixture { store, contexts };
+        for _ in 0..num_rounds {
+            fixture.extend_chain();
+        }
+        fixture
+    }
+
+    /// The leader of the current round.
+    pub fn leader(&self) -> Author {
+        PacemakerState::leader(&self.store, self.store.current_round())
+    }
+
+    /// Propose a block at the current round and vote for it with all the nodes, so that every
+    /// context executes it, without creating the QC yet. Return the block proposed by the leader and the votes, as they would be
+    /// received from the network.
+    pub fn propose_and_vote(&mut self) -> (Record, Vec<Record>) {
+        let leader = self.leader();
+        let previous_qc_hash = self.store.highest_quorum_certificate_hash();
+        let clock = NodeTime(self.store.current_round().0 as i64 * 10);
+        let context = &mut self.contexts[leader.0];
+        let command = context.fetch();
+        self.store
+            .propose_block(leader, previous_qc_hash, clock, command, context)
+            .unwrap();
+        let block_hash = *self.store.blocks_proposed_by(leader).last().unwrap();
+        let block = self.store.block(block_hash).unwrap().clone();
+        let mut votes = Vec::new();
+        for index in 0..self.contexts.len() {
+            let author = Author(index);
+            self.store
+                .create_vote(author, block_hash, clock + 1, &mut self.contexts[index])
+                .unwrap();
+            votes.push(Record::Vote(
+                self.store.current_vote(author).unwrap().clone(),
+            ));
+        }
+        (Record::Block(block), votes)
+    }
+
+    /// Certify one more block and return the records of the new round: the block, the votes,
+    /// and the QC.
+    pub fn extend_chain(&mut self) -> Vec<Record> {
+        let (block, mut records) = self.propose_and_vote();
+        records.insert(0, block);
+        let leader = self.leader();
+        assert!(self
+            .store
+            .check_for_new_quorum_certificate(leader, &mut self.contexts[leader.0])
+            .unwrap());
+        let qc_hash = self.store.highest_quorum_certificate_hash();
+        records.push(Record::QuorumCertificate(
+            self.store.quorum_certificate(qc_hash).unwrap().clone(),
+        ));
+        records
+    }
+
+    /// The records of the next round, created on a copy of the fixture, so that they can be
+    /// inserted as network records in the current store.
+    pub fn next_round_records(&self) -> Vec<Record> {
+        self.clone().extend_chain()
+    }
+}
+
+/// The first validator of a simulated run of `num_nodes` validators up to `max_clock`, with
+/// its SMR context. The longer the run, the more records are held by its store.
+pub fn simulated_validator(
+    num_nodes: usize,
+    max_clock: i64,
+) -> (Participant, SimulatedContext, NodeTime) {
+    let mut scenario = Scenario::from_toml(&format!(
+        "nodes = {}\nmax_clock = {}\nseed = 1",
+        num_nodes, max_clock
+    ))
+    .expect("Fixture scenarios are valid");
+    // Stay in the first epoch, so that the store keeps growing.
+    scenario.commands_per_epoch = usize::MAX;
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(max_clock), None);
+    let replica = sim.simulated_node(Author(0));
+    (
+        replica.node().clone(),
+        replica.context().clone(),
+        NodeTime(max_clock),
+    )
+}
@@ -0,0 +1,282 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Byzantine validators that flood honest nodes with records that are well-formed and
+//! correctly signed, but useless: blocks extending unknown certificates, votes for unknown
+//! blocks, and timeouts of past rounds. Honest nodes still pay to receive and verify them.
+//! Flooders may also replay the honest votes that they received in earlier rounds and epochs,
+//! which honest nodes must reject as well.
+
+use super::*;
+use base_types::*;
+use commit_rule::CommitRule;
+use committee::VotingCommittee;
+use data_sync::*;
+use hashing::HashFunction;
+use node::NodeState;
+use record::*;
+use serialization::{stable_hash, CanonicalEncode};
+use smr_context::SMRContext;
+use std::{collections::VecDeque, sync::Arc};
+
+#[cfg(test)]
+#[path = "unit_tests/flooder_tests.rs"]
+mod flooder_tests;
+
+#[derive(Clone, Debug)]
+pub struct FloodingNode {
+    /// Tracks the chain through data synchronization, so that the junk refers to the current
+    /// epoch and rounds. Its pacemaker never runs, so that it never votes nor proposes.
+    node: NodeState,
+    /// Time between two broadcasts of junk.
+    interval: Duration,
+    /// Number of records in each broadcast.
+    records_per_message: usize,
+    /// Time of the latest broadcast, if any.
+    latest_flood_time: Option<NodeTime>,
+    /// Number of broadcasts so far.
+    num_floods: usize,
+    /// Whether the votes of our broadcasts are honest votes received earlier.
+    replays_votes: bool,
+    /// The latest honest votes received, at most `MAX_RECORDED_VOTES`.
+    recorded_votes: VecDeque<Vote>,
+    /// Number of broadcasts that replayed a vote.
+    replayed_votes: usize,
+}
+
+/// Number of honest votes kept for replay.
+const MAX_RECORDED_VOTES: usize = 64;
+
+impl FloodingNode {
+    pub fn new(
+        local_author: Author,
+        initial_state: State,
+        node_time: NodeTime,
+        target_commit_interval: Duration,
+        interval: Duration,
+        records_per_message: usize,
+        smr_context: &SMRContext,
+    ) -> Self {
+        assert!(interval > 0, "Flooding intervals must be positive");
+        FloodingNode {
+            // The pacemaker parameters are irrelevant.
+            node: NodeState::new(
+                local_author,
+                initial_state,
+                node_time,
+                target_commit_interval,
+                0,
+                1.0,
+                1.0,
+                smr_context,
+            ),
+            interval,
+            records_per_message,
+            latest_flood_time: None,
+            num_floods: 0,
+            replays_votes: false,
+            recorded_votes: VecDeque::new(),
+            replayed_votes: 0,
+        }
+    }
+
+    pub fn node_state(&self) -> &NodeState {
+        &self.node
+    }
+
+    pub fn node_state_mut(&mut self) -> &mut NodeState {
+        &mut self.node
+    }
+
+    pub fn num_floods(&self) -> usize {
+        self.num_floods
+    }
+
+    /// Replay honest votes received earlier instead of sending votes for unknown blocks. Every
+    /// other replayed vote is moved to the current epoch and round, as if it had just been
+    /// cast, although its signature still covers the original ones.
+    pub fn set_vote_replay(&mut self) {
+        self.replays_votes = true;
+    }
+
+    pub fn replayed_votes(&self) -> usize {
+        self.replayed_votes
+    }
+
+    pub fn set_commit_rule(&mut self, commit_rule: Arc<CommitRule>) {
+        self.node.set_commit_rule(commit_rule);
+    }
+
+    pub fn set_hash_function(&mut self, hash_function: HashFunction) {
+        self.node.set_hash_function(hash_function);
+    }
+
+    pub fn set_chain_id(&mut self, chain_id: ChainId) {
+        self.node.set_chain_id(chain_id);
+    }
+
+    pub fn set_committee(&mut self, committee: VotingCommittee) {
+        self.node.set_committee(committee);
+    }
+
+    /// A hash that no honest record has, different for each broadcast and record.
+    fn unknown_hash(&self, index: usize) -> u64 {
+        let mut bytes = Vec::new();
+        self.node.local_author().0.encode(&mut bytes);
+        self.num_floods.encode(&mut bytes);
+        index.encode(&mut bytes);
+        stable_hash(&bytes)
+    }
+
+    /// The honest vote to replay in the current broadcast, if any were received.
+    fn replayed_vote(&self) -> Option<Vote> {
+        if self.recorded_votes.is_empty() {
+            return None;
+        }
+        let mut vote = self.recorded_votes[self.num_floods % self.recorded_votes.len()].clone();
+        if self.num_floods % 2 == 1 {
+            vote.epoch_id = self.node.epoch_id();
+            vote.round = self.node.record_store().current_round();
+        }
+        Some(vote)
+    }
+
+    /// A block extending an unknown QC at the current round, then a vote for an unknown block
+    /// (or a replayed vote) and timeouts, all at past rounds.
+    fn junk(&self) -> (Vec<Timeout>, Option<Vote>, Option<Block>) {
+        let epoch_id = self.node.epoch_id();
+        let author = self.node.public_key();
+        let chain_id = self.node.chain_id();
+        let hasher = self.node.hash_function();
+        let current_round = self.node.record_store().current_round();
+        let past_round = |index: usize| Round(current_round.0.saturating_sub(index + 1));
+        let block = match Record::make_block(
+            epoch_id,
+            None,
+            self.latest_flood_time.unwrap_or(NodeTime(0)),
+            QuorumCertificateHash(self.unknown_hash(0)),
+            current_round,
+            author,
+            chain_id,
+            &hasher,
+        ) {
+            Record::Block(block) => block,
+            _ => unreachable!(),
+        };
+        let vote = if self.records_per_message < 2 {
+            None
+        } else if self.replays_votes {
+            self.replayed_vote()
+        } else {
+            match Record::make_vote(
+                epoch_id,
+                past_round(0),
+                BlockHash(self.unknown_hash(1)),
+                State(self.unknown_hash(2)),
+                author,
+                None,
+                None,
+                self.latest_flood_time.unwrap_or(NodeTime(0)),
+                chain_id,
+                &hasher,
+            ) {
+                Record::Vote(vote) => Some(vote),
+                _ => unreachable!(),
+            }
+        };
+        let timeouts = (0..self.records_per_message.saturating_sub(2))
+            .map(|index| {
+                match Record::make_timeout(
+                    epoch_id,
+                    past_round(index),
+                    Round(0),
+                    author,
+                    chain_id,
+                    &hasher,
+                ) {
+                    Record::Timeout(timeout) => timeout,
+                    _ => unreachable!(),
+                }
+            })
+            .collect();
+        (timeouts, vote, Some(block))
+    }
+}
+
+impl<Context: SMRContext> ConsensusNode<Context> for FloodingNode {
+    fn update_node(&mut self, clock: NodeTime, smr_context: &mut Context) -> NodeUpdateActions {
+        let mut actions = self.node.update_follower(clock, smr_context);
+        let next_flood_time = match self.latest_flood_time {
+            Some(time) => time + self.interval,
+            None => clock,
+        };
+        if clock >= next_flood_time {
+            self.latest_flood_time = Some(clock);
+            self.num_floods += 1;
+            if self.replays_votes
+                && self.records_per_message >= 2
+                && !self.recorded_votes.is_empty()
+            {
+                self.replayed_votes += 1;
+            }
+            actions.should_broadcast = true;
+        }
+        actions.next_scheduled_update = std::cmp::min(
+            actions.next_scheduled_update,
+            self.latest_flood_time.unwrap() + self.interval,
+        );
+        actions
+    }
+}
+
+impl<Context: SMRContext> DataSyncNode<Context> for FloodingNode {
+    type Notification = DataSyncNotification;
+    type Request = DataSyncRequest;
+    type Response = DataSyncResponse;
+
+    fn create_notification(&self) -> DataSyncNotification {
+        let (timeouts, vote, block) = self.junk();
+        DataSyncNotification::with_records(self.node.epoch_id(), timeouts, vote, block)
+    }
+
+    fn create_request(&self) -> DataSyncRequest {
+        DataSyncNode::<Context>::create_request(&self.node)
+    }
+
+    fn handle_request(&self, request: DataSyncRequest) -> DataSyncResponse {
+        DataSyncNode::<Context>::handle_request(&self.node, request)
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: DataSyncNotification,
+        smr_context: &mut Context,
+    ) -> Option<DataSyncRequest> {
+        if self.replays_votes {
+            if let Some(vote) = notification.current_vote() {
+                if vote.author != self.node.local_author() {
+                    if self.recorded_votes.len() == MAX_RECORDED_VOTES {
+                        self.recorded_votes.pop_front();
+                    }
+                    self.recorded_votes.push_back(vote.clone());
+                }
+            }
+        }
+        self.node.handle_notification(notification, smr_context)
+    }
+
+    fn handle_response(
+        &mut self,
+        response: DataSyncResponse,
+        smr_context: &mut Context,
+        clock: NodeTime,
+    ) {
+        self.node.handle_response(response, smr_context, clock)
+    }
+}
+
+impl ActiveRound for FloodingNode {
+    fn active_round(&self) -> Round {
+        self.node.record_store().current_round()
+    }
+}
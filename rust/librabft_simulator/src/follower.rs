@@ -0,0 +1,472 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Followers are nodes that do not vote or propose, e.g. full nodes serving clients. They
+//! verify the records they receive, track the committed chain, and take part in data
+//! synchronization like any other node.
+
+use super::*;
+use bft_simulator_runtime::{simulator::GlobalTime, RoundTriage};
+use commit_rule::CommitRule;
+use committee::VotingCommittee;
+use data_sync::*;
+use flooder::FloodingNode;
+use hashing::HashFunction;
+use light_client::LightClient;
+use node::NodeState;
+use record::{CommittedState, VerifyError};
+use scenario::Scenario;
+use serde::Serialize;
+use smr_context::SMRContext;
+use std::sync::Arc;
+use triage::RoundSummary;
+
+#[cfg(test)]
+#[path = "unit_tests/follower_tests.rs"]
+mod follower_tests;
+
+#[derive(Clone, Debug)]
+pub struct FollowerState {
+    /// Verifies and stores records, and handles data synchronization. Its pacemaker never runs,
+    /// so that it never votes nor proposes.
+    node: NodeState,
+    /// The latest commit certificate that was verified, if any.
+    verified_commit: Option<CommittedState>,
+    /// Verifies commit certificates, and the epoch changes that they announce, independently of
+    /// the execution of the node.
+    light_client: LightClient,
+}
+
+impl FollowerState {
+    pub fn new(
+        local_author: Author,
+        initial_state: State,
+        node_time: NodeTime,
+        target_commit_interval: Duration,
+        smr_context: &SMRContext,
+    ) -> Self {
+        // The pacemaker parameters are irrelevant.
+        let node = NodeState::new(
+            local_author,
+            initial_state,
+            node_time,
+            target_commit_interval,
+            0,
+            1.0,
+            1.0,
+            smr_context,
+        );
+        let light_client = LightClient::new(node.epoch_id(), node.configuration().clone());
+        FollowerState {
+            node,
+            verified_commit: None,
+            light_client,
+        }
+    }
+
+    pub fn node_state(&self) -> &NodeState {
+        &self.node
+    }
+
+    pub fn node_state_mut(&mut self) -> &mut NodeState {
+        &mut self.node
+    }
+
+    pub fn verified_commit(&self) -> Option<&CommittedState> {
+        self.verified_commit.as_ref()
+    }
+
+    pub fn light_client(&self) -> &LightClient {
+        &self.light_client
+    }
+
+    /// Number of epochs entered by the node that no verified certificate announced yet.
+    pub fn uncertified_epoch_changes(&self) -> usize {
+        self.node.epoch_id().0 - self.light_client.epoch_id().0
+    }
+
+    pub fn set_commit_rule(&mut self, commit_rule: Arc<CommitRule>) {
+        self.node.set_commit_rule(commit_rule);
+    }
+
+    pub fn set_hash_function(&mut self, hash_function: HashFunction) {
+        self.node.set_hash_function(hash_function);
+        self.light_client.set_hash_function(hash_function);
+    }
+
+    pub fn set_chain_id(&mut self, chain_id: ChainId) {
+        self.node.set_chain_id(chain_id);
+        self.light_client.set_chain_id(chain_id);
+    }
+
+    pub fn set_committee(&mut self, committee: VotingCommittee) {
+        self.node.set_committee(committee);
+        self.light_client.set_committee(committee);
+    }
+
+    pub fn set_sketch_cells(&mut self, sketch_cells: usize) {
+        self.node.set_sketch_cells(sketch_cells);
+    }
+
+    pub fn set_orphan_buffer(&mut self, capacity: usize, max_round_age: usize) {
+        self.node.set_orphan_buffer(capacity, max_round_age);
+    }
+
+    pub fn set_send_dedup(&mut self) {
+        self.node.set_send_dedup();
+    }
+
+    pub fn set_past_epochs_kept(&mut self, past_epochs_kept: usize) {
+        self.node.set_past_epochs_kept(past_epochs_kept);
+    }
+
+    pub fn set_epoch_grace_period(&mut self, epoch_grace_period: Duration) {
+        self.node.set_epoch_grace_period(epoch_grace_period);
+    }
+
+    pub fn set_quorum_certificate_cache(&mut self, capacity: usize) {
+        self.node.set_quorum_certificate_cache(capacity);
+    }
+
+    pub fn set_checkpoint_interval(&mut self, interval: usize) {
+        self.node.set_checkpoint_interval(interval);
+    }
+
+    pub fn set_parallel_verification(&mut self) {
+        self.node.set_parallel_verification();
+    }
+
+    #[cfg(feature = "disk_store")]
+    pub fn set_disk_storage(&mut self, storage: disk_store::DiskStorage) -> Result<()> {
+        self.node.set_disk_storage(storage)
+    }
+
+    /// Verify the highest commit certificate of the node, after the epoch changes since the
+    /// last update. Return whether the commits of the node may be delivered, that is, whether
+    /// the certificate committing them was verified, if there is one.
+    fn verify_highest_commit_certificate(&mut self) -> bool {
+        // Otherwise, commits are only known from the local chain.
+        if !self.node.record_store().commit_rule().certifies_commits() {
+            return true;
+        }
+        // Follow the node through the epochs that it entered since the last update, each time
+        // with the configuration announced by the last certificate of the previous epoch.
+        while self.light_client.epoch_id() < self.node.epoch_id() {
+            self.verify_last_certificate_of(self.light_client.epoch_id());
+            if let Err(error) = self.enter_certified_epoch() {
+                // Keep the last verified configuration: the commits of the later epochs are not
+                // delivered until a certificate announcing the next epoch verifies.
+                warn!(
+                    "{:?} Rejected epoch change: {}",
+                    self.node.local_author(),
+                    error
+                );
+                return false;
+            }
+        }
+        self.verify_last_certificate_of(self.node.epoch_id())
+    }
+
+    /// Verify the highest commit certificate of the given epoch, if any. Return `false` if it is
+    /// rejected.
+    fn verify_last_certificate_of(&mut self, epoch_id: EpochId) -> bool {
+        let certificate = match self
+            .node
+            .record_store_at(epoch_id)
+            .and_then(|store| store.highest_commit_certificate())
+        {
+            Some(certificate) => certificate,
+            None => return true,
+        };
+        match self.light_client.verify(certificate) {
+            Ok(committed) => {
+                self.verified_commit = Some(committed.clone());
+                true
+            }
+            Err(error) => {
+                warn!(
+                    "{:?} Rejected commit certificate: {}",
+                    self.node.local_author(),
+                    error
+                );
+                false
+            }
+        }
+    }
+
+    fn enter_certified_epoch(&mut self) -> std::result::Result<(), VerifyError> {
+        let epoch_id = match self.light_client.next_epoch() {
+            Some(next_epoch) => next_epoch.epoch_id,
+            None => {
+                return Err(VerifyError::InvalidEpochChange(EpochId(
+                    self.light_client.epoch_id().0 + 1,
+                )))
+            }
+        };
+        let configuration = self
+            .node
+            .configuration_at(epoch_id)
+            .ok_or(VerifyError::InvalidEpochChange(epoch_id))?
+            .clone();
+        self.light_client.enter_epoch(epoch_id, configuration)
+    }
+}
+
+impl<Context: SMRContext> ConsensusNode<Context> for FollowerState {
+    fn update_node(&mut self, clock: NodeTime, smr_context: &mut Context) -> NodeUpdateActions {
+        if self.verify_highest_commit_certificate() {
+            self.node.update_follower(clock, smr_context)
+        } else {
+            // Keep syncing until a certificate verifies, without delivering the commits.
+            self.node.update_follower_without_commits(clock)
+        }
+    }
+}
+
+impl<Context: SMRContext> DataSyncNode<Context> for FollowerState {
+    type Notification = DataSyncNotification;
+    type Request = DataSyncRequest;
+    type Response = DataSyncResponse;
+
+    fn create_notification(&self) -> DataSyncNotification {
+        DataSyncNode::<Context>::create_notification(&self.node)
+    }
+
+    fn create_request(&self) -> DataSyncRequest {
+        DataSyncNode::<Context>::create_request(&self.node)
+    }
+
+    fn handle_request(&self, request: DataSyncRequest) -> DataSyncResponse {
+        DataSyncNode::<Context>::handle_request(&self.node, request)
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: DataSyncNotification,
+        smr_context: &mut Context,
+    ) -> Option<DataSyncRequest> {
+        self.node.handle_notification(notification, smr_context)
+    }
+
+    fn filter_notification(
+        &mut self,
+        notification: &DataSyncNotification,
+        receiver: Author,
+    ) -> Option<DataSyncNotification> {
+        DataSyncNode::<Context>::filter_notification(&mut self.node, notification, receiver)
+    }
+
+    fn handle_response(
+        &mut self,
+        response: DataSyncResponse,
+        smr_context: &mut Context,
+        clock: NodeTime,
+    ) {
+        // Responses may start new epochs: stop at the first one that no verified certificate
+        // announced.
+        self.verify_highest_commit_certificate();
+        if self.uncertified_epoch_changes() == 0 {
+            self.node.handle_response(response, smr_context, clock)
+        }
+    }
+}
+
+impl ActiveRound for FollowerState {
+    fn active_round(&self) -> Round {
+        self.node.record_store().current_round()
+    }
+}
+
+impl CommitLog for FollowerState {
+    type Block = BlockHash;
+    type State = State;
+
+    fn commits(&self) -> &[Commit<BlockHash, State>] {
+        self.node.commits()
+    }
+}
+
+/// Validators, followers, and flooding validators, so that all can be simulated together.
+#[derive(Clone, Debug)]
+pub enum Participant {
+    Validator(NodeState),
+    Follower(FollowerState),
+    Flooder(FloodingNode),
+}
+
+impl Participant {
+    pub fn node_state(&self) -> &NodeState {
+        match self {
+            Participant::Validator(node) => node,
+            Participant::Follower(follower) => follower.node_state(),
+            Participant::Flooder(flooder) => flooder.node_state(),
+        }
+    }
+
+    pub fn node_state_mut(&mut self) -> &mut NodeState {
+        match self {
+            Participant::Validator(node) => node,
+            Participant::Follower(follower) => follower.node_state_mut(),
+            Participant::Flooder(flooder) => flooder.node_state_mut(),
+        }
+    }
+
+    pub fn is_follower(&self) -> bool {
+        match self {
+            Participant::Validator(_) | Participant::Flooder(_) => false,
+            Participant::Follower(_) => true,
+        }
+    }
+}
+
+impl<Context: SMRContext> ConsensusNode<Context> for Participant {
+    fn update_node(&mut self, clock: NodeTime, smr_context: &mut Context) -> NodeUpdateActions {
+        match self {
+            Participant::Validator(node) => node.update_node(clock, smr_context),
+            Participant::Follower(follower) => follower.update_node(clock, smr_context),
+            Participant::Flooder(flooder) => flooder.update_node(clock, smr_context),
+        }
+    }
+}
+
+impl<Context: SMRContext> DataSyncNode<Context> for Participant {
+    type Notification = DataSyncNotification;
+    type Request = DataSyncRequest;
+    type Response = DataSyncResponse;
+
+    fn create_notification(&self) -> DataSyncNotification {
+        match self {
+            Participant::Flooder(flooder) => DataSyncNode::<Context>::create_notification(flooder),
+            _ => DataSyncNode::<Context>::create_notification(self.node_state()),
+        }
+    }
+
+    fn create_request(&self) -> DataSyncRequest {
+        DataSyncNode::<Context>::create_request(self.node_state())
+    }
+
+    fn handle_request(&self, request: DataSyncRequest) -> DataSyncResponse {
+        DataSyncNode::<Context>::handle_request(self.node_state(), request)
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: DataSyncNotification,
+        smr_context: &mut Context,
+    ) -> Option<DataSyncRequest> {
+        match self {
+            Participant::Validator(node) => node.handle_notification(notification, smr_context),
+            Participant::Follower(follower) => {
+                follower.handle_notification(notification, smr_context)
+            }
+            Participant::Flooder(flooder) => flooder.handle_notification(notification, smr_context),
+        }
+    }
+
+    fn filter_notification(
+        &mut self,
+        notification: &DataSyncNotification,
+        receiver: Author,
+    ) -> Option<DataSyncNotification> {
+        match self {
+            Participant::Validator(node) => {
+                DataSyncNode::<Context>::filter_notification(node, notification, receiver)
+            }
+            Participant::Follower(follower) => {
+                DataSyncNode::<Context>::filter_notification(follower, notification, receiver)
+            }
+            Participant::Flooder(_) => None,
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        response: DataSyncResponse,
+        smr_context: &mut Context,
+        clock: NodeTime,
+    ) {
+        match self {
+            Participant::Validator(node) => node.handle_response(response, smr_context, clock),
+            Participant::Follower(follower) => {
+                follower.handle_response(response, smr_context, clock)
+            }
+            Participant::Flooder(flooder) => flooder.handle_response(response, smr_context, clock),
+        }
+    }
+}
+
+impl CommitLog for Participant {
+    type Block = BlockHash;
+    type State = State;
+
+    fn commits(&self) -> &[Commit<BlockHash, State>] {
+        self.node_state().commits()
+    }
+}
+
+impl RoundTriage for Participant {
+    type Summary = RoundSummary;
+
+    fn round_summary(&self, epoch: usize, round: Round) -> RoundSummary {
+        self.node_state().round_summary(epoch, round)
+    }
+}
+
+impl ActiveRound for Participant {
+    fn active_round(&self) -> Round {
+        match self {
+            Participant::Validator(node) => node.active_round(),
+            Participant::Follower(follower) => follower.active_round(),
+            Participant::Flooder(flooder) => flooder.active_round(),
+        }
+    }
+}
+
+/// How long commits take to reach the followers, in simulated time.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct PropagationReport {
+    /// Number of commands committed by at least one validator.
+    pub commands: usize,
+    /// Number of commits by followers.
+    pub follower_commits: usize,
+    /// Delays between the first commit of a command by a validator and its commits by the
+    /// followers.
+    pub mean_delay: f64,
+    pub max_delay: i64,
+}
+
+/// Run a scenario and measure the propagation of commits to its followers.
+pub fn measure_propagation(scenario: &Scenario) -> PropagationReport {
+    let mut sim = scenario.build_simulator();
+    // Time of the first commit of each command by a validator.
+    let mut first_commits: Vec<GlobalTime> = Vec::new();
+    // Time of each commit by a follower, with the position of the command.
+    let mut follower_commits: Vec<(usize, GlobalTime)> = Vec::new();
+    let mut commit_counts = vec![0; sim.replicas().len()];
+    sim.loop_until_with_observer(GlobalTime(scenario.max_clock), None, |sim, clock| {
+        for (replica, node) in sim.replicas().iter().enumerate() {
+            let count = node.context().committed_history().len();
+            for position in commit_counts[replica]..count {
+                if node.node().is_follower() {
+                    follower_commits.push((position, clock));
+                } else if position == first_commits.len() {
+                    first_commits.push(clock);
+                }
+            }
+            commit_counts[replica] = count;
+        }
+    });
+    let delays: Vec<_> = follower_commits
+        .iter()
+        .map(|(position, clock)| match first_commits.get(*position) {
+            Some(first) => std::cmp::max(clock.0 - first.0, 0),
+            None => 0,
+        })
+        .collect();
+    PropagationReport {
+        commands: first_commits.len(),
+        follower_commits: delays.len(),
+        mean_delay: delays.iter().sum::<i64>() as f64 / std::cmp::max(delays.len(), 1) as f64,
+        max_delay: delays.iter().cloned().max().unwrap_or(0),
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! The starting point of a network: its validators, their voting rights, and the state and
+//! records that the first epoch extends, with the conventions of `SimulatedContext`.
+
+use super::*;
+use bft_simulator_runtime::configuration::DEFAULT_RESILIENCE;
+use hashing::HashFunction;
+use node::NodeState;
+use pacemaker::{AdaptiveDelta, LeaderReputation};
+use scenario::{default_commands_per_epoch, PacemakerParameters};
+use simulated_context::SimulatedContext;
+use smr_context::EpochReader;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+#[path = "unit_tests/genesis_tests.rs"]
+mod genesis_tests;
+
+/// Collect and validate the parameters of a genesis. There are no validators by default; the
+/// other parameters default to those of scenarios.
+#[derive(Clone, Debug)]
+pub struct GenesisBuilder {
+    validators: Vec<(Author, usize)>,
+    resilience: usize,
+    commands_per_epoch: usize,
+    pacemaker: PacemakerParameters,
+    hash_function: HashFunction,
+    chain_id: ChainId,
+}
+
+impl Default for GenesisBuilder {
+    fn default() -> Self {
+        GenesisBuilder {
+            validators: Vec::new(),
+            resilience: DEFAULT_RESILIENCE,
+            commands_per_epoch: default_commands_per_epoch(),
+            pacemaker: PacemakerParameters::default(),
+            hash_function: HashFunction::default(),
+            chain_id: ChainId::default(),
+        }
+    }
+}
+
+impl GenesisBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `count` validators with one vote each, numbered after the ones added so far.
+    pub fn validators(mut self, count: usize) -> Self {
+        let first = self.validators.len();
+        self.validators
+            .extend((first..first + count).map(|index| (Author(index), 1)));
+        self
+    }
+
+    /// Add a validator with the given voting rights, e.g. its stake.
+    pub fn validator(mut self, author: Author, weight: usize) -> Self {
+        self.validators.push((author, weight));
+        self
+    }
+
+    /// Tolerate `f` faulty votes out of `n = resilience * f + 1` instead of `n = 3f + 1`.
+    pub fn resilience(mut self, resilience: usize) -> Self {
+        self.resilience = resilience;
+        self
+    }
+
+    /// End epochs after the given number of commands.
+    pub fn commands_per_epoch(mut self, commands_per_epoch: usize) -> Self {
+        self.commands_per_epoch = commands_per_epoch;
+        self
+    }
+
+    pub fn pacemaker(mut self, pacemaker: PacemakerParameters) -> Self {
+        self.pacemaker = pacemaker;
+        self
+    }
+
+    /// Hash records with the given function, e.g. SHA3-256 outside of simulations.
+    pub fn hash_function(mut self, hash_function: HashFunction) -> Self {
+        self.hash_function = hash_function;
+        self
+    }
+
+    /// Sign records for the given network, distinct from the other networks of the same
+    /// validators.
+    pub fn chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Check the parameters. Authors must be distinct, with some voting rights, and numbered
+    /// from 0 without gaps, since they also index the voting rights of simulated contexts.
+    pub fn build(&self) -> Result<Genesis> {
+        ensure!(
+            !self.validators.is_empty(),
+            "A genesis needs at least one validator."
+        );
+        ensure!(
+            self.resilience >= 2,
+            "Quorums must intersect: the resilience must be at least 2."
+        );
+        ensure!(
+            self.commands_per_epoch > 0,
+            "Epochs must allow at least one command."
+        );
+        let mut authors = BTreeSet::new();
+        for (author, weight) in &self.validators {
+            ensure!(authors.insert(*author), "{:?} is listed twice.", author);
+            ensure!(*weight > 0, "{:?} has no voting rights.", author);
+        }
+        if let Some(author) = authors
+            .iter()
+            .enumerate()
+            .find(|(index, author)| author.0 != *index)
+            .map(|(_, author)| author)
+        {
+            bail!(
+                "Validators must be numbered from 0 without gaps, found {:?}.",
+                author
+            );
+        }
+        let mut voting_rights = vec![0; self.validators.len()];
+        for (author, weight) in &self.validators {
+            voting_rights[author.0] = *weight;
+        }
+        Ok(Genesis {
+            voting_rights,
+            resilience: self.resilience,
+            commands_per_epoch: self.commands_per_epoch,
+            pacemaker: self.pacemaker.clone(),
+            hash_function: self.hash_function,
+            chain_id: self.chain_id,
+        })
+    }
+}
+
+/// A validated genesis. In the simulator, the signing key of a node is its author, so that
+/// nodes only need their author and their context to sign and verify records.
+#[derive(Clone, Debug)]
+pub struct Genesis {
+    voting_rights: Vec<usize>,
+    resilience: usize,
+    commands_per_epoch: usize,
+    pacemaker: PacemakerParameters,
+    hash_function: HashFunction,
+    chain_id: ChainId,
+}
+
+impl Genesis {
+    pub fn num_validators(&self) -> usize {
+        self.voting_rights.len()
+    }
+
+    /// The first epoch.
+    pub fn epoch_id(&self) -> EpochId {
+        EpochId(0)
+    }
+
+    /// Hash standing for the QC that the first blocks extend.
+    pub fn initial_hash(&self) -> QuorumCertificateHash {
+        self.epoch_id().initial_hash()
+    }
+
+    /// The state of the empty ledger.
+    pub fn initial_state(&self) -> State {
+        self.context(Author(0)).last_committed_state()
+    }
+
+    /// Voting rights in the first epoch.
+    pub fn configuration(&self) -> EpochConfiguration {
+        self.context(Author(0)).configuration(&self.initial_state())
+    }
+
+    /// The SMR context of a node, validator or not.
+    pub fn context(&self, author: Author) -> SimulatedContext {
+        let mut context =
+            SimulatedContext::new(author, self.num_validators(), self.commands_per_epoch);
+        context.set_voting_rights(self.voting_rights.clone());
+        context.set_resilience(self.resilience);
+        context
+    }
+
+    /// A validator starting at the given time from the initial state of its context.
+    pub fn node(&self, author: Author, context: &SimulatedContext, clock: NodeTime) -> NodeState {
+        let mut node = NodeState::new(
+            author,
+            context.last_committed_state(),
+            clock,
+            self.pacemaker.target_commit_interval,
+            self.pacemaker.delta,
+            self.pacemaker.gamma,
+            self.pacemaker.lambda,
+            context,
+        );
+        node.set_hash_function(self.hash_function);
+        node.set_chain_id(self.chain_id);
+        node.set_optimistic_responsiveness(self.pacemaker.optimistic_responsiveness);
+        if let Some(adaptive_delta) = &self.pacemaker.adaptive_delta {
+            node.set_adaptive_delta(AdaptiveDelta::new(
+                adaptive_delta.smoothing,
+                adaptive_delta.margin,
+            ));
+        }
+        if let Some(leader_reputation) = &self.pacemaker.leader_reputation {
+            node.set_leader_reputation(LeaderReputation::new(leader_reputation.exclusion_window));
+        }
+        node
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Golden traces: scenarios together with the blocks that each validator committed and the QCs
+//! that it certified when the traces were recorded. Replaying them catches refactorings that
+//! change the behavior of the protocol by accident.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use record::Record;
+use scenario::Scenario;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fs, path::Path};
+
+#[cfg(test)]
+#[path = "unit_tests/golden_traces_tests.rs"]
+mod golden_traces_tests;
+
+/// Scenarios of the corpus, by name. The seed of each scenario is part of the trace.
+pub const GOLDEN_SCENARIOS: &[(&str, &str)] = &[
+    (
+        "crash_one_node",
+        include_str!("../scenarios/crash_one_node.toml"),
+    ),
+    ("reordering", include_str!("../scenarios/reordering.toml")),
+    ("twins", include_str!("../scenarios/twins.toml")),
+    (
+        "stake_changes",
+        include_str!("../scenarios/stake_changes.toml"),
+    ),
+    (
+        "lock_violation",
+        include_str!("../scenarios/lock_violation.toml"),
+    ),
+];
+
+/// A block, or the block certified by a QC, identified by its epoch and round.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GoldenBlock {
+    pub epoch: usize,
+    pub round: Round,
+    pub block: BlockHash,
+}
+
+/// What a validator reached at the end of the run.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GoldenOutcome {
+    /// All the blocks committed by the validator, in order.
+    pub commits: Vec<GoldenBlock>,
+    /// The QCs of the current epoch in the chains of the highest QC and of the highest commit
+    /// certificate of the validator, by increasing round.
+    pub quorum_certificates: Vec<GoldenBlock>,
+}
+
+/// The inputs of a run and the expected outcome of each validator.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GoldenTrace {
+    pub name: String,
+    pub scenario: Scenario,
+    pub outcomes: Vec<GoldenOutcome>,
+}
+
+impl GoldenTrace {
+    /// Run a scenario, from seed 0 unless it has its own, and record its outcome.
+    pub fn record(name: &str, scenario: &Scenario) -> Result<Self> {
+        let mut scenario = scenario.clone();
+        scenario.seed.get_or_insert(0);
+        let outcomes = run_outcomes(&scenario)?;
+        Ok(GoldenTrace {
+            name: name.to_string(),
+            scenario,
+            outcomes,
+        })
+    }
+
+    /// Replay the scenario and check that every validator reaches the expected outcome.
+    pub fn check(&self) -> Result<()> {
+        let outcomes = run_outcomes(&self.scenario)?;
+        ensure!(
+            outcomes.len() == self.outcomes.len(),
+            "Golden trace {}: expected {} validators, found {}",
+            self.name,
+            self.outcomes.len(),
+            outcomes.len()
+        );
+        for (node, (expected, actual)) in self.outcomes.iter().zip(outcomes.iter()).enumerate() {
+            check_blocks(
+                &self.name,
+                node,
+                "commit",
+                &expected.commits,
+                &actual.commits,
+            )?;
+            check_blocks(
+                &self.name,
+                node,
+                "QC",
+                &expected.quorum_certificates,
+                &actual.quorum_certificates,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Report the first difference between two sequences of blocks, if any.
+fn check_blocks(
+    name: &str,
+    node: usize,
+    kind: &str,
+    expected: &[GoldenBlock],
+    actual: &[GoldenBlock],
+) -> Result<()> {
+    for (index, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+        ensure!(
+            expected == actual,
+            "Golden trace {}: {} #{} of validator {} diverged: expected {:?}, found {:?}",
+            name,
+            kind,
+            index,
+            node,
+            expected,
+            actual
+        );
+    }
+    ensure!(
+        expected.len() == actual.len(),
+        "Golden trace {}: expected {} {}s for validator {}, found {}",
+        name,
+        expected.len(),
+        kind,
+        node,
+        actual.len()
+    );
+    Ok(())
+}
+
+fn run_outcomes(scenario: &Scenario) -> Result<Vec<GoldenOutcome>> {
+    scenario.check()?;
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    Ok(sim.replicas()[..scenario.nodes]
+        .iter()
+        .map(|replica| {
+            let node = replica.node().node_state();
+            let commits = node
+                .commits()
+                .iter()
+                .map(|commit| GoldenBlock {
+                    epoch: commit.epoch,
+                    round: commit.round,
+                    block: commit.block,
+                })
+                .collect();
+            let quorum_certificates = node
+                .record_store()
+                .unknown_records(BTreeSet::new())
+                .into_iter()
+                .filter_map(|record| match record {
+                    Record::QuorumCertificate(qc) => Some(GoldenBlock {
+                        epoch: qc.epoch_id.0,
+                        round: qc.round,
+                        block: qc.certified_block_hash,
+                    }),
+                    _ => None,
+                })
+                .collect();
+            GoldenOutcome {
+                commits,
+                quorum_certificates,
+            }
+        })
+        .collect())
+}
+
+/// Record the golden traces of all the scenarios of the corpus, one JSON file per scenario in
+/// the given directory.
+pub fn write_golden_traces(directory: &str) -> Result<()> {
+    for (name, data) in GOLDEN_SCENARIOS {
+        let trace = GoldenTrace::record(name, &Scenario::from_toml(data)?)?;
+        let path = Path::new(directory).join(format!("{}.json", name));
+        fs::write(path, serde_json::to_string_pretty(&trace)? + "\n")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hash functions of records. Deployments need a cryptographic hash function, while pure
+//! simulations only need hashes to be deterministic and can use a much faster one. All the
+//! replicas of a run must use the same function, since hashes link records together and are
+//! signed.
+
+use serde::{Deserialize, Serialize};
+use serialization::{stable_hash, CanonicalEncode};
+use sha3::{Digest, Sha3_256 as Sha3};
+
+#[cfg(test)]
+#[path = "unit_tests/hashing_tests.rs"]
+mod hashing_tests;
+
+/// A deterministic hash function of byte strings, truncated to 64 bits.
+pub trait Hasher: Send + Sync {
+    fn hash(&self, bytes: &[u8]) -> u64;
+
+    /// Hash of `bytes` prefixed with the encoding of `domain`, so that values of different
+    /// domains, e.g. blocks and votes, never share hashes even when their encodings coincide.
+    fn hash_in_domain(&self, domain: &str, bytes: &[u8]) -> u64 {
+        let mut input = Vec::with_capacity(domain.len() + bytes.len() + 1);
+        domain.encode(&mut input);
+        input.extend_from_slice(bytes);
+        self.hash(&input)
+    }
+}
+
+/// 64-bit FNV-1a: fast, but not collision resistant.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Fnv1a;
+
+impl Hasher for Fnv1a {
+    fn hash(&self, bytes: &[u8]) -> u64 {
+        stable_hash(bytes)
+    }
+}
+
+/// The first 8 bytes of SHA3-256, in little-endian order.
+///
+/// Truncating the digest to 64 bits leaves about 32 bits of collision resistance: a Byzantine
+/// node can find two records with the same hash after some 2^32 attempts, e.g. to equivocate
+/// behind a single signed hash. This is enough to keep honest records apart, but not to resist
+/// a determined adversary, which would need the full digest.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Sha3_256;
+
+impl Hasher for Sha3_256 {
+    fn hash(&self, bytes: &[u8]) -> u64 {
+        let digest = Sha3::digest(bytes);
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(prefix)
+    }
+}
+
+/// Choice of the hash function of a run, e.g. in scenarios and commit chains. The default is
+/// SHA3-256, so that deployments do not hash with FNV-1a by accident; pure simulations opt in
+/// to FNV-1a with `HashFunction::SIMULATION`.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashFunction {
+    /// For pure simulations.
+    Fnv1a,
+    /// For deployments.
+    #[default]
+    Sha3_256,
+}
+
+impl HashFunction {
+    /// The hash function of pure simulations, e.g. of nodes built outside of a genesis.
+    pub const SIMULATION: HashFunction = HashFunction::Fnv1a;
+}
+
+impl Hasher for HashFunction {
+    fn hash(&self, bytes: &[u8]) -> u64 {
+        match self {
+            HashFunction::Fnv1a => Fnv1a.hash(bytes),
+            HashFunction::Sha3_256 => Sha3_256.hash(bytes),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Read-only snapshots of the status of a node, its record store, pacemaker, and commit
+//! tracker, which monitoring tools can serialize as they are instead of formatting the live
+//! types themselves.
+
+use super::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+#[path = "unit_tests/introspection_tests.rs"]
+mod introspection_tests;
+
+/// Version of the layout of snapshots, increased whenever a field is renamed or removed.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+
+/// Status of a validator at a given time, see `NodeState::snapshot`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    /// Always `SNAPSHOT_SCHEMA_VERSION` when the snapshot is taken.
+    pub schema_version: u32,
+    pub author: Author,
+    pub epoch_id: EpochId,
+    pub latest_voted_round: Round,
+    pub locked_round: Round,
+    pub latest_query_all_time: NodeTime,
+    /// Number of commits delivered to the SMR layer so far.
+    pub num_commits: usize,
+    /// Number of record stores of past epochs still kept.
+    pub num_past_record_stores: usize,
+    /// Failures of the local actions of the latest update, as displayed.
+    pub update_errors: Vec<String>,
+    pub record_store: RecordStoreSnapshot,
+    pub pacemaker: PacemakerSnapshot,
+    pub commit_tracker: CommitTrackerSnapshot,
+}
+
+/// Status of the record store of the current epoch.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct RecordStoreSnapshot {
+    pub epoch_id: EpochId,
+    pub current_round: Round,
+    pub highest_quorum_certificate_round: Round,
+    pub highest_quorum_certificate_hash: QuorumCertificateHash,
+    pub highest_timeout_certificate_round: Round,
+    pub highest_committed_round: Round,
+    pub num_blocks: usize,
+    pub num_quorum_certificates: usize,
+    /// Authors of the votes and timeouts held for the current round.
+    pub current_voters: Vec<Author>,
+    pub current_timeout_authors: Vec<Author>,
+}
+
+/// Status of the pacemaker.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct PacemakerSnapshot {
+    pub active_epoch: EpochId,
+    pub active_round: Round,
+    pub active_leader: Option<Author>,
+    pub active_round_start_time: NodeTime,
+    pub active_round_duration: Duration,
+    /// Current value of `delta`, learned from the round durations if adaptive.
+    pub delta: Duration,
+    pub gamma: f64,
+    pub lambda: f64,
+    pub optimistic_responsiveness: bool,
+    pub adaptive_delta: bool,
+    /// Leaders skipped at the active round, if leader reputation is enabled.
+    pub excluded_leaders: Option<Vec<Author>>,
+}
+
+/// Status of the commit tracker, which asks to query all nodes when commits stall.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CommitTrackerSnapshot {
+    pub epoch_id: EpochId,
+    pub highest_committed_round: Round,
+    pub latest_commit_time: NodeTime,
+    pub target_commit_interval: Duration,
+}
+
+/// What a validator tells clients about the leader that it expects, see
+/// `NodeState::leader_hint`.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct LeaderHint {
+    pub epoch_id: EpochId,
+    /// Active round of the validator.
+    pub round: Round,
+    /// Leader of the active round, if the pacemaker knows it.
+    pub leader: Option<Author>,
+}
+
+impl LeaderHint {
+    /// Whether the hint was given at a later round than `other`.
+    pub fn is_newer_than(&self, other: &LeaderHint) -> bool {
+        (self.epoch_id, self.round) > (other.epoch_id, other.round)
+    }
+}
@@ -0,0 +1,404 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! A reference `SMRContext` where the replicated state machine is an in-memory key-value
+//! store. Each command stands for a put or a delete operation, and the current epoch is stored
+//! under a reserved key, which is incremented by the execution of every `commands_per_epoch`
+//! commands, as a reconfiguration transaction would do.
+//!
+//! Commands do not carry a payload, so their operations are derived deterministically from
+//! them: every node must use the same number of keys.
+//!
+//! Blocks are applied as transactions, see `BatchExecutor`, as a store backed by a database
+//! would do: the store of the base state is copied, changed by each command, and only becomes
+//! visible as a new state on commit.
+
+use super::*;
+use base_types::*;
+use batch_executor::{execute_batch, BatchExecutor};
+use record::QuorumCertificate;
+use serialization::{stable_hash, to_canonical_bytes, CanonicalEncode};
+use smr_context::*;
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(test)]
+#[path = "unit_tests/kv_context_tests.rs"]
+mod kv_context_tests;
+
+/// Key holding the current epoch id. User keys are always smaller.
+pub const EPOCH_KEY: u64 = u64::MAX;
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum KvOperation {
+    Put { key: u64, value: u64 },
+    Delete { key: u64 },
+}
+
+impl KvOperation {
+    /// The operation submitted by a client as `command`, on keys in `0..num_keys`, which must
+    /// not be empty. One operation out of four is a deletion.
+    pub fn of(command: &Command, num_keys: u64) -> Self {
+        let seed = stable_hash(&to_canonical_bytes(command));
+        let key = seed % num_keys;
+        if (seed / num_keys).is_multiple_of(4) {
+            KvOperation::Delete { key }
+        } else {
+            KvOperation::Put {
+                key,
+                value: command.index as u64,
+            }
+        }
+    }
+}
+
+/// Content of the store after executing a sequence of commands.
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct KvStore {
+    entries: BTreeMap<u64, u64>,
+    /// Number of commands executed so far.
+    executed: u64,
+    /// Chained hash of the executed commands and their times, so that distinct histories
+    /// never share a state.
+    history: u64,
+}
+
+impl KvStore {
+    pub fn get(&self, key: u64) -> Option<u64> {
+        self.entries.get(&key).cloned()
+    }
+
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    pub fn epoch_id(&self) -> EpochId {
+        EpochId(self.get(EPOCH_KEY).unwrap_or(0) as usize)
+    }
+
+    /// Hash of the canonical encoding of the store.
+    pub fn state(&self) -> State {
+        let mut bytes = Vec::new();
+        self.executed.encode(&mut bytes);
+        self.history.encode(&mut bytes);
+        let entries: Vec<_> = self.entries.iter().map(|(k, v)| (*k, *v)).collect();
+        entries.encode(&mut bytes);
+        State(stable_hash(&bytes))
+    }
+
+    fn apply(
+        &mut self,
+        command: Option<&Command>,
+        time: NodeTime,
+        num_keys: u64,
+        commands_per_epoch: usize,
+    ) {
+        let mut bytes = Vec::new();
+        self.history.encode(&mut bytes);
+        command.cloned().encode(&mut bytes);
+        time.encode(&mut bytes);
+        self.history = stable_hash(&bytes);
+        // Empty blocks only change the history.
+        let command = match command {
+            Some(command) => command,
+            None => return,
+        };
+        match KvOperation::of(command, num_keys) {
+            KvOperation::Put { key, value } => {
+                self.entries.insert(key, value);
+            }
+            // Deleting a missing key is a no-op.
+            KvOperation::Delete { key } => {
+                self.entries.remove(&key);
+            }
+        }
+        self.executed += 1;
+        if self.executed.is_multiple_of(commands_per_epoch as u64) {
+            *self.entries.entry(EPOCH_KEY).or_insert(0) += 1;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KvContext {
+    author: Author,
+    num_nodes: usize,
+    num_keys: u64,
+    commands_per_epoch: usize,
+    next_command_index: usize,
+    /// Commands waiting to be proposed.
+    mempool: VecDeque<Command>,
+    last_committed_store: KvStore,
+    /// Versions of the store before the last commit, from the oldest, until they are pruned.
+    past_stores: VecDeque<(State, KvStore)>,
+    pending_stores: HashMap<State, KvStore>,
+    /// All the committed states, in order.
+    committed_states: Vec<State>,
+    /// Open transaction, if any.
+    transaction: Option<KvTransaction>,
+}
+
+/// Changes of a batch of commands that are not visible yet.
+#[derive(Clone, Debug)]
+struct KvTransaction {
+    store: KvStore,
+    time: NodeTime,
+    /// Whether a command was applied, as opposed to an empty block.
+    applied: bool,
+}
+
+impl KvContext {
+    pub fn new(author: Author, num_nodes: usize, commands_per_epoch: usize, num_keys: u64) -> Self {
+        assert!(num_keys > 0, "Key-value stores need at least one key");
+        KvContext {
+            author,
+            num_nodes,
+            num_keys,
+            commands_per_epoch,
+            next_command_index: 0,
+            mempool: VecDeque::new(),
+            last_committed_store: KvStore::default(),
+            past_stores: VecDeque::new(),
+            pending_stores: HashMap::new(),
+            committed_states: Vec::new(),
+            transaction: None,
+        }
+    }
+
+    /// Queue a new client command and return the operation that it stands for.
+    pub fn submit(&mut self) -> (Command, KvOperation) {
+        let command = Command {
+            proposer: self.author,
+            index: self.next_command_index,
+        };
+        self.next_command_index += 1;
+        self.mempool.push_back(command.clone());
+        let operation = KvOperation::of(&command, self.num_keys);
+        (command, operation)
+    }
+
+    pub fn last_committed_store(&self) -> &KvStore {
+        &self.last_committed_store
+    }
+
+    pub fn last_committed_state(&self) -> State {
+        self.last_committed_store.state()
+    }
+
+    pub fn committed_states(&self) -> &[State] {
+        &self.committed_states
+    }
+
+    /// The committed version of the store at the given state, unless it was pruned, e.g. to
+    /// read the value of a key at some past commit.
+    pub fn committed_store(&self, state: &State) -> Option<&KvStore> {
+        if *state == self.last_committed_state() {
+            return Some(&self.last_committed_store);
+        }
+        self.past_stores
+            .iter()
+            .find(|(past_state, _)| past_state == state)
+            .map(|(_, store)| store)
+    }
+
+    /// Number of committed versions of the store that are kept, the last one included.
+    pub fn num_committed_stores(&self) -> usize {
+        self.past_stores.len() + 1
+    }
+
+    fn store(&self, state: &State) -> Option<&KvStore> {
+        if *state == self.last_committed_state() {
+            Some(&self.last_committed_store)
+        } else {
+            self.pending_stores.get(state)
+        }
+    }
+}
+
+impl CommandFetcher for KvContext {
+    /// Clients submit one new command for each fetch.
+    fn fetch(&mut self) -> Option<Command> {
+        self.submit();
+        self.mempool.pop_front()
+    }
+
+    fn mempool_depth(&self) -> usize {
+        self.mempool.len()
+    }
+}
+
+impl StateComputer for KvContext {
+    fn compute(
+        &mut self,
+        base_state: &State,
+        command: Option<Command>,
+        time: NodeTime,
+        previous_author: Option<Author>,
+        previous_voters: Vec<Author>,
+    ) -> Option<State> {
+        self.compute_batch(
+            base_state,
+            command.as_slice(),
+            time,
+            previous_author,
+            previous_voters,
+        )
+    }
+}
+
+impl KvContext {
+    /// Execute a batch of commands, e.g. the commands of a block, as a single transaction.
+    pub fn compute_batch(
+        &mut self,
+        base_state: &State,
+        commands: &[Command],
+        time: NodeTime,
+        previous_author: Option<Author>,
+        previous_voters: Vec<Author>,
+    ) -> Option<State> {
+        let state = execute_batch(
+            self,
+            base_state,
+            commands,
+            time,
+            previous_author,
+            previous_voters,
+        )?;
+        debug!(
+            "{:?} Executing {:?} after {:?} gave {:?}",
+            self.author, commands, base_state, state
+        );
+        Some(state)
+    }
+}
+
+impl BatchExecutor for KvContext {
+    fn begin_transaction(
+        &mut self,
+        base_state: &State,
+        time: NodeTime,
+        _previous_author: Option<Author>,
+        _previous_voters: Vec<Author>,
+    ) -> bool {
+        assert!(
+            self.transaction.is_none(),
+            "{:?} A transaction is already open",
+            self.author
+        );
+        let store = match self.store(base_state) {
+            Some(store) => store.clone(),
+            None => {
+                error!(
+                    "{:?} Trying to execute a batch after {:?} but the base state is not available",
+                    self.author, base_state
+                );
+                return false;
+            }
+        };
+        self.transaction = Some(KvTransaction {
+            store,
+            time,
+            applied: false,
+        });
+        true
+    }
+
+    fn apply_command(&mut self, command: &Command) -> bool {
+        let transaction = match &mut self.transaction {
+            Some(transaction) => transaction,
+            None => return false,
+        };
+        transaction.store.apply(
+            Some(command),
+            transaction.time,
+            self.num_keys,
+            self.commands_per_epoch,
+        );
+        transaction.applied = true;
+        true
+    }
+
+    fn commit_transaction(&mut self) -> Option<State> {
+        let mut transaction = self.transaction.take()?;
+        if !transaction.applied {
+            transaction.store.apply(
+                None,
+                transaction.time,
+                self.num_keys,
+                self.commands_per_epoch,
+            );
+        }
+        let state = transaction.store.state();
+        self.pending_stores.insert(state.clone(), transaction.store);
+        Some(state)
+    }
+
+    fn abort_transaction(&mut self) {
+        self.transaction = None;
+    }
+}
+
+impl StateFinalizer for KvContext {
+    fn commit(
+        &mut self,
+        state: &State,
+        _time: NodeTime,
+        _commit_certificate: Option<&QuorumCertificate>,
+    ) {
+        let store = self
+            .pending_stores
+            .remove(state)
+            .expect("Committed states should be known");
+        let previous_store = std::mem::replace(&mut self.last_committed_store, store);
+        self.past_stores
+            .push_back((previous_store.state(), previous_store));
+        self.committed_states.push(state.clone());
+    }
+
+    fn discard(&mut self, state: &State) {
+        self.pending_stores
+            .remove(state)
+            .expect("Discarded states should be known");
+    }
+
+    /// Drop the committed versions of the store before the given one.
+    fn prune(&mut self, up_to_state: &State) {
+        if *up_to_state == self.last_committed_state() {
+            self.past_stores.clear();
+        } else if let Some(position) = self
+            .past_stores
+            .iter()
+            .position(|(state, _)| state == up_to_state)
+        {
+            self.past_stores.drain(..position);
+        }
+    }
+}
+
+impl EpochReader for KvContext {
+    fn read_epoch_id(&self, state: &State) -> EpochId {
+        self.store(state)
+            .expect("Read states should be known")
+            .epoch_id()
+    }
+
+    fn configuration(&self, _state: &State) -> EpochConfiguration {
+        EpochConfiguration::new(
+            (0..self.num_nodes)
+                .map(|index| (Author(index), 1))
+                .collect(),
+        )
+    }
+}
+
+impl SignatureVerifier for KvContext {}
+
+impl StateSnapshots for KvContext {}
+
+impl SMRContext for KvContext {}
+
+/// Commands are executed for real, hence their cost is not simulated.
+impl ProcessingTime for KvContext {
+    fn take_processing_time(&mut self) -> Duration {
+        0
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(bare_trait_objects)]
+
+#[macro_use]
+extern crate failure;
+extern crate rand;
+#[macro_use]
+extern crate log;
+extern crate bft_simulator_runtime;
+extern crate csv;
+#[cfg(any(test, feature = "proptest"))]
+extern crate proptest;
+extern crate prost;
+extern crate rayon;
+extern crate serde;
+extern crate serde_json;
+extern crate sha3;
+#[cfg(feature = "disk_store")]
+extern crate sled;
+#[cfg(feature = "server")]
+extern crate tiny_http;
+extern crate toml;
+
+use std::{collections::BTreeMap, fmt::Debug};
+
+// Comments in the following form are used for code-block generation in the consensus report:
+//    "// -- BEGIN FILE name --"
+//    "// -- END FILE --"
+// Do not modify definitions without changing the report as well :)
+
+pub mod adversarial_scheduler;
+pub mod base_types;
+pub mod batch_executor;
+pub mod campaign;
+pub mod checkpoints;
+pub mod clients;
+pub mod commit_chain;
+pub mod commit_rule;
+pub mod commit_stream;
+pub mod committee;
+pub mod comparison;
+pub mod conformance;
+pub mod cross_shard;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod data_sync;
+pub mod debugger;
+#[cfg(feature = "disk_store")]
+pub mod disk_store;
+pub mod epoch_changes;
+pub mod experiment;
+pub mod fixtures;
+pub mod flooder;
+pub mod follower;
+pub mod genesis;
+pub mod golden_traces;
+pub mod hashing;
+pub mod introspection;
+pub mod kv_context;
+pub mod light_client;
+pub mod model_checker;
+pub mod node;
+pub mod node_error;
+pub mod node_handle;
+pub mod orphan_buffer;
+pub mod outages;
+pub mod pacemaker;
+pub mod proposal_limiter;
+pub mod proto;
+pub mod qc_cache;
+pub mod reads;
+pub mod reconciliation;
+pub mod record;
+pub mod record_arena;
+pub mod record_builder;
+pub mod record_store;
+pub mod scenario;
+pub mod send_dedup;
+pub mod serialization;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shards;
+pub mod simulated_context;
+pub mod smr_context;
+pub mod soak;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
+pub mod test_vectors;
+pub mod triage;
+pub mod unhappy_path;
+
+use bft_simulator_runtime::{
+    base_types::*, ActiveRound, Commit, CommitLog, ConsensusNode, DataSyncNode, EpochConfiguration,
+    NodeUpdateActions, ProcessingTime,
+};
+
+use base_types::*;
@@ -0,0 +1,122 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Light clients track the committed chain from commit certificates alone, without records
+//! nor execution. Starting from a trusted configuration, they only enter the next epoch with a
+//! configuration whose digest was announced by a commit certificate of the current one.
+
+use super::*;
+use committee::VotingCommittee;
+use hashing::HashFunction;
+use record::*;
+
+#[cfg(test)]
+#[path = "unit_tests/light_client_tests.rs"]
+mod light_client_tests;
+
+#[derive(Clone, Debug)]
+pub struct LightClient {
+    /// The current epoch and its voting rights.
+    epoch_id: EpochId,
+    configuration: EpochConfiguration,
+    /// Hash function and network of the signed records.
+    hash_function: HashFunction,
+    chain_id: ChainId,
+    /// Voters of each round, if only a committee of the validators votes.
+    committee: Option<VotingCommittee>,
+    /// The highest commit verified in the current epoch, if any.
+    latest_commit: Option<CommittedState>,
+}
+
+impl LightClient {
+    pub fn new(epoch_id: EpochId, configuration: EpochConfiguration) -> Self {
+        LightClient {
+            epoch_id,
+            configuration,
+            hash_function: HashFunction::SIMULATION,
+            chain_id: ChainId::default(),
+            committee: None,
+            latest_commit: None,
+        }
+    }
+
+    pub fn set_hash_function(&mut self, hash_function: HashFunction) {
+        self.hash_function = hash_function;
+    }
+
+    pub fn set_chain_id(&mut self, chain_id: ChainId) {
+        self.chain_id = chain_id;
+    }
+
+    /// Verify certificates against the committee of their round rather than the whole epoch.
+    pub fn set_committee(&mut self, committee: VotingCommittee) {
+        self.committee = Some(committee);
+    }
+
+    pub fn epoch_id(&self) -> EpochId {
+        self.epoch_id
+    }
+
+    pub fn configuration(&self) -> &EpochConfiguration {
+        &self.configuration
+    }
+
+    pub fn latest_commit(&self) -> Option<&CommittedState> {
+        self.latest_commit.as_ref()
+    }
+
+    /// The next epoch, once a verified commit certificate of the current one announced it.
+    pub fn next_epoch(&self) -> Option<EpochChange> {
+        self.latest_commit
+            .as_ref()
+            .and_then(|committed| committed.next_epoch)
+    }
+
+    /// Verify a commit certificate of the current epoch, and keep what it proves if it is the
+    /// highest one so far.
+    pub fn verify(
+        &mut self,
+        certificate: &QuorumCertificate,
+    ) -> std::result::Result<&CommittedState, VerifyError> {
+        if certificate.epoch_id != self.epoch_id {
+            return Err(VerifyError::WrongEpoch(certificate.epoch_id));
+        }
+        let committee = self.committee.map(|committee| {
+            committee.select(&self.configuration, self.epoch_id, certificate.round)
+        });
+        let committed = verify_commit_certificate(
+            certificate,
+            committee.as_ref().unwrap_or(&self.configuration),
+            self.chain_id,
+            &self.hash_function,
+        )?;
+        let is_higher = match &self.latest_commit {
+            Some(latest) => latest.certified_round < committed.certified_round,
+            None => true,
+        };
+        if is_higher {
+            self.latest_commit = Some(committed);
+        }
+        Ok(self.latest_commit.as_ref().unwrap())
+    }
+
+    /// Enter the given epoch, provided that a verified certificate announced it together with
+    /// the digest of `configuration`.
+    pub fn enter_epoch(
+        &mut self,
+        epoch_id: EpochId,
+        configuration: EpochConfiguration,
+    ) -> std::result::Result<(), VerifyError> {
+        match self.next_epoch() {
+            Some(next_epoch)
+                if next_epoch.epoch_id == epoch_id && next_epoch.certifies(&configuration) =>
+            {
+                self.epoch_id = epoch_id;
+                self.configuration = configuration;
+                self.latest_commit = None;
+                Ok(())
+            }
+            _ => Err(VerifyError::InvalidEpochChange(epoch_id)),
+        }
+    }
+}
@@ -1,79 +1,95 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
-#![allow(bare_trait_objects)]
-
-#[macro_use]
-extern crate failure;
-extern crate rand;
 #[macro_use]
 extern crate log;
 extern crate bft_simulator_runtime;
 extern crate clap;
 extern crate env_logger;
+extern crate librabft_simulator;
 
+use bft_simulator_runtime::{base_types::*, configuration, simulator};
 use clap::{App, Arg};
-use std::{collections::BTreeMap, fmt::Debug};
-
-// Comments in the following form are used for code-block generation in the consensus report:
-//    "// -- BEGIN FILE name --"
-//    "// -- END FILE --"
-// Do not modify definitions without changing the report as well :)
-
-mod base_types;
-mod data_sync;
-mod node;
-mod pacemaker;
-mod record;
-mod record_store;
-mod simulated_context;
-mod smr_context;
-
-use bft_simulator_runtime::{
-    base_types::*, simulator, ActiveRound, ConsensusNode, DataSyncNode, EpochConfiguration,
-    NodeUpdateActions,
+#[cfg(feature = "dashboard")]
+use librabft_simulator::dashboard;
+use librabft_simulator::{
+    epoch_changes, golden_traces, outages, scenario, scenario::Scenario, test_vectors, unhappy_path,
 };
-
-use base_types::*;
-use data_sync::*;
-use node::NodeState;
-use simulated_context::SimulatedContext;
+use std::{fs, path::Path};
 
 fn main() {
     let args = get_arguments();
 
     env_logger::init();
-    let context_factory =
-        |author, num_nodes| SimulatedContext::new(author, num_nodes, args.commands_per_epoch);
-    let node_factory = |author: Author, context: &SimulatedContext, clock: NodeTime| {
-        NodeState::new(
-            author,
-            context.last_committed_state(),
-            clock,
-            args.target_commit_interval,
-            args.delta,
-            args.gamma,
-            args.lambda,
-            context,
-        )
+    if let Some(path) = &args.generate_test_vectors {
+        test_vectors::write_test_vectors(path).expect("Failed to write test vectors");
+        return;
+    }
+    if let Some(directory) = &args.generate_golden_traces {
+        golden_traces::write_golden_traces(directory).expect("Failed to write golden traces");
+        return;
+    }
+    let scenario = match &args.scenario {
+        Some(path) => Scenario::load(path).expect("Failed to load scenario"),
+        None => args.to_scenario(),
     };
-    let delay_distribution = simulator::RandomDelay::new(args.mean, args.variance);
-    let mut sim = simulator::Simulator::<
-        NodeState,
-        SimulatedContext,
-        DataSyncNotification,
-        DataSyncRequest,
-        DataSyncResponse,
-    >::new(
-        args.nodes,
-        delay_distribution,
-        context_factory,
-        node_factory,
-    );
-    let contexts = sim.loop_until(
-        simulator::GlobalTime(args.max_clock),
+    if args.outage_library {
+        for report in outages::evaluate_resilience(&scenario) {
+            println!(
+                "{}: {} to {} commands executed per node, quorum impossible during {:?}",
+                report.name, report.min_commits, report.max_commits, report.quorum_impossible
+            );
+        }
+        return;
+    }
+    if args.epoch_changes {
+        for report in epoch_changes::evaluate_epoch_changes(&scenario) {
+            println!(
+                "{}: {} to {} commands executed per node, epochs {:?}, {} stale records, {} late records, {}",
+                report.name,
+                report.min_commits,
+                report.max_commits,
+                report.epochs,
+                report.stale_records,
+                report.late_records,
+                report.violation.as_deref().unwrap_or("safe")
+            );
+        }
+        return;
+    }
+    let mut sim = scenario.build_simulator();
+    let conditions = scenario
+        .stop
+        .as_ref()
+        .map(|stop| stop.conditions())
+        .unwrap_or_default();
+    #[cfg(feature = "dashboard")]
+    let outcome = match args.dashboard {
+        Some(refresh_period) => {
+            let mut dashboard = dashboard::Dashboard::new(refresh_period, 50);
+            sim.run_until_with_observer(
+                simulator::GlobalTime(scenario.max_clock),
+                args.output_data_files,
+                &conditions,
+                |sim, clock| dashboard.observe(sim, clock),
+            )
+        }
+        None => sim.run_until_with_observer(
+            simulator::GlobalTime(scenario.max_clock),
+            args.output_data_files,
+            &conditions,
+            |_, _| (),
+        ),
+    };
+    #[cfg(not(feature = "dashboard"))]
+    let outcome = sim.run_until_with_observer(
+        simulator::GlobalTime(scenario.max_clock),
         args.output_data_files,
+        &conditions,
+        |_, _| (),
     );
+    warn!("Run outcome: {:?}", outcome);
+    let contexts: Vec<_> = sim.replicas().iter().map(|node| node.context()).collect();
     warn!("Commands executed per node: {:#?}", {
         let x: Vec<_> = contexts
             .iter()
@@ -82,6 +98,36 @@ fn main() {
         x
     });
     info!("SMR contexts: {:#?}", contexts);
+    for (start, end) in scenario::quorum_impossible_intervals(&sim, scenario.max_clock) {
+        warn!(
+            "Commits were impossible during [{}, {}): crashed nodes held too many votes",
+            start, end
+        );
+    }
+    let unhappy_path = unhappy_path::analyze_unhappy_path(&scenario, &sim);
+    warn!(
+        "Rounds without QC: {}, by streak length {:?}, by leader failure {:?}",
+        unhappy_path.failed_rounds(),
+        unhappy_path.failed_round_streaks,
+        unhappy_path.leader_failures
+    );
+    warn!(
+        "Abandoned branches by depth: {:?}",
+        unhappy_path.fork_depths
+    );
+    if let Some(path) = &args.export_dot {
+        if !Path::new(path).exists() {
+            fs::create_dir_all(path).expect("could not create DOT output dir");
+        }
+        for index in 0..scenario.nodes {
+            let node = sim.simulated_node(Author(index)).node().node_state();
+            fs::write(
+                format!("{}/node_{}.dot", path, index),
+                node.record_store().export_dot(),
+            )
+            .expect("could not write DOT file");
+        }
+    }
 }
 
 struct CliArguments {
@@ -94,11 +140,98 @@ struct CliArguments {
     delta: Duration,
     gamma: f64,
     lambda: f64,
+    arrival_rate: usize,
+    mempool_threshold: Option<usize>,
+    max_uncommitted_depth: Option<usize>,
     output_data_files: Option<String>,
+    generate_test_vectors: Option<String>,
+    generate_golden_traces: Option<String>,
+    scenario: Option<String>,
+    export_dot: Option<String>,
+    outage_library: bool,
+    epoch_changes: bool,
+    #[cfg(feature = "dashboard")]
+    dashboard: Option<Duration>,
+}
+
+impl CliArguments {
+    fn to_scenario(&self) -> Scenario {
+        Scenario {
+            nodes: self.nodes,
+            followers: 0,
+            max_clock: self.max_clock,
+            stop: None,
+            voting_rights: Vec::new(),
+            resilience: configuration::DEFAULT_RESILIENCE,
+            reconfigurations: Vec::new(),
+            key_rotations: Vec::new(),
+            joins: Vec::new(),
+            removals: Vec::new(),
+            commands_per_epoch: self.commands_per_epoch,
+            commit_rule: Default::default(),
+            hash_function: None,
+            chain_id: Default::default(),
+            fast_path: None,
+            committee: None,
+            shards: Vec::new(),
+            cross_shard: None,
+            pacemaker: scenario::PacemakerParameters {
+                target_commit_interval: self.target_commit_interval,
+                delta: self.delta,
+                gamma: self.gamma,
+                lambda: self.lambda,
+                optimistic_responsiveness: true,
+                adaptive_delta: None,
+                leader_reputation: None,
+                min_proposal_interval: None,
+            },
+            network: scenario::NetworkParameters {
+                mean: self.mean,
+                variance: self.variance,
+                ..scenario::NetworkParameters::default()
+            },
+            workload: scenario::WorkloadParameters {
+                arrival_rate: self.arrival_rate,
+                mempool_threshold: self.mempool_threshold,
+                max_payload_bytes: None,
+                max_uncommitted_depth: self.max_uncommitted_depth,
+                payload: None,
+                clients: 1,
+            },
+            cpu: scenario::CpuParameters::default(),
+            inbound_queue: None,
+            faults: Vec::new(),
+            topology: scenario::Topology::default(),
+            link_faults: Vec::new(),
+            twins: Vec::new(),
+            flooders: Vec::new(),
+            lock_violators: Vec::new(),
+            qc_withholders: Vec::new(),
+            double_spenders: Vec::new(),
+            censors: Vec::new(),
+            partitions: Vec::new(),
+            clocks: Vec::new(),
+            hardware: Vec::new(),
+            sketch_cells: None,
+            orphan_buffer: None,
+            send_dedup: false,
+            past_epochs_kept: None,
+            epoch_grace_period: None,
+            max_clock_drift: None,
+            qc_cache: None,
+            disk_store: None,
+            scheduler: None,
+            client_requests: None,
+            checkpoint_interval: None,
+            reads: None,
+            triage_dir: None,
+            seed: None,
+        }
+    }
 }
 
 fn get_arguments() -> CliArguments {
-    let matches = App::new("Consensus simulator")
+    let app = App::new("Consensus simulator")
         .about("A monte-carlo simulation of the LibraBFT consensus protocol")
         .arg(
             Arg::with_name("max_clock")
@@ -154,10 +287,69 @@ fn get_arguments() -> CliArguments {
                 .help("Coefficient to control the frequency of query-all actions")
                 .default_value("0.5"),
         )
+        .arg(
+            Arg::with_name("arrival_rate")
+                .long("arrival_rate")
+                .help("Number of client commands arriving in the mempool of a node for each proposal")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("mempool_threshold")
+                .long("mempool_threshold")
+                .help("Mempool depth above which leaders defer low-priority commands")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_uncommitted_depth")
+                .long("max_uncommitted_depth")
+                .help("Number of uncommitted ancestors above which leaders propose empty blocks")
+                .takes_value(true),
+        )
         .arg(Arg::with_name("create_csv").long("create_csv").help(
             "If given this argument, csv files will be generated with data on the simulation"
         ).takes_value(true))
-        .get_matches();
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .help("TOML file describing the scenario to run, instead of the parameters above")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("export_dot")
+                .long("export_dot")
+                .help("Directory where to write the final block tree of each node in DOT format")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("outage_library")
+                .long("outage_library")
+                .help("Run the canned regional outages on top of the scenario and report commits"),
+        )
+        .arg(
+            Arg::with_name("epoch_changes")
+                .long("epoch_changes")
+                .help("Run the canned epoch-change scenarios on top of the scenario and check safety"),
+        )
+        .arg(
+            Arg::with_name("generate_test_vectors")
+                .long("generate_test_vectors")
+                .help("Write the canonical test vectors to the given file and exit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("generate_golden_traces")
+                .long("generate_golden_traces")
+                .help("Record the golden traces into the given directory and exit")
+                .takes_value(true),
+        );
+    #[cfg(feature = "dashboard")]
+    let app = app.arg(
+        Arg::with_name("dashboard")
+            .long("dashboard")
+            .help("Show a live terminal dashboard, refreshed every given amount of simulated time")
+            .takes_value(true),
+    );
+    let matches = app.get_matches();
 
     CliArguments {
         max_clock: matches
@@ -189,6 +381,31 @@ fn get_arguments() -> CliArguments {
             .unwrap(),
         gamma: matches.value_of("gamma").unwrap().parse::<f64>().unwrap(),
         lambda: matches.value_of("lambda").unwrap().parse::<f64>().unwrap(),
+        arrival_rate: matches
+            .value_of("arrival_rate")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        mempool_threshold: matches
+            .value_of("mempool_threshold")
+            .map(|x| x.parse::<usize>().unwrap()),
+        max_uncommitted_depth: matches
+            .value_of("max_uncommitted_depth")
+            .map(|x| x.parse::<usize>().unwrap()),
         output_data_files: matches.value_of("create_csv").map(|x| x.to_string()),
+        scenario: matches.value_of("scenario").map(|x| x.to_string()),
+        export_dot: matches.value_of("export_dot").map(|x| x.to_string()),
+        outage_library: matches.is_present("outage_library"),
+        epoch_changes: matches.is_present("epoch_changes"),
+        #[cfg(feature = "dashboard")]
+        dashboard: matches
+            .value_of("dashboard")
+            .map(|x| x.parse::<Duration>().unwrap()),
+        generate_test_vectors: matches
+            .value_of("generate_test_vectors")
+            .map(|x| x.to_string()),
+        generate_golden_traces: matches
+            .value_of("generate_golden_traces")
+            .map(|x| x.to_string()),
     }
 }
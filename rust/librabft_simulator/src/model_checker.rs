@@ -0,0 +1,345 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded model checking of small configurations. Instead of sampling random network delays,
+//! all the interleavings of message deliveries and timer expirations are enumerated up to a
+//! given depth, and the invariants are checked on every branch.
+//!
+//! Each node has its own logical clock, which only moves forward when its scheduled update is
+//! triggered. A data-synchronization exchange (notification, request, response) is delivered
+//! atomically. Interleavings that only differ by the order of independent transitions are
+//! pruned using sleep sets.
+
+use super::*;
+use campaign::consistent_histories;
+use data_sync::{DataSyncNotification, DataSyncRequest};
+use node::NodeState;
+use scenario::PacemakerParameters;
+use serde::Serialize;
+use simulated_context::SimulatedContext;
+use std::{collections::BTreeSet, fmt};
+
+#[cfg(test)]
+#[path = "unit_tests/model_checker_tests.rs"]
+mod model_checker_tests;
+
+/// Bounds of the exploration.
+#[derive(Clone, Debug)]
+pub struct ModelCheckerConfig {
+    pub nodes: usize,
+    /// Maximal number of transitions in a schedule.
+    pub max_depth: usize,
+    /// Schedules also stop as soon as a node enters a higher round.
+    pub max_round: Round,
+    /// Every schedule must be extendable by this number of fair transitions into a new commit.
+    pub liveness_horizon: usize,
+    /// Whether to prune equivalent interleavings.
+    pub partial_order_reduction: bool,
+    pub pacemaker: PacemakerParameters,
+}
+
+impl Default for ModelCheckerConfig {
+    fn default() -> Self {
+        ModelCheckerConfig {
+            nodes: 4,
+            max_depth: 6,
+            max_round: Round(6),
+            liveness_horizon: 1000,
+            partial_order_reduction: true,
+            pacemaker: PacemakerParameters::default(),
+        }
+    }
+}
+
+/// One step of a schedule.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub enum Transition {
+    /// Deliver a notification, then the request and the response that it triggers, if any.
+    Notify {
+        sender: Author,
+        receiver: Author,
+        notification: Box<DataSyncNotification>,
+    },
+    /// Deliver a query-all request and its response.
+    Query {
+        sender: Author,
+        receiver: Author,
+        request: DataSyncRequest,
+    },
+    /// Trigger the scheduled update of a node.
+    Timer(Author),
+}
+
+impl Transition {
+    /// The node whose state is changed by the transition.
+    fn writes(&self) -> Author {
+        match self {
+            Transition::Notify { receiver, .. } | Transition::Query { receiver, .. } => *receiver,
+            Transition::Timer(author) => *author,
+        }
+    }
+
+    /// The node whose state is only read by the transition, if any.
+    fn reads(&self) -> Option<Author> {
+        match self {
+            Transition::Notify { sender, .. } | Transition::Query { sender, .. } => Some(*sender),
+            Transition::Timer(_) => None,
+        }
+    }
+
+    /// Whether the two transitions commute from any state where both are enabled.
+    fn is_independent(&self, other: &Transition) -> bool {
+        let conflicts = |t1: &Transition, t2: &Transition| {
+            t1.writes() == t2.writes() || Some(t1.writes()) == t2.reads()
+        };
+        !conflicts(self, other) && !conflicts(other, self)
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transition::Notify {
+                sender, receiver, ..
+            } => write!(f, "notify {:?} -> {:?}", sender, receiver),
+            Transition::Query {
+                sender, receiver, ..
+            } => write!(f, "query {:?} -> {:?}", sender, receiver),
+            Transition::Timer(author) => write!(f, "timer {:?}", author),
+        }
+    }
+}
+
+/// Outcome of an exhaustive exploration.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct ModelCheckReport {
+    /// Number of states visited.
+    pub states: usize,
+    /// Number of complete schedules on which liveness was checked.
+    pub leaves: usize,
+    /// Number of transitions skipped by the partial-order reduction.
+    pub pruned: usize,
+    /// A schedule leading to inconsistent commits, if any. The exploration stops there.
+    pub safety_violation: Option<Vec<String>>,
+    /// A schedule after which no commit happens within the liveness horizon, if any.
+    pub liveness_violation: Option<Vec<String>>,
+}
+
+#[derive(Clone)]
+struct ModelState {
+    nodes: Vec<NodeState>,
+    contexts: Vec<SimulatedContext>,
+    clocks: Vec<NodeTime>,
+    next_updates: Vec<NodeTime>,
+    /// Messages in flight.
+    pending: BTreeSet<Transition>,
+}
+
+impl ModelState {
+    fn new(config: &ModelCheckerConfig) -> Self {
+        let contexts: Vec<_> = (0..config.nodes)
+            .map(|index| SimulatedContext::new(Author(index), config.nodes, 30000))
+            .collect();
+        let nodes = contexts
+            .iter()
+            .enumerate()
+            .map(|(index, context)| {
+                NodeState::new(
+                    Author(index),
+                    context.last_committed_state(),
+                    NodeTime(0),
+                    config.pacemaker.target_commit_interval,
+                    config.pacemaker.delta,
+                    config.pacemaker.gamma,
+                    config.pacemaker.lambda,
+                    context,
+                )
+            })
+            .collect();
+        ModelState {
+            nodes,
+            contexts,
+            clocks: vec![NodeTime(0); config.nodes],
+            next_updates: vec![NodeTime(0); config.nodes],
+            pending: BTreeSet::new(),
+        }
+    }
+
+    fn enabled(&self) -> Vec<Transition> {
+        let mut transitions: Vec<_> = self.pending.iter().cloned().collect();
+        for (index, deadline) in self.next_updates.iter().enumerate() {
+            if *deadline < NodeTime::never() {
+                transitions.push(Transition::Timer(Author(index)));
+            }
+        }
+        transitions
+    }
+
+    fn commits(&self) -> usize {
+        self.contexts
+            .iter()
+            .map(|context| context.committed_history().len())
+            .sum()
+    }
+
+    fn max_round(&self) -> Round {
+        self.nodes
+            .iter()
+            .map(ActiveRound::active_round)
+            .max()
+            .unwrap_or(Round(0))
+    }
+
+    fn exchange(&mut self, sender: Author, receiver: Author, request: DataSyncRequest) {
+        let response =
+            DataSyncNode::<SimulatedContext>::handle_request(&self.nodes[sender.0], request);
+        let clock = self.clocks[receiver.0];
+        self.nodes[receiver.0].handle_response(response, &mut self.contexts[receiver.0], clock);
+    }
+
+    fn apply(&mut self, transition: &Transition) {
+        self.pending.remove(transition);
+        let author = transition.writes();
+        match transition.clone() {
+            Transition::Notify {
+                sender,
+                receiver,
+                notification,
+            } => {
+                let node = &mut self.nodes[receiver.0];
+                if let Some(request) =
+                    node.handle_notification(*notification, &mut self.contexts[receiver.0])
+                {
+                    self.exchange(sender, receiver, request);
+                }
+            }
+            Transition::Query {
+                sender,
+                receiver,
+                request,
+            } => self.exchange(sender, receiver, request),
+            Transition::Timer(_) => {
+                self.clocks[author.0] =
+                    std::cmp::max(self.clocks[author.0], self.next_updates[author.0]);
+            }
+        }
+        let actions =
+            self.nodes[author.0].update_node(self.clocks[author.0], &mut self.contexts[author.0]);
+        self.next_updates[author.0] = actions.next_scheduled_update;
+        let others = (0..self.nodes.len())
+            .map(Author)
+            .filter(|other| *other != author);
+        let mut receivers: BTreeSet<_> = actions.should_send.into_iter().collect();
+        if actions.should_broadcast {
+            receivers.extend(others.clone());
+        }
+        let node = &self.nodes[author.0];
+        for receiver in receivers {
+            self.pending.insert(Transition::Notify {
+                sender: author,
+                receiver,
+                notification: Box::new(DataSyncNode::<SimulatedContext>::create_notification(node)),
+            });
+        }
+        if actions.should_query_all {
+            for sender in others {
+                self.pending.insert(Transition::Query {
+                    sender,
+                    receiver: author,
+                    request: DataSyncNode::<SimulatedContext>::create_request(node),
+                });
+            }
+        }
+    }
+
+    /// Whether a new commit happens within `horizon` transitions when messages are delivered
+    /// in order before any timer is triggered, earliest deadline first.
+    fn commits_eventually(&self, horizon: usize) -> bool {
+        let mut state = self.clone();
+        let commits = state.commits();
+        for _ in 0..horizon {
+            let transition = match state.pending.iter().next() {
+                Some(transition) => transition.clone(),
+                None => match (0..state.nodes.len())
+                    .filter(|index| state.next_updates[*index] < NodeTime::never())
+                    .min_by_key(|index| state.next_updates[*index])
+                {
+                    Some(index) => Transition::Timer(Author(index)),
+                    None => return false,
+                },
+            };
+            state.apply(&transition);
+            if state.commits() > commits {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+struct ModelChecker<'a> {
+    config: &'a ModelCheckerConfig,
+    report: ModelCheckReport,
+    schedule: Vec<Transition>,
+}
+
+impl<'a> ModelChecker<'a> {
+    fn current_schedule(&self) -> Vec<String> {
+        self.schedule.iter().map(ToString::to_string).collect()
+    }
+
+    fn explore(&mut self, state: &ModelState, mut sleep: BTreeSet<Transition>) {
+        self.report.states += 1;
+        let contexts: Vec<_> = state.contexts.iter().collect();
+        if !consistent_histories(&contexts) {
+            self.report.safety_violation = Some(self.current_schedule());
+            return;
+        }
+        let enabled = state.enabled();
+        if self.schedule.len() >= self.config.max_depth
+            || state.max_round() > self.config.max_round
+            || enabled.is_empty()
+        {
+            self.report.leaves += 1;
+            if self.report.liveness_violation.is_none()
+                && !state.commits_eventually(self.config.liveness_horizon)
+            {
+                self.report.liveness_violation = Some(self.current_schedule());
+            }
+            return;
+        }
+        for transition in enabled {
+            if self.report.safety_violation.is_some() {
+                return;
+            }
+            if sleep.contains(&transition) {
+                self.report.pruned += 1;
+                continue;
+            }
+            let child_sleep = sleep
+                .iter()
+                .filter(|other| other.is_independent(&transition))
+                .cloned()
+                .collect();
+            let mut child = state.clone();
+            child.apply(&transition);
+            self.schedule.push(transition.clone());
+            self.explore(&child, child_sleep);
+            self.schedule.pop();
+            if self.config.partial_order_reduction {
+                sleep.insert(transition);
+            }
+        }
+    }
+}
+
+/// Explore all the schedules allowed by `config` from the initial state.
+pub fn check(config: &ModelCheckerConfig) -> ModelCheckReport {
+    let mut checker = ModelChecker {
+        config,
+        report: ModelCheckReport::default(),
+        schedule: Vec::new(),
+    };
+    checker.explore(&ModelState::new(config), BTreeSet::new());
+    checker.report
+}
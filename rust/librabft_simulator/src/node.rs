@@ -3,14 +3,32 @@
 
 use super::*;
 use base_types::*;
+use checkpoints::{Checkpoint, CheckpointLog};
+use commit_rule::{CommitRule, FastPath};
+use commit_stream::{CommitNotification, CommitStream, CommitSubscribers};
+use committee::VotingCommittee;
+use data_sync::{EpochProof, EpochSnapshot};
+#[cfg(feature = "disk_store")]
+use disk_store::DiskStorage;
+use hashing::HashFunction;
+use introspection::{CommitTrackerSnapshot, LeaderHint, NodeSnapshot, SNAPSHOT_SCHEMA_VERSION};
+use light_client::LightClient;
+use node_error::{NodeError, NodeResult};
+use orphan_buffer::{OrphanBuffer, OrphanStats};
 use pacemaker::*;
+use proposal_limiter::{ProposalDenial, ProposalLimiter};
+use qc_cache::{QuorumCertificateCache, QuorumCertificateCacheStats};
+use reads::{ReadError, ReadResponse};
+use reconciliation::ReconciliationStats;
 use record::*;
 use record_store::*;
-use smr_context::SMRContext;
+use send_dedup::SentRecords;
+use smr_context::{LoadSheddingPolicy, Priority, SMRContext};
 
 use std::{
     cmp::{max, min},
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
 };
 
 #[cfg(test)]
@@ -18,7 +36,7 @@ use std::{
 mod node_tests;
 
 // -- BEGIN FILE node_state --
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NodeState {
     /// Module dedicated to storing records for the current epoch.
     record_store: RecordStoreState,
@@ -38,11 +56,112 @@ pub struct NodeState {
     tracker: CommitTracker,
     /// Record stores from previous epochs.
     past_record_stores: HashMap<EpochId, RecordStoreState>,
+    /// Optional behaviors of the node, set once before it starts.
+    settings: NodeSettings,
+    /// Counters and measurements reported by the node.
+    metrics: NodeMetrics,
+    /// State of the features on top of the core protocol, e.g. commit delivery and data sync.
+    extensions: NodeExtensions,
 }
 // -- END FILE --
 
+/// Optional behaviors of a node, all disabled by default.
+#[derive(Clone, Debug, Default)]
+struct NodeSettings {
+    /// Number of past epochs whose record stores are kept, if bounded.
+    past_epochs_kept: Option<usize>,
+    /// How long after entering an epoch we still verify and store the blocks and QCs of the
+    /// previous one, if at all.
+    epoch_grace_period: Option<Duration>,
+    /// How to select commands when the mempool is overloaded, if at all.
+    load_shedding: Option<LoadSheddingPolicy>,
+    /// Number of uncommitted ancestors above which leaders propose empty blocks, if any.
+    max_uncommitted_depth: Option<usize>,
+    /// Clients whose commands we never propose, if the node is Byzantine.
+    censored_clients: BTreeSet<usize>,
+    /// Number of cells of the sketches sent in data-sync requests, if any.
+    sketch_cells: Option<usize>,
+    /// Whether we serve snapshots of the state starting our current epoch, and bootstrap from
+    /// one when we have not committed anything yet.
+    snapshot_sync: bool,
+    /// Whether the signatures of the records received in batches are verified in parallel.
+    parallel_verification: bool,
+    /// Whether the node is Byzantine and ignores its voting constraints.
+    violates_locks: bool,
+    /// Whether the node is Byzantine and proposes blocks extending stale QCs.
+    withholds_quorum_certificates: bool,
+    /// Whether the node is Byzantine and proposes again the command of its latest commit.
+    proposes_double_spends: bool,
+}
+
+/// Counters and measurements of a node.
+#[derive(Clone, Debug, Default)]
+struct NodeMetrics {
+    /// Number of blocks and QCs of the previous epoch inserted during grace periods.
+    late_records: usize,
+    /// Usefulness of the records received during data sync.
+    reconciliation: ReconciliationStats,
+    /// Number of records received from the network and rejected, by reason.
+    rejections: BTreeMap<&'static str, usize>,
+    /// Number of blocks delivered to the SMR layer after a commit on the fast path.
+    fast_path_commits: usize,
+    /// Number of votes that broke the voting constraints.
+    lock_violations: usize,
+    /// Number of QCs left out of the chains extended by our proposals.
+    withheld_quorum_certificates: usize,
+    /// Number of our proposals replaying a committed command.
+    double_spends: usize,
+    /// Number of proposals that we did not vote for because the SMR layer rejected their
+    /// command.
+    rejected_payloads: usize,
+    /// Number of rounds whose proposal we did not vote for because we were not in the
+    /// committee of the round.
+    committee_abstentions: usize,
+    /// Largest number of uncommitted blocks seen in the chain of the highest QC.
+    deepest_uncommitted_chain: usize,
+    /// Local time between each of our proposals and the creation of its QC.
+    quorum_certificate_latencies: Vec<Duration>,
+    /// Time at which we installed a snapshot, if we bootstrapped from one.
+    snapshot_time: Option<NodeTime>,
+    /// Time of our first vote with voting rights, if any.
+    first_vote_time: Option<NodeTime>,
+    /// The command of each block of the commit log, and the local time between its proposal
+    /// and its commit.
+    commit_latencies: Vec<(Option<Command>, Duration)>,
+}
+
+/// State of the features of a node beyond voting and proposing.
+#[derive(Clone, Debug, Default)]
+struct NodeExtensions {
+    /// The previous epoch and the end of its grace period, if it is not over yet.
+    grace_period: Option<(EpochId, NodeTime)>,
+    /// All the commits delivered to the SMR layer.
+    commit_log: Vec<Commit<BlockHash, State>>,
+    /// Subscriptions to the commits delivered to the SMR layer.
+    commit_subscribers: CommitSubscribers,
+    /// Blocks of the current epoch reported to the SMR layer as conflicting with a commit.
+    rolled_back_blocks: HashSet<BlockHash>,
+    /// Records of the current epoch received before their parent, if buffered at all.
+    orphans: Option<OrphanBuffer>,
+    /// Records already sent to each peer in notifications, if they are not sent again.
+    sent_records: Option<SentRecords>,
+    /// Until when we delay the QC of our proposal of the given round to gather a fast-path quorum.
+    fast_quorum_deadline: Option<(EpochId, Round, NodeTime)>,
+    /// Snapshot of the state starting the current epoch, if we serve snapshots.
+    epoch_snapshot: Option<EpochSnapshot>,
+    /// How each epoch since the first one started, if we serve snapshots.
+    epoch_proofs: Vec<EpochProof>,
+    /// Limits on our own proposals, if any.
+    proposal_limiter: Option<ProposalLimiter>,
+    /// Failures of our own actions during the latest update, e.g. a vote for a block that could
+    /// not be executed.
+    update_errors: Vec<NodeError>,
+    /// Signed digests of our commits, if enabled.
+    checkpoint_log: Option<CheckpointLog>,
+}
+
 // -- BEGIN FILE commit_tracker --
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct CommitTracker {
     /// Latest epoch identifier that was processed.
     epoch_id: EpochId,
@@ -64,9 +183,20 @@ impl CommitTracker {
             target_commit_interval,
         }
     }
+
+    /// Read-only copy of the status of the tracker, for monitoring.
+    pub fn snapshot(&self) -> CommitTrackerSnapshot {
+        CommitTrackerSnapshot {
+            epoch_id: self.epoch_id,
+            highest_committed_round: self.highest_committed_round,
+            latest_commit_time: self.latest_commit_time,
+            target_commit_interval: self.target_commit_interval,
+        }
+    }
 }
 
 impl NodeState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         local_author: Author,
         initial_state: State,
@@ -95,6 +225,9 @@ impl NodeState {
             latest_query_all_time: node_time,
             tracker,
             past_record_stores: HashMap::new(),
+            settings: NodeSettings::default(),
+            metrics: NodeMetrics::default(),
+            extensions: NodeExtensions::default(),
         }
     }
 
@@ -116,7 +249,7 @@ impl NodeState {
         }
         match self.past_record_stores.get(&epoch_id) {
             None => None,
-            Some(store) => Some(&*store),
+            Some(store) => Some(store),
         }
     }
 
@@ -124,6 +257,483 @@ impl NodeState {
         &self.pacemaker
     }
 
+    pub fn locked_round(&self) -> Round {
+        self.locked_round
+    }
+
+    pub fn latest_voted_round(&self) -> Round {
+        self.latest_voted_round
+    }
+
+    /// Voting rights in the current epoch.
+    pub fn configuration(&self) -> &EpochConfiguration {
+        self.record_store.configuration()
+    }
+
+    /// Voting rights in the given epoch, if we went through it.
+    pub fn configuration_at(&self, epoch_id: EpochId) -> Option<&EpochConfiguration> {
+        if epoch_id == self.epoch_id {
+            return Some(self.record_store.configuration());
+        }
+        self.past_record_stores
+            .get(&epoch_id)
+            .map(RecordStoreState::configuration)
+    }
+
+    /// Only keep the record stores of the last `past_epochs_kept` epochs. Peers lagging behind
+    /// older epochs then have to catch up from other nodes.
+    pub fn set_past_epochs_kept(&mut self, past_epochs_kept: usize) {
+        self.settings.past_epochs_kept = Some(past_epochs_kept);
+    }
+
+    /// Keep verifying and storing the blocks and QCs of the previous epoch for the given
+    /// duration after entering a new one, so that peers that are still in the previous epoch
+    /// can sync from us until they catch up.
+    pub fn set_epoch_grace_period(&mut self, epoch_grace_period: Duration) {
+        self.settings.epoch_grace_period = Some(epoch_grace_period);
+    }
+
+    /// Keep a snapshot of the state starting each epoch for peers that join the network, and
+    /// bootstrap from a peer's snapshot rather than replaying all the epochs if we have not
+    /// committed anything yet.
+    pub fn set_snapshot_sync(&mut self) {
+        self.settings.snapshot_sync = true;
+    }
+
+    /// Whether our next data-sync requests ask for a snapshot.
+    pub(crate) fn needs_snapshot(&self) -> bool {
+        self.settings.snapshot_sync
+            && self.extensions.commit_log.is_empty()
+            && self.metrics.snapshot_time.is_none()
+    }
+
+    pub(crate) fn epoch_snapshot(&self) -> Option<&EpochSnapshot> {
+        self.extensions.epoch_snapshot.as_ref()
+    }
+
+    /// Time at which we installed a snapshot, if we bootstrapped from one.
+    pub fn snapshot_time(&self) -> Option<NodeTime> {
+        self.metrics.snapshot_time
+    }
+
+    /// Time of our first vote with voting rights, if any.
+    pub fn first_vote_time(&self) -> Option<NodeTime> {
+        self.metrics.first_vote_time
+    }
+
+    /// Number of blocks and QCs of the previous epoch inserted during grace periods.
+    pub fn late_records(&self) -> usize {
+        self.metrics.late_records
+    }
+
+    /// Whether the records of the given epoch are still accepted, after the end of the epoch.
+    pub fn in_grace_period(&self, epoch_id: EpochId) -> bool {
+        match (
+            self.extensions.grace_period,
+            self.record_store.local_clock(),
+        ) {
+            (Some((grace_epoch_id, end)), Some(clock)) => {
+                grace_epoch_id == epoch_id && clock <= end
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of record stores kept for past epochs.
+    pub fn num_past_record_stores(&self) -> usize {
+        self.past_record_stores.len()
+    }
+
+    /// Read-only copy of the status of the node and of its record store, pacemaker, and commit
+    /// tracker, for monitoring tools.
+    pub fn snapshot(&self) -> NodeSnapshot {
+        NodeSnapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            author: self.local_author,
+            epoch_id: self.epoch_id,
+            latest_voted_round: self.latest_voted_round,
+            locked_round: self.locked_round,
+            latest_query_all_time: self.latest_query_all_time,
+            num_commits: self.extensions.commit_log.len(),
+            num_past_record_stores: self.past_record_stores.len(),
+            update_errors: self
+                .extensions
+                .update_errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect(),
+            record_store: self.record_store.snapshot(),
+            pacemaker: self.pacemaker.snapshot(),
+            commit_tracker: self.tracker.snapshot(),
+        }
+    }
+
+    /// The leader that clients should send their commands to, as known by the pacemaker.
+    pub fn leader_hint(&self) -> LeaderHint {
+        LeaderHint {
+            epoch_id: self.pacemaker.active_epoch(),
+            round: self.pacemaker.active_round(),
+            leader: self.pacemaker.active_leader(),
+        }
+    }
+
+    /// Sign a digest of all our committed states every `interval` rounds.
+    pub fn set_checkpoint_interval(&mut self, interval: usize) {
+        self.extensions.checkpoint_log = Some(CheckpointLog::new(interval));
+    }
+
+    /// Checkpoints taken so far, if enabled.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        self.extensions
+            .checkpoint_log
+            .as_ref()
+            .map_or(&[], CheckpointLog::checkpoints)
+    }
+
+    /// The latest committed state, however far behind the other validators we are.
+    pub fn read_local(&self) -> ReadResponse {
+        match self.extensions.commit_log.last() {
+            Some(commit) => ReadResponse {
+                author: self.local_author,
+                epoch_id: EpochId(commit.epoch),
+                round: commit.round,
+                state: Some(commit.state.clone()),
+                commits: self.extensions.commit_log.len(),
+            },
+            None => ReadResponse {
+                author: self.local_author,
+                epoch_id: self.epoch_id,
+                round: Round(0),
+                state: None,
+                commits: 0,
+            },
+        }
+    }
+
+    /// The latest committed state, if we lead the active round and the round started less
+    /// than `lease` ago at local time `clock`.
+    pub fn read_with_lease(
+        &self,
+        clock: NodeTime,
+        lease: Duration,
+    ) -> std::result::Result<ReadResponse, ReadError> {
+        if self.pacemaker.active_leader() != Some(self.local_author) {
+            return Err(ReadError::NotLeader(self.leader_hint()));
+        }
+        let expiry = self.pacemaker.active_round_start_time() + lease;
+        if clock >= expiry {
+            return Err(ReadError::LeaseExpired { expiry });
+        }
+        Ok(self.read_local())
+    }
+
+    pub fn set_load_shedding_policy(&mut self, policy: LoadSheddingPolicy) {
+        self.settings.load_shedding = Some(policy);
+    }
+
+    /// Describe our records with sketches of the given number of cells in data-sync requests,
+    /// so that peers can send exactly the records that we miss.
+    pub fn set_sketch_cells(&mut self, sketch_cells: usize) {
+        self.settings.sketch_cells = Some(sketch_cells);
+    }
+
+    pub fn sketch_cells(&self) -> Option<usize> {
+        self.settings.sketch_cells
+    }
+
+    pub fn reconciliation_stats(&self) -> &ReconciliationStats {
+        &self.metrics.reconciliation
+    }
+
+    /// Number of records received from the network and rejected, by reason, see
+    /// `RejectReason::name`.
+    pub fn rejections(&self) -> &BTreeMap<&'static str, usize> {
+        &self.metrics.rejections
+    }
+
+    /// Keep up to `capacity` records received before their parent, and insert them when the
+    /// parent arrives. Records more than `max_round_age` rounds old are dropped.
+    pub fn set_orphan_buffer(&mut self, capacity: usize, max_round_age: usize) {
+        self.extensions.orphans = Some(OrphanBuffer::new(capacity, max_round_age));
+    }
+
+    pub fn orphan_stats(&self) -> Option<&OrphanStats> {
+        self.extensions.orphans.as_ref().map(OrphanBuffer::stats)
+    }
+
+    /// Leave out of notifications the records that were sent to the same receiver before.
+    /// Lost records are then only recovered by data sync.
+    pub fn set_send_dedup(&mut self) {
+        self.extensions.sent_records = Some(SentRecords::default());
+    }
+
+    /// Behave as a Byzantine validator that votes for every proposal of the current leader,
+    /// once per round but regardless of `locked_round` and `latest_voted_round`: below its
+    /// lock, and after creating a timeout for the round, which amounts to a second vote.
+    pub fn set_lock_violation(&mut self) {
+        self.settings.violates_locks = true;
+    }
+
+    pub fn lock_violations(&self) -> usize {
+        self.metrics.lock_violations
+    }
+
+    /// Behave as a Byzantine leader that withholds the newest QCs: proposals extend the oldest
+    /// QC not below `locked_round` in the chain of the highest QC, so that honest nodes still
+    /// vote for them while the newer blocks are abandoned.
+    pub fn set_quorum_certificate_withholding(&mut self) {
+        self.settings.withholds_quorum_certificates = true;
+    }
+
+    pub fn withheld_quorum_certificates(&self) -> usize {
+        self.metrics.withheld_quorum_certificates
+    }
+
+    /// Behave as a Byzantine leader that proposes again the command of its latest commit, as a
+    /// double spend. The node votes for its own proposals regardless of their validity.
+    pub fn set_double_spending(&mut self) {
+        self.settings.proposes_double_spends = true;
+    }
+
+    pub fn double_spends(&self) -> usize {
+        self.metrics.double_spends
+    }
+
+    /// Number of proposals left without our vote because `validate_payload` rejected them.
+    pub fn rejected_payloads(&self) -> usize {
+        self.metrics.rejected_payloads
+    }
+
+    /// Number of proposals left without our vote because we were not in the committee of
+    /// their round.
+    pub fn committee_abstentions(&self) -> usize {
+        self.metrics.committee_abstentions
+    }
+
+    /// Failures of our own actions, i.e. timeouts, proposals, votes, and QCs, during the
+    /// latest call to `update_node`.
+    pub fn update_errors(&self) -> &[NodeError] {
+        &self.extensions.update_errors
+    }
+
+    /// Keep the error of a local action for the host, if any.
+    fn check_action<T>(&mut self, result: NodeResult<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                debug!("{:?} Failed to act: {}", self.local_author, error);
+                self.extensions.update_errors.push(error);
+                None
+            }
+        }
+    }
+
+    /// Largest number of uncommitted blocks seen so far in the chain of the highest QC.
+    pub fn deepest_uncommitted_chain(&self) -> usize {
+        self.metrics.deepest_uncommitted_chain
+    }
+
+    /// Local time between each of our proposals that was certified and the creation of its QC.
+    pub fn quorum_certificate_latencies(&self) -> &[Duration] {
+        &self.metrics.quorum_certificate_latencies
+    }
+
+    /// Propose at most once per round, even if the pacemaker asks again, and wait at least
+    /// `min_interval` between two proposals. In strict mode, a second proposal for the same round
+    /// panics instead of being dropped.
+    pub fn set_proposal_limiter(&mut self, min_interval: Duration, strict: bool) {
+        self.extensions.proposal_limiter = Some(ProposalLimiter::new(min_interval, strict));
+    }
+
+    pub fn proposal_limiter(&self) -> Option<&ProposalLimiter> {
+        self.extensions.proposal_limiter.as_ref()
+    }
+
+    pub(crate) fn sent_records_mut(&mut self) -> Option<&mut SentRecords> {
+        self.extensions.sent_records.as_mut()
+    }
+
+    /// Record the current time of the local clock, against which the times of blocks are
+    /// checked.
+    pub(crate) fn update_clock(&mut self, clock: NodeTime) {
+        self.record_store.update_clock(clock);
+    }
+
+    /// Account for the blocks and QCs received in a data-sync response.
+    pub(crate) fn record_sync_stats(&mut self, received: usize, missing: usize) {
+        self.metrics.reconciliation.records_received += received;
+        self.metrics.reconciliation.records_missing += missing;
+    }
+
+    /// Apply back-pressure: instead of extending a chain with more than `max_uncommitted_depth`
+    /// uncommitted blocks, leaders propose empty blocks until some of them are committed.
+    pub fn set_max_uncommitted_depth(&mut self, max_uncommitted_depth: usize) {
+        self.settings.max_uncommitted_depth = Some(max_uncommitted_depth);
+    }
+
+    /// Behave as a Byzantine leader that never proposes the commands of the given clients.
+    /// They stay in the mempool, while other commands are proposed as usual.
+    pub fn set_censored_clients(&mut self, clients: BTreeSet<usize>) {
+        self.settings.censored_clients = clients;
+    }
+
+    /// Change the rule deciding when blocks are committed, in this epoch and the next ones.
+    pub fn set_commit_rule(&mut self, commit_rule: Arc<CommitRule>) {
+        self.record_store.set_commit_rule(commit_rule);
+    }
+
+    /// Commit with one QC less when the last QC of a chain gathers enough votes.
+    pub fn set_fast_path(&mut self, fast_path: FastPath) {
+        self.record_store.set_fast_path(fast_path);
+    }
+
+    /// Only let a committee of the validators vote at each round, in this epoch and the next
+    /// ones.
+    pub fn set_committee(&mut self, committee: VotingCommittee) {
+        self.record_store.set_committee(committee);
+    }
+
+    pub fn committee(&self) -> Option<VotingCommittee> {
+        self.record_store.committee()
+    }
+
+    /// Reject the blocks whose time is more than the given duration ahead of the local clock.
+    pub fn set_max_clock_drift(&mut self, max_clock_drift: Duration) {
+        self.record_store.set_max_clock_drift(max_clock_drift);
+    }
+
+    /// Remember the last `capacity` QCs whose signatures were verified, so that copies received
+    /// from other peers are not verified again. The cache is kept across epochs.
+    pub fn set_quorum_certificate_cache(&mut self, capacity: usize) {
+        self.record_store
+            .set_quorum_certificate_cache(QuorumCertificateCache::new(capacity));
+    }
+
+    pub fn quorum_certificate_cache_stats(&self) -> Option<&QuorumCertificateCacheStats> {
+        self.record_store.quorum_certificate_cache_stats()
+    }
+
+    /// Verify the signatures of the records received in batches, i.e. in data-sync responses,
+    /// on the rayon thread pool before inserting them one by one in their original order.
+    pub fn set_parallel_verification(&mut self) {
+        self.settings.parallel_verification = true;
+    }
+
+    /// Hash records with the given function, as all the other replicas. Must be called before
+    /// any record is created or received.
+    pub fn set_hash_function(&mut self, hash_function: HashFunction) {
+        self.record_store.set_hash_function(hash_function);
+    }
+
+    pub fn hash_function(&self) -> HashFunction {
+        self.record_store.hash_function()
+    }
+
+    /// Sign and verify records for the given network, as all the other replicas. Must be called
+    /// before any record is created or received.
+    pub fn set_chain_id(&mut self, chain_id: ChainId) {
+        self.record_store.set_chain_id(chain_id);
+    }
+
+    pub fn chain_id(&self) -> ChainId {
+        self.record_store.chain_id()
+    }
+
+    /// The key signing our records in the current epoch.
+    pub fn public_key(&self) -> PublicKey {
+        self.record_store.public_key(self.local_author)
+    }
+
+    /// Keep the blocks and QCs of this epoch and the next ones in the given database. Must be
+    /// called before any record is received.
+    #[cfg(feature = "disk_store")]
+    pub fn set_disk_storage(&mut self, storage: DiskStorage) -> Result<()> {
+        self.record_store.set_disk_storage(storage)
+    }
+
+    pub fn fast_path_commits(&self) -> usize {
+        self.metrics.fast_path_commits
+    }
+
+    /// The command of each committed block, in the order of the commit log, and the time
+    /// between its proposal and its commit. Both times are read from the local clock, which is
+    /// also the clock of the proposer in the absence of clock skews.
+    pub fn commit_latencies(&self) -> &[(Option<Command>, Duration)] {
+        &self.metrics.commit_latencies
+    }
+
+    /// Receive the commits delivered to the SMR layer from now on, including the ones of later
+    /// epochs.
+    pub fn subscribe_commits(&mut self) -> CommitStream {
+        self.extensions.commit_subscribers.subscribe()
+    }
+
+    pub fn set_optimistic_responsiveness(&mut self, optimistic_responsiveness: bool) {
+        self.pacemaker
+            .set_optimistic_responsiveness(optimistic_responsiveness);
+    }
+
+    /// Learn `delta` from the observed round durations instead of using a fixed value.
+    pub fn set_adaptive_delta(&mut self, adaptive_delta: AdaptiveDelta) {
+        self.pacemaker.set_adaptive_delta(adaptive_delta);
+    }
+
+    pub fn adaptive_delta(&self) -> Option<&AdaptiveDelta> {
+        self.pacemaker.adaptive_delta()
+    }
+
+    /// Deprioritize the leaders of the rounds that recently timed out.
+    pub fn set_leader_reputation(&mut self, leader_reputation: LeaderReputation) {
+        self.pacemaker.set_leader_reputation(leader_reputation);
+    }
+
+    pub fn leader_reputation(&self) -> Option<&LeaderReputation> {
+        self.pacemaker.leader_reputation()
+    }
+
+    /// Whether extending the given QC would exceed the maximal number of uncommitted blocks.
+    fn is_under_back_pressure(&self, previous_qc_hash: QuorumCertificateHash) -> bool {
+        match self.settings.max_uncommitted_depth {
+            Some(max_depth) => self.record_store.uncommitted_depth(previous_qc_hash) > max_depth,
+            None => false,
+        }
+    }
+
+    /// While our proposal has a quorum of votes but not a fast-path quorum, the time until which
+    /// we wait for more votes before creating its QC.
+    fn fast_quorum_deadline(&mut self, clock: NodeTime) -> Option<NodeTime> {
+        let fast_path = self.record_store.fast_path()?;
+        let weight = self.record_store.won_election_weight(self.local_author)?;
+        if weight >= self.record_store.fast_quorum_threshold()? {
+            return None;
+        }
+        let round = self.record_store.current_round();
+        match self.extensions.fast_quorum_deadline {
+            Some((epoch_id, r, deadline)) if epoch_id == self.epoch_id && r == round => {
+                Some(deadline)
+            }
+            _ => {
+                let deadline = clock + fast_path.wait;
+                self.extensions.fast_quorum_deadline = Some((self.epoch_id, round, deadline));
+                Some(deadline)
+            }
+        }
+    }
+
+    /// Fetch the next command to propose, leaving out censored clients and deferring
+    /// low-priority commands and large payloads under overload.
+    fn fetch_command(&self, smr_context: &mut SMRContext) -> Option<Command> {
+        if !self.settings.censored_clients.is_empty() {
+            return smr_context.fetch_excluding_clients(&self.settings.censored_clients);
+        }
+        match &self.settings.load_shedding {
+            Some(policy) if smr_context.mempool_depth() > policy.mempool_threshold => {
+                smr_context.fetch_with_limits(Priority::High, policy.max_payload_bytes)
+            }
+            _ => smr_context.fetch(),
+        }
+    }
+
     pub fn update_tracker(&mut self, clock: NodeTime) {
         // Ignore actions
         self.tracker.update_tracker(
@@ -134,19 +744,121 @@ impl NodeState {
         );
     }
 
+    /// Insert a record of the given epoch. Rejected records are counted by reason, so that
+    /// callers may ignore the result. With an orphan buffer, records of the current epoch whose
+    /// parent is unknown are kept until the parent is inserted.
     pub fn insert_network_record(
         &mut self,
         epoch_id: EpochId,
         record: Record,
         smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted> {
+        self.insert_record(epoch_id, record, false, smr_context)
+    }
+
+    /// Insert records of the given epoch in order, as `insert_network_record` does. With
+    /// parallel verification, the signatures of the records of the current epoch are verified
+    /// in parallel first.
+    pub fn insert_network_records(
+        &mut self,
+        epoch_id: EpochId,
+        records: Vec<Record>,
+        smr_context: &mut SMRContext,
     ) {
-        if epoch_id == self.epoch_id {
-            self.record_store.insert_network_record(record, smr_context);
+        let verified = if self.settings.parallel_verification && epoch_id == self.epoch_id {
+            let verified = self.record_store.verify_signatures_in_parallel(&records);
+            let count = records
+                .iter()
+                .zip(&verified)
+                .filter(|(_, verified)| **verified)
+                .map(|(record, _)| record.num_signatures())
+                .sum();
+            smr_context.verified_signatures_in_parallel(count);
+            verified
+        } else {
+            vec![false; records.len()]
+        };
+        for (record, verified) in records.into_iter().zip(verified) {
+            let _ = self.insert_record(epoch_id, record, verified, smr_context);
+        }
+    }
+
+    /// Insert a record of the given epoch, whose signatures were already verified if
+    /// `verified_signatures` is true.
+    fn insert_record(
+        &mut self,
+        epoch_id: EpochId,
+        record: Record,
+        verified_signatures: bool,
+        smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted> {
+        let result = if epoch_id == self.epoch_id {
+            let orphan = self.extensions.orphans.as_ref().map(|_| record.clone());
+            let result = if verified_signatures {
+                self.record_store
+                    .insert_verified_network_record(record, smr_context)
+            } else {
+                self.record_store.insert_network_record(record, smr_context)
+            };
+            match (&result, orphan) {
+                (Ok(inserted), _) => self.adopt_orphans(inserted.digest, smr_context),
+                (Err(RejectReason::UnknownParent), Some(orphan)) => {
+                    let current_round = self.record_store.current_round();
+                    if let Some(orphans) = &mut self.extensions.orphans {
+                        orphans.push(orphan, current_round);
+                    }
+                }
+                _ => (),
+            }
+            result
+        } else if self.in_grace_period(epoch_id) && self.past_record_stores.contains_key(&epoch_id)
+        {
+            let result = self
+                .past_record_stores
+                .get_mut(&epoch_id)
+                .unwrap()
+                .insert_late_record(record, smr_context);
+            if result.is_ok() {
+                self.metrics.late_records += 1;
+            }
+            result
         } else {
             debug!(
                 "{:?} Skipped records outside the current epoch ({:?} instead of {:?})",
                 self.local_author, epoch_id, self.epoch_id
             );
+            if epoch_id > self.epoch_id {
+                Err(RejectReason::FutureEpoch)
+            } else {
+                Err(RejectReason::PastEpoch)
+            }
+        };
+        if let Err(reason) = result {
+            *self.metrics.rejections.entry(reason.name()).or_insert(0) += 1;
+        }
+        result
+    }
+
+    /// Insert the buffered records waiting for the given record, then their own children.
+    fn adopt_orphans(&mut self, digest: u64, smr_context: &mut SMRContext) {
+        let mut parents = vec![digest];
+        while let Some(parent) = parents.pop() {
+            let current_round = self.record_store.current_round();
+            let children = match &mut self.extensions.orphans {
+                Some(orphans) => {
+                    orphans.evict_stale(current_round);
+                    orphans.take_children(parent)
+                }
+                None => return,
+            };
+            for child in children {
+                if let Ok(inserted) = self.record_store.insert_network_record(child, smr_context) {
+                    parents.push(inserted.digest);
+                    if let Some(orphans) = &mut self.extensions.orphans {
+                        orphans.record_adoption();
+                    }
+                }
+            }
         }
     }
 }
@@ -157,6 +869,15 @@ impl ActiveRound for NodeState {
     }
 }
 
+impl CommitLog for NodeState {
+    type Block = BlockHash;
+    type State = State;
+
+    fn commits(&self) -> &[Commit<BlockHash, State>] {
+        &self.extensions.commit_log
+    }
+}
+
 // -- BEGIN FILE process_pacemaker_actions --
 impl NodeState {
     fn process_pacemaker_actions(
@@ -171,27 +892,114 @@ impl NodeState {
         actions.should_query_all = pacemaker_actions.should_query_all;
         actions.should_send = pacemaker_actions.should_send;
         if let Some(round) = pacemaker_actions.should_create_timeout {
-            self.record_store
+            let result = self
+                .record_store
                 .create_timeout(self.local_author, round, smr_context);
+            self.check_action(result);
             // Prevent voting at a round for which we have created a timeout already.
             self.latest_voted_round.max_update(round);
         }
         if let Some(previous_qc_hash) = pacemaker_actions.should_propose_block {
-            self.record_store.propose_block(
-                self.local_author,
-                previous_qc_hash,
-                clock,
-                smr_context,
-            );
+            self.propose_block(previous_qc_hash, clock, &mut actions, smr_context);
         }
         actions
     }
 }
 // -- END FILE --
 
+impl NodeState {
+    /// Propose a block extending the given QC, unless the proposal limiter defers it, in which
+    /// case the next update is scheduled when proposing is allowed again.
+    fn propose_block(
+        &mut self,
+        mut previous_qc_hash: QuorumCertificateHash,
+        clock: NodeTime,
+        actions: &mut NodeUpdateActions,
+        smr_context: &mut SMRContext,
+    ) {
+        let round = self.record_store.current_round();
+        if let Some(limiter) = &mut self.extensions.proposal_limiter {
+            match limiter.check(self.epoch_id, round, clock) {
+                Ok(()) => (),
+                Err(ProposalDenial::SameRound) => return,
+                Err(ProposalDenial::TooEarly(allowed_time)) => {
+                    actions.next_scheduled_update =
+                        min(actions.next_scheduled_update, allowed_time);
+                    return;
+                }
+            }
+        }
+        if self.settings.withholds_quorum_certificates {
+            let stale_qc_hash = self
+                .record_store
+                .oldest_quorum_certificate_hash(previous_qc_hash, self.locked_round);
+            self.metrics.withheld_quorum_certificates += self
+                .record_store
+                .uncommitted_depth(previous_qc_hash)
+                .saturating_sub(self.record_store.uncommitted_depth(stale_qc_hash));
+            previous_qc_hash = stale_qc_hash;
+        }
+        let command = match self.proposed_command(previous_qc_hash, smr_context) {
+            Some(command) => command,
+            None => return,
+        };
+        let result = self.record_store.propose_block(
+            self.local_author,
+            previous_qc_hash,
+            clock,
+            command,
+            smr_context,
+        );
+        self.check_action(result);
+        if let Some(limiter) = &mut self.extensions.proposal_limiter {
+            limiter.record(self.epoch_id, round, clock);
+        }
+    }
+
+    /// The command of our next proposal, `Some(None)` for an empty block, or `None` if we should
+    /// not propose yet.
+    fn proposed_command(
+        &mut self,
+        previous_qc_hash: QuorumCertificateHash,
+        smr_context: &mut SMRContext,
+    ) -> Option<Option<Command>> {
+        let replayed_command = if self.settings.proposes_double_spends {
+            self.metrics
+                .commit_latencies
+                .iter()
+                .rev()
+                .find_map(|(command, _)| command.clone())
+        } else {
+            None
+        };
+        if replayed_command.is_some() {
+            self.metrics.double_spends += 1;
+            return Some(replayed_command);
+        }
+        if self.is_under_back_pressure(previous_qc_hash) {
+            // Leave the commands in the mempool and propose an empty block.
+            return Some(None);
+        }
+        match self.fetch_command(smr_context) {
+            Some(command) => Some(Some(command)),
+            // Censors propose empty blocks rather than let the round time out.
+            None if !self.settings.censored_clients.is_empty() => Some(None),
+            None => None,
+        }
+    }
+}
+
 // -- BEGIN FILE consensus_node_impl --
 impl<Context: SMRContext> ConsensusNode<Context> for NodeState {
     fn update_node(&mut self, clock: NodeTime, smr_context: &mut Context) -> NodeUpdateActions {
+        self.update_clock(clock);
+        self.extensions.update_errors.clear();
+        // Nodes without voting rights in the current epoch, e.g. removed validators, neither
+        // propose, vote, nor time out: they only follow the chain until they are validators
+        // again.
+        if self.record_store.configuration().weight(&self.local_author) == 0 {
+            return self.update_follower(clock, smr_context);
+        }
         // Update pacemaker state and process pacemaker actions (e.g., creating a timeout, proposing
         // a block).
         let pacemaker_actions = self.pacemaker.update_pacemaker(
@@ -203,42 +1011,20 @@ impl<Context: SMRContext> ConsensusNode<Context> for NodeState {
         );
         let mut actions = self.process_pacemaker_actions(pacemaker_actions, clock, smr_context);
         // Vote on a valid proposal block designated by the pacemaker, if any.
-        if let Some((block_hash, block_round, proposer)) =
-            self.record_store.proposed_block(&self.pacemaker)
-        {
-            // Enforce voting constraints.
-            if block_round > self.latest_voted_round
-                && self.record_store.previous_round(block_hash) >= self.locked_round
-            {
-                // Update the latest voted round.
-                self.latest_voted_round = block_round;
-                // Update the locked round.
-                self.locked_round = max(
-                    self.locked_round,
-                    self.record_store.second_previous_round(block_hash),
-                );
-                // Try to execute the command contained the a block and create a vote.
-                if self
-                    .record_store
-                    .create_vote(self.local_author, block_hash, smr_context)
-                {
-                    // Ask to notify and send our vote to the author of the block.
-                    actions.should_send = vec![proposer];
-                }
-            }
+        if let Some(proposer) = self.vote_on_proposed_block(clock, smr_context) {
+            // Ask to notify and send our vote to the author of the block.
+            actions.should_send = vec![proposer];
         }
         // Check if our last proposal has reached a quorum of votes and create a QC.
-        if self
-            .record_store
-            .check_for_new_quorum_certificate(self.local_author, smr_context)
-        {
+        if self.create_quorum_certificate(clock, &mut actions, smr_context) {
             // Broadcast the QC to finish our work as a leader.
             actions.should_broadcast = true;
             // Schedule a new run now to process the new QC.
             actions.next_scheduled_update = clock;
         }
         // Check for new commits and verify if we should start a new epoch.
-        self.process_commits(smr_context);
+        self.process_commits(clock, smr_context);
+        self.observe_uncommitted_chain();
         // Update the commit tracker and ask that we query all nodes if needed.
         let tracker_actions = self.tracker.update_tracker(
             self.latest_query_all_time,
@@ -261,46 +1047,479 @@ impl<Context: SMRContext> ConsensusNode<Context> for NodeState {
 }
 // -- END FILE --
 
-// -- BEGIN FILE process_commits --
 impl NodeState {
-    pub fn process_commits(&mut self, smr_context: &mut SMRContext) {
-        // For all commits that have not been processed yet, according to the commit tracker..
-        for (round, state) in self
+    /// Vote for the block proposed by the leader chosen by the pacemaker, if the voting
+    /// constraints and the SMR layer allow it. Return the author of the block if we voted.
+    fn vote_on_proposed_block(
+        &mut self,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) -> Option<Author> {
+        let (block_hash, block_round, proposer) =
+            self.record_store.proposed_block(&self.pacemaker)?;
+        // Validators outside the committee of the round abstain, without moving the lock.
+        if block_round > self.latest_voted_round
+            && !self.record_store.is_voter(self.local_author, block_round)
+        {
+            self.metrics.committee_abstentions += 1;
+            self.latest_voted_round = block_round;
+        }
+        // Enforce voting constraints.
+        let commit_rule = self.record_store.commit_rule();
+        let previous_round = self.record_store.previous_round(block_hash);
+        let highest_timeout_certificate = (
+            self.record_store.highest_timeout_certificate_round(),
+            self.record_store.highest_timeout_certificate_qc_round(),
+        );
+        let is_safe_to_vote = block_round > self.latest_voted_round
+            && commit_rule.is_safe_to_vote(
+                block_round,
+                previous_round,
+                self.locked_round,
+                highest_timeout_certificate,
+            );
+        let may_vote = if self.settings.violates_locks {
+            let may_vote = self.record_store.current_vote(self.local_author).is_none();
+            if may_vote && !is_safe_to_vote {
+                self.metrics.lock_violations += 1;
+            }
+            may_vote
+        } else {
+            is_safe_to_vote
+        };
+        if !may_vote {
+            return None;
+        }
+        // Ask the SMR layer whether the command of the block is valid after its parent.
+        let is_valid_payload = self.settings.proposes_double_spends
+            || smr_context.validate_payload(
+                self.record_store.previous_state(block_hash),
+                self.record_store
+                    .block(block_hash)
+                    .and_then(|block| block.command.as_ref()),
+            );
+        // Update the latest voted round. After an invalid command, we abstain for the rest of
+        // the round, without moving the lock.
+        self.latest_voted_round = block_round;
+        if !is_valid_payload {
+            warn!(
+                "{:?} Rejected the command of the proposal of {:?} at {:?}",
+                self.local_author, proposer, block_round
+            );
+            self.metrics.rejected_payloads += 1;
+            return None;
+        }
+        // Update the locked round. The fast path requires locking on the previous QC.
+        let mut locked_round = commit_rule.locked_round(
+            previous_round,
+            self.record_store.second_previous_round(block_hash),
+        );
+        if self.record_store.fast_path().is_some() {
+            locked_round = max(locked_round, previous_round);
+        }
+        self.locked_round = max(self.locked_round, locked_round);
+        // Try to execute the command contained the a block and create a vote.
+        let result =
+            self.record_store
+                .create_vote(self.local_author, block_hash, clock, smr_context);
+        self.check_action(result)?;
+        if self.metrics.first_vote_time.is_none()
+            && self.record_store.configuration().weight(&self.local_author) > 0
+        {
+            self.metrics.first_vote_time = Some(clock);
+        }
+        Some(proposer)
+    }
+
+    /// Create a QC if our last proposal has reached a quorum of votes, unless we are still
+    /// waiting for a fast-path quorum, in which case the next update is scheduled at the
+    /// deadline. Return whether a QC was created.
+    fn create_quorum_certificate(
+        &mut self,
+        clock: NodeTime,
+        actions: &mut NodeUpdateActions,
+        smr_context: &mut SMRContext,
+    ) -> bool {
+        if let Some(deadline) = self.fast_quorum_deadline(clock) {
+            if clock < deadline {
+                actions.next_scheduled_update = min(actions.next_scheduled_update, deadline);
+                return false;
+            }
+        }
+        let result = self
+            .record_store
+            .check_for_new_quorum_certificate(self.local_author, smr_context);
+        if !self.check_action(result).unwrap_or(false) {
+            return false;
+        }
+        if let Some(block) = self
             .record_store
-            .committed_states_after(self.tracker.highest_committed_round)
+            .highest_quorum_certificate()
+            .and_then(|qc| self.record_store.block(qc.certified_block_hash))
         {
-            // .. deliver the committed state to the SMR layer, together with a commit certificate,
-            // if any.
-            if round == self.record_store.highest_committed_round() {
-                smr_context.commit(&state, self.record_store.highest_commit_certificate())
-            } else {
-                smr_context.commit(&state, None);
-            };
-            // .. check if the current epoch just ended. If it did..
+            self.metrics
+                .quorum_certificate_latencies
+                .push(clock.0 - block.time.0);
+        }
+        true
+    }
+
+    /// Track the longest chain of uncommitted blocks below the highest QC.
+    fn observe_uncommitted_chain(&mut self) {
+        let uncommitted_depth = self
+            .record_store
+            .uncommitted_depth(self.record_store.highest_quorum_certificate_hash());
+        self.metrics.deepest_uncommitted_chain =
+            max(self.metrics.deepest_uncommitted_chain, uncommitted_depth);
+    }
+}
+
+// -- BEGIN FILE process_commits --
+impl NodeState {
+    pub fn process_commits(&mut self, clock: NodeTime, smr_context: &mut SMRContext) {
+        let mut latest_commit = None;
+        let mut new_epoch = None;
+        // For all commits that have not been processed yet, according to the commit tracker..
+        for (round, block_hash, state) in self.pending_commits() {
+            // .. deliver the committed state to the SMR layer, together with its quorum time and a
+            // commit certificate, if any.
+            let time = self.deliver_commit(round, block_hash, &state, clock, smr_context);
+            latest_commit = Some((block_hash, state.clone()));
+            // .. check if the current epoch just ended. If it did, stop delivering commits.
             let new_epoch_id = smr_context.read_epoch_id(&state);
             if new_epoch_id > self.epoch_id {
-                // .. create a new record store and switch to the new epoch.
-                let new_record_store = RecordStoreState::new(
-                    new_epoch_id.initial_hash(),
-                    state.clone(),
-                    new_epoch_id,
-                    smr_context.configuration(&state),
-                );
-                let old_record_store = std::mem::replace(&mut self.record_store, new_record_store);
-                self.past_record_stores
-                    .insert(self.epoch_id, old_record_store);
-                self.epoch_id = new_epoch_id;
-                // .. initialize voting constraints.
-                self.latest_voted_round = Round(0);
-                self.locked_round = Round(0);
-                // .. stop delivering commits after an epoch change.
+                new_epoch = Some((new_epoch_id, state, time));
                 break;
             }
         }
+        // Roll back the blocks that conflict with the new commits, and let the SMR layer forget
+        // what the new commits made obsolete.
+        if let Some((block_hash, state)) = latest_commit {
+            self.rollback_conflicting_blocks(block_hash, smr_context);
+            smr_context.prune(&state);
+        }
+        // Switch to the new epoch, if the current one ended.
+        if let Some((new_epoch_id, state, time)) = new_epoch {
+            self.end_epoch(new_epoch_id, state, time, clock, smr_context);
+        }
     }
 }
 // -- END FILE --
 
+impl NodeState {
+    /// Commits that have not been processed yet, according to the commit tracker.
+    fn pending_commits(&self) -> Vec<(Round, BlockHash, State)> {
+        self.record_store
+            .committed_blocks(self.tracker.highest_committed_round)
+            .map(|commit| (commit.round, commit.block_hash, commit.state.clone()))
+            .collect()
+    }
+
+    /// Deliver a committed state to the SMR layer and to the subscribers of the node, and
+    /// record it. Return the quorum time of its block.
+    fn deliver_commit(
+        &mut self,
+        round: Round,
+        block_hash: BlockHash,
+        state: &State,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) -> NodeTime {
+        // The QC ending a fast-path chain does not certify the state that it commits.
+        let fast_path = self.record_store.is_fast_commit(round);
+        let time = self.record_store.quorum_time(block_hash);
+        let certificate = if round == self.record_store.highest_committed_round() && !fast_path {
+            self.record_store.highest_commit_certificate()
+        } else {
+            None
+        };
+        smr_context.commit(state, time, certificate);
+        if !self.extensions.commit_subscribers.is_empty() {
+            self.extensions
+                .commit_subscribers
+                .notify(&CommitNotification {
+                    epoch_id: self.epoch_id,
+                    round,
+                    block_hash,
+                    state: state.clone(),
+                    time,
+                    certificate: certificate.cloned(),
+                });
+        }
+        // Leaders only aggregate the votes announcing the epoch change that they execute, so
+        // a certificate committing this block announces the next epoch if and only if the block
+        // ends the epoch.
+        let new_epoch_id = smr_context.read_epoch_id(state);
+        let expected = if new_epoch_id > self.epoch_id {
+            Some(EpochChange::new(
+                new_epoch_id,
+                &smr_context.configuration(state),
+            ))
+        } else {
+            None
+        };
+        debug_assert!(
+            certificate.is_none_or(|certificate| {
+                certificate.committed_state.as_ref() != Some(state)
+                    || certificate.next_epoch == expected
+            }),
+            "{:?} The commit certificate of {:?} does not announce {:?}",
+            self.local_author,
+            round,
+            expected
+        );
+        if fast_path {
+            info!(
+                "{:?} Committed {:?} on the fast path",
+                self.local_author, round
+            );
+            self.metrics.fast_path_commits += 1;
+        }
+        self.extensions.commit_log.push(Commit {
+            epoch: self.epoch_id.0,
+            round,
+            block: block_hash,
+            state: state.clone(),
+        });
+        if let Some(checkpoint_log) = &mut self.extensions.checkpoint_log {
+            checkpoint_log.add_commit(
+                self.epoch_id,
+                round,
+                state,
+                self.record_store.public_key(self.local_author),
+                self.record_store.chain_id(),
+                self.record_store.hash_function(),
+            );
+        }
+        let block = self
+            .record_store
+            .block(block_hash)
+            .expect("Committed blocks should be known");
+        self.metrics
+            .commit_latencies
+            .push((block.command.clone(), clock.0 - block.time.0));
+        time
+    }
+
+    /// Keep a snapshot of the last state of the current epoch for joining peers, if we serve
+    /// snapshots, and switch to the new epoch.
+    fn end_epoch(
+        &mut self,
+        new_epoch_id: EpochId,
+        state: State,
+        time: NodeTime,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) {
+        if self.settings.snapshot_sync {
+            let configuration = smr_context.configuration(&state);
+            let certificates = self
+                .record_store
+                .certificates_announcing(EpochChange::new(new_epoch_id, &configuration));
+            // Joining peers need the certificate of the state itself, while any certificate
+            // announcing the new epoch proves its configuration.
+            let certificate = certificates
+                .iter()
+                .find(|qc| qc.committed_state.as_ref() == Some(&state))
+                .map(|qc| (*qc).clone());
+            let proof = certificates
+                .first()
+                .map(|qc| EpochProof::new((*qc).clone(), configuration));
+            self.extensions.epoch_snapshot = certificate.and_then(|qc| {
+                let data = smr_context.export_snapshot(&state)?;
+                let proofs = self.extensions.epoch_proofs.clone();
+                Some(EpochSnapshot::new(qc, time, data, proofs))
+            });
+            self.extensions.epoch_proofs.extend(proof);
+        }
+        self.start_epoch(new_epoch_id, state, time, clock, smr_context);
+    }
+
+    /// Switch to a new epoch starting at the given committed state.
+    fn start_epoch(
+        &mut self,
+        new_epoch_id: EpochId,
+        state: State,
+        time: NodeTime,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) {
+        self.extensions.rolled_back_blocks.clear();
+        // Create a new record store and switch to the new epoch.
+        let mut new_record_store = RecordStoreState::new(
+            new_epoch_id.initial_hash(),
+            state.clone(),
+            new_epoch_id,
+            smr_context.configuration(&state),
+        );
+        new_record_store.set_commit_rule(self.record_store.shared_commit_rule());
+        if let Some(fast_path) = self.record_store.fast_path() {
+            new_record_store.set_fast_path(fast_path);
+        }
+        if let Some(committee) = self.record_store.committee() {
+            new_record_store.set_committee(committee);
+        }
+        if let Some(max_clock_drift) = self.record_store.max_clock_drift() {
+            new_record_store.set_max_clock_drift(max_clock_drift);
+        }
+        if let Some(qc_cache) = self.record_store.take_quorum_certificate_cache() {
+            new_record_store.set_quorum_certificate_cache(qc_cache);
+        }
+        new_record_store.set_hash_function(self.record_store.hash_function());
+        new_record_store.set_chain_id(self.record_store.chain_id());
+        #[cfg(feature = "disk_store")]
+        {
+            if let Some(storage) = self.record_store.disk_storage() {
+                // Without its database, the new epoch keeps its records in memory.
+                if let Err(error) = new_record_store.set_disk_storage(storage.clone()) {
+                    error!(
+                        "Failed to open the database of {:?}: {}",
+                        new_epoch_id, error
+                    );
+                }
+            }
+        }
+        if let Some(local_clock) = self.record_store.local_clock() {
+            new_record_store.update_clock(local_clock);
+        }
+        new_record_store.set_initial_time(time);
+        let old_record_store = std::mem::replace(&mut self.record_store, new_record_store);
+        self.past_record_stores
+            .insert(self.epoch_id, old_record_store);
+        self.extensions.grace_period = self
+            .settings
+            .epoch_grace_period
+            .map(|grace_period| (self.epoch_id, clock + grace_period));
+        self.epoch_id = new_epoch_id;
+        if let Some(kept) = self.settings.past_epochs_kept {
+            self.past_record_stores
+                .retain(|epoch_id, _| epoch_id.0 + kept >= new_epoch_id.0);
+        }
+        if let Some(orphans) = &mut self.extensions.orphans {
+            orphans.clear();
+        }
+        // Initialize voting constraints.
+        self.latest_voted_round = Round(0);
+        self.locked_round = Round(0);
+    }
+
+    /// Skip to the epoch started by a peer's snapshot instead of replaying the commits of the
+    /// epochs in between. The snapshot must be committed by a commit certificate announcing
+    /// the epoch and its configuration. Like a checkpoint, its signatures are only checked if
+    /// we went through the epoch of the certificate.
+    pub(crate) fn install_snapshot(
+        &mut self,
+        snapshot: &EpochSnapshot,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) -> bool {
+        let certificate = &snapshot.certificate;
+        let (new_epoch, state) = match (certificate.next_epoch, &certificate.committed_state) {
+            (Some(change), Some(state)) if change.epoch_id > self.epoch_id => {
+                (change, state.clone())
+            }
+            _ => return false,
+        };
+        let new_epoch_id = new_epoch.epoch_id;
+        // We may not know the epoch of the certificate: only trust the configuration of our
+        // current epoch, and the epoch changes that its certificates prove.
+        if let Err(error) = snapshot.verify(self.light_client()) {
+            warn!(
+                "{:?} Rejecting the snapshot of {:?}: {}",
+                self.local_author, new_epoch_id, error
+            );
+            return false;
+        }
+        // The SMR layer refuses states that do not start the announced epoch, so that the new
+        // epoch cannot contradict its certificate.
+        if !smr_context.import_snapshot(&state, &new_epoch, &snapshot.data) {
+            return false;
+        }
+        let configuration = smr_context.configuration(&state);
+        debug_assert!(
+            smr_context.read_epoch_id(&state) == new_epoch_id
+                && new_epoch.certifies(&configuration)
+        );
+        self.extensions.epoch_proofs = snapshot.proofs.clone();
+        self.extensions
+            .epoch_proofs
+            .push(EpochProof::new(certificate.clone(), configuration));
+        info!(
+            "{:?} Installed a snapshot of {:?}",
+            self.local_author, new_epoch_id
+        );
+        self.metrics.snapshot_time = Some(clock);
+        self.extensions.epoch_snapshot = Some(snapshot.clone());
+        self.start_epoch(new_epoch_id, state, snapshot.time, clock, smr_context);
+        true
+    }
+
+    /// A light client trusting the configuration of our current epoch.
+    fn light_client(&self) -> LightClient {
+        let mut light_client =
+            LightClient::new(self.epoch_id, self.record_store.configuration().clone());
+        light_client.set_hash_function(self.hash_function());
+        light_client.set_chain_id(self.chain_id());
+        if let Some(committee) = self.record_store.committee() {
+            light_client.set_committee(committee);
+        }
+        light_client
+    }
+
+    fn rollback_conflicting_blocks(&mut self, block_hash: BlockHash, smr_context: &mut SMRContext) {
+        let rolled_back_blocks = &mut self.extensions.rolled_back_blocks;
+        let branch: Vec<_> = self
+            .record_store
+            .blocks_conflicting_with(block_hash)
+            .into_iter()
+            .filter(|hash| rolled_back_blocks.insert(*hash))
+            .collect();
+        if !branch.is_empty() {
+            smr_context.rollback(&branch);
+        }
+    }
+}
+
+impl NodeState {
+    /// Deliver new commits and query all nodes when no commit happened for too long, without
+    /// taking part in the protocol otherwise. This is the main handler of followers.
+    pub fn update_follower(
+        &mut self,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+    ) -> NodeUpdateActions {
+        self.process_commits(clock, smr_context);
+        let tracker_actions = self.tracker.update_tracker(
+            self.latest_query_all_time,
+            clock,
+            self.epoch_id,
+            &self.record_store,
+        );
+        let mut actions = NodeUpdateActions::new();
+        actions.should_query_all = tracker_actions.should_query_all;
+        actions.next_scheduled_update = tracker_actions.next_scheduled_update;
+        if actions.should_query_all {
+            self.latest_query_all_time = clock;
+        }
+        actions
+    }
+
+    /// Query all nodes periodically without delivering commits nor tracking them, e.g. while
+    /// followers cannot verify the commit certificates of the node.
+    pub fn update_follower_without_commits(&mut self, clock: NodeTime) -> NodeUpdateActions {
+        let mut actions = NodeUpdateActions::new();
+        let deadline = self.latest_query_all_time + self.tracker.target_commit_interval;
+        if clock >= deadline {
+            actions.should_query_all = true;
+            actions.next_scheduled_update = clock + self.tracker.target_commit_interval;
+            self.latest_query_all_time = clock;
+        } else {
+            actions.next_scheduled_update = deadline;
+        }
+        actions
+    }
+}
+
 // -- BEGIN FILE commit_tracker_impl --
 #[derive(Debug)]
 pub struct CommitTrackerUpdateActions {
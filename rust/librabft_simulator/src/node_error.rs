@@ -0,0 +1,116 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Errors of the local actions of a node, i.e. proposing a block, voting, timing out, and
+//! creating a QC, so that hosts can react to them instead of reading logs.
+
+use super::*;
+use record::Record;
+use record_store::RejectReason;
+use std::fmt;
+
+#[cfg(test)]
+#[path = "unit_tests/node_error_tests.rs"]
+mod node_error_tests;
+
+/// Outcome of a local action of a node.
+pub type NodeResult<T> = std::result::Result<T, NodeError>;
+
+/// Why a local action of a node failed.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum NodeError {
+    /// The record store rejected a record created by the node, e.g. a second timeout at the
+    /// same round.
+    Validation(RejectReason),
+    /// The signature of a record created by the node did not verify against the key of its
+    /// author in the epoch.
+    Crypto { author: Author },
+    /// A record needed by the action is missing from the record store, or could not be saved.
+    Storage(StorageError),
+    /// A record created by the node belongs to an epoch that the record store does not accept
+    /// any more, or not yet.
+    EpochMismatch { expected: EpochId, found: EpochId },
+    /// The command of the block to vote for could not be executed.
+    Execution { block_hash: BlockHash },
+}
+
+/// Records missing from a record store, or failures of its storage.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum StorageError {
+    UnknownBlock(BlockHash),
+    UnknownQuorumCertificate(QuorumCertificateHash),
+    /// The record store failed to save a record, e.g. on disk.
+    WriteFailed,
+}
+
+impl NodeError {
+    /// The error reported when the record store of the given epoch rejects a local record.
+    pub fn rejected(reason: RejectReason, expected: EpochId, record: &Record) -> Self {
+        match reason {
+            RejectReason::BadSignature => NodeError::Crypto {
+                author: record.signer().0,
+            },
+            RejectReason::FutureEpoch | RejectReason::PastEpoch => NodeError::EpochMismatch {
+                expected,
+                found: record.epoch_id().unwrap_or(expected),
+            },
+            RejectReason::UnknownParent => match record {
+                Record::Block(block) => NodeError::Storage(StorageError::UnknownQuorumCertificate(
+                    block.previous_quorum_certificate_hash,
+                )),
+                Record::Vote(vote) => {
+                    NodeError::Storage(StorageError::UnknownBlock(vote.certified_block_hash))
+                }
+                Record::QuorumCertificate(qc) => {
+                    NodeError::Storage(StorageError::UnknownBlock(qc.certified_block_hash))
+                }
+                Record::Timeout(_) => NodeError::Validation(reason),
+            },
+            RejectReason::StorageFailure => NodeError::Storage(StorageError::WriteFailed),
+            _ => NodeError::Validation(reason),
+        }
+    }
+
+    /// Name of the kind of error in metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeError::Validation(_) => "validation",
+            NodeError::Crypto { .. } => "crypto",
+            NodeError::Storage(_) => "storage",
+            NodeError::EpochMismatch { .. } => "epoch_mismatch",
+            NodeError::Execution { .. } => "execution",
+        }
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::Validation(reason) => {
+                write!(f, "The record store rejected the record: {:?}.", reason)
+            }
+            NodeError::Crypto { author } => {
+                write!(f, "Invalid signature from {:?} in this epoch.", author)
+            }
+            NodeError::Storage(StorageError::UnknownBlock(block_hash)) => {
+                write!(f, "Unknown block {:?}.", block_hash)
+            }
+            NodeError::Storage(StorageError::UnknownQuorumCertificate(qc_hash)) => {
+                write!(f, "Unknown QC {:?}.", qc_hash)
+            }
+            NodeError::Storage(StorageError::WriteFailed) => {
+                write!(f, "The record store failed to save the record.")
+            }
+            NodeError::EpochMismatch { expected, found } => write!(
+                f,
+                "The record belongs to {:?} but the record store to {:?}.",
+                found, expected
+            ),
+            NodeError::Execution { block_hash } => {
+                write!(f, "The block {:?} could not be executed.", block_hash)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
@@ -0,0 +1,117 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Shared access to a node from the threads of a host, e.g. to answer status queries while
+//! another thread drives the node.
+
+use super::*;
+use node::NodeState;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(test)]
+#[path = "unit_tests/node_handle_tests.rs"]
+mod node_handle_tests;
+
+/// A `Send + Sync` handle on a node, cloned to share it between threads.
+///
+/// Updates change the record store and the pacemaker together, e.g. a new QC moves to the
+/// next round, so both stay under one lock and readers see them as left by the last complete
+/// call. The lock is a read-write lock instead: the `&self` parts of the `DataSyncNode`
+/// interface only take a read lock, like queries through `read`, so serving data-sync
+/// requests and inspecting the node do not wait for each other. Only updates take the write
+/// lock.
+#[derive(Debug)]
+pub struct NodeHandle<Node = NodeState> {
+    node: Arc<RwLock<Node>>,
+}
+
+impl<Node> Clone for NodeHandle<Node> {
+    fn clone(&self) -> Self {
+        NodeHandle {
+            node: self.node.clone(),
+        }
+    }
+}
+
+impl<Node> NodeHandle<Node> {
+    pub fn new(node: Node) -> Self {
+        NodeHandle {
+            node: Arc::new(RwLock::new(node)),
+        }
+    }
+
+    /// Shared access to the node, e.g. `handle.read().record_store()`.
+    pub fn read(&self) -> RwLockReadGuard<'_, Node> {
+        self.node
+            .read()
+            .expect("A thread panicked while updating the node")
+    }
+
+    /// Exclusive access to the node, e.g. to change its settings.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Node> {
+        self.node
+            .write()
+            .expect("A thread panicked while updating the node")
+    }
+}
+
+impl<Node, Context> ConsensusNode<Context> for NodeHandle<Node>
+where
+    Node: ConsensusNode<Context>,
+{
+    fn update_node(&mut self, clock: NodeTime, context: &mut Context) -> NodeUpdateActions {
+        self.write().update_node(clock, context)
+    }
+}
+
+impl<Node, Context> DataSyncNode<Context> for NodeHandle<Node>
+where
+    Node: DataSyncNode<Context>,
+{
+    type Notification = Node::Notification;
+    type Request = Node::Request;
+    type Response = Node::Response;
+
+    fn create_notification(&self) -> Self::Notification {
+        self.read().create_notification()
+    }
+
+    fn create_request(&self) -> Self::Request {
+        self.read().create_request()
+    }
+
+    fn handle_request(&self, request: Self::Request) -> Self::Response {
+        self.read().handle_request(request)
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: Self::Notification,
+        context: &mut Context,
+    ) -> Option<Self::Request> {
+        self.write().handle_notification(notification, context)
+    }
+
+    fn handle_response(
+        &mut self,
+        response: Self::Response,
+        context: &mut Context,
+        clock: NodeTime,
+    ) {
+        self.write().handle_response(response, context, clock)
+    }
+
+    fn filter_notification(
+        &mut self,
+        notification: &Self::Notification,
+        receiver: Author,
+    ) -> Option<Self::Notification> {
+        self.write().filter_notification(notification, receiver)
+    }
+}
+
+impl<Node: ActiveRound> ActiveRound for NodeHandle<Node> {
+    fn active_round(&self) -> Round {
+        self.read().active_round()
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded buffers of the records received before their parent, e.g. a block before the QC
+//! that it extends, so that they can be inserted as soon as the parent arrives instead of being
+//! fetched again with a query-all.
+
+use super::*;
+use hashing::Fnv1a;
+use record::*;
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "unit_tests/orphan_buffer_tests.rs"]
+mod orphan_buffer_tests;
+
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct OrphanStats {
+    /// Records buffered because their parent was unknown.
+    pub buffered: usize,
+    /// Buffered records inserted after their parent arrived.
+    pub adopted: usize,
+    /// Buffered records dropped because the buffer was full, their round was too old, or the
+    /// epoch ended.
+    pub evicted: usize,
+}
+
+impl OrphanStats {
+    pub fn add(&mut self, other: &OrphanStats) {
+        self.buffered += other.buffered;
+        self.adopted += other.adopted;
+        self.evicted += other.evicted;
+    }
+}
+
+/// Hash of the record that must be inserted before the given one: the QC extended by a block,
+/// or the block certified by a vote or a QC. Timeouts have no parent.
+pub fn parent_hash(record: &Record) -> Option<u64> {
+    match record {
+        Record::Block(block) => Some(block.previous_quorum_certificate_hash.0),
+        Record::Vote(vote) => Some(vote.certified_block_hash.0),
+        Record::QuorumCertificate(qc) => Some(qc.certified_block_hash.0),
+        Record::Timeout(_) => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Orphan {
+    parent: u64,
+    digest: u64,
+    sequence_number: u64,
+    record: Record,
+}
+
+/// Records of the current epoch waiting for their parent, at most `capacity` of them. Records
+/// more than `max_round_age` rounds behind the current round are evicted, then, on overflow,
+/// the record of the lowest round.
+#[derive(Clone, Debug)]
+pub struct OrphanBuffer {
+    capacity: usize,
+    max_round_age: usize,
+    orphans: Vec<Orphan>,
+    num_pushed: u64,
+    stats: OrphanStats,
+}
+
+impl OrphanBuffer {
+    pub fn new(capacity: usize, max_round_age: usize) -> Self {
+        assert!(capacity > 0, "Orphan buffers must hold at least one record");
+        OrphanBuffer {
+            capacity,
+            max_round_age,
+            orphans: Vec::new(),
+            num_pushed: 0,
+            stats: OrphanStats::default(),
+        }
+    }
+
+    /// Drop the records that are too old for the given current round.
+    pub fn evict_stale(&mut self, current_round: Round) {
+        let max_round_age = self.max_round_age;
+        let length = self.orphans.len();
+        self.orphans
+            .retain(|orphan| orphan.record.round().0 + max_round_age >= current_round.0);
+        self.stats.evicted += length - self.orphans.len();
+    }
+
+    /// Buffer a record whose parent is unknown, unless it is buffered already or too old.
+    pub fn push(&mut self, record: Record, current_round: Round) {
+        let parent = match parent_hash(&record) {
+            Some(parent) => parent,
+            None => return,
+        };
+        // Digests only detect copies within the buffer, so any hash function will do.
+        let digest = record.digest(&Fnv1a);
+        if self.orphans.iter().any(|orphan| orphan.digest == digest) {
+            return;
+        }
+        self.orphans.push(Orphan {
+            parent,
+            digest,
+            sequence_number: self.num_pushed,
+            record,
+        });
+        self.num_pushed += 1;
+        self.stats.buffered += 1;
+        self.evict_stale(current_round);
+        if self.orphans.len() > self.capacity {
+            let (_, index) = self
+                .orphans
+                .iter()
+                .enumerate()
+                .map(|(index, orphan)| ((orphan.record.round(), orphan.sequence_number), index))
+                .min()
+                .unwrap();
+            self.orphans.remove(index);
+            self.stats.evicted += 1;
+        }
+    }
+
+    /// Remove the records waiting for the given parent, in arrival order.
+    pub fn take_children(&mut self, parent: u64) -> Vec<Record> {
+        let (children, orphans) = std::mem::take(&mut self.orphans)
+            .into_iter()
+            .partition(|orphan| orphan.parent == parent);
+        self.orphans = orphans;
+        children
+            .into_iter()
+            .map(|orphan: Orphan| orphan.record)
+            .collect()
+    }
+
+    /// Account for a buffered record that was inserted.
+    pub fn record_adoption(&mut self) {
+        self.stats.adopted += 1;
+    }
+
+    /// Drop all the records, e.g. at the end of an epoch.
+    pub fn clear(&mut self) {
+        self.stats.evicted += self.orphans.len();
+        self.orphans.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+
+    pub fn stats(&self) -> &OrphanStats {
+        &self.stats
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use scenario::{quorum_impossible_intervals, CrashFault, LinkFault, Scenario};
+
+#[cfg(test)]
+#[path = "unit_tests/outages_tests.rs"]
+mod outages_tests;
+
+/// Crash every node of `region` during `[start, end)`.
+pub fn region_down(base: &Scenario, region: &str, start: i64, end: i64) -> Scenario {
+    let mut scenario = base.clone();
+    for node in base.topology.nodes(region) {
+        scenario.faults.push(CrashFault {
+            node: *node,
+            start,
+            end: Some(end),
+        });
+    }
+    scenario
+}
+
+/// Add `extra_delay` to every message crossing a region boundary during `[start, end)`.
+pub fn backbone_degraded(base: &Scenario, extra_delay: Duration, start: i64, end: i64) -> Scenario {
+    let mut scenario = base.clone();
+    let regions: Vec<_> = base.topology.regions.keys().collect();
+    for (i, from) in regions.iter().enumerate() {
+        for to in &regions[i + 1..] {
+            scenario.link_faults.push(LinkFault {
+                from: from.to_string(),
+                to: to.to_string(),
+                start,
+                end,
+                extra_delay,
+            });
+        }
+    }
+    scenario
+}
+
+/// Take the regions down one after the other, each for `window`, starting at `start`.
+pub fn rolling_maintenance(base: &Scenario, start: i64, window: i64) -> Scenario {
+    let mut scenario = base.clone();
+    for (index, region) in base.topology.regions.keys().enumerate() {
+        let region_start = start + index as i64 * window;
+        scenario = region_down(&scenario, region, region_start, region_start + window);
+    }
+    scenario
+}
+
+/// The canned failure scenarios derived from `base`: each region down in turn, a degraded
+/// backbone, and a rolling maintenance. Outages cover the middle third of the run so that the
+/// recovery is observed as well.
+pub fn outage_library(base: &Scenario) -> Vec<(String, Scenario)> {
+    let start = base.max_clock / 3;
+    let end = 2 * base.max_clock / 3;
+    let mut library = Vec::new();
+    for region in base.topology.regions.keys() {
+        library.push((
+            format!("region_down:{}", region),
+            region_down(base, region, start, end),
+        ));
+    }
+    let extra_delay = 10 * base.network.mean as Duration;
+    library.push((
+        "backbone_degraded".to_string(),
+        backbone_degraded(base, extra_delay, start, end),
+    ));
+    let num_regions = std::cmp::max(base.topology.regions.len(), 1) as i64;
+    library.push((
+        "rolling_maintenance".to_string(),
+        rolling_maintenance(base, start, (end - start) / num_regions),
+    ));
+    library
+}
+
+/// Number of commands committed by the nodes at the end of a canned scenario.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ResilienceReport {
+    pub name: String,
+    pub min_commits: usize,
+    pub max_commits: usize,
+    /// Intervals during which too many nodes were down to commit anything.
+    pub quorum_impossible: Vec<(i64, i64)>,
+}
+
+/// Run the outage library over the validator distribution of `base`, and the baseline itself.
+pub fn evaluate_resilience(base: &Scenario) -> Vec<ResilienceReport> {
+    std::iter::once(("baseline".to_string(), base.clone()))
+        .chain(outage_library(base))
+        .map(|(name, scenario)| {
+            let mut sim = scenario.build_simulator();
+            let quorum_impossible = quorum_impossible_intervals(&sim, scenario.max_clock);
+            let commits: Vec<_> = sim
+                .loop_until(GlobalTime(scenario.max_clock), None)
+                .iter()
+                .map(|context| context.committed_history().len())
+                .collect();
+            ResilienceReport {
+                name,
+                min_commits: commits.iter().cloned().min().unwrap_or(0),
+                max_commits: commits.iter().cloned().max().unwrap_or(0),
+                quorum_impossible,
+            }
+        })
+        .collect()
+}
@@ -3,12 +3,14 @@
 
 use std::{
     cmp::{max, min},
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
     hash::{Hash, Hasher},
 };
 
 use super::*;
+use introspection::PacemakerSnapshot;
 use record_store::*;
+use serde::Serialize;
 
 #[cfg(test)]
 #[path = "unit_tests/pacemaker_tests.rs"]
@@ -57,7 +59,7 @@ pub trait Pacemaker: Debug {
 // -- END FILE --
 
 // -- BEGIN FILE pacemaker_state --
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PacemakerState {
     /// Active epoch.
     active_epoch: EpochId,
@@ -75,9 +77,142 @@ pub struct PacemakerState {
     gamma: f64,
     /// Coefficient to control the frequency of query-all actions.
     lambda: f64,
+    /// Whether leaders propose as soon as they enter a round, that is, as soon as they see the
+    /// QC or TC of the previous round, instead of waiting for `delta` first.
+    optimistic_responsiveness: bool,
+    /// Estimation of `delta` from the observed round durations, if enabled.
+    adaptive_delta: Option<AdaptiveDelta>,
+    /// Outcomes of the recent rounds for their leaders, if leader reputation is enabled.
+    leader_reputation: Option<LeaderReputation>,
 }
 // -- END FILE --
 
+/// Learn `delta` from the durations of the rounds that end with a QC: `delta` follows an
+/// exponentially weighted moving average of these durations, multiplied by a safety margin.
+/// Rounds ending with a TC are not observed since their duration reflects the timeout itself.
+#[derive(Clone, Debug)]
+pub struct AdaptiveDelta {
+    /// Weight of each new observation in the average, in `(0, 1]`.
+    smoothing: f64,
+    /// Factor applied to the average to obtain `delta`, at least 1.
+    margin: f64,
+    /// Average duration of the rounds observed so far, if any.
+    average: Option<f64>,
+    /// Successive values of `delta` with the local time at which they were learned.
+    history: Vec<(NodeTime, Duration)>,
+}
+
+impl AdaptiveDelta {
+    pub fn new(smoothing: f64, margin: f64) -> Self {
+        assert!(
+            0.0 < smoothing && smoothing <= 1.0,
+            "Invalid smoothing factor"
+        );
+        assert!(margin >= 1.0, "Invalid safety margin");
+        AdaptiveDelta {
+            smoothing,
+            margin,
+            average: None,
+            history: Vec::new(),
+        }
+    }
+
+    /// Account for a round of the given duration and return the new estimate of `delta`.
+    fn observe(&mut self, clock: NodeTime, round_duration: Duration) -> Duration {
+        let sample = round_duration as f64;
+        let average = match self.average {
+            Some(average) => average + self.smoothing * (sample - average),
+            None => sample,
+        };
+        self.average = Some(average);
+        let delta = max(1, (self.margin * average).ceil() as Duration);
+        self.history.push((clock, delta));
+        delta
+    }
+
+    pub fn history(&self) -> &[(NodeTime, Duration)] {
+        &self.history
+    }
+}
+
+/// What became of the round of a leader.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize)]
+pub enum LeaderOutcome {
+    /// The block proposed by the leader was committed.
+    Committed,
+    /// The round ended with a TC.
+    TimedOut,
+}
+
+/// Leader reputation in the style of DiemBFT: leaders whose round ended with a TC are not
+/// elected again during the next `exclusion_window` rounds, unless one of their blocks is
+/// committed in the meantime. Outcomes are observed locally and reset at each epoch.
+#[derive(Clone, Debug)]
+pub struct LeaderReputation {
+    /// Number of rounds after a timeout during which its leader is skipped.
+    exclusion_window: usize,
+    /// Leader and outcome of the recent rounds of the current epoch.
+    outcomes: BTreeMap<Round, (Author, LeaderOutcome)>,
+    /// Round of the latest commit accounted for.
+    highest_committed_round: Round,
+}
+
+impl LeaderReputation {
+    pub fn new(exclusion_window: usize) -> Self {
+        assert!(exclusion_window > 0, "Invalid exclusion window");
+        LeaderReputation {
+            exclusion_window,
+            outcomes: BTreeMap::new(),
+            highest_committed_round: Round(0),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.outcomes.clear();
+        self.highest_committed_round = Round(0);
+    }
+
+    /// Credit the authors of the blocks committed since the last call.
+    fn observe_commits(&mut self, record_store: &RecordStore) {
+        for commit in record_store.committed_blocks(self.highest_committed_round) {
+            let author = record_store.block(commit.block_hash).unwrap().author;
+            self.outcomes
+                .insert(commit.round, (author, LeaderOutcome::Committed));
+        }
+        self.highest_committed_round = record_store.highest_committed_round();
+    }
+
+    fn observe_timeout(&mut self, round: Round, leader: Author) {
+        self.outcomes
+            .entry(round)
+            .or_insert((leader, LeaderOutcome::TimedOut));
+    }
+
+    /// Forget the outcomes that are too old to matter for the given round.
+    fn prune(&mut self, round: Round) {
+        let oldest = Round(round.0.saturating_sub(self.exclusion_window));
+        self.outcomes = self.outcomes.split_off(&oldest);
+    }
+
+    /// Leaders whose latest outcome in the window before the given round is a timeout.
+    fn excluded_leaders(&self, round: Round) -> BTreeSet<Author> {
+        let oldest = Round(round.0.saturating_sub(self.exclusion_window));
+        let mut latest_outcomes = BTreeMap::new();
+        for (author, outcome) in self.outcomes.range(oldest..round).map(|x| x.1) {
+            latest_outcomes.insert(*author, *outcome);
+        }
+        latest_outcomes
+            .into_iter()
+            .filter(|(_, outcome)| *outcome == LeaderOutcome::TimedOut)
+            .map(|(author, _)| author)
+            .collect()
+    }
+
+    pub fn outcomes(&self) -> &BTreeMap<Round, (Author, LeaderOutcome)> {
+        &self.outcomes
+    }
+}
+
 impl PacemakerState {
     pub fn new(
         epoch_id: EpochId,
@@ -95,23 +230,98 @@ impl PacemakerState {
             delta,
             gamma,
             lambda,
+            optimistic_responsiveness: true,
+            adaptive_delta: None,
+            leader_reputation: None,
+        }
+    }
+
+    /// Skip the leaders of the rounds that recently timed out in the leader election.
+    pub fn set_leader_reputation(&mut self, leader_reputation: LeaderReputation) {
+        self.leader_reputation = Some(leader_reputation);
+    }
+
+    pub fn leader_reputation(&self) -> Option<&LeaderReputation> {
+        self.leader_reputation.as_ref()
+    }
+
+    /// Replace the fixed `delta` by an estimate learned from the durations of the rounds. The
+    /// initial value of `delta` is used until the first round ends with a QC.
+    pub fn set_adaptive_delta(&mut self, adaptive_delta: AdaptiveDelta) {
+        self.adaptive_delta = Some(adaptive_delta);
+    }
+
+    pub fn adaptive_delta(&self) -> Option<&AdaptiveDelta> {
+        self.adaptive_delta.as_ref()
+    }
+
+    /// Local time at which the active round started.
+    pub fn active_round_start_time(&self) -> NodeTime {
+        self.active_round_start_time
+    }
+
+    /// Read-only copy of the status of the pacemaker, for monitoring.
+    pub fn snapshot(&self) -> PacemakerSnapshot {
+        PacemakerSnapshot {
+            active_epoch: self.active_epoch,
+            active_round: self.active_round,
+            active_leader: self.active_leader,
+            active_round_start_time: self.active_round_start_time,
+            active_round_duration: self.active_round_duration,
+            delta: self.delta,
+            gamma: self.gamma,
+            lambda: self.lambda,
+            optimistic_responsiveness: self.optimistic_responsiveness,
+            adaptive_delta: self.adaptive_delta.is_some(),
+            excluded_leaders: self.leader_reputation.as_ref().map(|reputation| {
+                reputation
+                    .excluded_leaders(self.active_round)
+                    .into_iter()
+                    .collect()
+            }),
+        }
+    }
+
+    /// Disable optimistic responsiveness to compare with protocols where leaders wait for a
+    /// known bound on network delays before proposing. Round durations are extended by the
+    /// same amount.
+    pub fn set_optimistic_responsiveness(&mut self, optimistic_responsiveness: bool) {
+        self.optimistic_responsiveness = optimistic_responsiveness;
+    }
+
+    /// Time to wait after entering a round before proposing a block.
+    fn proposal_delay(&self) -> Duration {
+        if self.optimistic_responsiveness {
+            0
+        } else {
+            self.delta
         }
     }
 
     pub fn leader(record_store: &RecordStore, round: Round) -> Author {
         let mut hasher = DefaultHasher::new();
         round.hash(&mut hasher);
-        let author = record_store.pick_author(hasher.finish());
-        author
+        record_store.pick_author(hasher.finish())
+    }
+
+    /// Leader of the given round, taking reputation into account if enabled. Without recent
+    /// timeouts, this is the same as `leader`.
+    fn elect_leader(&self, record_store: &RecordStore, round: Round) -> Author {
+        match &self.leader_reputation {
+            Some(reputation) => {
+                let mut hasher = DefaultHasher::new();
+                round.hash(&mut hasher);
+                record_store
+                    .pick_author_excluding(hasher.finish(), &reputation.excluded_leaders(round))
+            }
+            None => Self::leader(record_store, round),
+        }
     }
 
     fn duration(&self, record_store: &RecordStore, round: Round) -> Duration {
-        let highest_commit_certificate_round = if record_store.highest_committed_round() > Round(0)
-        {
-            record_store.highest_committed_round() + 2
-        } else {
-            Round(0)
-        };
+        let highest_commit_certificate_round = record_store
+            .highest_commit_certificate()
+            .map_or(Round(0), |qc| qc.round);
         assert!(
             round > highest_commit_certificate_round,
             "Active round is higher than any QC round."
@@ -134,6 +344,12 @@ impl PacemakerUpdateActions {
     }
 }
 
+impl Default for PacemakerUpdateActions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Pacemaker for PacemakerState {
     // -- BEGIN FILE pacemaker_impl --
     fn update_pacemaker(
@@ -155,13 +371,36 @@ impl Pacemaker for PacemakerState {
         if epoch_id > self.active_epoch
             || (epoch_id == self.active_epoch && active_round > self.active_round)
         {
+            // .. learn from the duration of the previous round if it ended with a QC, not
+            // counting the wait before proposals, which depends on delta itself
+            if epoch_id == self.active_epoch
+                && self.active_round > Round(0)
+                && record_store.highest_quorum_certificate_round() == self.active_round
+            {
+                let proposal_time = self.active_round_start_time + self.proposal_delay();
+                if let Some(adaptive_delta) = &mut self.adaptive_delta {
+                    self.delta = adaptive_delta.observe(clock, max(0, clock.0 - proposal_time.0));
+                }
+            }
+            // .. update the reputation of the leaders
+            if let Some(reputation) = &mut self.leader_reputation {
+                if epoch_id > self.active_epoch {
+                    reputation.reset();
+                } else if record_store.highest_timeout_certificate_round() == self.active_round {
+                    if let Some(leader) = self.active_leader {
+                        reputation.observe_timeout(self.active_round, leader);
+                    }
+                }
+                reputation.observe_commits(record_store);
+                reputation.prune(active_round);
+            }
             // .. store the new value
             self.active_epoch = epoch_id;
             self.active_round = active_round;
             // .. start a timer
             self.active_round_start_time = clock;
             // .. compute the leader
-            self.active_leader = Some(Self::leader(record_store, active_round));
+            self.active_leader = Some(self.elect_leader(record_store, active_round));
             // .. compute the duration
             self.active_round_duration = self.duration(record_store, active_round);
             // .. synchronize with the leader.
@@ -169,16 +408,23 @@ impl Pacemaker for PacemakerState {
                 actions.should_send = self.active_leader.into_iter().collect();
             }
         }
+        let proposal_time = self.active_round_start_time + self.proposal_delay();
         // If we are the leader and have not proposed yet..
-        if self.active_leader == Some(local_author) && record_store.proposed_block(&*self) == None {
-            // .. propose a block on top of the highest QC that we know.
-            actions.should_propose_block = Some(record_store.highest_quorum_certificate_hash());
-            actions.should_broadcast = true;
-            // .. force an immediate update to vote on our own proposal.
-            actions.next_scheduled_update = clock;
+        if self.active_leader == Some(local_author) && record_store.proposed_block(&*self).is_none()
+        {
+            if clock >= proposal_time {
+                // .. propose a block on top of the highest QC that we know.
+                actions.should_propose_block = Some(record_store.highest_quorum_certificate_hash());
+                actions.should_broadcast = true;
+                // .. force an immediate update to vote on our own proposal.
+                actions.next_scheduled_update = clock;
+            } else {
+                // .. or wait until we may propose.
+                actions.next_scheduled_update = proposal_time;
+            }
         }
         if !record_store.has_timeout(local_author, active_round) {
-            let timeout_deadline = self.active_round_start_time + self.active_round_duration;
+            let timeout_deadline = proposal_time + self.active_round_duration;
             // If we have not created a timeout yet, check if the round has passed its maximal
             // duration. Then, either broadcast a new timeout now, or schedule an update
             // in the future.
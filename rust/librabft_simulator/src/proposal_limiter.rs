@@ -0,0 +1,92 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Leader-side limits on proposals: at most one proposal per epoch and round, whatever the
+//! pacemaker asks for, and a minimum interval between two consecutive proposals.
+
+use super::*;
+use base_types::*;
+
+#[cfg(test)]
+#[path = "unit_tests/proposal_limiter_tests.rs"]
+mod proposal_limiter_tests;
+
+/// Why a proposal was not allowed.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ProposalDenial {
+    /// We already proposed a block for this epoch and round.
+    SameRound,
+    /// The minimum interval since our latest proposal ends at the given time.
+    TooEarly(NodeTime),
+}
+
+#[derive(Clone, Debug)]
+pub struct ProposalLimiter {
+    /// Minimum time between two of our proposals.
+    min_interval: Duration,
+    /// Whether a second proposal for the same round is a bug worth a panic, as in simulations,
+    /// rather than only being dropped.
+    strict: bool,
+    /// Epoch, round, and time of our latest proposal.
+    latest_proposal: Option<(EpochId, Round, NodeTime)>,
+    /// Number of proposals dropped because we already proposed for the round.
+    duplicates: usize,
+    /// Number of times a proposal was postponed to respect the minimum interval.
+    deferrals: usize,
+}
+
+impl ProposalLimiter {
+    pub fn new(min_interval: Duration, strict: bool) -> Self {
+        assert!(min_interval >= 0, "Invalid minimum proposal interval");
+        ProposalLimiter {
+            min_interval,
+            strict,
+            latest_proposal: None,
+            duplicates: 0,
+            deferrals: 0,
+        }
+    }
+
+    /// Check whether we may propose for the given epoch and round at the given time, and
+    /// account for the denial if not. In strict mode, a second proposal for the same round
+    /// panics.
+    pub fn check(
+        &mut self,
+        epoch_id: EpochId,
+        round: Round,
+        clock: NodeTime,
+    ) -> std::result::Result<(), ProposalDenial> {
+        let (latest_epoch_id, latest_round, latest_time) = match self.latest_proposal {
+            None => return Ok(()),
+            Some(latest_proposal) => latest_proposal,
+        };
+        if (epoch_id, round) <= (latest_epoch_id, latest_round) {
+            assert!(
+                !self.strict,
+                "Proposing again at epoch {:?} and round {:?} (latest proposal at epoch {:?} and round {:?})",
+                epoch_id, round, latest_epoch_id, latest_round
+            );
+            self.duplicates += 1;
+            return Err(ProposalDenial::SameRound);
+        }
+        let allowed_time = latest_time + self.min_interval;
+        if clock < allowed_time {
+            self.deferrals += 1;
+            return Err(ProposalDenial::TooEarly(allowed_time));
+        }
+        Ok(())
+    }
+
+    /// Record a proposal made for the given epoch and round at the given time.
+    pub fn record(&mut self, epoch_id: EpochId, round: Round, clock: NodeTime) {
+        self.latest_proposal = Some((epoch_id, round, clock));
+    }
+
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    pub fn deferrals(&self) -> usize {
+        self.deferrals
+    }
+}
@@ -0,0 +1,475 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Protobuf wire format of records and data-synchronization messages, as specified in
+//! `proto/librabft.proto`. The message types are written by hand after the schema, so that
+//! building the crate does not require `protoc`: both must be kept in sync.
+
+use super::*;
+use base_types::*;
+use prost::Message;
+use record::*;
+
+#[cfg(test)]
+#[path = "unit_tests/proto_tests.rs"]
+mod proto_tests;
+
+/// Protobuf messages of `proto/librabft.proto`.
+pub mod messages {
+    use prost::{Message, Oneof};
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Command {
+        #[prost(uint64, tag = "1")]
+        pub proposer: u64,
+        #[prost(uint64, tag = "2")]
+        pub index: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Block {
+        #[prost(message, optional, tag = "1")]
+        pub command: Option<Command>,
+        #[prost(int64, tag = "2")]
+        pub time: i64,
+        #[prost(uint64, tag = "3")]
+        pub previous_quorum_certificate_hash: u64,
+        #[prost(uint64, tag = "4")]
+        pub round: u64,
+        #[prost(uint64, tag = "5")]
+        pub author: u64,
+        #[prost(uint64, tag = "6")]
+        pub signature: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct EpochChange {
+        #[prost(uint64, tag = "1")]
+        pub epoch_id: u64,
+        #[prost(uint64, tag = "2")]
+        pub configuration_digest: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Vote {
+        #[prost(uint64, tag = "1")]
+        pub epoch_id: u64,
+        #[prost(uint64, tag = "2")]
+        pub round: u64,
+        #[prost(uint64, tag = "3")]
+        pub certified_block_hash: u64,
+        #[prost(uint64, tag = "4")]
+        pub state: u64,
+        #[prost(uint64, optional, tag = "5")]
+        pub committed_state: Option<u64>,
+        #[prost(uint64, tag = "6")]
+        pub author: u64,
+        #[prost(uint64, tag = "7")]
+        pub signature: u64,
+        #[prost(int64, tag = "8")]
+        pub time: i64,
+        #[prost(message, optional, tag = "9")]
+        pub next_epoch: Option<EpochChange>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct VoteSignature {
+        #[prost(uint64, tag = "1")]
+        pub author: u64,
+        #[prost(uint64, tag = "2")]
+        pub signature: u64,
+        #[prost(int64, tag = "3")]
+        pub time: i64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QuorumCertificate {
+        #[prost(uint64, tag = "1")]
+        pub epoch_id: u64,
+        #[prost(uint64, tag = "2")]
+        pub round: u64,
+        #[prost(uint64, tag = "3")]
+        pub certified_block_hash: u64,
+        #[prost(uint64, tag = "4")]
+        pub state: u64,
+        #[prost(uint64, optional, tag = "5")]
+        pub committed_state: Option<u64>,
+        #[prost(message, repeated, tag = "6")]
+        pub votes: Vec<VoteSignature>,
+        #[prost(uint64, tag = "7")]
+        pub author: u64,
+        #[prost(uint64, tag = "8")]
+        pub signature: u64,
+        #[prost(message, optional, tag = "9")]
+        pub next_epoch: Option<EpochChange>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Timeout {
+        #[prost(uint64, tag = "1")]
+        pub epoch_id: u64,
+        #[prost(uint64, tag = "2")]
+        pub round: u64,
+        #[prost(uint64, tag = "3")]
+        pub highest_certified_block_round: u64,
+        #[prost(uint64, tag = "4")]
+        pub author: u64,
+        #[prost(uint64, tag = "5")]
+        pub signature: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Record {
+        #[prost(oneof = "RecordKind", tags = "1, 2, 3, 4")]
+        pub record: Option<RecordKind>,
+    }
+
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum RecordKind {
+        #[prost(message, tag = "1")]
+        Block(Block),
+        #[prost(message, tag = "2")]
+        Vote(Vote),
+        #[prost(message, tag = "3")]
+        QuorumCertificate(QuorumCertificate),
+        #[prost(message, tag = "4")]
+        Timeout(Timeout),
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct DataSyncNotification {
+        #[prost(uint64, tag = "1")]
+        pub current_epoch: u64,
+        #[prost(message, optional, tag = "2")]
+        pub highest_commit_certificate: Option<QuorumCertificate>,
+        #[prost(message, optional, tag = "3")]
+        pub highest_quorum_certificate: Option<QuorumCertificate>,
+        #[prost(message, repeated, tag = "4")]
+        pub timeouts: Vec<Timeout>,
+        #[prost(message, optional, tag = "5")]
+        pub current_vote: Option<Vote>,
+        #[prost(message, optional, tag = "6")]
+        pub proposed_block: Option<Block>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct RoundDigest {
+        #[prost(uint64, tag = "1")]
+        pub round: u64,
+        #[prost(uint64, tag = "2")]
+        pub digest: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct SketchCell {
+        #[prost(int64, tag = "1")]
+        pub count: i64,
+        #[prost(uint64, tag = "2")]
+        pub key_sum: u64,
+        #[prost(uint64, tag = "3")]
+        pub hash_sum: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct RecordSketch {
+        #[prost(message, repeated, tag = "1")]
+        pub cells: Vec<SketchCell>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct RecordSummary {
+        #[prost(uint64, tag = "1")]
+        pub highest_committed_round: u64,
+        #[prost(message, repeated, tag = "2")]
+        pub round_digests: Vec<RoundDigest>,
+        #[prost(message, optional, tag = "3")]
+        pub sketch: Option<RecordSketch>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct DataSyncRequest {
+        #[prost(uint64, tag = "1")]
+        pub current_epoch: u64,
+        #[prost(message, optional, tag = "3")]
+        pub summary: Option<RecordSummary>,
+        #[prost(bool, tag = "4")]
+        pub needs_snapshot: bool,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct EpochRecords {
+        #[prost(uint64, tag = "1")]
+        pub epoch_id: u64,
+        #[prost(message, repeated, tag = "2")]
+        pub records: Vec<Record>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct EpochSnapshot {
+        #[prost(message, optional, tag = "1")]
+        pub certificate: Option<QuorumCertificate>,
+        #[prost(int64, tag = "2")]
+        pub time: i64,
+        #[prost(bytes = "vec", tag = "3")]
+        pub data: Vec<u8>,
+        #[prost(message, repeated, tag = "4")]
+        pub proofs: Vec<EpochProof>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct EpochProof {
+        #[prost(message, optional, tag = "1")]
+        pub certificate: Option<QuorumCertificate>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub configuration: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct DataSyncResponse {
+        #[prost(uint64, tag = "1")]
+        pub current_epoch: u64,
+        #[prost(message, repeated, tag = "2")]
+        pub records: Vec<EpochRecords>,
+        #[prost(message, optional, tag = "3")]
+        pub snapshot: Option<EpochSnapshot>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct NetworkMessage {
+        #[prost(uint64, tag = "1")]
+        pub sender: u64,
+        #[prost(oneof = "NetworkMessageKind", tags = "2, 3, 4")]
+        pub message: Option<NetworkMessageKind>,
+    }
+
+    #[allow(clippy::large_enum_variant)]
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum NetworkMessageKind {
+        #[prost(message, tag = "2")]
+        Notification(DataSyncNotification),
+        #[prost(message, tag = "3")]
+        Request(DataSyncRequest),
+        #[prost(message, tag = "4")]
+        Response(DataSyncResponse),
+    }
+}
+
+/// Conversion between a type and its protobuf message.
+pub trait Proto: Sized {
+    type Message: Message + Default;
+
+    fn to_proto(&self) -> Self::Message;
+    fn from_proto(message: Self::Message) -> Result<Self>;
+
+    fn to_proto_bytes(&self) -> Vec<u8> {
+        self.to_proto().encode_to_vec()
+    }
+
+    fn from_proto_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_proto(Self::Message::decode(bytes)?)
+    }
+}
+
+impl Proto for Command {
+    type Message = messages::Command;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::Command {
+            proposer: self.proposer.0 as u64,
+            index: self.index as u64,
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(Command {
+            proposer: Author(message.proposer as usize),
+            index: message.index as usize,
+        })
+    }
+}
+
+impl Proto for EpochChange {
+    type Message = messages::EpochChange;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::EpochChange {
+            epoch_id: self.epoch_id.0 as u64,
+            configuration_digest: self.configuration_digest,
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(EpochChange {
+            epoch_id: EpochId(message.epoch_id as usize),
+            configuration_digest: message.configuration_digest,
+        })
+    }
+}
+
+impl Proto for Block {
+    type Message = messages::Block;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::Block {
+            command: self.command.as_ref().map(Proto::to_proto),
+            time: self.time.0,
+            previous_quorum_certificate_hash: self.previous_quorum_certificate_hash.0,
+            round: self.round.0 as u64,
+            author: self.author.0 as u64,
+            signature: self.signature.0,
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(Block {
+            command: message.command.map(Command::from_proto).transpose()?,
+            time: NodeTime(message.time),
+            previous_quorum_certificate_hash: QuorumCertificateHash(
+                message.previous_quorum_certificate_hash,
+            ),
+            round: Round(message.round as usize),
+            author: Author(message.author as usize),
+            signature: Signature(message.signature),
+        })
+    }
+}
+
+impl Proto for Vote {
+    type Message = messages::Vote;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::Vote {
+            epoch_id: self.epoch_id.0 as u64,
+            round: self.round.0 as u64,
+            certified_block_hash: self.certified_block_hash.0,
+            state: self.state.0,
+            committed_state: self.committed_state.as_ref().map(|state| state.0),
+            author: self.author.0 as u64,
+            signature: self.signature.0,
+            time: self.time.0,
+            next_epoch: self.next_epoch.as_ref().map(Proto::to_proto),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(Vote {
+            epoch_id: EpochId(message.epoch_id as usize),
+            round: Round(message.round as usize),
+            certified_block_hash: BlockHash(message.certified_block_hash),
+            state: State(message.state),
+            committed_state: message.committed_state.map(State),
+            next_epoch: message
+                .next_epoch
+                .map(EpochChange::from_proto)
+                .transpose()?,
+            time: NodeTime(message.time),
+            author: Author(message.author as usize),
+            signature: Signature(message.signature),
+        })
+    }
+}
+
+impl Proto for QuorumCertificate {
+    type Message = messages::QuorumCertificate;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::QuorumCertificate {
+            epoch_id: self.epoch_id.0 as u64,
+            round: self.round.0 as u64,
+            certified_block_hash: self.certified_block_hash.0,
+            state: self.state.0,
+            committed_state: self.committed_state.as_ref().map(|state| state.0),
+            votes: self
+                .votes
+                .iter()
+                .map(|(author, time, signature)| messages::VoteSignature {
+                    author: author.0 as u64,
+                    signature: signature.0,
+                    time: time.0,
+                })
+                .collect(),
+            author: self.author.0 as u64,
+            signature: self.signature.0,
+            next_epoch: self.next_epoch.as_ref().map(Proto::to_proto),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(QuorumCertificate {
+            epoch_id: EpochId(message.epoch_id as usize),
+            round: Round(message.round as usize),
+            certified_block_hash: BlockHash(message.certified_block_hash),
+            state: State(message.state),
+            committed_state: message.committed_state.map(State),
+            next_epoch: message
+                .next_epoch
+                .map(EpochChange::from_proto)
+                .transpose()?,
+            votes: message
+                .votes
+                .into_iter()
+                .map(|vote| {
+                    (
+                        Author(vote.author as usize),
+                        NodeTime(vote.time),
+                        Signature(vote.signature),
+                    )
+                })
+                .collect(),
+            author: Author(message.author as usize),
+            signature: Signature(message.signature),
+        })
+    }
+}
+
+impl Proto for Timeout {
+    type Message = messages::Timeout;
+
+    fn to_proto(&self) -> Self::Message {
+        messages::Timeout {
+            epoch_id: self.epoch_id.0 as u64,
+            round: self.round.0 as u64,
+            highest_certified_block_round: self.highest_certified_block_round.0 as u64,
+            author: self.author.0 as u64,
+            signature: self.signature.0,
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        Ok(Timeout {
+            epoch_id: EpochId(message.epoch_id as usize),
+            round: Round(message.round as usize),
+            highest_certified_block_round: Round(message.highest_certified_block_round as usize),
+            author: Author(message.author as usize),
+            signature: Signature(message.signature),
+        })
+    }
+}
+
+impl Proto for Record {
+    type Message = messages::Record;
+
+    fn to_proto(&self) -> Self::Message {
+        let record = match self {
+            Record::Block(x) => messages::RecordKind::Block(x.to_proto()),
+            Record::Vote(x) => messages::RecordKind::Vote(x.to_proto()),
+            Record::QuorumCertificate(x) => messages::RecordKind::QuorumCertificate(x.to_proto()),
+            Record::Timeout(x) => messages::RecordKind::Timeout(x.to_proto()),
+        };
+        messages::Record {
+            record: Some(record),
+        }
+    }
+
+    fn from_proto(message: Self::Message) -> Result<Self> {
+        match message.record {
+            Some(messages::RecordKind::Block(x)) => Ok(Record::Block(Block::from_proto(x)?)),
+            Some(messages::RecordKind::Vote(x)) => Ok(Record::Vote(Vote::from_proto(x)?)),
+            Some(messages::RecordKind::QuorumCertificate(x)) => {
+                Ok(Record::QuorumCertificate(QuorumCertificate::from_proto(x)?))
+            }
+            Some(messages::RecordKind::Timeout(x)) => Ok(Record::Timeout(Timeout::from_proto(x)?)),
+            None => bail!("Unknown or missing kind of record."),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded caches of the QCs whose signatures were verified, so that a QC received again from
+//! other peers, in notifications or data-sync responses, is not verified again.
+
+use super::*;
+use base_types::*;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+
+#[cfg(test)]
+#[path = "unit_tests/qc_cache_tests.rs"]
+mod qc_cache_tests;
+
+#[derive(Eq, PartialEq, Clone, Debug, Default, Serialize)]
+pub struct QuorumCertificateCacheStats {
+    /// QCs whose signatures were skipped because they were verified before.
+    pub hits: usize,
+    /// QCs whose signatures had to be verified.
+    pub misses: usize,
+    /// Signatures left unchecked thanks to the cache, votes included.
+    pub signatures_saved: usize,
+}
+
+impl QuorumCertificateCacheStats {
+    pub fn add(&mut self, other: &QuorumCertificateCacheStats) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.signatures_saved += other.signatures_saved;
+    }
+}
+
+/// The last `capacity` QCs whose signatures were verified. Digests of QCs cover their votes
+/// but not the signature of their author, so entries are keyed by both. Verification only
+/// depends on the configuration of the epoch of the QC, which is part of its digest.
+#[derive(Clone, Debug)]
+pub struct QuorumCertificateCache {
+    capacity: usize,
+    entries: HashSet<(QuorumCertificateHash, Signature)>,
+    order: VecDeque<(QuorumCertificateHash, Signature)>,
+    stats: QuorumCertificateCacheStats,
+}
+
+impl QuorumCertificateCache {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "QC caches must hold at least one QC");
+        QuorumCertificateCache {
+            capacity,
+            entries: HashSet::new(),
+            order: VecDeque::new(),
+            stats: QuorumCertificateCacheStats::default(),
+        }
+    }
+
+    /// Whether the QC of the given digest and signature was verified already, in which case
+    /// its `num_signatures` signatures are not checked again.
+    pub fn lookup(
+        &mut self,
+        hash: QuorumCertificateHash,
+        signature: Signature,
+        num_signatures: usize,
+    ) -> bool {
+        if self.entries.contains(&(hash, signature)) {
+            self.stats.hits += 1;
+            self.stats.signatures_saved += num_signatures;
+            true
+        } else {
+            self.stats.misses += 1;
+            false
+        }
+    }
+
+    /// Whether the QC of the given digest and signature is cached, without counting a hit or
+    /// a miss.
+    pub fn contains(&self, hash: QuorumCertificateHash, signature: Signature) -> bool {
+        self.entries.contains(&(hash, signature))
+    }
+
+    /// Remember a QC whose signatures were verified, evicting the oldest one if the cache is
+    /// full.
+    pub fn insert(&mut self, hash: QuorumCertificateHash, signature: Signature) {
+        if !self.entries.insert((hash, signature)) {
+            return;
+        }
+        self.order.push_back((hash, signature));
+        if self.order.len() > self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.entries.remove(&oldest);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn stats(&self) -> &QuorumCertificateCacheStats {
+        &self.stats
+    }
+}
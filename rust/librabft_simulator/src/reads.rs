@@ -0,0 +1,249 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reads of the committed state, with three levels of consistency: the local state of any
+//! validator, the state of a leader holding a lease, and the freshest state of a quorum. Reads
+//! issued during a simulation measure how far behind the freshest validator each level is.
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use introspection::LeaderHint;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use scenario::{NodeSimulator, ReadParameters};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt};
+
+#[cfg(test)]
+#[path = "unit_tests/reads_tests.rs"]
+mod reads_tests;
+
+/// Where reads are served from.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadConsistency {
+    /// The committed state of any validator, however late it is.
+    Local,
+    /// The committed state of the leader of the active round, while the round started less
+    /// than a lease ago.
+    LeaderLease,
+    /// The freshest committed state among a quorum of validators.
+    Quorum,
+}
+
+/// Committed state returned by a read.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct ReadResponse {
+    /// Validator that served the read.
+    pub author: Author,
+    /// Epoch and round of the latest committed block, or the current epoch and round 0 if
+    /// none.
+    pub epoch_id: EpochId,
+    pub round: Round,
+    /// State after the latest committed block, if any.
+    pub state: Option<State>,
+    /// Number of blocks committed by the validator so far.
+    pub commits: usize,
+}
+
+/// Why a read was refused.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum ReadError {
+    /// Lease reads are only served by the leader of the active round.
+    NotLeader(LeaderHint),
+    /// The lease of the leader ran out at the given local time.
+    LeaseExpired { expiry: NodeTime },
+    /// The validators that answered do not form a quorum.
+    NoQuorum { weight: usize, threshold: usize },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::NotLeader(hint) => write!(
+                f,
+                "Not the leader of {:?}, try {:?}.",
+                hint.round, hint.leader
+            ),
+            ReadError::LeaseExpired { expiry } => {
+                write!(f, "The lease of the leader expired at {:?}.", expiry)
+            }
+            ReadError::NoQuorum { weight, threshold } => write!(
+                f,
+                "Only {} votes answered out of the {} needed.",
+                weight, threshold
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// The freshest of the responses of distinct validators, if they form a quorum.
+pub fn quorum_read(
+    responses: Vec<ReadResponse>,
+    configuration: &EpochConfiguration,
+) -> std::result::Result<ReadResponse, ReadError> {
+    let authors: BTreeSet<_> = responses.iter().map(|response| response.author).collect();
+    let weight = configuration.count_votes(authors.iter());
+    let threshold = configuration.quorum_threshold();
+    if weight < threshold {
+        return Err(ReadError::NoQuorum { weight, threshold });
+    }
+    Ok(responses
+        .into_iter()
+        .max_by_key(|response| response.commits)
+        .expect("A quorum is not empty"))
+}
+
+/// Staleness of the reads of one level of consistency, in committed blocks behind the
+/// freshest validator at the time of the read.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct ReadStats {
+    pub consistency: ReadConsistency,
+    /// Number of reads served.
+    pub reads: usize,
+    /// Number of reads refused, or unanswered because validators were down.
+    pub failures: usize,
+    /// Number of reads behind the freshest validator.
+    pub stale_reads: usize,
+    pub total_staleness: usize,
+    pub max_staleness: usize,
+}
+
+impl ReadStats {
+    fn new(consistency: ReadConsistency) -> Self {
+        ReadStats {
+            consistency,
+            reads: 0,
+            failures: 0,
+            stale_reads: 0,
+            total_staleness: 0,
+            max_staleness: 0,
+        }
+    }
+
+    /// Mean number of blocks that reads are behind, if any read was served.
+    pub fn mean_staleness(&self) -> Option<f64> {
+        if self.reads == 0 {
+            None
+        } else {
+            Some(self.total_staleness as f64 / self.reads as f64)
+        }
+    }
+
+    fn add(&mut self, result: std::result::Result<ReadResponse, ReadError>, freshest: usize) {
+        match result {
+            Ok(response) => {
+                let staleness = freshest - response.commits;
+                self.reads += 1;
+                self.total_staleness += staleness;
+                self.max_staleness = std::cmp::max(self.max_staleness, staleness);
+                if staleness > 0 {
+                    self.stale_reads += 1;
+                }
+            }
+            Err(_) => self.failures += 1,
+        }
+    }
+}
+
+/// Reads issued periodically during a simulation, at every level of consistency of the
+/// parameters. Reads do not change the state of validators, so they are issued by an observer
+/// of the simulation, after the first event at or after each period.
+#[derive(Debug)]
+pub struct SimulatedReads {
+    parameters: ReadParameters,
+    num_nodes: usize,
+    next_time: i64,
+    rng: StdRng,
+    stats: Vec<ReadStats>,
+}
+
+impl SimulatedReads {
+    /// Reads from the first `num_nodes` replicas.
+    pub fn new(parameters: &ReadParameters, num_nodes: usize, seed: u64) -> Self {
+        SimulatedReads {
+            parameters: parameters.clone(),
+            num_nodes,
+            next_time: parameters.period,
+            rng: StdRng::seed_from_u64(seed),
+            stats: parameters
+                .consistency
+                .iter()
+                .map(|consistency| ReadStats::new(*consistency))
+                .collect(),
+        }
+    }
+
+    pub fn stats(&self) -> &[ReadStats] {
+        &self.stats
+    }
+
+    /// Issue the reads due at `clock`, if any.
+    pub fn observe(&mut self, sim: &NodeSimulator, clock: GlobalTime) {
+        if clock.0 < self.next_time {
+            return;
+        }
+        while self.next_time <= clock.0 {
+            self.next_time += self.parameters.period;
+        }
+        let validators = &sim.replicas()[..self.num_nodes];
+        let freshest = validators
+            .iter()
+            .map(|replica| replica.node().node_state().commits().len())
+            .max()
+            .unwrap_or(0);
+        let live: Vec<_> = validators
+            .iter()
+            .filter(|replica| !replica.is_crashed(clock))
+            .collect();
+        for index in 0..self.stats.len() {
+            let result = if live.is_empty() {
+                Err(ReadError::NoQuorum {
+                    weight: 0,
+                    threshold: 1,
+                })
+            } else {
+                let replica = live[self.rng.gen_range(0, live.len())];
+                let node = replica.node().node_state();
+                match self.stats[index].consistency {
+                    ReadConsistency::Local => Ok(node.read_local()),
+                    ReadConsistency::LeaderLease => {
+                        // Validators that are not the leader answer with a hint, which the
+                        // reader follows once.
+                        let leader = match node.leader_hint().leader {
+                            Some(leader) if leader.0 < self.num_nodes => sim.simulated_node(leader),
+                            _ => replica,
+                        };
+                        if leader.is_crashed(clock) {
+                            Err(ReadError::NotLeader(node.leader_hint()))
+                        } else {
+                            leader
+                                .node()
+                                .node_state()
+                                .read_with_lease(leader.local_time(clock), self.parameters.lease)
+                        }
+                    }
+                    ReadConsistency::Quorum => {
+                        let mut quorum = live.clone();
+                        quorum.shuffle(&mut self.rng);
+                        let configuration = node.configuration();
+                        let threshold = configuration.quorum_threshold();
+                        let mut weight = 0;
+                        let responses = quorum
+                            .iter()
+                            .take_while(|replica| {
+                                let before = weight;
+                                weight += configuration.weight(&replica.author());
+                                before < threshold
+                            })
+                            .map(|replica| replica.node().node_state().read_local())
+                            .collect();
+                        quorum_read(responses, configuration)
+                    }
+                }
+            };
+            self.stats[index].add(result, freshest);
+        }
+    }
+}
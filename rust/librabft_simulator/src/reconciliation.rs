@@ -0,0 +1,173 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Invertible Bloom lookup tables (IBLT) summarizing sets of record hashes, so that two nodes
+//! can compute the difference of their record sets from a message whose size only depends on
+//! the size of the difference.
+
+use serde::Serialize;
+use serialization::{stable_hash, to_canonical_bytes};
+
+#[cfg(test)]
+#[path = "unit_tests/reconciliation_tests.rs"]
+mod reconciliation_tests;
+
+/// Number of cells where each key is inserted, one in each part of the table.
+const NUM_HASHES: usize = 3;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug, Default)]
+pub struct SketchCell {
+    /// Number of keys inserted, minus the number of keys removed by subtraction.
+    pub count: i64,
+    /// Exclusive or of the keys.
+    pub key_sum: u64,
+    /// Exclusive or of the checksums of the keys.
+    pub hash_sum: u64,
+}
+
+impl SketchCell {
+    fn toggle(&mut self, key: u64, count: i64) {
+        self.count += count;
+        self.key_sum ^= key;
+        self.hash_sum ^= checksum(key);
+    }
+
+    /// Whether the cell contains a single key, up to subtraction.
+    fn is_pure(&self) -> bool {
+        (self.count == 1 || self.count == -1) && self.hash_sum == checksum(self.key_sum)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.count == 0 && self.key_sum == 0 && self.hash_sum == 0
+    }
+}
+
+/// Spread the entropy of a FNV hash over all its bits, so that small moduli of the result are
+/// uniformly distributed.
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+fn checksum(key: u64) -> u64 {
+    stable_hash(&to_canonical_bytes(&(key, NUM_HASHES as u64)))
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct InvertibleBloomLookupTable {
+    cells: Vec<SketchCell>,
+}
+
+impl InvertibleBloomLookupTable {
+    /// Create an empty table. Differences of about `num_cells / 2` keys can usually be decoded.
+    pub fn new(num_cells: usize) -> Self {
+        // Round up so that each hash function has a part of the same size.
+        let part = std::cmp::max(num_cells.div_ceil(NUM_HASHES), 1);
+        InvertibleBloomLookupTable {
+            cells: vec![SketchCell::default(); part * NUM_HASHES],
+        }
+    }
+
+    pub fn from_cells(cells: Vec<SketchCell>) -> Self {
+        InvertibleBloomLookupTable { cells }
+    }
+
+    pub fn cells(&self) -> &[SketchCell] {
+        &self.cells
+    }
+
+    fn indices(&self, key: u64) -> Vec<usize> {
+        let part = self.cells.len() / NUM_HASHES;
+        (0..NUM_HASHES)
+            .map(|i| {
+                let hash = mix(stable_hash(&to_canonical_bytes(&(key, i as u64))));
+                i * part + (hash % part as u64) as usize
+            })
+            .collect()
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        for index in self.indices(key) {
+            self.cells[index].toggle(key, 1);
+        }
+    }
+
+    /// The table of the difference of two sets, provided that both tables have the same size.
+    pub fn subtract(&self, other: &InvertibleBloomLookupTable) -> Option<Self> {
+        if self.cells.len() != other.cells.len() || !self.cells.len().is_multiple_of(NUM_HASHES) {
+            return None;
+        }
+        let cells = self
+            .cells
+            .iter()
+            .zip(other.cells.iter())
+            .map(|(cell, other)| SketchCell {
+                count: cell.count - other.count,
+                key_sum: cell.key_sum ^ other.key_sum,
+                hash_sum: cell.hash_sum ^ other.hash_sum,
+            })
+            .collect();
+        Some(InvertibleBloomLookupTable { cells })
+    }
+
+    /// List the keys of a difference table: first the keys inserted in the left-hand side of
+    /// the subtraction, then the keys of the right-hand side. Return `None` if the difference is
+    /// too large for the table.
+    pub fn decode(mut self) -> Option<(Vec<u64>, Vec<u64>)> {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        let mut pure: Vec<_> = (0..self.cells.len())
+            .filter(|index| self.cells[*index].is_pure())
+            .collect();
+        while let Some(index) = pure.pop() {
+            let cell = self.cells[index];
+            if !cell.is_pure() {
+                continue;
+            }
+            if cell.count > 0 {
+                left.push(cell.key_sum);
+            } else {
+                right.push(cell.key_sum);
+            }
+            for other in self.indices(cell.key_sum) {
+                self.cells[other].toggle(cell.key_sum, -cell.count);
+                if self.cells[other].is_pure() {
+                    pure.push(other);
+                }
+            }
+        }
+        if self.cells.iter().all(SketchCell::is_empty) {
+            Some((left, right))
+        } else {
+            None
+        }
+    }
+}
+
+/// How much of the data received during data sync was actually missing.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default, Serialize)]
+pub struct ReconciliationStats {
+    /// Blocks and QCs received in data-sync responses.
+    pub records_received: usize,
+    /// Received blocks and QCs that were not known yet.
+    pub records_missing: usize,
+}
+
+impl ReconciliationStats {
+    pub fn add(&mut self, other: &ReconciliationStats) {
+        self.records_received += other.records_received;
+        self.records_missing += other.records_missing;
+    }
+
+    /// Fraction of the received blocks and QCs that were missing, if any were received.
+    pub fn efficiency(&self) -> Option<f64> {
+        if self.records_received == 0 {
+            None
+        } else {
+            Some(self.records_missing as f64 / self.records_received as f64)
+        }
+    }
+}
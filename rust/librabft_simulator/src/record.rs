@@ -3,8 +3,12 @@
 
 use super::*;
 use base_types::*;
+use hashing::Hasher as RecordHasher;
+use serde::{Deserialize, Serialize};
+use serialization::{configuration_digest, CanonicalEncode};
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::BTreeSet,
+    fmt,
     hash::{Hash, Hasher},
 };
 
@@ -19,7 +23,7 @@ mod record_tests;
 
 // -- BEGIN FILE records --
 /// A record read from the network.
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Hash)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Hash, Serialize, Deserialize)]
 pub enum Record {
     /// Proposed block, containing a command, e.g. a set of Libra transactions.
     Block(Block),
@@ -31,10 +35,11 @@ pub enum Record {
     Timeout(Timeout),
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Serialize, Deserialize)]
 pub struct Block {
-    /// User-defined command to execute in the state machine.
-    pub command: Command,
+    /// User-defined command to execute in the state machine, if any. Empty blocks only advance
+    /// the time of the state machine.
+    pub command: Option<Command>,
     /// Time proposed for command execution.
     pub time: NodeTime,
     /// Hash of the quorum certificate of the previous block.
@@ -47,7 +52,17 @@ pub struct Block {
     pub signature: Signature,
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+/// The start of the next epoch, as announced by the votes and the QC that commit the last block
+/// of an epoch.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug, Hash, Serialize, Deserialize)]
+pub struct EpochChange {
+    /// The next epoch.
+    pub epoch_id: EpochId,
+    /// Digest of the configuration (i.e. voting rights) of the next epoch.
+    pub configuration_digest: u64,
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Serialize, Deserialize)]
 pub struct Vote {
     /// The current epoch.
     pub epoch_id: EpochId,
@@ -60,13 +75,17 @@ pub struct Vote {
     /// Execution state of the ancestor block (if any) that will match
     /// the commit rule when a QC is formed at this round.
     pub committed_state: Option<State>,
+    /// The next epoch, if the committed state ends the current one.
+    pub next_epoch: Option<EpochChange>,
+    /// Local time of the author when voting, no earlier than the time of the block.
+    pub time: NodeTime,
     /// Creator of the vote.
     pub author: Author,
     /// Signs the hash of the vote, that is, all the fields above.
     pub signature: Signature,
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Serialize, Deserialize)]
 pub struct QuorumCertificate {
     /// The current epoch.
     pub epoch_id: EpochId,
@@ -79,15 +98,17 @@ pub struct QuorumCertificate {
     /// Execution state of the ancestor block (if any) that matches
     /// the commit rule thanks to this QC.
     pub committed_state: Option<State>,
-    /// A collections of votes sharing the fields above.
-    pub votes: Vec<(Author, Signature)>,
+    /// The next epoch, if the committed state ends the current one.
+    pub next_epoch: Option<EpochChange>,
+    /// A collections of votes sharing the fields above, with the time of each vote.
+    pub votes: Vec<(Author, NodeTime, Signature)>,
     /// The leader who proposed the certified block should also sign the QC.
     pub author: Author,
     /// Signs the hash of the QC, that is, all the fields above.
     pub signature: Signature,
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Serialize, Deserialize)]
 pub struct Timeout {
     /// The current epoch.
     pub epoch_id: EpochId,
@@ -119,6 +140,8 @@ impl Hash for Vote {
         self.certified_block_hash.hash(state);
         self.state.hash(state);
         self.committed_state.hash(state);
+        self.next_epoch.hash(state);
+        self.time.hash(state);
         self.author.hash(state);
     }
 }
@@ -130,6 +153,7 @@ impl Hash for QuorumCertificate {
         self.certified_block_hash.hash(state);
         self.state.hash(state);
         self.committed_state.hash(state);
+        self.next_epoch.hash(state);
         self.votes.hash(state);
         self.author.hash(state);
     }
@@ -143,56 +167,307 @@ impl Hash for Timeout {
     }
 }
 
+impl QuorumCertificate {
+    /// Hash of the vote that the given author must have signed at the given time to be part of
+    /// this QC.
+    pub fn vote_signing_hash(
+        &self,
+        author: Author,
+        time: NodeTime,
+        chain_id: ChainId,
+        hasher: &RecordHasher,
+    ) -> u64 {
+        Record::Vote(Vote {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            certified_block_hash: self.certified_block_hash,
+            state: self.state.clone(),
+            committed_state: self.committed_state.clone(),
+            next_epoch: self.next_epoch,
+            time,
+            author,
+            signature: Signature(0), // ignored
+        })
+        .signing_hash(self.epoch_id, chain_id, hasher)
+    }
+
+    /// Median of the times of the votes, weighted by voting rights: the earliest time at which
+    /// more than half of the voting rights of the QC had voted. As long as Byzantine nodes hold
+    /// less than half of the votes of a quorum, this time lies between the clocks of two honest
+    /// voters, whatever the times chosen by Byzantine nodes and by the leader.
+    pub fn quorum_time(&self, configuration: &EpochConfiguration) -> NodeTime {
+        let mut times: Vec<_> = self
+            .votes
+            .iter()
+            .map(|(author, time, _)| (*time, configuration.weight(author)))
+            .collect();
+        times.sort();
+        let total: usize = times.iter().map(|(_, weight)| weight).sum();
+        let mut weight = 0;
+        for (time, vote_weight) in &times {
+            weight += vote_weight;
+            if 2 * weight > total {
+                return *time;
+            }
+        }
+        // Only for QCs without voting rights, which are never inserted in a record store.
+        times.last().map_or(NodeTime(0), |(time, _)| *time)
+    }
+}
+
+impl EpochChange {
+    /// Announce the given epoch, starting with the given configuration.
+    pub fn new(epoch_id: EpochId, configuration: &EpochConfiguration) -> Self {
+        EpochChange {
+            epoch_id,
+            configuration_digest: configuration_digest(configuration),
+        }
+    }
+
+    /// Whether the given configuration is the one announced for the next epoch.
+    pub fn certifies(&self, configuration: &EpochConfiguration) -> bool {
+        self.configuration_digest == configuration_digest(configuration)
+    }
+}
+
+/// What a valid commit certificate proves.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct CommittedState {
+    /// The epoch of the certificate.
+    pub epoch_id: EpochId,
+    /// Round of the certified block whose QC completes the commit rule.
+    pub certified_round: Round,
+    /// Execution state of the committed ancestor block.
+    pub state: State,
+    /// The next epoch, if the committed block is the last one of the epoch of the certificate.
+    pub next_epoch: Option<EpochChange>,
+}
+
+/// Reasons for rejecting a commit certificate.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum VerifyError {
+    NotACommitCertificate,
+    UnknownAuthor(Author),
+    DuplicateVote(Author),
+    InvalidSignature(Author),
+    InsufficientQuorum { weight: usize, threshold: usize },
+    InvalidEpochChange(EpochId),
+    WrongEpoch(EpochId),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::NotACommitCertificate => {
+                write!(f, "The QC does not complete a commit rule.")
+            }
+            VerifyError::UnknownAuthor(author) => {
+                write!(f, "{:?} has no voting rights in this epoch.", author)
+            }
+            VerifyError::DuplicateVote(author) => write!(f, "{:?} voted more than once.", author),
+            VerifyError::InvalidSignature(author) => {
+                write!(f, "Invalid signature from {:?}.", author)
+            }
+            VerifyError::InsufficientQuorum { weight, threshold } => write!(
+                f,
+                "Votes weigh {} but a quorum needs {}.",
+                weight, threshold
+            ),
+            VerifyError::InvalidEpochChange(epoch_id) => {
+                write!(f, "Invalid change to {:?}.", epoch_id)
+            }
+            VerifyError::WrongEpoch(epoch_id) => {
+                write!(f, "The certificate belongs to {:?}.", epoch_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Check the votes and the signature of a QC against the voting rights and the keys of its
+/// epoch.
+pub fn verify_quorum_certificate(
+    certificate: &QuorumCertificate,
+    configuration: &EpochConfiguration,
+    chain_id: ChainId,
+    hasher: &RecordHasher,
+) -> std::result::Result<(), VerifyError> {
+    let check_signature = |signature: Signature, hash: u64, author: Author| {
+        if configuration.weight(&author) == 0 {
+            return Err(VerifyError::UnknownAuthor(author));
+        }
+        signature
+            .check_with_key(hash, configuration.public_key(author))
+            .map_err(|_| VerifyError::InvalidSignature(author))
+    };
+    let mut voters = BTreeSet::new();
+    for (author, time, signature) in &certificate.votes {
+        if !voters.insert(*author) {
+            return Err(VerifyError::DuplicateVote(*author));
+        }
+        check_signature(
+            *signature,
+            certificate.vote_signing_hash(*author, *time, chain_id, hasher),
+            *author,
+        )?;
+    }
+    let weight = configuration.count_votes(&voters);
+    let threshold = configuration.quorum_threshold();
+    if weight < threshold {
+        return Err(VerifyError::InsufficientQuorum { weight, threshold });
+    }
+    let record = Record::QuorumCertificate(certificate.clone());
+    check_signature(
+        certificate.signature,
+        record.signing_hash(certificate.epoch_id, chain_id, hasher),
+        certificate.author,
+    )
+}
+
+/// Check a commit certificate, as returned by `highest_commit_certificate()`, against the
+/// voting rights of its epoch. This only needs the certificate itself, so that light clients
+/// and external tools can validate commit proofs without running a node.
+pub fn verify_commit_certificate(
+    certificate: &QuorumCertificate,
+    configuration: &EpochConfiguration,
+    chain_id: ChainId,
+    hasher: &RecordHasher,
+) -> std::result::Result<CommittedState, VerifyError> {
+    let state = certificate
+        .committed_state
+        .clone()
+        .ok_or(VerifyError::NotACommitCertificate)?;
+    if let Some(next_epoch) = certificate.next_epoch {
+        if next_epoch.epoch_id <= certificate.epoch_id {
+            return Err(VerifyError::InvalidEpochChange(next_epoch.epoch_id));
+        }
+    }
+    verify_quorum_certificate(certificate, configuration, chain_id, hasher)?;
+    Ok(CommittedState {
+        epoch_id: certificate.epoch_id,
+        certified_round: certificate.round,
+        state,
+        next_epoch: certificate.next_epoch,
+    })
+}
+
 impl Record {
-    pub fn digest(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
-        hasher.finish()
+    /// Name of the type of the record, e.g. "quorum_certificate".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Record::Block(_) => "block",
+            Record::Vote(_) => "vote",
+            Record::QuorumCertificate(_) => "quorum_certificate",
+            Record::Timeout(_) => "timeout",
+        }
+    }
+
+    /// Epoch of the record, if it carries one. Blocks belong to the epoch of the chain that
+    /// they extend.
+    pub fn epoch_id(&self) -> Option<EpochId> {
+        match self {
+            Record::Block(_) => None,
+            Record::Vote(vote) => Some(vote.epoch_id),
+            Record::QuorumCertificate(qc) => Some(qc.epoch_id),
+            Record::Timeout(timeout) => Some(timeout.epoch_id),
+        }
+    }
+
+    pub fn round(&self) -> Round {
+        match self {
+            Record::Block(block) => block.round,
+            Record::Vote(vote) => vote.round,
+            Record::QuorumCertificate(qc) => qc.round,
+            Record::Timeout(timeout) => timeout.round,
+        }
+    }
+
+    /// Number of signatures to verify when receiving the record, including the votes of a QC.
+    pub fn num_signatures(&self) -> usize {
+        match self {
+            Record::QuorumCertificate(qc) => qc.votes.len() + 1,
+            _ => 1,
+        }
     }
 
+    /// Hash of the canonical encoding of the record, excluding its signature. The domain of the
+    /// hash is the kind of the record, e.g. "block", so that records of different kinds never
+    /// share hashes.
+    pub fn digest(&self, hasher: &RecordHasher) -> u64 {
+        hasher.hash_in_domain(self.kind(), &self.content_bytes())
+    }
+
+    /// Hash actually signed by the author of the record. Signatures are domain-separated by kind
+    /// of record, by network, and by epoch, so that a signature from another network or from a
+    /// previous epoch can never be replayed, even when the same authors are involved. Records do
+    /// not carry their network, and blocks do not carry their epoch, so these must be provided
+    /// by the caller. The domain of the hash is "signature", which no record digest uses.
+    pub fn signing_hash(&self, epoch_id: EpochId, chain_id: ChainId, hasher: &RecordHasher) -> u64 {
+        let mut bytes = Vec::new();
+        self.kind().encode(&mut bytes);
+        chain_id.encode(&mut bytes);
+        epoch_id.encode(&mut bytes);
+        self.digest(hasher).encode(&mut bytes);
+        hasher.hash_in_domain("signature", &bytes)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn make_block(
-        command: Command,
+        epoch_id: EpochId,
+        command: Option<Command>,
         time: NodeTime,
         previous_quorum_certificate_hash: QuorumCertificateHash,
         round: Round,
-        author: Author,
+        author: impl Into<PublicKey>,
+        chain_id: ChainId,
+        hasher: &RecordHasher,
     ) -> Record {
+        let key = author.into();
         let mut value = Record::Block(Block {
             command,
             time,
             previous_quorum_certificate_hash,
             round,
-            author,
+            author: key.author,
             signature: Signature(0),
         });
-        let hash = value.digest();
+        let hash = value.signing_hash(epoch_id, chain_id, hasher);
         match &mut value {
-            Record::Block(block) => block.signature = Signature::sign(hash, block.author),
+            Record::Block(block) => block.signature = Signature::sign_with_key(hash, key),
             _ => unreachable!(),
         }
         value
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn make_vote(
         epoch_id: EpochId,
         round: Round,
         certified_block_hash: BlockHash,
         state: State,
-        author: Author,
+        author: impl Into<PublicKey>,
         committed_state: Option<State>,
+        next_epoch: Option<EpochChange>,
+        time: NodeTime,
+        chain_id: ChainId,
+        hasher: &RecordHasher,
     ) -> Record {
+        let key = author.into();
         let mut value = Record::Vote(Vote {
             epoch_id,
             round,
             certified_block_hash,
             state,
-            author,
+            author: key.author,
             signature: Signature(0),
             committed_state,
+            next_epoch,
+            time,
         });
-        let hash = value.digest();
+        let hash = value.signing_hash(epoch_id, chain_id, hasher);
         match &mut value {
-            Record::Vote(vote) => vote.signature = Signature::sign(hash, vote.author),
+            Record::Vote(vote) => vote.signature = Signature::sign_with_key(hash, key),
             _ => unreachable!(),
         }
         value
@@ -202,32 +477,40 @@ impl Record {
         epoch_id: EpochId,
         round: Round,
         highest_certified_block_round: Round,
-        author: Author,
+        author: impl Into<PublicKey>,
+        chain_id: ChainId,
+        hasher: &RecordHasher,
     ) -> Record {
+        let key = author.into();
         let mut value = Record::Timeout(Timeout {
             epoch_id,
             round,
             highest_certified_block_round,
-            author,
+            author: key.author,
             signature: Signature(0),
         });
-        let hash = value.digest();
+        let hash = value.signing_hash(epoch_id, chain_id, hasher);
         match &mut value {
-            Record::Timeout(timeout) => timeout.signature = Signature::sign(hash, timeout.author),
+            Record::Timeout(timeout) => timeout.signature = Signature::sign_with_key(hash, key),
             _ => unreachable!(),
         }
         value
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn make_quorum_certificate(
         epoch_id: EpochId,
         round: Round,
         certified_block_hash: BlockHash,
         state: State,
-        votes: Vec<(Author, Signature)>,
+        votes: Vec<(Author, NodeTime, Signature)>,
         committed_state: Option<State>,
-        author: Author,
+        next_epoch: Option<EpochChange>,
+        author: impl Into<PublicKey>,
+        chain_id: ChainId,
+        hasher: &RecordHasher,
     ) -> Record {
+        let key = author.into();
         let mut value = Record::QuorumCertificate(QuorumCertificate {
             epoch_id,
             round,
@@ -235,17 +518,28 @@ impl Record {
             state,
             votes,
             committed_state,
-            author,
+            next_epoch,
+            author: key.author,
             signature: Signature(0),
         });
-        let hash = value.digest();
+        let hash = value.signing_hash(epoch_id, chain_id, hasher);
         match &mut value {
-            Record::QuorumCertificate(qc) => qc.signature = Signature::sign(hash, qc.author),
+            Record::QuorumCertificate(qc) => qc.signature = Signature::sign_with_key(hash, key),
             _ => unreachable!(),
         }
         value
     }
 
+    /// The author of the record and its signature, as checked by record stores.
+    pub fn signer(&self) -> (Author, Signature) {
+        match self {
+            Record::Block(x) => (x.author, x.signature),
+            Record::Vote(x) => (x.author, x.signature),
+            Record::QuorumCertificate(x) => (x.author, x.signature),
+            Record::Timeout(x) => (x.author, x.signature),
+        }
+    }
+
     #[cfg(test)]
     pub fn author(&self) -> Author {
         match self {
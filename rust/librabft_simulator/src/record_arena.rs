@@ -0,0 +1,214 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage of the blocks and QCs of a record store. Each record is allocated once, when it is
+//! inserted, and shared by all the clones of the store afterwards, e.g. the states explored by
+//! the model checker or the record stores kept for past epochs. Cloning a store thus copies
+//! pointers and slot indexes instead of commands and votes.
+//!
+//! With a backend, e.g. on disk, records are also saved when inserted, and only a bounded
+//! number of them stay in memory. Evicted records are read again from the backend when needed.
+
+use bft_simulator_runtime::base_types::Result;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+#[cfg(test)]
+#[path = "unit_tests/record_arena_tests.rs"]
+mod record_arena_tests;
+
+/// Storage outside of memory for the records of an arena.
+pub trait RecordBackend<H, R>: Debug + Send + Sync {
+    /// Save a record when it is inserted in the arena.
+    fn store(&self, hash: &H, record: &R) -> Result<()>;
+    /// Read a record saved by `store`.
+    fn load(&self, hash: &H) -> Result<R>;
+}
+
+/// Records in insertion order, with each hash interned as the index of the slot of its record.
+#[derive(Debug)]
+pub struct RecordArena<H, R> {
+    /// Hash and content of each record, by slot. Contents are missing while evicted.
+    slots: Vec<(H, OnceLock<Arc<R>>)>,
+    /// Slot of each hash.
+    index: HashMap<H, u32>,
+    /// Where records are saved to be evicted, if anywhere.
+    backend: Option<Arc<dyn RecordBackend<H, R>>>,
+    /// Maximal number of records kept in memory after an insertion, if there is a backend.
+    cache_capacity: usize,
+    /// Number of records in memory.
+    resident: AtomicUsize,
+    /// Next slot to evict: records are evicted in insertion order, oldest first.
+    eviction_cursor: usize,
+}
+
+impl<H, R> Default for RecordArena<H, R>
+where
+    H: Copy + Eq + Hash,
+{
+    fn default() -> Self {
+        RecordArena {
+            slots: Vec::new(),
+            index: HashMap::new(),
+            backend: None,
+            cache_capacity: usize::MAX,
+            resident: AtomicUsize::new(0),
+            eviction_cursor: 0,
+        }
+    }
+}
+
+impl<H, R> Clone for RecordArena<H, R>
+where
+    H: Clone,
+{
+    fn clone(&self) -> Self {
+        RecordArena {
+            slots: self.slots.clone(),
+            index: self.index.clone(),
+            backend: self.backend.clone(),
+            cache_capacity: self.cache_capacity,
+            resident: AtomicUsize::new(self.resident.load(Ordering::Relaxed)),
+            eviction_cursor: self.eviction_cursor,
+        }
+    }
+}
+
+impl<H, R> RecordArena<H, R>
+where
+    H: Copy + Eq + Hash,
+{
+    /// An empty arena that saves its records to `backend` and keeps at most `cache_capacity`
+    /// of them in memory after each insertion.
+    pub fn with_backend(backend: Arc<dyn RecordBackend<H, R>>, cache_capacity: usize) -> Self {
+        assert!(cache_capacity > 0, "Invalid cache capacity");
+        RecordArena {
+            backend: Some(backend),
+            cache_capacity,
+            ..RecordArena::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Number of records currently in memory.
+    pub fn resident(&self) -> usize {
+        self.resident.load(Ordering::Relaxed)
+    }
+
+    pub fn contains_key(&self, hash: &H) -> bool {
+        self.index.contains_key(hash)
+    }
+
+    /// The slot of a record, in insertion order. Slots are never reused, so they can stand for
+    /// hashes in the tables of the store.
+    pub fn slot(&self, hash: &H) -> Option<u32> {
+        self.index.get(hash).cloned()
+    }
+
+    pub fn get(&self, hash: &H) -> Option<&R> {
+        let slot = *self.index.get(hash)?;
+        self.get_slot(slot)
+    }
+
+    /// The record stored at a given slot, read from the backend if it was evicted, and then
+    /// kept in memory until the next insertion. Records that cannot be read are missing.
+    pub fn get_slot(&self, slot: u32) -> Option<&R> {
+        let (_, record) = &self.slots[slot as usize];
+        if let Some(record) = record.get() {
+            return Some(record);
+        }
+        let loaded = self.load_slot(slot as usize)?;
+        Some(record.get_or_init(|| {
+            self.resident.fetch_add(1, Ordering::Relaxed);
+            loaded
+        }))
+    }
+
+    /// The record stored at a given slot, shared with the arena if it is in memory, or read
+    /// from the backend otherwise, without keeping it in memory.
+    fn load_slot(&self, slot: usize) -> Option<Arc<R>> {
+        let (hash, record) = &self.slots[slot];
+        if let Some(record) = record.get() {
+            return Some(record.clone());
+        }
+        let backend = self
+            .backend
+            .as_ref()
+            .expect("Records are only evicted with a backend");
+        match backend.load(hash) {
+            Ok(record) => Some(Arc::new(record)),
+            Err(error) => {
+                error!("Failed to read an evicted record: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Store a record under the given hash, unless a record is already stored under it.
+    /// Return whether the record was inserted, or the error of the backend, in which case the
+    /// arena is unchanged.
+    pub fn insert(&mut self, hash: H, record: R) -> Result<bool> {
+        if self.index.contains_key(&hash) {
+            return Ok(false);
+        }
+        if let Some(backend) = &self.backend {
+            backend.store(&hash, &record)?;
+        }
+        self.index.insert(hash, self.slots.len() as u32);
+        self.slots.push((hash, OnceLock::from(Arc::new(record))));
+        *self.resident.get_mut() += 1;
+        self.evict();
+        Ok(true)
+    }
+
+    /// Whether records may be evicted from memory, i.e. whether the arena has a backend.
+    pub fn has_backend(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// Drop the oldest records in memory until the cache fits its capacity, except for the
+    /// latest one.
+    fn evict(&mut self) {
+        let resident = self.resident.get_mut();
+        while *resident > self.cache_capacity {
+            if self.eviction_cursor + 1 >= self.slots.len() {
+                self.eviction_cursor = 0;
+            }
+            if self.slots[self.eviction_cursor].1.take().is_some() {
+                *resident -= 1;
+            }
+            self.eviction_cursor += 1;
+        }
+    }
+
+    /// Hashes of the records, in insertion order.
+    pub fn keys(&self) -> impl Iterator<Item = &H> {
+        self.slots.iter().map(|(hash, _)| hash)
+    }
+
+    /// Records, in insertion order. Evicted records are read from the backend one at a time
+    /// and are not kept in memory. Records that cannot be read are skipped.
+    pub fn values(&self) -> impl Iterator<Item = Arc<R>> + '_ {
+        (0..self.slots.len()).filter_map(move |slot| self.load_slot(slot))
+    }
+
+    /// Records with their hashes, in insertion order, see `values`.
+    pub fn iter(&self) -> impl Iterator<Item = (&H, Arc<R>)> {
+        (0..self.slots.len())
+            .filter_map(move |slot| Some((&self.slots[slot].0, self.load_slot(slot)?)))
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fluent construction of records for tests and fuzzers. Unlike the `Record::make_*`
+//! constructors, every field has a default, so that callers only spell out what matters to
+//! them, and `unchecked()` keeps the signatures given by the caller to build invalid records.
+
+use super::*;
+use base_types::*;
+use hashing::HashFunction;
+use record::*;
+
+#[cfg(test)]
+#[path = "unit_tests/record_builder_tests.rs"]
+mod record_builder_tests;
+
+/// Builds a block, a vote, a QC, or a timeout. Fields default to epoch 0, round 1, an empty
+/// block at time 0, votes at time 1, zero hashes and states, no committed state, no votes, and
+/// the default network and hash function.
+///
+/// Setters of fields that the kind of record does not have are ignored, e.g. `command` on a
+/// vote, so that a fuzzer may apply the same sequence of setters to any kind of record.
+#[derive(Clone, Debug)]
+pub struct RecordBuilder {
+    epoch_id: EpochId,
+    chain_id: ChainId,
+    hash_function: HashFunction,
+    record: Record,
+    /// Authors whose votes are signed for the final content of the QC.
+    voters: Vec<Author>,
+    /// Time of the votes of `voters`.
+    voters_time: NodeTime,
+}
+
+impl RecordBuilder {
+    fn new(record: Record) -> Self {
+        RecordBuilder {
+            epoch_id: EpochId(0),
+            chain_id: ChainId::default(),
+            hash_function: HashFunction::SIMULATION,
+            record,
+            voters: Vec::new(),
+            voters_time: NodeTime(1),
+        }
+    }
+
+    pub fn block(author: Author) -> Self {
+        Self::new(Record::Block(Block {
+            command: None,
+            time: NodeTime(0),
+            previous_quorum_certificate_hash: QuorumCertificateHash(0),
+            round: Round(1),
+            author,
+            signature: Signature(0),
+        }))
+    }
+
+    pub fn vote(author: Author) -> Self {
+        Self::new(Record::Vote(Vote {
+            epoch_id: EpochId(0),
+            round: Round(1),
+            certified_block_hash: BlockHash(0),
+            state: State(0),
+            committed_state: None,
+            next_epoch: None,
+            time: NodeTime(1),
+            author,
+            signature: Signature(0),
+        }))
+    }
+
+    pub fn quorum_certificate(author: Author) -> Self {
+        Self::new(Record::QuorumCertificate(QuorumCertificate {
+            epoch_id: EpochId(0),
+            round: Round(1),
+            certified_block_hash: BlockHash(0),
+            state: State(0),
+            committed_state: None,
+            next_epoch: None,
+            votes: Vec::new(),
+            author,
+            signature: Signature(0),
+        }))
+    }
+
+    pub fn timeout(author: Author) -> Self {
+        Self::new(Record::Timeout(Timeout {
+            epoch_id: EpochId(0),
+            round: Round(1),
+            highest_certified_block_round: Round(0),
+            author,
+            signature: Signature(0),
+        }))
+    }
+
+    /// Epoch of the record. Blocks do not carry it but their signature depends on it.
+    pub fn epoch(&mut self, epoch_id: EpochId) -> &mut Self {
+        self.epoch_id = epoch_id;
+        match &mut self.record {
+            Record::Block(_) => (),
+            Record::Vote(vote) => vote.epoch_id = epoch_id,
+            Record::QuorumCertificate(qc) => qc.epoch_id = epoch_id,
+            Record::Timeout(timeout) => timeout.epoch_id = epoch_id,
+        }
+        self
+    }
+
+    /// Network of the record, which signatures depend on.
+    pub fn chain_id(&mut self, chain_id: ChainId) -> &mut Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Hash function of the signatures, which must match the one of the receiving store.
+    pub fn hash_function(&mut self, hash_function: HashFunction) -> &mut Self {
+        self.hash_function = hash_function;
+        self
+    }
+
+    pub fn round(&mut self, round: Round) -> &mut Self {
+        match &mut self.record {
+            Record::Block(block) => block.round = round,
+            Record::Vote(vote) => vote.round = round,
+            Record::QuorumCertificate(qc) => qc.round = round,
+            Record::Timeout(timeout) => timeout.round = round,
+        }
+        self
+    }
+
+    pub fn author(&mut self, author: Author) -> &mut Self {
+        match &mut self.record {
+            Record::Block(block) => block.author = author,
+            Record::Vote(vote) => vote.author = author,
+            Record::QuorumCertificate(qc) => qc.author = author,
+            Record::Timeout(timeout) => timeout.author = author,
+        }
+        self
+    }
+
+    pub fn command(&mut self, command: Command) -> &mut Self {
+        if let Record::Block(block) = &mut self.record {
+            block.command = Some(command);
+        }
+        self
+    }
+
+    /// Time of a block or of a vote, or time of the votes of a QC added with `voters`.
+    pub fn time(&mut self, time: NodeTime) -> &mut Self {
+        match &mut self.record {
+            Record::Block(block) => block.time = time,
+            Record::Vote(vote) => vote.time = time,
+            Record::QuorumCertificate(_) => self.voters_time = time,
+            Record::Timeout(_) => (),
+        }
+        self
+    }
+
+    pub fn previous_quorum_certificate(&mut self, hash: QuorumCertificateHash) -> &mut Self {
+        if let Record::Block(block) = &mut self.record {
+            block.previous_quorum_certificate_hash = hash;
+        }
+        self
+    }
+
+    pub fn certified_block(&mut self, hash: BlockHash) -> &mut Self {
+        match &mut self.record {
+            Record::Vote(vote) => vote.certified_block_hash = hash,
+            Record::QuorumCertificate(qc) => qc.certified_block_hash = hash,
+            _ => (),
+        }
+        self
+    }
+
+    pub fn state(&mut self, state: State) -> &mut Self {
+        match &mut self.record {
+            Record::Vote(vote) => vote.state = state,
+            Record::QuorumCertificate(qc) => qc.state = state,
+            _ => (),
+        }
+        self
+    }
+
+    pub fn committed_state(&mut self, committed_state: Option<State>) -> &mut Self {
+        match &mut self.record {
+            Record::Vote(vote) => vote.committed_state = committed_state,
+            Record::QuorumCertificate(qc) => qc.committed_state = committed_state,
+            _ => (),
+        }
+        self
+    }
+
+    pub fn next_epoch(&mut self, next_epoch: Option<EpochChange>) -> &mut Self {
+        match &mut self.record {
+            Record::Vote(vote) => vote.next_epoch = next_epoch,
+            Record::QuorumCertificate(qc) => qc.next_epoch = next_epoch,
+            _ => (),
+        }
+        self
+    }
+
+    pub fn highest_certified_block_round(&mut self, round: Round) -> &mut Self {
+        if let Record::Timeout(timeout) = &mut self.record {
+            timeout.highest_certified_block_round = round;
+        }
+        self
+    }
+
+    /// Add votes from the given authors to a QC. Their signatures are computed when the record
+    /// is built, so that they match its final content, even with `unchecked()`.
+    pub fn voters<I: IntoIterator<Item = Author>>(&mut self, authors: I) -> &mut Self {
+        self.voters.extend(authors);
+        self
+    }
+
+    /// Add votes to a QC with arbitrary signatures, e.g. to test invalid or duplicate votes.
+    pub fn votes<I: IntoIterator<Item = (Author, NodeTime, Signature)>>(
+        &mut self,
+        votes: I,
+    ) -> &mut Self {
+        if let Record::QuorumCertificate(qc) = &mut self.record {
+            qc.votes.extend(votes);
+        }
+        self
+    }
+
+    /// Signature of the record. Only kept by `unchecked()`: `build()` always signs the record.
+    pub fn signature(&mut self, signature: Signature) -> &mut Self {
+        set_signature(&mut self.record, signature);
+        self
+    }
+
+    /// The record, signed by its author for the given epoch.
+    pub fn build(&self) -> Record {
+        let mut record = self.unchecked();
+        let signature = Signature::sign(
+            record.signing_hash(self.epoch_id, self.chain_id, &self.hash_function),
+            record.signer().0,
+        );
+        set_signature(&mut record, signature);
+        record
+    }
+
+    /// The record with the signature given by the caller, `Signature(0)` by default. Only the
+    /// votes added with `voters` are signed.
+    pub fn unchecked(&self) -> Record {
+        let mut record = self.record.clone();
+        if let Record::QuorumCertificate(qc) = &mut record {
+            let votes = self
+                .voters
+                .iter()
+                .map(|author| {
+                    let hash = qc.vote_signing_hash(
+                        *author,
+                        self.voters_time,
+                        self.chain_id,
+                        &self.hash_function,
+                    );
+                    (*author, self.voters_time, Signature::sign(hash, *author))
+                })
+                .collect::<Vec<_>>();
+            qc.votes.extend(votes);
+        }
+        record
+    }
+}
+
+fn set_signature(record: &mut Record, signature: Signature) {
+    match record {
+        Record::Block(block) => block.signature = signature,
+        Record::Vote(vote) => vote.signature = signature,
+        Record::QuorumCertificate(qc) => qc.signature = signature,
+        Record::Timeout(timeout) => timeout.signature = signature,
+    }
+}
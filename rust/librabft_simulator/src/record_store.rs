@@ -3,12 +3,25 @@
 
 use super::*;
 use base_types::*;
-use pacemaker::{Pacemaker, PacemakerState};
+use commit_rule::{CommitRule, FastPath, LibraBftCommitRule};
+use committee::VotingCommittee;
+#[cfg(feature = "disk_store")]
+use disk_store::DiskStorage;
+use hashing::HashFunction;
+use introspection::RecordStoreSnapshot;
+use node_error::{NodeError, NodeResult, StorageError};
+use pacemaker::Pacemaker;
+use qc_cache::{QuorumCertificateCache, QuorumCertificateCacheStats};
+use rayon::prelude::*;
+use reconciliation::InvertibleBloomLookupTable;
 use record::*;
+use record_arena::RecordArena;
 use smr_context::SMRContext;
 use std::{
-    collections::{BTreeSet, HashMap},
-    fmt::Debug,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{Debug, Write},
+    sync::Arc,
 };
 
 #[cfg(test)]
@@ -16,7 +29,7 @@ use std::{
 mod record_store_tests;
 
 // -- BEGIN FILE record_store --
-pub trait RecordStore: Debug {
+pub trait RecordStore: Debug + ChainQueries + RecordQueries {
     /// Return the hash of a QC at the highest round, or the initial hash.
     fn highest_quorum_certificate_hash(&self) -> QuorumCertificateHash;
     /// Query the round of the highest QC.
@@ -32,9 +45,12 @@ pub trait RecordStore: Debug {
     /// Current round as seen by the record store.
     fn current_round(&self) -> Round;
 
-    /// Iterate on the committed blocks starting after the round `after_round` and ending with the
-    /// highest commit known so far.
-    fn committed_states_after(&self, after_round: Round) -> Vec<(Round, State)>;
+    /// Same as `committed_blocks` but only return the rounds and copies of the states.
+    fn committed_states_after(&self, after_round: Round) -> Vec<(Round, State)> {
+        self.committed_blocks(after_round)
+            .map(|commit| (commit.round, commit.state.clone()))
+            .collect()
+    }
 
     /// Access the block proposed by the leader chosen by the Pacemaker (if any).
     fn proposed_block(&self, pacemaker: &Pacemaker) -> Option<(BlockHash, Round, Author)>;
@@ -42,29 +58,37 @@ pub trait RecordStore: Debug {
     fn has_timeout(&self, author: Author, round: Round) -> bool;
 
     /// Create a timeout.
-    fn create_timeout(&mut self, author: Author, round: Round, smr_context: &mut SMRContext);
-    /// Fetch a command from mempool and propose a block.
+    fn create_timeout(
+        &mut self,
+        author: Author,
+        round: Round,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<()>;
+    /// Propose a block containing the given command, or an empty block.
     fn propose_block(
         &mut self,
         local_author: Author,
         previous_qc_hash: QuorumCertificateHash,
         clock: NodeTime,
+        command: Option<Command>,
         smr_context: &mut SMRContext,
-    );
-    /// Execute the command contained in a block and vote for the resulting state.
-    /// Return false if the execution failed.
+    ) -> NodeResult<()>;
+    /// Execute the command contained in a block and vote for the resulting state at the given
+    /// time.
     fn create_vote(
         &mut self,
         local_author: Author,
         block_hash: BlockHash,
+        clock: NodeTime,
         smr_context: &mut SMRContext,
-    ) -> bool;
-    /// Try to create a QC for the last block that we have proposed.
+    ) -> NodeResult<()>;
+    /// Try to create a QC for the last block that we have proposed. Return whether a QC was
+    /// created.
     fn check_for_new_quorum_certificate(
         &mut self,
         local_author: Author,
         smr_context: &mut SMRContext,
-    ) -> bool;
+    ) -> NodeResult<bool>;
 
     /// Compute the previous round and the second previous round of a block.
     fn previous_round(&self, block_hash: BlockHash) -> Round;
@@ -78,22 +102,250 @@ pub trait RecordStore: Debug {
     fn block(&self, block_hash: BlockHash) -> Option<&Block>;
     fn known_quorum_certificate_rounds(&self) -> BTreeSet<Round>;
     fn unknown_records(&self, known_qc_rounds: BTreeSet<Round>) -> Vec<Record>;
-    fn insert_network_record(&mut self, record: Record, smr_context: &mut SMRContext);
+    /// Verify and insert a record received from the network, or explain why it was rejected.
+    fn insert_network_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted>;
 }
 // -- END FILE --
 
+/// Queries on the chains of blocks and QCs of a record store, beyond the ones of the
+/// protocol itself, e.g. to deliver commits, roll back, and apply back pressure.
+pub trait ChainQueries {
+    /// Query the highest QC round reported by the timeouts of the highest TC.
+    fn highest_timeout_certificate_qc_round(&self) -> Round;
+    /// The rule deciding when blocks are committed.
+    fn commit_rule(&self) -> &CommitRule;
+    /// Iterate on the committed blocks starting after the round `after_round` and ending with the
+    /// highest commit known so far. States are borrowed from the store.
+    fn committed_blocks(&self, after_round: Round) -> CommittedBlocks<'_>;
+    /// Time of a block agreed by a quorum: the median time of the votes of the QC that it
+    /// extends, weighted by voting rights, or the time of the block if it extends the initial
+    /// hash of the epoch. It only depends on the content of the block.
+    fn quorum_time(&self, block_hash: BlockHash) -> NodeTime;
+    /// Blocks that neither are ancestors of the given block nor extend it, by increasing round.
+    fn blocks_conflicting_with(&self, block_hash: BlockHash) -> Vec<BlockHash>;
+    /// Number of certified blocks that are not committed yet in the chain ending with the given
+    /// QC.
+    fn uncommitted_depth(&self, qc_hash: QuorumCertificateHash) -> usize;
+    /// The oldest QC of at least the given round in the chain ending with the given QC.
+    fn oldest_quorum_certificate_hash(
+        &self,
+        qc_hash: QuorumCertificateHash,
+        min_round: Round,
+    ) -> QuorumCertificateHash;
+    /// Execution state after the parent of a block, or the initial state of the epoch.
+    fn previous_state(&self, block_hash: BlockHash) -> &State;
+    /// Same as `pick_author` but skipping the excluded authors, unless all of them are.
+    fn pick_author_excluding(&self, seed: u64, excluded: &BTreeSet<Author>) -> Author;
+}
+
+/// Queries on the records of a record store, e.g. for data sync and monitoring.
+pub trait RecordQueries {
+    /// Access a QC by its hash.
+    fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate>;
+    /// Summarize the blocks and QCs that we know, for peers to send us only what we miss,
+    /// optionally with a sketch of the given number of cells.
+    fn record_summary(&self, sketch_cells: Option<usize>) -> RecordSummary;
+    /// Blocks and QCs missing from the summary of a peer, by increasing round, followed by
+    /// timeouts.
+    fn records_unknown_to(&self, peer_summary: &RecordSummary) -> Vec<Record>;
+    /// Whether the record is already known.
+    fn has_record(&self, record: &Record) -> bool;
+    /// Blocks proposed by the given author, by increasing round.
+    fn blocks_proposed_by(&self, author: Author) -> Vec<BlockHash>;
+    /// Rounds for which at least one QC is known, on any branch.
+    fn certified_rounds(&self) -> BTreeSet<Round>;
+    /// Blocks known at the given round, on any branch.
+    fn blocks_at(&self, round: Round) -> Vec<BlockHash>;
+    /// QCs known at the given round, on any branch.
+    fn quorum_certificates_at(&self, round: Round) -> Vec<QuorumCertificateHash>;
+    /// Render the tree of blocks and QCs in Graphviz DOT format.
+    fn export_dot(&self) -> String;
+}
+
+/// A block committed by the highest commit of a record store, with the state certified for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommittedBlock<'a> {
+    pub round: Round,
+    pub block_hash: BlockHash,
+    pub state: &'a State,
+}
+
+/// Committed blocks by increasing round, see `ChainQueries::committed_blocks`.
+#[derive(Clone, Debug)]
+pub struct CommittedBlocks<'a> {
+    /// QCs of the committed blocks, by decreasing round.
+    quorum_certificates: Vec<&'a QuorumCertificate>,
+}
+
+impl<'a> Iterator for CommittedBlocks<'a> {
+    type Item = CommittedBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let qc = self.quorum_certificates.pop()?;
+        Some(CommittedBlock {
+            round: qc.round,
+            block_hash: qc.certified_block_hash,
+            state: &qc.state,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.quorum_certificates.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for CommittedBlocks<'a> {}
+
+/// Outcome of the insertion of a record received from the network.
+pub type InsertResult<T> = std::result::Result<T, RejectReason>;
+
+/// A record that was verified and inserted.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Inserted {
+    /// Digest of the record.
+    pub digest: u64,
+}
+
+/// Why a record received from the network was not inserted.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum RejectReason {
+    /// The signature of the record, or of a vote of a QC, is invalid.
+    BadSignature,
+    /// The author of the record, or of a vote of a QC, has no voting rights in the epoch, e.g.
+    /// a validator that was removed, or the author of a vote is not in the committee of its
+    /// round.
+    UnknownAuthor,
+    /// The QC extended by a block, or the block certified by a vote or a QC, is unknown.
+    UnknownParent,
+    /// A vote or a timeout for a past round.
+    StaleRound,
+    /// A vote or a timeout for a future round.
+    FutureRound,
+    /// The record is known already, or its author already has a vote or a timeout at the
+    /// current round.
+    Duplicate,
+    /// The record belongs to a later epoch.
+    FutureEpoch,
+    /// The record belongs to an epoch that was stopped.
+    PastEpoch,
+    /// A block or a vote whose time is too far ahead of the local clock.
+    FutureTime,
+    /// The block certified by a QC could not be executed.
+    NotExecuted,
+    /// Execution gave a different state than the one certified by a QC.
+    StateMismatch,
+    /// The record could not be saved by the storage of the record store, e.g. on disk.
+    StorageFailure,
+    /// Any other violation of the protocol.
+    Invalid(&'static str),
+}
+
+impl RejectReason {
+    /// Name of the reason in metrics.
+    pub fn name(self) -> &'static str {
+        match self {
+            RejectReason::BadSignature => "bad_signature",
+            RejectReason::UnknownAuthor => "unknown_author",
+            RejectReason::UnknownParent => "unknown_parent",
+            RejectReason::StaleRound => "stale_round",
+            RejectReason::FutureRound => "future_round",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::FutureEpoch => "future_epoch",
+            RejectReason::PastEpoch => "past_epoch",
+            RejectReason::FutureTime => "future_time",
+            RejectReason::NotExecuted => "not_executed",
+            RejectReason::StateMismatch => "state_mismatch",
+            RejectReason::StorageFailure => "storage_failure",
+            RejectReason::Invalid(_) => "invalid",
+        }
+    }
+}
+
+fn check(condition: bool, reason: RejectReason) -> InsertResult<()> {
+    if condition {
+        Ok(())
+    } else {
+        Err(reason)
+    }
+}
+
+/// Whether a vote or a QC of the given epoch may announce `next_epoch`: only together with a
+/// committed state, and only for a later epoch.
+fn check_next_epoch(
+    epoch_id: EpochId,
+    committed_state: &Option<State>,
+    next_epoch: Option<EpochChange>,
+) -> InsertResult<()> {
+    match next_epoch {
+        Some(next_epoch) => check(
+            committed_state.is_some() && next_epoch.epoch_id > epoch_id,
+            RejectReason::Invalid(
+                "Only the committed state of a vote or a QC can announce a later epoch",
+            ),
+        ),
+        None => Ok(()),
+    }
+}
+
+fn check_signature(signature: &Signature, hash: u64, key: PublicKey) -> InsertResult<()> {
+    signature
+        .check_with_key(hash, key)
+        .map_err(|_| RejectReason::BadSignature)
+}
+
+/// Check the signature of a record of the given epoch and, for QCs, the signatures of their
+/// votes, whose authors must be validators. This only depends on the epoch, not on the other
+/// records, so that records can be checked in any order, e.g. in parallel.
+fn check_signatures(
+    record: &Record,
+    epoch_id: EpochId,
+    chain_id: ChainId,
+    hash_function: &HashFunction,
+    configuration: &EpochConfiguration,
+) -> InsertResult<()> {
+    if let Record::QuorumCertificate(qc) = record {
+        for (author, time, signature) in &qc.votes {
+            check(
+                configuration.weight(author) > 0,
+                RejectReason::UnknownAuthor,
+            )?;
+            check_signature(
+                signature,
+                qc.vote_signing_hash(*author, *time, chain_id, hash_function),
+                configuration.public_key(*author),
+            )?;
+        }
+    }
+    let (author, signature) = record.signer();
+    check_signature(
+        &signature,
+        record.signing_hash(epoch_id, chain_id, hash_function),
+        configuration.public_key(author),
+    )
+}
+
 // -- BEGIN FILE record_store_state --
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RecordStoreState {
     /// Epoch initialization.
     epoch_id: EpochId,
     configuration: EpochConfiguration,
     initial_hash: QuorumCertificateHash,
     initial_state: State,
-    /// Storage of verified blocks and QCs.
-    blocks: HashMap<BlockHash, Block>,
-    quorum_certificates: HashMap<QuorumCertificateHash, QuorumCertificate>,
-    current_proposed_block: Option<BlockHash>,
+    settings: StoreSettings,
+    extensions: StoreExtensions,
+    /// Storage of verified blocks and QCs, shared with the clones of the store.
+    blocks: RecordArena<BlockHash, Block>,
+    quorum_certificates: RecordArena<QuorumCertificateHash, QuorumCertificate>,
+    indexes: RecordIndexes,
+    /// First block proposed by each author at the current round. The pacemaker decides which
+    /// author is the leader.
+    current_proposals: HashMap<Author, BlockHash>,
     /// Computed round values.
     highest_quorum_certificate_round: Round,
     highest_quorum_certificate_hash: QuorumCertificateHash,
@@ -112,7 +364,7 @@ pub struct RecordStoreState {
 }
 
 /// Counting votes for a proposed block and its execution state.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum ElectionState {
     Ongoing {
         ballot: HashMap<(BlockHash, State), usize>,
@@ -125,6 +377,82 @@ enum ElectionState {
 }
 // -- END FILE --
 
+/// Rules and parameters of the epoch, set once when the store is created.
+#[derive(Clone, Debug)]
+struct StoreSettings {
+    commit_rule: Arc<CommitRule>,
+    fast_path: Option<FastPath>,
+    /// Voters of each round, if only a committee of the validators votes.
+    committee: Option<VotingCommittee>,
+    /// Hash function of the records, the same for all the replicas.
+    hash_function: HashFunction,
+    /// Network of the replicas, signed by all the records.
+    chain_id: ChainId,
+    /// How far ahead of the local clock the time of a block or a vote may be, if bounded.
+    max_clock_drift: Option<Duration>,
+    /// Lower bound on the times of the blocks extending the initial hash, if any: the quorum
+    /// time of the last block of the previous epoch.
+    initial_time: Option<NodeTime>,
+}
+
+/// Local state of the store, besides the records and the computed round values.
+#[derive(Clone, Debug)]
+struct StoreExtensions {
+    /// Voting rights of the committee of the current round, if any.
+    current_committee: Option<EpochConfiguration>,
+    /// Latest known time of the local clock, if any.
+    local_clock: Option<NodeTime>,
+    /// QCs whose signatures were verified already, if they are cached.
+    qc_cache: Option<QuorumCertificateCache>,
+    /// Database holding the blocks and QCs of the store, if they are kept on disk.
+    #[cfg(feature = "disk_store")]
+    disk_storage: Option<DiskStorage>,
+    /// Number of QCs in the chain of the highest commit, shorter on the fast path.
+    highest_commit_chain_length: usize,
+    /// Rounds of the blocks committed on the fast path.
+    fast_committed_rounds: BTreeSet<Round>,
+}
+
+/// Indexes of the verified blocks and QCs, updated when records are inserted.
+#[derive(Clone, Debug, Default)]
+struct RecordIndexes {
+    /// Slot of the block certified by the QC that each block extends, by slot of the block.
+    block_parents: Vec<Option<u32>>,
+    /// Round of each block, by slot, so that walking the tree of blocks does not read them.
+    block_rounds: Vec<Round>,
+    /// Indexes of the blocks and QCs by round, and of the blocks by author.
+    round_index: BTreeMap<Round, RoundRecords>,
+    author_index: HashMap<Author, BTreeSet<(Round, BlockHash)>>,
+}
+
+/// Blocks and QCs known at a given round.
+#[derive(Clone, Debug, Default)]
+struct RoundRecords {
+    blocks: Vec<BlockHash>,
+    quorum_certificates: Vec<QuorumCertificateHash>,
+}
+
+impl RoundRecords {
+    /// Digest of the records, regardless of their order.
+    fn digest(&self) -> u64 {
+        let blocks = self.blocks.iter().map(|hash| hash.0);
+        let qcs = self.quorum_certificates.iter().map(|hash| hash.0);
+        blocks.chain(qcs).fold(0, |digest, hash| digest ^ hash)
+    }
+}
+
+/// Compact description of the blocks and QCs known to a node in an epoch.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct RecordSummary {
+    /// Round of the highest commit. Records of the committed chain are known up to this round.
+    pub highest_committed_round: Round,
+    /// For each higher round with known blocks or QCs, a digest of their hashes.
+    pub round_digests: BTreeMap<Round, u64>,
+    /// Sketch of the hashes of the same blocks and QCs, from which peers may decode exactly
+    /// which of their records are missing.
+    pub sketch: Option<InvertibleBloomLookupTable>,
+}
+
 struct BackwardQuorumCertificateIterator<'a> {
     store: &'a RecordStoreState,
     current_hash: QuorumCertificateHash,
@@ -152,7 +480,7 @@ impl<'a> Iterator for BackwardQuorumCertificateIterator<'a> {
         let qc = self.store.quorum_certificate(self.current_hash).unwrap();
         let block = self.store.block(qc.certified_block_hash).unwrap();
         self.current_hash = block.previous_quorum_certificate_hash;
-        return Some(qc);
+        Some(qc)
     }
 }
 
@@ -169,9 +497,28 @@ impl RecordStoreState {
             initial_hash,
             initial_state,
             epoch_id,
-            blocks: HashMap::new(),
-            quorum_certificates: HashMap::new(),
-            current_proposed_block: None,
+            settings: StoreSettings {
+                commit_rule: Arc::new(LibraBftCommitRule),
+                fast_path: None,
+                committee: None,
+                hash_function: HashFunction::SIMULATION,
+                chain_id: ChainId::default(),
+                max_clock_drift: None,
+                initial_time: None,
+            },
+            extensions: StoreExtensions {
+                current_committee: None,
+                local_clock: None,
+                qc_cache: None,
+                #[cfg(feature = "disk_store")]
+                disk_storage: None,
+                highest_commit_chain_length: 1,
+                fast_committed_rounds: BTreeSet::new(),
+            },
+            blocks: RecordArena::default(),
+            quorum_certificates: RecordArena::default(),
+            indexes: RecordIndexes::default(),
+            current_proposals: HashMap::new(),
             highest_quorum_certificate_round: Round(0),
             highest_quorum_certificate_hash: initial_hash,
             highest_timeout_certificate_round: Round(0),
@@ -188,6 +535,418 @@ impl RecordStoreState {
         }
     }
 
+    pub fn epoch_id(&self) -> EpochId {
+        self.epoch_id
+    }
+
+    pub fn configuration(&self) -> &EpochConfiguration {
+        &self.configuration
+    }
+
+    /// The key signing the records of the author in this epoch.
+    pub fn public_key(&self, author: Author) -> PublicKey {
+        self.configuration.public_key(author)
+    }
+
+    /// Keep the blocks and QCs of the store in the given database, with only the most recent
+    /// ones in memory. Must be called before any record is inserted.
+    #[cfg(feature = "disk_store")]
+    pub fn set_disk_storage(&mut self, storage: DiskStorage) -> Result<()> {
+        ensure!(
+            self.blocks.is_empty() && self.quorum_certificates.is_empty(),
+            "Records are already in memory."
+        );
+        let blocks = storage.records::<Block>(self.epoch_id)?;
+        let quorum_certificates = storage.records::<QuorumCertificate>(self.epoch_id)?;
+        self.blocks = RecordArena::with_backend(Arc::new(blocks), storage.cache_capacity());
+        self.quorum_certificates =
+            RecordArena::with_backend(Arc::new(quorum_certificates), storage.cache_capacity());
+        self.extensions.disk_storage = Some(storage);
+        Ok(())
+    }
+
+    #[cfg(feature = "disk_store")]
+    pub fn disk_storage(&self) -> Option<&DiskStorage> {
+        self.extensions.disk_storage.as_ref()
+    }
+
+    /// Number of blocks and QCs currently in memory.
+    pub fn resident_records(&self) -> usize {
+        self.blocks.resident() + self.quorum_certificates.resident()
+    }
+
+    pub fn set_commit_rule(&mut self, commit_rule: Arc<CommitRule>) {
+        self.settings.commit_rule = commit_rule;
+    }
+
+    pub fn shared_commit_rule(&self) -> Arc<CommitRule> {
+        self.settings.commit_rule.clone()
+    }
+
+    pub fn set_fast_path(&mut self, fast_path: FastPath) {
+        self.settings.fast_path = Some(fast_path);
+    }
+
+    pub fn fast_path(&self) -> Option<FastPath> {
+        self.settings.fast_path
+    }
+
+    /// Only let a committee of the validators vote at each round.
+    pub fn set_committee(&mut self, committee: VotingCommittee) {
+        self.extensions.current_committee =
+            Some(committee.select(&self.configuration, self.epoch_id, self.current_round));
+        self.settings.committee = Some(committee);
+    }
+
+    pub fn committee(&self) -> Option<VotingCommittee> {
+        self.settings.committee
+    }
+
+    /// Voting rights of the votes and QCs of the given round: the ones of its committee, if
+    /// any, or else the ones of the epoch.
+    pub fn voting_configuration(&self, round: Round) -> Cow<'_, EpochConfiguration> {
+        match (&self.settings.committee, &self.extensions.current_committee) {
+            (None, _) => Cow::Borrowed(&self.configuration),
+            (Some(_), Some(current)) if round == self.current_round => Cow::Borrowed(current),
+            (Some(committee), _) => {
+                Cow::Owned(committee.select(&self.configuration, self.epoch_id, round))
+            }
+        }
+    }
+
+    /// Whether the author may vote at the given round.
+    pub fn is_voter(&self, author: Author, round: Round) -> bool {
+        self.voting_configuration(round).weight(&author) > 0
+    }
+
+    /// Hash the records of the store with the given function. Must be called before any record
+    /// is created or received.
+    pub fn set_hash_function(&mut self, hash_function: HashFunction) {
+        assert!(
+            self.blocks.is_empty() && self.quorum_certificates.is_empty(),
+            "Records are already hashed"
+        );
+        self.settings.hash_function = hash_function;
+    }
+
+    pub fn hash_function(&self) -> HashFunction {
+        self.settings.hash_function
+    }
+
+    /// Sign and verify records for the given network. Must be called before any record is
+    /// created or received.
+    pub fn set_chain_id(&mut self, chain_id: ChainId) {
+        assert!(
+            self.blocks.is_empty() && self.quorum_certificates.is_empty(),
+            "Records are already signed"
+        );
+        self.settings.chain_id = chain_id;
+    }
+
+    pub fn chain_id(&self) -> ChainId {
+        self.settings.chain_id
+    }
+
+    pub fn set_max_clock_drift(&mut self, max_clock_drift: Duration) {
+        self.settings.max_clock_drift = Some(max_clock_drift);
+    }
+
+    pub fn max_clock_drift(&self) -> Option<Duration> {
+        self.settings.max_clock_drift
+    }
+
+    /// Skip the signatures of the QCs found in the given cache, and cache the QCs verified
+    /// from now on.
+    pub fn set_quorum_certificate_cache(&mut self, qc_cache: QuorumCertificateCache) {
+        self.extensions.qc_cache = Some(qc_cache);
+    }
+
+    /// Hand over the cache of verified QCs, e.g. to the store of the next epoch.
+    pub fn take_quorum_certificate_cache(&mut self) -> Option<QuorumCertificateCache> {
+        self.extensions.qc_cache.take()
+    }
+
+    pub fn quorum_certificate_cache_stats(&self) -> Option<&QuorumCertificateCacheStats> {
+        self.extensions
+            .qc_cache
+            .as_ref()
+            .map(QuorumCertificateCache::stats)
+    }
+
+    /// Record the current time of the local clock, against which the times of blocks and
+    /// votes are checked.
+    pub fn update_clock(&mut self, clock: NodeTime) {
+        self.extensions.local_clock = std::cmp::max(self.extensions.local_clock, Some(clock));
+    }
+
+    /// Verify and insert a block or a QC of this epoch after the node moved on to the next
+    /// one, so that peers still in this epoch can verify it and sync from us. Blocks are not
+    /// executed any more, hence the states of QCs are not checked. Votes and timeouts are
+    /// rejected, since this store never creates QCs nor timeout certificates again.
+    pub fn insert_late_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted> {
+        if let Record::Vote(_) | Record::Timeout(_) = record {
+            return Err(RejectReason::PastEpoch);
+        }
+        if let Some(reason) = self.outdated(&record) {
+            return Err(reason);
+        }
+        self.try_insert_network_record(record, smr_context, false, false)
+    }
+
+    pub fn local_clock(&self) -> Option<NodeTime> {
+        self.extensions.local_clock
+    }
+
+    /// Require the blocks extending the initial hash to be no earlier than the given time.
+    pub fn set_initial_time(&mut self, initial_time: NodeTime) {
+        self.settings.initial_time = Some(initial_time);
+    }
+
+    /// Lowest time allowed for a block extending the given QC, if any.
+    fn min_block_time(&self, previous_qc_hash: QuorumCertificateHash) -> Option<NodeTime> {
+        match self.quorum_certificate(previous_qc_hash) {
+            Some(qc) => Some(qc.quorum_time(&self.configuration)),
+            None => self.settings.initial_time,
+        }
+    }
+
+    /// Reject the times too far ahead of the local clock, if bounded.
+    fn check_time(&self, time: NodeTime) -> InsertResult<()> {
+        if let (Some(max_clock_drift), Some(local_clock)) =
+            (self.settings.max_clock_drift, self.extensions.local_clock)
+        {
+            check(
+                time.0 <= local_clock.0.saturating_add(max_clock_drift),
+                RejectReason::FutureTime,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Read-only copy of the status of the store, for monitoring.
+    pub fn snapshot(&self) -> RecordStoreSnapshot {
+        let mut current_voters: Vec<_> = self.current_votes.keys().cloned().collect();
+        current_voters.sort();
+        let mut current_timeout_authors: Vec<_> = self.current_timeouts.keys().cloned().collect();
+        current_timeout_authors.sort();
+        RecordStoreSnapshot {
+            epoch_id: self.epoch_id,
+            current_round: self.current_round,
+            highest_quorum_certificate_round: self.highest_quorum_certificate_round,
+            highest_quorum_certificate_hash: self.highest_quorum_certificate_hash,
+            highest_timeout_certificate_round: self.highest_timeout_certificate_round,
+            highest_committed_round: self.highest_committed_round,
+            num_blocks: self.blocks.len(),
+            num_quorum_certificates: self.quorum_certificates.len(),
+            current_voters,
+            current_timeout_authors,
+        }
+    }
+
+    /// The QCs of the highest certified chain that announce the given epoch change, highest
+    /// first. A QC committing a descendant of the last state of the epoch announces it too.
+    pub fn certificates_announcing(&self, next_epoch: EpochChange) -> Vec<&QuorumCertificate> {
+        BackwardQuorumCertificateIterator::new(self, self.highest_quorum_certificate_hash)
+            .filter(|qc| qc.next_epoch == Some(next_epoch))
+            .collect()
+    }
+
+    /// Whether the block at the given round was committed on the fast path.
+    pub fn is_fast_commit(&self, round: Round) -> bool {
+        self.extensions.fast_committed_rounds.contains(&round)
+    }
+
+    /// Weight of the votes for the block that won the election of the current round, if it was
+    /// proposed by the given author and its QC is not created yet.
+    pub fn won_election_weight(&self, author: Author) -> Option<usize> {
+        match &self.current_election {
+            ElectionState::Won { block_hash, state }
+                if self.block(*block_hash).unwrap().author == author =>
+            {
+                let voters = self
+                    .current_votes
+                    .values()
+                    .filter(|vote| vote.certified_block_hash == *block_hash && vote.state == *state)
+                    .map(|vote| &vote.author);
+                Some(
+                    self.voting_configuration(self.current_round)
+                        .count_votes(voters),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// Minimal weight of the votes of a QC of the current round ending a commit chain on the
+    /// fast path, if enabled.
+    pub fn fast_quorum_threshold(&self) -> Option<usize> {
+        self.fast_quorum_threshold_at(self.current_round)
+    }
+
+    fn fast_quorum_threshold_at(&self, round: Round) -> Option<usize> {
+        let fast_path = self.settings.fast_path?;
+        let configuration = self.voting_configuration(round);
+        Some(std::cmp::max(
+            configuration
+                .total_votes()
+                .saturating_sub(fast_path.missing_votes),
+            configuration.quorum_threshold(),
+        ))
+    }
+
+    /// Check the invariants relating the records of the store to each other and to the
+    /// computed values: every QC certifies a known block, every block extends the initial QC
+    /// through known blocks, the votes of QCs, ballots, and timeouts weigh what they should, and
+    /// the commit markers agree with the chain of the highest commit. Called after each
+    /// insertion in debug builds.
+    pub fn check_internal_consistency(&self) -> Result<()> {
+        for (hash, qc) in self.quorum_certificates.iter() {
+            let block = match self.blocks.get(&qc.certified_block_hash) {
+                Some(block) => block,
+                None => bail!("QC {:?} certifies an unknown block", hash),
+            };
+            ensure!(
+                block.round == qc.round,
+                "QC {:?} is at round {:?} but certifies a block at round {:?}",
+                hash,
+                qc.round,
+                block.round
+            );
+            let voters: BTreeSet<_> = qc.votes.iter().map(|(author, _, _)| author).collect();
+            ensure!(
+                voters.len() == qc.votes.len(),
+                "QC {:?} counts a vote twice",
+                hash
+            );
+            let configuration = self.voting_configuration(qc.round);
+            ensure!(
+                configuration.count_votes(voters.into_iter()) >= configuration.quorum_threshold(),
+                "QC {:?} does not have a quorum of votes",
+                hash
+            );
+        }
+        for (slot, (hash, block)) in self.blocks.iter().enumerate() {
+            let expected_parent = if block.previous_quorum_certificate_hash == self.initial_hash {
+                None
+            } else {
+                match self
+                    .quorum_certificates
+                    .get(&block.previous_quorum_certificate_hash)
+                {
+                    Some(qc) => self.blocks.slot(&qc.certified_block_hash),
+                    None => bail!("Block {:?} extends an unknown QC", hash),
+                }
+            };
+            ensure!(
+                self.indexes.block_parents.get(slot) == Some(&expected_parent)
+                    && self.indexes.block_rounds.get(slot) == Some(&block.round),
+                "Block {:?} is not indexed with its parent and round",
+                hash
+            );
+            // Parents are inserted first, so the chain of parents reaches the initial QC.
+            if let Some(parent) = expected_parent {
+                ensure!(
+                    (parent as usize) < slot
+                        && self.indexes.block_rounds[parent as usize] < block.round,
+                    "Block {:?} does not extend an earlier block at a lower round",
+                    hash
+                );
+            }
+        }
+        if let ElectionState::Ongoing { ballot } = &self.current_election {
+            for ((block_hash, state), weight) in ballot {
+                let voters = self
+                    .current_votes
+                    .values()
+                    .filter(|vote| vote.certified_block_hash == *block_hash && vote.state == *state)
+                    .map(|vote| &vote.author);
+                ensure!(
+                    self.voting_configuration(self.current_round)
+                        .count_votes(voters)
+                        == *weight,
+                    "The ballot for block {:?} does not match the current votes",
+                    block_hash
+                );
+            }
+        }
+        ensure!(
+            self.voting_configuration(self.current_round)
+                .count_votes(self.current_timeouts.keys())
+                == self.current_timeouts_weight,
+            "The weight of the current timeouts does not match the timeouts"
+        );
+        ensure!(
+            self.highest_quorum_certificate_hash == self.initial_hash
+                || self
+                    .quorum_certificate(self.highest_quorum_certificate_hash)
+                    .map(|qc| qc.round)
+                    == Some(self.highest_quorum_certificate_round),
+            "The highest QC is unknown or not at the highest QC round"
+        );
+        ensure!(
+            self.highest_committed_round <= self.highest_quorum_certificate_round
+                && self.highest_quorum_certificate_round < self.current_round,
+            "The highest commit, highest QC, and current rounds are not in order"
+        );
+        match self.highest_commit_certificate_hash {
+            Some(cc_hash) => {
+                ensure!(
+                    self.quorum_certificates.contains_key(&cc_hash),
+                    "The highest commit certificate is unknown"
+                );
+                ensure!(
+                    self.ancestor_rounds(cc_hash)
+                        .nth(self.extensions.highest_commit_chain_length - 1)
+                        == Some(self.highest_committed_round),
+                    "The highest committed round is not on the chain of the commit certificate"
+                );
+            }
+            None => ensure!(
+                self.highest_committed_round == Round(0),
+                "A commit is recorded without a commit certificate"
+            ),
+        }
+        ensure!(
+            self.extensions
+                .fast_committed_rounds
+                .iter()
+                .all(|round| *round <= self.highest_committed_round),
+            "A commit on the fast path is above the highest commit"
+        );
+        Ok(())
+    }
+
+    /// Whether all the records are in memory, so that checking them does not read the disk.
+    fn is_resident(&self) -> bool {
+        #[cfg(feature = "disk_store")]
+        let resident = self.extensions.disk_storage.is_none();
+        #[cfg(not(feature = "disk_store"))]
+        let resident = true;
+        resident
+    }
+
+    /// In debug builds, check that the store is still consistent after an insertion and that
+    /// the commit markers did not move back from the given rounds.
+    fn debug_check_consistency(&self, highest_committed_round: Round, current_round: Round) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        assert!(
+            self.highest_committed_round >= highest_committed_round
+                && self.current_round >= current_round,
+            "The commit markers of the record store moved back"
+        );
+        if self.is_resident() {
+            if let Err(error) = self.check_internal_consistency() {
+                panic!("Inconsistent record store: {}", error);
+            }
+        }
+    }
+
     fn ancestor_rounds<'a>(
         &'a self,
         qc_hash: QuorumCertificateHash,
@@ -200,191 +959,462 @@ impl RecordStoreState {
             return;
         }
         self.current_round = round;
-        self.current_proposed_block = None;
+        self.current_proposals = HashMap::new();
         self.current_timeouts = HashMap::new();
         self.current_votes = HashMap::new();
         self.current_timeouts_weight = 0;
         self.current_election = ElectionState::Ongoing {
             ballot: HashMap::new(),
         };
+        if let Some(committee) = &self.settings.committee {
+            self.extensions.current_committee =
+                Some(committee.select(&self.configuration, self.epoch_id, round));
+        }
     }
 
-    fn update_commit_3chain_round(&mut self, qc_hash: QuorumCertificateHash) {
-        let rounds = {
-            let mut iter = self.ancestor_rounds(qc_hash);
-            let r3 = iter.next();
-            let r2 = iter.next();
-            let r1 = iter.next();
-            (r1, r2, r3)
-        };
-        if let (Some(r1), Some(r2), Some(r3)) = rounds {
-            if r3 == r2 + 1 && r2 == r1 + 1 {
-                if r1 > self.highest_committed_round {
-                    self.highest_committed_round = r1;
-                    self.highest_commit_certificate_hash = Some(qc_hash);
-                }
+    fn update_commit_round(&mut self, qc_hash: QuorumCertificateHash) {
+        let length = self.settings.commit_rule.chain_length();
+        let mut rounds: Vec<_> = self.ancestor_rounds(qc_hash).take(length).collect();
+        rounds.reverse();
+        if self.settings.commit_rule.is_commit_chain(&rounds)
+            && rounds[0] > self.highest_committed_round
+        {
+            self.highest_committed_round = rounds[0];
+            self.highest_commit_certificate_hash = Some(qc_hash);
+            self.extensions.highest_commit_chain_length = length;
+        }
+        // On the fast path, the chain ending with a QC of enough votes may be one QC shorter.
+        let fast_rounds = &rounds[rounds.len().saturating_sub(length - 1)..];
+        if self.is_fast_commit_certificate(qc_hash)
+            && self.settings.commit_rule.is_fast_commit_chain(fast_rounds)
+            && fast_rounds[0] > self.highest_committed_round
+        {
+            self.highest_committed_round = fast_rounds[0];
+            self.highest_commit_certificate_hash = Some(qc_hash);
+            self.extensions.highest_commit_chain_length = fast_rounds.len();
+            self.extensions.fast_committed_rounds.insert(fast_rounds[0]);
+        }
+    }
+
+    fn is_fast_commit_certificate(&self, qc_hash: QuorumCertificateHash) -> bool {
+        let qc = self.quorum_certificate(qc_hash).unwrap();
+        match self.fast_quorum_threshold_at(qc.round) {
+            Some(threshold) => {
+                self.voting_configuration(qc.round)
+                    .count_votes(qc.votes.iter().map(|(author, _, _)| author))
+                    >= threshold
             }
+            None => false,
         }
     }
 
     fn vote_committed_state(&self, block_hash: BlockHash) -> Option<State> {
+        if !self.settings.commit_rule.certifies_commits() {
+            return None;
+        }
         let block = self.block(block_hash).unwrap();
-        let r3 = block.round;
-        let qc2_hash = block.previous_quorum_certificate_hash;
-        let mut iter = BackwardQuorumCertificateIterator::new(&self, qc2_hash);
-        let opt_qc2 = iter.next();
-        let opt_qc1 = iter.next();
-        if let (Some(qc1), Some(qc2)) = (opt_qc1, opt_qc2) {
-            let r2 = qc2.round;
-            let r1 = qc1.round;
-            if r3 == r2 + 1 && r2 == r1 + 1 {
-                return Some(qc1.state.clone());
-            }
+        // The QCs that would form a commit chain with a QC for the block.
+        let mut qcs: Vec<_> =
+            BackwardQuorumCertificateIterator::new(self, block.previous_quorum_certificate_hash)
+                .take(self.settings.commit_rule.chain_length() - 1)
+                .collect();
+        qcs.reverse();
+        let mut rounds: Vec<_> = qcs.iter().map(|qc| qc.round).collect();
+        rounds.push(block.round);
+        if self.settings.commit_rule.is_commit_chain(&rounds) {
+            return Some(qcs[0].state.clone());
         }
         None
     }
 
-    fn verify_network_record(&self, record: &Record) -> Result<u64> {
-        let hash = record.digest();
+    /// The epoch that starts after the given committed state, if it ends the current epoch.
+    fn vote_next_epoch(
+        &self,
+        committed_state: &Option<State>,
+        smr_context: &SMRContext,
+    ) -> Option<EpochChange> {
+        let state = committed_state.as_ref()?;
+        let epoch_id = smr_context.read_epoch_id(state);
+        if epoch_id > self.epoch_id {
+            Some(EpochChange::new(
+                epoch_id,
+                &smr_context.configuration(state),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Reason to reject a record without further checks: blocks and QCs that are already
+    /// inserted, votes and timeouts that are not for the current round or whose author already
+    /// has one. With many nodes, most of the records received from peers are such copies, so
+    /// they are filtered before any signature is verified.
+    fn outdated(&self, record: &Record) -> Option<RejectReason> {
+        let known = match record {
+            Record::Block(_) => self
+                .blocks
+                .contains_key(&BlockHash(record.digest(&self.settings.hash_function))),
+            Record::QuorumCertificate(_) => {
+                self.quorum_certificates
+                    .contains_key(&QuorumCertificateHash(
+                        record.digest(&self.settings.hash_function),
+                    ))
+            }
+            Record::Vote(vote) => {
+                return self.round_reason(vote.round, self.current_votes.contains_key(&vote.author))
+            }
+            Record::Timeout(timeout) => {
+                return self.round_reason(
+                    timeout.round,
+                    self.current_timeouts.contains_key(&timeout.author),
+                )
+            }
+        };
+        if known {
+            Some(RejectReason::Duplicate)
+        } else {
+            None
+        }
+    }
+
+    /// Reason to reject a vote or a timeout at the given round, if any.
+    fn round_reason(&self, round: Round, author_is_known: bool) -> Option<RejectReason> {
+        if round < self.current_round {
+            Some(RejectReason::StaleRound)
+        } else if round > self.current_round {
+            Some(RejectReason::FutureRound)
+        } else if author_is_known {
+            Some(RejectReason::Duplicate)
+        } else {
+            None
+        }
+    }
+
+    /// Reason to reject a record of the given epoch, if it is not the current one.
+    fn epoch_reason(&self, epoch_id: EpochId) -> InsertResult<()> {
+        if epoch_id > self.epoch_id {
+            Err(RejectReason::FutureEpoch)
+        } else if epoch_id < self.epoch_id {
+            Err(RejectReason::PastEpoch)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check a record received from the network against the following rules, in this order,
+    /// and return its digest.
+    /// * Blocks: not known yet, signed by their author for the current epoch, and extending
+    ///   either the initial hash of the epoch or a known QC. Rounds start at 1 and increase
+    ///   along the chain. Times increase along the chain, are no earlier than the quorum time
+    ///   of the QC that they extend (or than the initial time of the epoch) and, if
+    ///   `max_clock_drift` is set, are at most that far ahead of the local clock.
+    /// * Votes: of the current epoch, for a known block of the same round, later than the
+    ///   block, with the committed state given by the commit rule, at the current round, at
+    ///   most one per author, and signed by their author. Their times are bounded by
+    ///   `max_clock_drift` like the ones of blocks.
+    /// * QCs: of the current epoch, not known yet, for a known block of the same round, signed
+    ///   by the author of the block, with the committed state given by the commit rule, and
+    ///   with correctly signed votes of distinct authors, later than the block, forming a
+    ///   quorum.
+    /// * Timeouts: of the current epoch, at the current round, at most one per author, signed
+    ///   by their author, who belongs to the committee of the round if any, and referring to a
+    ///   certified round that is below their own round and not above the highest known QC.
+    ///
+    /// Records of another epoch cannot refer to the blocks and QCs of this one, since the
+    /// chain of each epoch starts from its own initial hash.
+    /// Records must be signed by validators of the epoch.
+    fn check_author(&self, author: Author) -> InsertResult<()> {
+        check(
+            self.configuration.weight(&author) > 0,
+            RejectReason::UnknownAuthor,
+        )
+    }
+
+    fn check_signatures(&self, record: &Record) -> InsertResult<()> {
+        check_signatures(
+            record,
+            self.epoch_id,
+            self.settings.chain_id,
+            &self.settings.hash_function,
+            &self.configuration,
+        )
+    }
+
+    /// Check the signatures of the given records of this epoch in parallel, except for the
+    /// copies of known records and the QCs found in the cache, which are skipped as they
+    /// would be during insertion. Return which records are correctly signed, in order.
+    pub fn verify_signatures_in_parallel(&self, records: &[Record]) -> Vec<bool> {
+        let pending: Vec<_> = records
+            .iter()
+            .map(|record| {
+                let cached = match (&self.extensions.qc_cache, record) {
+                    (Some(qc_cache), Record::QuorumCertificate(qc)) => qc_cache.contains(
+                        QuorumCertificateHash(record.digest(&self.settings.hash_function)),
+                        qc.signature,
+                    ),
+                    _ => false,
+                };
+                self.outdated(record).is_none() && !cached
+            })
+            .collect();
+        let (epoch_id, chain_id) = (self.epoch_id, self.settings.chain_id);
+        let (hash_function, configuration) = (&self.settings.hash_function, &self.configuration);
+        records
+            .par_iter()
+            .zip(pending)
+            .map(|(record, pending)| {
+                pending
+                    && check_signatures(record, epoch_id, chain_id, hash_function, configuration)
+                        .is_ok()
+            })
+            .collect()
+    }
+
+    /// Insert a record whose signatures were already verified, e.g. by
+    /// `verify_signatures_in_parallel`.
+    pub fn insert_verified_network_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted> {
+        if let Some(reason) = self.outdated(&record) {
+            return Err(reason);
+        }
+        self.try_insert_network_record(record, smr_context, true, true)
+    }
+
+    /// Save a verified block or QC in its arena. Return whether it was new, or reject it if the
+    /// backend of the arena failed to save it.
+    fn store_record<H, R>(arena: &mut RecordArena<H, R>, hash: H, record: R) -> InsertResult<bool>
+    where
+        H: Copy + Eq + std::hash::Hash + Debug,
+    {
+        arena.insert(hash, record).map_err(|error| {
+            error!("Failed to save record {:?}: {}", hash, error);
+            RejectReason::StorageFailure
+        })
+    }
+
+    /// Verify a record of the given digest. Signatures are only checked unless
+    /// `verified_signatures` says that they were already.
+    fn verify_network_record(
+        &self,
+        record: &Record,
+        hash: u64,
+        verified_signatures: bool,
+    ) -> InsertResult<()> {
+        let signing_hash = record.signing_hash(
+            self.epoch_id,
+            self.settings.chain_id,
+            &self.settings.hash_function,
+        );
         match record {
             Record::Block(block) => {
-                ensure!(
+                check(
                     !self.blocks.contains_key(&BlockHash(hash)),
-                    "Block was already inserted."
-                );
-                block.signature.check(hash, block.author)?;
-                ensure!(
+                    RejectReason::Duplicate,
+                )?;
+                self.check_author(block.author)?;
+                if !verified_signatures {
+                    check_signature(
+                        &block.signature,
+                        signing_hash,
+                        self.public_key(block.author),
+                    )?;
+                }
+                check(
                     block.previous_quorum_certificate_hash == self.initial_hash
                         || self
                             .quorum_certificates
                             .contains_key(&block.previous_quorum_certificate_hash),
-                    "The previous QC (if any) must be verified first."
-                );
+                    RejectReason::UnknownParent,
+                )?;
                 if self.initial_hash == block.previous_quorum_certificate_hash {
-                    ensure!(block.round > Round(0), "Rounds must start at 1");
+                    check(
+                        block.round > Round(0),
+                        RejectReason::Invalid("Rounds must start at 1"),
+                    )?;
                 } else {
                     let previous_qc = self
                         .quorum_certificate(block.previous_quorum_certificate_hash)
                         .unwrap();
                     let previous_block = self.block(previous_qc.certified_block_hash).unwrap();
-                    ensure!(
+                    check(
                         block.round > previous_block.round,
-                        "Rounds must be increasing"
-                    );
+                        RejectReason::Invalid("Rounds must be increasing"),
+                    )?;
+                    check(
+                        block.time > previous_block.time,
+                        RejectReason::Invalid("Block times must be increasing"),
+                    )?;
                 }
+                if let Some(min_time) = self.min_block_time(block.previous_quorum_certificate_hash)
+                {
+                    check(
+                        block.time >= min_time,
+                        RejectReason::Invalid(
+                            "Block times must not be earlier than the quorum time of their parent",
+                        ),
+                    )?;
+                }
+                self.check_time(block.time)?;
             }
             Record::Vote(vote) => {
-                ensure!(
-                    vote.epoch_id == self.epoch_id,
-                    "Epoch identifier of vote ({:?}) must match the current epoch ({:?}).",
-                    vote.epoch_id,
-                    self.epoch_id
-                );
-                ensure!(
+                self.epoch_reason(vote.epoch_id)?;
+                check(
                     self.blocks.contains_key(&vote.certified_block_hash),
-                    "The certified block hash of a vote must be verified first."
-                );
-                ensure!(
+                    RejectReason::UnknownParent,
+                )?;
+                check(
                     self.block(vote.certified_block_hash).unwrap().round == vote.round,
-                    "The round of the vote must match the certified block."
-                );
-                ensure!(
+                    RejectReason::Invalid("The round of the vote must match the certified block"),
+                )?;
+                check(
+                    vote.time > self.block(vote.certified_block_hash).unwrap().time,
+                    RejectReason::Invalid("Votes must be later than the certified block"),
+                )?;
+                self.check_time(vote.time)?;
+                check(
                     self.vote_committed_state(vote.certified_block_hash) == vote.committed_state,
-                    "The committed_state value of a vote must follow the commit rule."
-                );
-                ensure!(
-                    vote.round == self.current_round,
-                    "Only accepting votes for a proposal at the current {:?}. This one was at {:?}",
-                    self.current_round,
-                    vote.round
-                );
-                ensure!(
-                    !self.current_votes.contains_key(&vote.author),
-                    "We insert votes only for authors who haven't voted yet."
-                );
-                vote.signature.check(hash, vote.author)?
+                    RejectReason::Invalid(
+                        "The committed_state value of a vote must follow the commit rule",
+                    ),
+                )?;
+                check_next_epoch(vote.epoch_id, &vote.committed_state, vote.next_epoch)?;
+                if let Some(reason) =
+                    self.round_reason(vote.round, self.current_votes.contains_key(&vote.author))
+                {
+                    return Err(reason);
+                }
+                self.check_author(vote.author)?;
+                check(
+                    self.is_voter(vote.author, vote.round),
+                    RejectReason::UnknownAuthor,
+                )?;
+                if !verified_signatures {
+                    check_signature(&vote.signature, signing_hash, self.public_key(vote.author))?;
+                }
             }
             Record::QuorumCertificate(qc) => {
-                ensure!(
-                    qc.epoch_id == self.epoch_id,
-                    "Epoch identifier of QC ({:?}) must match the current epoch ({:?}).",
-                    qc.epoch_id,
-                    self.epoch_id
-                );
-                ensure!(
+                self.epoch_reason(qc.epoch_id)?;
+                check(
                     !self
                         .quorum_certificates
                         .contains_key(&QuorumCertificateHash(hash)),
-                    "QuorumCertificate was already inserted."
-                );
-                ensure!(
+                    RejectReason::Duplicate,
+                )?;
+                check(
                     self.blocks.contains_key(&qc.certified_block_hash),
-                    "The certified block hash of a QC must be verified first."
-                );
-                ensure!(
+                    RejectReason::UnknownParent,
+                )?;
+                check(
                     self.block(qc.certified_block_hash).unwrap().round == qc.round,
-                    "The round of the QC must match the certified block."
-                );
-                ensure!(
+                    RejectReason::Invalid("The round of the QC must match the certified block"),
+                )?;
+                check(
                     qc.author == self.block(qc.certified_block_hash).unwrap().author,
-                    "QCs must be created by the author of the certified block"
-                );
-                ensure!(
+                    RejectReason::Invalid(
+                        "QCs must be created by the author of the certified block",
+                    ),
+                )?;
+                check(
                     self.vote_committed_state(qc.certified_block_hash) == qc.committed_state,
-                    "The committed_state value of a QC must follow the commit rule."
-                );
-                let mut weight = 0;
-                for (author, signature) in &qc.votes {
-                    let original_vote_digest = Record::digest(&Record::Vote(Vote {
-                        epoch_id: self.epoch_id,
-                        round: qc.round,
-                        certified_block_hash: qc.certified_block_hash,
-                        state: qc.state.clone(),
-                        committed_state: qc.committed_state.clone(),
-                        author: author.clone(),
-                        signature: Signature(0), // ignored
-                    }));
-                    signature.check(original_vote_digest, *author)?;
-                    weight += self.configuration.weight(author);
+                    RejectReason::Invalid(
+                        "The committed_state value of a QC must follow the commit rule",
+                    ),
+                )?;
+                check_next_epoch(qc.epoch_id, &qc.committed_state, qc.next_epoch)?;
+                let block_time = self.block(qc.certified_block_hash).unwrap().time;
+                let configuration = self.voting_configuration(qc.round);
+                let mut voters = BTreeSet::new();
+                for (author, time, _) in &qc.votes {
+                    check(
+                        voters.insert(*author),
+                        RejectReason::Invalid("Votes in QCs must have distinct authors"),
+                    )?;
+                    check(
+                        *time > block_time,
+                        RejectReason::Invalid(
+                            "Votes in QCs must be later than the certified block",
+                        ),
+                    )?;
+                    self.check_author(*author)?;
+                    check(
+                        configuration.weight(author) > 0,
+                        RejectReason::UnknownAuthor,
+                    )?;
                 }
-                ensure!(
-                    weight >= self.configuration.quorum_threshold(),
-                    "Votes in QCs must form a quorum"
-                );
-                // TODO: do not recompute hash
-                qc.signature
-                    .check(Record::QuorumCertificate(qc.clone()).digest(), qc.author)?;
+                if !verified_signatures {
+                    self.check_signatures(record)?;
+                }
+                check(
+                    configuration.count_votes(&voters) >= configuration.quorum_threshold(),
+                    RejectReason::Invalid("Votes in QCs must form a quorum"),
+                )?;
             }
             Record::Timeout(timeout) => {
-                ensure!(
-                    timeout.epoch_id == self.epoch_id,
-                    "Epoch identifier of timeout ({:?}) must match the current epoch ({:?}).",
-                    timeout.epoch_id,
-                    self.epoch_id
-                );
-                ensure!(
+                self.epoch_reason(timeout.epoch_id)?;
+                check(
+                    timeout.highest_certified_block_round < timeout.round,
+                    RejectReason::Invalid(
+                        "Timeouts must refer to a certified block round below their own",
+                    ),
+                )?;
+                check(
                     timeout.highest_certified_block_round
                         <= self.highest_quorum_certificate_round(),
-                    "Timeouts must refer to a known certified block round."
-                );
-                ensure!(
-                    timeout.round == self.current_round,
-                    "Accepting only timeouts at the current {:?}. This one was at {:?}",
-                    self.current_round,
-                    timeout.round
-                );
-                ensure!(
-                    !self.current_timeouts.contains_key(&timeout.author),
-                    "A timeout is already known for the same round and the same author"
-                );
-                timeout.signature.check(hash, timeout.author)?;
+                    RejectReason::Invalid("Timeouts must refer to a known certified block round"),
+                )?;
+                if let Some(reason) = self.round_reason(
+                    timeout.round,
+                    self.current_timeouts.contains_key(&timeout.author),
+                ) {
+                    return Err(reason);
+                }
+                self.check_author(timeout.author)?;
+                check(
+                    self.is_voter(timeout.author, timeout.round),
+                    RejectReason::UnknownAuthor,
+                )?;
+                if !verified_signatures {
+                    check_signature(
+                        &timeout.signature,
+                        signing_hash,
+                        self.public_key(timeout.author),
+                    )?;
+                }
             }
         }
-        Ok(hash)
+        Ok(())
     }
 
-    fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate> {
-        self.quorum_certificates.get(&qc_hash)
+    /// Sketch of the hashes of the blocks and QCs after the given round.
+    fn sketch(&self, after_round: Round, num_cells: usize) -> InvertibleBloomLookupTable {
+        let mut sketch = InvertibleBloomLookupTable::new(num_cells);
+        for records in self
+            .indexes
+            .round_index
+            .range(after_round + 1..)
+            .map(|x| x.1)
+        {
+            for hash in &records.blocks {
+                sketch.insert(hash.0);
+            }
+            for hash in &records.quorum_certificates {
+                sketch.insert(hash.0);
+            }
+        }
+        sketch
+    }
+
+    fn committed_block_hashes(&self) -> HashSet<BlockHash> {
+        let cc_hash = self
+            .highest_commit_certificate_hash
+            .unwrap_or(self.initial_hash);
+        BackwardQuorumCertificateIterator::new(self, cc_hash)
+            .skip(self.extensions.highest_commit_chain_length - 1)
+            .map(|qc| qc.certified_block_hash)
+            .collect()
     }
 
     fn compute_state(&self, block_hash: BlockHash, smr_context: &mut SMRContext) -> Option<State> {
@@ -400,45 +1430,139 @@ impl RecordStoreState {
                 (&previous_qc.state, Some(previous_qc.author), voters)
             }
         };
-        smr_context.compute(
+        smr_context.speculate(
+            block_hash,
+            block,
             previous_state,
-            block.command.clone(),
-            block.time,
             previous_voters,
             previous_author,
         )
     }
 
+    /// Insert a record that we created. Rejections are also logged by `insert_network_record`.
+    fn insert_local_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<()> {
+        match self.insert_network_record(record.clone(), smr_context) {
+            Ok(_) => Ok(()),
+            Err(reason) => Err(NodeError::rejected(reason, self.epoch_id, &record)),
+        }
+    }
+
+    /// Verify and insert a record. The states of QCs are checked by executing their block,
+    /// unless `execute` is false. Signatures are not checked again if `verified_signatures`
+    /// is true.
     fn try_insert_network_record(
         &mut self,
         record: Record,
         smr_context: &mut SMRContext,
-    ) -> Result<()> {
+        execute: bool,
+        verified_signatures: bool,
+    ) -> InsertResult<Inserted> {
+        let (highest_committed_round, current_round) =
+            (self.highest_committed_round, self.current_round);
+        let result = self.insert_record(record, smr_context, execute, verified_signatures);
+        if result.is_ok() {
+            self.debug_check_consistency(highest_committed_round, current_round);
+        }
+        result
+    }
+
+    fn insert_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+        execute: bool,
+        mut verified_signatures: bool,
+    ) -> InsertResult<Inserted> {
         // First, check that the record is "relevant" and that invariants of "verified records",
         // such as chaining, are respected.
-        let hash = self.verify_network_record(&record)?;
+        let hash = record.digest(&self.settings.hash_function);
+        if !verified_signatures {
+            verified_signatures = match (&mut self.extensions.qc_cache, &record) {
+                (Some(qc_cache), Record::QuorumCertificate(qc)) => qc_cache.lookup(
+                    QuorumCertificateHash(hash),
+                    qc.signature,
+                    record.num_signatures(),
+                ),
+                _ => false,
+            };
+            if !verified_signatures {
+                smr_context.verified_signatures(record.num_signatures());
+            }
+            // QCs are received again from every peer until their block arrives: if they are
+            // cached, verify their signatures right away, before checking their parent, so that
+            // the copies are not verified again.
+            if let Record::QuorumCertificate(qc) = &record {
+                if self.extensions.qc_cache.is_some()
+                    && !verified_signatures
+                    && qc.epoch_id == self.epoch_id
+                {
+                    self.check_signatures(&record)?;
+                    verified_signatures = true;
+                }
+            }
+        }
+        if let (Some(qc_cache), Record::QuorumCertificate(qc)) =
+            (&mut self.extensions.qc_cache, &record)
+        {
+            if verified_signatures && qc.epoch_id == self.epoch_id {
+                qc_cache.insert(QuorumCertificateHash(hash), qc.signature);
+            }
+        }
+        self.verify_network_record(&record, hash, verified_signatures)?;
         // Second, insert the record. In the case of QC, this is where check execution states.
         match record {
             Record::Block(block) => {
                 let block_hash = BlockHash(hash);
-                if block.round == self.current_round
-                    && PacemakerState::leader(&*self, block.round) == block.author
-                {
-                    // If we use a VRF, this assumes that we have inserted the highest commit rule
-                    // beforehand.
-                    self.current_proposed_block = Some(block_hash);
+                let parent = if block.previous_quorum_certificate_hash == self.initial_hash {
+                    None
+                } else {
+                    let qc = self
+                        .quorum_certificate(block.previous_quorum_certificate_hash)
+                        .unwrap();
+                    self.blocks.slot(&qc.certified_block_hash)
+                };
+                let (round, author) = (block.round, block.author);
+                // Save the block first, so that the indexes only refer to stored blocks.
+                if !Self::store_record(&mut self.blocks, block_hash, block)? {
+                    return Err(RejectReason::Duplicate);
+                }
+                self.indexes.block_parents.push(parent);
+                self.indexes.block_rounds.push(round);
+                if round == self.current_round {
+                    self.current_proposals.entry(author).or_insert(block_hash);
                 }
-                self.blocks.insert(block_hash, block);
+                self.indexes
+                    .round_index
+                    .entry(round)
+                    .or_default()
+                    .blocks
+                    .push(block_hash);
+                self.indexes
+                    .author_index
+                    .entry(author)
+                    .or_default()
+                    .insert((round, block_hash));
             }
             Record::Vote(vote) => {
                 self.current_votes.insert(vote.author, vote.clone());
+                let (weight, threshold) = {
+                    let configuration = self.voting_configuration(vote.round);
+                    (
+                        configuration.weight(&vote.author),
+                        configuration.quorum_threshold(),
+                    )
+                };
                 let has_newly_won_election = match &mut self.current_election {
                     ElectionState::Ongoing { ballot } => {
                         let entry = ballot
                             .entry((vote.certified_block_hash, vote.state.clone()))
                             .or_insert(0);
-                        *entry += self.configuration.weight(&vote.author);
-                        if *entry >= self.configuration.quorum_threshold() {
+                        *entry += weight;
+                        if *entry >= threshold {
                             Some(ElectionState::Won {
                                 block_hash: vote.certified_block_hash,
                                 state: vote.state,
@@ -458,18 +1582,32 @@ impl RecordStoreState {
                 let qc_hash = QuorumCertificateHash(hash);
                 let qc_round = qc.round;
                 let qc_state = qc.state.clone();
-                self.quorum_certificates.insert(qc_hash, qc);
-                // Make sure that the state in the QC is known to execution.
-                match self.compute_state(block_hash, smr_context) {
-                    Some(state) => {
-                        ensure!(
-                            state == qc_state,
-                            "I computed a different state for a QC. This is very bad: {:?}",
-                            qc_state
-                        );
-                    }
-                    None => {
-                        bail!("I failed to execute a block with a QC at {:?} while my last commit is at {:?}", qc_round, self.highest_committed_round);
+                if !Self::store_record(&mut self.quorum_certificates, qc_hash, qc)? {
+                    return Err(RejectReason::Duplicate);
+                }
+                self.indexes
+                    .round_index
+                    .entry(qc_round)
+                    .or_default()
+                    .quorum_certificates
+                    .push(qc_hash);
+                // Make sure that the state in the QC is known to execution, unless blocks of this
+                // epoch are not executed any more.
+                if execute {
+                    match self.compute_state(block_hash, smr_context) {
+                        Some(state) => {
+                            if state != qc_state {
+                                error!(
+                                    "I computed a different state for a QC. This is very bad: {:?}",
+                                    qc_state
+                                );
+                                return Err(RejectReason::StateMismatch);
+                            }
+                        }
+                        None => {
+                            debug!("I failed to execute a block with a QC at {:?} while my last commit is at {:?}", qc_round, self.highest_committed_round);
+                            return Err(RejectReason::NotExecuted);
+                        }
                     }
                 }
                 // Update computed values.
@@ -478,22 +1616,28 @@ impl RecordStoreState {
                     self.highest_quorum_certificate_hash = qc_hash;
                 }
                 self.update_current_round(qc_round + 1);
-                self.update_commit_3chain_round(qc_hash);
+                self.update_commit_round(qc_hash);
             }
             Record::Timeout(timeout) => {
                 self.current_timeouts
                     .insert(timeout.author, timeout.clone());
-                self.current_timeouts_weight += self.configuration.weight(&timeout.author);
-                if self.current_timeouts_weight >= self.configuration.quorum_threshold() {
-                    let timeout_certificate =
-                        self.current_timeouts.iter().map(|x| x.1.clone()).collect();
+                let configuration = self.voting_configuration(self.current_round);
+                let (weight, quorum_threshold) = (
+                    configuration.weight(&timeout.author),
+                    configuration.quorum_threshold(),
+                );
+                self.current_timeouts_weight += weight;
+                if self.current_timeouts_weight >= quorum_threshold {
+                    let mut timeout_certificate: Vec<_> =
+                        self.current_timeouts.values().cloned().collect();
+                    timeout_certificate.sort_by_key(|timeout| timeout.author);
                     self.highest_timeout_certificate = Some(timeout_certificate);
                     self.highest_timeout_certificate_round = self.current_round;
                     self.update_current_round(self.current_round + 1);
                 }
             }
         }
-        Ok(())
+        Ok(Inserted { digest: hash })
     }
 }
 
@@ -510,25 +1654,6 @@ impl RecordStore for RecordStoreState {
         self.highest_quorum_certificate_hash
     }
 
-    fn committed_states_after(&self, after_round: Round) -> Vec<(Round, State)> {
-        let cc_hash = self
-            .highest_commit_certificate_hash
-            .unwrap_or(self.initial_hash);
-        let mut iter = BackwardQuorumCertificateIterator::new(self, cc_hash);
-        iter.next();
-        iter.next();
-        let mut commits = Vec::new();
-        while let Some(qc) = iter.next() {
-            if qc.round <= after_round {
-                break;
-            }
-            info!("Delivering committed state for round {:?}", qc.round);
-            commits.push((qc.round, qc.state.clone()));
-        }
-        commits.reverse();
-        commits
-    }
-
     fn highest_quorum_certificate_round(&self) -> Round {
         self.highest_quorum_certificate_round
     }
@@ -571,35 +1696,37 @@ impl RecordStore for RecordStoreState {
             // Pacemaker is behind. We have already cleaned up proposals at this round.
             return None;
         }
-        if let Some(leader) = pacemaker.active_leader() {
-            match &self.current_proposed_block {
-                None => None,
-                Some(hash) => {
-                    let block = self.block(*hash).unwrap();
-                    assert_eq!(block.round, self.current_round);
-                    assert_eq!(block.author, leader);
-                    Some((*hash, block.round, block.author))
-                }
-            }
-        } else {
-            None
-        }
+        let leader = pacemaker.active_leader()?;
+        let hash = self.current_proposals.get(&leader)?;
+        let block = self.block(*hash).unwrap();
+        assert_eq!(block.round, self.current_round);
+        Some((*hash, block.round, block.author))
     }
 
-    fn create_timeout(&mut self, author: Author, round: Round, smr_context: &mut SMRContext) {
-        self.insert_network_record(
+    fn create_timeout(
+        &mut self,
+        author: Author,
+        round: Round,
+        smr_context: &mut SMRContext,
+    ) -> NodeResult<()> {
+        self.insert_local_record(
             Record::make_timeout(
                 self.epoch_id,
                 round,
                 self.highest_quorum_certificate_round(),
-                author,
+                self.public_key(author),
+                self.settings.chain_id,
+                &self.settings.hash_function,
             ),
             smr_context,
-        );
+        )
     }
 
     fn has_timeout(&self, author: Author, round: Round) -> bool {
-        round == self.current_round && self.current_timeouts.contains_key(&author)
+        // Validators outside the committee of the round never time out, as if they had done it
+        // already, so that only the committee broadcasts timeouts.
+        round == self.current_round
+            && (self.current_timeouts.contains_key(&author) || !self.is_voter(author, round))
     }
 
     fn propose_block(
@@ -607,41 +1734,62 @@ impl RecordStore for RecordStoreState {
         local_author: Author,
         previous_qc_hash: QuorumCertificateHash,
         clock: NodeTime,
+        command: Option<Command>,
         smr_context: &mut SMRContext,
-    ) {
-        if let Some(command) = smr_context.fetch() {
-            let block = Record::make_block(
-                command,
-                clock,
+    ) -> NodeResult<()> {
+        if previous_qc_hash != self.initial_hash
+            && !self.quorum_certificates.contains_key(&previous_qc_hash)
+        {
+            return Err(NodeError::Storage(StorageError::UnknownQuorumCertificate(
                 previous_qc_hash,
-                self.current_round,
-                local_author,
-            );
-            self.insert_network_record(block, smr_context)
+            )));
         }
+        // Clocks may be skewed: never propose a time earlier than the quorum time of the parent.
+        let time = std::cmp::max(Some(clock), self.min_block_time(previous_qc_hash)).unwrap();
+        let block = Record::make_block(
+            self.epoch_id,
+            command,
+            time,
+            previous_qc_hash,
+            self.current_round,
+            self.public_key(local_author),
+            self.settings.chain_id,
+            &self.settings.hash_function,
+        );
+        self.insert_local_record(block, smr_context)
     }
 
     fn create_vote(
         &mut self,
         local_author: Author,
         block_hash: BlockHash,
+        clock: NodeTime,
         smr_context: &mut SMRContext,
-    ) -> bool {
+    ) -> NodeResult<()> {
+        let block = match self.block(block_hash) {
+            Some(block) => block,
+            None => return Err(NodeError::Storage(StorageError::UnknownBlock(block_hash))),
+        };
+        let (round, time) = (block.round, std::cmp::max(clock, block.time + 1));
         let committed_state = self.vote_committed_state(block_hash);
         match self.compute_state(block_hash, smr_context) {
             Some(state) => {
+                let next_epoch = self.vote_next_epoch(&committed_state, smr_context);
                 let vote = Record::make_vote(
                     self.epoch_id,
-                    self.block(block_hash).unwrap().round,
+                    round,
                     block_hash,
                     state,
-                    local_author,
+                    self.public_key(local_author),
                     committed_state,
+                    next_epoch,
+                    time,
+                    self.settings.chain_id,
+                    &self.settings.hash_function,
                 );
-                self.insert_network_record(vote, smr_context);
-                true
+                self.insert_local_record(vote, smr_context)
             }
-            None => false,
+            None => Err(NodeError::Execution { block_hash }),
         }
     }
 
@@ -649,42 +1797,61 @@ impl RecordStore for RecordStoreState {
         &mut self,
         local_author: Author,
         smr_context: &mut SMRContext,
-    ) -> bool {
+    ) -> NodeResult<bool> {
         let quorum_certificate = match &self.current_election {
             ElectionState::Won { block_hash, state } => {
                 if self.block(*block_hash).unwrap().author != local_author {
-                    return false;
+                    return Ok(false);
                 }
                 let committed_state = self.vote_committed_state(*block_hash);
-                let authors_and_signatures = self
+                // Only aggregate the votes announcing the epoch change that we execute, if any.
+                if self.compute_state(*block_hash, smr_context).is_none() {
+                    return Err(NodeError::Execution {
+                        block_hash: *block_hash,
+                    });
+                }
+                let next_epoch = self.vote_next_epoch(&committed_state, smr_context);
+                let configuration = self.voting_configuration(self.current_round);
+                let mut authors_and_signatures: Vec<_> = self
                     .current_votes
-                    .iter()
-                    .filter_map(|(_, vote)| {
-                        if vote.state == *state {
-                            Some((vote.author, vote.signature))
+                    .values()
+                    .filter_map(|vote| {
+                        if vote.state == *state && vote.next_epoch == next_epoch {
+                            Some((vote.author, vote.time, vote.signature))
                         } else {
                             None
                         }
                     })
                     .collect();
-                let quorum_certificate = Record::make_quorum_certificate(
+                if configuration
+                    .count_votes(authors_and_signatures.iter().map(|(author, _, _)| author))
+                    < configuration.quorum_threshold()
+                {
+                    return Ok(false);
+                }
+                // Sort the votes, so that the digest of the QC does not depend on the order of
+                // the hash map.
+                authors_and_signatures.sort_by_key(|(author, _, _)| *author);
+                Record::make_quorum_certificate(
                     self.epoch_id,
                     self.current_round,
                     *block_hash,
                     state.clone(),
                     authors_and_signatures,
                     committed_state,
-                    local_author,
-                );
-                quorum_certificate
+                    next_epoch,
+                    self.public_key(local_author),
+                    self.settings.chain_id,
+                    &self.settings.hash_function,
+                )
             }
             _ => {
-                return false;
+                return Ok(false);
             }
         };
         self.current_election = ElectionState::Closed;
-        self.insert_network_record(quorum_certificate, smr_context);
-        true
+        self.insert_local_record(quorum_certificate, smr_context)?;
+        Ok(true)
     }
 
     fn highest_commit_certificate(&self) -> Option<&QuorumCertificate> {
@@ -703,7 +1870,10 @@ impl RecordStore for RecordStoreState {
         if let Some(highest_tc) = &self.highest_timeout_certificate {
             timeouts.extend(highest_tc.iter().cloned());
         }
-        timeouts.extend(self.current_timeouts.iter().map(|(_, tc)| tc.clone()));
+        // Sort the current timeouts, so that runs do not depend on the order of the hash map.
+        let mut current_timeouts: Vec<_> = self.current_timeouts.values().cloned().collect();
+        current_timeouts.sort_by_key(|timeout| timeout.author);
+        timeouts.extend(current_timeouts);
         timeouts
     }
 
@@ -749,9 +1919,7 @@ impl RecordStore for RecordStoreState {
         let chain2: Vec<_> = BackwardQuorumCertificateIterator::new(self, highest_cc_hash)
             .take_while(|qc| !known_qc_rounds.contains(&qc.round))
             .collect();
-        let qcs = merge_sort(chain1.into_iter(), chain2.into_iter(), |qc1, qc2| {
-            qc2.round.cmp(&qc1.round)
-        });
+        let qcs = merge_sort(chain1, chain2, |qc1, qc2| qc2.round.cmp(&qc1.round));
         let mut result = Vec::new();
         for n in (0..qcs.len()).rev() {
             let qc = qcs[n];
@@ -764,20 +1932,317 @@ impl RecordStore for RecordStoreState {
             result.push(Record::Timeout(timeout.clone()));
         }
         // Skipping votes intentionally.
-        if let Some(block_hash) = &self.current_proposed_block {
+        let mut proposals: Vec<_> = self.current_proposals.iter().collect();
+        proposals.sort();
+        for (_, block_hash) in proposals {
             result.push(Record::Block(self.block(*block_hash).unwrap().clone()));
         }
         result
     }
 
-    fn insert_network_record(&mut self, record: Record, smr_context: &mut SMRContext) {
+    fn insert_network_record(
+        &mut self,
+        record: Record,
+        smr_context: &mut SMRContext,
+    ) -> InsertResult<Inserted> {
         debug!("Inserting {:?}", record);
-        match self.try_insert_network_record(record, smr_context) {
-            Err(err) => {
-                debug!("=> Skipped: {}", err);
+        if let Some(reason) = self.outdated(&record) {
+            debug!("=> Skipped: {:?}", reason);
+            return Err(reason);
+        }
+        let result = self.try_insert_network_record(record, smr_context, true, false);
+        if let Err(reason) = &result {
+            debug!("=> Skipped: {:?}", reason);
+        }
+        // TODO: discard unneeded records from self.blocks and self.quorum_certificates
+        result
+    }
+}
+
+impl ChainQueries for RecordStoreState {
+    fn highest_timeout_certificate_qc_round(&self) -> Round {
+        self.highest_timeout_certificate
+            .iter()
+            .flatten()
+            .map(|timeout| timeout.highest_certified_block_round)
+            .max()
+            .unwrap_or(Round(0))
+    }
+
+    fn commit_rule(&self) -> &CommitRule {
+        &*self.settings.commit_rule
+    }
+
+    fn committed_blocks(&self, after_round: Round) -> CommittedBlocks<'_> {
+        let cc_hash = self
+            .highest_commit_certificate_hash
+            .unwrap_or(self.initial_hash);
+        let quorum_certificates = BackwardQuorumCertificateIterator::new(self, cc_hash)
+            .skip(self.extensions.highest_commit_chain_length - 1)
+            .take_while(|qc| qc.round > after_round)
+            .inspect(|qc| info!("Delivering committed state for round {:?}", qc.round))
+            .collect();
+        CommittedBlocks {
+            quorum_certificates,
+        }
+    }
+
+    fn quorum_time(&self, block_hash: BlockHash) -> NodeTime {
+        let block = self.block(block_hash).unwrap();
+        match self.quorum_certificate(block.previous_quorum_certificate_hash) {
+            Some(qc) => qc.quorum_time(&self.configuration),
+            None => block.time,
+        }
+    }
+
+    fn blocks_conflicting_with(&self, block_hash: BlockHash) -> Vec<BlockHash> {
+        let round = self.block(block_hash).unwrap().round;
+        let target = self.blocks.slot(&block_hash).unwrap() as usize;
+        let mut is_ancestor = vec![false; self.blocks.len()];
+        let mut current = Some(target);
+        while let Some(slot) = current {
+            is_ancestor[slot] = true;
+            current = self.indexes.block_parents[slot].map(|parent| parent as usize);
+        }
+        // Parents are inserted before their children, so a single pass in slot order decides
+        // whether the ancestor of a higher block at the round of the given block or below is
+        // the given block.
+        let mut is_conflicting = vec![false; self.blocks.len()];
+        let mut conflicting = Vec::new();
+        for (slot, hash) in self.blocks.keys().enumerate() {
+            let block_round = self.indexes.block_rounds[slot];
+            is_conflicting[slot] = if block_round <= round {
+                !is_ancestor[slot]
+            } else {
+                match self.indexes.block_parents[slot] {
+                    Some(parent) if parent as usize == target => false,
+                    Some(parent) if self.indexes.block_rounds[parent as usize] > round => {
+                        is_conflicting[parent as usize]
+                    }
+                    _ => true,
+                }
+            };
+            if is_conflicting[slot] {
+                conflicting.push((block_round, *hash));
+            }
+        }
+        conflicting.sort();
+        conflicting.into_iter().map(|(_, hash)| hash).collect()
+    }
+
+    fn uncommitted_depth(&self, qc_hash: QuorumCertificateHash) -> usize {
+        BackwardQuorumCertificateIterator::new(self, qc_hash)
+            .take_while(|qc| qc.round > self.highest_committed_round)
+            .count()
+    }
+
+    fn oldest_quorum_certificate_hash(
+        &self,
+        qc_hash: QuorumCertificateHash,
+        min_round: Round,
+    ) -> QuorumCertificateHash {
+        let mut oldest_hash = qc_hash;
+        while oldest_hash != self.initial_hash {
+            let qc = self.quorum_certificate(oldest_hash).unwrap();
+            let block = self.block(qc.certified_block_hash).unwrap();
+            let previous_hash = block.previous_quorum_certificate_hash;
+            match self.quorum_certificate(previous_hash) {
+                Some(previous_qc) if previous_qc.round >= min_round => oldest_hash = previous_hash,
+                _ => break,
             }
-            Ok(()) => (),
+        }
+        oldest_hash
+    }
+
+    fn previous_state(&self, block_hash: BlockHash) -> &State {
+        let block = self.block(block_hash).unwrap();
+        let hash = block.previous_quorum_certificate_hash;
+        if hash == self.initial_hash {
+            &self.initial_state
+        } else {
+            &self.quorum_certificate(hash).unwrap().state
+        }
+    }
+
+    fn pick_author_excluding(&self, seed: u64, excluded: &BTreeSet<Author>) -> Author {
+        self.configuration
+            .pick_author_excluding(seed, excluded)
+            .unwrap_or_else(|| self.configuration.pick_author(seed))
+    }
+}
+
+impl RecordQueries for RecordStoreState {
+    fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate> {
+        self.quorum_certificates.get(&qc_hash)
+    }
+
+    fn record_summary(&self, sketch_cells: Option<usize>) -> RecordSummary {
+        RecordSummary {
+            highest_committed_round: self.highest_committed_round,
+            round_digests: self
+                .indexes
+                .round_index
+                .range(self.highest_committed_round + 1..)
+                .map(|(round, records)| (*round, records.digest()))
+                .collect(),
+            sketch: sketch_cells
+                .map(|num_cells| self.sketch(self.highest_committed_round, num_cells)),
+        }
+    }
+
+    fn records_unknown_to(&self, peer_summary: &RecordSummary) -> Vec<Record> {
+        let after_round = peer_summary.highest_committed_round;
+        // Hashes of the records missing from the peer, if the sketch of the peer can be
+        // decoded. Otherwise, resend all the records of the rounds with a different digest.
+        let missing: Option<HashSet<u64>> = peer_summary.sketch.as_ref().and_then(|sketch| {
+            let difference = self
+                .sketch(after_round, sketch.cells().len())
+                .subtract(sketch)?;
+            let (missing, _) = difference.decode()?;
+            Some(missing.into_iter().collect())
+        });
+        let is_missing = |hash: u64| match &missing {
+            Some(missing) => missing.contains(&hash),
+            None => true,
         };
-        // TODO: discard unneeded records from self.blocks and self.quorum_certificates
+        let mut result = Vec::new();
+        for (round, records) in self.indexes.round_index.range(after_round + 1..) {
+            if missing.is_none() && peer_summary.round_digests.get(round) == Some(&records.digest())
+            {
+                continue;
+            }
+            // Blocks go first since a QC at the same round certifies one of them.
+            for hash in records.blocks.iter().filter(|hash| is_missing(hash.0)) {
+                result.push(Record::Block(self.block(*hash).unwrap().clone()));
+            }
+            for hash in records
+                .quorum_certificates
+                .iter()
+                .filter(|hash| is_missing(hash.0))
+            {
+                result.push(Record::QuorumCertificate(
+                    self.quorum_certificate(*hash).unwrap().clone(),
+                ));
+            }
+        }
+        for timeout in self.timeouts() {
+            result.push(Record::Timeout(timeout));
+        }
+        result
+    }
+
+    fn has_record(&self, record: &Record) -> bool {
+        let hash = record.digest(&self.settings.hash_function);
+        match record {
+            Record::Block(_) => self.blocks.contains_key(&BlockHash(hash)),
+            Record::QuorumCertificate(_) => self
+                .quorum_certificates
+                .contains_key(&QuorumCertificateHash(hash)),
+            Record::Vote(vote) => self.current_votes.get(&vote.author) == Some(vote),
+            Record::Timeout(timeout) => self.timeouts().contains(timeout),
+        }
+    }
+
+    fn blocks_proposed_by(&self, author: Author) -> Vec<BlockHash> {
+        match self.indexes.author_index.get(&author) {
+            Some(blocks) => blocks.iter().map(|(_, hash)| *hash).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn certified_rounds(&self) -> BTreeSet<Round> {
+        self.indexes
+            .round_index
+            .iter()
+            .filter(|(_, records)| !records.quorum_certificates.is_empty())
+            .map(|(round, _)| *round)
+            .collect()
+    }
+
+    fn blocks_at(&self, round: Round) -> Vec<BlockHash> {
+        self.indexes
+            .round_index
+            .get(&round)
+            .map_or_else(Vec::new, |records| records.blocks.clone())
+    }
+
+    fn quorum_certificates_at(&self, round: Round) -> Vec<QuorumCertificateHash> {
+        self.indexes
+            .round_index
+            .get(&round)
+            .map_or_else(Vec::new, |records| records.quorum_certificates.clone())
+    }
+
+    fn export_dot(&self) -> String {
+        let committed = self.committed_block_hashes();
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|(hash, block)| (block.round, **hash));
+        let mut qcs: Vec<_> = self.quorum_certificates.iter().collect();
+        qcs.sort_by_key(|(hash, qc)| (qc.round, **hash));
+
+        let mut out = String::new();
+        writeln!(out, "digraph epoch_{} {{", self.epoch_id.0).unwrap();
+        writeln!(out, "  rankdir=LR;").unwrap();
+        writeln!(
+            out,
+            "  \"qc{}\" [shape=ellipse, label=\"initial\"];",
+            self.initial_hash.0
+        )
+        .unwrap();
+        for (hash, block) in blocks {
+            let mut label = format!("B{} by {}", block.round.0, block.author.0);
+            // Votes are only kept for the current round.
+            let voters: Vec<_> = self
+                .current_votes
+                .values()
+                .filter(|vote| vote.certified_block_hash == *hash)
+                .map(|vote| vote.author.0.to_string())
+                .collect();
+            if !voters.is_empty() {
+                write!(label, "\\nvotes: {}", voters.join(",")).unwrap();
+            }
+            let style = if committed.contains(hash) {
+                ", style=filled, fillcolor=lightblue"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  \"b{}\" [shape=box, label=\"{}\"{}];",
+                hash.0, label, style
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "  \"qc{}\" -> \"b{}\";",
+                block.previous_quorum_certificate_hash.0, hash.0
+            )
+            .unwrap();
+        }
+        for (hash, qc) in qcs {
+            let voters: Vec<_> = qc.votes.iter().map(|(a, _, _)| a.0.to_string()).collect();
+            let style = if Some(*hash) == self.highest_commit_certificate_hash {
+                ", style=filled, fillcolor=lightblue"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  \"qc{}\" [shape=ellipse, label=\"QC{}\\nvotes: {}\"{}];",
+                hash.0,
+                qc.round.0,
+                voters.join(","),
+                style
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "  \"b{}\" -> \"qc{}\";",
+                qc.certified_block_hash.0, hash.0
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
     }
 }
@@ -0,0 +1,1838 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use adversarial_scheduler::QuorumDelayScheduler;
+use bft_simulator_runtime::{
+    bandwidth::PayloadSizes,
+    configuration::DEFAULT_RESILIENCE,
+    inbound_queue::QueuePolicy,
+    simulator::{
+        ClockSkew, GlobalTime, LinkDegradation, LinkOrdering, Partition, RandomDelay, Simulator,
+        StopConditions,
+    },
+};
+use commit_rule::{CommitRuleKind, FastPath};
+use committee::{CommitteeRotation, VotingCommittee};
+use data_sync::*;
+#[cfg(feature = "disk_store")]
+use disk_store::DiskStorage;
+use flooder::FloodingNode;
+use follower::{FollowerState, Participant};
+use hashing::HashFunction;
+use node::NodeState;
+use pacemaker::{AdaptiveDelta, LeaderReputation};
+use reads::ReadConsistency;
+use serde::{Deserialize, Serialize};
+use shards::{ShardedContext, ShardedNode, ShardedSimulator};
+use simulated_context::SimulatedContext;
+use smr_context::{EpochReader, LoadSheddingPolicy};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+#[cfg(test)]
+#[path = "unit_tests/scenario_tests.rs"]
+mod scenario_tests;
+
+/// Simulator running LibraBFT validators and followers on top of simulated SMR contexts.
+pub type NodeSimulator = Simulator<
+    Participant,
+    SimulatedContext,
+    DataSyncNotification,
+    DataSyncRequest,
+    DataSyncResponse,
+>;
+
+/// Complete description of a simulation run, usually loaded from a TOML file.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// The number of validators to simulate.
+    pub nodes: usize,
+    /// The number of followers, which do not vote. Their authors come after the validators.
+    #[serde(default)]
+    pub followers: usize,
+    /// Time at which to stop the simulation, i.e. its simulated-time budget.
+    pub max_clock: i64,
+    /// Conditions to stop the simulation before `max_clock`, if any.
+    #[serde(default)]
+    pub stop: Option<StopParameters>,
+    /// Voting rights of each validator, e.g. its stake. Validators have one vote each by
+    /// default.
+    #[serde(default)]
+    pub voting_rights: Vec<usize>,
+    /// Quorums tolerate `f` faulty votes out of `n = resilience * f + 1`, e.g. 5 to explore
+    /// fast paths. The classic value is 3.
+    #[serde(default = "default_resilience")]
+    pub resilience: usize,
+    /// Changes of the voting rights at epoch boundaries.
+    #[serde(default)]
+    pub reconfigurations: Vec<Reconfiguration>,
+    /// Changes of the keys of validators at epoch boundaries.
+    #[serde(default)]
+    pub key_rotations: Vec<KeyRotation>,
+    /// Validators added to the network during the run, which bootstrap from a snapshot.
+    #[serde(default)]
+    pub joins: Vec<Join>,
+    /// Validators removed from the network during the run, which keep following the chain.
+    #[serde(default)]
+    pub removals: Vec<Removal>,
+    /// The maximum number of commands per epoch.
+    #[serde(default = "default_commands_per_epoch")]
+    pub commands_per_epoch: usize,
+    #[serde(default)]
+    pub pacemaker: PacemakerParameters,
+    /// The rule deciding when blocks are committed.
+    #[serde(default)]
+    pub commit_rule: CommitRuleKind,
+    /// Hash function of the records: `fnv1a` for pure simulations, `sha3_256` for deployments.
+    /// If unset, simulations use `fnv1a` and deployed nodes `sha3_256`, see `hash_function()`.
+    #[serde(default)]
+    pub hash_function: Option<HashFunction>,
+    /// Network signed by all the records, so that records cannot be replayed across networks.
+    #[serde(default)]
+    pub chain_id: ChainId,
+    /// Commit with one QC less when the last QC of a chain gathers a super-quorum of votes.
+    #[serde(default)]
+    pub fast_path: Option<FastPathParameters>,
+    /// Only let a stake-weighted sample of the validators vote at each round, if any.
+    #[serde(default)]
+    pub committee: Option<CommitteeParameters>,
+    /// Independent consensus instances, each run by some of the validators over the same
+    /// network, if any. See `build_sharded_simulator`.
+    #[serde(default)]
+    pub shards: Vec<Shard>,
+    /// Transactions between two shards, coordinated with two-phase commit, if any.
+    #[serde(default)]
+    pub cross_shard: Option<CrossShardParameters>,
+    #[serde(default)]
+    pub network: NetworkParameters,
+    #[serde(default)]
+    pub workload: WorkloadParameters,
+    #[serde(default)]
+    pub cpu: CpuParameters,
+    /// Bound the messages waiting for busy nodes, instead of queuing them without limit.
+    #[serde(default)]
+    pub inbound_queue: Option<InboundQueueParameters>,
+    /// Nodes to crash during the run.
+    #[serde(default)]
+    pub faults: Vec<CrashFault>,
+    #[serde(default)]
+    pub topology: Topology,
+    /// Inter-region links to slow down during the run.
+    #[serde(default)]
+    pub link_faults: Vec<LinkFault>,
+    /// Nodes to duplicate with the same identity and signing key, to test safety under
+    /// equivocation. The twin of `twins[i]` is the replica `nodes + followers + i`.
+    #[serde(default)]
+    pub twins: Vec<usize>,
+    /// Validators that stop voting and proposing, and flood the other nodes with useless
+    /// records instead.
+    #[serde(default)]
+    pub flooders: Vec<Flooder>,
+    /// Validators that ignore their locks and vote at most once per round, even after timing
+    /// out. Twins of these validators violate locks too.
+    #[serde(default)]
+    pub lock_violators: Vec<usize>,
+    /// Validators that withhold the newest QCs when they lead: their proposals extend the
+    /// oldest QC compatible with their lock. Twins of these validators withhold QCs too.
+    #[serde(default)]
+    pub qc_withholders: Vec<usize>,
+    /// Validators that propose again the command of their latest commit when they lead, which
+    /// honest validators refuse to vote for. Twins of these validators double spend too.
+    #[serde(default)]
+    pub double_spenders: Vec<usize>,
+    /// Validators that never propose the commands of some clients.
+    #[serde(default)]
+    pub censors: Vec<Censor>,
+    /// Network partitions between replicas, including twins.
+    #[serde(default)]
+    pub partitions: Vec<PartitionFault>,
+    /// Nodes whose clocks deviate from the global clock. Other clocks are exact.
+    #[serde(default)]
+    pub clocks: Vec<NodeClock>,
+    /// Nodes running on other hardware than the reference one.
+    #[serde(default)]
+    pub hardware: Vec<HardwareProfile>,
+    /// Number of cells of the sketches describing the records of a replica in data-sync
+    /// requests, if any.
+    #[serde(default)]
+    pub sketch_cells: Option<usize>,
+    /// Records received before their parent that each replica keeps for later, if any.
+    #[serde(default)]
+    pub orphan_buffer: Option<OrphanBufferParameters>,
+    /// Whether replicas leave out of their notifications the records sent to the same
+    /// receiver before.
+    #[serde(default)]
+    pub send_dedup: bool,
+    /// Number of past epochs whose records each replica keeps to answer data-sync requests,
+    /// if bounded. At least 1, since notifications carry the commit certificate of the
+    /// previous epoch.
+    #[serde(default)]
+    pub past_epochs_kept: Option<usize>,
+    /// How long replicas keep verifying and storing the blocks and QCs of the previous epoch
+    /// after entering a new one, for stragglers to sync from them, if at all.
+    #[serde(default)]
+    pub epoch_grace_period: Option<Duration>,
+    /// How far ahead of its local clock the time of a block may be for a validator to accept
+    /// it, if bounded.
+    #[serde(default)]
+    pub max_clock_drift: Option<Duration>,
+    /// Number of verified QCs that each replica remembers so as not to verify them again when
+    /// they are received from other peers, if any.
+    #[serde(default)]
+    pub qc_cache: Option<usize>,
+    /// Whether replicas keep their blocks and QCs on disk, with only a bounded number of them
+    /// in memory. Requires the `disk_store` feature.
+    #[serde(default)]
+    pub disk_store: Option<DiskStoreParameters>,
+    /// Adversary delaying network messages on top of the network model, if any.
+    #[serde(default)]
+    pub scheduler: Option<SchedulerParameters>,
+    /// Clients sending requests to validators and waiting for their commit, if any.
+    #[serde(default)]
+    pub client_requests: Option<ClientRequestParameters>,
+    /// Number of rounds between two checkpoints of the commits of each replica, which are
+    /// cross-checked at the end of the run, if any.
+    #[serde(default)]
+    pub checkpoint_interval: Option<usize>,
+    /// Reads of the committed state issued periodically, if any.
+    #[serde(default)]
+    pub reads: Option<ReadParameters>,
+    /// Directory where to write the triage bundle of a divergence between the commits of
+    /// honest replicas, as `divergence_<seed>.json`, if any.
+    #[serde(default)]
+    pub triage_dir: Option<String>,
+    /// Seed of the random network delays. Runs without a seed are not reproducible.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PacemakerParameters {
+    /// Minimal interval between query-all actions when no commit happens.
+    pub target_commit_interval: Duration,
+    /// Maximal duration of the first round after a commit rule.
+    pub delta: Duration,
+    /// Exponent to increase round durations.
+    pub gamma: f64,
+    /// Coefficient to control the frequency of query-all actions.
+    pub lambda: f64,
+    /// Whether leaders propose as soon as they see the certificate of the previous round,
+    /// rather than after waiting for `delta`.
+    pub optimistic_responsiveness: bool,
+    /// Learn `delta` from the observed round durations, starting from the value above.
+    pub adaptive_delta: Option<AdaptiveDeltaParameters>,
+    /// Skip the leaders of the rounds that recently timed out.
+    pub leader_reputation: Option<LeaderReputationParameters>,
+    /// Minimum time between two proposals of the same leader.
+    pub min_proposal_interval: Option<Duration>,
+}
+
+/// Estimation of `delta` as an exponentially weighted moving average of the durations of the
+/// rounds ending with a QC, times a safety margin.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveDeltaParameters {
+    /// Weight of each new round duration in the average, in `(0, 1]`.
+    pub smoothing: f64,
+    /// Factor applied to the average, at least 1.
+    pub margin: f64,
+}
+
+impl Default for AdaptiveDeltaParameters {
+    fn default() -> Self {
+        AdaptiveDeltaParameters {
+            smoothing: 0.125,
+            margin: 2.0,
+        }
+    }
+}
+
+/// Leader reputation: after a round ends with a TC, its leader is not elected again for a while,
+/// unless one of its blocks is committed.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeaderReputationParameters {
+    /// Number of rounds after a timeout during which its leader is skipped.
+    pub exclusion_window: usize,
+}
+
+impl Default for LeaderReputationParameters {
+    fn default() -> Self {
+        LeaderReputationParameters {
+            exclusion_window: 10,
+        }
+    }
+}
+
+/// Super-quorums of the fast path.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FastPathParameters {
+    /// Number of votes that a super-quorum may miss, out of all the votes.
+    pub missing_votes: usize,
+    /// Maximal time during which leaders wait for a super-quorum after reaching a quorum.
+    pub wait: Duration,
+}
+
+impl Default for FastPathParameters {
+    fn default() -> Self {
+        FastPathParameters {
+            missing_votes: 0,
+            wait: 10,
+        }
+    }
+}
+
+/// Voting committees, see `VotingCommittee`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommitteeParameters {
+    /// Number of seats drawn among the validators.
+    pub seats: usize,
+    #[serde(default)]
+    pub rotation: CommitteeRotation,
+}
+
+impl CommitteeParameters {
+    fn committee(&self) -> VotingCommittee {
+        VotingCommittee::new(self.seats, self.rotation)
+    }
+}
+
+/// Validators running one of the consensus instances of a sharded scenario. Each shard has its
+/// own chain, where only its members vote, with their voting rights in the scenario.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Shard {
+    pub members: Vec<usize>,
+}
+
+/// Early-stop conditions of a run. Runs that stop early report why in their outcome.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StopParameters {
+    /// Stop once a validator has committed this many blocks.
+    pub commits: Option<usize>,
+    /// Stop once the run has taken this many milliseconds of real time. Such runs are not
+    /// reproducible.
+    pub wall_clock: Option<u64>,
+    /// Stop at the first divergence between the commits of honest replicas, instead of
+    /// panicking.
+    pub on_violation: bool,
+}
+
+impl StopParameters {
+    pub fn conditions(&self) -> StopConditions {
+        StopConditions {
+            commits: self.commits,
+            wall_clock: self.wall_clock.map(std::time::Duration::from_millis),
+            stop_on_violation: self.on_violation,
+        }
+    }
+}
+
+/// Databases of the disk-backed record stores.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskStoreParameters {
+    /// Directory holding the database of each replica, in `node_<author>`. Databases are
+    /// temporary if missing.
+    pub path: Option<String>,
+    /// Maximal number of blocks, and of QCs, that each record store keeps in memory.
+    pub cache_capacity: usize,
+}
+
+impl Default for DiskStoreParameters {
+    fn default() -> Self {
+        DiskStoreParameters {
+            path: None,
+            cache_capacity: 1000,
+        }
+    }
+}
+
+#[cfg(feature = "disk_store")]
+impl DiskStoreParameters {
+    /// The database of a replica.
+    fn open(&self, author: Author) -> Result<DiskStorage> {
+        match &self.path {
+            Some(path) => DiskStorage::open(
+                Path::new(path).join(format!("node_{}", author.0)),
+                self.cache_capacity,
+            ),
+            None => DiskStorage::temporary(self.cache_capacity),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkParameters {
+    /// The mean value of the network delay.
+    pub mean: f64,
+    /// The variance of the network delay.
+    pub variance: f64,
+    /// Length of the intervals of the bandwidth time series in run reports.
+    pub bandwidth_interval: Duration,
+    /// Probability that a message is lost.
+    pub loss_rate: f64,
+    /// Disseminate broadcasts by gossip rather than sending them to all nodes directly.
+    pub gossip: Option<GossipParameters>,
+    /// Number of nodes, drawn at random, that each query-all action reaches, if not all of
+    /// them.
+    pub query_fanout: Option<usize>,
+    /// Bytes that each link transmits per unit of time, if messages take longer to arrive as
+    /// they grow.
+    pub throughput: Option<f64>,
+    /// Order in which each replica receives the messages of another one: `{ kind =
+    /// "unordered" }`, `{ kind = "fifo" }`, or `{ kind = "bounded", window = <duration> }` for
+    /// messages arriving at most `window` before the ones sent earlier.
+    pub ordering: LinkOrdering,
+    /// Links between given pairs of nodes with their own ordering.
+    pub links: Vec<LinkParameters>,
+}
+
+/// Strategy of an adversarial scheduler.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum SchedulerStrategy {
+    /// Delay the votes that would complete a quorum at their receiver, see
+    /// `QuorumDelayScheduler`.
+    #[serde(rename = "quorum_delay")]
+    QuorumDelay,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulerParameters {
+    pub strategy: SchedulerStrategy,
+    /// Asynchrony bound: the maximal extra delay of a message.
+    pub max_delay: Duration,
+}
+
+/// How clients choose the validator to send a request to. Both follow the redirects of the
+/// validators that are not the leader.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderDiscovery {
+    /// Send to the latest leader learned from a redirect or an accepted request, or to the
+    /// next validator after a timeout.
+    Sticky,
+    /// Send each new request, and each retry, to a random validator.
+    Random,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientRequestParameters {
+    /// Number of clients, each sending one request at a time.
+    pub count: usize,
+    /// Number of requests sent by each client.
+    pub requests: usize,
+    /// Time between the response to a request and the next request of the same client.
+    pub think_time: Duration,
+    /// One-way delay between clients and validators.
+    pub link_delay: Duration,
+    /// Time after which a client sends a request again, elsewhere, if it is not committed.
+    pub retry_timeout: Duration,
+    /// Number of times that a request is tried, not counting redirects, before the client
+    /// gives up.
+    pub max_attempts: usize,
+    /// Interval at which validators check whether the requests that they accepted are
+    /// committed.
+    pub poll_period: Duration,
+    pub discovery: LeaderDiscovery,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadParameters {
+    /// Time between two rounds of reads.
+    pub period: Duration,
+    /// How long after the start of its round a leader serves lease reads.
+    pub lease: Duration,
+    /// Levels of consistency to read at, once per round of reads each.
+    pub consistency: Vec<ReadConsistency>,
+}
+
+/// Cross-shard transactions, see `CrossShardCoordinator`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrossShardParameters {
+    /// Number of transactions, each between two shards drawn at random.
+    pub transactions: usize,
+    /// Time between the starts of two transactions.
+    pub interval: Duration,
+    /// How long the coordinator waits for both shards to prepare a transaction before
+    /// aborting it.
+    pub timeout: Duration,
+    /// Time between two reads of the chains of the shards by the coordinator.
+    pub poll_period: Duration,
+    /// Number of keys locked by transactions, one each, if they may conflict.
+    pub keys: Option<u64>,
+}
+
+/// Ordering of the messages between two nodes, in both directions.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkParameters {
+    pub between: [usize; 2],
+    pub ordering: LinkOrdering,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InboundQueueParameters {
+    /// Maximal number of messages waiting for each node.
+    pub capacity: usize,
+    /// Order in which nodes serve waiting messages: "fifo", "priority" (votes, then
+    /// certificates, proposals, and data sync), or "newest_round_first".
+    pub policy: QueuePolicy,
+}
+
+impl Default for InboundQueueParameters {
+    fn default() -> Self {
+        InboundQueueParameters {
+            capacity: 100,
+            policy: QueuePolicy::Fifo,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrphanBufferParameters {
+    /// Maximal number of records waiting for their parent at each replica.
+    pub capacity: usize,
+    /// Number of rounds behind the current one after which waiting records are dropped.
+    pub max_round_age: usize,
+}
+
+impl Default for OrphanBufferParameters {
+    fn default() -> Self {
+        OrphanBufferParameters {
+            capacity: 64,
+            max_round_age: 3,
+        }
+    }
+}
+
+/// Random regular overlay used to gossip broadcasts.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GossipParameters {
+    /// Number of neighbors of each node, validators and followers alike.
+    pub degree: usize,
+    /// Time spent by a node before forwarding a broadcast, on top of the network delay.
+    pub relay_delay: Duration,
+}
+
+impl Default for GossipParameters {
+    fn default() -> Self {
+        GossipParameters {
+            degree: 8,
+            relay_delay: 1,
+        }
+    }
+}
+
+/// Simulated time spent by nodes on CPU-bound work, delaying their next actions.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpuParameters {
+    /// Time to verify a signature, including each vote of a QC.
+    pub signature_verification: Duration,
+    /// Time to execute a command.
+    pub execution: Duration,
+    /// Number of workers verifying the signatures of data-sync responses in parallel, if
+    /// replicas do.
+    pub verification_workers: Option<usize>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkloadParameters {
+    /// Number of client commands arriving in the mempool of a node for each proposal.
+    pub arrival_rate: usize,
+    /// Mempool depth above which leaders defer low-priority commands, if any.
+    pub mempool_threshold: Option<usize>,
+    /// Largest payload, in bytes, that leaders propose above the mempool threshold, if any.
+    /// Needs `mempool_threshold` and `payload`.
+    pub max_payload_bytes: Option<usize>,
+    /// Number of uncommitted ancestors above which leaders propose empty blocks, if any.
+    pub max_uncommitted_depth: Option<usize>,
+    /// Size of the payload of each command, e.g. `{ distribution = "fixed", bytes = 1000 }` or
+    /// `{ distribution = "uniform", min = 100, max = 10000 }`. Commands have no payload by
+    /// default.
+    pub payload: Option<PayloadSizes>,
+    /// Number of clients submitting commands in turn to each node.
+    pub clients: usize,
+}
+
+/// New voting rights of each validator from the start of the given epoch on, as if decided by
+/// the commands of the previous epoch. Validators without voting rights still follow the chain.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reconfiguration {
+    pub epoch: usize,
+    pub voting_rights: Vec<usize>,
+}
+
+/// A new key for a validator from the start of the given epoch on, as if announced by a command
+/// of the previous epoch. Records signed with its previous key are rejected from then on.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyRotation {
+    pub node: usize,
+    pub epoch: usize,
+}
+
+/// A validator without voting rights before the given epoch, as if added by a command of the
+/// previous one, and offline until `start`. It then bootstraps from a peer's snapshot of the
+/// state starting the current epoch, syncs the records of that epoch, and votes from the given
+/// epoch on. The other validators serve snapshots for it.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Join {
+    pub node: usize,
+    pub epoch: usize,
+    #[serde(default)]
+    pub start: i64,
+}
+
+/// A validator without voting rights from the given epoch on, as if removed by a command of
+/// the previous one. It then follows the chain without proposing, voting or timing out, and the
+/// other validators reject its records.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Removal {
+    pub node: usize,
+    pub epoch: usize,
+}
+
+/// A node that stops processing events during `[start, end)`, or forever if `end` is missing.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CrashFault {
+    pub node: usize,
+    pub start: i64,
+    pub end: Option<i64>,
+}
+
+/// A Byzantine validator that broadcasts `records_per_message` useless records every
+/// `interval`: a block extending an unknown QC, a vote for an unknown block, and timeouts of
+/// past rounds. With `replay_votes`, the vote is instead one of the latest honest votes that
+/// the flooder received, replayed as is or moved to the current epoch and round.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Flooder {
+    pub node: usize,
+    #[serde(default = "default_flood_interval")]
+    pub interval: Duration,
+    #[serde(default = "default_records_per_message")]
+    pub records_per_message: usize,
+    #[serde(default)]
+    pub replay_votes: bool,
+}
+
+/// A Byzantine validator that leaves the commands of the given clients in its mempool instead
+/// of proposing them.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Censor {
+    pub node: usize,
+    pub clients: Vec<usize>,
+}
+
+/// Clock of a node that reads `offset` when the node starts and runs faster by a factor
+/// `1 + drift`, e.g. `drift = -0.001` for a clock that loses one unit of time every 1000.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeClock {
+    pub node: usize,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default)]
+    pub drift: f64,
+}
+
+/// Hardware of a node: `speed` divides its `[cpu]` costs, e.g. `speed = 0.5` for a node twice
+/// as slow as the others, and `upload_throughput` limits the bytes that it sends per unit of
+/// time, if at all.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HardwareProfile {
+    pub node: usize,
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    pub upload_throughput: Option<f64>,
+}
+
+/// Placement of the nodes into named regions. Nodes may belong to no region at all.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Topology {
+    pub regions: BTreeMap<String, Vec<usize>>,
+    /// Mean round-trip time between two nodes of the same region, if it differs from twice
+    /// the mean network delay.
+    pub intra_region_rtt: Option<f64>,
+    /// Mean round-trip time between the nodes of two regions, e.g. `eu = 80.0` under
+    /// `[topology.rtt.us]`. Each pair of regions is given once, in either order.
+    pub rtt: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// Extra network delay between the nodes of two regions during `[start, end)`.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkFault {
+    pub from: String,
+    pub to: String,
+    pub start: i64,
+    pub end: i64,
+    pub extra_delay: Duration,
+}
+
+/// Replicas that only talk to replicas of the same group during `[start, end)`.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PartitionFault {
+    pub groups: Vec<Vec<usize>>,
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Topology {
+    /// Spread `nodes` nodes over the given regions in a round-robin fashion.
+    pub fn round_robin(nodes: usize, regions: &[&str]) -> Topology {
+        let mut topology = Topology::default();
+        for index in 0..nodes {
+            topology
+                .regions
+                .entry(regions[index % regions.len()].to_string())
+                .or_default()
+                .push(index);
+        }
+        topology
+    }
+
+    pub fn nodes(&self, region: &str) -> &[usize] {
+        self.regions.get(region).map_or(&[], |nodes| nodes)
+    }
+
+    /// The region of a node, if any.
+    pub fn region_of(&self, node: usize) -> Option<&str> {
+        self.regions
+            .iter()
+            .find(|(_, nodes)| nodes.contains(&node))
+            .map(|(region, _)| region.as_str())
+    }
+
+    /// Mean round-trip time between the nodes of two regions, if given.
+    pub fn rtt(&self, region1: &str, region2: &str) -> Option<f64> {
+        if region1 == region2 {
+            return self.intra_region_rtt;
+        }
+        let get = |from: &str, to: &str| self.rtt.get(from).and_then(|rtts| rtts.get(to));
+        get(region1, region2)
+            .or_else(|| get(region2, region1))
+            .cloned()
+    }
+}
+
+fn default_resilience() -> usize {
+    DEFAULT_RESILIENCE
+}
+
+pub(crate) fn default_commands_per_epoch() -> usize {
+    30000
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+fn default_flood_interval() -> Duration {
+    5
+}
+
+fn default_records_per_message() -> usize {
+    20
+}
+
+impl Default for PacemakerParameters {
+    fn default() -> Self {
+        PacemakerParameters {
+            target_commit_interval: 100_000,
+            delta: 20,
+            gamma: 2.0,
+            lambda: 0.5,
+            optimistic_responsiveness: true,
+            adaptive_delta: None,
+            leader_reputation: None,
+            min_proposal_interval: None,
+        }
+    }
+}
+
+impl Default for NetworkParameters {
+    fn default() -> Self {
+        NetworkParameters {
+            mean: 10.0,
+            variance: 4.0,
+            bandwidth_interval: 1000,
+            loss_rate: 0.0,
+            gossip: None,
+            query_fanout: None,
+            throughput: None,
+            ordering: LinkOrdering::default(),
+            links: Vec::new(),
+        }
+    }
+}
+
+impl Default for ClientRequestParameters {
+    fn default() -> Self {
+        ClientRequestParameters {
+            count: 1,
+            requests: 10,
+            think_time: 0,
+            link_delay: 10,
+            retry_timeout: 1000,
+            max_attempts: 5,
+            poll_period: 10,
+            discovery: LeaderDiscovery::Sticky,
+        }
+    }
+}
+
+impl Default for ReadParameters {
+    fn default() -> Self {
+        ReadParameters {
+            period: 50,
+            lease: 100,
+            consistency: vec![
+                ReadConsistency::Local,
+                ReadConsistency::LeaderLease,
+                ReadConsistency::Quorum,
+            ],
+        }
+    }
+}
+
+impl Default for CrossShardParameters {
+    fn default() -> Self {
+        CrossShardParameters {
+            transactions: 20,
+            interval: 50,
+            timeout: 500,
+            poll_period: 5,
+            keys: None,
+        }
+    }
+}
+
+impl Default for WorkloadParameters {
+    fn default() -> Self {
+        WorkloadParameters {
+            arrival_rate: 1,
+            mempool_threshold: None,
+            max_payload_bytes: None,
+            max_uncommitted_depth: None,
+            payload: None,
+            clients: 1,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn from_toml(data: &str) -> Result<Scenario> {
+        let scenario: Scenario = toml::from_str(data)?;
+        scenario.check()?;
+        Ok(scenario)
+    }
+
+    pub fn load(path: &str) -> Result<Scenario> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        ensure!(self.nodes > 0, "A scenario needs at least one node.");
+        ensure!(
+            self.resilience >= 2,
+            "Quorums must intersect: the resilience must be at least 2."
+        );
+        ensure!(
+            self.network.bandwidth_interval > 0,
+            "Bandwidth intervals must not be empty."
+        );
+        ensure!(
+            0.0 <= self.network.loss_rate && self.network.loss_rate < 1.0,
+            "The loss rate must be in [0, 1)."
+        );
+        if let Some(throughput) = self.network.throughput {
+            ensure!(throughput > 0.0, "The link throughput must be positive.");
+        }
+        if let Some(PayloadSizes::Uniform { min, max }) = self.workload.payload {
+            ensure!(min <= max, "Payload sizes must be in a non-empty range.");
+        }
+        if self.workload.max_payload_bytes.is_some() {
+            ensure!(
+                self.workload.mempool_threshold.is_some() && self.workload.payload.is_some(),
+                "Capping payload sizes under overload needs a mempool threshold and payload sizes."
+            );
+        }
+        if let Some(inbound_queue) = &self.inbound_queue {
+            ensure!(
+                inbound_queue.capacity > 0,
+                "Inbound queues must hold at least one message."
+            );
+        }
+        if let Some(orphan_buffer) = &self.orphan_buffer {
+            ensure!(
+                orphan_buffer.capacity > 0,
+                "Orphan buffers must hold at least one record."
+            );
+        }
+        if let Some(past_epochs_kept) = self.past_epochs_kept {
+            ensure!(
+                past_epochs_kept > 0,
+                "Replicas must keep the records of at least one past epoch."
+            );
+        }
+        if let Some(epoch_grace_period) = self.epoch_grace_period {
+            ensure!(
+                epoch_grace_period >= 0,
+                "The epoch grace period must not be negative."
+            );
+        }
+        if let Some(max_clock_drift) = self.max_clock_drift {
+            ensure!(
+                max_clock_drift >= 0,
+                "The maximal clock drift must not be negative."
+            );
+        }
+        if let Some(qc_cache) = self.qc_cache {
+            ensure!(qc_cache > 0, "QC caches must hold at least one QC.");
+        }
+        ensure!(
+            self.checkpoint_interval != Some(0),
+            "Checkpoints need a positive interval."
+        );
+        if let Some(stop) = &self.stop {
+            ensure!(
+                stop.commits != Some(0),
+                "Runs must stop after at least one commit."
+            );
+        }
+        if let Some(disk_store) = &self.disk_store {
+            ensure!(
+                cfg!(feature = "disk_store"),
+                "Disk-backed record stores require the disk_store feature."
+            );
+            ensure!(
+                disk_store.cache_capacity > 0,
+                "Record caches must hold at least one record."
+            );
+            ensure!(
+                disk_store.path.is_none() || self.twins.is_empty(),
+                "Twins cannot share the database of their validator."
+            );
+        }
+        if let Some(gossip) = &self.network.gossip {
+            let num_nodes = self.nodes + self.followers;
+            ensure!(
+                gossip.degree > 0 && gossip.degree < num_nodes,
+                "The gossip degree must be in [1, nodes + followers)."
+            );
+            ensure!(
+                (gossip.degree * num_nodes).is_multiple_of(2),
+                "The gossip degree or the number of nodes must be even."
+            );
+            ensure!(
+                gossip.relay_delay >= 0,
+                "The gossip relay delay must not be negative."
+            );
+        }
+        ensure!(
+            self.network.query_fanout != Some(0),
+            "Query-all actions must reach at least one node."
+        );
+        for ordering in std::iter::once(&self.network.ordering)
+            .chain(self.network.links.iter().map(|link| &link.ordering))
+        {
+            if let LinkOrdering::Bounded { window } = ordering {
+                ensure!(*window >= 0, "Reordering windows must not be negative.");
+            }
+        }
+        if let Some(scheduler) = &self.scheduler {
+            ensure!(
+                scheduler.max_delay >= 0,
+                "The asynchrony bound must not be negative."
+            );
+        }
+        if let Some(clients) = &self.client_requests {
+            ensure!(
+                clients.count > 0 && clients.max_attempts > 0,
+                "Client requests need at least one client and one attempt."
+            );
+            ensure!(
+                clients.think_time >= 0 && clients.link_delay >= 0,
+                "Client delays must not be negative."
+            );
+            ensure!(
+                clients.retry_timeout > 0 && clients.poll_period > 0,
+                "Client timeouts and polling periods must be positive."
+            );
+        }
+        if let Some(reads) = &self.reads {
+            ensure!(
+                reads.period > 0 && reads.lease >= 0,
+                "Reads need a positive period and a non-negative lease."
+            );
+        }
+        if let Some(cross_shard) = &self.cross_shard {
+            ensure!(
+                self.shards.len() >= 2,
+                "Cross-shard transactions need at least two shards."
+            );
+            ensure!(
+                cross_shard.interval >= 0 && cross_shard.timeout > 0 && cross_shard.poll_period > 0,
+                "Cross-shard transactions need a non-negative interval, and a positive timeout \
+                 and poll period."
+            );
+            ensure!(
+                cross_shard.keys != Some(0),
+                "Transactions need at least one key to lock."
+            );
+        }
+        for link in &self.network.links {
+            let [node1, node2] = link.between;
+            ensure!(
+                node1 != node2 && std::cmp::max(node1, node2) < self.nodes + self.followers,
+                "Links must be between two distinct nodes."
+            );
+        }
+        ensure!(
+            self.cpu.signature_verification >= 0 && self.cpu.execution >= 0,
+            "CPU costs must not be negative."
+        );
+        if let Some(verification_workers) = self.cpu.verification_workers {
+            ensure!(
+                verification_workers > 0,
+                "Signature verification needs at least one worker."
+            );
+        }
+        if let Some(adaptive_delta) = &self.pacemaker.adaptive_delta {
+            ensure!(
+                0.0 < adaptive_delta.smoothing && adaptive_delta.smoothing <= 1.0,
+                "The smoothing factor of delta must be in (0, 1]."
+            );
+            ensure!(
+                adaptive_delta.margin >= 1.0,
+                "The safety margin of delta must be at least 1."
+            );
+        }
+        if let Some(leader_reputation) = &self.pacemaker.leader_reputation {
+            ensure!(
+                leader_reputation.exclusion_window > 0,
+                "The exclusion window of leader reputation must not be empty."
+            );
+        }
+        if let Some(min_proposal_interval) = self.pacemaker.min_proposal_interval {
+            ensure!(
+                min_proposal_interval >= 0,
+                "The minimum proposal interval must not be negative."
+            );
+        }
+        if let Some(fast_path) = &self.fast_path {
+            ensure!(
+                self.commit_rule.rule().chain_length() > 2,
+                "The fast path needs a commit rule with chains of at least 3 QCs."
+            );
+            ensure!(
+                fast_path.wait >= 0,
+                "The fast-path wait must not be negative."
+            );
+        }
+        if let Some(committee) = &self.committee {
+            ensure!(committee.seats > 0, "Committees need at least one seat.");
+        }
+        if !self.voting_rights.is_empty() {
+            self.check_voting_rights(&self.voting_rights)?;
+        }
+        if !self.shards.is_empty() {
+            ensure!(
+                self.followers == 0
+                    && self.twins.is_empty()
+                    && self.reconfigurations.is_empty()
+                    && self.joins.is_empty()
+                    && self.removals.is_empty()
+                    && self.scheduler.is_none()
+                    && self.network.gossip.is_none()
+                    && self.client_requests.is_none()
+                    && self.reads.is_none(),
+                "Shards do not support followers, twins, changes of the validator set, \
+                 schedulers, gossip, client requests, or reads."
+            );
+        }
+        for (index, shard) in self.shards.iter().enumerate() {
+            let members: BTreeSet<_> = shard.members.iter().collect();
+            ensure!(
+                !members.is_empty() && members.len() == shard.members.len(),
+                "Shard {} must have distinct members",
+                index
+            );
+            for member in &shard.members {
+                ensure!(
+                    *member < self.nodes,
+                    "Shard {} refers to unknown node {}",
+                    index,
+                    member
+                );
+            }
+            self.check_voting_rights(&self.shard_voting_rights(index))?;
+        }
+        if let Some(max_depth) = self.workload.max_uncommitted_depth {
+            ensure!(
+                max_depth + 1 >= self.commit_rule.rule().chain_length(),
+                "Back-pressure must allow the uncommitted blocks of a commit chain."
+            );
+        }
+        for reconfiguration in &self.reconfigurations {
+            ensure!(
+                reconfiguration.epoch > 0,
+                "Reconfigurations must happen after the first epoch."
+            );
+            self.check_voting_rights(&reconfiguration.voting_rights)?;
+        }
+        for rotation in &self.key_rotations {
+            ensure!(
+                rotation.node < self.nodes,
+                "Key rotation refers to unknown node {}",
+                rotation.node
+            );
+            ensure!(
+                rotation.epoch > 0,
+                "Key rotations must happen after the first epoch."
+            );
+        }
+        let mut joining_nodes = BTreeSet::new();
+        for join in &self.joins {
+            ensure!(
+                join.node < self.nodes,
+                "Join refers to unknown node {}",
+                join.node
+            );
+            ensure!(
+                joining_nodes.insert(join.node),
+                "Node {} joins twice",
+                join.node
+            );
+            ensure!(
+                join.epoch > 0,
+                "Validators must join after the first epoch."
+            );
+            ensure!(join.start >= 0, "Join times must not be negative.");
+        }
+        let mut removed_nodes = BTreeSet::new();
+        for removal in &self.removals {
+            ensure!(
+                removal.node < self.nodes,
+                "Removal refers to unknown node {}",
+                removal.node
+            );
+            ensure!(
+                removed_nodes.insert(removal.node),
+                "Node {} is removed twice",
+                removal.node
+            );
+            ensure!(
+                removal.epoch > 0,
+                "Validators must be removed after the first epoch."
+            );
+            ensure!(
+                self.joins
+                    .iter()
+                    .all(|join| join.node != removal.node || join.epoch < removal.epoch),
+                "Node {} must be removed after it joins",
+                removal.node
+            );
+        }
+        ensure!(
+            removed_nodes.len() < self.nodes,
+            "Some validators must remain after the removals."
+        );
+        for fault in &self.faults {
+            ensure!(
+                fault.node < self.nodes,
+                "Fault refers to unknown node {}",
+                fault.node
+            );
+            ensure!(
+                fault.end.is_none_or(|end| fault.start < end),
+                "Fault intervals must not be empty."
+            );
+        }
+        for nodes in self.topology.regions.values() {
+            for node in nodes {
+                ensure!(
+                    *node < self.nodes,
+                    "Topology refers to unknown node {}",
+                    node
+                );
+            }
+        }
+        for (region, rtts) in &self.topology.rtt {
+            for (other, rtt) in rtts {
+                ensure!(
+                    self.topology.regions.contains_key(region)
+                        && self.topology.regions.contains_key(other),
+                    "Round-trip time refers to unknown region {} or {}",
+                    region,
+                    other
+                );
+                ensure!(*rtt > 0.0, "Round-trip times must be positive.");
+            }
+        }
+        if !self.topology.rtt.is_empty() {
+            for region in self.topology.regions.keys() {
+                for other in self.topology.regions.keys() {
+                    ensure!(
+                        region == other || self.topology.rtt(region, other).is_some(),
+                        "Missing round-trip time between regions {} and {}",
+                        region,
+                        other
+                    );
+                }
+            }
+        }
+        ensure!(
+            self.topology.intra_region_rtt.is_none_or(|rtt| rtt > 0.0),
+            "Round-trip times must be positive."
+        );
+        for fault in &self.link_faults {
+            for region in &[&fault.from, &fault.to] {
+                ensure!(
+                    self.topology.regions.contains_key(*region),
+                    "Link fault refers to unknown region {}",
+                    region
+                );
+            }
+            ensure!(
+                fault.start < fault.end,
+                "Fault intervals must not be empty."
+            );
+        }
+        for node in &self.twins {
+            ensure!(*node < self.nodes, "Twin refers to unknown node {}", node);
+        }
+        let mut flooding_nodes = BTreeSet::new();
+        for flooder in &self.flooders {
+            ensure!(
+                flooder.node < self.nodes,
+                "Flooder refers to unknown node {}",
+                flooder.node
+            );
+            ensure!(
+                flooding_nodes.insert(flooder.node),
+                "Flooder {} is given twice",
+                flooder.node
+            );
+            ensure!(
+                flooder.interval > 0 && flooder.records_per_message > 0,
+                "Flooders must send at least one record at positive intervals."
+            );
+            ensure!(
+                !flooder.replay_votes || flooder.records_per_message >= 2,
+                "Flooders replaying votes must send at least two records, the second one being the vote."
+            );
+        }
+        for node in &self.lock_violators {
+            ensure!(
+                *node < self.nodes && !flooding_nodes.contains(node),
+                "Lock violator refers to unknown node or flooder {}",
+                node
+            );
+        }
+        for node in &self.qc_withholders {
+            ensure!(
+                *node < self.nodes && !flooding_nodes.contains(node),
+                "QC withholder refers to unknown node or flooder {}",
+                node
+            );
+        }
+        for node in &self.double_spenders {
+            ensure!(
+                *node < self.nodes && !flooding_nodes.contains(node),
+                "Double spender refers to unknown node or flooder {}",
+                node
+            );
+        }
+        ensure!(
+            self.workload.clients > 0,
+            "Commands must be submitted by at least one client."
+        );
+        for censor in &self.censors {
+            ensure!(
+                censor.node < self.nodes && !flooding_nodes.contains(&censor.node),
+                "Censor refers to unknown node or flooder {}",
+                censor.node
+            );
+            ensure!(
+                !censor.clients.is_empty()
+                    && censor
+                        .clients
+                        .iter()
+                        .all(|client| *client < self.workload.clients),
+                "Censor {} must refer to known clients",
+                censor.node
+            );
+        }
+        let mut skewed_nodes = BTreeSet::new();
+        for clock in &self.clocks {
+            ensure!(
+                clock.node < self.nodes + self.followers,
+                "Clock refers to unknown node {}",
+                clock.node
+            );
+            ensure!(
+                skewed_nodes.insert(clock.node),
+                "Clock of node {} is given twice",
+                clock.node
+            );
+            ensure!(
+                -1.0 < clock.drift && clock.drift < 1.0,
+                "Clock drifts must be in (-1, 1)."
+            );
+        }
+        let mut profiled_nodes = BTreeSet::new();
+        for profile in &self.hardware {
+            ensure!(
+                profile.node < self.nodes + self.followers,
+                "Hardware profile refers to unknown node {}",
+                profile.node
+            );
+            ensure!(
+                profiled_nodes.insert(profile.node),
+                "Hardware of node {} is given twice",
+                profile.node
+            );
+            ensure!(
+                profile.speed > 0.0
+                    && profile
+                        .upload_throughput
+                        .is_none_or(|throughput| throughput > 0.0),
+                "Hardware speeds and upload throughputs must be positive."
+            );
+        }
+        let replicas = self.nodes + self.followers + self.twins.len();
+        for fault in &self.partitions {
+            for replica in fault.groups.iter().flatten() {
+                ensure!(
+                    *replica < replicas,
+                    "Partition refers to unknown replica {}",
+                    replica
+                );
+            }
+            ensure!(
+                fault.start < fault.end,
+                "Fault intervals must not be empty."
+            );
+        }
+        Ok(())
+    }
+
+    fn check_voting_rights(&self, voting_rights: &[usize]) -> Result<()> {
+        ensure!(
+            voting_rights.len() == self.nodes,
+            "Voting rights must be given for each of the {} nodes.",
+            self.nodes
+        );
+        ensure!(
+            voting_rights.iter().sum::<usize>() > 0,
+            "Validators need some voting rights."
+        );
+        Ok(())
+    }
+
+    /// Voting rights of the members of the given shard, as in the scenario. Other validators
+    /// have none.
+    fn shard_voting_rights(&self, shard: usize) -> Vec<usize> {
+        (0..self.nodes)
+            .map(|node| {
+                if self.shards[shard].members.contains(&node) {
+                    self.voting_rights.get(node).cloned().unwrap_or(1)
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+
+    /// The scenario of a single shard: only its members vote, on a chain of its own.
+    pub fn shard_scenario(&self, shard: usize) -> Scenario {
+        let mut scenario = self.clone();
+        scenario.voting_rights = self.shard_voting_rights(shard);
+        scenario.chain_id = ChainId(self.chain_id.0 + shard as u64);
+        scenario.shards = Vec::new();
+        scenario
+    }
+
+    /// Hash function of the records of the nodes built by `make_node`: the one of the scenario,
+    /// or FNV-1a by default since scenarios are simulations unless they are deployed.
+    pub fn hash_function(&self) -> HashFunction {
+        self.hash_function.unwrap_or(HashFunction::SIMULATION)
+    }
+
+    /// Hash records with SHA3-256 unless the scenario chooses a hash function, e.g. when its
+    /// nodes run as separate processes.
+    pub fn deploy(&mut self) {
+        self.hash_function.get_or_insert(HashFunction::default());
+    }
+
+    /// Create the context of a node. Only validators have voting rights.
+    pub fn make_context(&self, author: Author) -> SimulatedContext {
+        let mut context = SimulatedContext::new(author, self.nodes, self.commands_per_epoch);
+        if !self.voting_rights.is_empty() {
+            context.set_voting_rights(self.voting_rights.clone());
+        }
+        for reconfiguration in &self.reconfigurations {
+            context.schedule_voting_rights(
+                EpochId(reconfiguration.epoch),
+                reconfiguration.voting_rights.clone(),
+            );
+        }
+        for rotation in &self.key_rotations {
+            context.schedule_key_rotation(EpochId(rotation.epoch), Author(rotation.node));
+        }
+        for join in &self.joins {
+            context.schedule_join(EpochId(join.epoch), Author(join.node));
+        }
+        for removal in &self.removals {
+            context.schedule_removal(EpochId(removal.epoch), Author(removal.node));
+        }
+        context.set_resilience(self.resilience);
+        if let Some(keys) = self
+            .cross_shard
+            .as_ref()
+            .and_then(|cross_shard| cross_shard.keys)
+        {
+            context.set_lock_keys(keys);
+        }
+        context.set_arrival_rate(self.workload.arrival_rate);
+        context.set_clients(self.workload.clients);
+        if let Some(payload) = self.workload.payload {
+            context.set_payload_sizes(payload);
+        }
+        let speed = self
+            .hardware
+            .iter()
+            .find(|profile| profile.node == author.0)
+            .map_or(1.0, |profile| profile.speed);
+        let cost = |reference: Duration| (reference as f64 / speed).round() as Duration;
+        context.set_cpu_costs(
+            cost(self.cpu.signature_verification),
+            cost(self.cpu.execution),
+        );
+        if let Some(verification_workers) = self.cpu.verification_workers {
+            context.set_verification_workers(verification_workers);
+        }
+        context
+    }
+
+    /// Create a validator, or a follower if `author` is not among the first `nodes` authors.
+    /// Validators listed in `flooders`, `lock_violators`, `qc_withholders`, `double_spenders`
+    /// or `censors` are Byzantine.
+    pub fn make_node(
+        &self,
+        author: Author,
+        context: &SimulatedContext,
+        clock: NodeTime,
+    ) -> Participant {
+        if author.0 >= self.nodes {
+            let mut follower = FollowerState::new(
+                author,
+                context.last_committed_state(),
+                clock,
+                self.pacemaker.target_commit_interval,
+                context,
+            );
+            follower.set_commit_rule(self.commit_rule.rule());
+            follower.set_hash_function(self.hash_function());
+            follower.set_chain_id(self.chain_id);
+            if let Some(committee) = &self.committee {
+                follower.set_committee(committee.committee());
+            }
+            if let Some(sketch_cells) = self.sketch_cells {
+                follower.set_sketch_cells(sketch_cells);
+            }
+            if let Some(orphan_buffer) = &self.orphan_buffer {
+                follower.set_orphan_buffer(orphan_buffer.capacity, orphan_buffer.max_round_age);
+            }
+            if self.send_dedup {
+                follower.set_send_dedup();
+            }
+            if let Some(past_epochs_kept) = self.past_epochs_kept {
+                follower.set_past_epochs_kept(past_epochs_kept);
+            }
+            if let Some(epoch_grace_period) = self.epoch_grace_period {
+                follower.set_epoch_grace_period(epoch_grace_period);
+            }
+            if let Some(qc_cache) = self.qc_cache {
+                follower.set_quorum_certificate_cache(qc_cache);
+            }
+            if let Some(interval) = self.checkpoint_interval {
+                follower.set_checkpoint_interval(interval);
+            }
+            if self.cpu.verification_workers.is_some() {
+                follower.set_parallel_verification();
+            }
+            #[cfg(feature = "disk_store")]
+            {
+                if let Some(disk_store) = &self.disk_store {
+                    if let Err(error) = disk_store
+                        .open(author)
+                        .and_then(|storage| follower.set_disk_storage(storage))
+                    {
+                        error!(
+                            "Follower {:?} keeps its records in memory: {}",
+                            author, error
+                        );
+                    }
+                }
+            }
+            return Participant::Follower(follower);
+        }
+        if let Some(flooder) = self
+            .flooders
+            .iter()
+            .find(|flooder| flooder.node == author.0)
+        {
+            let replay_votes = flooder.replay_votes;
+            let mut flooder = FloodingNode::new(
+                author,
+                context.last_committed_state(),
+                clock,
+                self.pacemaker.target_commit_interval,
+                flooder.interval,
+                flooder.records_per_message,
+                context,
+            );
+            flooder.set_commit_rule(self.commit_rule.rule());
+            flooder.set_hash_function(self.hash_function());
+            flooder.set_chain_id(self.chain_id);
+            if let Some(committee) = &self.committee {
+                flooder.set_committee(committee.committee());
+            }
+            if replay_votes {
+                flooder.set_vote_replay();
+            }
+            return Participant::Flooder(flooder);
+        }
+        let mut node = NodeState::new(
+            author,
+            context.last_committed_state(),
+            clock,
+            self.pacemaker.target_commit_interval,
+            self.pacemaker.delta,
+            self.pacemaker.gamma,
+            self.pacemaker.lambda,
+            context,
+        );
+        if let Some(mempool_threshold) = self.workload.mempool_threshold {
+            node.set_load_shedding_policy(LoadSheddingPolicy {
+                mempool_threshold,
+                max_payload_bytes: self.workload.max_payload_bytes,
+            });
+        }
+        if let Some(max_uncommitted_depth) = self.workload.max_uncommitted_depth {
+            node.set_max_uncommitted_depth(max_uncommitted_depth);
+        }
+        node.set_optimistic_responsiveness(self.pacemaker.optimistic_responsiveness);
+        if let Some(adaptive_delta) = &self.pacemaker.adaptive_delta {
+            node.set_adaptive_delta(AdaptiveDelta::new(
+                adaptive_delta.smoothing,
+                adaptive_delta.margin,
+            ));
+        }
+        if let Some(leader_reputation) = &self.pacemaker.leader_reputation {
+            node.set_leader_reputation(LeaderReputation::new(leader_reputation.exclusion_window));
+        }
+        // Validators never propose twice in a round: simulations turn any violation into a panic.
+        node.set_proposal_limiter(self.pacemaker.min_proposal_interval.unwrap_or(0), true);
+        node.set_commit_rule(self.commit_rule.rule());
+        node.set_hash_function(self.hash_function());
+        node.set_chain_id(self.chain_id);
+        if let Some(fast_path) = &self.fast_path {
+            node.set_fast_path(FastPath {
+                missing_votes: fast_path.missing_votes,
+                wait: fast_path.wait,
+            });
+        }
+        if let Some(committee) = &self.committee {
+            node.set_committee(committee.committee());
+        }
+        if let Some(sketch_cells) = self.sketch_cells {
+            node.set_sketch_cells(sketch_cells);
+        }
+        if let Some(orphan_buffer) = &self.orphan_buffer {
+            node.set_orphan_buffer(orphan_buffer.capacity, orphan_buffer.max_round_age);
+        }
+        if self.send_dedup {
+            node.set_send_dedup();
+        }
+        if let Some(past_epochs_kept) = self.past_epochs_kept {
+            node.set_past_epochs_kept(past_epochs_kept);
+        }
+        if let Some(epoch_grace_period) = self.epoch_grace_period {
+            node.set_epoch_grace_period(epoch_grace_period);
+        }
+        if let Some(max_clock_drift) = self.max_clock_drift {
+            node.set_max_clock_drift(max_clock_drift);
+        }
+        if let Some(qc_cache) = self.qc_cache {
+            node.set_quorum_certificate_cache(qc_cache);
+        }
+        if let Some(interval) = self.checkpoint_interval {
+            node.set_checkpoint_interval(interval);
+        }
+        if self.cpu.verification_workers.is_some() {
+            node.set_parallel_verification();
+        }
+        if !self.joins.is_empty() {
+            node.set_snapshot_sync();
+        }
+        #[cfg(feature = "disk_store")]
+        {
+            if let Some(disk_store) = &self.disk_store {
+                if let Err(error) = disk_store
+                    .open(author)
+                    .and_then(|storage| node.set_disk_storage(storage))
+                {
+                    error!(
+                        "Replica {:?} keeps its records in memory: {}",
+                        author, error
+                    );
+                }
+            }
+        }
+        if self.lock_violators.contains(&author.0) {
+            node.set_lock_violation();
+        }
+        if self.qc_withholders.contains(&author.0) {
+            node.set_quorum_certificate_withholding();
+        }
+        if self.double_spenders.contains(&author.0) {
+            node.set_double_spending();
+        }
+        for censor in &self.censors {
+            if censor.node == author.0 {
+                node.set_censored_clients(censor.clients.iter().cloned().collect());
+            }
+        }
+        Participant::Validator(node)
+    }
+
+    /// Create the nodes and schedule the faults of the scenario. Shards are ignored, see
+    /// `build_sharded_simulator`.
+    pub fn build_simulator(&self) -> NodeSimulator {
+        let context_factory = |author, _| self.make_context(author);
+        let node_factory =
+            |author, context: &SimulatedContext, clock| self.make_node(author, context, clock);
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut sim = NodeSimulator::with_clock_skews(
+            seed,
+            self.clock_skews(),
+            RandomDelay::new(self.network.mean, self.network.variance),
+            &context_factory,
+            &node_factory,
+        );
+        self.configure_network(&mut sim, seed);
+        if let Some(scheduler) = &self.scheduler {
+            let context = sim.simulated_node(Author(0)).context();
+            let configuration = context.configuration(&context.last_committed_state());
+            let strategy = match scheduler.strategy {
+                SchedulerStrategy::QuorumDelay => {
+                    QuorumDelayScheduler::new(configuration, scheduler.max_delay)
+                }
+            };
+            sim.set_scheduler(Box::new(strategy), scheduler.max_delay);
+        }
+        for node in &self.twins {
+            sim.add_twin(Author(*node), &context_factory, &node_factory);
+        }
+        self.schedule_faults(&mut sim);
+        sim
+    }
+
+    /// Create the nodes of all the shards of the scenario, where each validator runs the
+    /// consensus instances of its shards, and schedule the faults of the scenario.
+    pub fn build_sharded_simulator(&self) -> ShardedSimulator {
+        let scenarios: Vec<_> = (0..self.shards.len())
+            .map(|shard| self.shard_scenario(shard))
+            .collect();
+        let context_factory = |author, _| ShardedContext::new(&scenarios, author);
+        let node_factory = |author, context: &ShardedContext, clock| {
+            ShardedNode::new(&scenarios, author, context, clock)
+        };
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut sim = ShardedSimulator::with_clock_skews(
+            seed,
+            self.clock_skews(),
+            RandomDelay::new(self.network.mean, self.network.variance),
+            &context_factory,
+            &node_factory,
+        );
+        self.configure_network(&mut sim, seed);
+        self.schedule_faults(&mut sim);
+        sim
+    }
+
+    fn clock_skews(&self) -> Vec<ClockSkew> {
+        let mut clock_skews = vec![ClockSkew::default(); self.nodes + self.followers];
+        for clock in &self.clocks {
+            clock_skews[clock.node] = ClockSkew {
+                offset: clock.offset,
+                drift: clock.drift,
+            };
+        }
+        clock_skews
+    }
+
+    /// Set up the links, the messages and the queues of the scenario.
+    fn configure_network<Node, Context, Notification, Request, Response>(
+        &self,
+        sim: &mut Simulator<Node, Context, Notification, Request, Response>,
+        seed: u64,
+    ) where
+        Notification: Ord + Debug,
+        Request: Ord + Debug,
+        Response: Ord + Debug,
+    {
+        sim.set_loss_rate(self.network.loss_rate);
+        sim.set_link_ordering(self.network.ordering);
+        for link in &self.network.links {
+            let [node1, node2] = link.between;
+            sim.set_link_ordering_between(Author(node1), Author(node2), link.ordering);
+        }
+        if let Some(triage_dir) = &self.triage_dir {
+            fs::create_dir_all(triage_dir).expect("Failed to create the triage directory");
+            let path = Path::new(triage_dir).join(format!("divergence_{}.json", seed));
+            sim.set_triage_path(path.to_string_lossy().into_owned());
+        }
+        if let Some(payload) = self.workload.payload {
+            sim.set_payload_sizes(payload);
+        }
+        if let Some(throughput) = self.network.throughput {
+            sim.set_link_throughput(throughput);
+        }
+        if let Some(gossip) = &self.network.gossip {
+            sim.enable_gossip(gossip.degree, gossip.relay_delay);
+        }
+        if let Some(fanout) = self.network.query_fanout {
+            sim.sample_queries(fanout);
+        }
+        if let Some(inbound_queue) = &self.inbound_queue {
+            sim.set_inbound_queues(inbound_queue.capacity, inbound_queue.policy);
+        }
+    }
+
+    /// Set up the regions and the hardware of the scenario, and schedule its faults.
+    fn schedule_faults<Node, Context, Notification, Request, Response>(
+        &self,
+        sim: &mut Simulator<Node, Context, Notification, Request, Response>,
+    ) where
+        Notification: Ord + Debug,
+        Request: Ord + Debug,
+        Response: Ord + Debug,
+    {
+        for node1 in 0..self.nodes {
+            for node2 in node1 + 1..self.nodes {
+                let rtt = match (
+                    self.topology.region_of(node1),
+                    self.topology.region_of(node2),
+                ) {
+                    (Some(region1), Some(region2)) => self.topology.rtt(region1, region2),
+                    _ => None,
+                };
+                if let Some(rtt) = rtt {
+                    sim.set_link_delay(
+                        Author(node1),
+                        Author(node2),
+                        RandomDelay::new(rtt / 2.0, self.network.variance),
+                    );
+                }
+            }
+        }
+        for profile in &self.hardware {
+            if let Some(upload_throughput) = profile.upload_throughput {
+                sim.set_upload_throughput(Author(profile.node), upload_throughput);
+            }
+        }
+        for join in &self.joins {
+            if join.start > 0 {
+                sim.add_crash(Author(join.node), GlobalTime(0), GlobalTime(join.start));
+            }
+        }
+        for fault in &self.faults {
+            sim.add_crash(
+                Author(fault.node),
+                GlobalTime(fault.start),
+                fault.end.map_or(GlobalTime::never(), GlobalTime),
+            );
+        }
+        let authors = |region: &str| -> BTreeSet<Author> {
+            self.topology
+                .nodes(region)
+                .iter()
+                .map(|node| Author(*node))
+                .collect()
+        };
+        for fault in &self.link_faults {
+            sim.add_link_degradation(LinkDegradation {
+                group_a: authors(&fault.from),
+                group_b: authors(&fault.to),
+                start: GlobalTime(fault.start),
+                end: GlobalTime(fault.end),
+                extra_delay: fault.extra_delay,
+            });
+        }
+        for fault in &self.partitions {
+            sim.add_partition(Partition {
+                groups: fault
+                    .groups
+                    .iter()
+                    .map(|group| group.iter().cloned().collect())
+                    .collect(),
+                start: GlobalTime(fault.start),
+                end: GlobalTime(fault.end),
+            });
+        }
+    }
+}
+
+/// Intervals `[start, end)` of the run of `sim` during which the crash faults leave less than a
+/// quorum of live validators, so that commits are impossible rather than merely slow.
+pub fn quorum_impossible_intervals(sim: &NodeSimulator, max_clock: i64) -> Vec<(i64, i64)> {
+    let context = sim.simulated_node(Author(0)).context();
+    let configuration = context.configuration(&context.last_committed_state());
+    sim.quorum_impossible_intervals(&configuration, GlobalTime(max_clock))
+        .into_iter()
+        .map(|(start, end)| (start.0, end.0))
+        .collect()
+}
+
+/// Run a scenario to completion and return the final SMR context of each replica, followers
+/// included.
+pub fn run_scenario(scenario: &Scenario) -> Vec<SimulatedContext> {
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None)
+        .into_iter()
+        .cloned()
+        .collect()
+}
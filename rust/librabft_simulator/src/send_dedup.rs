@@ -0,0 +1,66 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Records already sent to each peer, so that regular broadcasts do not carry the same
+//! certificates, timeouts, and proposals over and over again.
+
+use super::*;
+
+#[cfg(test)]
+#[path = "unit_tests/send_dedup_tests.rs"]
+mod send_dedup_tests;
+
+/// Kinds of records, as named by `Record::kind`.
+const KINDS: [&str; 4] = ["block", "vote", "quorum_certificate", "timeout"];
+
+/// Records sent to each peer, as one bitmap per epoch and round where bit `author * 4 + kind`
+/// tells whether the record of this kind and author was sent. Honest nodes create at most one
+/// record of each kind per round, so a set bit means that the record is a copy.
+#[derive(Clone, Debug, Default)]
+pub struct SentRecords {
+    peers: BTreeMap<Author, BTreeMap<(EpochId, Round), Vec<u64>>>,
+}
+
+impl SentRecords {
+    /// Mark the record of the given epoch, round, author, and kind (see `Record::kind`) as
+    /// sent to a peer. Return whether it was not already.
+    pub fn insert(
+        &mut self,
+        peer: Author,
+        epoch_id: EpochId,
+        round: Round,
+        author: Author,
+        kind: &str,
+    ) -> bool {
+        let kind_index = KINDS
+            .iter()
+            .position(|name| *name == kind)
+            .expect("Unknown kind of record");
+        let bit = author.0 * KINDS.len() + kind_index;
+        let bitmap = self
+            .peers
+            .entry(peer)
+            .or_default()
+            .entry((epoch_id, round))
+            .or_default();
+        if bitmap.len() <= bit / 64 {
+            bitmap.resize(bit / 64 + 1, 0);
+        }
+        let mask = 1 << (bit % 64);
+        let is_new = bitmap[bit / 64] & mask == 0;
+        bitmap[bit / 64] |= mask;
+        is_new
+    }
+
+    /// Forget the records of the rounds before the given one.
+    pub fn prune(&mut self, epoch_id: EpochId, round: Round) {
+        for rounds in self.peers.values_mut() {
+            *rounds = rounds.split_off(&(epoch_id, round));
+        }
+    }
+
+    /// Number of bitmaps kept.
+    pub fn num_bitmaps(&self) -> usize {
+        self.peers.values().map(BTreeMap::len).sum()
+    }
+}
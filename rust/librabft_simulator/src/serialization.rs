@@ -0,0 +1,532 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Versioned canonical encoding of records, in the style of BCS:
+//! * integers are fixed-width little-endian (`usize` values are encoded as `u64`),
+//! * sequences and strings are prefixed by their length in ULEB128,
+//! * options are prefixed by one byte, 0 or 1,
+//! * enum variants are prefixed by their index in ULEB128,
+//! * struct fields follow each other in declaration order.
+//!
+//! A complete encoding starts with the version byte `VERSION` followed by the length of the
+//! payload in ULEB128. Record digests are computed over canonical bytes with a stable hash
+//! function, so that they do not depend on the platform or on the Rust release.
+
+use super::*;
+use base_types::*;
+use record::*;
+
+#[cfg(test)]
+#[path = "unit_tests/serialization_tests.rs"]
+mod serialization_tests;
+
+/// Version of the encoding. Must be increased whenever the encoding of a type changes.
+pub const VERSION: u8 = 5;
+
+pub trait CanonicalEncode {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+pub trait CanonicalDecode: Sized {
+    fn decode(input: &mut Reader) -> Result<Self>;
+}
+
+/// Bytes that remain to be decoded.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= len, "Unexpected end of input.");
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_uleb128(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                ensure!(
+                    byte != 0 || shift == 0,
+                    "ULEB128 values must be minimally encoded."
+                );
+                return Ok(value);
+            }
+        }
+        bail!("ULEB128 value overflows 64 bits.")
+    }
+}
+
+fn write_uleb128(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Encode a value with the version and length header.
+pub fn to_canonical_bytes<T: CanonicalEncode>(value: &T) -> Vec<u8> {
+    let mut payload = Vec::new();
+    value.encode(&mut payload);
+    let mut out = vec![VERSION];
+    write_uleb128(payload.len() as u64, &mut out);
+    out.extend(payload);
+    out
+}
+
+/// Number of bytes of a value in the canonical encoding, without the version and length header.
+pub fn encoded_len<T: CanonicalEncode>(value: &T) -> usize {
+    let mut payload = Vec::new();
+    value.encode(&mut payload);
+    payload.len()
+}
+
+/// Decode a value encoded by `to_canonical_bytes`. Trailing bytes are rejected.
+pub fn from_canonical_bytes<T: CanonicalDecode>(bytes: &[u8]) -> Result<T> {
+    let mut reader = Reader::new(bytes);
+    let version = reader.read_u8()?;
+    ensure!(
+        version == VERSION,
+        "Unsupported encoding version {} (expected {})",
+        version,
+        VERSION
+    );
+    let len = reader.read_uleb128()?;
+    let payload = reader.take(len as usize)?;
+    ensure!(reader.is_empty(), "Trailing bytes after the payload.");
+    let mut reader = Reader::new(payload);
+    let value = T::decode(&mut reader)?;
+    ensure!(reader.is_empty(), "Trailing bytes in the payload.");
+    Ok(value)
+}
+
+pub use bft_simulator_runtime::base_types::stable_hash;
+
+impl CanonicalEncode for u8 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl CanonicalDecode for u8 {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        input.read_u8()
+    }
+}
+
+impl CanonicalEncode for u64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl CanonicalDecode for u64 {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(input.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+impl CanonicalEncode for i64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl CanonicalDecode for i64 {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok(u64::decode(input)? as i64)
+    }
+}
+
+impl CanonicalEncode for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out)
+    }
+}
+
+impl CanonicalDecode for usize {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok(u64::decode(input)? as usize)
+    }
+}
+
+impl CanonicalEncode for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_uleb128(self.len() as u64, out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+        }
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for Option<T> {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        match input.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            tag => bail!("Invalid option tag {}", tag),
+        }
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_uleb128(self.len() as u64, out);
+        for value in self {
+            value.encode(out);
+        }
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for Vec<T> {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        let len = input.read_uleb128()?;
+        // Do not trust the length for allocations.
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(T::decode(input)?);
+        }
+        Ok(values)
+    }
+}
+
+impl<A: CanonicalEncode, B: CanonicalEncode> CanonicalEncode for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+}
+
+impl<A: CanonicalDecode, B: CanonicalDecode> CanonicalDecode for (A, B) {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl<A: CanonicalEncode, B: CanonicalEncode, C: CanonicalEncode> CanonicalEncode for (A, B, C) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+        self.2.encode(out);
+    }
+}
+
+impl<A: CanonicalDecode, B: CanonicalDecode, C: CanonicalDecode> CanonicalDecode for (A, B, C) {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok((A::decode(input)?, B::decode(input)?, C::decode(input)?))
+    }
+}
+
+macro_rules! canonical_newtype {
+    ($name:ident, $inner:ty) => {
+        impl CanonicalEncode for $name {
+            fn encode(&self, out: &mut Vec<u8>) {
+                self.0.encode(out)
+            }
+        }
+
+        impl CanonicalDecode for $name {
+            fn decode(input: &mut Reader) -> Result<Self> {
+                Ok($name(<$inner>::decode(input)?))
+            }
+        }
+    };
+}
+
+canonical_newtype!(Author, usize);
+canonical_newtype!(Round, usize);
+canonical_newtype!(NodeTime, i64);
+canonical_newtype!(Signature, u64);
+canonical_newtype!(EpochId, usize);
+canonical_newtype!(ChainId, u64);
+canonical_newtype!(BlockHash, u64);
+canonical_newtype!(QuorumCertificateHash, u64);
+canonical_newtype!(State, u64);
+
+impl CanonicalEncode for Command {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.proposer.encode(out);
+        self.index.encode(out);
+    }
+}
+
+impl CanonicalDecode for Command {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok(Command {
+            proposer: Author::decode(input)?,
+            index: usize::decode(input)?,
+        })
+    }
+}
+
+impl CanonicalEncode for EpochChange {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.epoch_id.encode(out);
+        self.configuration_digest.encode(out);
+    }
+}
+
+impl CanonicalDecode for EpochChange {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok(EpochChange {
+            epoch_id: EpochId::decode(input)?,
+            configuration_digest: u64::decode(input)?,
+        })
+    }
+}
+
+/// Voting rights by increasing author, then the resilience, then the versions of the rotated
+/// keys by increasing author.
+impl CanonicalEncode for EpochConfiguration {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let voting_rights: Vec<_> = self
+            .voting_rights()
+            .iter()
+            .map(|(author, weight)| (*author, *weight))
+            .collect();
+        voting_rights.encode(out);
+        self.resilience().encode(out);
+        let key_versions: Vec<_> = self
+            .key_versions()
+            .iter()
+            .map(|(author, version)| (*author, *version))
+            .collect();
+        key_versions.encode(out);
+    }
+}
+
+impl CanonicalDecode for EpochConfiguration {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        let voting_rights = Vec::<(Author, usize)>::decode(input)?;
+        let resilience = usize::decode(input)?;
+        let key_versions = Vec::<(Author, usize)>::decode(input)?;
+        ensure!(
+            voting_rights.windows(2).all(|pair| pair[0].0 < pair[1].0)
+                && key_versions.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "Authors of configurations must be distinct and increasing."
+        );
+        ensure!(
+            voting_rights
+                .iter()
+                .try_fold(0usize, |total, (_, votes)| total.checked_add(*votes))
+                .is_some(),
+            "Voting rights of configurations must not overflow."
+        );
+        ensure!(resilience >= 2, "Invalid resilience {}", resilience);
+        Ok(
+            EpochConfiguration::with_resilience(voting_rights.into_iter().collect(), resilience)
+                .with_key_versions(key_versions.into_iter().collect()),
+        )
+    }
+}
+
+/// Digest of a configuration, as announced by the commit certificates that end an epoch.
+pub fn configuration_digest(configuration: &EpochConfiguration) -> u64 {
+    stable_hash(&to_canonical_bytes(configuration))
+}
+
+/// Fields of a record covered by its signature, that is, all of them except the signature.
+trait SignedContent {
+    fn encode_content(&self, out: &mut Vec<u8>);
+    fn signature(&self) -> Signature;
+}
+
+impl SignedContent for Block {
+    fn encode_content(&self, out: &mut Vec<u8>) {
+        self.command.encode(out);
+        self.time.encode(out);
+        self.previous_quorum_certificate_hash.encode(out);
+        self.round.encode(out);
+        self.author.encode(out);
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature
+    }
+}
+
+impl SignedContent for Vote {
+    fn encode_content(&self, out: &mut Vec<u8>) {
+        self.epoch_id.encode(out);
+        self.round.encode(out);
+        self.certified_block_hash.encode(out);
+        self.state.encode(out);
+        self.committed_state.encode(out);
+        self.next_epoch.encode(out);
+        self.time.encode(out);
+        self.author.encode(out);
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature
+    }
+}
+
+impl SignedContent for QuorumCertificate {
+    fn encode_content(&self, out: &mut Vec<u8>) {
+        self.epoch_id.encode(out);
+        self.round.encode(out);
+        self.certified_block_hash.encode(out);
+        self.state.encode(out);
+        self.committed_state.encode(out);
+        self.next_epoch.encode(out);
+        self.votes.encode(out);
+        self.author.encode(out);
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature
+    }
+}
+
+impl SignedContent for Timeout {
+    fn encode_content(&self, out: &mut Vec<u8>) {
+        self.epoch_id.encode(out);
+        self.round.encode(out);
+        self.highest_certified_block_round.encode(out);
+        self.author.encode(out);
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature
+    }
+}
+
+// Blocks and QCs are also encoded on their own, e.g. to be stored on disk.
+impl CanonicalEncode for Block {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.encode_content(out);
+        self.signature.encode(out);
+    }
+}
+
+impl CanonicalDecode for Block {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok(Block {
+            command: Option::decode(input)?,
+            time: NodeTime::decode(input)?,
+            previous_quorum_certificate_hash: QuorumCertificateHash::decode(input)?,
+            round: Round::decode(input)?,
+            author: Author::decode(input)?,
+            signature: Signature::decode(input)?,
+        })
+    }
+}
+
+impl CanonicalEncode for QuorumCertificate {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.encode_content(out);
+        self.signature.encode(out);
+    }
+}
+
+impl CanonicalDecode for QuorumCertificate {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        Ok(QuorumCertificate {
+            epoch_id: EpochId::decode(input)?,
+            round: Round::decode(input)?,
+            certified_block_hash: BlockHash::decode(input)?,
+            state: State::decode(input)?,
+            committed_state: Option::decode(input)?,
+            next_epoch: Option::decode(input)?,
+            votes: Vec::decode(input)?,
+            author: Author::decode(input)?,
+            signature: Signature::decode(input)?,
+        })
+    }
+}
+
+impl Record {
+    fn variant_index(&self) -> u64 {
+        match self {
+            Record::Block(_) => 0,
+            Record::Vote(_) => 1,
+            Record::QuorumCertificate(_) => 2,
+            Record::Timeout(_) => 3,
+        }
+    }
+
+    fn content(&self) -> &dyn SignedContent {
+        match self {
+            Record::Block(x) => x,
+            Record::Vote(x) => x,
+            Record::QuorumCertificate(x) => x,
+            Record::Timeout(x) => x,
+        }
+    }
+
+    /// Canonical bytes of the record without its signature, from which digests are computed.
+    pub fn content_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        write_uleb128(self.variant_index(), &mut out);
+        self.content().encode_content(&mut out);
+        out
+    }
+}
+
+impl CanonicalEncode for Record {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_uleb128(self.variant_index(), out);
+        let content = self.content();
+        content.encode_content(out);
+        content.signature().encode(out);
+    }
+}
+
+impl CanonicalDecode for Record {
+    fn decode(input: &mut Reader) -> Result<Self> {
+        let record = match input.read_uleb128()? {
+            0 => Record::Block(Block::decode(input)?),
+            1 => Record::Vote(Vote {
+                epoch_id: EpochId::decode(input)?,
+                round: Round::decode(input)?,
+                certified_block_hash: BlockHash::decode(input)?,
+                state: State::decode(input)?,
+                committed_state: Option::decode(input)?,
+                next_epoch: Option::decode(input)?,
+                time: NodeTime::decode(input)?,
+                author: Author::decode(input)?,
+                signature: Signature::decode(input)?,
+            }),
+            2 => Record::QuorumCertificate(QuorumCertificate::decode(input)?),
+            3 => Record::Timeout(Timeout {
+                epoch_id: EpochId::decode(input)?,
+                round: Round::decode(input)?,
+                highest_certified_block_round: Round::decode(input)?,
+                author: Author::decode(input)?,
+                signature: Signature::decode(input)?,
+            }),
+            index => bail!("Unknown kind of record {}", index),
+        };
+        Ok(record)
+    }
+}
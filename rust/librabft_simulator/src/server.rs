@@ -0,0 +1,175 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Small REST API driving the campaign runner, so that simulations can be run on a shared
+//! server by external tools:
+//! * `POST /jobs[?seed=N]` with a TOML scenario as body starts a job and returns its id,
+//! * `GET /jobs` lists all jobs, `GET /jobs/{id}` polls the status of a job,
+//! * `GET /jobs/{id}/report` and `GET /jobs/{id}/trace` fetch the results of a finished job.
+
+use super::*;
+use bft_simulator_runtime::simulator::TraceEntry;
+use campaign::RunReport;
+use scenario::Scenario;
+use serde::Serialize;
+use std::{
+    panic,
+    sync::{Arc, Mutex},
+    thread,
+};
+use tiny_http;
+
+#[cfg(test)]
+#[path = "unit_tests/server_tests.rs"]
+mod server_tests;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Job {
+    id: usize,
+    status: JobStatus,
+    seed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    report: Option<RunReport>,
+    #[serde(skip)]
+    trace: Vec<TraceEntry>,
+}
+
+/// An HTTP response, before it is written on the wire.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Response {
+            status,
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &BTreeMap::from([("error", message)]))
+    }
+}
+
+/// Jobs submitted to the server. Each job runs in its own thread.
+#[derive(Clone, Default)]
+pub struct CampaignServer {
+    jobs: Arc<Mutex<Vec<Job>>>,
+}
+
+impl CampaignServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer a request without any I/O.
+    pub fn handle(&self, method: &str, url: &str, body: &str) -> Response {
+        let (path, query) = match url.find('?') {
+            Some(index) => (&url[..index], &url[index + 1..]),
+            None => (url, ""),
+        };
+        let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match (method, segments.as_slice()) {
+            ("POST", ["jobs"]) => self.submit(query, body),
+            ("GET", ["jobs"]) => Response::json(200, &*self.jobs.lock().unwrap()),
+            ("GET", ["jobs", id]) => self.with_job(id, |job| Response::json(200, job)),
+            ("GET", ["jobs", id, "report"]) => self.with_job(id, |job| match &job.report {
+                Some(report) => Response::json(200, report),
+                None => Response::error(409, "The job has no report yet."),
+            }),
+            ("GET", ["jobs", id, "trace"]) => self.with_job(id, |job| match job.status {
+                JobStatus::Done => Response::json(200, &job.trace),
+                _ => Response::error(409, "The job has no trace yet."),
+            }),
+            _ => Response::error(404, "Unknown route."),
+        }
+    }
+
+    fn with_job<F>(&self, id: &str, f: F) -> Response
+    where
+        F: FnOnce(&Job) -> Response,
+    {
+        let jobs = self.jobs.lock().unwrap();
+        match id.parse::<usize>().ok().and_then(|id| jobs.get(id)) {
+            Some(job) => f(job),
+            None => Response::error(404, "Unknown job."),
+        }
+    }
+
+    fn submit(&self, query: &str, body: &str) -> Response {
+        let scenario = match Scenario::from_toml(body) {
+            Ok(scenario) => scenario,
+            Err(err) => return Response::error(400, &err.to_string()),
+        };
+        let seed = match query.strip_prefix("seed=").map(str::parse::<u64>) {
+            Some(Ok(seed)) => seed,
+            Some(Err(_)) => return Response::error(400, "Invalid seed."),
+            None => scenario.seed.unwrap_or_else(rand::random),
+        };
+        let id = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let id = jobs.len();
+            jobs.push(Job {
+                id,
+                status: JobStatus::Running,
+                seed,
+                error: None,
+                report: None,
+                trace: Vec::new(),
+            });
+            id
+        };
+        let jobs = self.jobs.clone();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(|| campaign::replay(&scenario, seed));
+            let job = &mut jobs.lock().unwrap()[id];
+            match result {
+                Ok((report, trace)) => {
+                    job.status = JobStatus::Done;
+                    job.report = Some(report);
+                    job.trace = trace;
+                }
+                Err(_) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some("The simulation panicked.".to_string());
+                }
+            }
+        });
+        Response::json(201, &BTreeMap::from([("id", id)]))
+    }
+
+    /// Serve requests on the given address until the process is stopped.
+    pub fn serve(&self, address: &str) -> Result<()> {
+        let server = tiny_http::Server::http(address).map_err(|err| format_err!("{}", err))?;
+        info!("Listening on {}", address);
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            let response = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => self.handle(request.method().as_str(), request.url(), &body),
+                Err(err) => Response::error(400, &err.to_string()),
+            };
+            let header =
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                    .unwrap();
+            request.respond(
+                tiny_http::Response::from_string(response.body)
+                    .with_status_code(response.status)
+                    .with_header(header),
+            )?;
+        }
+        Ok(())
+    }
+}
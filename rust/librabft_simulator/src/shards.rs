@@ -0,0 +1,458 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Shards: independent consensus instances run by subsets of the validators over the same
+//! simulated network. Each validator runs one `Participant` and one `SimulatedContext` per
+//! shard that it belongs to, and bundles the messages of its shards together, so that its
+//! crashes, clock, hardware and links are shared by all of them.
+
+use super::*;
+use bft_simulator_runtime::{
+    bandwidth::{BandwidthStats, ByteCounts, MessageSize},
+    inbound_queue::MessagePriority,
+    simulator::{GlobalTime, RunOutcome, Simulator},
+    ActiveRound, Commit, CommitLog, ConsensusNode, DataSyncNode, InstanceCommits,
+    NodeUpdateActions, ProcessingTime, RoundTriage,
+};
+use campaign::{consistent_histories, mean_block_interval, with_seed};
+use cross_shard::{CrossShardCoordinator, CrossShardStats};
+use data_sync::{DataSyncNotification, DataSyncRequest, DataSyncResponse};
+use follower::Participant;
+use scenario::Scenario;
+use serde::Serialize;
+use serialization::encoded_len;
+use simulated_context::SimulatedContext;
+use std::collections::BTreeSet;
+use triage::RoundSummary;
+
+#[cfg(test)]
+#[path = "unit_tests/shards_tests.rs"]
+mod shards_tests;
+
+/// Simulator running the shards of a scenario, see `Scenario::build_sharded_simulator`.
+pub type ShardedSimulator = Simulator<
+    ShardedNode,
+    ShardedContext,
+    ShardMessages<DataSyncNotification>,
+    ShardMessages<DataSyncRequest>,
+    ShardMessages<DataSyncResponse>,
+>;
+
+/// Messages of several shards sent together, by shard.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct ShardMessages<M>(pub Vec<(usize, M)>);
+
+impl<M: MessageSize> MessageSize for ShardMessages<M> {
+    fn byte_counts(&self) -> ByteCounts {
+        let shards: Vec<_> = self.0.iter().map(|(shard, _)| *shard as u64).collect();
+        let mut counts = vec![("header", encoded_len(&shards))];
+        for (_, message) in &self.0 {
+            counts.extend(message.byte_counts());
+        }
+        counts
+    }
+
+    fn payloads(&self) -> Vec<u64> {
+        self.0
+            .iter()
+            .flat_map(|(_, message)| message.payloads())
+            .collect()
+    }
+}
+
+impl<M: MessagePriority> MessagePriority for ShardMessages<M> {
+    fn priority(&self) -> usize {
+        self.0
+            .iter()
+            .map(|(_, message)| message.priority())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn round(&self) -> Round {
+        self.0
+            .iter()
+            .map(|(_, message)| message.round())
+            .max()
+            .unwrap_or(Round(0))
+    }
+}
+
+/// The SMR contexts of a validator, by shard.
+#[derive(Clone, Debug)]
+pub struct ShardedContext {
+    shards: BTreeMap<usize, SimulatedContext>,
+}
+
+impl ShardedContext {
+    /// Create the contexts of the shards of `author`, given the scenario of each shard.
+    pub fn new(scenarios: &[Scenario], author: Author) -> Self {
+        let shards = scenarios
+            .iter()
+            .enumerate()
+            .filter(|(_, scenario)| scenario.voting_rights[author.0] > 0)
+            .map(|(shard, scenario)| (shard, scenario.make_context(author)))
+            .collect();
+        ShardedContext { shards }
+    }
+
+    pub fn shard(&self, shard: usize) -> Option<&SimulatedContext> {
+        self.shards.get(&shard)
+    }
+
+    pub fn shard_mut(&mut self, shard: usize) -> Option<&mut SimulatedContext> {
+        self.shards.get_mut(&shard)
+    }
+
+    pub fn shards(&self) -> &BTreeMap<usize, SimulatedContext> {
+        &self.shards
+    }
+}
+
+impl ProcessingTime for ShardedContext {
+    fn take_processing_time(&mut self) -> Duration {
+        self.shards
+            .values_mut()
+            .map(SimulatedContext::take_processing_time)
+            .sum()
+    }
+}
+
+/// What the consensus instance of a shard asked for at the latest update of its node.
+#[derive(Clone, Debug, Default)]
+struct ShardActions {
+    /// Members of the shard to notify.
+    receivers: BTreeSet<Author>,
+    should_query_all: bool,
+}
+
+/// A validator running the consensus instances of its shards.
+#[derive(Clone, Debug)]
+pub struct ShardedNode {
+    author: Author,
+    shards: BTreeMap<usize, Participant>,
+    /// Members of each shard of the node.
+    members: BTreeMap<usize, BTreeSet<Author>>,
+    /// Actions of each shard at the latest update, read when sending its messages.
+    actions: BTreeMap<usize, ShardActions>,
+}
+
+impl ShardedNode {
+    /// Create the consensus instances of the shards of `author`, given the scenario of each
+    /// shard.
+    pub fn new(
+        scenarios: &[Scenario],
+        author: Author,
+        context: &ShardedContext,
+        clock: NodeTime,
+    ) -> Self {
+        let mut shards = BTreeMap::new();
+        let mut members = BTreeMap::new();
+        for (shard, shard_context) in context.shards() {
+            let scenario = &scenarios[*shard];
+            shards.insert(*shard, scenario.make_node(author, shard_context, clock));
+            members.insert(
+                *shard,
+                (0..scenario.nodes)
+                    .filter(|node| scenario.voting_rights[*node] > 0)
+                    .map(Author)
+                    .collect(),
+            );
+        }
+        ShardedNode {
+            author,
+            shards,
+            members,
+            actions: BTreeMap::new(),
+        }
+    }
+
+    pub fn shard(&self, shard: usize) -> Option<&Participant> {
+        self.shards.get(&shard)
+    }
+
+    pub fn shards(&self) -> &BTreeMap<usize, Participant> {
+        &self.shards
+    }
+
+    /// Bundle the messages created by the shards that asked for them.
+    fn bundle<M, F>(&self, wanted: impl Fn(&ShardActions) -> bool, create: F) -> ShardMessages<M>
+    where
+        F: Fn(&Participant) -> M,
+    {
+        ShardMessages(
+            self.actions
+                .iter()
+                .filter(|(_, actions)| wanted(actions))
+                .map(|(shard, _)| (*shard, create(&self.shards[shard])))
+                .collect(),
+        )
+    }
+}
+
+impl ConsensusNode<ShardedContext> for ShardedNode {
+    fn update_node(&mut self, clock: NodeTime, context: &mut ShardedContext) -> NodeUpdateActions {
+        let mut actions = NodeUpdateActions::new();
+        let mut receivers = BTreeSet::new();
+        let author = self.author;
+        self.actions.clear();
+        for (shard, node) in &mut self.shards {
+            let shard_context = context.shards.get_mut(shard).unwrap();
+            let shard_actions = node.update_node(clock, shard_context);
+            actions.next_scheduled_update = std::cmp::min(
+                actions.next_scheduled_update,
+                shard_actions.next_scheduled_update,
+            );
+            // Broadcasts only go to the members of the shard.
+            let shard_receivers: BTreeSet<_> = if shard_actions.should_broadcast {
+                self.members[shard]
+                    .iter()
+                    .filter(|member| **member != author)
+                    .cloned()
+                    .collect()
+            } else {
+                shard_actions.should_send.into_iter().collect()
+            };
+            actions.should_query_all |= shard_actions.should_query_all;
+            receivers.extend(shard_receivers.iter().cloned());
+            self.actions.insert(
+                *shard,
+                ShardActions {
+                    receivers: shard_receivers,
+                    should_query_all: shard_actions.should_query_all,
+                },
+            );
+        }
+        actions.should_send = receivers.into_iter().collect();
+        actions
+    }
+}
+
+impl DataSyncNode<ShardedContext> for ShardedNode {
+    type Notification = ShardMessages<DataSyncNotification>;
+    type Request = ShardMessages<DataSyncRequest>;
+    type Response = ShardMessages<DataSyncResponse>;
+
+    fn create_notification(&self) -> Self::Notification {
+        self.bundle(
+            |actions| !actions.receivers.is_empty(),
+            DataSyncNode::<SimulatedContext>::create_notification,
+        )
+    }
+
+    fn create_request(&self) -> Self::Request {
+        self.bundle(
+            |actions| actions.should_query_all,
+            DataSyncNode::<SimulatedContext>::create_request,
+        )
+    }
+
+    /// Answer the requests of the shards of the node. Requests of other shards are ignored.
+    fn handle_request(&self, request: Self::Request) -> Self::Response {
+        ShardMessages(
+            request
+                .0
+                .into_iter()
+                .filter_map(|(shard, request)| {
+                    let node = self.shards.get(&shard)?;
+                    Some((
+                        shard,
+                        DataSyncNode::<SimulatedContext>::handle_request(node, request),
+                    ))
+                })
+                .collect(),
+        )
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: Self::Notification,
+        context: &mut ShardedContext,
+    ) -> Option<Self::Request> {
+        let mut requests = Vec::new();
+        for (shard, notification) in notification.0 {
+            if let (Some(node), Some(shard_context)) =
+                (self.shards.get_mut(&shard), context.shards.get_mut(&shard))
+            {
+                if let Some(request) = node.handle_notification(notification, shard_context) {
+                    requests.push((shard, request));
+                }
+            }
+        }
+        if requests.is_empty() {
+            None
+        } else {
+            Some(ShardMessages(requests))
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        response: Self::Response,
+        context: &mut ShardedContext,
+        clock: NodeTime,
+    ) {
+        for (shard, response) in response.0 {
+            if let (Some(node), Some(shard_context)) =
+                (self.shards.get_mut(&shard), context.shards.get_mut(&shard))
+            {
+                node.handle_response(response, shard_context, clock);
+            }
+        }
+    }
+
+    /// Only send to a receiver the notifications of the shards that address it, each filtered
+    /// by its consensus instance.
+    fn filter_notification(
+        &mut self,
+        notification: &Self::Notification,
+        receiver: Author,
+    ) -> Option<Self::Notification> {
+        let mut filtered = Vec::new();
+        for (shard, notification) in &notification.0 {
+            if !self.actions[shard].receivers.contains(&receiver) {
+                continue;
+            }
+            let node = self.shards.get_mut(shard).unwrap();
+            let notification =
+                DataSyncNode::<SimulatedContext>::filter_notification(node, notification, receiver)
+                    .unwrap_or_else(|| notification.clone());
+            filtered.push((*shard, notification));
+        }
+        Some(ShardMessages(filtered))
+    }
+}
+
+impl CommitLog for ShardedNode {
+    type Block = BlockHash;
+    type State = State;
+
+    /// The commits of the first shard of the node.
+    fn commits(&self) -> &[Commit<BlockHash, State>] {
+        self.shards.values().next().map_or(&[], CommitLog::commits)
+    }
+
+    fn instance_commits(&self) -> InstanceCommits<'_, BlockHash, State> {
+        self.shards
+            .iter()
+            .map(|(shard, node)| (*shard, node.commits()))
+            .collect()
+    }
+}
+
+impl RoundTriage for ShardedNode {
+    type Summary = BTreeMap<usize, RoundSummary>;
+
+    fn round_summary(&self, epoch: usize, round: Round) -> Self::Summary {
+        self.shards
+            .iter()
+            .map(|(shard, node)| (*shard, node.round_summary(epoch, round)))
+            .collect()
+    }
+}
+
+impl ActiveRound for ShardedNode {
+    fn active_round(&self) -> Round {
+        self.shards
+            .values()
+            .map(ActiveRound::active_round)
+            .max()
+            .unwrap_or(Round(0))
+    }
+}
+
+/// Summary of a run of a sharded scenario.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct ShardedRunReport {
+    /// Seed from which the run can be replayed.
+    pub seed: u64,
+    /// Why and when the run ended.
+    pub outcome: RunOutcome,
+    /// Bytes sent on the network by all the shards.
+    pub bandwidth: BandwidthStats,
+    pub shards: Vec<ShardReport>,
+    /// If transactions span two shards, what their coordinator observed.
+    pub cross_shard: Option<CrossShardStats>,
+}
+
+/// Progress of the consensus instance of a shard.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct ShardReport {
+    pub members: Vec<usize>,
+    /// Number of commands committed by each member.
+    pub commits: Vec<usize>,
+    /// Whether the committed histories of the members are prefixes of one another.
+    pub consistent: bool,
+    /// Mean time between the proposals of two consecutive committed blocks, if at least two
+    /// blocks were committed.
+    pub mean_block_interval: Option<Duration>,
+    /// Mean time between the proposals of the members and the creation of their QC, if any
+    /// was certified.
+    pub qc_latency: Option<Duration>,
+}
+
+/// Run a sharded scenario once. The seed of the scenario is used unless another one is given.
+pub fn run_shards(scenario: &Scenario, seed: Option<u64>) -> ShardedRunReport {
+    let seed = seed.or(scenario.seed).unwrap_or_else(rand::random);
+    let scenario = with_seed(scenario, seed);
+    let mut sim = scenario.build_sharded_simulator();
+    sim.enable_bandwidth_accounting(scenario.network.bandwidth_interval);
+    let conditions = scenario
+        .stop
+        .as_ref()
+        .map(|stop| stop.conditions())
+        .unwrap_or_default();
+    let mut coordinator = scenario
+        .cross_shard
+        .as_ref()
+        .map(|parameters| CrossShardCoordinator::new(parameters, &scenario, seed));
+    let max_clock = GlobalTime(scenario.max_clock);
+    let outcome = match &mut coordinator {
+        Some(coordinator) => coordinator.run(&mut sim, max_clock, &conditions),
+        None => sim.run_until(max_clock, &conditions),
+    };
+    let shards = scenario
+        .shards
+        .iter()
+        .enumerate()
+        .map(|(shard, parameters)| {
+            let replicas: Vec<_> = parameters
+                .members
+                .iter()
+                .map(|member| &sim.replicas()[*member])
+                .collect();
+            let contexts: Vec<_> = replicas
+                .iter()
+                .map(|replica| replica.context().shard(shard).unwrap())
+                .collect();
+            let latencies: Vec<Duration> = replicas
+                .iter()
+                .flat_map(|replica| {
+                    let node = replica.node().shard(shard).unwrap().node_state();
+                    node.quorum_certificate_latencies().to_vec()
+                })
+                .collect();
+            ShardReport {
+                members: parameters.members.clone(),
+                commits: contexts
+                    .iter()
+                    .map(|context| context.committed_history().len())
+                    .collect(),
+                consistent: consistent_histories(&contexts),
+                mean_block_interval: mean_block_interval(&contexts),
+                qc_latency: if latencies.is_empty() {
+                    None
+                } else {
+                    Some(latencies.iter().sum::<Duration>() / latencies.len() as Duration)
+                },
+            }
+        })
+        .collect();
+    ShardedRunReport {
+        seed,
+        outcome,
+        bandwidth: sim.bandwidth().cloned().unwrap_or_default(),
+        shards,
+        cross_shard: coordinator.map(|coordinator| coordinator.stats()),
+    }
+}
@@ -3,10 +3,14 @@
 
 use super::*;
 use base_types::*;
+use bft_simulator_runtime::{bandwidth::PayloadSizes, configuration::DEFAULT_RESILIENCE};
+use cross_shard::{lock_key, CrossShardOperation, TransactionPhase, TransactionStatus};
+use data_sync::payload_id;
 use record::*;
+use serialization::{from_canonical_bytes, to_canonical_bytes};
 use smr_context::*;
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap, HashSet, VecDeque},
     hash::{Hash, Hasher},
 };
 
@@ -19,68 +23,380 @@ pub struct SimulatedLedgerState {
     /// All the executed commands and theirs consensus times of execution.
     /// TODO: use linked lists with sharing
     execution_history: Vec<(Command, NodeTime)>,
+    /// Number of commands executed before each empty block, and the time of the empty block.
+    empty_blocks: Vec<(usize, NodeTime)>,
 }
 
 impl SimulatedLedgerState {
     fn new() -> SimulatedLedgerState {
         SimulatedLedgerState {
             execution_history: Vec::new(),
+            empty_blocks: Vec::new(),
         }
     }
 
     fn key(&self) -> State {
         let mut hasher = DefaultHasher::new();
-        self.execution_history.hash(&mut hasher);
+        self.hash(&mut hasher);
         State(hasher.finish())
     }
 
-    fn execute(&mut self, command: Command, time: NodeTime) {
-        self.execution_history.push((command, time));
+    fn execute(&mut self, command: Option<Command>, time: NodeTime) {
+        match command {
+            Some(command) => self.execution_history.push((command, time)),
+            None => self.empty_blocks.push((self.execution_history.len(), time)),
+        }
     }
 
-    fn happened_just_before(&self, other: &SimulatedLedgerState) -> bool {
-        if self.execution_history.len() + 1 != other.execution_history.len() {
-            return false;
-        }
-        for i in 0..self.execution_history.len() {
-            if self.execution_history[i] != other.execution_history[i] {
-                return false;
-            }
+    fn len(&self) -> usize {
+        self.execution_history.len() + self.empty_blocks.len()
+    }
+
+    /// Whether the history of `self` starts with the history of `other`.
+    fn extends(&self, other: &SimulatedLedgerState) -> bool {
+        self.execution_history.starts_with(&other.execution_history)
+            && self.empty_blocks.starts_with(&other.empty_blocks)
+    }
+
+    /// Status of the cross-shard transactions of the history, as a participant of two-phase
+    /// commit: a transaction is prepared unless its key is locked by another prepared one,
+    /// and an abort before the prepare makes the prepare refused. Operations that do not apply
+    /// to the current status, e.g. duplicates, change nothing.
+    fn transactions(&self, lock_keys: Option<u64>) -> BTreeMap<usize, TransactionStatus> {
+        let mut statuses = BTreeMap::new();
+        let mut locks = BTreeMap::new();
+        for (command, _) in &self.execution_history {
+            let operation = match CrossShardOperation::of(command) {
+                Some(operation) => operation,
+                None => continue,
+            };
+            let transaction = operation.transaction;
+            let key = lock_keys.map(|keys| lock_key(transaction, keys));
+            let status = match (operation.phase, statuses.get(&transaction)) {
+                (TransactionPhase::Prepare, None) => match key {
+                    Some(key) if locks.contains_key(&key) => TransactionStatus::Refused,
+                    Some(key) => {
+                        locks.insert(key, transaction);
+                        TransactionStatus::Prepared
+                    }
+                    None => TransactionStatus::Prepared,
+                },
+                (TransactionPhase::Commit, Some(TransactionStatus::Prepared)) => {
+                    locks.retain(|_, holder| *holder != transaction);
+                    TransactionStatus::Committed
+                }
+                (TransactionPhase::Abort, None)
+                | (TransactionPhase::Abort, Some(TransactionStatus::Refused)) => {
+                    TransactionStatus::Aborted
+                }
+                (TransactionPhase::Abort, Some(TransactionStatus::Prepared)) => {
+                    locks.retain(|_, holder| *holder != transaction);
+                    TransactionStatus::Aborted
+                }
+                _ => continue,
+            };
+            statuses.insert(transaction, status);
         }
-        return true;
+        statuses
+    }
+
+    fn happened_just_before(&self, other: &SimulatedLedgerState) -> bool {
+        self.len() + 1 == other.len() && other.extends(self)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let execution_history: Vec<_> = self
+            .execution_history
+            .iter()
+            .map(|(command, time)| (command.clone(), time.0))
+            .collect();
+        let empty_blocks: Vec<_> = self
+            .empty_blocks
+            .iter()
+            .map(|(num_commands, time)| (*num_commands, time.0))
+            .collect();
+        to_canonical_bytes(&(execution_history, empty_blocks))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<SimulatedLedgerState> {
+        let (execution_history, empty_blocks): LedgerEncoding = from_canonical_bytes(bytes)?;
+        Ok(SimulatedLedgerState {
+            execution_history: execution_history
+                .into_iter()
+                .map(|(command, time)| (command, NodeTime(time)))
+                .collect(),
+            empty_blocks: empty_blocks
+                .into_iter()
+                .map(|(num_commands, time)| (num_commands, NodeTime(time)))
+                .collect(),
+        })
     }
 }
 
+/// Canonical encoding of a ledger state, with times as integers.
+type LedgerEncoding = (Vec<(Command, i64)>, Vec<(usize, i64)>);
+
+/// Every n-th command submitted by clients is of high priority.
+const HIGH_PRIORITY_PERIOD: usize = 4;
+
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub struct SimulatedContext {
     author: Author,
-    num_nodes: usize,
+    /// Voting rights of the nodes `0..n` from a given epoch on, as if decided by committed
+    /// reconfiguration transactions.
+    voting_rights: BTreeMap<EpochId, Vec<usize>>,
+    /// Nodes announcing a new key in the epoch before the given one, as if by a committed
+    /// transaction, so that the new key signs their records from the given epoch on.
+    key_rotations: BTreeMap<EpochId, BTreeSet<Author>>,
+    /// Nodes without voting rights before the given epoch, as if added to the validator set by
+    /// a committed transaction of the previous one.
+    joins: BTreeMap<Author, EpochId>,
+    /// Nodes without voting rights from the given epoch on, as if removed from the validator
+    /// set by a committed transaction of the previous one.
+    removals: BTreeMap<Author, EpochId>,
+    /// Number of votes per faulty vote tolerated, as in `n = 3f + 1`.
+    resilience: usize,
     max_command_per_epoch: usize,
     next_fetched_command_index: usize,
     last_committed_ledger_state: SimulatedLedgerState,
+    /// Quorum time of the latest commit, if any.
+    last_committed_time: Option<NodeTime>,
     pending_ledger_states: HashMap<State, SimulatedLedgerState>,
+    /// States of the blocks executed speculatively and not committed yet.
+    speculated_blocks: HashMap<BlockHash, State>,
+    /// States rolled back after a conflicting commit. They must never be committed.
+    rolled_back_states: HashSet<State>,
+    /// States dropped by `prune` because they did not extend the committed ledger. They must
+    /// never be committed either.
+    pruned_states: HashSet<State>,
+    /// Number of new client commands arriving in the mempool for each fetch.
+    arrival_rate: usize,
+    /// Number of fetches so far, used as a logical clock for the mempool.
+    fetch_count: usize,
+    /// Commands waiting in the mempool, with the value of `fetch_count` when they arrived.
+    mempool: VecDeque<(Command, usize)>,
+    /// Number of clients submitting commands in turn.
+    clients: usize,
+    /// Simulated time to verify a signature.
+    signature_verification_cost: Duration,
+    /// Number of signatures verified at the same time by batches.
+    verification_workers: usize,
+    /// Simulated time to execute a command.
+    execution_cost: Duration,
+    /// Time spent verifying signatures and executing commands since the last call to
+    /// `take_processing_time`.
+    processing_time: Duration,
+    /// Number of keys locked by cross-shard transactions, one each, if they may conflict.
+    lock_keys: Option<u64>,
+    /// Sizes of the payloads of commands, if they are simulated.
+    payload_sizes: Option<PayloadSizes>,
 }
 
 impl SimulatedContext {
     pub fn new(author: Author, num_nodes: usize, max_command_per_epoch: usize) -> Self {
         SimulatedContext {
             author,
-            num_nodes,
+            voting_rights: vec![(EpochId(0), vec![1; num_nodes])].into_iter().collect(),
+            key_rotations: BTreeMap::new(),
+            joins: BTreeMap::new(),
+            removals: BTreeMap::new(),
+            resilience: DEFAULT_RESILIENCE,
             max_command_per_epoch,
             next_fetched_command_index: 0,
             last_committed_ledger_state: SimulatedLedgerState::new(),
+            last_committed_time: None,
             pending_ledger_states: HashMap::new(),
+            speculated_blocks: HashMap::new(),
+            rolled_back_states: HashSet::new(),
+            pruned_states: HashSet::new(),
+            arrival_rate: 1,
+            fetch_count: 0,
+            mempool: VecDeque::new(),
+            clients: 1,
+            signature_verification_cost: 0,
+            verification_workers: 1,
+            execution_cost: 0,
+            processing_time: 0,
+            lock_keys: None,
+            payload_sizes: None,
+        }
+    }
+
+    /// Give heterogeneous voting rights to the nodes, e.g. according to their stake, instead
+    /// of one vote each.
+    pub fn set_voting_rights(&mut self, voting_rights: Vec<usize>) {
+        self.voting_rights.insert(EpochId(0), voting_rights);
+    }
+
+    /// Change the voting rights at the start of the given epoch, e.g. when a stakeholder joins
+    /// or leaves the validator set.
+    pub fn schedule_voting_rights(&mut self, epoch_id: EpochId, voting_rights: Vec<usize>) {
+        self.voting_rights.insert(epoch_id, voting_rights);
+    }
+
+    /// Rotate the key of the given node at the start of the given epoch: the configuration of
+    /// the epoch, hence the record stores of all the nodes, expect signatures with the next
+    /// version of its key.
+    pub fn schedule_key_rotation(&mut self, epoch_id: EpochId, author: Author) {
+        self.key_rotations
+            .entry(epoch_id)
+            .or_default()
+            .insert(author);
+    }
+
+    /// Add the given node to the validator set at the start of the given epoch: it has no voting
+    /// rights before, whatever the other schedules say.
+    pub fn schedule_join(&mut self, epoch_id: EpochId, author: Author) {
+        self.joins.insert(author, epoch_id);
+    }
+
+    /// Remove the given node from the validator set at the start of the given epoch: it has no
+    /// voting rights from then on, whatever the other schedules say.
+    pub fn schedule_removal(&mut self, epoch_id: EpochId, author: Author) {
+        self.removals.insert(author, epoch_id);
+    }
+
+    /// Derive quorums from `n = resilience * f + 1` instead of `n = 3f + 1`.
+    pub fn set_resilience(&mut self, resilience: usize) {
+        self.resilience = resilience;
+    }
+
+    /// Simulate an overloaded mempool by receiving more than one command per fetch.
+    pub fn set_arrival_rate(&mut self, arrival_rate: usize) {
+        self.arrival_rate = arrival_rate;
+    }
+
+    /// Let the given number of clients submit commands in turn, instead of a single one.
+    pub fn set_clients(&mut self, clients: usize) {
+        self.clients = clients;
+    }
+
+    /// Let the mempool know the sizes of the payloads of commands, as the network simulates
+    /// them, so that leaders may cap them under overload.
+    pub fn set_payload_sizes(&mut self, payload_sizes: PayloadSizes) {
+        self.payload_sizes = Some(payload_sizes);
+    }
+
+    /// Size of the payload of a command, or 0 if payload sizes are not simulated.
+    pub fn payload_size(&self, command: &Command) -> usize {
+        self.payload_sizes
+            .map_or(0, |payload_sizes| payload_sizes.size(payload_id(command)))
+    }
+
+    /// The client that submitted a command, among `0..clients`.
+    pub fn client(command: &Command, clients: usize) -> usize {
+        command.index % clients
+    }
+
+    /// Commands waiting in the mempool, from the oldest to the newest.
+    pub fn pending_commands(&self) -> impl Iterator<Item = &Command> {
+        self.mempool.iter().map(|(command, _)| command)
+    }
+
+    /// Make signature verification and command execution take some simulated time, so that
+    /// busy nodes process their next events later. Both are free by default.
+    pub fn set_cpu_costs(&mut self, signature_verification: Duration, execution: Duration) {
+        self.signature_verification_cost = signature_verification;
+        self.execution_cost = execution;
+    }
+
+    /// Verify the signatures of batches with the given number of workers, so that a batch only
+    /// takes as long as the signatures verified by each worker.
+    pub fn set_verification_workers(&mut self, workers: usize) {
+        assert!(
+            workers > 0,
+            "Signature verification needs at least one worker"
+        );
+        self.verification_workers = workers;
+    }
+
+    /// Operations of cross-shard transactions are of high priority, since prepared
+    /// transactions hold their locks until their decision is executed.
+    pub fn priority(command: &Command) -> Priority {
+        if CrossShardOperation::of(command).is_some()
+            || command.index.is_multiple_of(HIGH_PRIORITY_PERIOD)
+        {
+            Priority::High
+        } else {
+            Priority::Low
         }
     }
 
+    /// Number of fetches since the oldest pending command of at least the given priority
+    /// arrived in the mempool.
+    pub fn oldest_pending_age(&self, min_priority: Priority) -> usize {
+        self.mempool
+            .iter()
+            .find(|(command, _)| Self::priority(command) >= min_priority)
+            .map_or(0, |(_, arrival)| self.fetch_count - arrival)
+    }
+
+    /// Add a new client command to the mempool, as if it was submitted externally.
+    pub fn submit(&mut self) -> Command {
+        let command = Command {
+            proposer: self.author,
+            index: self.next_fetched_command_index,
+        };
+        self.next_fetched_command_index += 1;
+        self.mempool.push_back((command.clone(), self.fetch_count));
+        command
+    }
+
+    /// Let each cross-shard transaction lock one of the given number of keys while it is
+    /// prepared, so that transactions on the same key conflict. Transactions never conflict
+    /// by default.
+    pub fn set_lock_keys(&mut self, keys: u64) {
+        assert!(keys > 0, "Transactions need at least one key to lock");
+        self.lock_keys = Some(keys);
+    }
+
+    /// Add an operation of a cross-shard transaction to the mempool, as if it was submitted by
+    /// its coordinator. Operations go before client commands, see `priority`.
+    pub fn submit_operation(&mut self, operation: CrossShardOperation) -> Command {
+        let command = operation.command(self.author);
+        self.mempool.push_front((command.clone(), self.fetch_count));
+        command
+    }
+
+    /// Status of the cross-shard transactions in the committed ledger.
+    pub fn transactions(&self) -> BTreeMap<usize, TransactionStatus> {
+        self.last_committed_ledger_state
+            .transactions(self.lock_keys)
+    }
+
     pub fn last_committed_state(&self) -> State {
         self.last_committed_ledger_state.key()
     }
 
+    /// Quorum time of the latest commit, if any.
+    pub fn last_committed_time(&self) -> Option<NodeTime> {
+        self.last_committed_time
+    }
+
     pub fn committed_history(&self) -> &Vec<(Command, NodeTime)> {
         &self.last_committed_ledger_state.execution_history
     }
 
+    /// Number of states rolled back so far.
+    pub fn rolled_back_states(&self) -> usize {
+        self.rolled_back_states.len()
+    }
+
+    /// Number of states dropped by `prune` so far.
+    pub fn pruned_states(&self) -> usize {
+        self.pruned_states.len()
+    }
+
+    /// Number of states executed speculatively that are neither committed nor rolled back.
+    pub fn pending_states(&self) -> usize {
+        self.pending_ledger_states.len()
+    }
+
+    /// Number of committed empty blocks.
+    pub fn committed_empty_blocks(&self) -> usize {
+        self.last_committed_ledger_state.empty_blocks.len()
+    }
+
     fn get_ledger_state(&self, state: &State) -> Option<&SimulatedLedgerState> {
         if state == &self.last_committed_ledger_state.key() {
             Some(&self.last_committed_ledger_state)
@@ -92,11 +408,100 @@ impl SimulatedContext {
 
 impl CommandFetcher for SimulatedContext {
     fn fetch(&mut self) -> Option<Command> {
-        let command = Command {
-            proposer: self.author,
-            index: self.next_fetched_command_index,
+        self.fetch_with_limits(Priority::Low, None)
+    }
+
+    fn mempool_depth(&self) -> usize {
+        self.mempool.len()
+    }
+
+    fn fetch_with_limits(
+        &mut self,
+        min_priority: Priority,
+        max_payload_bytes: Option<usize>,
+    ) -> Option<Command> {
+        self.receive_commands();
+        let speculated = self.speculated_operations();
+        let proposable = |command: &Command| {
+            CrossShardOperation::of(command)
+                .is_none_or(|operation| !speculated.contains(&operation))
+                && max_payload_bytes.is_none_or(|max| self.payload_size(command) <= max)
         };
-        self.next_fetched_command_index += 1;
+        let position = self
+            .mempool
+            .iter()
+            .position(|(command, _)| proposable(command) && Self::priority(command) >= min_priority)
+            .or_else(|| {
+                self.mempool
+                    .iter()
+                    .position(|(command, _)| proposable(command))
+            })?;
+        self.take(position)
+    }
+
+    fn fetch_excluding_clients(&mut self, clients: &BTreeSet<usize>) -> Option<Command> {
+        self.receive_commands();
+        let speculated = self.speculated_operations();
+        let num_clients = self.clients;
+        let position = self.mempool.iter().position(|(command, _)| {
+            !clients.contains(&Self::client(command, num_clients))
+                && CrossShardOperation::of(command)
+                    .is_none_or(|operation| !speculated.contains(&operation))
+        })?;
+        self.take(position)
+    }
+}
+
+impl SimulatedContext {
+    /// Let new client commands arrive in the mempool before a fetch.
+    fn receive_commands(&mut self) {
+        self.fetch_count += 1;
+        for _ in 0..self.arrival_rate {
+            let command = Command {
+                proposer: self.author,
+                index: self.next_fetched_command_index,
+            };
+            self.next_fetched_command_index += 1;
+            self.mempool.push_back((command, self.fetch_count));
+        }
+    }
+
+    /// Drop from the mempool the operations of cross-shard transactions that are committed,
+    /// and return the ones executed speculatively, e.g. as proposed by another validator.
+    /// These are not proposed again unless their blocks are abandoned.
+    fn speculated_operations(&mut self) -> HashSet<CrossShardOperation> {
+        if !self
+            .mempool
+            .iter()
+            .any(|(command, _)| CrossShardOperation::of(command).is_some())
+        {
+            return HashSet::new();
+        }
+        let operations = |ledger_state: &SimulatedLedgerState| -> HashSet<_> {
+            ledger_state
+                .execution_history
+                .iter()
+                .filter_map(|(command, _)| CrossShardOperation::of(command))
+                .collect()
+        };
+        let committed = operations(&self.last_committed_ledger_state);
+        self.mempool.retain(|(command, _)| {
+            CrossShardOperation::of(command).is_none_or(|operation| !committed.contains(&operation))
+        });
+        self.pending_ledger_states
+            .values()
+            .flat_map(operations)
+            .collect()
+    }
+
+    /// Remove the command at the given position of the mempool, unless it is an operation of a
+    /// cross-shard transaction: operations stay until they are committed, so that the ones of
+    /// abandoned blocks are proposed again.
+    fn take(&mut self, position: usize) -> Option<Command> {
+        let command = self.mempool[position].0.clone();
+        if CrossShardOperation::of(&command).is_none() {
+            self.mempool.remove(position);
+        }
         Some(command)
     }
 }
@@ -105,7 +510,7 @@ impl StateComputer for SimulatedContext {
     fn compute(
         &mut self,
         base_state: &State,
-        command: Command,
+        command: Option<Command>,
         time: NodeTime,
         _previous_author: Option<Author>,
         _previous_voters: Vec<Author>,
@@ -113,6 +518,9 @@ impl StateComputer for SimulatedContext {
         match self.get_ledger_state(base_state) {
             Some(ledger_state) => {
                 let mut new_ledger_state = ledger_state.clone();
+                if command.is_some() {
+                    self.processing_time += self.execution_cost;
+                }
                 new_ledger_state.execute(command.clone(), time);
                 let new_state = new_ledger_state.key();
                 self.pending_ledger_states
@@ -132,11 +540,49 @@ impl StateComputer for SimulatedContext {
             }
         }
     }
+
+    /// Commands already executed in the history of the parent state are double spends. The
+    /// parent state may not be executed yet, in which case the command is accepted and
+    /// execution decides.
+    fn validate_payload(&self, parent_state: &State, payload: Option<&Command>) -> bool {
+        match (self.get_ledger_state(parent_state), payload) {
+            (Some(ledger_state), Some(command)) => !ledger_state
+                .execution_history
+                .iter()
+                .any(|(executed, _)| executed == command),
+            _ => true,
+        }
+    }
+
+    fn speculate(
+        &mut self,
+        block_hash: BlockHash,
+        block: &Block,
+        base_state: &State,
+        previous_author: Option<Author>,
+        previous_voters: Vec<Author>,
+    ) -> Option<State> {
+        let state = self.compute(
+            base_state,
+            block.command.clone(),
+            block.time,
+            previous_author,
+            previous_voters,
+        )?;
+        self.speculated_blocks.insert(block_hash, state.clone());
+        Some(state)
+    }
 }
 
 impl StateFinalizer for SimulatedContext {
-    fn commit(&mut self, state: &State, certificate: Option<&QuorumCertificate>) {
+    fn commit(&mut self, state: &State, time: NodeTime, certificate: Option<&QuorumCertificate>) {
         info!("{:?} Delivering commit for state: {:?}", self.author, state);
+        assert!(
+            !self.pruned_states.contains(state),
+            "{:?} Committing a state that was pruned: {:?}",
+            self.author,
+            state
+        );
         let ledger_state = self
             .pending_ledger_states
             .remove(state)
@@ -149,6 +595,15 @@ impl StateFinalizer for SimulatedContext {
         assert!(self
             .last_committed_ledger_state
             .happened_just_before(&ledger_state));
+        assert!(
+            !self.rolled_back_states.contains(state),
+            "{:?} Committing a state that was rolled back: {:?}",
+            self.author,
+            state
+        );
+
+        self.speculated_blocks
+            .retain(|_, speculated_state| speculated_state != state);
         if let Some(qc) = certificate {
             if let Some(state2) = &qc.committed_state {
                 assert_eq!(state, state2);
@@ -158,6 +613,14 @@ impl StateFinalizer for SimulatedContext {
                 );
             }
         }
+        assert!(
+            self.last_committed_time <= Some(time),
+            "{:?} Committed time {:?} is earlier than the previous one: {:?}",
+            self.author,
+            time,
+            self.last_committed_time
+        );
+        self.last_committed_time = Some(time);
         self.last_committed_ledger_state = ledger_state
     }
 
@@ -167,26 +630,176 @@ impl StateFinalizer for SimulatedContext {
             .remove(state)
             .expect("Discarded states should be known");
     }
+
+    /// Discard the speculative states of the blocks, after checking that they diverge from the
+    /// committed ledger.
+    fn rollback(&mut self, branch: &[BlockHash]) {
+        for block_hash in branch {
+            let state = match self.speculated_blocks.remove(block_hash) {
+                Some(state) => state,
+                None => continue,
+            };
+            {
+                let ledger_state = self
+                    .get_ledger_state(&state)
+                    .expect("Rolled back states should be known");
+                let committed = &self.last_committed_ledger_state;
+                assert!(
+                    !ledger_state.extends(committed) && !committed.extends(ledger_state),
+                    "{:?} Rolling back {:?} with state {:?} which is consistent with the committed ledger",
+                    self.author,
+                    block_hash,
+                    state
+                );
+            }
+            self.discard(&state);
+            self.rolled_back_states.insert(state);
+        }
+    }
+
+    /// Drop the speculative states that do not extend the last commit, since the next commits
+    /// must. Pruning up to an older commit does nothing.
+    fn prune(&mut self, up_to_state: &State) {
+        let committed = &self.last_committed_ledger_state;
+        if *up_to_state != committed.key() {
+            return;
+        }
+        let pruned: Vec<_> = self
+            .pending_ledger_states
+            .iter()
+            .filter(|(_, ledger_state)| {
+                ledger_state.len() <= committed.len() || !ledger_state.extends(committed)
+            })
+            .map(|(state, _)| state.clone())
+            .collect();
+        for state in pruned {
+            debug!("{:?} Pruning state: {:?}", self.author, state);
+            self.pending_ledger_states.remove(&state);
+            self.speculated_blocks
+                .retain(|_, speculated_state| *speculated_state != state);
+            self.pruned_states.insert(state);
+        }
+    }
 }
 
 impl EpochReader for SimulatedContext {
     fn read_epoch_id(&self, state: &State) -> EpochId {
-        let num_commands = self
+        let ledger_state = self
             .get_ledger_state(state)
-            .expect("Read states should be known")
-            .execution_history
-            .len();
-        EpochId(num_commands / self.max_command_per_epoch)
+            .expect("Read states should be known");
+        self.epoch_of(ledger_state)
+    }
+
+    fn configuration(&self, state: &State) -> EpochConfiguration {
+        self.epoch_configuration(self.read_epoch_id(state))
+    }
+}
+
+impl SimulatedContext {
+    /// The epoch of a ledger state, given by the number of commands executed so far.
+    fn epoch_of(&self, ledger_state: &SimulatedLedgerState) -> EpochId {
+        EpochId(ledger_state.execution_history.len() / self.max_command_per_epoch)
+    }
+
+    /// The configuration of the given epoch, from the scheduled changes of voting rights, keys
+    /// and validators.
+    fn epoch_configuration(&self, epoch_id: EpochId) -> EpochConfiguration {
+        let (_, voting_rights) = self
+            .voting_rights
+            .range(..=epoch_id)
+            .next_back()
+            .expect("Voting rights of the first epoch should be known");
+        let mut key_versions = BTreeMap::new();
+        for (_, authors) in self.key_rotations.range(..=epoch_id) {
+            for author in authors {
+                *key_versions.entry(*author).or_insert(0) += 1;
+            }
+        }
+        EpochConfiguration::with_resilience(
+            voting_rights
+                .iter()
+                .enumerate()
+                .map(|(index, votes)| {
+                    let author = Author(index);
+                    let joined = self
+                        .joins
+                        .get(&author)
+                        .is_none_or(|join_epoch_id| epoch_id >= *join_epoch_id);
+                    let removed = self
+                        .removals
+                        .get(&author)
+                        .is_some_and(|removal_epoch_id| epoch_id >= *removal_epoch_id);
+                    if joined && !removed {
+                        (author, *votes)
+                    } else {
+                        (author, 0)
+                    }
+                })
+                .collect(),
+            self.resilience,
+        )
+        .with_key_versions(key_versions)
+    }
+}
+
+impl SignatureVerifier for SimulatedContext {
+    fn verified_signatures(&mut self, count: usize) {
+        self.processing_time += self.signature_verification_cost * count as Duration;
+    }
+
+    fn verified_signatures_in_parallel(&mut self, count: usize) {
+        let rounds = count.div_ceil(self.verification_workers);
+        self.processing_time += self.signature_verification_cost * rounds as Duration;
+    }
+}
+
+/// Snapshots are the canonical encoding of the ledger, so that importing one replays nothing.
+impl StateSnapshots for SimulatedContext {
+    fn export_snapshot(&self, state: &State) -> Option<Vec<u8>> {
+        self.get_ledger_state(state)
+            .map(SimulatedLedgerState::to_bytes)
     }
 
-    fn configuration(&self, _state: &State) -> EpochConfiguration {
-        // We do not simulate changes in the voting rights yet.
-        let mut voting_rights = BTreeMap::new();
-        for index in 0..self.num_nodes {
-            voting_rights.insert(Author(index), 1);
+    fn import_snapshot(&mut self, state: &State, epoch: &EpochChange, snapshot: &[u8]) -> bool {
+        let ledger_state = match SimulatedLedgerState::from_bytes(snapshot) {
+            Ok(ledger_state) => ledger_state,
+            Err(error) => {
+                warn!("{:?} Invalid snapshot: {}", self.author, error);
+                return false;
+            }
+        };
+        let epoch_id = self.epoch_of(&ledger_state);
+        if EpochChange::new(epoch_id, &self.epoch_configuration(epoch_id)) != *epoch {
+            warn!(
+                "{:?} Rejecting a snapshot that does not start the announced epoch {:?}",
+                self.author, epoch.epoch_id
+            );
+            return false;
         }
-        EpochConfiguration::new(voting_rights)
+        if &ledger_state.key() != state
+            || ledger_state.len() <= self.last_committed_ledger_state.len()
+            || !ledger_state.extends(&self.last_committed_ledger_state)
+        {
+            warn!(
+                "{:?} Rejecting a snapshot that does not extend our ledger to {:?}",
+                self.author, state
+            );
+            return false;
+        }
+        info!(
+            "{:?} Installing snapshot of state: {:?}",
+            self.author, state
+        );
+        self.last_committed_ledger_state = ledger_state;
+        self.prune(state);
+        true
     }
 }
 
 impl SMRContext for SimulatedContext {}
+
+impl ProcessingTime for SimulatedContext {
+    fn take_processing_time(&mut self) -> Duration {
+        std::mem::replace(&mut self.processing_time, 0)
+    }
+}
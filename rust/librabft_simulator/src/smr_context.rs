@@ -2,25 +2,66 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
-use base_types::{Command, State};
-use record::QuorumCertificate;
+use base_types::{BlockHash, Command, State};
+use record::{Block, EpochChange, QuorumCertificate};
+use std::collections::BTreeSet;
+
+/// Priority of a command, as decided by the SMR layer.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+pub enum Priority {
+    Low,
+    High,
+}
+
+/// Leader policy used to shed load when the mempool is overloaded.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct LoadSheddingPolicy {
+    /// Mempool depth above which low-priority commands are deferred.
+    pub mempool_threshold: usize,
+    /// Largest payload, in bytes, proposed while the mempool is above the threshold, if any.
+    /// Commands with larger payloads are deferred as well.
+    pub max_payload_bytes: Option<usize>,
+}
 
 // -- BEGIN FILE smr_apis --
 pub trait CommandFetcher {
     /// How to fetch valid commands to submit to the consensus protocol.
     fn fetch(&mut self) -> Option<Command>;
+
+    /// Number of commands waiting to be fetched.
+    fn mempool_depth(&self) -> usize {
+        0
+    }
+
+    /// Fetch the oldest command of at least the given priority and with a payload of at most
+    /// `max_payload_bytes`, deferring the others. Fall back to the oldest command within the
+    /// size limit if there is none. Contexts that do not know payload sizes ignore the limit.
+    fn fetch_with_limits(
+        &mut self,
+        _min_priority: Priority,
+        _max_payload_bytes: Option<usize>,
+    ) -> Option<Command> {
+        self.fetch()
+    }
+
+    /// Fetch the oldest command not submitted by one of the given clients, deferring the
+    /// others. Return nothing if there is none.
+    fn fetch_excluding_clients(&mut self, _clients: &BTreeSet<usize>) -> Option<Command> {
+        self.fetch()
+    }
 }
 
 pub trait StateComputer {
-    /// How to execute a command and obtain the next state.
+    /// How to execute a command and obtain the next state. Empty blocks must also yield a new
+    /// state, for instance by recording the time of execution.
     /// If execution fails, the value `None` is returned, meaning that the
     /// command should be rejected.
     fn compute(
         &mut self,
         // The state before executing the command.
         base_state: &State,
-        // Command to execute.
-        command: Command,
+        // Command to execute, or `None` for an empty block.
+        command: Option<Command>,
         // Time associated to this execution step, in agreement with
         // other consensus nodes.
         time: NodeTime,
@@ -29,15 +70,61 @@ pub trait StateComputer {
         // Suggest to reward the voters of the previous block, if any.
         previous_voters: Vec<Author>,
     ) -> Option<State>;
+
+    /// Check the command of a proposed block against the state after its parent, before voting
+    /// for it, so that semantically invalid commands (e.g. overspending) do not gather a quorum.
+    /// By default, all commands are valid.
+    fn validate_payload(&self, _parent_state: &State, _payload: Option<&Command>) -> bool {
+        true
+    }
+
+    /// Execute a block before it is committed. By default, this computes the state after the
+    /// command of the block.
+    fn speculate(
+        &mut self,
+        // The block to execute.
+        _block_hash: BlockHash,
+        block: &Block,
+        // The state after the previous block.
+        base_state: &State,
+        previous_author: Option<Author>,
+        previous_voters: Vec<Author>,
+    ) -> Option<State> {
+        self.compute(
+            base_state,
+            block.command.clone(),
+            block.time,
+            previous_author,
+            previous_voters,
+        )
+    }
 }
 
 /// How to communicate that a state was committed or discarded.
 pub trait StateFinalizer {
-    /// Report that a state was committed, together with a commit certificate.
-    fn commit(&mut self, state: &State, commit_certificate: Option<&QuorumCertificate>);
+    /// Report that a state was committed, together with the quorum time of its block and a
+    /// commit certificate. Quorum times do not decrease from one commit to the next and, unlike
+    /// the times proposed by leaders, cannot be moved by a minority of Byzantine voters, except
+    /// for the first block of each epoch.
+    fn commit(
+        &mut self,
+        state: &State,
+        time: NodeTime,
+        commit_certificate: Option<&QuorumCertificate>,
+    );
 
     /// Report that a state was discarded.
     fn discard(&mut self, state: &State);
+
+    /// Report that a commit conflicts with the given blocks, from the lowest round to the
+    /// highest, so that their speculative execution, if any, must be rolled back. By default,
+    /// nothing happens.
+    fn rollback(&mut self, _branch: &[BlockHash]) {}
+
+    /// Report that the commits up to the given state were delivered, rollbacks included, so
+    /// that the versions of the state machine before it and the states that do not extend it
+    /// will not be requested any more. By default, nothing happens.
+    fn prune(&mut self, _up_to_state: &State) {}
 }
 
 /// How to communicate that a state was committed or discarded.
@@ -49,5 +136,36 @@ pub trait EpochReader {
     fn configuration(&self, state: &State) -> EpochConfiguration;
 }
 
-pub trait SMRContext: CommandFetcher + StateComputer + StateFinalizer + EpochReader {}
+/// How to account for the signatures verified by the node, e.g. to model their CPU cost.
+pub trait SignatureVerifier {
+    /// Report that the node verified the given number of signatures. By default, nothing
+    /// happens.
+    fn verified_signatures(&mut self, _count: usize) {}
+
+    /// Report that the node verified the given number of signatures as a batch, in parallel.
+    /// By default, this is the same as verifying them one after the other.
+    fn verified_signatures_in_parallel(&mut self, count: usize) {
+        self.verified_signatures(count);
+    }
+}
+
+/// How to transfer a committed state to nodes that cannot replay the commits leading to it,
+/// e.g. validators joining the network.
+pub trait StateSnapshots {
+    /// Encode a committed state, if it is still available. By default, there are no snapshots.
+    fn export_snapshot(&self, _state: &State) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Replace the committed state by a snapshot, provided that it decodes to the given state,
+    /// starts the given epoch, and extends the current one. By default, snapshots are rejected.
+    fn import_snapshot(&mut self, _state: &State, _epoch: &EpochChange, _snapshot: &[u8]) -> bool {
+        false
+    }
+}
+
+pub trait SMRContext:
+    CommandFetcher + StateComputer + StateFinalizer + EpochReader + SignatureVerifier + StateSnapshots
+{
+}
 // -- END FILE --
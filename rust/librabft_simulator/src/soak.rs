@@ -0,0 +1,274 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Long runs through many epochs, with periodic reconfigurations and random faults, checking
+//! safety and memory bounds along the way, e.g. to exercise the record stores of past epochs.
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, Partition};
+use campaign::consistent_histories;
+use epoch_changes::{check_commit_logs, SHORT_EPOCH};
+use orphan_buffer::OrphanStats;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use scenario::{NodeSimulator, OrphanBufferParameters, Reconfiguration, Scenario};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[path = "unit_tests/soak_tests.rs"]
+mod soak_tests;
+
+/// Settings of a soak run.
+#[derive(PartialEq, Clone, Debug)]
+pub struct SoakParameters {
+    /// Epoch to reach before stopping.
+    pub epochs: usize,
+    /// Change the voting rights every this many epochs.
+    pub reconfiguration_period: usize,
+    /// Time between two checks of the invariants. Each window gets at most one fault, which
+    /// ends within the window.
+    pub check_interval: Duration,
+    /// Probability that a window gets a fault.
+    pub fault_rate: f64,
+    /// Number of past epochs whose records each replica keeps.
+    pub past_epochs_kept: usize,
+    /// Give up when the leading replica has not changed epoch for this many windows.
+    pub stall_windows: usize,
+}
+
+impl Default for SoakParameters {
+    fn default() -> Self {
+        SoakParameters {
+            epochs: 1000,
+            reconfiguration_period: 5,
+            check_interval: 500,
+            fault_rate: 0.3,
+            past_epochs_kept: 2,
+            stall_windows: 20,
+        }
+    }
+}
+
+/// Health of the honest replicas at the end of a soak run.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct SoakReport {
+    pub seed: u64,
+    /// Time at which the run stopped.
+    pub clock: i64,
+    /// Epoch reached by each honest replica.
+    pub epochs: Vec<usize>,
+    /// Number of commands committed by each honest replica.
+    pub commits: Vec<usize>,
+    /// Honest replicas more than `past_epochs_kept` epochs behind the leading one, which can no
+    /// longer catch up through data sync.
+    pub lagging: Vec<usize>,
+    pub reconfigurations: usize,
+    pub crashes: usize,
+    pub partitions: usize,
+    /// Number of windows after which the invariants were checked.
+    pub checks: usize,
+    /// Highest number of record stores of past epochs held by a replica at a check.
+    pub max_past_record_stores: usize,
+    /// Records received from the network and rejected by honest replicas, by reason.
+    pub rejections: BTreeMap<&'static str, usize>,
+    pub orphans: OrphanStats,
+    /// Whether the leading replica stopped changing epochs before reaching the target.
+    pub stalled: bool,
+    /// Why safety or a memory bound was broken, if it was. The run stops at the first
+    /// violation.
+    pub violation: Option<String>,
+}
+
+/// Derive the scenario of a soak run from `base`: short epochs, bounded past record stores
+/// and orphan buffers, and new random voting rights every `reconfiguration_period` epochs.
+pub fn soak_scenario(base: &Scenario, parameters: &SoakParameters, rng: &mut StdRng) -> Scenario {
+    let mut scenario = base.clone();
+    scenario.commands_per_epoch = SHORT_EPOCH;
+    scenario.past_epochs_kept = Some(parameters.past_epochs_kept);
+    if scenario.orphan_buffer.is_none() {
+        scenario.orphan_buffer = Some(OrphanBufferParameters::default());
+    }
+    scenario.reconfigurations = (1..=parameters.epochs / parameters.reconfiguration_period)
+        .map(|index| Reconfiguration {
+            epoch: index * parameters.reconfiguration_period,
+            voting_rights: (0..base.nodes).map(|_| rng.gen_range(1, 4)).collect(),
+        })
+        .collect();
+    scenario
+}
+
+/// Run `base` until its replicas reach `parameters.epochs`, adding faults window by window
+/// and checking after each window that the commit logs agree and that no replica keeps more
+/// past record stores than allowed.
+pub fn soak(base: &Scenario, seed: u64, parameters: &SoakParameters) -> Result<SoakReport> {
+    ensure!(
+        parameters.epochs > 0 && parameters.reconfiguration_period > 0,
+        "Soak runs need at least one epoch and a reconfiguration period."
+    );
+    ensure!(
+        parameters.check_interval > 1,
+        "Check intervals must be longer than one unit of time."
+    );
+    ensure!(
+        0.0 <= parameters.fault_rate && parameters.fault_rate <= 1.0,
+        "The fault rate must be in [0, 1]."
+    );
+    ensure!(
+        parameters.past_epochs_kept > 0,
+        "Replicas must keep the records of at least one past epoch."
+    );
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut scenario = soak_scenario(base, parameters, &mut rng);
+    scenario.seed = Some(seed);
+    let mut sim = scenario.build_simulator();
+    // Twins and flooders are not honest, nor are the nodes that twins duplicate.
+    let honest: Vec<_> = (0..scenario.nodes + scenario.followers)
+        .filter(|replica| {
+            !scenario.twins.contains(replica)
+                && !scenario
+                    .flooders
+                    .iter()
+                    .any(|flooder| flooder.node == *replica)
+        })
+        .collect();
+    let mut report = SoakReport {
+        seed,
+        clock: 0,
+        epochs: Vec::new(),
+        commits: Vec::new(),
+        lagging: Vec::new(),
+        reconfigurations: scenario.reconfigurations.len(),
+        crashes: 0,
+        partitions: 0,
+        checks: 0,
+        max_past_record_stores: 0,
+        rejections: BTreeMap::new(),
+        orphans: OrphanStats::default(),
+        stalled: false,
+        violation: None,
+    };
+    // Length of the prefix of the commit logs already checked.
+    let mut checked = 0;
+    let mut leading_epoch = 0;
+    let mut idle_windows = 0;
+    loop {
+        let start = sim.clock().0;
+        let end = start + parameters.check_interval;
+        if rng.gen_bool(parameters.fault_rate) {
+            add_fault(&mut sim, &scenario, &mut report, &mut rng, start, end);
+        }
+        sim.loop_until(GlobalTime(end), None);
+        report.checks += 1;
+        let nodes: Vec<_> = honest
+            .iter()
+            .map(|replica| sim.replicas()[*replica].node().node_state())
+            .collect();
+        let logs: Vec<_> = nodes
+            .iter()
+            .map(|node| &node.commits()[std::cmp::min(checked, node.commits().len())..])
+            .collect();
+        if let Err(error) = check_commit_logs(&logs) {
+            report.violation = Some(error.to_string());
+            break;
+        }
+        // Keep the last commit checked to compare it with the next ones.
+        checked = nodes
+            .iter()
+            .map(|node| node.commits().len())
+            .min()
+            .unwrap_or(0)
+            .saturating_sub(1);
+        let past_record_stores = nodes
+            .iter()
+            .map(|node| node.num_past_record_stores())
+            .max()
+            .unwrap_or(0);
+        report.max_past_record_stores =
+            std::cmp::max(report.max_past_record_stores, past_record_stores);
+        if past_record_stores > parameters.past_epochs_kept {
+            report.violation = Some(format!(
+                "A replica keeps {} past record stores instead of {}",
+                past_record_stores, parameters.past_epochs_kept
+            ));
+            break;
+        }
+        let epoch = nodes
+            .iter()
+            .map(|node| node.epoch_id().0)
+            .max()
+            .unwrap_or(0);
+        if epoch >= parameters.epochs {
+            break;
+        }
+        if epoch > leading_epoch {
+            leading_epoch = epoch;
+            idle_windows = 0;
+        } else {
+            idle_windows += 1;
+            if idle_windows >= parameters.stall_windows {
+                report.stalled = true;
+                break;
+            }
+        }
+    }
+    let contexts: Vec<_> = honest
+        .iter()
+        .map(|replica| sim.replicas()[*replica].context())
+        .collect();
+    if report.violation.is_none() && !consistent_histories(&contexts) {
+        report.violation = Some("Inconsistent committed histories".to_string());
+    }
+    report.commits = contexts
+        .iter()
+        .map(|context| context.committed_history().len())
+        .collect();
+    report.clock = sim.clock().0;
+    for replica in &honest {
+        let node = sim.replicas()[*replica].node().node_state();
+        report.epochs.push(node.epoch_id().0);
+        for (reason, count) in node.rejections() {
+            *report.rejections.entry(*reason).or_insert(0) += count;
+        }
+        if let Some(stats) = node.orphan_stats() {
+            report.orphans.add(stats);
+        }
+    }
+    let leading_epoch = report.epochs.iter().cloned().max().unwrap_or(0);
+    report.lagging = honest
+        .iter()
+        .zip(&report.epochs)
+        .filter(|(_, epoch)| **epoch + parameters.past_epochs_kept < leading_epoch)
+        .map(|(replica, _)| *replica)
+        .collect();
+    Ok(report)
+}
+
+/// Crash a validator or isolate a replica during a random part of `[start, end)`.
+fn add_fault(
+    sim: &mut NodeSimulator,
+    scenario: &Scenario,
+    report: &mut SoakReport,
+    rng: &mut StdRng,
+    start: i64,
+    end: i64,
+) {
+    let fault_start = rng.gen_range(start, end - 1);
+    let fault_end = rng.gen_range(fault_start + 1, end);
+    if rng.gen() {
+        let node = rng.gen_range(0, scenario.nodes);
+        sim.add_crash(Author(node), GlobalTime(fault_start), GlobalTime(fault_end));
+        report.crashes += 1;
+    } else {
+        let replicas = scenario.nodes + scenario.followers + scenario.twins.len();
+        let isolated = rng.gen_range(0, replicas);
+        // Replicas outside of the groups are isolated.
+        sim.add_partition(Partition {
+            groups: vec![(0..replicas)
+                .filter(|replica| *replica != isolated)
+                .collect()],
+            start: GlobalTime(fault_start),
+            end: GlobalTime(fault_end),
+        });
+        report.partitions += 1;
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Proptest strategies generating records, network schedules, and simulation parameters.
+
+use super::*;
+use pacemaker::PacemakerState;
+use proptest::{collection::vec, prelude::*, sample::Index};
+use record::*;
+use record_store::*;
+use scenario::{CrashFault, NetworkParameters, PacemakerParameters};
+use simulated_context::SimulatedContext;
+use smr_context::*;
+use std::collections::HashMap;
+
+#[cfg(test)]
+#[path = "unit_tests/strategies_tests.rs"]
+mod strategies_tests;
+
+/// What happens during one round of a generated execution.
+#[derive(Clone, Debug)]
+pub enum RoundOutcome {
+    /// The leader proposes a block and the given nodes vote for it.
+    Proposal { voters: Vec<Author> },
+    /// The given nodes time out.
+    Timeout { authors: Vec<Author> },
+}
+
+/// A small change turning a valid record sequence into a near-valid one.
+#[derive(Clone, Debug)]
+pub enum Mutation {
+    /// Remove a record.
+    Drop(Index),
+    /// Deliver a record twice.
+    Duplicate(Index),
+    /// Exchange two records.
+    Swap(Index, Index),
+    /// Claim another author without signing again.
+    Impersonate(Index, Author),
+    /// Move a record to another epoch without signing again.
+    ChangeEpoch(Index, EpochId),
+}
+
+/// Build the records of an execution in epoch 0 where rounds end as described by `outcomes`.
+/// Rounds that neither reach a QC nor a timeout certificate are closed by a timeout of every
+/// node, so that all the records remain valid.
+pub fn execute_rounds(num_nodes: usize, outcomes: &[RoundOutcome]) -> Vec<Record> {
+    let mut contexts: HashMap<_, _> = (0..num_nodes)
+        .map(|index| {
+            (
+                Author(index),
+                SimulatedContext::new(Author(index), num_nodes, 30000),
+            )
+        })
+        .collect();
+    let initial_state = contexts[&Author(0)].last_committed_state();
+    let epoch_id = EpochId(0);
+    let mut store = RecordStoreState::new(
+        epoch_id.initial_hash(),
+        initial_state.clone(),
+        epoch_id,
+        contexts[&Author(0)].configuration(&initial_state),
+    );
+    let mut records = Vec::new();
+    for (index, outcome) in outcomes.iter().enumerate() {
+        let round = store.current_round();
+        let time = NodeTime(10 * index as i64);
+        let mut timeout_authors = Vec::new();
+        match outcome {
+            RoundOutcome::Proposal { voters } => {
+                let leader = PacemakerState::leader(&store, round);
+                let context = contexts.get_mut(&leader).unwrap();
+                let command = match context.fetch() {
+                    Some(command) => command,
+                    None => break,
+                };
+                let block = Record::make_block(
+                    epoch_id,
+                    Some(command),
+                    time,
+                    store.highest_quorum_certificate_hash(),
+                    round,
+                    store.public_key(leader),
+                    store.chain_id(),
+                    &store.hash_function(),
+                );
+                let block_hash = BlockHash(block.digest(&store.hash_function()));
+                store
+                    .insert_network_record(block.clone(), context)
+                    .expect("Blocks of the leader should be valid");
+                records.push(block);
+                for voter in voters {
+                    if store
+                        .create_vote(*voter, block_hash, time, contexts.get_mut(voter).unwrap())
+                        .is_ok()
+                    {
+                        if let Some(vote) = store.current_vote(*voter) {
+                            records.push(Record::Vote(vote.clone()));
+                        }
+                    }
+                }
+                if store
+                    .check_for_new_quorum_certificate(leader, contexts.get_mut(&leader).unwrap())
+                    == Ok(true)
+                {
+                    let qc = store.highest_quorum_certificate().unwrap();
+                    records.push(Record::QuorumCertificate(qc.clone()));
+                }
+            }
+            RoundOutcome::Timeout { authors } => timeout_authors = authors.clone(),
+        }
+        if store.current_round() == round {
+            timeout_authors.extend((0..num_nodes).map(Author));
+        }
+        for author in timeout_authors {
+            if store.current_round() > round || store.has_timeout(author, round) {
+                continue;
+            }
+            let timeout = Record::make_timeout(
+                epoch_id,
+                round,
+                store.highest_quorum_certificate_round(),
+                store.public_key(author),
+                store.chain_id(),
+                &store.hash_function(),
+            );
+            store
+                .insert_network_record(timeout.clone(), contexts.get_mut(&author).unwrap())
+                .expect("Timeouts at the current round should be valid");
+            records.push(timeout);
+        }
+    }
+    records
+}
+
+/// Apply a mutation to a record sequence.
+pub fn apply_mutation(records: &mut Vec<Record>, mutation: &Mutation) {
+    if records.is_empty() {
+        return;
+    }
+    match mutation {
+        Mutation::Drop(index) => {
+            records.remove(index.index(records.len()));
+        }
+        Mutation::Duplicate(index) => {
+            let record = records[index.index(records.len())].clone();
+            records.push(record);
+        }
+        Mutation::Swap(index1, index2) => {
+            let len = records.len();
+            records.swap(index1.index(len), index2.index(len));
+        }
+        Mutation::Impersonate(index, author) => {
+            let len = records.len();
+            match &mut records[index.index(len)] {
+                Record::Block(x) => x.author = *author,
+                Record::Vote(x) => x.author = *author,
+                Record::QuorumCertificate(x) => x.author = *author,
+                Record::Timeout(x) => x.author = *author,
+            }
+        }
+        Mutation::ChangeEpoch(index, epoch_id) => {
+            let len = records.len();
+            match &mut records[index.index(len)] {
+                Record::Block(_) => (),
+                Record::Vote(x) => x.epoch_id = *epoch_id,
+                Record::QuorumCertificate(x) => x.epoch_id = *epoch_id,
+                Record::Timeout(x) => x.epoch_id = *epoch_id,
+            }
+        }
+    }
+}
+
+pub fn arb_author(num_nodes: usize) -> impl Strategy<Value = Author> {
+    (0..num_nodes).prop_map(Author)
+}
+
+pub fn arb_round_outcome(num_nodes: usize) -> impl Strategy<Value = RoundOutcome> {
+    let authors = || {
+        proptest::sample::subsequence(
+            (0..num_nodes).map(Author).collect::<Vec<_>>(),
+            0..=num_nodes,
+        )
+    };
+    prop_oneof![
+        3 => authors().prop_map(|voters| RoundOutcome::Proposal { voters }),
+        1 => authors().prop_map(|authors| RoundOutcome::Timeout { authors }),
+    ]
+}
+
+/// Valid records of an execution with up to `max_rounds` rounds, in creation order.
+pub fn arb_valid_records(
+    num_nodes: usize,
+    max_rounds: usize,
+) -> impl Strategy<Value = Vec<Record>> {
+    vec(arb_round_outcome(num_nodes), 0..=max_rounds)
+        .prop_map(move |outcomes| execute_rounds(num_nodes, &outcomes))
+}
+
+pub fn arb_mutation(num_nodes: usize) -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        any::<Index>().prop_map(Mutation::Drop),
+        any::<Index>().prop_map(Mutation::Duplicate),
+        (any::<Index>(), any::<Index>()).prop_map(|(i, j)| Mutation::Swap(i, j)),
+        (any::<Index>(), arb_author(num_nodes)).prop_map(|(i, a)| Mutation::Impersonate(i, a)),
+        (any::<Index>(), (0..3usize).prop_map(EpochId))
+            .prop_map(|(i, e)| Mutation::ChangeEpoch(i, e)),
+    ]
+}
+
+/// Valid records altered by up to `max_mutations` mutations.
+pub fn arb_near_valid_records(
+    num_nodes: usize,
+    max_rounds: usize,
+    max_mutations: usize,
+) -> impl Strategy<Value = Vec<Record>> {
+    (
+        arb_valid_records(num_nodes, max_rounds),
+        vec(arb_mutation(num_nodes), 0..=max_mutations),
+    )
+        .prop_map(|(mut records, mutations)| {
+            for mutation in &mutations {
+                apply_mutation(&mut records, mutation);
+            }
+            records
+        })
+}
+
+/// A network schedule: the order in which each node receives the given records.
+pub fn arb_network_schedule(
+    num_nodes: usize,
+    records: Vec<Record>,
+) -> impl Strategy<Value = Vec<Vec<Record>>> {
+    vec(Just(records).prop_shuffle(), num_nodes)
+}
+
+pub fn arb_pacemaker_parameters() -> impl Strategy<Value = PacemakerParameters> {
+    (1..100i64, 1.0..3.0f64, 0.1..1.0f64, any::<bool>()).prop_map(
+        |(delta, gamma, lambda, optimistic_responsiveness)| PacemakerParameters {
+            delta,
+            gamma,
+            lambda,
+            optimistic_responsiveness,
+            ..PacemakerParameters::default()
+        },
+    )
+}
+
+pub fn arb_network_parameters() -> impl Strategy<Value = NetworkParameters> {
+    (1.0..40.0f64, 0.0..20.0f64).prop_map(|(mean, variance)| NetworkParameters {
+        mean,
+        variance,
+        ..NetworkParameters::default()
+    })
+}
+
+/// Crash faults of arbitrary nodes during `[0, max_clock)`, possibly without recovery.
+pub fn arb_crash_faults(
+    num_nodes: usize,
+    max_clock: i64,
+    max_faults: usize,
+) -> impl Strategy<Value = Vec<CrashFault>> {
+    let fault = (
+        0..num_nodes,
+        0..max_clock,
+        proptest::option::of(1..max_clock),
+    )
+        .prop_map(|(node, start, duration)| CrashFault {
+            node,
+            start,
+            end: duration.map(|duration| start + duration),
+        });
+    vec(fault, 0..=max_faults)
+}
@@ -0,0 +1,180 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use base_types::*;
+use hashing::HashFunction;
+use record::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[cfg(test)]
+#[path = "unit_tests/test_vectors_tests.rs"]
+mod test_vectors_tests;
+
+/// All test vectors belong to the first epoch.
+const EPOCH_ID: EpochId = EpochId(0);
+
+/// Test vectors use the hash function of deployments, on a network of their own.
+const HASH_FUNCTION: HashFunction = HashFunction::Sha3_256;
+const CHAIN_ID: ChainId = ChainId(1);
+
+/// A record together with the digest and signature that compatible implementations must
+/// reproduce.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Short description of the vector.
+    pub name: String,
+    /// The record as it is sent on the network.
+    pub record: Record,
+    /// Expected digest of the record.
+    pub digest: u64,
+    /// Expected signature of the record by its author.
+    pub signature: Signature,
+}
+
+impl TestVector {
+    fn new(name: &str, record: Record) -> Self {
+        let digest = record.digest(&HASH_FUNCTION);
+        let (_, signature) = record.signer();
+        TestVector {
+            name: name.to_string(),
+            record,
+            digest,
+            signature,
+        }
+    }
+
+    /// Check that the record still hashes and verifies to the expected values.
+    pub fn check(&self) -> Result<()> {
+        let digest = self.record.digest(&HASH_FUNCTION);
+        ensure!(
+            digest == self.digest,
+            "Test vector {}: digest changed from {} to {}",
+            self.name,
+            self.digest,
+            digest
+        );
+        if let Record::QuorumCertificate(qc) = &self.record {
+            for (author, time, signature) in &qc.votes {
+                signature.check(
+                    qc.vote_signing_hash(*author, *time, CHAIN_ID, &HASH_FUNCTION),
+                    *author,
+                )?;
+            }
+        }
+        let (author, _) = self.record.signer();
+        self.signature.check(
+            self.record.signing_hash(EPOCH_ID, CHAIN_ID, &HASH_FUNCTION),
+            author,
+        )?;
+        Ok(())
+    }
+}
+
+/// Build a fixed chain of three certified blocks in epoch 0 with three authors of equal
+/// voting rights, so that the last QC is a commit certificate, which also announces epoch 1.
+/// States are constants so that the vectors do not depend on a particular SMR context.
+pub fn canonical_test_vectors() -> Vec<TestVector> {
+    let epoch_id = EPOCH_ID;
+    let authors = [Author(0), Author(1), Author(2)];
+    let mut vectors = Vec::new();
+    let mut previous_qc_hash = epoch_id.initial_hash();
+    let mut certified_states = Vec::new();
+    let next_epoch = EpochChange::new(
+        EpochId(1),
+        &EpochConfiguration::new(authors.iter().map(|author| (*author, 1)).collect()),
+    );
+    for round in 1..=3 {
+        let leader = authors[round % authors.len()];
+        let block = Record::make_block(
+            epoch_id,
+            Some(Command {
+                proposer: leader,
+                index: round,
+            }),
+            NodeTime(10 * round as i64),
+            previous_qc_hash,
+            Round(round),
+            leader,
+            CHAIN_ID,
+            &HASH_FUNCTION,
+        );
+        let certified_block_hash = BlockHash(block.digest(&HASH_FUNCTION));
+        let state = State(1000 + round as u64);
+        // The third QC of a contiguous chain commits the state of the first block.
+        let (committed_state, next_epoch) = if round == 3 {
+            (certified_states.first().cloned(), Some(next_epoch))
+        } else {
+            (None, None)
+        };
+        let votes: Vec<_> = authors
+            .iter()
+            .map(|author| {
+                Record::make_vote(
+                    epoch_id,
+                    Round(round),
+                    certified_block_hash,
+                    state.clone(),
+                    *author,
+                    committed_state.clone(),
+                    next_epoch,
+                    NodeTime(10 * round as i64 + 1 + author.0 as i64),
+                    CHAIN_ID,
+                    &HASH_FUNCTION,
+                )
+            })
+            .collect();
+        let signatures = votes
+            .iter()
+            .map(|vote| match vote {
+                Record::Vote(x) => (x.author, x.time, x.signature),
+                _ => unreachable!(),
+            })
+            .collect();
+        let qc = Record::make_quorum_certificate(
+            epoch_id,
+            Round(round),
+            certified_block_hash,
+            state.clone(),
+            signatures,
+            committed_state,
+            next_epoch,
+            leader,
+            CHAIN_ID,
+            &HASH_FUNCTION,
+        );
+        previous_qc_hash = QuorumCertificateHash(qc.digest(&HASH_FUNCTION));
+        certified_states.push(state);
+
+        vectors.push(TestVector::new(&format!("block_round_{}", round), block));
+        if round == 1 {
+            vectors.push(TestVector::new("vote_round_1", votes[0].clone()));
+        }
+        let name = if round == 3 {
+            "commit_certificate_round_3".to_string()
+        } else {
+            format!("quorum_certificate_round_{}", round)
+        };
+        vectors.push(TestVector::new(&name, qc));
+    }
+    vectors.push(TestVector::new(
+        "timeout_round_4",
+        Record::make_timeout(
+            epoch_id,
+            Round(4),
+            Round(3),
+            authors[1],
+            CHAIN_ID,
+            &HASH_FUNCTION,
+        ),
+    ));
+    vectors
+}
+
+/// Regenerate the canonical test vectors in JSON format.
+pub fn write_test_vectors(path: &str) -> Result<()> {
+    let data = serde_json::to_string_pretty(&canonical_test_vectors())?;
+    fs::write(path, data + "\n")?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! What a node knows of a round, for the triage bundles written by the simulator when honest
+//! nodes commit different blocks or states at the same round.
+
+use super::*;
+use bft_simulator_runtime::RoundTriage;
+use node::NodeState;
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "unit_tests/triage_tests.rs"]
+mod triage_tests;
+
+/// A block known at the round.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct BlockSummary {
+    pub hash: BlockHash,
+    pub author: Author,
+    pub previous_quorum_certificate_hash: QuorumCertificateHash,
+    pub command: Option<Command>,
+    pub time: NodeTime,
+}
+
+/// A vote for a block of the round.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct VoteSummary {
+    pub author: Author,
+    pub certified_block_hash: BlockHash,
+    pub state: State,
+    pub committed_state: Option<State>,
+}
+
+/// A QC known at the round.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct QuorumCertificateSummary {
+    pub hash: QuorumCertificateHash,
+    pub author: Author,
+    pub certified_block_hash: BlockHash,
+    pub state: State,
+    pub committed_state: Option<State>,
+    pub voters: Vec<Author>,
+}
+
+/// The view of a node on a round. Locks and highest rounds are those of the node when the
+/// summary is taken, which may be in a later epoch.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize)]
+pub struct RoundSummary {
+    pub node_epoch: EpochId,
+    pub current_round: Round,
+    pub locked_round: Round,
+    pub latest_voted_round: Round,
+    pub highest_quorum_certificate_round: Round,
+    pub highest_committed_round: Round,
+    /// Whether the node still keeps the records of the epoch of the round. Blocks, votes, and
+    /// QCs are empty otherwise.
+    pub records_available: bool,
+    pub blocks: Vec<BlockSummary>,
+    /// Votes held by the node for the round, only kept while the round is current.
+    pub votes: Vec<VoteSummary>,
+    pub quorum_certificates: Vec<QuorumCertificateSummary>,
+    /// Blocks committed by the node at the round, with the digests of the resulting states.
+    pub commits: Vec<(BlockHash, State)>,
+}
+
+impl RoundSummary {
+    pub fn of(node: &NodeState, epoch_id: EpochId, round: Round) -> Self {
+        let store = node.record_store();
+        let mut summary = RoundSummary {
+            node_epoch: node.epoch_id(),
+            current_round: store.current_round(),
+            locked_round: node.locked_round(),
+            latest_voted_round: node.latest_voted_round(),
+            highest_quorum_certificate_round: store.highest_quorum_certificate_round(),
+            highest_committed_round: store.highest_committed_round(),
+            records_available: false,
+            blocks: Vec::new(),
+            votes: Vec::new(),
+            quorum_certificates: Vec::new(),
+            commits: node
+                .commits()
+                .iter()
+                .filter(|commit| commit.epoch == epoch_id.0 && commit.round == round)
+                .map(|commit| (commit.block, commit.state.clone()))
+                .collect(),
+        };
+        let (store, configuration) = match (
+            node.record_store_at(epoch_id),
+            node.configuration_at(epoch_id),
+        ) {
+            (Some(store), Some(configuration)) => (store, configuration),
+            _ => return summary,
+        };
+        summary.records_available = true;
+        summary.blocks = store
+            .blocks_at(round)
+            .into_iter()
+            .filter_map(|hash| {
+                store.block(hash).map(|block| BlockSummary {
+                    hash,
+                    author: block.author,
+                    previous_quorum_certificate_hash: block.previous_quorum_certificate_hash,
+                    command: block.command.clone(),
+                    time: block.time,
+                })
+            })
+            .collect();
+        summary.votes = configuration
+            .voting_rights()
+            .keys()
+            .filter_map(|author| store.current_vote(*author))
+            .filter(|vote| vote.round == round)
+            .map(|vote| VoteSummary {
+                author: vote.author,
+                certified_block_hash: vote.certified_block_hash,
+                state: vote.state.clone(),
+                committed_state: vote.committed_state.clone(),
+            })
+            .collect();
+        summary.quorum_certificates = store
+            .quorum_certificates_at(round)
+            .into_iter()
+            .filter_map(|hash| {
+                store
+                    .quorum_certificate(hash)
+                    .map(|qc| QuorumCertificateSummary {
+                        hash,
+                        author: qc.author,
+                        certified_block_hash: qc.certified_block_hash,
+                        state: qc.state.clone(),
+                        committed_state: qc.committed_state.clone(),
+                        voters: qc.votes.iter().map(|(author, _, _)| *author).collect(),
+                    })
+            })
+            .collect();
+        summary
+    }
+}
+
+impl RoundTriage for NodeState {
+    type Summary = RoundSummary;
+
+    fn round_summary(&self, epoch: usize, round: Round) -> RoundSummary {
+        RoundSummary::of(self, EpochId(epoch), round)
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! Statistics on the rounds that did not go well in a run: streaks of rounds without a QC,
+//! branches of blocks abandoned by the committed chain, and why the leaders of the failed
+//! rounds did not make progress.
+
+use super::*;
+use pacemaker::PacemakerState;
+use record_store::RecordStore;
+use scenario::{NodeSimulator, Scenario};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+#[cfg(test)]
+#[path = "unit_tests/unhappy_path_tests.rs"]
+mod unhappy_path_tests;
+
+/// Why the leader of a round without QC did not make progress.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+pub enum LeaderFailure {
+    /// The leader was crashed when the round started.
+    Crashed,
+    /// The leader is a Byzantine validator of the scenario, or one of its twins.
+    Byzantine,
+    /// The leader was up and honest: its proposal or the votes were lost or too slow.
+    Network,
+}
+
+/// Unhappy-path statistics of a run, as seen by the validator with the most commits.
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct UnhappyPathReport {
+    /// Number of maximal streaks of consecutive rounds without QC, by length.
+    pub failed_round_streaks: BTreeMap<usize, usize>,
+    /// Number of abandoned branches, i.e. chains of blocks conflicting with the committed
+    /// chain, by number of blocks.
+    pub fork_depths: BTreeMap<usize, usize>,
+    /// Number of rounds without QC, by cause of the failure of their leader.
+    pub leader_failures: BTreeMap<LeaderFailure, usize>,
+}
+
+impl UnhappyPathReport {
+    pub fn failed_rounds(&self) -> usize {
+        self.leader_failures.values().sum()
+    }
+
+    /// Account for the rounds of the given record store, whose epoch has the given voting
+    /// rights. `leader_failure` tells why the leader of a failed round did not make progress,
+    /// given the local time at which the round started.
+    pub fn observe_epoch<F>(
+        &mut self,
+        record_store: &RecordStore,
+        configuration: &EpochConfiguration,
+        leader_failure: F,
+    ) where
+        F: Fn(Author, NodeTime) -> LeaderFailure,
+    {
+        let mut blocks_by_round = BTreeMap::new();
+        for author in configuration.voting_rights().keys() {
+            for hash in record_store.blocks_proposed_by(*author) {
+                let block = record_store.block(hash).unwrap();
+                blocks_by_round.entry(block.round).or_insert(block);
+            }
+        }
+        let certified_rounds = record_store.certified_rounds();
+        let mut streak = 0;
+        for round in 1..=record_store.highest_quorum_certificate_round().0 {
+            let round = Round(round);
+            if certified_rounds.contains(&round) {
+                if streak > 0 {
+                    *self.failed_round_streaks.entry(streak).or_insert(0) += 1;
+                }
+                streak = 0;
+                continue;
+            }
+            streak += 1;
+            // Prefer the author of an actual proposal, in case leader reputation was used.
+            let (leader, start_time) = match blocks_by_round.get(&round) {
+                Some(block) => (block.author, block.time),
+                None => (
+                    PacemakerState::leader(record_store, round),
+                    blocks_by_round
+                        .range(..round)
+                        .next_back()
+                        .map_or(NodeTime(0), |(_, block)| block.time),
+                ),
+            };
+            *self
+                .leader_failures
+                .entry(leader_failure(leader, start_time))
+                .or_insert(0) += 1;
+        }
+        self.observe_forks(record_store);
+    }
+
+    fn observe_forks(&mut self, record_store: &RecordStore) {
+        let committed_hash = match record_store.committed_blocks(Round(0)).last() {
+            Some(commit) => commit.block_hash,
+            None => return,
+        };
+        let conflicting = record_store.blocks_conflicting_with(committed_hash);
+        let conflicting_set: HashSet<_> = conflicting.iter().cloned().collect();
+        let parent = |hash: BlockHash| {
+            let block = record_store.block(hash).unwrap();
+            record_store
+                .quorum_certificate(block.previous_quorum_certificate_hash)
+                .map(|qc| qc.certified_block_hash)
+                .filter(|parent| conflicting_set.contains(parent))
+        };
+        // Blocks are sorted by round, so parents come before their children.
+        let mut depths = HashMap::new();
+        let mut parents = HashSet::new();
+        for hash in &conflicting {
+            let depth = match parent(*hash) {
+                Some(parent) => {
+                    parents.insert(parent);
+                    depths[&parent] + 1
+                }
+                None => 1,
+            };
+            depths.insert(*hash, depth);
+        }
+        for hash in conflicting.iter().filter(|hash| !parents.contains(hash)) {
+            *self.fork_depths.entry(depths[hash]).or_insert(0) += 1;
+        }
+    }
+}
+
+/// Validators that misbehave in the scenario, twins included.
+pub fn byzantine_validators(scenario: &Scenario) -> BTreeSet<usize> {
+    let mut nodes = BTreeSet::new();
+    nodes.extend(scenario.twins.iter().cloned());
+    nodes.extend(scenario.flooders.iter().map(|flooder| flooder.node));
+    nodes.extend(scenario.lock_violators.iter().cloned());
+    nodes.extend(scenario.qc_withholders.iter().cloned());
+    nodes.extend(scenario.double_spenders.iter().cloned());
+    nodes.extend(scenario.censors.iter().map(|censor| censor.node));
+    nodes
+}
+
+/// Compute the unhappy-path statistics of a finished run of `scenario`, over the epochs whose
+/// record stores are still kept by the honest validator with the most commits. Local times are
+/// compared with the crash faults as if clocks were exact.
+pub fn analyze_unhappy_path(scenario: &Scenario, sim: &NodeSimulator) -> UnhappyPathReport {
+    let byzantine = byzantine_validators(scenario);
+    let leader_failure = |leader: Author, time: NodeTime| {
+        if byzantine.contains(&leader.0) {
+            LeaderFailure::Byzantine
+        } else if scenario.faults.iter().any(|fault| {
+            fault.node == leader.0
+                && fault.start <= time.0
+                && fault.end.is_none_or(|end| time.0 < end)
+        }) {
+            LeaderFailure::Crashed
+        } else {
+            LeaderFailure::Network
+        }
+    };
+    let mut report = UnhappyPathReport::default();
+    let reference = sim.replicas()[..scenario.nodes]
+        .iter()
+        .filter(|replica| !byzantine.contains(&replica.author().0))
+        .max_by_key(|replica| replica.context().committed_history().len());
+    if let Some(replica) = reference {
+        let node = replica.node().node_state();
+        for epoch_id in 0..=node.epoch_id().0 {
+            let epoch_id = EpochId(epoch_id);
+            if let (Some(record_store), Some(configuration)) = (
+                node.record_store_at(epoch_id),
+                node.configuration_at(epoch_id),
+            ) {
+                report.observe_epoch(record_store, configuration, leader_failure);
+            }
+        }
+    }
+    report
+}
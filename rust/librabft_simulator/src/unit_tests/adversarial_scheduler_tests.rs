@@ -0,0 +1,42 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use record::Record;
+use record_builder::RecordBuilder;
+
+/// A notification from `author` to `receiver` carrying its vote for the given block.
+fn vote(
+    author: usize,
+    receiver: usize,
+    block: u64,
+) -> Event<DataSyncNotification, DataSyncRequest, DataSyncResponse> {
+    let vote = match RecordBuilder::vote(Author(author))
+        .certified_block(BlockHash(block))
+        .build()
+    {
+        Record::Vote(vote) => vote,
+        _ => unreachable!(),
+    };
+    Event::DataSyncNotifyEvent {
+        receiver: Author(receiver),
+        sender: Author(author),
+        notification: DataSyncNotification::with_records(EpochId(0), Vec::new(), Some(vote), None),
+    }
+}
+
+#[test]
+fn test_quorum_delay_scheduler() {
+    let configuration = EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect());
+    let mut scheduler = QuorumDelayScheduler::new(configuration, 50);
+    let mut delay = |event| scheduler.extra_delay(GlobalTime(0), GlobalTime(10), &event);
+    assert_eq!(delay(vote(0, 0, 1)), 0);
+    assert_eq!(delay(vote(1, 0, 1)), 0);
+    // The third vote would complete a quorum of 3 votes out of 4, and so would the fourth.
+    assert_eq!(delay(vote(2, 0, 1)), 50);
+    assert_eq!(delay(vote(3, 0, 1)), 50);
+    // Copies of delivered votes go through, as well as votes for other blocks or receivers.
+    assert_eq!(delay(vote(1, 0, 1)), 0);
+    assert_eq!(delay(vote(2, 0, 2)), 0);
+    assert_eq!(delay(vote(2, 1, 1)), 0);
+}
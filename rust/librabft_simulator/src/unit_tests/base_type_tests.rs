@@ -18,4 +18,17 @@ fn test_merge_sort() {
         vec![0, 2, 5, 6, 9],
         merge_sort(vec![0, 2, 6, 9], vec![2, 5, 6], u64::cmp),
     );
+    // Elements of the same rank are all kept, unless they are equal.
+    assert_eq!(
+        vec![(0, 'a'), (1, 'b'), (1, 'c'), (2, 'd')],
+        merge_sort(
+            vec![(0, 'a'), (1, 'b')],
+            vec![(1, 'c'), (2, 'd')],
+            |x, y| x.0.cmp(&y.0)
+        ),
+    );
+    assert_eq!(
+        vec![(1, 'b')],
+        merge_sort(vec![(1, 'b')], vec![(1, 'b')], |x, y| x.0.cmp(&y.0)),
+    );
 }
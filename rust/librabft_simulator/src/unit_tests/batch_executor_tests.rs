@@ -0,0 +1,55 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use simulated_context::SimulatedContext;
+use smr_context::CommandFetcher;
+
+#[test]
+fn test_compute_transactions() {
+    let mut context = SimulatedContext::new(Author(0), 2, 100);
+    let s0 = context.last_committed_state();
+    let c1 = context.fetch().unwrap();
+    let c2 = context.fetch().unwrap();
+    let s1 = context
+        .compute(&s0, Some(c1.clone()), NodeTime(1), None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .compute(&s1, Some(c2.clone()), NodeTime(1), None, Vec::new())
+        .unwrap();
+    // Batches are computed one command after the other.
+    assert_eq!(
+        compute_batch(
+            &mut context,
+            &s0,
+            &[c1.clone(), c2],
+            NodeTime(1),
+            None,
+            Vec::new()
+        ),
+        Some(s2)
+    );
+    assert_eq!(
+        compute_batch(&mut context, &s0, &[], NodeTime(1), None, Vec::new()),
+        context.compute(&s0, None, NodeTime(1), None, Vec::new())
+    );
+
+    // The adapter drops the commands of aborted transactions.
+    let mut transaction = ComputeTransaction::new(&mut context);
+    assert!(transaction.begin_transaction(&s0, NodeTime(1), None, Vec::new()));
+    assert!(transaction.apply_command(&c1));
+    transaction.abort_transaction();
+    assert_eq!(transaction.commit_transaction(), None);
+    // Commands cannot be executed after a missing state.
+    assert_eq!(
+        execute_batch(
+            &mut transaction,
+            &State(7),
+            &[c1],
+            NodeTime(1),
+            None,
+            Vec::new()
+        ),
+        None
+    );
+}
@@ -0,0 +1,602 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::inbound_queue::QueuePolicy;
+use bft_simulator_runtime::simulator::StopReason;
+use scenario::{OrphanBufferParameters, StopParameters};
+
+const CRASH_ONE_NODE: &str = include_str!("../../scenarios/crash_one_node.toml");
+const REORDERING: &str = include_str!("../../scenarios/reordering.toml");
+const COMMITTEE: &str = include_str!("../../scenarios/committee.toml");
+const COMMITTEE_LARGE: &str = include_str!("../../scenarios/committee_large.toml");
+
+#[test]
+fn test_runs_are_reproducible() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let report = run(&scenario, Some(7));
+    assert_eq!(report.seed, 7);
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+    assert_eq!(run(&scenario, Some(7)), report);
+
+    let (replayed, trace) = replay(&scenario, 7);
+    assert_eq!(replayed, report);
+    assert!(!trace.is_empty());
+    // Node 3 is crashed during [300, 900).
+    assert!(trace.iter().any(|entry| entry.dropped));
+    assert!(trace
+        .iter()
+        .filter(|entry| entry.dropped)
+        .all(|entry| entry.target == Author(3)));
+}
+
+#[test]
+fn test_branch_from_snapshot() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let report = run(&scenario, Some(7));
+    // Resuming the original run from the snapshot changes nothing.
+    let (original, branched) = branch(&scenario, 7, 600, 8);
+    assert_eq!(original, report);
+    // Another schedule after the snapshot gives another run, which is still safe and
+    // reproducible.
+    assert!(branched.consistent);
+    assert_ne!(branched.bandwidth, report.bandwidth);
+    assert_eq!(branch(&scenario, 7, 600, 8).1, branched);
+}
+
+#[test]
+fn test_saved_snapshots() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let path = std::env::temp_dir().join(format!("snapshot_tests_{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    save_snapshot(&scenario, 7, 600, path).unwrap();
+    // Resuming from the file gives the same run as without a snapshot.
+    assert_eq!(resume(&scenario, path).unwrap(), run(&scenario, Some(7)));
+    // Snapshots that the replay does not reproduce are rejected.
+    let mut snapshot = SimulationSnapshot::load(path).unwrap();
+    assert!(!snapshot.pending_events.is_empty());
+    assert!(snapshot
+        .replicas
+        .iter()
+        .all(|replica| !replica.commits.is_empty()));
+    snapshot.rng.1 += 1;
+    snapshot.save(path).unwrap();
+    assert!(resume(&scenario, path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_analyze_and_fuzz() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let report = analyze(&scenario, 0..3);
+    assert_eq!(report.runs, 3);
+    assert!(report.min_commits > 0);
+    assert!(report.inconsistent_seeds.is_empty());
+
+    let report = fuzz(&scenario, 0, 3);
+    assert_eq!(report.iterations, 3);
+    assert!(report.failures.is_empty());
+}
+
+#[test]
+fn test_optimistic_responsiveness() {
+    let mut scenario = Scenario::from_toml("nodes = 4\nmax_clock = 2000").unwrap();
+    let responsive = run(&scenario, Some(3)).mean_block_interval.unwrap();
+    scenario.pacemaker.optimistic_responsiveness = false;
+    let report = run(&scenario, Some(3));
+    assert!(report.consistent);
+    // Leaders now wait for `delta` in every round. Proposal times are local to each leader, so
+    // this is only approximate.
+    assert!(report.mean_block_interval.unwrap() > responsive + scenario.pacemaker.delta / 2);
+}
+
+#[test]
+fn test_sketches_reduce_sync_traffic() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let report = run(&scenario, Some(7));
+    scenario.sketch_cells = Some(24);
+    let sketched = run(&scenario, Some(7));
+    assert!(sketched.consistent);
+    // Peers send the exact difference instead of every record of the rounds that differ.
+    assert!(sketched.reconciliation.records_received < report.reconciliation.records_received);
+    assert!(sketched.reconciliation.efficiency() > report.reconciliation.efficiency());
+}
+
+#[test]
+fn test_bandwidth_accounting() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 2000").unwrap();
+    let bandwidth = run(&scenario, Some(3)).bandwidth;
+    assert!(bandwidth.total_bytes > 0);
+    assert_eq!(bandwidth.sent.iter().sum::<usize>(), bandwidth.total_bytes);
+    assert_eq!(
+        bandwidth.received.iter().sum::<usize>(),
+        bandwidth.total_bytes
+    );
+    assert_eq!(
+        bandwidth.by_message.values().sum::<usize>(),
+        bandwidth.total_bytes
+    );
+    assert_eq!(
+        bandwidth.by_content.values().sum::<usize>(),
+        bandwidth.total_bytes
+    );
+    assert_eq!(
+        bandwidth.time_series.iter().sum::<usize>(),
+        bandwidth.total_bytes
+    );
+    // Intervals of 1000 up to `max_clock`, included.
+    assert_eq!(bandwidth.time_series.len(), 3);
+    assert!(bandwidth.time_series[0] > 0 && bandwidth.time_series[1] > 0);
+    for kind in &["block", "vote", "quorum_certificate", "summary"] {
+        assert!(bandwidth.by_content[kind] > 0);
+    }
+    // Without failures, leaders mostly send blocks and QCs while other nodes mostly send votes.
+    assert!(bandwidth.by_message["notify"] > bandwidth.by_message["response"]);
+}
+
+#[test]
+fn test_cpu_costs_reduce_throughput() {
+    let mut scenario = Scenario::from_toml("nodes = 4\nmax_clock = 2000").unwrap();
+    let free = run(&scenario, Some(3));
+    scenario.cpu.signature_verification = 1;
+    scenario.cpu.execution = 10;
+    let report = run(&scenario, Some(3));
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+    // Verifying QCs and executing blocks now delays every round.
+    assert!(report.commits.iter().max() < free.commits.iter().min());
+    assert!(report.mean_block_interval > free.mean_block_interval);
+}
+
+#[test]
+fn test_clock_drift() {
+    let mut scenario =
+        Scenario::from_toml(include_str!("../../scenarios/clock_skew.toml")).unwrap();
+    let report = run(&scenario, Some(3));
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+    // Timeouts take longer with slow clocks, so fewer rounds are led by live nodes.
+    scenario.clocks = (0..4)
+        .map(|node| scenario::NodeClock {
+            node,
+            offset: 0,
+            drift: -0.5,
+        })
+        .collect();
+    let slow = run(&scenario, Some(3));
+    assert!(slow.consistent);
+    assert!(slow.commits.iter().max() < report.commits.iter().min());
+}
+
+#[test]
+fn test_adaptive_delta() {
+    let scenario =
+        Scenario::from_toml(include_str!("../../scenarios/adaptive_delta.toml")).unwrap();
+    let report = run(&scenario, Some(3));
+    assert!(report.consistent);
+    let learned_deltas = report.learned_deltas.unwrap();
+    assert_eq!(learned_deltas.len(), 4);
+    for deltas in learned_deltas {
+        // Delta quickly drops from its initial value to about twice the latency of a round..
+        let (_, before) = deltas
+            .iter()
+            .rev()
+            .find(|(time, _)| *time < NodeTime(1400))
+            .unwrap();
+        assert!(*before < 80);
+        // .. then increases when the network slows down.
+        let (_, after) = deltas.last().unwrap();
+        assert!(*after > 150);
+    }
+    let fixed = run(&Scenario::from_toml(CRASH_ONE_NODE).unwrap(), Some(3));
+    assert_eq!(fixed.learned_deltas, None);
+}
+
+#[test]
+fn test_leader_reputation() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    scenario.faults[0].end = None;
+    let report = run(&scenario, Some(3));
+    scenario.pacemaker.leader_reputation = Some(Default::default());
+    let reputation = run(&scenario, Some(3));
+    assert!(reputation.consistent);
+    // The crashed node is skipped after its first timeout, instead of stalling every round it
+    // is elected for.
+    assert!(reputation.commits[..3].iter().min() > report.commits[..3].iter().max());
+}
+
+#[test]
+fn test_fast_path() {
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\ncommit_rule = \"two_chain\"\n[fast_path]"
+    )
+    .is_err());
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let report = run(&scenario, Some(3));
+    assert_eq!(report.fast_path_commits, None);
+    scenario.fast_path = Some(Default::default());
+    let fast = run(&scenario, Some(3));
+    assert!(fast.consistent);
+    // Blocks are committed on the fast path while all the nodes are up.
+    assert!(fast
+        .fast_path_commits
+        .unwrap()
+        .iter()
+        .all(|commits| *commits > 0));
+    // Super-quorums of all the votes cannot form while a node is down for good..
+    scenario.faults[0].end = None;
+    let crashed = run(&scenario, Some(3));
+    assert!(crashed.consistent);
+    assert!(crashed.commits[..3].iter().all(|commits| *commits > 0));
+    let before_crash = crashed.fast_path_commits.unwrap();
+    // .. unless they may miss one vote.
+    scenario.fast_path.as_mut().unwrap().missing_votes = 1;
+    let tolerant = run(&scenario, Some(3));
+    assert!(tolerant.consistent);
+    assert!(tolerant.fast_path_commits.unwrap()[..3]
+        .iter()
+        .zip(&before_crash)
+        .all(|(tolerant, strict)| tolerant > strict));
+
+    let mut twins = Scenario::from_toml(include_str!("../../scenarios/twins.toml")).unwrap();
+    twins.fast_path = Some(Default::default());
+    assert!(analyze(&twins, 0..5).inconsistent_seeds.is_empty());
+}
+
+#[test]
+fn test_committee() {
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\n[committee]\nseats = 0").is_err());
+    let mut scenario = Scenario::from_toml(COMMITTEE).unwrap();
+    let report = run(&scenario, Some(3));
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+    // Each validator sits out the rounds whose committee it is not in.
+    let abstentions = report.committee_abstentions.unwrap();
+    assert!(abstentions.iter().all(|abstentions| *abstentions > 0));
+    // Votes are only sent by committee members, hence fewer votes than with flat voting.
+    let committee_votes = report.bandwidth.by_content["vote"];
+    scenario.committee = None;
+    let flat = run(&scenario, Some(3));
+    assert_eq!(flat.committee_abstentions, None);
+    assert!(committee_votes < flat.bandwidth.by_content["vote"]);
+}
+
+#[test]
+fn test_large_committee() {
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\n[network]\nquery_fanout = 0").is_err());
+    let mut scenario = Scenario::from_toml(COMMITTEE_LARGE).unwrap();
+    // Fewer validators than in the scenario, so that the unsampled run stays fast.
+    scenario.nodes = 60;
+    scenario.max_clock = 300;
+    let report = run(&scenario, Some(3));
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+    // Query-all actions reach a sample of the nodes, hence fewer requests.
+    scenario.network.query_fanout = None;
+    let unsampled = run(&scenario, Some(3));
+    assert!(unsampled.consistent);
+    assert!(report.bandwidth.by_message["request"] < unsampled.bandwidth.by_message["request"]);
+}
+
+#[test]
+fn test_inbound_queues() {
+    let mut scenario = Scenario::from_toml(
+        "nodes = 7\nmax_clock = 2000\n[cpu]\nsignature_verification = 2\nexecution = 1",
+    )
+    .unwrap();
+    let unbounded = run(&scenario, Some(3));
+    assert_eq!(unbounded.queues, None);
+    for policy in &[
+        QueuePolicy::Fifo,
+        QueuePolicy::Priority,
+        QueuePolicy::NewestRoundFirst,
+    ] {
+        scenario.inbound_queue = Some(scenario::InboundQueueParameters {
+            capacity: 4,
+            policy: *policy,
+        });
+        let report = run(&scenario, Some(3));
+        assert!(report.consistent);
+        assert!(report.commits.iter().all(|commits| *commits > 0));
+        // Messages pile up while nodes verify signatures, until some of them are dropped.
+        let queues = report.queues.unwrap();
+        assert_eq!(queues.len(), 7);
+        assert!(queues.iter().all(|stats| stats.max_length <= 4));
+        assert!(queues.iter().all(|stats| stats.dropped > 0));
+    }
+    // Large queues never overflow.
+    scenario.inbound_queue = Some(Default::default());
+    let report = run(&scenario, Some(3));
+    assert!(report
+        .queues
+        .unwrap()
+        .iter()
+        .all(|stats| stats.dropped == 0));
+}
+
+#[test]
+fn test_orphan_buffer_under_reordering() {
+    let mut scenario = Scenario::from_toml(REORDERING).unwrap();
+    let report = run(&scenario, Some(0));
+    assert!(report.consistent);
+    let orphans = report.orphans.unwrap();
+    assert!(orphans.buffered > 0);
+    assert!(orphans.adopted > 0);
+    assert!(orphans.adopted + orphans.evicted <= orphans.buffered);
+
+    scenario.orphan_buffer = None;
+    let report = run(&scenario, Some(0));
+    assert_eq!(report.orphans, None);
+    assert!(report.rejections["unknown_parent"] > 0);
+}
+
+#[test]
+fn test_send_dedup_saves_bandwidth() {
+    let mut scenario = Scenario::from_toml("nodes = 4\nmax_clock = 2000").unwrap();
+    scenario.orphan_buffer = Some(OrphanBufferParameters::default());
+    let report = run(&scenario, Some(0));
+    assert_eq!(report.bandwidth.suppressed_bytes, 0);
+    scenario.send_dedup = true;
+    let deduplicated = run(&scenario, Some(0));
+    assert!(deduplicated.consistent);
+    assert!(deduplicated.commits.iter().all(|commits| *commits > 0));
+    let bandwidth = &deduplicated.bandwidth;
+    assert!(bandwidth.suppressed_by_content["quorum_certificate"] > 0);
+    assert_eq!(
+        bandwidth.suppressed_bytes,
+        bandwidth.suppressed_by_content.values().sum::<usize>()
+    );
+    assert!(bandwidth.by_message["notify"] < report.bandwidth.by_message["notify"]);
+}
+
+#[test]
+fn test_payload_latencies() {
+    let run_with = |network: &str| {
+        let scenario = Scenario::from_toml(&format!(
+            "nodes = 4\nmax_clock = 3000\nseed = 2\n\
+             [workload.payload]\ndistribution = \"fixed\"\nbytes = 1000\n[network]\n{}",
+            network
+        ))
+        .unwrap();
+        run(&scenario, None)
+    };
+    let fast = run_with("");
+    let slow = run_with("throughput = 100.0");
+    let payload_bytes = fast.bandwidth.by_content["payload"];
+    assert!(payload_bytes > 0 && payload_bytes % 1000 == 0);
+    // Blocks of the same size take longer to be committed on slower links.
+    let latency = |report: &RunReport| {
+        let latencies = report.payload_latencies.as_ref().unwrap();
+        let bucket = latencies
+            .iter()
+            .find(|latency| latency.min_bytes == 512)
+            .unwrap();
+        assert!(bucket.commits > 0);
+        bucket.mean_latency
+    };
+    assert!(latency(&slow) > latency(&fast));
+    assert!(slow.commits[0] < fast.commits[0]);
+
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1000\nseed = 2").unwrap();
+    let report = run(&scenario, None);
+    assert_eq!(report.payload_latencies, None);
+    assert!(!report.bandwidth.by_content.contains_key("payload"));
+}
+
+#[test]
+fn test_qc_withholding() {
+    let mut scenario =
+        Scenario::from_toml(include_str!("../../scenarios/qc_withholding.toml")).unwrap();
+    let attacked = run(&scenario, None);
+    assert!(attacked.consistent);
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(
+        sim.simulated_node(Author(0))
+            .node()
+            .node_state()
+            .withheld_quorum_certificates()
+            > 0
+    );
+    // Back-pressure does not prevent commits under attack.
+    scenario.workload.max_uncommitted_depth = Some(1);
+    let report = run(&scenario, None);
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+
+    scenario.workload.max_uncommitted_depth = None;
+    scenario.qc_withholders.clear();
+    let honest = run(&scenario, None);
+    assert!(attacked.commits[1] < honest.commits[1]);
+    assert!(attacked.deepest_uncommitted_chain >= honest.deepest_uncommitted_chain);
+}
+
+#[test]
+fn test_double_spending() {
+    let scenario =
+        Scenario::from_toml(include_str!("../../scenarios/double_spending.toml")).unwrap();
+    let report = run(&scenario, None);
+    assert!(report.consistent);
+    assert!(report.commits.iter().all(|commits| *commits > 0));
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let node = |index| sim.simulated_node(Author(index)).node().node_state();
+    assert!(node(0).double_spends() > 0);
+    assert_eq!(node(0).rejected_payloads(), 0);
+    assert!((1..4).all(|index| node(index).rejected_payloads() > 0));
+    // Replayed commands never gather a quorum, hence are never committed twice.
+    for replica in sim.replicas() {
+        let context = replica.context();
+        let commands: std::collections::BTreeSet<_> = context
+            .committed_history()
+            .iter()
+            .map(|(command, _)| command)
+            .collect();
+        assert_eq!(commands.len(), context.committed_history().len());
+    }
+}
+
+#[test]
+fn test_censorship() {
+    let mut scenario = Scenario::from_toml(include_str!("../../scenarios/censoring.toml")).unwrap();
+    let report = run(&scenario, None);
+    assert!(report.consistent);
+    let latencies = report.client_latencies.unwrap();
+    assert_eq!(latencies.len(), 2);
+    assert_eq!(latencies[0].pending, 0);
+    assert!(latencies[1].pending > 0);
+    assert!(latencies[1].commits < latencies[0].commits);
+
+    scenario.censors.clear();
+    let latencies = run(&scenario, None).client_latencies.unwrap();
+    assert_eq!(latencies[1].commits, latencies[0].commits);
+    assert!(latencies.iter().all(|latency| latency.mean_latency > 0));
+}
+
+#[test]
+fn test_hardware_profiles() {
+    let mut scenario =
+        Scenario::from_toml(include_str!("../../scenarios/mixed_fleet.toml")).unwrap();
+    let mixed = run(&scenario, None);
+    assert!(mixed.consistent);
+    let latency = |report: &RunReport, node: usize| report.qc_latencies[node].unwrap();
+    assert!((0..3).all(|node| latency(&mixed, node) < latency(&mixed, 3)));
+
+    scenario.hardware.clear();
+    let uniform = run(&scenario, None);
+    assert!(latency(&uniform, 3) < latency(&mixed, 3));
+    assert!(mixed.commits[0] < uniform.commits[0]);
+}
+
+#[test]
+fn test_region_latencies() {
+    let mut scenario =
+        Scenario::from_toml(include_str!("../../scenarios/global_regions.toml")).unwrap();
+    let report = run(&scenario, None);
+    assert!(report.consistent);
+    let latencies = report.region_latencies.unwrap();
+    assert_eq!(latencies.len(), 3);
+    // Asia observes commits last, being far from the quorums formed by the US and Europe.
+    assert!(latencies["asia"] > latencies["us"]);
+    assert!(latencies["asia"] > latencies["eu"]);
+
+    scenario.topology.rtt.clear();
+    scenario.topology.intra_region_rtt = None;
+    let latencies = run(&scenario, None).region_latencies.unwrap();
+    assert!(latencies["asia"] < 100);
+    assert_eq!(
+        run(&Scenario::from_toml(CRASH_ONE_NODE).unwrap(), None).region_latencies,
+        None
+    );
+}
+
+#[test]
+fn test_min_proposal_interval() {
+    let mut scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 2000\nseed = 1\n[pacemaker]\nmin_proposal_interval = 200",
+    )
+    .unwrap();
+    let limited = run(&scenario, None);
+    assert!(limited.consistent);
+    assert!(limited.commits[0] > 0);
+    let deferred = limited.deferred_proposals.unwrap();
+    assert_eq!(deferred.len(), 4);
+    assert!(deferred.iter().sum::<usize>() > 0);
+
+    scenario.pacemaker.min_proposal_interval = None;
+    let unlimited = run(&scenario, None);
+    assert_eq!(unlimited.deferred_proposals, None);
+    assert!(limited.commits[0] < unlimited.commits[0]);
+}
+
+#[test]
+fn test_qc_cache() {
+    let mut scenario = Scenario::from_toml(
+        "nodes = 7\nmax_clock = 2000\nseed = 1\nqc_cache = 100\n\
+         [cpu]\nsignature_verification = 1\n[network]\nmean = 10.0\nvariance = 100.0",
+    )
+    .unwrap();
+    let cached = run(&scenario, None);
+    assert!(cached.consistent);
+    let stats = cached.qc_cache.clone().unwrap();
+    // With reordering, QCs arrive before their block, from every peer.
+    assert!(stats.hits > 0);
+    assert!(stats.signatures_saved > stats.hits);
+
+    scenario.qc_cache = None;
+    let uncached = run(&scenario, None);
+    assert_eq!(uncached.qc_cache, None);
+    assert!(cached.commits[0] >= uncached.commits[0]);
+}
+
+#[test]
+fn test_parallel_verification() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    scenario.cpu.signature_verification = 2;
+    scenario.cpu.verification_workers = Some(4);
+    let report = run(&scenario, Some(5));
+    assert!(report.consistent);
+    // Records are still inserted in order, so runs can be replayed.
+    assert_eq!(run(&scenario, Some(5)), report);
+}
+
+#[test]
+fn test_stop_conditions() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let full = run(&scenario, Some(1));
+    assert_eq!(full.outcome.reason, StopReason::SimulatedTime);
+    assert!(full.outcome.clock.0 <= scenario.max_clock);
+
+    scenario.stop = Some(StopParameters {
+        commits: Some(3),
+        ..StopParameters::default()
+    });
+    let short = run(&scenario, Some(1));
+    assert_eq!(short.outcome.reason, StopReason::Commits);
+    assert!(short.outcome.clock < full.outcome.clock);
+    assert!(short.outcome.events < full.outcome.events);
+    assert!(short.consistent);
+    assert_eq!(run(&scenario, Some(1)), short);
+
+    scenario.stop = Some(StopParameters {
+        wall_clock: Some(0),
+        ..StopParameters::default()
+    });
+    let outcome = run(&scenario, Some(1)).outcome;
+    assert_eq!(outcome.reason, StopReason::WallClock);
+    assert_eq!(outcome.events, 0);
+}
+
+#[test]
+fn test_stop_on_violation() {
+    // Same as `test_lock_violations_beyond_resilience`, which panics without the stop condition.
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 2000\ntwins = [0, 1]\nlock_violators = [0, 1]\nseed = 0\n\
+         [stop]\non_violation = true\n\
+         [[partitions]]\ngroups = [[0, 1, 2], [4, 5, 3]]\nstart = 0\nend = 1000",
+    )
+    .unwrap();
+    let report = run(&scenario, None);
+    match report.outcome.reason {
+        StopReason::Violation(violation) => assert!(violation.starts_with("Execution diverged")),
+        reason => panic!("Unexpected outcome {:?}", reason),
+    }
+    assert!(report.outcome.clock.0 < scenario.max_clock);
+}
+
+#[test]
+fn test_quorum_delay_scheduler() {
+    let mut scenario =
+        Scenario::from_toml(include_str!("../../scenarios/quorum_delay.toml")).unwrap();
+    let adversarial = run(&scenario, None);
+    assert!(adversarial.consistent);
+    // QCs still form once the delayed votes arrive.
+    assert!(adversarial.commits[0] > 0);
+    assert!(adversarial.delayed_messages.unwrap() > 0);
+
+    scenario.scheduler = None;
+    let benign = run(&scenario, None);
+    assert_eq!(benign.delayed_messages, None);
+    assert!(adversarial.commits[0] < benign.commits[0]);
+}
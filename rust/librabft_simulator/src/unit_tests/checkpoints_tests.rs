@@ -0,0 +1,94 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, StopConditions};
+use campaign::run;
+use scenario::Scenario;
+
+fn add_commits(log: &mut CheckpointLog, rounds: &[usize], states: &[u64]) {
+    for (round, state) in rounds.iter().zip(states) {
+        log.add_commit(
+            EpochId(0),
+            Round(*round),
+            &State(*state),
+            PublicKey::initial(Author(1)),
+            ChainId(0),
+            HashFunction::Fnv1a,
+        );
+    }
+}
+
+#[test]
+fn test_checkpoint_log() {
+    let mut log = CheckpointLog::new(10);
+    add_commits(&mut log, &[1, 2, 3, 11, 12, 25, 31], &[1, 2, 3, 4, 5, 6, 7]);
+    let rounds: Vec<_> = log.checkpoints().iter().map(|c| c.round.0).collect();
+    assert_eq!(rounds, vec![1, 11, 25, 31]);
+    let heights: Vec<_> = log.checkpoints().iter().map(|c| c.height).collect();
+    assert_eq!(heights, vec![1, 4, 6, 7]);
+    assert_eq!(log.checkpoints().last().unwrap().digest, log.digest());
+
+    let checkpoint = log.checkpoints()[1].clone();
+    let key = PublicKey::initial(Author(1));
+    assert!(checkpoint
+        .verify(key, ChainId(0), HashFunction::Fnv1a)
+        .is_ok());
+    assert!(checkpoint
+        .verify(key, ChainId(1), HashFunction::Fnv1a)
+        .is_err());
+    assert!(checkpoint
+        .verify(
+            PublicKey::initial(Author(2)),
+            ChainId(0),
+            HashFunction::Fnv1a
+        )
+        .is_err());
+    let mut forged = checkpoint.clone();
+    forged.digest += 1;
+    assert!(forged.verify(key, ChainId(0), HashFunction::Fnv1a).is_err());
+
+    // The digest covers the whole history, not only the latest states.
+    let mut other = CheckpointLog::new(10);
+    add_commits(&mut other, &[1, 2, 3, 11], &[2, 1, 3, 4]);
+    assert_eq!(other.checkpoints()[1].height, checkpoint.height);
+    assert_ne!(other.checkpoints()[1].digest, checkpoint.digest);
+}
+
+#[test]
+fn test_cross_check() {
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 2000\nseed = 1\ncheckpoint_interval = 10\n\
+         [[faults]]\nnode = 3\nstart = 300\nend = 900",
+    )
+    .unwrap();
+    let report = run(&scenario, None).checkpoints.unwrap();
+    assert!(report.checkpoints > 4);
+    assert!(report.cross_checked > 0);
+    assert_eq!(report.invalid_signatures, 0);
+    assert_eq!(report.unverified, 0);
+    assert!(report.mismatches.is_empty());
+    assert_eq!(report.skipped_replicas, 0);
+
+    // Replicas of runs with different network delays commit different blocks at the same
+    // rounds.
+    let mut sims: Vec<_> = [1, 2]
+        .iter()
+        .map(|seed| {
+            let mut scenario = scenario.clone();
+            scenario.seed = Some(*seed);
+            scenario.build_simulator()
+        })
+        .collect();
+    for sim in &mut sims {
+        sim.run_until(GlobalTime(2000), &StopConditions::default());
+    }
+    let nodes: Vec<_> = sims
+        .iter()
+        .map(|sim| sim.replicas()[0].node().node_state())
+        .collect();
+    let report = cross_check(&nodes);
+    assert!(report.cross_checked > 0);
+    assert!(!report.mismatches.is_empty());
+    assert_eq!(report.invalid_signatures, 0);
+}
@@ -0,0 +1,81 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use campaign::run;
+use introspection::LeaderHint;
+use scenario::{CrashFault, Scenario};
+
+fn client_scenario() -> Scenario {
+    Scenario::from_toml(
+        "nodes = 4\nmax_clock = 3000\nseed = 1\n\
+         [client_requests]\ncount = 3\nrequests = 5",
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_client_requests() {
+    let scenario = client_scenario();
+    let report = run(&scenario, None);
+    assert!(report.consistent);
+    let stats = report.client_requests.clone().unwrap();
+    assert!(stats.completed > 0);
+    assert_eq!(stats.failed, 0);
+    // Most validators are not the leader when a request reaches them.
+    assert!(stats.redirects > 0);
+    assert!(stats.attempts >= stats.requests);
+    let mean_latency = stats.mean_latency.unwrap();
+    assert!(mean_latency >= 2 * scenario.client_requests.unwrap().link_delay);
+    assert!(stats.max_latency.unwrap() >= mean_latency);
+    assert_eq!(run(&client_scenario(), None), report);
+}
+
+#[test]
+fn test_client_retries_after_crash() {
+    let healthy = run(&client_scenario(), None).client_requests.unwrap();
+    let mut scenario = client_scenario();
+    scenario.faults.push(CrashFault {
+        node: 0,
+        start: 0,
+        end: None,
+    });
+    for discovery in &[LeaderDiscovery::Sticky, LeaderDiscovery::Random] {
+        scenario.client_requests.as_mut().unwrap().discovery = *discovery;
+        let stats = run(&scenario, None).client_requests.unwrap();
+        // Requests sent or redirected to the crashed validator are retried elsewhere.
+        assert!(stats.timeouts > 0);
+        assert!(stats.attempts > stats.requests);
+        assert!(stats.completed > 0);
+        assert!(stats.mean_latency.unwrap() > healthy.mean_latency.unwrap());
+    }
+}
+
+#[test]
+fn test_leader_hints() {
+    let scenario = client_scenario();
+    let mut sim = scenario.build_simulator();
+    sim.run_until(GlobalTime(500), &StopConditions::default());
+    let node = sim.replicas()[1].node().node_state();
+    let hint = node.leader_hint();
+    assert_eq!(hint.round, node.pacemaker().active_round());
+    assert_eq!(hint.leader, node.pacemaker().active_leader());
+    let earlier = LeaderHint {
+        round: Round(hint.round.0 - 1),
+        ..hint
+    };
+    assert!(hint.is_newer_than(&earlier));
+    assert!(!earlier.is_newer_than(&hint));
+
+    // Leaders change at every round, yet clients following the hints of the validators are
+    // misdirected less often than clients picking validators at random.
+    let sticky = run(&scenario, None).client_requests.unwrap();
+    let mut scenario = client_scenario();
+    scenario.client_requests.as_mut().unwrap().discovery = LeaderDiscovery::Random;
+    let random = run(&scenario, None).client_requests.unwrap();
+    for stats in &[&sticky, &random] {
+        assert!(stats.misdirected >= stats.redirects);
+        assert!(stats.arrivals > stats.misdirected);
+    }
+    assert!(sticky.misdirection_rate().unwrap() < random.misdirection_rate().unwrap());
+}
@@ -0,0 +1,219 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use scenario::{NodeSimulator, Scenario};
+
+fn run() -> NodeSimulator {
+    let scenario =
+        Scenario::from_toml("nodes = 4\nmax_clock = 3000\nseed = 1\ncommands_per_epoch = 10")
+            .unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    sim
+}
+
+fn genesis() -> EpochConfiguration {
+    EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect())
+}
+
+#[test]
+fn test_export_and_verify() {
+    let sim = run();
+    for replica in sim.replicas() {
+        let node = replica.node().node_state();
+        assert!(node.epoch_id() > EpochId(1));
+        let commit_chain = export_commit_chain(node);
+        assert_eq!(commit_chain.epochs.len(), node.epoch_id().0 + 1);
+        // Bundles can be exported as JSON.
+        let json = serde_json::to_string(&commit_chain).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CommitChain>(&json).unwrap(),
+            commit_chain
+        );
+        assert_eq!(
+            verify_commit_chain(&commit_chain, &genesis()),
+            Ok(node.commits().to_vec())
+        );
+    }
+}
+
+#[test]
+fn test_reject_tampered_chains() {
+    let sim = run();
+    let replica = &sim.replicas()[0];
+    let node = replica.node().node_state();
+    let genesis = genesis();
+    let commit_chain = export_commit_chain(node);
+    let verify =
+        |commit_chain: &CommitChain| verify_commit_chain(commit_chain, &genesis).map(|_| ());
+
+    let mut skipped = commit_chain.clone();
+    skipped.epochs.remove(0);
+    assert_eq!(
+        verify(&skipped),
+        Err(ChainError::UnexpectedEpoch {
+            previous: None,
+            found: EpochId(1)
+        })
+    );
+
+    let mut broken = commit_chain.clone();
+    let round = broken.epochs[1].chain.remove(2).block.round;
+    let round_after = broken.epochs[1].chain[2].block.round;
+    assert!(round < round_after);
+    assert_eq!(
+        verify(&broken),
+        Err(ChainError::BrokenLink {
+            epoch_id: EpochId(1),
+            round: round_after
+        })
+    );
+
+    let mut forged = commit_chain.clone();
+    let chain = &mut forged.epochs[0].chain;
+    let (last, round) = (chain.len() - 1, chain[chain.len() - 1].block.round);
+    chain[last].certificate.votes.truncate(2);
+    assert_eq!(
+        verify(&forged),
+        Err(ChainError::InvalidCertificate {
+            epoch_id: EpochId(0),
+            round,
+            error: VerifyError::InsufficientQuorum {
+                weight: 2,
+                threshold: 3
+            }
+        })
+    );
+
+    // Stop the first epoch at its first commit certificate, before the epoch change.
+    let mut reordered = commit_chain.clone();
+    reordered.epochs.swap(1, 2);
+    assert_eq!(
+        verify(&reordered),
+        Err(ChainError::UnexpectedEpoch {
+            previous: Some(EpochId(2)),
+            found: EpochId(1)
+        })
+    );
+
+    let mut other_genesis = commit_chain.clone();
+    other_genesis.epochs[0].configuration =
+        EpochConfiguration::new((0..3).map(|index| (Author(index), 1)).collect());
+    assert_eq!(verify(&other_genesis), Err(ChainError::UnexpectedGenesis));
+
+    // Voting rights of later epochs must match the digest announced by the previous epoch.
+    let mut other_voters = commit_chain.clone();
+    other_voters.epochs[1].configuration =
+        EpochConfiguration::new((0..4).map(|index| (Author(index), 2)).collect());
+    assert_eq!(
+        verify(&other_voters),
+        Err(ChainError::UncertifiedConfiguration {
+            epoch_id: EpochId(1)
+        })
+    );
+
+    let mut unended = commit_chain.clone();
+    unended.epochs[0].end_round = None;
+    assert_eq!(
+        verify(&unended),
+        Err(ChainError::MissingEpochChange {
+            epoch_id: EpochId(0)
+        })
+    );
+
+    let mut truncated = commit_chain.clone();
+    let chain = &mut truncated.epochs[0].chain;
+    let first_commit = chain
+        .iter()
+        .position(|link| link.certificate.committed_state.is_some())
+        .unwrap();
+    chain.truncate(first_commit + 1);
+    let round = truncated.epochs[0].end_round.unwrap();
+    assert_eq!(
+        verify(&truncated),
+        Err(ChainError::UncommittedEpochChange {
+            epoch_id: EpochId(0),
+            round
+        })
+    );
+
+    // The last epoch may have just started, without any commit yet.
+    let mut uncommitted = commit_chain;
+    let epoch = uncommitted
+        .epochs
+        .iter_mut()
+        .rev()
+        .find(|epoch| !epoch.chain.is_empty())
+        .unwrap();
+    let epoch_id = epoch.epoch_id;
+    let chain = &mut epoch.chain;
+    let no_commit = chain
+        .iter()
+        .position(|link| link.certificate.committed_state.is_none())
+        .unwrap();
+    chain.truncate(no_commit + 1);
+    assert_eq!(
+        verify(&uncommitted),
+        Err(ChainError::UncommittedChain { epoch_id })
+    );
+}
+
+#[test]
+fn test_hash_function_of_chains() {
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 1500\nseed = 1\ncommands_per_epoch = 10\nhash_function = \"sha3_256\"",
+    )
+    .unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let node = sim.replicas()[0].node().node_state();
+    let mut commit_chain = export_commit_chain(node);
+    assert_eq!(commit_chain.hash_function, HashFunction::Sha3_256);
+    let commits = verify_commit_chain(&commit_chain, &genesis()).unwrap();
+    assert!(!commits.is_empty());
+    assert_eq!(commits, node.commits().to_vec());
+    // Links and signatures only hold with the hash function of the run.
+    commit_chain.hash_function = HashFunction::Fnv1a;
+    assert!(verify_commit_chain(&commit_chain, &genesis()).is_err());
+}
+
+#[test]
+fn test_chain_id_of_chains() {
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 1500\nseed = 1\ncommands_per_epoch = 10\nchain_id = 7",
+    )
+    .unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let node = sim.replicas()[0].node().node_state();
+    let mut commit_chain = export_commit_chain(node);
+    assert_eq!(commit_chain.chain_id, ChainId(7));
+    assert!(!verify_commit_chain(&commit_chain, &genesis())
+        .unwrap()
+        .is_empty());
+    // Certificates of another network do not verify, even with the same epochs and authors.
+    commit_chain.chain_id = ChainId(0);
+    assert!(verify_commit_chain(&commit_chain, &genesis()).is_err());
+}
+
+#[test]
+fn test_key_rotations_in_chains() {
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 3000\nseed = 1\ncommands_per_epoch = 10\n[[key_rotations]]\nnode = 2\nepoch = 1",
+    )
+    .unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let node = sim.replicas()[0].node().node_state();
+    let mut commit_chain = export_commit_chain(node);
+    // Certificates of each epoch are checked against the keys announced for that epoch.
+    assert_eq!(commit_chain.epochs[1].configuration.key_versions().len(), 1);
+    assert_eq!(
+        verify_commit_chain(&commit_chain, &genesis()).unwrap(),
+        node.commits().to_vec()
+    );
+    commit_chain.epochs[1].configuration = genesis();
+    assert!(verify_commit_chain(&commit_chain, &genesis()).is_err());
+}
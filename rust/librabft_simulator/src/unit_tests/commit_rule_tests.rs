@@ -0,0 +1,86 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use follower::Participant;
+use scenario::Scenario;
+
+const CRASH_ONE_NODE: &str = include_str!("../../scenarios/crash_one_node.toml");
+const FOLLOWERS: &str = include_str!("../../scenarios/followers.toml");
+
+fn rounds(values: &[usize]) -> Vec<Round> {
+    values.iter().cloned().map(Round).collect()
+}
+
+#[test]
+fn test_three_chains() {
+    for rule in &[CommitRuleKind::LibraBft, CommitRuleKind::ChainedHotStuff] {
+        let rule = rule.rule();
+        assert_eq!(rule.chain_length(), 3);
+        assert!(rule.is_commit_chain(&rounds(&[4, 5, 6])));
+        assert!(!rule.is_commit_chain(&rounds(&[4, 5, 7])));
+        assert!(!rule.is_commit_chain(&rounds(&[3, 5, 6])));
+        assert!(!rule.is_commit_chain(&rounds(&[5, 6])));
+    }
+    assert!(CommitRuleKind::LibraBft.rule().certifies_commits());
+    assert!(!CommitRuleKind::ChainedHotStuff.rule().certifies_commits());
+}
+
+#[test]
+fn test_chained_hotstuff_simulation() {
+    let scenario = Scenario::from_toml(&format!(
+        "{}\ncommit_rule = \"chained_hotstuff\"",
+        FOLLOWERS
+    ))
+    .unwrap();
+    assert_eq!(scenario.commit_rule, CommitRuleKind::ChainedHotStuff);
+    let mut sim = scenario.build_simulator();
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(campaign::consistent_histories(&contexts));
+    for context in contexts {
+        assert!(!context.committed_history().is_empty());
+    }
+    for replica in sim.replicas() {
+        let record_store = replica.node().node_state().record_store();
+        assert!(!record_store.commit_rule().certifies_commits());
+        // Commits are not certified by QCs.
+        let certificate = record_store.highest_commit_certificate().unwrap();
+        assert_eq!(certificate.committed_state, None);
+        if let Participant::Follower(follower) = replica.node() {
+            assert!(follower.verified_commit().is_none());
+        }
+    }
+}
+
+#[test]
+fn test_two_chains() {
+    let rule = CommitRuleKind::TwoChain.rule();
+    assert_eq!(rule.chain_length(), 2);
+    assert!(rule.is_commit_chain(&rounds(&[5, 6])));
+    assert!(!rule.is_commit_chain(&rounds(&[4, 6])));
+    assert!(!rule.is_commit_chain(&rounds(&[4, 5, 6])));
+    assert!(rule.certifies_commits());
+    // Nodes lock on their highest QC.
+    assert_eq!(rule.locked_round(Round(5), Round(4)), Round(5));
+    let no_tc = (Round(0), Round(0));
+    assert!(rule.is_safe_to_vote(Round(6), Round(5), Round(5), no_tc));
+    assert!(!rule.is_safe_to_vote(Round(7), Round(5), Round(5), no_tc));
+    // After a TC of round 6 reporting a QC at round 5, extending round 5 is safe, but not
+    // extending a lower round.
+    let tc = (Round(6), Round(5));
+    assert!(rule.is_safe_to_vote(Round(7), Round(5), Round(5), tc));
+    assert!(!rule.is_safe_to_vote(Round(7), Round(4), Round(5), tc));
+    assert!(!rule.is_safe_to_vote(Round(8), Round(5), Round(5), tc));
+}
+
+#[test]
+fn test_rules_under_the_same_faults() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    for kind in &[CommitRuleKind::LibraBft, CommitRuleKind::TwoChain] {
+        scenario.commit_rule = *kind;
+        let report = campaign::run(&scenario, Some(7));
+        assert!(report.consistent);
+        assert!(report.commits.iter().all(|commits| *commits > 0));
+    }
+}
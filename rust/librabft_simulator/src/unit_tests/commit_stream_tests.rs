@@ -0,0 +1,72 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use scenario::Scenario;
+
+fn notification(round: usize) -> CommitNotification {
+    CommitNotification {
+        epoch_id: EpochId(0),
+        round: Round(round),
+        block_hash: BlockHash(round as u64),
+        state: State(round as u64),
+        time: NodeTime(round as i64),
+        certificate: None,
+    }
+}
+
+#[test]
+fn test_subscribers() {
+    let mut subscribers = CommitSubscribers::default();
+    let first = subscribers.subscribe();
+    subscribers.notify(&notification(1));
+    let second = subscribers.subscribe();
+    subscribers.notify(&notification(2));
+    assert_eq!(first.drain(), vec![notification(1), notification(2)]);
+    assert_eq!(second.try_next(), Some(notification(2)));
+    assert_eq!(second.try_next(), None);
+
+    // Dropped streams are forgotten at the next commit.
+    drop(first);
+    assert_eq!(subscribers.len(), 2);
+    subscribers.notify(&notification(3));
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(second.into_receiver().recv(), Ok(notification(3)));
+}
+
+#[test]
+fn test_simulated_commits() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1000\nseed = 3").unwrap();
+    let mut sim = scenario.build_simulator();
+    let stream = sim
+        .simulated_node_mut(Author(0))
+        .node_mut()
+        .node_state_mut()
+        .subscribe_commits();
+    sim.loop_until(GlobalTime(500), None);
+    let mut notifications = stream.drain();
+    sim.loop_until(GlobalTime(1000), None);
+    notifications.extend(stream.drain());
+
+    let node = sim.simulated_node(Author(0)).node().node_state();
+    let commits = node.commits();
+    assert!(!commits.is_empty());
+    assert_eq!(notifications.len(), commits.len());
+    for (notification, commit) in notifications.iter().zip(commits) {
+        assert_eq!(notification.epoch_id.0, commit.epoch);
+        assert_eq!(notification.round, commit.round);
+        assert_eq!(notification.block_hash, commit.block);
+        assert_eq!(notification.state, commit.state);
+    }
+    // The last commit comes with the certificate that completes the commit rule.
+    let last = notifications.last().unwrap();
+    let certificate = last.certificate.as_ref().unwrap();
+    assert_eq!(certificate.committed_state.as_ref(), Some(&last.state));
+    assert_eq!(
+        Some(last.time),
+        sim.simulated_node(Author(0))
+            .context()
+            .last_committed_time()
+    );
+}
@@ -0,0 +1,53 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+fn configuration(voting_rights: &[usize]) -> EpochConfiguration {
+    EpochConfiguration::new(
+        voting_rights
+            .iter()
+            .enumerate()
+            .map(|(index, votes)| (Author(index), *votes))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_committee_selection() {
+    let configuration = configuration(&[1; 100]);
+    let committee = VotingCommittee::new(10, CommitteeRotation::PerRound);
+    let selected = committee.select(&configuration, EpochId(0), Round(1));
+    assert_eq!(selected.total_votes(), 10);
+    assert!(selected.voting_rights().len() <= 10);
+    assert_eq!(selected.resilience(), configuration.resilience());
+    // Every replica draws the same committee, and a new one at each round.
+    assert_eq!(
+        committee.select(&configuration, EpochId(0), Round(1)),
+        selected
+    );
+    assert!(
+        (2..10).any(|round| committee.select(&configuration, EpochId(0), Round(round)) != selected)
+    );
+
+    let per_epoch = VotingCommittee::new(10, CommitteeRotation::PerEpoch);
+    let selected = per_epoch.select(&configuration, EpochId(0), Round(1));
+    assert!(
+        (2..10).all(|round| per_epoch.select(&configuration, EpochId(0), Round(round)) == selected)
+    );
+    assert!(
+        (1..10).any(|epoch| per_epoch.select(&configuration, EpochId(epoch), Round(1)) != selected)
+    );
+}
+
+#[test]
+fn test_committee_is_stake_weighted() {
+    // Validators without voting rights are never drawn, and node 0 holds most of the stake.
+    let configuration = configuration(&[90, 0, 5, 5]);
+    let committee = VotingCommittee::new(50, CommitteeRotation::PerRound);
+    let selected = committee.select(&configuration, EpochId(0), Round(1));
+    assert_eq!(selected.weight(&Author(1)), 0);
+    assert!(selected.weight(&Author(0)) > 25);
+    // Node 0 alone gathers a quorum of the committee.
+    assert!(selected.weight(&Author(0)) >= selected.quorum_threshold());
+}
@@ -0,0 +1,64 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_report_format() {
+    assert_eq!(ReportFormat::parse("csv").unwrap(), ReportFormat::Csv);
+    assert_eq!(
+        ReportFormat::parse("markdown").unwrap(),
+        ReportFormat::Markdown
+    );
+    assert!(ReportFormat::parse("html").is_err());
+}
+
+#[test]
+fn test_variants() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 500").unwrap();
+    let scenarios = vec![("base".to_string(), base.clone())];
+    let variants = variants(&scenarios, &[]).unwrap();
+    assert_eq!(variants, scenarios);
+    let sweeps = vec![Sweep::parse("pacemaker.delta=20,40").unwrap()];
+    let variants = comparison::variants(&scenarios, &sweeps).unwrap();
+    let names: Vec<_> = variants.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["base pacemaker.delta=20", "base pacemaker.delta=40"]
+    );
+    assert_eq!(variants[1].1.pacemaker.delta, 40);
+}
+
+#[test]
+fn test_compare() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 1000").unwrap();
+    let mut non_responsive = base.clone();
+    non_responsive.pacemaker.optimistic_responsiveness = false;
+    let variants = vec![
+        ("responsive".to_string(), base),
+        ("non_responsive".to_string(), non_responsive),
+    ];
+    let report = compare(&variants, &[1, 2]).unwrap();
+    assert_eq!(report.variants.len(), 2);
+    for variant in &report.variants {
+        assert_eq!(variant.runs, 2);
+        assert!(variant.inconsistent_seeds.is_empty());
+        assert_eq!(variant.throughput.as_ref().unwrap().samples, 2);
+        assert!(variant.bytes_per_commit.as_ref().unwrap().mean > 0.0);
+    }
+    // Waiting for `delta` before proposing slows down commits.
+    let latency = |index: usize| report.variants[index].commit_latency.as_ref().unwrap().p50;
+    assert!(latency(0) < latency(1));
+
+    let markdown = report.to_markdown();
+    let lines: Vec<_> = markdown.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("| variant | runs | unsafe runs | latency p50 |"));
+    assert!(lines[3].starts_with("| non_responsive | 2 | 0 | "));
+    let csv = report.to_csv().unwrap();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.starts_with("variant,runs,unsafe runs,"));
+    let json: serde_json::Value =
+        serde_json::from_str(&report.render(ReportFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["variants"][0]["name"], "responsive");
+}
@@ -0,0 +1,45 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+const CRASH_ONE_NODE: &str = include_str!("../../scenarios/crash_one_node.toml");
+
+#[test]
+fn test_record_steps() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    scenario.seed = Some(3);
+    let mut sim = scenario.build_simulator();
+    let steps = record_steps(&mut sim, GlobalTime(scenario.max_clock));
+    assert!(steps.len() > 1);
+    for (previous, step) in steps.iter().zip(steps.iter().skip(1)) {
+        assert_ne!(previous.nodes, step.nodes);
+        assert!(previous.clock <= step.clock);
+        for (node1, node2) in previous.nodes.iter().zip(step.nodes.iter()) {
+            if node1.epoch == node2.epoch {
+                assert!(node1.locked_round <= node2.locked_round);
+                assert!(node1.latest_voted_round <= node2.latest_voted_round);
+                assert!(node1.committed_round <= node2.committed_round);
+            }
+        }
+    }
+    assert!(steps
+        .last()
+        .unwrap()
+        .nodes
+        .iter()
+        .all(|node| node.committed_round > Round(0)));
+}
+
+#[test]
+fn test_export_itf() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let trace = export_itf(&scenario, 3);
+    assert_eq!(trace["#meta"]["format"], "ITF");
+    assert_eq!(trace["vars"].as_array().unwrap().len(), 7);
+    let states = trace["states"].as_array().unwrap();
+    assert_eq!(states[0]["#meta"]["index"], 0);
+    assert_eq!(states[0]["locked_round"]["#map"][3], json!([3, 0]));
+    assert_eq!(states[0]["qc_rounds"]["#map"][0][1], json!({ "#set": [] }));
+    assert_eq!(trace, export_itf(&scenario, 3));
+}
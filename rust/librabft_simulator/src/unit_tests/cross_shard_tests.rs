@@ -0,0 +1,61 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use shards::run_shards;
+
+const CROSS_SHARD: &str = include_str!("../../scenarios/cross_shard.toml");
+
+#[test]
+fn test_operation_commands() {
+    for transaction in 0..4 {
+        for phase in &[
+            TransactionPhase::Prepare,
+            TransactionPhase::Commit,
+            TransactionPhase::Abort,
+        ] {
+            let operation = CrossShardOperation {
+                transaction,
+                phase: *phase,
+            };
+            assert_eq!(
+                CrossShardOperation::of(&operation.command(Author(1))),
+                Some(operation)
+            );
+        }
+    }
+    let command = Command {
+        proposer: Author(1),
+        index: 3,
+    };
+    assert_eq!(CrossShardOperation::of(&command), None);
+}
+
+#[test]
+fn test_cross_shard_parameters() {
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\n[[shards]]\nmembers = [0, 1, 2, 3]\n[cross_shard]"
+    )
+    .is_err());
+    let scenario = Scenario::from_toml(CROSS_SHARD).unwrap();
+    let mut toml = CROSS_SHARD.replace("keys = 16", "keys = 0");
+    assert!(Scenario::from_toml(&toml).is_err());
+    toml = CROSS_SHARD.replace("timeout = 400", "timeout = 0");
+    assert!(Scenario::from_toml(&toml).is_err());
+    assert_eq!(scenario.cross_shard.unwrap().transactions, 25);
+}
+
+#[test]
+fn test_cross_shard_transactions() {
+    let scenario = Scenario::from_toml(CROSS_SHARD).unwrap();
+    let report = run_shards(&scenario, None);
+    let stats = report.cross_shard.unwrap();
+    assert_eq!(stats.transactions, 25);
+    assert_eq!(stats.committed + stats.aborted, stats.transactions);
+    assert!(stats.committed > 0);
+    // The crashes of shard 1 make some transactions time out, but never break atomicity.
+    assert!(stats.timeouts > 0);
+    assert_eq!(stats.atomicity_violations, 0);
+    assert!(stats.mean_latency.unwrap() >= stats.mean_prepare_latency.unwrap());
+    assert!(report.shards.iter().all(|shard| shard.consistent));
+}
@@ -0,0 +1,23 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_render() {
+    let rows = vec![NodeRow {
+        author: Author(1),
+        epoch_id: EpochId(0),
+        active_round: Round(7),
+        locked_round: Round(5),
+        highest_committed_round: Round(4),
+        queue_depth: 3,
+    }];
+    let frame = Dashboard::render(GlobalTime(120), &rows);
+    let lines: Vec<_> = frame.lines().collect();
+    assert_eq!(lines[0], "Simulated time: 120");
+    assert_eq!(
+        lines[2].split_whitespace().collect::<Vec<_>>(),
+        vec!["1", "0", "7", "5", "4", "3"]
+    );
+}
@@ -1,4 +1,29 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use scenario::Scenario;
+use simulated_context::SimulatedContext;
 
+#[test]
+fn test_requests_for_pruned_epochs() {
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 3000\nseed = 1\ncommands_per_epoch = 10\npast_epochs_kept = 1",
+    )
+    .unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let node = sim.replicas()[0].node().node_state();
+    let epoch_id = node.epoch_id();
+    assert!(epoch_id > EpochId(2));
+    assert_eq!(node.num_past_record_stores(), 1);
+    assert!(node.record_store_at(EpochId(0)).is_none());
+    // A replica stuck in the first epoch only gets the records of the epochs still kept.
+    let lagging = scenario.make_node(Author(1), &scenario.make_context(Author(1)), NodeTime(0));
+    let request = DataSyncNode::<SimulatedContext>::create_request(&lagging);
+    assert_eq!(request.current_epoch, EpochId(0));
+    let response = DataSyncNode::<SimulatedContext>::handle_request(node, request);
+    let epochs: Vec<_> = response.records.iter().map(|(epoch, _)| *epoch).collect();
+    assert_eq!(epochs, vec![EpochId(epoch_id.0 - 1), epoch_id]);
+}
@@ -0,0 +1,73 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+fn debugger() -> Debugger {
+    Debugger::new(&Scenario::from_toml("nodes = 4\nmax_clock = 2000\nseed = 3").unwrap())
+}
+
+#[test]
+fn test_step_follows_the_run() {
+    let mut debugger = debugger();
+    assert!(debugger.execute("step").unwrap().contains(" timer Author("));
+    assert_eq!(debugger.execute("step 199").unwrap().lines().count(), 199);
+    let steps = debugger.simulator().trace().unwrap().to_vec();
+    assert_eq!(steps.len(), 200);
+
+    let mut sim = Scenario::from_toml("nodes = 4\nmax_clock = 2000\nseed = 3")
+        .unwrap()
+        .build_simulator();
+    sim.enable_trace();
+    sim.loop_until(debugger.simulator().clock(), None);
+    assert_eq!(&sim.trace().unwrap()[..200], &steps[..]);
+
+    debugger.execute("continue 2000").unwrap();
+    assert!(!debugger.simulator().replicas()[0]
+        .context()
+        .committed_history()
+        .is_empty());
+}
+
+#[test]
+fn test_inspect_and_inject() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.execute("next 3").unwrap().lines().count(), 3);
+    assert!(debugger.execute("show 2").unwrap().contains("NodeState"));
+    debugger.execute("step 10").unwrap();
+    debugger.execute("inject 0 1").unwrap();
+    let clock = debugger.simulator().clock().0;
+    assert!(debugger
+        .execute("next 20")
+        .unwrap()
+        .lines()
+        .any(|line| line == format!("{} notify Author(1) Author(0)", clock)));
+}
+
+#[test]
+fn test_faults() {
+    let mut debugger = debugger();
+    debugger.execute("crash 3 0 3000").unwrap();
+    debugger.execute("partition 0 3000 0,1 2").unwrap();
+    debugger.execute("continue 2000").unwrap();
+    // Neither node 2 nor node 3 can take part in a quorum.
+    let sim = debugger.simulator();
+    assert!(sim.replicas()[0].context().committed_history().is_empty());
+    assert!(sim
+        .trace()
+        .unwrap()
+        .iter()
+        .any(|entry| entry.target == Author(3) && entry.dropped));
+}
+
+#[test]
+fn test_invalid_commands() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.execute("  ").unwrap(), "");
+    assert!(debugger.execute("show 4").is_err());
+    assert!(debugger.execute("step 1 2").is_err());
+    assert!(debugger.execute("crash 7 0 10").is_err());
+    assert!(debugger.execute("frobnicate").is_err());
+    debugger.execute("continue 100").unwrap();
+    assert!(debugger.execute("continue 50").is_err());
+}
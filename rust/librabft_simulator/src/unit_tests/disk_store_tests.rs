@@ -0,0 +1,134 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use fixtures::ChainFixture;
+use record_store::{RecordStore, RecordStoreState};
+use scenario::Scenario;
+use simulated_context::SimulatedContext;
+use smr_context::EpochReader;
+
+/// The contexts of a chain fixture, and an empty store for its first epoch.
+fn empty_chain(num_nodes: usize) -> (RecordStoreState, Vec<SimulatedContext>) {
+    let contexts: Vec<_> = (0..num_nodes)
+        .map(|index| SimulatedContext::new(Author(index), num_nodes, usize::MAX))
+        .collect();
+    let state = contexts[0].last_committed_state();
+    let store = RecordStoreState::new(
+        QuorumCertificateHash(0),
+        state.clone(),
+        EpochId(0),
+        contexts[0].configuration(&state),
+    );
+    (store, contexts)
+}
+
+/// A chain fixture whose store keeps at most `cache_capacity` blocks and QCs in memory.
+fn disk_chain(num_nodes: usize, num_rounds: usize, storage: &DiskStorage) -> ChainFixture {
+    let (mut store, contexts) = empty_chain(num_nodes);
+    store.set_disk_storage(storage.clone()).unwrap();
+    let mut fixture = ChainFixture { store, contexts };
+    for _ in 0..num_rounds {
+        fixture.extend_chain();
+    }
+    fixture
+}
+
+#[test]
+fn test_disk_chain() {
+    let storage = DiskStorage::temporary(3).unwrap();
+    let fixture = disk_chain(4, 10, &storage);
+    assert_eq!(fixture.store.resident_records(), 6);
+
+    let expected = ChainFixture::new(4, 10);
+    assert_eq!(
+        fixture.store.highest_committed_round(),
+        expected.store.highest_committed_round()
+    );
+    assert_eq!(
+        fixture.store.highest_quorum_certificate_hash(),
+        expected.store.highest_quorum_certificate_hash()
+    );
+    // Evicted records are read again from disk.
+    let block_hash = fixture.store.blocks_proposed_by(Author(0))[0];
+    assert_eq!(
+        fixture.store.block(block_hash),
+        expected.store.block(block_hash)
+    );
+
+    let records = storage
+        .records_in_rounds(EpochId(0), Round(3)..Round(5))
+        .unwrap();
+    let rounds: Vec<_> = records
+        .iter()
+        .map(|record| match record {
+            Record::Block(block) => (block.round, "block"),
+            Record::QuorumCertificate(qc) => (qc.round, "qc"),
+            _ => panic!("Only blocks and QCs are stored"),
+        })
+        .collect();
+    assert_eq!(
+        rounds,
+        vec![
+            (Round(3), "block"),
+            (Round(3), "qc"),
+            (Round(4), "block"),
+            (Round(4), "qc")
+        ]
+    );
+    assert!(storage
+        .records_in_rounds(EpochId(1), Round(0)..Round(10))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_disk_record_store() {
+    let mut expected = ChainFixture::new(4, 0);
+    let records: Vec<_> = (0..10).flat_map(|_| expected.extend_chain()).collect();
+    let (store, mut contexts) = empty_chain(4);
+    let mut store = DiskRecordStore::open(store, DiskStorage::temporary(3).unwrap()).unwrap();
+    for record in records {
+        if let Record::Block(_) | Record::QuorumCertificate(_) = record {
+            store
+                .insert_network_record(record, &mut contexts[0])
+                .unwrap();
+        }
+    }
+    assert_eq!(store.resident_records(), 6);
+    assert_eq!(
+        store.highest_committed_round(),
+        expected.store.highest_committed_round()
+    );
+    assert_eq!(
+        store.highest_quorum_certificate_hash(),
+        expected.store.highest_quorum_certificate_hash()
+    );
+    let block_hash = store.blocks_proposed_by(Author(0))[0];
+    assert_eq!(store.block(block_hash), expected.store.block(block_hash));
+    assert_eq!(
+        store.records_in_rounds(Round(3)..Round(5)).unwrap().len(),
+        4
+    );
+    // Only empty stores are moved to disk.
+    assert!(DiskRecordStore::open(expected.store, DiskStorage::temporary(3).unwrap()).is_err());
+}
+
+#[test]
+fn test_disk_store_scenario() {
+    let histories = |scenario: &Scenario| -> Vec<_> {
+        let mut sim = scenario.build_simulator();
+        sim.loop_until(GlobalTime(scenario.max_clock), None)
+            .iter()
+            .map(|context| context.committed_history().clone())
+            .collect()
+    };
+    let base = "nodes = 4\nmax_clock = 2000\nseed = 3\n";
+    let expected = histories(&Scenario::from_toml(base).unwrap());
+    assert!(!expected[0].is_empty());
+    let scenario =
+        Scenario::from_toml(&format!("{}[disk_store]\ncache_capacity = 5", base)).unwrap();
+    assert_eq!(histories(&scenario), expected);
+    assert!(Scenario::from_toml(&format!("{}[disk_store]\ncache_capacity = 0", base)).is_err());
+}
@@ -0,0 +1,85 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+fn commit(epoch: usize, round: usize, block: u64) -> Commit<BlockHash, State> {
+    Commit {
+        epoch,
+        round: Round(round),
+        block: BlockHash(block),
+        state: State(block),
+    }
+}
+
+#[test]
+fn test_epoch_change_library() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 3000").unwrap();
+    let library = epoch_change_library(&base);
+    let names: Vec<_> = library.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["commit_race", "staggered_entry", "stale_epoch_leader"]
+    );
+    for (_, scenario) in &library {
+        assert_eq!(scenario.commands_per_epoch, SHORT_EPOCH);
+    }
+    // Node 3 is cut off every other window of 10 * delta.
+    let partitions = &library[1].1.partitions;
+    assert_eq!(partitions[0].groups, vec![vec![0, 1, 2]]);
+    assert_eq!(
+        partitions[1].start - partitions[0].start,
+        20 * base.pacemaker.delta
+    );
+    // The twin of node 3 is replica 4.
+    assert_eq!(library[2].1.twins, vec![3]);
+    assert_eq!(library[2].1.partitions[0].groups, vec![vec![0, 1, 2, 3]]);
+}
+
+#[test]
+fn test_check_commit_logs() {
+    let log = vec![commit(0, 1, 1), commit(0, 2, 2), commit(1, 1, 3)];
+    assert!(check_commit_logs(&[&log, &log[..2], &[]]).is_ok());
+
+    let conflicting = vec![commit(0, 1, 1), commit(0, 2, 4)];
+    assert!(check_commit_logs(&[&log, &conflicting]).is_err());
+    // A block of the previous epoch may not be committed after the switch.
+    let stale = vec![commit(0, 1, 1), commit(1, 1, 3), commit(0, 3, 5)];
+    assert!(check_commit_logs(&[&stale]).is_err());
+}
+
+#[test]
+fn test_evaluate_epoch_changes() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 3000\nseed = 3").unwrap();
+    let reports = evaluate_epoch_changes(&base);
+    assert_eq!(reports.len(), 4);
+    for report in &reports {
+        assert_eq!(report.violation, None, "{}", report.name);
+        assert!(report.min_commits > 0);
+    }
+    assert!(reports[0].epochs.iter().all(|epoch| *epoch == 0));
+    for report in &reports[1..] {
+        assert!(report.epochs.iter().all(|epoch| *epoch > 0));
+        assert!(report.stale_records > 0);
+    }
+    // The node duplicated by the twin is not honest.
+    assert_eq!(reports[3].epochs.len(), 3);
+    assert!(reports.iter().all(|report| report.late_records == 0));
+}
+
+#[test]
+fn test_epoch_grace_period() {
+    let mut base = Scenario::from_toml("nodes = 4\nmax_clock = 3000\nseed = 3").unwrap();
+    base.epoch_grace_period = Some(10 * base.pacemaker.delta);
+    let reports = evaluate_epoch_changes(&base);
+    for report in &reports {
+        assert_eq!(report.violation, None, "{}", report.name);
+        assert!(report.min_commits > 0);
+    }
+    assert_eq!(reports[0].late_records, 0);
+    // The leader kept in the previous epoch keeps sending it records after the switch.
+    let stale_leader = reports
+        .iter()
+        .find(|report| report.name == "stale_epoch_leader");
+    assert!(stale_leader.unwrap().late_records > 0);
+}
@@ -0,0 +1,86 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_statistics() {
+    assert_eq!(Statistics::new(Vec::new()), None);
+    let statistics = Statistics::new((1..=10).rev().map(f64::from).collect()).unwrap();
+    assert_eq!(statistics.samples, 10);
+    assert_eq!(statistics.mean, 5.5);
+    assert_eq!(statistics.min, 1.0);
+    assert_eq!(statistics.p50, 5.0);
+    assert_eq!(statistics.p90, 9.0);
+    assert_eq!(statistics.p99, 10.0);
+    assert_eq!(statistics.max, 10.0);
+}
+
+#[test]
+fn test_parse_sweep() {
+    let sweep = Sweep::parse("network.loss_rate=0,0.1").unwrap();
+    assert_eq!(sweep.parameter, "network.loss_rate");
+    assert_eq!(
+        sweep.values,
+        vec![toml::Value::Integer(0), toml::Value::Float(0.1)]
+    );
+    assert!(Sweep::parse("pacemaker.delta").is_err());
+    assert!(Sweep::parse("pacemaker.delta=ten").is_err());
+}
+
+#[test]
+fn test_with_parameters() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 500").unwrap();
+    let scenario = with_parameters(
+        &base,
+        &[
+            ("pacemaker.delta".to_string(), toml::Value::Integer(25)),
+            ("network.loss_rate".to_string(), toml::Value::Integer(0)),
+            (
+                "workload.max_uncommitted_depth".to_string(),
+                toml::Value::Integer(4),
+            ),
+        ],
+    )
+    .unwrap();
+    assert_eq!(scenario.pacemaker.delta, 25);
+    assert_eq!(scenario.network.loss_rate, 0.0);
+    assert_eq!(scenario.workload.max_uncommitted_depth, Some(4));
+    assert_eq!(scenario.nodes, base.nodes);
+    // Values are checked like in scenario files.
+    let set = |path: &str, value| with_parameters(&base, &[(path.to_string(), value)]);
+    assert!(set("pacemaker.latency", toml::Value::Integer(1)).is_err());
+    assert!(set("nodes.count", toml::Value::Integer(1)).is_err());
+    assert!(set("network.loss_rate", toml::Value::Float(1.5)).is_err());
+}
+
+#[test]
+fn test_run_experiment() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 1000").unwrap();
+    let sweeps = vec![
+        Sweep::parse("pacemaker.delta=20,40").unwrap(),
+        Sweep::parse("network.loss_rate=0,0.2").unwrap(),
+    ];
+    let report = run_experiment(&base, &sweeps, &[1, 2], Some(2)).unwrap();
+    assert_eq!(report.points.len(), 4);
+    let last = &report.points[3];
+    assert_eq!(last.parameters["pacemaker.delta"], toml::Value::Integer(40));
+    assert_eq!(
+        last.parameters["network.loss_rate"],
+        toml::Value::Float(0.2)
+    );
+    for point in &report.points {
+        assert_eq!(point.runs, 2);
+        assert!(point.inconsistent_seeds.is_empty());
+        assert_eq!(point.commits.as_ref().unwrap().samples, 8);
+    }
+    // Results do not depend on the number of threads.
+    assert_eq!(
+        run_experiment(&base, &sweeps, &[1, 2], Some(1)).unwrap(),
+        report
+    );
+    // Losing messages slows down commits.
+    let commits = |index: usize| report.points[index].commits.as_ref().unwrap().mean;
+    assert!(commits(1) < commits(0));
+    assert!(run_experiment(&base, &sweeps, &[], None).is_err());
+}
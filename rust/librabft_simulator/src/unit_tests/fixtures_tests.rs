@@ -0,0 +1,30 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_chain_fixture() {
+    let mut fixture = ChainFixture::new(4, 20);
+    assert_eq!(fixture.store.highest_quorum_certificate_round(), Round(20));
+    assert!(fixture.store.highest_committed_round() > Round(0));
+
+    // The records of the next round are accepted from the network, in order.
+    let records = fixture.next_round_records();
+    assert_eq!(records.len(), 2 + 4);
+    let mut context = fixture.contexts[0].clone();
+    for record in records {
+        assert!(fixture
+            .store
+            .insert_network_record(record, &mut context)
+            .is_ok());
+    }
+    assert_eq!(fixture.store.highest_quorum_certificate_round(), Round(21));
+}
+
+#[test]
+fn test_simulated_validator() {
+    let (node, _, clock) = simulated_validator(4, 500);
+    assert_eq!(clock, NodeTime(500));
+    assert!(node.node_state().record_store().highest_committed_round() > Round(0));
+}
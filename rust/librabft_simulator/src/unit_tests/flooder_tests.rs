@@ -0,0 +1,122 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use campaign;
+use follower::Participant;
+use scenario::{CrashFault, Scenario};
+use simulated_context::SimulatedContext;
+
+const FLOODING: &str = include_str!("../../scenarios/flooding.toml");
+const VOTE_REPLAY: &str = include_str!("../../scenarios/vote_replay.toml");
+
+#[test]
+fn test_junk_records() {
+    let context = SimulatedContext::new(Author(3), 4, 100);
+    let flooder = FloodingNode::new(
+        Author(3),
+        context.last_committed_state(),
+        NodeTime(0),
+        100,
+        5,
+        10,
+        &context,
+    );
+    let (timeouts, vote, block) = flooder.junk();
+    assert_eq!(timeouts.len(), 8);
+    assert!(timeouts.iter().all(|timeout| timeout.author == Author(3)));
+    assert_eq!(vote.unwrap().author, Author(3));
+    let block = block.unwrap();
+    assert_ne!(
+        block.previous_quorum_certificate_hash,
+        QuorumCertificateHash(0)
+    );
+    assert_eq!(block.round, flooder.active_round());
+}
+
+#[test]
+fn test_flooder_sends_junk() {
+    let scenario = Scenario::from_toml(FLOODING).unwrap();
+    let mut sim = scenario.build_simulator();
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(campaign::consistent_histories(&contexts));
+    for context in &contexts[..3] {
+        assert!(!context.committed_history().is_empty());
+    }
+    match sim.simulated_node(Author(3)).node() {
+        Participant::Flooder(flooder) => {
+            assert!(flooder.num_floods() > 350);
+            assert_eq!(flooder.node_state().latest_voted_round(), Round(0));
+            // The flooder still tracks the chain, so that its junk refers to recent rounds.
+            assert!(flooder.active_round() > Round(0));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_flooding_costs_bandwidth() {
+    let flooding = Scenario::from_toml(FLOODING).unwrap();
+    // The same validator, silent instead.
+    let crashed = Scenario {
+        flooders: Vec::new(),
+        faults: vec![CrashFault {
+            node: 3,
+            start: 0,
+            end: None,
+        }],
+        ..flooding.clone()
+    };
+    let flooding_report = campaign::run(&flooding, None);
+    let crashed_report = campaign::run(&crashed, None);
+    assert!(flooding_report.consistent);
+    // Junk blocks are rejected for their unknown parents.
+    assert!(
+        flooding_report.rejections["unknown_parent"]
+            > crashed_report
+                .rejections
+                .get("unknown_parent")
+                .cloned()
+                .unwrap_or(0)
+    );
+    let bandwidth = &flooding_report.bandwidth;
+    for index in 0..3 {
+        assert!(bandwidth.sent[3] > 10 * bandwidth.sent[index]);
+        assert!(bandwidth.received[index] > 10 * crashed_report.bandwidth.received[index]);
+    }
+}
+
+#[test]
+fn test_vote_replay() {
+    let scenario = Scenario::from_toml(VOTE_REPLAY).unwrap();
+    let mut sim = scenario.build_simulator();
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(campaign::consistent_histories(&contexts));
+    let votes = match sim.simulated_node(Author(3)).node() {
+        Participant::Flooder(flooder) => {
+            assert!(flooder.replayed_votes() > 300);
+            assert!(flooder.recorded_votes.len() > 10);
+            flooder.recorded_votes.clone()
+        }
+        _ => unreachable!(),
+    };
+    for replica in &sim.replicas()[..3] {
+        let mut node = replica.node().node_state().clone();
+        let mut context = replica.context().clone();
+        assert!(node.epoch_id() > EpochId(0));
+        assert!(node.rejections()["invalid"] > 0);
+        let epoch_id = node.epoch_id();
+        let current_round = node.record_store().current_round();
+        for vote in &votes {
+            let mut moved = vote.clone();
+            moved.epoch_id = epoch_id;
+            moved.round = current_round;
+            for replayed in [vote.clone(), moved] {
+                assert!(node
+                    .insert_network_record(epoch_id, Record::Vote(replayed), &mut context)
+                    .is_err());
+            }
+        }
+    }
+}
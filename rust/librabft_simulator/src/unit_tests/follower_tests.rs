@@ -0,0 +1,111 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+const FOLLOWERS: &str = include_str!("../../scenarios/followers.toml");
+
+#[test]
+fn test_followers_track_commits() {
+    let scenario = Scenario::from_toml(FOLLOWERS).unwrap();
+    let mut sim = scenario.build_simulator();
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert_eq!(contexts.len(), 6);
+    assert!(campaign::consistent_histories(&contexts));
+    for context in &contexts[4..] {
+        assert!(!context.committed_history().is_empty());
+    }
+    for index in 4..6 {
+        let node = sim.simulated_node(Author(index)).node();
+        assert!(node.is_follower());
+        // Followers have no voting rights and never vote.
+        assert_eq!(node.node_state().configuration().weight(&Author(index)), 0);
+        assert_eq!(node.node_state().latest_voted_round(), Round(0));
+        match node {
+            Participant::Follower(follower) => {
+                let committed = follower.verified_commit().unwrap();
+                assert!(committed.certified_round > Round(0));
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn test_measure_propagation() {
+    let scenario = Scenario::from_toml(FOLLOWERS).unwrap();
+    let report = measure_propagation(&scenario);
+    assert!(report.commands > 0);
+    assert!(report.follower_commits > 0);
+    assert!(report.mean_delay >= 0.0);
+    assert!(report.max_delay as f64 >= report.mean_delay);
+}
+
+#[test]
+fn test_followers_verify_epoch_changes() {
+    let mut scenario = Scenario::from_toml(FOLLOWERS).unwrap();
+    scenario.commands_per_epoch = epoch_changes::SHORT_EPOCH;
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    for index in 4..6 {
+        match sim.simulated_node(Author(index)).node() {
+            Participant::Follower(follower) => {
+                let epoch_id = follower.node_state().epoch_id();
+                assert!(epoch_id > EpochId(1));
+                // Every epoch change was announced by a verified commit certificate.
+                assert_eq!(follower.uncertified_epoch_changes(), 0);
+                assert_eq!(follower.light_client().epoch_id(), epoch_id);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn test_followers_reject_forged_certificates() {
+    let scenario = Scenario::from_toml(FOLLOWERS).unwrap();
+    let mut sim = scenario.build_simulator();
+    // Follower 4 only trusts certificates of another network, so that all the certificates it
+    // receives look forged.
+    match sim.simulated_node_mut(Author(4)).node_mut() {
+        Participant::Follower(follower) => follower.light_client.set_chain_id(ChainId(1)),
+        _ => unreachable!(),
+    }
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(contexts[4].committed_history().is_empty());
+    assert!(!contexts[5].committed_history().is_empty());
+    match sim.simulated_node(Author(4)).node() {
+        Participant::Follower(follower) => {
+            assert!(follower.verified_commit().is_none());
+            // The follower still syncs the records committing the blocks.
+            let record_store = follower.node_state().record_store();
+            assert!(record_store.highest_committed_round() > Round(0));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_followers_keep_verified_configuration() {
+    let mut scenario = Scenario::from_toml(FOLLOWERS).unwrap();
+    scenario.commands_per_epoch = epoch_changes::SHORT_EPOCH;
+    let mut sim = scenario.build_simulator();
+    // No certificate verifies for follower 4, so no epoch change is certified either.
+    match sim.simulated_node_mut(Author(4)).node_mut() {
+        Participant::Follower(follower) => follower.light_client.set_chain_id(ChainId(1)),
+        _ => unreachable!(),
+    }
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let peer_epoch_id = sim.simulated_node(Author(5)).node().node_state().epoch_id();
+    match sim.simulated_node(Author(4)).node() {
+        Participant::Follower(follower) => {
+            // The follower stops following the epochs, and never trusts the configuration of its own execution instead.
+            assert_eq!(follower.light_client().epoch_id(), EpochId(0));
+            assert!(follower.verified_commit().is_none());
+            let epoch_id = follower.node_state().epoch_id();
+            assert_eq!(follower.uncertified_epoch_changes(), epoch_id.0);
+            assert!(epoch_id < peer_epoch_id);
+        }
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, RandomDelay, Simulator};
+use data_sync::*;
+
+#[test]
+fn test_build() {
+    let genesis = GenesisBuilder::new()
+        .validator(Author(1), 2)
+        .validator(Author(0), 1)
+        .validators(2)
+        .build()
+        .unwrap();
+    assert_eq!(genesis.num_validators(), 4);
+    let configuration = genesis.configuration();
+    assert_eq!(configuration.weight(&Author(0)), 1);
+    assert_eq!(configuration.weight(&Author(1)), 2);
+    assert_eq!(configuration.weight(&Author(3)), 1);
+    assert_eq!(configuration.total_votes(), 5);
+    // All nodes start from the same state.
+    assert_eq!(
+        genesis.context(Author(3)).last_committed_state(),
+        genesis.initial_state()
+    );
+    assert_eq!(genesis.initial_hash(), EpochId(0).initial_hash());
+}
+
+#[test]
+fn test_invalid_genesis() {
+    let error = |builder: GenesisBuilder| builder.build().unwrap_err().to_string();
+    assert_eq!(
+        error(GenesisBuilder::new()),
+        "A genesis needs at least one validator."
+    );
+    assert_eq!(
+        error(GenesisBuilder::new().validators(2).validator(Author(1), 3)),
+        "Author(1) is listed twice."
+    );
+    assert_eq!(
+        error(GenesisBuilder::new().validators(1).validator(Author(1), 0)),
+        "Author(1) has no voting rights."
+    );
+    assert_eq!(
+        error(GenesisBuilder::new().validators(1).validator(Author(2), 1)),
+        "Validators must be numbered from 0 without gaps, found Author(2)."
+    );
+    assert!(GenesisBuilder::new()
+        .validators(4)
+        .resilience(1)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_simulate_from_genesis() {
+    let genesis = GenesisBuilder::new()
+        .validators(4)
+        .commands_per_epoch(10)
+        .build()
+        .unwrap();
+    let mut sim = Simulator::<
+        NodeState,
+        SimulatedContext,
+        DataSyncNotification,
+        DataSyncRequest,
+        DataSyncResponse,
+    >::with_seed(
+        3,
+        genesis.num_validators(),
+        RandomDelay::new(10.0, 4.0),
+        |author, _| genesis.context(author),
+        |author, context, clock| genesis.node(author, context, clock),
+    );
+    sim.loop_until(GlobalTime(2000), None);
+    for replica in sim.replicas() {
+        assert!(replica.node().epoch_id() > EpochId(0));
+    }
+}
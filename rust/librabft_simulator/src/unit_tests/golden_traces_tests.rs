@@ -0,0 +1,51 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+// Re-record with `cargo run --bin librabft_simulator -- --generate_golden_traces
+// rust/librabft_simulator/golden_traces` after an intended change of behavior, and review the
+// diff.
+const TRACES: &[&str] = &[
+    include_str!("../../golden_traces/crash_one_node.json"),
+    include_str!("../../golden_traces/reordering.json"),
+    include_str!("../../golden_traces/twins.json"),
+    include_str!("../../golden_traces/stake_changes.json"),
+    include_str!("../../golden_traces/lock_violation.json"),
+];
+
+fn golden_traces() -> Vec<GoldenTrace> {
+    TRACES
+        .iter()
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_golden_traces() {
+    let traces = golden_traces();
+    let names: Vec<_> = traces.iter().map(|trace| trace.name.as_str()).collect();
+    let corpus: Vec<_> = GOLDEN_SCENARIOS.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, corpus);
+    for trace in &traces {
+        assert!(!trace.outcomes[0].commits.is_empty());
+        if let Err(error) = trace.check() {
+            panic!("{}", error);
+        }
+    }
+}
+
+#[test]
+fn test_golden_trace_divergence() {
+    let mut trace = golden_traces().remove(0);
+    trace.outcomes[1].commits[2].block = BlockHash(0);
+    let error = trace.check().unwrap_err().to_string();
+    assert!(
+        error.contains("commit #2 of validator 1 diverged"),
+        "{}",
+        error
+    );
+    trace = golden_traces().remove(0);
+    trace.outcomes[0].quorum_certificates.pop();
+    assert!(trace.check().is_err());
+}
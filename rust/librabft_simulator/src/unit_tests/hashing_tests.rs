@@ -0,0 +1,43 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_hash_functions() {
+    assert_eq!(Fnv1a.hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    // SHA3-256("") starts with a7 ff c6 f8 bf 1e d7 66.
+    assert_eq!(Sha3_256.hash(b""), 0x66d7_1ebf_f8c6_ffa7);
+    assert_eq!(HashFunction::Sha3_256.hash(b"a"), Sha3_256.hash(b"a"));
+    assert_eq!(HashFunction::default(), HashFunction::Sha3_256);
+    assert_eq!(HashFunction::SIMULATION, HashFunction::Fnv1a);
+}
+
+#[test]
+fn test_domain_separation() {
+    for hasher in &[HashFunction::Fnv1a, HashFunction::Sha3_256] {
+        assert_ne!(
+            hasher.hash_in_domain("block", b"x"),
+            hasher.hash_in_domain("vote", b"x")
+        );
+        // Domains are length-prefixed, so they cannot absorb the start of the value.
+        assert_ne!(
+            hasher.hash_in_domain("ab", b"c"),
+            hasher.hash_in_domain("a", b"bc")
+        );
+        assert_ne!(hasher.hash_in_domain("block", b"x"), hasher.hash(b"x"));
+    }
+}
+
+#[test]
+fn test_hash_function_names() {
+    assert_eq!(
+        serde_json::to_string(&HashFunction::Sha3_256).unwrap(),
+        "\"sha3_256\""
+    );
+    assert_eq!(
+        serde_json::from_str::<HashFunction>("\"fnv1a\"").unwrap(),
+        HashFunction::Fnv1a
+    );
+    assert!(serde_json::from_str::<HashFunction>("\"md5\"").is_err());
+}
@@ -0,0 +1,41 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+use scenario::Scenario;
+
+#[test]
+fn test_node_snapshots() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1000\nseed = 1").unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    for replica in sim.replicas() {
+        let node = replica.node().node_state();
+        let snapshot = node.snapshot();
+        assert_eq!(snapshot.schema_version, SNAPSHOT_SCHEMA_VERSION);
+        assert_eq!(snapshot.author, node.local_author());
+        assert_eq!(snapshot.locked_round, node.locked_round());
+        assert_eq!(snapshot.num_commits, node.commits().len());
+        assert!(snapshot.num_commits > 0);
+        assert_eq!(snapshot.pacemaker.active_round, node.active_round());
+        assert_eq!(
+            snapshot.record_store.highest_committed_round,
+            node.record_store().highest_committed_round()
+        );
+        assert!(snapshot.record_store.num_blocks >= snapshot.record_store.num_quorum_certificates);
+        assert!(
+            snapshot.commit_tracker.highest_committed_round
+                <= snapshot.record_store.highest_committed_round
+        );
+        // Snapshots are exported and read back as they are.
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["record_store"]["current_round"],
+            snapshot.record_store.current_round.0
+        );
+        let parsed: NodeSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::{GlobalTime, RandomDelay, Simulator};
+use data_sync::{DataSyncNotification, DataSyncRequest, DataSyncResponse};
+use node::NodeState;
+
+#[test]
+#[should_panic(expected = "at least one key")]
+fn test_no_keys() {
+    KvContext::new(Author(0), 1, 3, 0);
+}
+
+#[test]
+fn test_operations() {
+    let mut context = KvContext::new(Author(0), 1, 3, 8);
+    let mut state = context.last_committed_state();
+    assert_eq!(context.read_epoch_id(&state), EpochId(0));
+    let mut expected = BTreeMap::new();
+    for time in 0..4 {
+        let command = context.fetch().unwrap();
+        match KvOperation::of(&command, 8) {
+            KvOperation::Put { key, value } => {
+                assert!(key < 8);
+                expected.insert(key, value);
+            }
+            KvOperation::Delete { key } => {
+                expected.remove(&key);
+            }
+        }
+        state = context
+            .compute(&state, Some(command), NodeTime(time), None, Vec::new())
+            .unwrap();
+        context.commit(&state, NodeTime(time), None);
+    }
+    let store = context.last_committed_store();
+    assert_eq!(store.executed(), 4);
+    for key in 0..8 {
+        assert_eq!(store.get(key), expected.get(&key).cloned());
+    }
+    // The reserved key was incremented after 3 commands.
+    assert_eq!(context.read_epoch_id(&state), EpochId(1));
+    assert_eq!(context.committed_states().len(), 4);
+
+    // Past versions of the store are kept until they are pruned.
+    let states = context.committed_states().to_vec();
+    assert_eq!(context.num_committed_stores(), 5);
+    assert_eq!(context.committed_store(&states[0]).unwrap().executed(), 1);
+    context.prune(&states[1]);
+    assert_eq!(context.num_committed_stores(), 3);
+    assert!(context.committed_store(&states[0]).is_none());
+    assert_eq!(context.committed_store(&states[1]).unwrap().executed(), 2);
+    context.prune(&state);
+    assert_eq!(context.num_committed_stores(), 1);
+}
+
+#[test]
+fn test_distinct_histories() {
+    let mut context = KvContext::new(Author(0), 1, 100, 8);
+    let base = context.last_committed_state();
+    let command = context.fetch().unwrap();
+    let state1 = context
+        .compute(&base, Some(command.clone()), NodeTime(1), None, Vec::new())
+        .unwrap();
+    let state2 = context
+        .compute(&base, Some(command), NodeTime(2), None, Vec::new())
+        .unwrap();
+    assert_ne!(state1, state2);
+    context.discard(&state1);
+    context.commit(&state2, NodeTime(0), None);
+}
+
+#[test]
+fn test_simulation() {
+    let mut sim = Simulator::<
+        NodeState,
+        KvContext,
+        DataSyncNotification,
+        DataSyncRequest,
+        DataSyncResponse,
+    >::with_seed(
+        5,
+        4,
+        RandomDelay::new(10.0, 4.0),
+        |author, num_nodes| KvContext::new(author, num_nodes, 20, 16),
+        |author, context: &KvContext, clock| {
+            NodeState::new(
+                author,
+                context.last_committed_state(),
+                clock,
+                100_000,
+                20,
+                2.0,
+                0.5,
+                context,
+            )
+        },
+    );
+    let contexts = sim.loop_until(GlobalTime(5000), None);
+    let longest = contexts
+        .iter()
+        .map(|context| context.committed_states())
+        .max_by_key(|states| states.len())
+        .unwrap();
+    for context in &contexts {
+        let states = context.committed_states();
+        assert!(!states.is_empty());
+        assert_eq!(states, &longest[..states.len()]);
+    }
+    // Nodes prune the past versions of the store after each commit.
+    for context in &contexts {
+        assert_eq!(context.num_committed_stores(), 1);
+    }
+    // Several epochs were started from the reserved key.
+    assert!(contexts[0].last_committed_store().epoch_id() > EpochId(1));
+    // Nodes log the commits delivered to their context.
+    for replica in sim.replicas() {
+        let states: Vec<_> = replica
+            .node()
+            .commits()
+            .iter()
+            .map(|commit| commit.state.clone())
+            .collect();
+        assert_eq!(&states[..], replica.context().committed_states());
+    }
+}
+
+#[test]
+fn test_batch_transactions() {
+    let mut context = KvContext::new(Author(0), 1, 100, 8);
+    let base = context.last_committed_state();
+    let c1 = context.fetch().unwrap();
+    let c2 = context.fetch().unwrap();
+    let time = NodeTime(1);
+
+    // Single commands and empty blocks give the same states as batches of zero or one command.
+    let s1 = context
+        .compute(&base, Some(c1.clone()), time, None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .compute(&s1, Some(c2.clone()), time, None, Vec::new())
+        .unwrap();
+    let empty = context.compute(&base, None, time, None, Vec::new());
+    assert_eq!(
+        context.compute_batch(&base, &[], time, None, Vec::new()),
+        empty
+    );
+    let batch = context
+        .compute_batch(&base, &[c1.clone(), c2.clone()], time, None, Vec::new())
+        .unwrap();
+    assert_eq!(context.store(&batch).unwrap().executed(), 2);
+    assert_eq!(
+        context.store(&batch).unwrap().get(0),
+        context.store(&s2).unwrap().get(0)
+    );
+
+    // Aborted transactions leave no state behind.
+    let num_pending = context.pending_stores.len();
+    assert!(context.begin_transaction(&base, NodeTime(2), None, Vec::new()));
+    assert!(context.apply_command(&c1));
+    context.abort_transaction();
+    assert!(context.commit_transaction().is_none());
+    assert_eq!(context.pending_stores.len(), num_pending);
+    assert!(!context.begin_transaction(&State(7), time, None, Vec::new()));
+    assert_eq!(
+        context.compute_batch(&State(7), &[c1], time, None, Vec::new()),
+        None
+    );
+}
@@ -0,0 +1,89 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use record_builder::RecordBuilder;
+
+fn configuration(num_nodes: usize) -> EpochConfiguration {
+    EpochConfiguration::new((0..num_nodes).map(|index| (Author(index), 1)).collect())
+}
+
+fn certificate(
+    epoch_id: EpochId,
+    round: Round,
+    next_epoch: Option<EpochChange>,
+) -> QuorumCertificate {
+    let record = RecordBuilder::quorum_certificate(Author(0))
+        .voters((0..3).map(Author))
+        .epoch(epoch_id)
+        .round(round)
+        .certified_block(BlockHash(47))
+        .state(State(5))
+        .committed_state(Some(State(4)))
+        .next_epoch(next_epoch)
+        .build();
+    match record {
+        Record::QuorumCertificate(qc) => qc,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_verify_keeps_highest_commit() {
+    let mut client = LightClient::new(EpochId(0), configuration(4));
+    assert_eq!(
+        client
+            .verify(&certificate(EpochId(0), Round(5), None))
+            .unwrap()
+            .certified_round,
+        Round(5)
+    );
+    assert_eq!(
+        client
+            .verify(&certificate(EpochId(0), Round(3), None))
+            .unwrap()
+            .certified_round,
+        Round(5)
+    );
+    assert_eq!(
+        client.verify(&certificate(EpochId(1), Round(6), None)),
+        Err(VerifyError::WrongEpoch(EpochId(1)))
+    );
+    assert_eq!(client.next_epoch(), None);
+}
+
+#[test]
+fn test_enter_announced_epoch() {
+    let mut client = LightClient::new(EpochId(0), configuration(4));
+    let next_configuration = configuration(5);
+    // Without an announcement, no configuration is trusted.
+    assert_eq!(
+        client.enter_epoch(EpochId(1), next_configuration.clone()),
+        Err(VerifyError::InvalidEpochChange(EpochId(1)))
+    );
+    let change = EpochChange::new(EpochId(1), &next_configuration);
+    client
+        .verify(&certificate(EpochId(0), Round(5), Some(change)))
+        .unwrap();
+    assert_eq!(client.next_epoch(), Some(change));
+    // Only the announced configuration and epoch are accepted.
+    assert_eq!(
+        client.enter_epoch(EpochId(1), configuration(4)),
+        Err(VerifyError::InvalidEpochChange(EpochId(1)))
+    );
+    assert_eq!(
+        client.enter_epoch(EpochId(2), next_configuration.clone()),
+        Err(VerifyError::InvalidEpochChange(EpochId(2)))
+    );
+    assert_eq!(
+        client.enter_epoch(EpochId(1), next_configuration.clone()),
+        Ok(())
+    );
+    assert_eq!(client.epoch_id(), EpochId(1));
+    assert_eq!(client.configuration(), &next_configuration);
+    assert!(client.latest_commit().is_none());
+    // Certificates of the new epoch are verified against the new voting rights.
+    assert!(client
+        .verify(&certificate(EpochId(1), Round(2), None))
+        .is_err());
+}
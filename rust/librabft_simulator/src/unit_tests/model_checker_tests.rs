@@ -0,0 +1,42 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_independence() {
+    let timer = Transition::Timer(Author(0));
+    let state = ModelState::new(&ModelCheckerConfig::default());
+    let request = DataSyncNode::<SimulatedContext>::create_request(&state.nodes[1]);
+    let query = |sender, receiver| Transition::Query {
+        sender: Author(sender),
+        receiver: Author(receiver),
+        request: request.clone(),
+    };
+    assert!(!timer.is_independent(&Transition::Timer(Author(0))));
+    assert!(timer.is_independent(&Transition::Timer(Author(1))));
+    assert!(!timer.is_independent(&query(0, 1)));
+    assert!(!timer.is_independent(&query(1, 0)));
+    assert!(timer.is_independent(&query(1, 2)));
+    assert!(query(1, 2).is_independent(&query(1, 3)));
+    assert!(!query(1, 2).is_independent(&query(2, 3)));
+}
+
+#[test]
+fn test_exhaustive_check() {
+    let config = ModelCheckerConfig {
+        max_depth: 4,
+        ..ModelCheckerConfig::default()
+    };
+    let report = check(&config);
+    assert_eq!(report.safety_violation, None);
+    assert_eq!(report.liveness_violation, None);
+    assert!(report.pruned > 0);
+
+    let unreduced = check(&ModelCheckerConfig {
+        partial_order_reduction: false,
+        ..config
+    });
+    assert_eq!(unreduced.pruned, 0);
+    assert!(unreduced.states > report.states);
+}
@@ -0,0 +1,87 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use record_store::{RecordStore, RecordStoreState};
+use simulated_context::SimulatedContext;
+use smr_context::{CommandFetcher, EpochReader};
+
+fn make_store(context: &SimulatedContext) -> RecordStoreState {
+    let state = context.last_committed_state().clone();
+    RecordStoreState::new(
+        EpochId(0).initial_hash(),
+        state.clone(),
+        EpochId(0),
+        context.configuration(&state),
+    )
+}
+
+#[test]
+fn test_local_action_errors() {
+    let mut context = SimulatedContext::new(Author(0), 2, 20);
+    let mut store = make_store(&context);
+
+    assert_eq!(
+        store.create_vote(Author(0), BlockHash(7), NodeTime(1), &mut context),
+        Err(NodeError::Storage(StorageError::UnknownBlock(BlockHash(7))))
+    );
+    let command = context.fetch();
+    assert_eq!(
+        store.propose_block(
+            Author(0),
+            QuorumCertificateHash(7),
+            NodeTime(1),
+            command,
+            &mut context
+        ),
+        Err(NodeError::Storage(StorageError::UnknownQuorumCertificate(
+            QuorumCertificateHash(7)
+        )))
+    );
+
+    assert_eq!(
+        store.create_timeout(Author(0), Round(1), &mut context),
+        Ok(())
+    );
+    assert_eq!(
+        store.create_timeout(Author(0), Round(1), &mut context),
+        Err(NodeError::Validation(RejectReason::Duplicate))
+    );
+    assert_eq!(
+        store.create_timeout(Author(0), Round(2), &mut context),
+        Err(NodeError::Validation(RejectReason::FutureRound))
+    );
+    assert_eq!(
+        store.check_for_new_quorum_certificate(Author(0), &mut context),
+        Ok(false)
+    );
+}
+
+#[test]
+fn test_rejected_records() {
+    let context = SimulatedContext::new(Author(0), 2, 20);
+    let store = make_store(&context);
+    let timeout = Record::make_timeout(
+        EpochId(1),
+        Round(1),
+        Round(0),
+        store.public_key(Author(1)),
+        store.chain_id(),
+        &store.hash_function(),
+    );
+    assert_eq!(
+        NodeError::rejected(RejectReason::FutureEpoch, EpochId(0), &timeout),
+        NodeError::EpochMismatch {
+            expected: EpochId(0),
+            found: EpochId(1)
+        }
+    );
+    assert_eq!(
+        NodeError::rejected(RejectReason::BadSignature, EpochId(0), &timeout),
+        NodeError::Crypto { author: Author(1) }
+    );
+    assert_eq!(
+        NodeError::rejected(RejectReason::StaleRound, EpochId(0), &timeout).name(),
+        "validation"
+    );
+}
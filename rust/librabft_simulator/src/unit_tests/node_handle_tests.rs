@@ -0,0 +1,84 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::transport::{LocalTransport, NodeRunner};
+use follower::Participant;
+use scenario::Scenario;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_send_sync() {
+    assert_send_sync::<NodeHandle>();
+    assert_send_sync::<NodeHandle<Participant>>();
+}
+
+#[test]
+fn test_concurrent_access() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1").unwrap();
+    let handles: Vec<_> = (0..scenario.nodes)
+        .map(|index| {
+            let author = Author(index);
+            let context = scenario.make_context(author);
+            NodeHandle::new(scenario.make_node(author, &context, NodeTime(0)))
+        })
+        .collect();
+    let running = Arc::new(AtomicBool::new(true));
+    // Inspect the nodes while they run.
+    let observer = {
+        let handles = handles.clone();
+        let running = running.clone();
+        thread::spawn(move || {
+            let mut progress = vec![(EpochId(0), Round(0), Round(0)); handles.len()];
+            while running.load(Ordering::SeqCst) {
+                for (handle, progress) in handles.iter().zip(progress.iter_mut()) {
+                    let node = handle.read();
+                    let state = node.node_state();
+                    let current = (
+                        state.epoch_id(),
+                        state.record_store().highest_committed_round(),
+                        state.pacemaker().active_round(),
+                    );
+                    // Rounds only move forward within an epoch.
+                    assert!(
+                        current.0 > progress.0
+                            || (current.1 >= progress.1 && current.2 >= progress.2)
+                    );
+                    *progress = current;
+                }
+                thread::sleep(std::time::Duration::from_millis(1));
+            }
+        })
+    };
+    let runners: Vec<_> = LocalTransport::network(scenario.nodes)
+        .into_iter()
+        .zip(handles.iter().cloned())
+        .enumerate()
+        .map(|(index, (transport, handle))| {
+            let scenario = scenario.clone();
+            thread::spawn(move || {
+                let mut runner = NodeRunner::new(
+                    Author(index),
+                    scenario.nodes,
+                    |author, _| scenario.make_context(author),
+                    |_, _, _| handle.clone(),
+                    transport,
+                );
+                runner.run_until(NodeTime(1000));
+            })
+        })
+        .collect();
+    for runner in runners {
+        runner.join().unwrap();
+    }
+    running.store(false, Ordering::SeqCst);
+    observer.join().unwrap();
+    for handle in &handles {
+        assert!(!handle.read().node_state().commits().is_empty());
+    }
+}
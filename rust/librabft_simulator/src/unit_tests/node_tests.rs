@@ -2,12 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use bft_simulator_runtime::{bandwidth::PayloadSizes, simulator};
+use hashing::Fnv1a;
 use simulated_context::*;
 use smr_context::*;
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
 
 #[test]
 fn test_node() {
@@ -32,14 +30,27 @@ fn test_node() {
 
     // Make a sequence of blocks / QCs
     let cmd = context.fetch().unwrap();
-    let b0 = Record::make_block(cmd.clone(), NodeTime(1), initial_hash, Round(1), Author(0));
+    let b0 = Record::make_block(
+        EpochId(0),
+        Some(cmd.clone()),
+        NodeTime(1),
+        initial_hash,
+        Round(1),
+        Author(0),
+        ChainId(0),
+        &Fnv1a,
+    );
 
-    let mut hasher = DefaultHasher::new();
-    b0.hash(&mut hasher);
-    let block_hash = BlockHash(hasher.finish());
+    let block_hash = BlockHash(b0.digest(&Fnv1a));
 
     let state = context
-        .compute(&initial_state, cmd.clone(), NodeTime(1), None, Vec::new())
+        .compute(
+            &initial_state,
+            Some(cmd.clone()),
+            NodeTime(1),
+            None,
+            Vec::new(),
+        )
         .unwrap();
 
     let v0 = match Record::make_vote(
@@ -49,6 +60,10 @@ fn test_node() {
         state.clone(),
         Author(0),
         /* commitment */ None,
+        /* next epoch */ None,
+        NodeTime(2),
+        ChainId(0),
+        &Fnv1a,
     ) {
         Record::Vote(x) => x,
         _ => unreachable!(),
@@ -58,16 +73,203 @@ fn test_node() {
         Round(1),
         block_hash,
         state,
-        /* votes */ vec![(Author(0), v0.signature)],
+        /* votes */ vec![(Author(0), v0.time, v0.signature)],
         /* commitment */ None,
+        /* next epoch */ None,
         Author(0),
+        ChainId(0),
+        &Fnv1a,
     );
-    let qc_hash = QuorumCertificateHash(qc0.digest());
+    let qc_hash = QuorumCertificateHash(qc0.digest(&Fnv1a));
 
-    node1.insert_network_record(epoch_id, b0, &mut context);
-    node1.insert_network_record(epoch_id, qc0, &mut context);
+    assert_eq!(
+        node1.insert_network_record(epoch_id, b0.clone(), &mut context),
+        Ok(Inserted {
+            digest: block_hash.0
+        })
+    );
+    assert!(node1
+        .insert_network_record(epoch_id, qc0.clone(), &mut context)
+        .is_ok());
     assert_eq!(
         node1.record_store.highest_quorum_certificate_hash(),
         qc_hash
     );
+
+    // Rejections are counted by reason.
+    assert_eq!(
+        node1.insert_network_record(epoch_id, b0, &mut context),
+        Err(RejectReason::Duplicate)
+    );
+    assert_eq!(
+        node1.insert_network_record(EpochId(1), qc0, &mut context),
+        Err(RejectReason::FutureEpoch)
+    );
+    assert_eq!(
+        node1.rejections().iter().collect::<Vec<_>>(),
+        vec![(&"duplicate", &1), (&"future_epoch", &1)]
+    );
+}
+
+const OVERLOAD_PAYLOADS: PayloadSizes = PayloadSizes::Uniform { min: 0, max: 1000 };
+
+fn run_overloaded_simulation(load_shedding: Option<LoadSheddingPolicy>) -> Vec<SimulatedContext> {
+    let context_factory = |author, num_nodes| {
+        let mut context = SimulatedContext::new(author, num_nodes, 30000);
+        context.set_arrival_rate(3);
+        context.set_payload_sizes(OVERLOAD_PAYLOADS);
+        context
+    };
+    let node_factory = |author: Author, context: &SimulatedContext, clock: NodeTime| {
+        let mut node = NodeState::new(
+            author,
+            context.last_committed_state(),
+            clock,
+            100000,
+            20,
+            2.0,
+            0.5,
+            context,
+        );
+        if let Some(policy) = load_shedding {
+            node.set_load_shedding_policy(policy);
+        }
+        node
+    };
+    let mut sim = simulator::Simulator::<
+        NodeState,
+        SimulatedContext,
+        data_sync::DataSyncNotification,
+        data_sync::DataSyncRequest,
+        data_sync::DataSyncResponse,
+    >::new(
+        3,
+        simulator::RandomDelay::new(10.0, 4.0),
+        context_factory,
+        node_factory,
+    );
+    sim.loop_until(simulator::GlobalTime(2000), None)
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn test_load_shedding_under_overload() {
+    let contexts = run_overloaded_simulation(Some(LoadSheddingPolicy {
+        mempool_threshold: 5,
+        max_payload_bytes: None,
+    }));
+    for context in &contexts {
+        // The system remains live..
+        assert!(!context.committed_history().is_empty());
+        // .. and high-priority commands wait for a bounded number of proposals.
+        assert!(context.oldest_pending_age(Priority::High) <= 4);
+    }
+    // Without load shedding, high-priority commands wait behind the backlog.
+    let contexts = run_overloaded_simulation(None);
+    assert!(contexts
+        .iter()
+        .any(|context| context.oldest_pending_age(Priority::High) > 4));
+}
+
+#[test]
+fn test_payload_cap_under_overload() {
+    // Number of committed commands, and how many have a payload above the cap.
+    let payloads = |context: &SimulatedContext| {
+        let history = context.committed_history();
+        let large = history
+            .iter()
+            .filter(|(command, _)| context.payload_size(command) > 200)
+            .count();
+        (history.len(), large)
+    };
+    let contexts = run_overloaded_simulation(Some(LoadSheddingPolicy {
+        mempool_threshold: 5,
+        max_payload_bytes: Some(200),
+    }));
+    for context in &contexts {
+        let (commits, large) = payloads(context);
+        // The system remains live with small payloads, and large payloads are only proposed
+        // before the mempools are overloaded, i.e. by the first 3 fetches of each of the 3
+        // nodes.
+        assert!(commits > 30);
+        assert!(large <= 9);
+    }
+    // Without a cap, most payloads are larger.
+    let contexts = run_overloaded_simulation(Some(LoadSheddingPolicy {
+        mempool_threshold: 5,
+        max_payload_bytes: None,
+    }));
+    for context in &contexts {
+        let (commits, large) = payloads(context);
+        assert!(large * 2 > commits);
+    }
+}
+
+#[test]
+fn test_single_proposal_per_round() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let mut node = NodeState::new(
+        Author(0),
+        context.last_committed_state(),
+        NodeTime(0),
+        1000,
+        30,
+        2.0,
+        0.5,
+        &context,
+    );
+    node.set_proposal_limiter(0, false);
+    let propose = || PacemakerUpdateActions {
+        should_propose_block: Some(QuorumCertificateHash(0)),
+        should_create_timeout: None,
+        should_send: Vec::new(),
+        should_broadcast: false,
+        should_query_all: false,
+        next_scheduled_update: NodeTime::never(),
+    };
+    // A pacemaker asking twice for the same round only gets one proposal.
+    node.process_pacemaker_actions(propose(), NodeTime(1), &mut context);
+    node.process_pacemaker_actions(propose(), NodeTime(2), &mut context);
+    assert_eq!(node.record_store().blocks_proposed_by(Author(0)).len(), 1);
+    assert_eq!(node.proposal_limiter().unwrap().duplicates(), 1);
+}
+
+#[test]
+fn test_parallel_verification() {
+    let mut fixture = fixtures::ChainFixture::new(4, 0);
+    let records: Vec<_> = (0..3).flat_map(|_| fixture.extend_chain()).collect();
+    let run = |parallel: bool| {
+        let mut context = SimulatedContext::new(Author(0), 4, usize::MAX);
+        context.set_cpu_costs(1, 0);
+        context.set_verification_workers(4);
+        let mut node = NodeState::new(
+            Author(0),
+            context.last_committed_state(),
+            NodeTime(0),
+            1000,
+            30,
+            2.0,
+            0.5,
+            &context,
+        );
+        if parallel {
+            node.set_parallel_verification();
+        }
+        node.insert_network_records(EpochId(0), records.clone(), &mut context);
+        (node, context.take_processing_time())
+    };
+    let (sequential, sequential_time) = run(false);
+    let (parallel, parallel_time) = run(true);
+    assert_eq!(
+        parallel.record_store().highest_quorum_certificate_round(),
+        Round(3)
+    );
+    assert_eq!(
+        parallel.record_store().highest_quorum_certificate_hash(),
+        sequential.record_store().highest_quorum_certificate_hash()
+    );
+    assert_eq!(parallel.rejections(), sequential.rejections());
+    assert!(parallel_time < sequential_time);
 }
@@ -0,0 +1,71 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use hashing::Fnv1a;
+
+fn block(parent: u64, round: usize) -> Record {
+    Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(0),
+        QuorumCertificateHash(parent),
+        Round(round),
+        Author(0),
+        ChainId(0),
+        &Fnv1a,
+    )
+}
+
+#[test]
+fn test_adopt_children() {
+    let mut buffer = OrphanBuffer::new(4, 3);
+    buffer.push(block(1, 5), Round(5));
+    buffer.push(block(2, 5), Round(5));
+    buffer.push(block(1, 6), Round(5));
+    // Copies are ignored.
+    buffer.push(block(1, 5), Round(5));
+    // Timeouts have no parent.
+    buffer.push(
+        Record::make_timeout(
+            EpochId(0),
+            Round(5),
+            Round(0),
+            Author(1),
+            ChainId(0),
+            &Fnv1a,
+        ),
+        Round(5),
+    );
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(buffer.take_children(1), vec![block(1, 5), block(1, 6)]);
+    assert_eq!(buffer.take_children(1), Vec::new());
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(buffer.stats().buffered, 3);
+}
+
+#[test]
+fn test_evictions() {
+    let mut buffer = OrphanBuffer::new(2, 3);
+    buffer.push(block(1, 6), Round(6));
+    buffer.push(block(2, 5), Round(6));
+    // The lowest round is evicted on overflow, even if it arrived last.
+    buffer.push(block(3, 4), Round(6));
+    assert_eq!(buffer.take_children(3), Vec::new());
+    assert_eq!(buffer.len(), 2);
+    // Round 5 is too old once round 9 starts.
+    buffer.evict_stale(Round(8));
+    assert_eq!(buffer.len(), 2);
+    buffer.evict_stale(Round(9));
+    assert_eq!(buffer.take_children(1), vec![block(1, 6)]);
+    buffer.clear();
+    assert!(buffer.is_empty());
+    assert_eq!(
+        buffer.stats(),
+        &OrphanStats {
+            buffered: 3,
+            adopted: 0,
+            evicted: 2,
+        }
+    );
+}
@@ -0,0 +1,55 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+const THREE_REGIONS: &str = include_str!("../../scenarios/three_regions.toml");
+
+#[test]
+fn test_outage_library() {
+    let base = Scenario::from_toml(THREE_REGIONS).unwrap();
+    let library = outage_library(&base);
+    let names: Vec<_> = library.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "region_down:asia",
+            "region_down:eu",
+            "region_down:us",
+            "backbone_degraded",
+            "rolling_maintenance"
+        ]
+    );
+    // Region "eu" holds nodes 1 and 4.
+    assert_eq!(library[1].1.faults.len(), 2);
+    // Three regions give three inter-region links.
+    assert_eq!(library[3].1.link_faults.len(), 3);
+    // Every node goes down exactly once during the maintenance.
+    assert_eq!(library[4].1.faults.len(), base.nodes);
+}
+
+#[test]
+fn test_evaluate_resilience() {
+    let base = Scenario::from_toml(THREE_REGIONS).unwrap();
+    let reports = evaluate_resilience(&base);
+    assert_eq!(reports.len(), 6);
+    for report in &reports {
+        assert!(report.min_commits <= report.max_commits);
+    }
+    // Losing the 2 nodes of region "asia" keeps a quorum of 5 out of 7 nodes.
+    let baseline = &reports[0];
+    assert!(baseline.min_commits > 0);
+    let region_down = reports
+        .iter()
+        .find(|report| report.name == "region_down:asia")
+        .unwrap();
+    assert!(region_down.min_commits > 0);
+    assert!(region_down.quorum_impossible.is_empty());
+    // Losing the 3 nodes of region "us" leaves 4 nodes, below the quorum of 5.
+    let region_down = reports
+        .iter()
+        .find(|report| report.name == "region_down:us")
+        .unwrap();
+    assert_eq!(region_down.quorum_impossible, vec![(1000, 2000)]);
+    assert_eq!(reports[5].quorum_impossible, vec![(1666, 1999)]);
+}
@@ -1,4 +1,132 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
+use super::*;
+use simulated_context::SimulatedContext;
+use smr_context::EpochReader;
 
+fn make_record_store() -> RecordStoreState {
+    let context = SimulatedContext::new(Author(0), 1, 100);
+    let state = context.last_committed_state();
+    let configuration = context.configuration(&state);
+    RecordStoreState::new(EpochId(0).initial_hash(), state, EpochId(0), configuration)
+}
+
+#[test]
+fn test_optimistic_responsiveness() {
+    let record_store = make_record_store();
+    let mut pacemaker = PacemakerState::new(EpochId(0), NodeTime(0), 20, 2.0, 0.5);
+    // The only node leads every round and proposes right away.
+    let actions = pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(5),
+    );
+    assert_eq!(pacemaker.active_round(), Round(1));
+    assert!(actions.should_propose_block.is_some());
+    assert_eq!(actions.next_scheduled_update, NodeTime(5));
+
+    let mut pacemaker = PacemakerState::new(EpochId(0), NodeTime(0), 20, 2.0, 0.5);
+    pacemaker.set_optimistic_responsiveness(false);
+    let actions = pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(5),
+    );
+    assert!(actions.should_propose_block.is_none());
+    assert_eq!(actions.next_scheduled_update, NodeTime(25));
+    let actions = pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(25),
+    );
+    assert!(actions.should_propose_block.is_some());
+    assert!(actions.should_create_timeout.is_none());
+    // Timeouts are delayed by the same amount.
+    let actions = pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(44),
+    );
+    assert!(actions.should_create_timeout.is_none());
+    let actions = pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(45),
+    );
+    assert_eq!(actions.should_create_timeout, Some(Round(1)));
+}
+
+#[test]
+fn test_adaptive_delta() {
+    let mut adaptive_delta = AdaptiveDelta::new(0.125, 2.0);
+    assert_eq!(adaptive_delta.observe(NodeTime(10), 10), 20);
+    assert_eq!(adaptive_delta.observe(NodeTime(30), 20), 23);
+    assert_eq!(adaptive_delta.observe(NodeTime(35), 0), 20);
+    assert_eq!(
+        adaptive_delta.history(),
+        &[(NodeTime(10), 20), (NodeTime(30), 23), (NodeTime(35), 20)]
+    );
+}
+
+#[test]
+fn test_leader_reputation() {
+    let mut contexts: Vec<_> = (0..4)
+        .map(|index| SimulatedContext::new(Author(index), 4, 100))
+        .collect();
+    let state = contexts[0].last_committed_state();
+    let configuration = contexts[0].configuration(&state);
+    let mut record_store =
+        RecordStoreState::new(EpochId(0).initial_hash(), state, EpochId(0), configuration);
+    let mut pacemaker = PacemakerState::new(EpochId(0), NodeTime(0), 20, 2.0, 0.5);
+    pacemaker.set_leader_reputation(LeaderReputation::new(5));
+    pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(0),
+    );
+    let failed_leader = pacemaker.active_leader().unwrap();
+    // Round 1 ends with a TC.
+    for (index, context) in contexts.iter_mut().enumerate().take(3) {
+        record_store
+            .create_timeout(Author(index), Round(1), context)
+            .unwrap();
+    }
+    pacemaker.update_pacemaker(
+        Author(0),
+        EpochId(0),
+        &record_store,
+        NodeTime(0),
+        NodeTime(50),
+    );
+    assert_eq!(pacemaker.active_round(), Round(2));
+    let reputation = pacemaker.leader_reputation().unwrap();
+    assert_eq!(
+        reputation.outcomes()[&Round(1)],
+        (failed_leader, LeaderOutcome::TimedOut)
+    );
+    // The failed leader is skipped during the next 5 rounds only.
+    for round in 2..7 {
+        assert_ne!(
+            pacemaker.elect_leader(&record_store, Round(round)),
+            failed_leader
+        );
+    }
+    assert!(reputation.excluded_leaders(Round(7)).is_empty());
+    assert_eq!(
+        pacemaker.elect_leader(&record_store, Round(7)),
+        PacemakerState::leader(&record_store, Round(7))
+    );
+}
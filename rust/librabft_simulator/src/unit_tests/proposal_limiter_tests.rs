@@ -0,0 +1,34 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_proposal_limiter() {
+    let mut limiter = ProposalLimiter::new(10, false);
+    let epoch_id = EpochId(0);
+    assert_eq!(limiter.check(epoch_id, Round(1), NodeTime(0)), Ok(()));
+    limiter.record(epoch_id, Round(1), NodeTime(0));
+    assert_eq!(
+        limiter.check(epoch_id, Round(1), NodeTime(20)),
+        Err(ProposalDenial::SameRound)
+    );
+    assert_eq!(
+        limiter.check(epoch_id, Round(2), NodeTime(5)),
+        Err(ProposalDenial::TooEarly(NodeTime(10)))
+    );
+    assert_eq!(limiter.check(epoch_id, Round(2), NodeTime(10)), Ok(()));
+    limiter.record(epoch_id, Round(2), NodeTime(10));
+    // Rounds start over in a new epoch.
+    assert_eq!(limiter.check(EpochId(1), Round(1), NodeTime(20)), Ok(()));
+    assert_eq!(limiter.duplicates(), 1);
+    assert_eq!(limiter.deferrals(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Proposing again")]
+fn test_strict_proposal_limiter() {
+    let mut limiter = ProposalLimiter::new(0, true);
+    limiter.record(EpochId(0), Round(3), NodeTime(0));
+    let _ = limiter.check(EpochId(0), Round(3), NodeTime(1));
+}
@@ -0,0 +1,69 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use proptest::prelude::*;
+use strategies::arb_near_valid_records;
+
+#[test]
+fn test_test_vectors_round_trip() {
+    for vector in test_vectors::canonical_test_vectors() {
+        let bytes = vector.record.to_proto_bytes();
+        assert_eq!(Record::from_proto_bytes(&bytes).unwrap(), vector.record);
+    }
+}
+
+#[test]
+fn test_invalid_messages() {
+    assert!(Record::from_proto_bytes(&[]).is_err());
+    assert!(Record::from_proto_bytes(&[0xff]).is_err());
+    assert!(Record::from_proto(messages::Record { record: None }).is_err());
+    // Blocks without a command are empty blocks.
+    let block = messages::Record {
+        record: Some(messages::RecordKind::Block(messages::Block::default())),
+    };
+    match Record::from_proto(block).unwrap() {
+        Record::Block(block) => assert_eq!(block.command, None),
+        record => panic!("Unexpected record {:?}", record),
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_records_round_trip(records in arb_near_valid_records(4, 6, 3)) {
+        for record in records {
+            let bytes = record.to_proto_bytes();
+            prop_assert_eq!(Record::from_proto_bytes(&bytes).unwrap(), record);
+        }
+    }
+}
+
+#[test]
+fn test_data_sync_round_trip() {
+    use data_sync::*;
+    use simulated_context::SimulatedContext;
+
+    let scenario = scenario::Scenario::from_toml("nodes = 4\nmax_clock = 500\nseed = 2").unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(bft_simulator_runtime::simulator::GlobalTime(500), None);
+    let node = sim.simulated_node(Author(0)).node();
+    let notification = DataSyncNode::<SimulatedContext>::create_notification(node);
+    assert_eq!(
+        DataSyncNotification::from_proto_bytes(&notification.to_proto_bytes()).unwrap(),
+        notification
+    );
+    let mut request = DataSyncNode::<SimulatedContext>::create_request(node);
+    assert_eq!(
+        DataSyncRequest::from_proto_bytes(&request.to_proto_bytes()).unwrap(),
+        request
+    );
+    // Ask for everything.
+    request = DataSyncRequest::from_proto(messages::DataSyncRequest::default()).unwrap();
+    let response = DataSyncNode::<SimulatedContext>::handle_request(node, request);
+    let bytes = response.to_proto_bytes();
+    assert!(!bytes.is_empty());
+    assert_eq!(
+        DataSyncResponse::from_proto_bytes(&bytes).unwrap(),
+        response
+    );
+}
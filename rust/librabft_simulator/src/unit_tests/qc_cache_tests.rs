@@ -0,0 +1,31 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_qc_cache() {
+    let mut cache = QuorumCertificateCache::new(2);
+    let (hash, signature) = (QuorumCertificateHash(1), Signature(1));
+    assert!(!cache.lookup(hash, signature, 5));
+    cache.insert(hash, signature);
+    assert!(cache.lookup(hash, signature, 5));
+    // Entries are keyed by the signature of the QC author as well.
+    assert!(!cache.lookup(hash, Signature(2), 5));
+    cache.insert(hash, signature);
+    assert_eq!(cache.len(), 1);
+    // The oldest QC is evicted first.
+    cache.insert(QuorumCertificateHash(2), Signature(2));
+    cache.insert(QuorumCertificateHash(3), Signature(3));
+    assert_eq!(cache.len(), 2);
+    assert!(!cache.lookup(hash, signature, 5));
+    assert!(cache.lookup(QuorumCertificateHash(3), Signature(3), 5));
+    assert_eq!(
+        cache.stats(),
+        &QuorumCertificateCacheStats {
+            hits: 2,
+            misses: 3,
+            signatures_saved: 10,
+        }
+    );
+}
@@ -0,0 +1,80 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::StopConditions;
+use campaign::run;
+use scenario::Scenario;
+
+#[test]
+fn test_read_modes() {
+    let scenario = Scenario::from_toml("nodes = 4\nmax_clock = 1000\nseed = 1").unwrap();
+    let mut sim = scenario.build_simulator();
+    let node = sim.replicas()[0].node().node_state();
+    let response = node.read_local();
+    assert_eq!((response.state, response.commits), (None, 0));
+
+    sim.run_until(GlobalTime(500), &StopConditions::default());
+    let nodes: Vec<_> = sim.replicas()[..4]
+        .iter()
+        .map(|replica| replica.node().node_state())
+        .collect();
+    let response = nodes[0].read_local();
+    assert!(response.commits > 0);
+    assert_eq!(
+        Some(&response.round),
+        nodes[0].commits().last().map(|c| &c.round)
+    );
+
+    let leader = nodes
+        .iter()
+        .find(|node| node.pacemaker().active_leader() == Some(node.local_author()))
+        .unwrap();
+    let follower = nodes
+        .iter()
+        .find(|node| node.pacemaker().active_leader() != Some(node.local_author()))
+        .unwrap();
+    let clock = NodeTime(500);
+    assert_eq!(
+        follower.read_with_lease(clock, 1000),
+        Err(ReadError::NotLeader(follower.leader_hint()))
+    );
+    assert_eq!(leader.read_with_lease(clock, 1000), Ok(leader.read_local()));
+    assert!(match leader.read_with_lease(clock, 0) {
+        Err(ReadError::LeaseExpired { expiry }) => expiry <= clock,
+        _ => false,
+    });
+
+    let configuration = nodes[0].configuration();
+    let responses: Vec<_> = nodes.iter().map(|node| node.read_local()).collect();
+    assert_eq!(
+        quorum_read(responses[..2].to_vec(), configuration),
+        Err(ReadError::NoQuorum {
+            weight: 2,
+            threshold: 3
+        })
+    );
+    let freshest = responses.iter().map(|response| response.commits).max();
+    assert_eq!(
+        quorum_read(responses.clone(), configuration).map(|response| response.commits),
+        Ok(freshest.unwrap())
+    );
+}
+
+#[test]
+fn test_read_staleness() {
+    let scenario = Scenario::from_toml(include_str!("../../scenarios/reads.toml")).unwrap();
+    let stats = run(&scenario, Some(1)).reads.unwrap();
+    let (local, lease, quorum) = (&stats[0], &stats[1], &stats[2]);
+    assert_eq!(local.consistency, ReadConsistency::Local);
+    for stats in &stats {
+        assert_eq!(stats.reads + stats.failures, local.reads);
+    }
+    // Local reads may hit the validator catching up after its crash.
+    assert_eq!(local.failures, 0);
+    assert!(local.max_staleness > quorum.max_staleness);
+    assert!(local.mean_staleness() > quorum.mean_staleness());
+    // Leaders only serve reads at the beginning of their rounds, but are up to date.
+    assert!(lease.failures > 0);
+    assert!(lease.mean_staleness() <= quorum.mean_staleness());
+}
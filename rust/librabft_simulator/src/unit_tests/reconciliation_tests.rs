@@ -0,0 +1,57 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+// Keys are hashed from their raw bytes rather than their canonical encoding, so that the cells
+// they land in, hence whether small differences decode, do not change with the encoding version.
+fn sketch_of(keys: &[u64], num_cells: usize) -> InvertibleBloomLookupTable {
+    let mut sketch = InvertibleBloomLookupTable::new(num_cells);
+    for key in keys {
+        sketch.insert(stable_hash(&key.to_le_bytes()));
+    }
+    sketch
+}
+
+#[test]
+fn test_decode_difference() {
+    let common: Vec<u64> = (0..100).collect();
+    let mut left = common.clone();
+    left.extend(&[1000, 1001, 1002]);
+    let mut right = common;
+    right.push(2000);
+    let difference = sketch_of(&left, 24)
+        .subtract(&sketch_of(&right, 24))
+        .unwrap();
+    let (mut only_left, only_right) = difference.decode().unwrap();
+    only_left.sort();
+    let mut expected: Vec<_> = [1000u64, 1001, 1002]
+        .iter()
+        .map(|key| stable_hash(&key.to_le_bytes()))
+        .collect();
+    expected.sort();
+    assert_eq!(only_left, expected);
+    assert_eq!(only_right, vec![stable_hash(&2000u64.to_le_bytes())]);
+}
+
+#[test]
+fn test_large_differences_are_not_decoded() {
+    let keys: Vec<u64> = (0..100).collect();
+    let difference = sketch_of(&keys, 12).subtract(&sketch_of(&[], 12)).unwrap();
+    assert!(difference.decode().is_none());
+    // Tables of different sizes cannot be compared.
+    assert!(sketch_of(&keys, 12)
+        .subtract(&sketch_of(&keys, 24))
+        .is_none());
+}
+
+#[test]
+fn test_efficiency() {
+    let mut stats = ReconciliationStats::default();
+    assert_eq!(stats.efficiency(), None);
+    stats.add(&ReconciliationStats {
+        records_received: 4,
+        records_missing: 3,
+    });
+    assert_eq!(stats.efficiency(), Some(0.75));
+}
@@ -0,0 +1,116 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::sync::Mutex;
+
+#[test]
+fn test_record_arena() {
+    let mut arena = RecordArena::default();
+    assert!(arena.is_empty());
+    assert!(arena.insert(7, "b").unwrap());
+    assert!(arena.insert(3, "a").unwrap());
+    // Records are never replaced.
+    assert!(!arena.insert(7, "c").unwrap());
+    assert_eq!(arena.len(), 2);
+    assert!(arena.contains_key(&3));
+    assert_eq!(arena.get(&7), Some(&"b"));
+    assert_eq!(arena.get(&4), None);
+    assert_eq!(arena.keys().cloned().collect::<Vec<_>>(), vec![7, 3]);
+
+    // Clones share the records inserted so far.
+    let mut clone = arena.clone();
+    assert!(Arc::ptr_eq(
+        arena.slots[0].1.get().unwrap(),
+        clone.slots[0].1.get().unwrap()
+    ));
+    clone.insert(5, "d").unwrap();
+    assert_eq!(clone.len(), 3);
+    assert_eq!(arena.len(), 2);
+    assert_eq!(
+        clone
+            .iter()
+            .map(|(hash, record)| (*hash, *record))
+            .collect::<Vec<_>>(),
+        vec![(7, "b"), (3, "a"), (5, "d")]
+    );
+}
+
+/// A backend in memory that counts the records read back, and fails to save records once
+/// `full` is set.
+#[derive(Debug, Default)]
+struct TestBackend {
+    records: Mutex<HashMap<u64, String>>,
+    loads: AtomicUsize,
+    full: std::sync::atomic::AtomicBool,
+}
+
+impl RecordBackend<u64, String> for TestBackend {
+    fn store(&self, hash: &u64, record: &String) -> Result<()> {
+        if self.full.load(Ordering::Relaxed) {
+            bail!("The backend is full");
+        }
+        self.records.lock().unwrap().insert(*hash, record.clone());
+        Ok(())
+    }
+
+    fn load(&self, hash: &u64) -> Result<String> {
+        self.loads.fetch_add(1, Ordering::Relaxed);
+        match self.records.lock().unwrap().get(hash) {
+            Some(record) => Ok(record.clone()),
+            None => bail!("Unknown record"),
+        }
+    }
+}
+
+#[test]
+fn test_record_arena_with_backend() {
+    let backend = Arc::new(TestBackend::default());
+    let mut arena = RecordArena::with_backend(backend.clone(), 2);
+    for hash in 0..5 {
+        assert!(arena.insert(hash, hash.to_string()).unwrap());
+    }
+    assert!(!arena.insert(3, "x".to_string()).unwrap());
+    // Every record is saved, and only the newest ones stay in memory.
+    assert_eq!(backend.records.lock().unwrap().len(), 5);
+    assert_eq!(arena.resident(), 2);
+    assert_eq!(arena.get(&4).unwrap(), "4");
+    assert_eq!(backend.loads.load(Ordering::Relaxed), 0);
+    // Evicted records are read again once, then kept until the next insertion.
+    assert_eq!(arena.get(&0).unwrap(), "0");
+    assert_eq!(arena.get_slot(0).unwrap(), "0");
+    assert_eq!(backend.loads.load(Ordering::Relaxed), 1);
+    assert_eq!(arena.resident(), 3);
+    // Iterating reads the other evicted records without keeping them in memory.
+    let values: Vec<_> = arena.values().map(|record| (*record).clone()).collect();
+    assert_eq!(values, vec!["0", "1", "2", "3", "4"]);
+    assert_eq!(backend.loads.load(Ordering::Relaxed), 3);
+    assert_eq!(arena.resident(), 3);
+    assert_eq!(arena.iter().count(), 5);
+    assert_eq!(arena.resident(), 3);
+    arena.insert(5, "5".to_string()).unwrap();
+    assert_eq!(arena.resident(), 2);
+    assert_eq!(arena.get(&5).unwrap(), "5");
+    // Clones share the backend.
+    let clone = arena.clone();
+    assert_eq!(clone.get(&1).unwrap(), "1");
+    assert_eq!(clone.resident(), 3);
+    assert_eq!(arena.resident(), 2);
+}
+
+#[test]
+fn test_record_arena_backend_failures() {
+    let backend = Arc::new(TestBackend::default());
+    let mut arena = RecordArena::with_backend(backend.clone(), 1);
+    arena.insert(0, "0".to_string()).unwrap();
+    arena.insert(1, "1".to_string()).unwrap();
+    // A record that the backend fails to save is not inserted.
+    backend.full.store(true, Ordering::Relaxed);
+    assert!(arena.insert(2, "2".to_string()).is_err());
+    assert!(!arena.contains_key(&2));
+    assert_eq!(arena.len(), 2);
+    // An evicted record that the backend fails to read is missing.
+    backend.records.lock().unwrap().remove(&0);
+    assert_eq!(arena.get(&0), None);
+    assert_eq!(arena.values().count(), 1);
+}
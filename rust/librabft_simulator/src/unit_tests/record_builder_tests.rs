@@ -0,0 +1,139 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use hashing::Fnv1a;
+
+#[test]
+fn test_build_matches_constructors() {
+    let command = Command {
+        proposer: Author(1),
+        index: 2,
+    };
+    assert_eq!(
+        RecordBuilder::block(Author(2))
+            .epoch(EpochId(1))
+            .command(command.clone())
+            .time(NodeTime(2))
+            .previous_quorum_certificate(QuorumCertificateHash(47))
+            .round(Round(3))
+            .build(),
+        Record::make_block(
+            EpochId(1),
+            Some(command),
+            NodeTime(2),
+            QuorumCertificateHash(47),
+            Round(3),
+            Author(2),
+            ChainId(0),
+            &Fnv1a,
+        )
+    );
+    assert_eq!(
+        RecordBuilder::vote(Author(1))
+            .epoch(EpochId(1))
+            .round(Round(3))
+            .certified_block(BlockHash(5))
+            .state(State(6))
+            .committed_state(Some(State(4)))
+            .build(),
+        Record::make_vote(
+            EpochId(1),
+            Round(3),
+            BlockHash(5),
+            State(6),
+            Author(1),
+            Some(State(4)),
+            None,
+            NodeTime(1),
+            ChainId(0),
+            &Fnv1a,
+        )
+    );
+    assert_eq!(
+        RecordBuilder::timeout(Author(1))
+            .epoch(EpochId(1))
+            .round(Round(3))
+            .highest_certified_block_round(Round(2))
+            // Ignored for timeouts.
+            .state(State(6))
+            .build(),
+        Record::make_timeout(
+            EpochId(1),
+            Round(3),
+            Round(2),
+            Author(1),
+            ChainId(0),
+            &Fnv1a
+        )
+    );
+}
+
+#[test]
+fn test_build_quorum_certificates() {
+    let configuration = EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect());
+    let mut builder = RecordBuilder::quorum_certificate(Author(0));
+    builder
+        .voters((0..3).map(Author))
+        .epoch(EpochId(1))
+        .round(Round(3))
+        .certified_block(BlockHash(47))
+        .state(State(5))
+        .committed_state(Some(State(4)));
+    let qc = match builder.build() {
+        Record::QuorumCertificate(qc) => qc,
+        _ => unreachable!(),
+    };
+    // Votes are signed for the final content of the QC, whatever the order of the setters.
+    assert_eq!(
+        verify_commit_certificate(&qc, &configuration, ChainId(0), &Fnv1a),
+        Ok(CommittedState {
+            epoch_id: EpochId(1),
+            certified_round: Round(3),
+            state: State(4),
+            next_epoch: None,
+        })
+    );
+
+    builder.votes(vec![(Author(3), NodeTime(1), Signature(0))]);
+    let qc = match builder.build() {
+        Record::QuorumCertificate(qc) => qc,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        verify_quorum_certificate(&qc, &configuration, ChainId(0), &Fnv1a),
+        Err(VerifyError::InvalidSignature(Author(3)))
+    );
+}
+
+#[test]
+fn test_unchecked_records() {
+    let mut builder = RecordBuilder::vote(Author(1));
+    builder.round(Round(3)).signature(Signature(7));
+    let vote = builder.unchecked();
+    assert_eq!(vote.signature(), Signature(7));
+    assert!(vote
+        .signature()
+        .check(vote.signing_hash(EpochId(0), ChainId(0), &Fnv1a), Author(1))
+        .is_err());
+    // Building the same record signs it again.
+    let vote = builder.build();
+    assert!(vote
+        .signature()
+        .check(vote.signing_hash(EpochId(0), ChainId(0), &Fnv1a), Author(1))
+        .is_ok());
+
+    // A QC without votes is accepted by the builder but not by verification.
+    let qc = match RecordBuilder::quorum_certificate(Author(0)).unchecked() {
+        Record::QuorumCertificate(qc) => qc,
+        _ => unreachable!(),
+    };
+    let configuration = EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect());
+    assert_eq!(
+        verify_quorum_certificate(&qc, &configuration, ChainId(0), &Fnv1a),
+        Err(VerifyError::InsufficientQuorum {
+            weight: 0,
+            threshold: 3
+        })
+    );
+}
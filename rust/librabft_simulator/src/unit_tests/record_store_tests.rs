@@ -2,6 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use hashing::Fnv1a;
+use pacemaker::PacemakerState;
+use qc_cache::QuorumCertificateCache;
+use record_builder::RecordBuilder;
 use simulated_context::SimulatedContext;
 use smr_context::*;
 
@@ -37,7 +41,7 @@ impl SharedRecordStore {
         }
     }
 
-    fn create_timeout(&mut self, author_id: usize, round: Round) {
+    fn create_timeout(&mut self, author_id: usize, round: Round) -> NodeResult<()> {
         let author = Author(author_id);
         self.store
             .create_timeout(author, round, self.contexts.get_mut(&author).unwrap())
@@ -50,48 +54,61 @@ impl SharedRecordStore {
         clock: NodeTime,
     ) {
         let author = Author(author_id);
-        self.store.propose_block(
-            author,
-            previous_qc_hash,
-            clock,
-            self.contexts.get_mut(&author).unwrap(),
-        );
+        let context = self.contexts.get_mut(&author).unwrap();
+        let command = context.fetch().unwrap();
+        self.store
+            .propose_block(author, previous_qc_hash, clock, Some(command), context)
+            .unwrap();
     }
 
-    fn create_vote(&mut self, author_id: usize, block_hash: BlockHash) -> bool {
+    fn create_vote(&mut self, author_id: usize, block_hash: BlockHash) -> NodeResult<()> {
         let author = Author(author_id);
-        self.store
-            .create_vote(author, block_hash, self.contexts.get_mut(&author).unwrap())
+        self.store.create_vote(
+            author,
+            block_hash,
+            NodeTime(0),
+            self.contexts.get_mut(&author).unwrap(),
+        )
     }
 
     fn check_for_new_quorum_certificate(&mut self) -> bool {
         let author = self.leader(self.store.current_round());
         self.store
             .check_for_new_quorum_certificate(author, self.contexts.get_mut(&author).unwrap())
+            .unwrap()
     }
 
     fn leader(&self, round: Round) -> Author {
         PacemakerState::leader(&self.store, round)
     }
 
+    /// The block proposed by the leader of the current round.
+    fn proposed_hash(&self) -> BlockHash {
+        self.store.current_proposals[&self.leader(self.store.current_round())]
+    }
+
     fn make_round(&mut self, clock: NodeTime) {
-        let author = self.leader(self.store.current_round());
-        let previous_qc_hash = self.store.highest_quorum_certificate_hash();
-        self.store.propose_block(
-            author,
-            previous_qc_hash,
-            clock,
-            self.contexts.get_mut(&author).unwrap(),
-        );
-        let proposed_hash = self.store.current_proposed_block.unwrap();
         let threshold = self
             .contexts
             .get(&Author(0))
             .unwrap()
             .configuration(&self.store.initial_state)
             .quorum_threshold();
-        for i in 0..threshold {
-            assert!(self.create_vote(i, proposed_hash));
+        self.make_round_with_votes(clock, threshold);
+    }
+
+    /// Make a round where the first `num_votes` nodes vote.
+    fn make_round_with_votes(&mut self, clock: NodeTime, num_votes: usize) {
+        let author = self.leader(self.store.current_round());
+        let previous_qc_hash = self.store.highest_quorum_certificate_hash();
+        let context = self.contexts.get_mut(&author).unwrap();
+        let command = context.fetch().unwrap();
+        self.store
+            .propose_block(author, previous_qc_hash, clock, Some(command), context)
+            .unwrap();
+        let proposed_hash = self.proposed_hash();
+        for i in 0..num_votes {
+            self.create_vote(i, proposed_hash).unwrap();
         }
         assert!(self.check_for_new_quorum_certificate());
     }
@@ -105,7 +122,7 @@ impl SharedRecordStore {
             .quorum_threshold();
         let round = self.store.current_round();
         for i in 0..threshold {
-            self.create_timeout(i, round);
+            self.create_timeout(i, round).unwrap();
         }
     }
 }
@@ -133,9 +150,12 @@ fn test_propose_and_vote_no_qc() {
     shared_store.propose_block(0, QuorumCertificateHash(0), NodeTime(1));
     shared_store.propose_block(1, QuorumCertificateHash(0), NodeTime(2));
     let block_hashes: Vec<_> = shared_store.store.blocks.keys().cloned().collect();
-    assert!(shared_store.create_vote(0, block_hashes[0]));
-    assert!(shared_store.create_vote(0, block_hashes[0]));
-    assert!(shared_store.create_vote(1, block_hashes[1]));
+    shared_store.create_vote(0, block_hashes[0]).unwrap();
+    assert_eq!(
+        shared_store.create_vote(0, block_hashes[0]),
+        Err(NodeError::Validation(RejectReason::Duplicate))
+    );
+    shared_store.create_vote(1, block_hashes[1]).unwrap();
     assert!(!shared_store.check_for_new_quorum_certificate());
     // We should count only one vote per author, hence no QC.
     let store = &shared_store.store;
@@ -157,9 +177,9 @@ fn test_vote_with_quorum() {
     let mut shared_store = SharedRecordStore::new(2, 20);
     shared_store.propose_block(0, QuorumCertificateHash(0), NodeTime(1));
     shared_store.propose_block(1, QuorumCertificateHash(0), NodeTime(2));
-    let proposed_hash = shared_store.store.current_proposed_block.unwrap();
-    assert!(shared_store.create_vote(0, proposed_hash));
-    assert!(shared_store.create_vote(1, proposed_hash));
+    let proposed_hash = shared_store.proposed_hash();
+    shared_store.create_vote(0, proposed_hash).unwrap();
+    shared_store.create_vote(1, proposed_hash).unwrap();
     assert!(shared_store.check_for_new_quorum_certificate());
     let store = &shared_store.store;
     assert_eq!(store.blocks.len(), 2);
@@ -175,9 +195,9 @@ fn test_vote_with_quorum() {
 fn test_timeouts_no_tc() {
     let mut shared_store = SharedRecordStore::new(2, 20);
     shared_store.propose_block(1, QuorumCertificateHash(0), NodeTime(2));
-    shared_store.create_timeout(0, Round(1));
-    shared_store.create_timeout(0, Round(1));
-    shared_store.create_timeout(1, Round(0));
+    let _ = shared_store.create_timeout(0, Round(1));
+    let _ = shared_store.create_timeout(0, Round(1));
+    let _ = shared_store.create_timeout(1, Round(0));
     // We should count only one timeout per author, at the current round, hence no TC.
     let store = &shared_store.store;
     assert_eq!(store.blocks.len(), 1);
@@ -197,10 +217,10 @@ fn test_timeouts_no_tc() {
 fn test_timeouts_with_tc() {
     let mut shared_store = SharedRecordStore::new(2, 20);
     shared_store.propose_block(1, QuorumCertificateHash(0), NodeTime(2));
-    shared_store.create_timeout(1, Round(0)); // should be ignored
-    shared_store.create_timeout(0, Round(1));
-    shared_store.create_timeout(1, Round(1)); // complete TC
-    shared_store.create_timeout(1, Round(2)); // single timeout
+    let _ = shared_store.create_timeout(1, Round(0)); // should be ignored
+    let _ = shared_store.create_timeout(0, Round(1));
+    let _ = shared_store.create_timeout(1, Round(1)); // complete TC
+    let _ = shared_store.create_timeout(1, Round(2)); // single timeout
     {
         let store = &shared_store.store;
         assert_eq!(store.blocks.len(), 1);
@@ -215,7 +235,7 @@ fn test_timeouts_with_tc() {
         assert_eq!(store.current_round(), Round(2));
         assert_eq!(store.current_timeouts.len(), 1);
     }
-    shared_store.create_timeout(0, Round(2)); // complete TC
+    let _ = shared_store.create_timeout(0, Round(2)); // complete TC
     let store = &shared_store.store;
     assert_eq!(store.blocks.len(), 1);
     assert_eq!(store.highest_timeout_certificate_round(), Round(2));
@@ -223,6 +243,739 @@ fn test_timeouts_with_tc() {
     assert_eq!(store.current_timeouts.len(), 0);
 }
 
+#[test]
+fn test_no_timeout_replay_across_epochs() {
+    let mut context = SimulatedContext::new(Author(0), 2, 20);
+    let state = context.last_committed_state();
+    let configuration = context.configuration(&state);
+    let mut store =
+        RecordStoreState::new(EpochId(1).initial_hash(), state, EpochId(1), configuration);
+    for index in 0..2 {
+        // Same authors and round, but signed in epoch 0.
+        let timeout = Record::make_timeout(
+            EpochId(0),
+            Round(1),
+            Round(0),
+            Author(index),
+            ChainId(0),
+            &Fnv1a,
+        );
+        assert_eq!(
+            store.insert_network_record(timeout.clone(), &mut context),
+            Err(RejectReason::PastEpoch)
+        );
+        let mut replayed = timeout;
+        if let Record::Timeout(x) = &mut replayed {
+            x.epoch_id = EpochId(1);
+        }
+        assert_eq!(
+            store.insert_network_record(replayed, &mut context),
+            Err(RejectReason::BadSignature)
+        );
+    }
+    assert_eq!(store.current_timeouts.len(), 0);
+    assert_eq!(store.current_round(), Round(1));
+    for index in 0..2 {
+        let timeout = Record::make_timeout(
+            EpochId(1),
+            Round(1),
+            Round(0),
+            Author(index),
+            ChainId(0),
+            &Fnv1a,
+        );
+        assert!(store.insert_network_record(timeout, &mut context).is_ok());
+    }
+    assert_eq!(store.highest_timeout_certificate_round(), Round(1));
+    assert_eq!(store.current_round(), Round(2));
+}
+
+#[test]
+fn test_no_replay_across_chains() {
+    let mut context = SimulatedContext::new(Author(0), 2, 20);
+    let state = context.last_committed_state();
+    let configuration = context.configuration(&state);
+    let mut store =
+        RecordStoreState::new(QuorumCertificateHash(0), state, EpochId(0), configuration);
+    store.set_chain_id(ChainId(1));
+    // Records of another network running the same epoch with the same authors.
+    let block = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(0),
+        Round(1),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(block, &mut context),
+        Err(RejectReason::BadSignature)
+    );
+    let timeout = Record::make_timeout(
+        EpochId(0),
+        Round(1),
+        Round(0),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(timeout, &mut context),
+        Err(RejectReason::BadSignature)
+    );
+    assert_eq!(store.current_timeouts.len(), 0);
+    let block = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(0),
+        Round(1),
+        Author(1),
+        ChainId(1),
+        &Fnv1a,
+    );
+    assert!(store.insert_network_record(block, &mut context).is_ok());
+}
+
+#[test]
+fn test_no_block_replay_across_epochs() {
+    let mut context = SimulatedContext::new(Author(0), 2, 20);
+    let state = context.last_committed_state();
+    let configuration = context.configuration(&state);
+    let initial_hash = EpochId(1).initial_hash();
+    let mut store = RecordStoreState::new(initial_hash, state, EpochId(1), configuration);
+    // Blocks do not carry an epoch, so only the signature tells replayed blocks apart.
+    let replayed = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        initial_hash,
+        Round(1),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(replayed, &mut context),
+        Err(RejectReason::BadSignature)
+    );
+    let block = Record::make_block(
+        EpochId(1),
+        None,
+        NodeTime(1),
+        initial_hash,
+        Round(1),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert!(store.insert_network_record(block, &mut context).is_ok());
+}
+
+#[test]
+fn test_signatures_use_the_keys_of_the_epoch() {
+    let mut context = SimulatedContext::new(Author(0), 2, 20);
+    let state = context.last_committed_state();
+    let configuration = context
+        .configuration(&state)
+        .with_key_versions(vec![(Author(1), 1)].into_iter().collect());
+    let mut store =
+        RecordStoreState::new(QuorumCertificateHash(0), state, EpochId(0), configuration);
+    let key = PublicKey::initial(Author(1));
+    assert_eq!(store.public_key(Author(1)), key.rotated());
+    // Signed with the key that was replaced.
+    let block = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(0),
+        Round(1),
+        key,
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(block, &mut context),
+        Err(RejectReason::BadSignature)
+    );
+    let block = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(0),
+        Round(1),
+        key.rotated(),
+        ChainId(0),
+        &Fnv1a,
+    );
+    let block_hash = BlockHash(block.digest(&Fnv1a));
+    assert!(store.insert_network_record(block, &mut context).is_ok());
+    // Local records are signed with the new key, so that QCs verify against the configuration.
+    store
+        .create_vote(Author(1), block_hash, NodeTime(2), &mut context)
+        .unwrap();
+    store
+        .create_vote(Author(0), block_hash, NodeTime(2), &mut context)
+        .unwrap();
+    assert!(store
+        .check_for_new_quorum_certificate(Author(1), &mut context)
+        .unwrap());
+    let qc = store.highest_quorum_certificate().unwrap();
+    assert!(verify_quorum_certificate(qc, store.configuration(), ChainId(0), &Fnv1a).is_ok());
+    assert!(verify_quorum_certificate(
+        qc,
+        &EpochConfiguration::new(store.configuration().voting_rights().clone()),
+        ChainId(0),
+        &Fnv1a
+    )
+    .is_err());
+}
+
+#[test]
+fn test_records_of_removed_validators() {
+    let mut context = SimulatedContext::new(Author(0), 4, 20);
+    context.set_voting_rights(vec![1, 1, 1, 0]);
+    let state = context.last_committed_state();
+    let configuration = context.configuration(&state);
+    let mut store =
+        RecordStoreState::new(QuorumCertificateHash(0), state, EpochId(0), configuration);
+    let make_block = |author| {
+        Record::make_block(
+            EpochId(0),
+            None,
+            NodeTime(1),
+            QuorumCertificateHash(0),
+            Round(1),
+            Author(author),
+            ChainId(0),
+            &Fnv1a,
+        )
+    };
+    assert_eq!(
+        store.insert_network_record(make_block(3), &mut context),
+        Err(RejectReason::UnknownAuthor)
+    );
+    let block = make_block(0);
+    let block_hash = BlockHash(block.digest(&Fnv1a));
+    assert!(store.insert_network_record(block, &mut context).is_ok());
+    store
+        .create_vote(Author(0), block_hash, NodeTime(2), &mut context)
+        .unwrap();
+    // The removed validator may still sign votes and timeouts, but nobody accepts them.
+    let vote = store.current_vote(Author(0)).unwrap().clone();
+    let vote = Record::make_vote(
+        EpochId(0),
+        Round(1),
+        block_hash,
+        vote.state,
+        Author(3),
+        vote.committed_state,
+        vote.next_epoch,
+        vote.time,
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(vote, &mut context),
+        Err(RejectReason::UnknownAuthor)
+    );
+    let timeout = Record::make_timeout(
+        EpochId(0),
+        Round(1),
+        Round(0),
+        Author(3),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(timeout, &mut context),
+        Err(RejectReason::UnknownAuthor)
+    );
+    // Quorums need the three remaining votes.
+    store
+        .create_vote(Author(1), block_hash, NodeTime(2), &mut context)
+        .unwrap();
+    assert!(!store
+        .check_for_new_quorum_certificate(Author(0), &mut context)
+        .unwrap());
+    store
+        .create_vote(Author(2), block_hash, NodeTime(2), &mut context)
+        .unwrap();
+    assert!(store
+        .check_for_new_quorum_certificate(Author(0), &mut context)
+        .unwrap());
+    let qc = store.highest_quorum_certificate().unwrap();
+    assert!(verify_quorum_certificate(qc, store.configuration(), ChainId(0), &Fnv1a).is_ok());
+}
+
+#[test]
+fn test_quorum_certificates_announce_the_executed_epoch_change() {
+    let mut shared_store = SharedRecordStore::new(7, 100);
+    for i in 0..3 {
+        shared_store.make_round_with_votes(NodeTime(i + 1), 7);
+    }
+    let leader = shared_store.leader(shared_store.store.current_round());
+    let previous_qc_hash = shared_store.store.highest_quorum_certificate_hash();
+    shared_store.propose_block(leader.0, previous_qc_hash, NodeTime(4));
+    let proposed_hash = shared_store.proposed_hash();
+    shared_store.create_vote(leader.0, proposed_hash).unwrap();
+    let vote = shared_store.store.current_vote(leader).unwrap().clone();
+    assert!(vote.committed_state.is_some());
+    assert_eq!(vote.next_epoch, None);
+    // Two validators announce an epoch change that the leader does not execute.
+    let configuration = shared_store.store.configuration().clone();
+    let others: Vec<_> = (0..7)
+        .map(Author)
+        .filter(|author| *author != leader)
+        .collect();
+    for author in &others[..2] {
+        let forged = Record::make_vote(
+            EpochId(0),
+            vote.round,
+            proposed_hash,
+            vote.state.clone(),
+            *author,
+            vote.committed_state.clone(),
+            Some(EpochChange::new(EpochId(1), &configuration)),
+            vote.time,
+            ChainId(0),
+            &Fnv1a,
+        );
+        let context = shared_store.contexts.get_mut(author).unwrap();
+        assert!(shared_store
+            .store
+            .insert_network_record(forged, context)
+            .is_ok());
+    }
+    // The block wins the election, but the leader waits for a quorum of matching votes.
+    for author in &others[2..5] {
+        shared_store.create_vote(author.0, proposed_hash).unwrap();
+        assert!(!shared_store.check_for_new_quorum_certificate());
+    }
+    shared_store
+        .create_vote(others[5].0, proposed_hash)
+        .unwrap();
+    assert!(shared_store.check_for_new_quorum_certificate());
+    let qc = shared_store.store.highest_quorum_certificate().unwrap();
+    assert_eq!(qc.next_epoch, None);
+    assert_eq!(qc.votes.len(), 5);
+}
+
+#[test]
+fn test_outdated_records() {
+    let mut shared_store = SharedRecordStore::new(3, 20);
+    shared_store.propose_block(1, QuorumCertificateHash(0), NodeTime(2));
+    let _ = shared_store.create_timeout(0, Round(1));
+    let store = &shared_store.store;
+    let block = Record::Block((*store.blocks.values().next().unwrap()).clone());
+    assert_eq!(store.outdated(&block), Some(RejectReason::Duplicate));
+    let timeout = |round, author| {
+        Record::make_timeout(
+            EpochId(0),
+            Round(round),
+            Round(0),
+            Author(author),
+            ChainId(0),
+            &Fnv1a,
+        )
+    };
+    assert_eq!(
+        store.outdated(&timeout(1, 0)),
+        Some(RejectReason::Duplicate)
+    );
+    assert_eq!(
+        store.outdated(&timeout(2, 1)),
+        Some(RejectReason::FutureRound)
+    );
+    assert_eq!(store.outdated(&timeout(1, 1)), None);
+}
+
+#[test]
+fn test_reject_reasons() {
+    let mut context = SimulatedContext::new(Author(0), 2, 20);
+    let state = context.last_committed_state();
+    let configuration = context.configuration(&state);
+    let mut store =
+        RecordStoreState::new(QuorumCertificateHash(0), state, EpochId(0), configuration);
+    let orphan = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(42),
+        Round(1),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(orphan, &mut context),
+        Err(RejectReason::UnknownParent)
+    );
+    let mut forged = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(0),
+        Round(1),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    if let Record::Block(block) = &mut forged {
+        block.author = Author(0);
+    }
+    assert_eq!(
+        store.insert_network_record(forged, &mut context),
+        Err(RejectReason::BadSignature)
+    );
+    let first = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(1),
+        QuorumCertificateHash(0),
+        Round(0),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(first, &mut context),
+        Err(RejectReason::Invalid("Rounds must start at 1"))
+    );
+    let stale = Record::make_timeout(
+        EpochId(0),
+        Round(0),
+        Round(0),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(stale, &mut context),
+        Err(RejectReason::StaleRound)
+    );
+    let future = Record::make_timeout(
+        EpochId(1),
+        Round(1),
+        Round(0),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_eq!(
+        store.insert_network_record(future, &mut context),
+        Err(RejectReason::FutureEpoch)
+    );
+}
+
+#[test]
+fn test_malformed_record_matrix() {
+    let mut shared_store = SharedRecordStore::new(4, 20);
+    shared_store.make_round(NodeTime(10));
+    shared_store.make_round(NodeTime(20));
+    let leader = shared_store.leader(Round(3));
+    let qc_hash = shared_store.store.highest_quorum_certificate_hash();
+    shared_store.propose_block(leader.0, qc_hash, NodeTime(30));
+    let _ = shared_store.create_timeout(0, Round(3));
+    let block_hash = shared_store.proposed_hash();
+    let previous_block_hash = shared_store
+        .store
+        .quorum_certificate(qc_hash)
+        .unwrap()
+        .certified_block_hash;
+    shared_store.store.set_max_clock_drift(100);
+    shared_store.store.update_clock(NodeTime(50));
+    shared_store.store.set_initial_time(NodeTime(5));
+    let committed_state = shared_store.store.vote_committed_state(block_hash);
+    let other = Author((leader.0 + 1) % 4);
+
+    let block = || {
+        let mut builder = RecordBuilder::block(other);
+        builder
+            .round(Round(4))
+            .time(NodeTime(40))
+            .previous_quorum_certificate(qc_hash);
+        builder
+    };
+    let vote = || {
+        let mut builder = RecordBuilder::vote(Author(1));
+        builder
+            .round(Round(3))
+            .time(NodeTime(31))
+            .certified_block(block_hash)
+            .committed_state(committed_state.clone());
+        builder
+    };
+    let quorum_certificate = || {
+        let mut builder = RecordBuilder::quorum_certificate(leader);
+        builder
+            .round(Round(3))
+            .time(NodeTime(31))
+            .certified_block(block_hash)
+            .committed_state(committed_state.clone())
+            .voters((0..3).map(Author));
+        builder
+    };
+    let timeout = || {
+        let mut builder = RecordBuilder::timeout(Author(1));
+        builder
+            .round(Round(3))
+            .highest_certified_block_round(Round(2));
+        builder
+    };
+    let known_block = Record::Block(shared_store.store.block(block_hash).unwrap().clone());
+    let cases = vec![
+        (
+            "block with a bad signature",
+            block().unchecked(),
+            RejectReason::BadSignature,
+        ),
+        (
+            "block signed for another epoch",
+            block().epoch(EpochId(1)).build(),
+            RejectReason::BadSignature,
+        ),
+        (
+            "block extending an unknown QC",
+            block()
+                .previous_quorum_certificate(QuorumCertificateHash(42))
+                .build(),
+            RejectReason::UnknownParent,
+        ),
+        (
+            "block extending the initial hash of another epoch",
+            block()
+                .previous_quorum_certificate(EpochId(1).initial_hash())
+                .build(),
+            RejectReason::UnknownParent,
+        ),
+        (
+            "block at round 0",
+            block()
+                .previous_quorum_certificate(QuorumCertificateHash(0))
+                .round(Round(0))
+                .build(),
+            RejectReason::Invalid("Rounds must start at 1"),
+        ),
+        (
+            "block at the round of its parent",
+            block().round(Round(2)).build(),
+            RejectReason::Invalid("Rounds must be increasing"),
+        ),
+        (
+            "block earlier than its parent",
+            block().time(NodeTime(20)).build(),
+            RejectReason::Invalid("Block times must be increasing"),
+        ),
+        (
+            "first block of the epoch earlier than the initial time",
+            block()
+                .previous_quorum_certificate(EpochId(0).initial_hash())
+                .time(NodeTime(4))
+                .build(),
+            RejectReason::Invalid(
+                "Block times must not be earlier than the quorum time of their parent",
+            ),
+        ),
+        (
+            "block in the far future",
+            block().time(NodeTime(151)).build(),
+            RejectReason::FutureTime,
+        ),
+        ("known block", known_block, RejectReason::Duplicate),
+        (
+            "vote for an unknown block",
+            vote().certified_block(BlockHash(42)).build(),
+            RejectReason::UnknownParent,
+        ),
+        (
+            "vote of a future epoch",
+            vote().epoch(EpochId(1)).build(),
+            RejectReason::FutureEpoch,
+        ),
+        (
+            "vote at another round than its block",
+            vote().certified_block(previous_block_hash).build(),
+            RejectReason::Invalid("The round of the vote must match the certified block"),
+        ),
+        (
+            "vote with a wrong committed state",
+            vote().committed_state(Some(State(7))).build(),
+            RejectReason::Invalid(
+                "The committed_state value of a vote must follow the commit rule",
+            ),
+        ),
+        (
+            "vote at a past round",
+            vote().round(Round(2)).build(),
+            RejectReason::StaleRound,
+        ),
+        (
+            "vote at a future round",
+            vote().round(Round(4)).build(),
+            RejectReason::FutureRound,
+        ),
+        (
+            "vote not later than its block",
+            vote().time(NodeTime(30)).build(),
+            RejectReason::Invalid("Votes must be later than the certified block"),
+        ),
+        (
+            "vote in the far future",
+            vote().time(NodeTime(151)).build(),
+            RejectReason::FutureTime,
+        ),
+        (
+            "vote with a bad signature",
+            vote().unchecked(),
+            RejectReason::BadSignature,
+        ),
+        (
+            "QC for an unknown block",
+            quorum_certificate().certified_block(BlockHash(42)).build(),
+            RejectReason::UnknownParent,
+        ),
+        (
+            "QC of a future epoch",
+            quorum_certificate().epoch(EpochId(1)).build(),
+            RejectReason::FutureEpoch,
+        ),
+        (
+            "QC at another round than its block",
+            quorum_certificate().round(Round(4)).build(),
+            RejectReason::Invalid("The round of the QC must match the certified block"),
+        ),
+        (
+            "QC by another author than the block",
+            quorum_certificate().author(other).build(),
+            RejectReason::Invalid("QCs must be created by the author of the certified block"),
+        ),
+        (
+            "QC with a wrong committed state",
+            quorum_certificate().committed_state(None).build(),
+            RejectReason::Invalid("The committed_state value of a QC must follow the commit rule"),
+        ),
+        (
+            "QC with votes not later than its block",
+            quorum_certificate().time(NodeTime(30)).build(),
+            RejectReason::Invalid("Votes in QCs must be later than the certified block"),
+        ),
+        (
+            "QC with duplicate votes",
+            quorum_certificate().voters(vec![Author(0)]).build(),
+            RejectReason::Invalid("Votes in QCs must have distinct authors"),
+        ),
+        (
+            "QC with a forged vote",
+            quorum_certificate()
+                .votes(vec![(Author(3), NodeTime(31), Signature(0))])
+                .build(),
+            RejectReason::BadSignature,
+        ),
+        (
+            "QC without a quorum",
+            RecordBuilder::quorum_certificate(leader)
+                .round(Round(3))
+                .time(NodeTime(31))
+                .certified_block(block_hash)
+                .committed_state(committed_state.clone())
+                .voters(vec![Author(0), Author(1)])
+                .build(),
+            RejectReason::Invalid("Votes in QCs must form a quorum"),
+        ),
+        (
+            "QC with a bad signature",
+            quorum_certificate().unchecked(),
+            RejectReason::BadSignature,
+        ),
+        (
+            "QC with a wrong execution state",
+            quorum_certificate().state(State(7)).build(),
+            RejectReason::StateMismatch,
+        ),
+        (
+            "timeout of a future epoch",
+            timeout().epoch(EpochId(1)).build(),
+            RejectReason::FutureEpoch,
+        ),
+        (
+            "timeout certifying its own round",
+            timeout().highest_certified_block_round(Round(3)).build(),
+            RejectReason::Invalid("Timeouts must refer to a certified block round below their own"),
+        ),
+        (
+            "timeout at a past round",
+            timeout().round(Round(2)).build(),
+            RejectReason::StaleRound,
+        ),
+        (
+            "timeout at a future round",
+            timeout()
+                .round(Round(4))
+                .highest_certified_block_round(Round(2))
+                .build(),
+            RejectReason::FutureRound,
+        ),
+        (
+            "second timeout of an author",
+            timeout().author(Author(0)).build(),
+            RejectReason::Duplicate,
+        ),
+        (
+            "timeout with a bad signature",
+            timeout().unchecked(),
+            RejectReason::BadSignature,
+        ),
+    ];
+    for (name, record, reason) in cases {
+        let mut store = shared_store.store.clone();
+        let mut context = shared_store.contexts[&Author(0)].clone();
+        assert_eq!(
+            store.insert_network_record(record, &mut context),
+            Err(reason),
+            "{}",
+            name
+        );
+    }
+    // The well-formed versions of the records above are accepted.
+    let mut context = shared_store.contexts[&Author(0)].clone();
+    let state = shared_store
+        .store
+        .compute_state(block_hash, &mut context)
+        .unwrap();
+    for record in [
+        block().build(),
+        block().time(NodeTime(150)).build(),
+        vote().build(),
+        vote().time(NodeTime(150)).build(),
+        quorum_certificate().state(state).build(),
+        timeout().build(),
+    ] {
+        let mut store = shared_store.store.clone();
+        let mut context = shared_store.contexts[&Author(0)].clone();
+        assert!(store.insert_network_record(record, &mut context).is_ok());
+    }
+}
+
+#[test]
+fn test_block_times_follow_the_chain() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    shared_store.make_round(NodeTime(10));
+    // A leader whose clock is behind proposes the quorum time of the previous block instead,
+    // i.e. the median time of the votes that certified it.
+    shared_store.make_round(NodeTime(5));
+    let qc_hash = shared_store.store.highest_quorum_certificate_hash();
+    let qc = shared_store.store.quorum_certificate(qc_hash).unwrap();
+    let block_hash = qc.certified_block_hash;
+    let block = shared_store.store.block(block_hash).unwrap();
+    assert_eq!(block.time, NodeTime(11));
+    assert_eq!(shared_store.store.quorum_time(block_hash), NodeTime(11));
+    // Votes are later than the block that they certify.
+    assert!(qc.votes.iter().all(|(_, time, _)| *time > block.time));
+}
+
 #[test]
 fn test_non_contiguous_qcs() {
     let mut shared_store = SharedRecordStore::new(2, 20);
@@ -291,3 +1044,343 @@ fn test_commit() {
             .as_ref()
     );
 }
+
+#[test]
+fn test_internal_consistency() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    shared_store.make_round(NodeTime(10));
+    shared_store.make_tc();
+    shared_store.make_round(NodeTime(30));
+    shared_store.make_round(NodeTime(40));
+    shared_store.make_round(NodeTime(50));
+    let store = &shared_store.store;
+    assert!(store.check_internal_consistency().is_ok());
+
+    let mut broken = store.clone();
+    broken.highest_committed_round = Round(4);
+    assert!(broken.check_internal_consistency().is_err());
+
+    let mut broken = store.clone();
+    broken.current_timeouts_weight += 1;
+    assert!(broken.check_internal_consistency().is_err());
+
+    let mut broken = store.clone();
+    broken.indexes.block_parents[1] = None;
+    assert!(broken.check_internal_consistency().is_err());
+
+    let mut broken = store.clone();
+    broken.highest_quorum_certificate_round = Round(4);
+    assert!(broken.check_internal_consistency().is_err());
+}
+
+#[test]
+fn test_fast_path_commit() {
+    let mut shared_store = SharedRecordStore::new(4, 20);
+    shared_store.store.set_fast_path(FastPath {
+        missing_votes: 0,
+        wait: 10,
+    });
+    assert_eq!(shared_store.store.fast_quorum_threshold(), Some(4));
+    // Two QCs suffice when the second one has all the votes.
+    shared_store.make_round_with_votes(NodeTime(10), 4);
+    shared_store.make_round_with_votes(NodeTime(20), 4);
+    assert_eq!(shared_store.store.highest_committed_round(), Round(1));
+    assert!(shared_store.store.is_fast_commit(Round(1)));
+    // Otherwise, the commit rule applies.
+    shared_store.make_round_with_votes(NodeTime(30), 3);
+    assert_eq!(shared_store.store.highest_committed_round(), Round(1));
+    shared_store.make_round_with_votes(NodeTime(40), 3);
+    let store = &shared_store.store;
+    assert_eq!(store.highest_committed_round(), Round(2));
+    assert!(!store.is_fast_commit(Round(2)));
+    let commits = store.committed_states_after(Round(0));
+    assert_eq!(commits.len(), 2);
+    assert_eq!(commits[0].0, Round(1));
+    assert_eq!(commits[1].0, Round(2));
+}
+
+#[test]
+fn test_committed_blocks() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    for round in 1..6 {
+        shared_store.make_round(NodeTime(round * 10));
+    }
+    let store = &shared_store.store;
+    let commits: Vec<_> = store.committed_blocks(Round(0)).collect();
+    assert_eq!(commits.len(), store.highest_committed_round().0);
+    for (commit, (round, state)) in commits.iter().zip(store.committed_states_after(Round(0))) {
+        assert_eq!(commit.round, round);
+        assert_eq!(*commit.state, state);
+        assert_eq!(store.block(commit.block_hash).unwrap().round, round);
+    }
+    // Iteration starts after the given round.
+    let mut iter = store.committed_blocks(Round(1));
+    assert_eq!(iter.len(), commits.len() - 1);
+    assert_eq!(iter.next(), Some(commits[1]));
+    assert_eq!(
+        store
+            .committed_blocks(store.highest_committed_round())
+            .len(),
+        0
+    );
+}
+
+#[test]
+fn test_blocks_conflicting_with_commit() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    shared_store.make_round(NodeTime(10));
+    let qc1_hash = shared_store.store.highest_quorum_certificate_hash();
+    // The block of round 2 is not certified and the next leader extends the QC of round 1.
+    let leader = shared_store.leader(Round(2)).0;
+    shared_store.propose_block(leader, qc1_hash, NodeTime(20));
+    let b2_hash = shared_store.proposed_hash();
+    shared_store.make_tc();
+    shared_store.make_round(NodeTime(30));
+    let b3_hash = shared_store
+        .store
+        .highest_quorum_certificate()
+        .unwrap()
+        .certified_block_hash;
+    shared_store.make_round(NodeTime(40));
+    shared_store.make_round(NodeTime(50));
+    let store = &shared_store.store;
+    assert_eq!(store.highest_committed_round(), Round(3));
+    assert_eq!(store.blocks_conflicting_with(b3_hash), vec![b2_hash]);
+    let b1_hash = store
+        .quorum_certificate(qc1_hash)
+        .unwrap()
+        .certified_block_hash;
+    assert!(store.blocks_conflicting_with(b1_hash).is_empty());
+    let b5_hash = store
+        .highest_quorum_certificate()
+        .unwrap()
+        .certified_block_hash;
+    assert_eq!(store.blocks_conflicting_with(b5_hash), vec![b2_hash]);
+}
+
+#[test]
+fn test_records_unknown_to_peer() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    shared_store.make_round(NodeTime(10));
+    shared_store.make_round(NodeTime(20));
+    let snapshot = shared_store.store.clone();
+    shared_store.make_round(NodeTime(30));
+    let store = &shared_store.store;
+    assert!(store
+        .records_unknown_to(&store.record_summary(None))
+        .is_empty());
+    // Only the block and the QC of the last round are missing from the snapshot.
+    let records = store.records_unknown_to(&snapshot.record_summary(None));
+    assert_eq!(records.len(), 2);
+    let qc = store.highest_quorum_certificate().unwrap();
+    assert_eq!(
+        records[0],
+        Record::Block(store.block(qc.certified_block_hash).unwrap().clone())
+    );
+    assert_eq!(records[1], Record::QuorumCertificate(qc.clone()));
+
+    // A new node catches up with all the records.
+    let mut peer = SharedRecordStore::new(2, 20);
+    let records = store.records_unknown_to(&peer.store.record_summary(None));
+    assert_eq!(records.len(), 6);
+    let context = peer.contexts.get_mut(&Author(0)).unwrap();
+    for record in records {
+        assert!(peer.store.insert_network_record(record, context).is_ok());
+    }
+    assert_eq!(peer.store.record_summary(None), store.record_summary(None));
+
+    let leader = shared_store.leader(Round(3));
+    assert_eq!(
+        store.blocks_proposed_by(leader).last(),
+        Some(&qc.certified_block_hash)
+    );
+}
+
+#[test]
+fn test_export_dot() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    shared_store.make_round(NodeTime(10));
+    shared_store.make_round(NodeTime(20));
+    shared_store.make_round(NodeTime(30));
+    let dot = shared_store.store.export_dot();
+    assert!(dot.starts_with("digraph epoch_0 {"));
+    assert_eq!(dot.matches("shape=box").count(), 3);
+    // Initial hash and three QCs.
+    assert_eq!(dot.matches("shape=ellipse").count(), 4);
+    // The block of round 1 and the commit certificate are highlighted.
+    assert_eq!(dot.matches("fillcolor=lightblue").count(), 2);
+    assert!(dot.contains("QC3\\nvotes: "));
+}
+
+#[test]
+fn test_weighted_votes_and_timeouts() {
+    let mut shared_store = SharedRecordStore::new(4, 20);
+    for context in shared_store.contexts.values_mut() {
+        context.set_voting_rights(vec![3, 1, 1, 1]);
+    }
+    let state = shared_store.store.initial_state.clone();
+    shared_store.store.configuration = shared_store.contexts[&Author(0)].configuration(&state);
+    assert_eq!(shared_store.store.configuration.quorum_threshold(), 5);
+    // Three small validators do not form a quorum of timeouts...
+    for index in 1..4 {
+        let _ = shared_store.create_timeout(index, Round(1));
+    }
+    assert_eq!(shared_store.store.current_round(), Round(1));
+    // .. but the large one completes it.
+    let _ = shared_store.create_timeout(0, Round(1));
+    assert_eq!(
+        shared_store.store.highest_timeout_certificate_round(),
+        Round(1)
+    );
+    assert_eq!(shared_store.store.current_round(), Round(2));
+    // Same for votes.
+    let leader = shared_store.leader(Round(2));
+    shared_store.propose_block(leader.0, QuorumCertificateHash(0), NodeTime(1));
+    let proposed_hash = shared_store.proposed_hash();
+    for index in 1..4 {
+        shared_store.create_vote(index, proposed_hash).unwrap();
+    }
+    assert!(!shared_store.check_for_new_quorum_certificate());
+    shared_store.create_vote(0, proposed_hash).unwrap();
+    assert!(shared_store.check_for_new_quorum_certificate());
+    assert_eq!(
+        shared_store.store.highest_quorum_certificate_round(),
+        Round(2)
+    );
+}
+
+#[test]
+fn test_qc_with_duplicate_votes() {
+    let mut shared_store = SharedRecordStore::new(4, 20);
+    let leader = shared_store.leader(Round(1));
+    shared_store.propose_block(leader.0, QuorumCertificateHash(0), NodeTime(1));
+    let proposed_hash = shared_store.proposed_hash();
+    for index in 0..2 {
+        shared_store.create_vote(index, proposed_hash).unwrap();
+    }
+    let vote = shared_store.store.current_votes[&Author(1)].clone();
+    let mut votes: Vec<_> = shared_store
+        .store
+        .current_votes
+        .values()
+        .map(|vote| (vote.author, vote.time, vote.signature))
+        .collect();
+    // Counting the second vote twice would reach the threshold of 3 votes.
+    votes.push((vote.author, vote.time, vote.signature));
+    let qc = Record::make_quorum_certificate(
+        EpochId(0),
+        Round(1),
+        proposed_hash,
+        vote.state,
+        votes,
+        None,
+        None,
+        leader,
+        ChainId(0),
+        &Fnv1a,
+    );
+    let hash = qc.digest(&Fnv1a);
+    assert!(shared_store
+        .store
+        .verify_network_record(&qc, hash, false)
+        .is_err());
+}
+
+#[test]
+fn test_insert_late_records() {
+    let fixture = fixtures::ChainFixture::new(4, 3);
+    let mut store = fixture.store.clone();
+    // A context that executed nothing: late records are not executed.
+    let mut context = SimulatedContext::new(Author(0), 4, usize::MAX);
+    let records = fixture.next_round_records();
+    let round = store.highest_quorum_certificate_round();
+    for record in records {
+        let result = store.insert_late_record(record.clone(), &mut context);
+        match record {
+            Record::Vote(_) => assert_eq!(result, Err(RejectReason::PastEpoch)),
+            _ => assert!(result.is_ok()),
+        }
+    }
+    assert_eq!(store.highest_quorum_certificate_round(), round + 1);
+    assert_eq!(context.pending_states(), 0);
+    // Copies are still filtered.
+    let block = fixture.next_round_records().remove(0);
+    assert_eq!(
+        store.insert_late_record(block, &mut context),
+        Err(RejectReason::Duplicate)
+    );
+}
+
+#[test]
+fn test_quorum_certificate_cache() {
+    let fixture = fixtures::ChainFixture::new(4, 1);
+    let records = fixture.next_round_records();
+    let (block, qc) = (records[0].clone(), records[5].clone());
+    let mut context = fixture.contexts[0].clone();
+    context.set_cpu_costs(10, 0);
+    let mut store = fixture.store.clone();
+    store.set_quorum_certificate_cache(QuorumCertificateCache::new(4));
+    // The signatures of a QC, its own and the ones of its four votes, are verified even if
+    // its block is unknown, and only once.
+    for _ in 0..2 {
+        assert_eq!(
+            store.insert_network_record(qc.clone(), &mut context),
+            Err(RejectReason::UnknownParent)
+        );
+    }
+    assert_eq!(context.take_processing_time(), 50);
+    // A forged signature of the QC author is not a cache hit.
+    let mut forged = qc.clone();
+    if let Record::QuorumCertificate(forged_qc) = &mut forged {
+        forged_qc.signature = Signature(0);
+    }
+    assert_eq!(
+        store.insert_network_record(forged, &mut context),
+        Err(RejectReason::BadSignature)
+    );
+    assert_eq!(context.take_processing_time(), 50);
+    assert!(store
+        .insert_network_record(block.clone(), &mut context)
+        .is_ok());
+    assert!(store
+        .insert_network_record(qc.clone(), &mut context)
+        .is_ok());
+    assert_eq!(context.take_processing_time(), 10);
+    let stats = store.quorum_certificate_cache_stats().unwrap().clone();
+    assert_eq!(
+        (stats.hits, stats.misses, stats.signatures_saved),
+        (2, 2, 10)
+    );
+
+    // Without a cache, every copy is verified.
+    let mut store = fixture.store.clone();
+    for _ in 0..2 {
+        let _ = store.insert_network_record(qc.clone(), &mut context);
+    }
+    assert_eq!(context.take_processing_time(), 100);
+    assert_eq!(store.quorum_certificate_cache_stats(), None);
+}
+
+#[test]
+fn test_verify_signatures_in_parallel() {
+    let fixture = fixtures::ChainFixture::new(4, 1);
+    let mut records = fixture.next_round_records();
+    let mut forged = records[0].clone();
+    if let Record::Block(block) = &mut forged {
+        block.signature = Signature(0);
+    }
+    records.push(forged);
+    let verified = fixture.store.verify_signatures_in_parallel(&records);
+    assert_eq!(verified, vec![true, true, true, true, true, true, false]);
+
+    let mut store = fixture.store.clone();
+    let mut context = fixture.contexts[0].clone();
+    assert!(store
+        .insert_verified_network_record(records[0].clone(), &mut context)
+        .is_ok());
+    // Copies of known records are not verified, whatever their signature.
+    assert_eq!(
+        store.verify_signatures_in_parallel(&[records[0].clone(), records[6].clone()]),
+        vec![false, false]
+    );
+}
@@ -2,30 +2,326 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use hashing::Fnv1a;
+use record_builder::RecordBuilder;
+use smr_context::EpochReader;
 
 #[test]
 fn test_block_signing() {
     let b = Record::make_block(
-        Command {
+        EpochId(0),
+        Some(Command {
             proposer: Author(1),
             index: 2,
-        },
+        }),
         NodeTime(2),
         QuorumCertificateHash(47),
         Round(3),
         Author(2),
+        ChainId(0),
+        &Fnv1a,
     );
-    assert!(b.signature().check(b.digest(), b.author()).is_ok());
-    assert!(b.signature().check(b.digest(), Author(1)).is_err());
+    assert!(b
+        .signature()
+        .check(b.signing_hash(EpochId(0), ChainId(0), &Fnv1a), b.author())
+        .is_ok());
+    assert!(b
+        .signature()
+        .check(b.signing_hash(EpochId(0), ChainId(0), &Fnv1a), Author(1))
+        .is_err());
     let b2 = Record::make_block(
-        Command {
+        EpochId(0),
+        Some(Command {
             proposer: Author(3),
             index: 2,
-        },
+        }),
         NodeTime(2),
         QuorumCertificateHash(47),
         Round(3),
         Author(2),
+        ChainId(0),
+        &Fnv1a,
     );
-    assert!(b.signature().check(b2.digest(), b.author()).is_err());
+    assert!(b
+        .signature()
+        .check(b2.signing_hash(EpochId(0), ChainId(0), &Fnv1a), b.author())
+        .is_err());
+}
+
+#[test]
+fn test_signatures_are_bound_to_epochs() {
+    let block = Record::make_block(
+        EpochId(0),
+        Some(Command {
+            proposer: Author(1),
+            index: 2,
+        }),
+        NodeTime(2),
+        QuorumCertificateHash(47),
+        Round(3),
+        Author(2),
+        ChainId(0),
+        &Fnv1a,
+    );
+    // Blocks do not carry an epoch: the same block signed for epoch 0 is invalid in epoch 1.
+    assert!(block
+        .signature()
+        .check(
+            block.signing_hash(EpochId(1), ChainId(0), &Fnv1a),
+            block.author()
+        )
+        .is_err());
+
+    let vote = Record::make_vote(
+        EpochId(0),
+        Round(3),
+        BlockHash(block.digest(&Fnv1a)),
+        State(5),
+        Author(1),
+        None,
+        None,
+        NodeTime(3),
+        ChainId(0),
+        &Fnv1a,
+    );
+    let mut replayed_vote = vote.clone();
+    if let Record::Vote(x) = &mut replayed_vote {
+        x.epoch_id = EpochId(1);
+    }
+    assert!(vote
+        .signature()
+        .check(vote.signing_hash(EpochId(0), ChainId(0), &Fnv1a), Author(1))
+        .is_ok());
+    assert!(replayed_vote
+        .signature()
+        .check(
+            replayed_vote.signing_hash(EpochId(1), ChainId(0), &Fnv1a),
+            Author(1)
+        )
+        .is_err());
+
+    // Votes collected in epoch 0 cannot be reused in a QC for epoch 1.
+    let qc = QuorumCertificate {
+        epoch_id: EpochId(1),
+        round: Round(3),
+        certified_block_hash: BlockHash(block.digest(&Fnv1a)),
+        state: State(5),
+        committed_state: None,
+        next_epoch: None,
+        votes: vec![(Author(1), NodeTime(3), vote.signature())],
+        author: Author(2),
+        signature: Signature(0),
+    };
+    assert!(vote
+        .signature()
+        .check(
+            qc.vote_signing_hash(Author(1), NodeTime(3), ChainId(0), &Fnv1a),
+            Author(1)
+        )
+        .is_err());
+
+    // Signatures are also separated by kind of record.
+    let timeout = Record::make_timeout(
+        EpochId(0),
+        Round(3),
+        Round(2),
+        Author(1),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert_ne!(
+        timeout.signing_hash(EpochId(0), ChainId(0), &Fnv1a),
+        timeout.digest(&Fnv1a)
+    );
+}
+
+#[test]
+fn test_signatures_are_bound_to_chains() {
+    let block = Record::make_block(
+        EpochId(0),
+        None,
+        NodeTime(2),
+        QuorumCertificateHash(47),
+        Round(3),
+        Author(2),
+        ChainId(0),
+        &Fnv1a,
+    );
+    assert!(block
+        .signature()
+        .check(
+            block.signing_hash(EpochId(0), ChainId(1), &Fnv1a),
+            block.author()
+        )
+        .is_err());
+    let timeout = Record::make_timeout(
+        EpochId(0),
+        Round(3),
+        Round(2),
+        Author(1),
+        ChainId(1),
+        &Fnv1a,
+    );
+    assert!(timeout
+        .signature()
+        .check(
+            timeout.signing_hash(EpochId(0), ChainId(1), &Fnv1a),
+            Author(1)
+        )
+        .is_ok());
+    assert!(timeout
+        .signature()
+        .check(
+            timeout.signing_hash(EpochId(0), ChainId(0), &Fnv1a),
+            Author(1)
+        )
+        .is_err());
+}
+
+#[test]
+fn test_verify_commit_certificate() {
+    let configuration = EpochConfiguration::new((0..4).map(|index| (Author(index), 1)).collect());
+    let block_hash = BlockHash(47);
+    let vote = |author| match Record::make_vote(
+        EpochId(1),
+        Round(3),
+        block_hash,
+        State(5),
+        Author(author),
+        Some(State(4)),
+        None,
+        NodeTime(author as i64),
+        ChainId(0),
+        &Fnv1a,
+    ) {
+        Record::Vote(vote) => (vote.author, vote.time, vote.signature),
+        _ => unreachable!(),
+    };
+    let certificate = |votes| match Record::make_quorum_certificate(
+        EpochId(1),
+        Round(3),
+        block_hash,
+        State(5),
+        votes,
+        Some(State(4)),
+        None,
+        Author(0),
+        ChainId(0),
+        &Fnv1a,
+    ) {
+        Record::QuorumCertificate(qc) => qc,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        verify_commit_certificate(
+            &certificate(vec![vote(0), vote(1), vote(2)]),
+            &configuration,
+            ChainId(0),
+            &Fnv1a,
+        ),
+        Ok(CommittedState {
+            epoch_id: EpochId(1),
+            certified_round: Round(3),
+            state: State(4),
+            next_epoch: None,
+        })
+    );
+    assert_eq!(
+        verify_commit_certificate(
+            &certificate(vec![vote(0), vote(1)]),
+            &configuration,
+            ChainId(0),
+            &Fnv1a
+        ),
+        Err(VerifyError::InsufficientQuorum {
+            weight: 2,
+            threshold: 3
+        })
+    );
+    assert_eq!(
+        verify_commit_certificate(
+            &certificate(vec![vote(0), vote(1), vote(1)]),
+            &configuration,
+            ChainId(0),
+            &Fnv1a,
+        ),
+        Err(VerifyError::DuplicateVote(Author(1)))
+    );
+    assert_eq!(
+        verify_commit_certificate(
+            &certificate(vec![vote(0), vote(1), vote(4)]),
+            &configuration,
+            ChainId(0),
+            &Fnv1a,
+        ),
+        Err(VerifyError::UnknownAuthor(Author(4)))
+    );
+    let (_, time, signature) = vote(2);
+    assert_eq!(
+        verify_commit_certificate(
+            &certificate(vec![vote(0), vote(1), (Author(3), time, signature)]),
+            &configuration,
+            ChainId(0),
+            &Fnv1a,
+        ),
+        Err(VerifyError::InvalidSignature(Author(3)))
+    );
+    let mut forged = certificate(vec![vote(0), vote(1), vote(2)]);
+    forged.committed_state = Some(State(6));
+    assert_eq!(
+        verify_commit_certificate(&forged, &configuration, ChainId(0), &Fnv1a),
+        Err(VerifyError::InvalidSignature(Author(0)))
+    );
+    forged.committed_state = None;
+    assert_eq!(
+        verify_commit_certificate(&forged, &configuration, ChainId(0), &Fnv1a),
+        Err(VerifyError::NotACommitCertificate)
+    );
+}
+
+#[test]
+fn test_verify_simulated_commit_certificates() {
+    let scenario = scenario::Scenario::from_toml("nodes = 4\nmax_clock = 1000\nseed = 1").unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(bft_simulator_runtime::simulator::GlobalTime(1000), None);
+    for index in 0..4 {
+        let node = sim.simulated_node(Author(index));
+        let store = node.node().node_state().record_store();
+        let certificate = store.highest_commit_certificate().unwrap();
+        let state = node.context().last_committed_state();
+        let configuration = node.context().configuration(&state);
+        assert!(verify_commit_certificate(certificate, &configuration, ChainId(0), &Fnv1a).is_ok());
+    }
+}
+
+#[test]
+fn test_quorum_time() {
+    let configuration = EpochConfiguration::new(
+        vec![
+            (Author(0), 1),
+            (Author(1), 1),
+            (Author(2), 1),
+            (Author(3), 3),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let quorum_time = |votes: Vec<(usize, i64)>| match RecordBuilder::quorum_certificate(Author(0))
+        .votes(
+            votes
+                .into_iter()
+                .map(|(author, time)| (Author(author), NodeTime(time), Signature(0))),
+        )
+        .unchecked()
+    {
+        Record::QuorumCertificate(qc) => qc.quorum_time(&configuration),
+        _ => unreachable!(),
+    };
+    // The median does not depend on the order of the votes, and outliers do not move it.
+    assert_eq!(quorum_time(vec![(0, 30), (1, 10), (2, 20)]), NodeTime(20));
+    assert_eq!(quorum_time(vec![(0, 1000), (1, 10), (2, 20)]), NodeTime(20));
+    assert_eq!(quorum_time(vec![(0, 0), (1, 10), (2, 20)]), NodeTime(10));
+    // Votes are weighted by voting rights.
+    assert_eq!(quorum_time(vec![(0, 10), (1, 20), (3, 30)]), NodeTime(30));
+    assert_eq!(quorum_time(vec![(0, 30), (1, 40), (3, 10)]), NodeTime(10));
 }
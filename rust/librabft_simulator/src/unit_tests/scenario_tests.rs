@@ -0,0 +1,595 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+const CRASH_ONE_NODE: &str = include_str!("../../scenarios/crash_one_node.toml");
+const TWINS: &str = include_str!("../../scenarios/twins.toml");
+const LOCK_VIOLATION: &str = include_str!("../../scenarios/lock_violation.toml");
+const STAKE: &str = include_str!("../../scenarios/stake.toml");
+const STAKE_CHANGES: &str = include_str!("../../scenarios/stake_changes.toml");
+const NODE_JOIN: &str = include_str!("../../scenarios/node_join.toml");
+const VALIDATOR_REMOVAL: &str = include_str!("../../scenarios/validator_removal.toml");
+
+#[test]
+fn test_parse_scenario() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    assert_eq!(scenario.nodes, 4);
+    assert_eq!(scenario.commands_per_epoch, 30000);
+    assert_eq!(scenario.pacemaker.lambda, 0.5);
+    assert_eq!(scenario.workload, WorkloadParameters::default());
+    assert_eq!(
+        scenario.faults,
+        vec![CrashFault {
+            node: 3,
+            start: 300,
+            end: Some(900),
+        }]
+    );
+}
+
+#[test]
+fn test_hash_function_of_deployments() {
+    let mut scenario = Scenario::from_toml("nodes = 4\nmax_clock = 10").unwrap();
+    assert_eq!(scenario.hash_function(), HashFunction::Fnv1a);
+    scenario.deploy();
+    assert_eq!(scenario.hash_function(), HashFunction::Sha3_256);
+    let context = scenario.make_context(Author(0));
+    let node = scenario.make_node(Author(0), &context, NodeTime(0));
+    assert_eq!(node.node_state().hash_function(), HashFunction::Sha3_256);
+    // Deployments keep the hash function chosen by the scenario.
+    let mut scenario =
+        Scenario::from_toml("nodes = 4\nmax_clock = 10\nhash_function = \"fnv1a\"").unwrap();
+    scenario.deploy();
+    assert_eq!(scenario.hash_function(), HashFunction::Fnv1a);
+}
+
+#[test]
+fn test_invalid_scenarios() {
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\nunknown = 1").is_err());
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\n[[faults]]\nnode = 4\nstart = 0\nend = 5"
+    )
+    .is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\npast_epochs_kept = 0").is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\nmax_clock_drift = -1").is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\n[network]\nthroughput = 0.0").is_err());
+    let payload = |sizes: &str| {
+        Scenario::from_toml(&format!(
+            "nodes = 4\nmax_clock = 10\n[workload.payload]\n{}",
+            sizes
+        ))
+    };
+    assert_eq!(
+        payload("distribution = \"uniform\"\nmin = 10\nmax = 20")
+            .unwrap()
+            .workload
+            .payload,
+        Some(PayloadSizes::Uniform { min: 10, max: 20 })
+    );
+    assert!(payload("distribution = \"uniform\"\nmin = 20\nmax = 10").is_err());
+    assert!(payload("distribution = \"normal\"\nbytes = 10").is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\n[workload]\nclients = 0").is_err());
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\n[workload]\nmempool_threshold = 5\nmax_payload_bytes = 10"
+    )
+    .is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\n[[hardware]]\nnode = 4").is_err());
+    let topology = |rtt: &str| {
+        Scenario::from_toml(&format!(
+            "nodes = 4\nmax_clock = 10\n[topology.regions]\nus = [0, 1]\neu = [2]\nasia = [3]\n{}",
+            rtt
+        ))
+    };
+    let scenario =
+        topology("[topology.rtt.us]\neu = 40.0\nasia = 160.0\n[topology.rtt.asia]\neu = 180.0")
+            .unwrap();
+    assert_eq!(scenario.topology.rtt("eu", "asia"), Some(180.0));
+    assert_eq!(scenario.topology.rtt("us", "us"), None);
+    assert!(topology("[topology.rtt.us]\neu = 40.0\nasia = 160.0").is_err());
+    assert!(topology("[topology.rtt.us]\neu = 40.0\nmars = 160.0").is_err());
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\n[[hardware]]\nnode = 0\nupload_throughput = 0.0"
+    )
+    .is_err());
+    assert!(
+        Scenario::from_toml("nodes = 4\nmax_clock = 10\n[[censors]]\nnode = 0\nclients = [1]")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_invalid_clocks() {
+    let clock = |node, drift| {
+        Scenario::from_toml(&format!(
+            "nodes = 4\nmax_clock = 10\n[[clocks]]\nnode = {}\ndrift = {}",
+            node, drift
+        ))
+    };
+    assert!(clock(3, 0.01).is_ok());
+    assert!(clock(4, 0.01).is_err());
+    assert!(clock(3, -1.0).is_err());
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\n[[clocks]]\nnode = 1\n[[clocks]]\nnode = 1"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_run_scenario() {
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let contexts = run_scenario(&scenario);
+    assert_eq!(contexts.len(), 4);
+    for context in contexts {
+        assert!(!context.committed_history().is_empty());
+    }
+}
+
+#[test]
+fn test_twins_scenario() {
+    let scenario = Scenario::from_toml(TWINS).unwrap();
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\ntwins = [4]").is_err());
+    let contexts = run_scenario(&scenario);
+    assert_eq!(contexts.len(), 5);
+    let longest = contexts
+        .iter()
+        .map(|context| context.committed_history())
+        .max_by_key(|history| history.len())
+        .unwrap();
+    assert!(!longest.is_empty());
+    for context in &contexts {
+        let history = context.committed_history();
+        assert_eq!(history[..], longest[..history.len()]);
+    }
+}
+
+#[test]
+fn test_lock_violation_scenario() {
+    let mut scenario = Scenario::from_toml(LOCK_VIOLATION).unwrap();
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\nlock_violators = [4]").is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\nqc_withholders = [4]").is_err());
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\ndouble_spenders = [4]").is_err());
+    for commit_rule in &[CommitRuleKind::LibraBft, CommitRuleKind::TwoChain] {
+        scenario.commit_rule = *commit_rule;
+        let mut violations = 0;
+        for seed in 0..5 {
+            scenario.seed = Some(seed);
+            let mut sim = scenario.build_simulator();
+            // The commit checker panics if honest nodes commit conflicting blocks.
+            sim.loop_until(GlobalTime(scenario.max_clock), None);
+            violations += sim
+                .replicas()
+                .iter()
+                .map(|replica| replica.node().node_state().lock_violations())
+                .sum::<usize>();
+            assert!(!sim.replicas()[1].context().committed_history().is_empty());
+        }
+        assert!(violations > 0);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Execution diverged")]
+fn test_lock_violations_beyond_resilience() {
+    // Two byzantine identities out of 4: each side of the partition forms a quorum.
+    let scenario = Scenario::from_toml(
+        "nodes = 4\nmax_clock = 2000\ntwins = [0, 1]\nlock_violators = [0, 1]\nseed = 0\n\
+         [[partitions]]\ngroups = [[0, 1, 2], [4, 5, 3]]\nstart = 0\nend = 1000",
+    )
+    .unwrap();
+    run_scenario(&scenario);
+}
+
+#[test]
+fn test_quorum_impossible_intervals() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let sim = scenario.build_simulator();
+    assert!(quorum_impossible_intervals(&sim, scenario.max_clock).is_empty());
+    scenario.faults.push(CrashFault {
+        node: 0,
+        start: 600,
+        end: None,
+    });
+    let sim = scenario.build_simulator();
+    assert_eq!(
+        quorum_impossible_intervals(&sim, scenario.max_clock),
+        vec![(600, 900)]
+    );
+}
+
+#[test]
+fn test_resilience() {
+    let mut scenario = Scenario::from_toml("nodes = 11\nmax_clock = 1000").unwrap();
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\nresilience = 1").is_err());
+    scenario.faults = (0..3)
+        .map(|node| CrashFault {
+            node,
+            start: 0,
+            end: None,
+        })
+        .collect();
+    // With n = 3f + 1, 3 crashes out of 11 are tolerated..
+    let mut sim = scenario.build_simulator();
+    assert!(quorum_impossible_intervals(&sim, scenario.max_clock).is_empty());
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(contexts[3..]
+        .iter()
+        .all(|context| !context.committed_history().is_empty()));
+    // .. but not with n = 5f + 1, where quorums need 9 votes.
+    scenario.resilience = 5;
+    let mut sim = scenario.build_simulator();
+    assert_eq!(
+        quorum_impossible_intervals(&sim, scenario.max_clock),
+        vec![(0, 1000)]
+    );
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(contexts
+        .iter()
+        .all(|context| context.committed_history().is_empty()));
+}
+
+#[test]
+fn test_stake_scenario() {
+    assert!(Scenario::from_toml("nodes = 4\nmax_clock = 10\nvoting_rights = [1, 1]").is_err());
+    assert!(Scenario::from_toml("nodes = 2\nmax_clock = 10\nvoting_rights = [0, 0]").is_err());
+    let mut scenario = Scenario::from_toml(STAKE).unwrap();
+    let sim = scenario.build_simulator();
+    let context = sim.simulated_node(Author(0)).context();
+    let configuration = context.configuration(&context.last_committed_state());
+    assert_eq!(configuration.weight(&Author(0)), 4);
+    assert_eq!(configuration.quorum_threshold(), 5);
+    // The large validator alone prevents quorums.
+    assert!(quorum_impossible_intervals(&sim, scenario.max_clock).is_empty());
+    scenario.faults.push(CrashFault {
+        node: 0,
+        start: 1000,
+        end: Some(1100),
+    });
+    let sim = scenario.build_simulator();
+    assert_eq!(
+        quorum_impossible_intervals(&sim, scenario.max_clock),
+        vec![(1000, 1100)]
+    );
+    // Commits go on while two small validators are crashed.
+    let mut sim = Scenario::from_toml(STAKE).unwrap().build_simulator();
+    sim.loop_until(GlobalTime(300), None);
+    let before = sim
+        .simulated_node(Author(0))
+        .context()
+        .committed_history()
+        .len();
+    sim.loop_until(GlobalTime(900), None);
+    let after = sim
+        .simulated_node(Author(0))
+        .context()
+        .committed_history()
+        .len();
+    assert!(after > before);
+}
+
+#[test]
+fn test_stake_changes_scenario() {
+    assert!(Scenario::from_toml(
+        "nodes = 2\nmax_clock = 10\n[[reconfigurations]]\nepoch = 0\nvoting_rights = [1, 1]"
+    )
+    .is_err());
+    assert!(Scenario::from_toml(
+        "nodes = 2\nmax_clock = 10\n[[reconfigurations]]\nepoch = 1\nvoting_rights = [1]"
+    )
+    .is_err());
+    let scenario = Scenario::from_toml(STAKE_CHANGES).unwrap();
+    let mut sim = scenario.build_simulator();
+    // Weight of node 4 and quorum threshold seen by node 0 at each epoch.
+    let mut configurations = BTreeMap::new();
+    sim.loop_until_with_observer(GlobalTime(scenario.max_clock), None, |sim, _| {
+        let node = sim.simulated_node(Author(0)).node().node_state();
+        let configuration = node.configuration();
+        configurations.insert(
+            node.epoch_id(),
+            (
+                configuration.weight(&Author(4)),
+                configuration.quorum_threshold(),
+            ),
+        );
+    });
+    assert_eq!(configurations[&EpochId(0)], (0, 3));
+    assert_eq!(configurations[&EpochId(1)], (6, 7));
+    assert_eq!(configurations[&EpochId(2)], (0, 3));
+    // Without the new stakeholder, the chain cannot go past the first reconfiguration.
+    let mut scenario = scenario;
+    scenario.faults.push(CrashFault {
+        node: 4,
+        start: 0,
+        end: None,
+    });
+    for context in run_scenario(&scenario).iter().take(4) {
+        assert_eq!(
+            context.committed_history().len(),
+            scenario.commands_per_epoch
+        );
+    }
+}
+
+#[test]
+fn test_key_rotation_scenario() {
+    let base = "nodes = 4\nmax_clock = 3000\nseed = 1\ncommands_per_epoch = 10\n";
+    let rotation = |node: usize, epoch: usize| {
+        Scenario::from_toml(&format!(
+            "{}[[key_rotations]]\nnode = {}\nepoch = {}",
+            base, node, epoch
+        ))
+    };
+    assert!(rotation(4, 1).is_err());
+    assert!(rotation(1, 0).is_err());
+    let mut scenario = rotation(1, 1).unwrap();
+    scenario
+        .key_rotations
+        .push(KeyRotation { node: 1, epoch: 2 });
+    let mut sim = scenario.build_simulator();
+    // Key of node 1 seen by node 0 at each epoch.
+    let mut keys = BTreeMap::new();
+    let contexts = sim.loop_until_with_observer(GlobalTime(scenario.max_clock), None, |sim, _| {
+        let node = sim.simulated_node(Author(0)).node().node_state();
+        keys.insert(node.epoch_id(), node.configuration().public_key(Author(1)));
+    });
+    assert!(campaign::consistent_histories(&contexts));
+    let key = PublicKey::initial(Author(1));
+    assert_eq!(keys[&EpochId(0)], key);
+    assert_eq!(keys[&EpochId(1)], key.rotated());
+    assert_eq!(keys[&EpochId(2)], key.rotated().rotated());
+    // Node 1 keeps signing records, hence the chain keeps growing after the rotations.
+    assert!(keys.len() > 3);
+    let node = sim.simulated_node(Author(1)).node().node_state();
+    assert_eq!(node.public_key(), key.rotated().rotated());
+}
+
+#[test]
+fn test_node_join() {
+    let base = "nodes = 4\nmax_clock = 1000\n";
+    let join = |node: usize, epoch: usize| {
+        Scenario::from_toml(&format!(
+            "{}[[joins]]\nnode = {}\nepoch = {}",
+            base, node, epoch
+        ))
+    };
+    assert!(join(4, 1).is_err());
+    assert!(join(3, 0).is_err());
+    assert!(join(3, 1).is_ok());
+
+    let scenario = Scenario::from_toml(NODE_JOIN).unwrap();
+    let start = scenario.joins[0].start;
+    let mut sim = scenario.build_simulator();
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(campaign::consistent_histories(&contexts));
+    let joiner = sim.simulated_node(Author(4)).node().node_state();
+    let peer = sim.simulated_node(Author(0)).node().node_state();
+    // The joiner skipped the epochs before the snapshot, then followed the others and voted.
+    let snapshot_time = joiner.snapshot_time().unwrap();
+    assert!(snapshot_time.0 >= start);
+    assert!(joiner.record_store_at(EpochId(1)).is_none());
+    assert!(joiner.first_vote_time().unwrap() >= snapshot_time);
+    assert!(joiner.epoch_id().0 + 1 >= peer.epoch_id().0);
+    assert_eq!(peer.snapshot_time(), None);
+    assert!(
+        sim.simulated_node(Author(4))
+            .context()
+            .committed_history()
+            .len()
+            > 3 * scenario.commands_per_epoch
+    );
+    // Run reports measure the bootstrap from the time the joiner comes online.
+    let joins = campaign::run(&scenario, None).joins.unwrap();
+    assert_eq!(joins[0].snapshot, Some(snapshot_time.0 - start));
+    assert_eq!(
+        joins[0].first_vote,
+        Some(joiner.first_vote_time().unwrap().0 - start)
+    );
+}
+
+#[test]
+fn test_forged_snapshots() {
+    let scenario = Scenario::from_toml(NODE_JOIN).unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    let peer = sim.simulated_node(Author(0)).node().node_state();
+    let snapshot = peer.epoch_snapshot().unwrap().clone();
+    assert!(snapshot.certificate.epoch_id > EpochId(0));
+    // A joiner that did not start yet only knows the configuration of the first epoch.
+    let mut context = scenario.make_context(Author(4));
+    let mut joiner = scenario.make_node(Author(4), &context, NodeTime(0));
+    let joiner = joiner.node_state_mut();
+    let initial_state = context.last_committed_state();
+    let install = |joiner: &mut NodeState, context: &mut SimulatedContext, snapshot| {
+        joiner.install_snapshot(snapshot, NodeTime(0), context)
+    };
+
+    // Without the proofs of the previous epochs, the certificate cannot be verified.
+    let mut forged = snapshot.clone();
+    forged.proofs.clear();
+    assert!(!install(joiner, &mut context, &forged));
+    // Certificates and configurations must be the ones signed by the validators.
+    let mut forged = snapshot.clone();
+    forged.certificate.votes.pop();
+    assert!(!install(joiner, &mut context, &forged));
+    let mut forged = snapshot.clone();
+    forged.certificate.committed_state = Some(State(0));
+    assert!(!install(joiner, &mut context, &forged));
+    let mut forged = snapshot.clone();
+    let mut voting_rights = forged.proofs[0].configuration.voting_rights().clone();
+    voting_rights.insert(Author(5), 100);
+    forged.proofs[0].configuration = EpochConfiguration::new(voting_rights);
+    assert!(!install(joiner, &mut context, &forged));
+    // The state must be the certified one.
+    let mut forged = snapshot.clone();
+    forged.data.pop();
+    assert!(!install(joiner, &mut context, &forged));
+    assert_eq!(context.last_committed_state(), initial_state);
+    assert_eq!(joiner.epoch_id(), EpochId(0));
+
+    assert!(install(joiner, &mut context, &snapshot));
+    assert_eq!(
+        joiner.epoch_id(),
+        snapshot.certificate.next_epoch.unwrap().epoch_id
+    );
+    assert_eq!(
+        Some(context.last_committed_state()),
+        snapshot.certificate.committed_state
+    );
+}
+
+#[test]
+fn test_validator_removal() {
+    let base = "nodes = 4\nmax_clock = 1000\n";
+    let removal = |node: usize, epoch: usize| {
+        Scenario::from_toml(&format!(
+            "{}[[removals]]\nnode = {}\nepoch = {}",
+            base, node, epoch
+        ))
+    };
+    assert!(removal(4, 1).is_err());
+    assert!(removal(3, 0).is_err());
+    assert!(removal(3, 1).is_ok());
+    let mut scenario = removal(3, 2).unwrap();
+    scenario.joins.push(Join {
+        node: 3,
+        epoch: 2,
+        start: 0,
+    });
+    assert!(scenario.check().is_err());
+
+    let scenario = Scenario::from_toml(VALIDATOR_REMOVAL).unwrap();
+    let mut sim = scenario.build_simulator();
+    // Whether node 4 has a vote in the highest QC, a timeout or a proposal in each epoch, as
+    // seen by node 0.
+    let mut activity = BTreeMap::new();
+    let contexts = sim.loop_until_with_observer(GlobalTime(scenario.max_clock), None, |sim, _| {
+        let node = sim.simulated_node(Author(0)).node().node_state();
+        let store = node.record_store();
+        let active = store
+            .highest_quorum_certificate()
+            .is_some_and(|qc| qc.votes.iter().any(|(author, _, _)| *author == Author(4)))
+            || store
+                .timeouts()
+                .iter()
+                .any(|timeout| timeout.author == Author(4))
+            || !store.blocks_proposed_by(Author(4)).is_empty();
+        *activity.entry(node.epoch_id()).or_insert(false) |= active;
+    });
+    assert!(campaign::consistent_histories(&contexts));
+    assert!(activity[&EpochId(0)]);
+    assert!(activity.len() > 3);
+    assert!(activity.range(EpochId(2)..).all(|(_, active)| !active));
+    // The removed validator keeps following the chain, without voting.
+    let removed = sim.simulated_node(Author(4)).node().node_state();
+    let peer = sim.simulated_node(Author(0)).node().node_state();
+    assert!(removed.epoch_id().0 + 1 >= peer.epoch_id().0);
+    assert_eq!(peer.configuration().weight(&Author(4)), 0);
+    assert_eq!(removed.latest_voted_round(), Round(0));
+}
+
+#[test]
+fn test_back_pressure() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    let contexts = run_scenario(&scenario);
+    assert!(contexts
+        .iter()
+        .all(|context| context.committed_empty_blocks() == 0));
+
+    scenario.workload.max_uncommitted_depth = Some(2);
+    let contexts = run_scenario(&scenario);
+    assert!(campaign::consistent_histories(
+        &contexts.iter().collect::<Vec<_>>()
+    ));
+    // Rounds of the crashed leader break commit chains, hence empty blocks.
+    assert!(contexts
+        .iter()
+        .any(|context| context.committed_empty_blocks() > 0));
+    assert!(contexts
+        .iter()
+        .all(|context| !context.committed_history().is_empty()));
+
+    assert!(Scenario::from_toml("nodes = 4\n[workload]\nmax_uncommitted_depth = 1").is_err());
+}
+
+#[test]
+fn test_rollbacks() {
+    // The bound on pending states below holds for most seeds but not all, so pin one.
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    scenario.seed = Some(0);
+    let contexts = run_scenario(&scenario);
+    assert!(campaign::consistent_histories(
+        &contexts.iter().collect::<Vec<_>>()
+    ));
+    // Blocks proposed around the crash are abandoned, and their states are rolled back instead
+    // of remaining pending.
+    for context in &contexts {
+        assert!(context.rolled_back_states() > 0);
+        assert!(context.pending_states() < context.rolled_back_states() + 3);
+    }
+}
+
+#[test]
+fn test_gossip() {
+    assert!(
+        Scenario::from_toml("nodes = 5\nmax_clock = 10\n[network.gossip]\ndegree = 3").is_err()
+    );
+    let scenario = Scenario::from_toml(
+        "nodes = 20\nmax_clock = 1000\nseed = 3\n[network.gossip]\ndegree = 4\nrelay_delay = 2",
+    )
+    .unwrap();
+    let mut sim = scenario.build_simulator();
+    let overlay = sim.gossip_overlay().unwrap();
+    assert_eq!(overlay.neighbors(Author(0)).len(), 4);
+    assert_eq!(overlay.relay_delay(), 2);
+    let contexts = sim.loop_until(GlobalTime(scenario.max_clock), None);
+    assert!(campaign::consistent_histories(&contexts));
+    // Proposals and QCs reach all the nodes through a few hops.
+    assert!(contexts
+        .iter()
+        .all(|context| !context.committed_history().is_empty()));
+}
+
+#[test]
+fn test_link_ordering() {
+    let scenario = |network: &str| {
+        Scenario::from_toml(&format!(
+            "nodes = 4\nmax_clock = 2000\nseed = 11\n[network]\nmean = 10.0\nvariance = 100.0\n{}",
+            network
+        ))
+    };
+    let unordered = scenario("").unwrap();
+    assert_eq!(unordered.network.ordering, LinkOrdering::Unordered);
+    let fifo = scenario("ordering = { kind = \"fifo\" }").unwrap();
+    assert_eq!(fifo.network.ordering, LinkOrdering::Fifo);
+    let bounded = scenario(
+        "ordering = { kind = \"bounded\", window = 5 }\n\
+         [[network.links]]\nbetween = [0, 3]\nordering = { kind = \"unordered\" }",
+    )
+    .unwrap();
+    assert_eq!(
+        bounded.network.ordering,
+        LinkOrdering::Bounded { window: 5 }
+    );
+    assert_eq!(bounded.network.links[0].between, [0, 3]);
+    assert!(scenario("ordering = { kind = \"bounded\", window = -1 }").is_err());
+    assert!(scenario("ordering = { kind = \"lifo\" }").is_err());
+    assert!(
+        scenario("[[network.links]]\nbetween = [0, 4]\nordering = { kind = \"fifo\" }").is_err()
+    );
+    assert!(
+        scenario("[[network.links]]\nbetween = [2, 2]\nordering = { kind = \"fifo\" }").is_err()
+    );
+
+    // The protocol tolerates any ordering, but the schedule depends on it.
+    let histories: Vec<_> = [unordered, fifo, bounded]
+        .iter()
+        .map(|scenario| {
+            let contexts = run_scenario(scenario);
+            assert!(contexts
+                .iter()
+                .all(|context| !context.committed_history().is_empty()));
+            contexts[0].committed_history().to_vec()
+        })
+        .collect();
+    assert_ne!(histories[0], histories[1]);
+}
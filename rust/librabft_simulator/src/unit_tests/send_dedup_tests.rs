@@ -0,0 +1,31 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_sent_records() {
+    let mut sent = SentRecords::default();
+    let epoch_id = EpochId(0);
+    assert!(sent.insert(Author(1), epoch_id, Round(3), Author(0), "block"));
+    assert!(!sent.insert(Author(1), epoch_id, Round(3), Author(0), "block"));
+    // Other peers, kinds, authors, rounds, and epochs are tracked separately.
+    assert!(sent.insert(Author(2), epoch_id, Round(3), Author(0), "block"));
+    assert!(sent.insert(
+        Author(1),
+        epoch_id,
+        Round(3),
+        Author(0),
+        "quorum_certificate"
+    ));
+    assert!(sent.insert(Author(1), epoch_id, Round(3), Author(70), "timeout"));
+    assert!(!sent.insert(Author(1), epoch_id, Round(3), Author(70), "timeout"));
+    assert!(sent.insert(Author(1), epoch_id, Round(4), Author(0), "block"));
+    assert!(sent.insert(Author(1), EpochId(1), Round(3), Author(0), "block"));
+    assert_eq!(sent.num_bitmaps(), 4);
+
+    sent.prune(epoch_id, Round(4));
+    assert_eq!(sent.num_bitmaps(), 2);
+    assert!(sent.insert(Author(1), epoch_id, Round(3), Author(0), "block"));
+    assert!(!sent.insert(Author(1), epoch_id, Round(4), Author(0), "block"));
+}
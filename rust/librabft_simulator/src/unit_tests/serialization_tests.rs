@@ -0,0 +1,121 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use hashing::Fnv1a;
+use proptest::prelude::*;
+use strategies::arb_near_valid_records;
+
+#[test]
+fn test_test_vectors_round_trip() {
+    for vector in test_vectors::canonical_test_vectors() {
+        let bytes = to_canonical_bytes(&vector.record);
+        assert_eq!(bytes[0], VERSION);
+        assert_eq!(
+            from_canonical_bytes::<Record>(&bytes).unwrap(),
+            vector.record
+        );
+        // Blocks and QCs are encoded the same on their own, without the kind of record.
+        let mut payload = Vec::new();
+        match &vector.record {
+            Record::Block(block) => {
+                block.encode(&mut payload);
+                let bytes = to_canonical_bytes(block);
+                assert_eq!(&from_canonical_bytes::<Block>(&bytes).unwrap(), block);
+            }
+            Record::QuorumCertificate(qc) => {
+                qc.encode(&mut payload);
+                let bytes = to_canonical_bytes(qc);
+                assert_eq!(
+                    &from_canonical_bytes::<QuorumCertificate>(&bytes).unwrap(),
+                    qc
+                );
+            }
+            _ => continue,
+        }
+        let mut record_payload = Vec::new();
+        vector.record.encode(&mut record_payload);
+        assert_eq!(record_payload[1..], payload[..]);
+    }
+}
+
+#[test]
+fn test_encoding() {
+    let command = Command {
+        proposer: Author(1),
+        index: 300,
+    };
+    assert_eq!(
+        to_canonical_bytes(&command),
+        vec![5, 16, 1, 0, 0, 0, 0, 0, 0, 0, 44, 1, 0, 0, 0, 0, 0, 0]
+    );
+    let mut bytes = Vec::new();
+    vec![Some(Round(1)), None].encode(&mut bytes);
+    assert_eq!(bytes, vec![2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+}
+
+#[test]
+fn test_configuration_round_trip() {
+    let mut voting_rights = BTreeMap::new();
+    voting_rights.insert(Author(0), 3);
+    voting_rights.insert(Author(2), 1);
+    let mut key_versions = BTreeMap::new();
+    key_versions.insert(Author(2), 1);
+    let configuration =
+        EpochConfiguration::with_resilience(voting_rights, 5).with_key_versions(key_versions);
+    let bytes = to_canonical_bytes(&configuration);
+    assert_eq!(
+        from_canonical_bytes::<EpochConfiguration>(&bytes).unwrap(),
+        configuration
+    );
+    // Resilience below 2.
+    let mut tampered = Vec::new();
+    Vec::<(Author, usize)>::new().encode(&mut tampered);
+    1usize.encode(&mut tampered);
+    Vec::<(Author, usize)>::new().encode(&mut tampered);
+    assert!(EpochConfiguration::decode(&mut Reader::new(&tampered)).is_err());
+}
+
+#[test]
+fn test_invalid_bytes() {
+    let record = test_vectors::canonical_test_vectors().pop().unwrap().record;
+    let bytes = to_canonical_bytes(&record);
+    assert!(from_canonical_bytes::<Record>(&[]).is_err());
+    // Unknown version.
+    let mut tampered = bytes.clone();
+    tampered[0] = VERSION + 1;
+    assert!(from_canonical_bytes::<Record>(&tampered).is_err());
+    // Truncated payload.
+    assert!(from_canonical_bytes::<Record>(&bytes[..bytes.len() - 1]).is_err());
+    // Trailing bytes.
+    let mut tampered = bytes.clone();
+    tampered.push(0);
+    assert!(from_canonical_bytes::<Record>(&tampered).is_err());
+    // Unknown kind of record.
+    let mut tampered = bytes;
+    tampered[2] = 7;
+    assert!(from_canonical_bytes::<Record>(&tampered).is_err());
+}
+
+#[test]
+fn test_digest_excludes_signature() {
+    let mut record = test_vectors::canonical_test_vectors().pop().unwrap().record;
+    let digest = record.digest(&Fnv1a);
+    match &mut record {
+        Record::Timeout(timeout) => timeout.signature = Signature(0),
+        _ => unreachable!(),
+    }
+    assert_eq!(record.digest(&Fnv1a), digest);
+}
+
+proptest! {
+    #[test]
+    fn prop_records_round_trip(records in arb_near_valid_records(4, 6, 3)) {
+        for record in records {
+            let bytes = to_canonical_bytes(&record);
+            prop_assert_eq!(from_canonical_bytes::<Record>(&bytes).unwrap(), record);
+        }
+    }
+}
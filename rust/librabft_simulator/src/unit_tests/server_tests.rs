@@ -0,0 +1,46 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+const CRASH_ONE_NODE: &str = include_str!("../../scenarios/crash_one_node.toml");
+
+#[test]
+fn test_job_lifecycle() {
+    let server = CampaignServer::new();
+    let response = server.handle("POST", "/jobs?seed=3", CRASH_ONE_NODE);
+    assert_eq!(response.status, 201);
+    assert_eq!(response.body, r#"{"id":0}"#);
+
+    let mut status = server.handle("GET", "/jobs/0", "");
+    while status.body.contains("running") {
+        assert_eq!(server.handle("GET", "/jobs/0/report", "").status, 409);
+        thread::sleep(std::time::Duration::from_millis(10));
+        status = server.handle("GET", "/jobs/0", "");
+    }
+    assert_eq!(status.body, r#"{"id":0,"status":"done","seed":3}"#);
+
+    let report = server.handle("GET", "/jobs/0/report", "");
+    assert_eq!(report.status, 200);
+    let scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    assert_eq!(
+        report.body,
+        serde_json::to_string(&campaign::run(&scenario, Some(3))).unwrap()
+    );
+    let trace = server.handle("GET", "/jobs/0/trace", "");
+    assert_eq!(trace.status, 200);
+    assert!(trace.body.contains(r#""kind":"timer""#));
+}
+
+#[test]
+fn test_invalid_requests() {
+    let server = CampaignServer::new();
+    assert_eq!(server.handle("POST", "/jobs", "nodes = 0").status, 400);
+    assert_eq!(
+        server.handle("POST", "/jobs?seed=x", CRASH_ONE_NODE).status,
+        400
+    );
+    assert_eq!(server.handle("GET", "/jobs/0", "").status, 404);
+    assert_eq!(server.handle("GET", "/jobs", "").body, "[]");
+    assert_eq!(server.handle("DELETE", "/jobs", "").status, 404);
+}
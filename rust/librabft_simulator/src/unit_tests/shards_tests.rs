@@ -0,0 +1,53 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::StopConditions;
+
+const SHARDS: &str = include_str!("../../scenarios/shards.toml");
+
+#[test]
+fn test_shard_scenario() {
+    let scenario = Scenario::from_toml(SHARDS).unwrap();
+    let shard = scenario.shard_scenario(1);
+    assert_eq!(shard.voting_rights, vec![0, 0, 0, 1, 1, 1, 1]);
+    assert_ne!(shard.chain_id, scenario.shard_scenario(0).chain_id);
+    assert!(shard.shards.is_empty());
+
+    let context = ShardedContext::new(&[scenario.shard_scenario(0), shard], Author(3));
+    assert_eq!(context.shards().len(), 2);
+
+    assert!(
+        Scenario::from_toml("nodes = 4\nmax_clock = 10\n[[shards]]\nmembers = [1, 4]").is_err()
+    );
+    assert!(
+        Scenario::from_toml("nodes = 4\nmax_clock = 10\n[[shards]]\nmembers = [1, 1]").is_err()
+    );
+    assert!(Scenario::from_toml(
+        "nodes = 4\nmax_clock = 10\ntwins = [1]\n[[shards]]\nmembers = [1, 2]"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_shards() {
+    let scenario = Scenario::from_toml(SHARDS).unwrap();
+    let report = run_shards(&scenario, None);
+    assert_eq!(report.shards.len(), 2);
+    for shard in &report.shards {
+        assert!(shard.consistent);
+        assert!(shard.commits.iter().all(|commits| *commits > 5));
+        assert!(shard.qc_latency.is_some());
+    }
+    // Validators only receive the messages of their shards.
+    assert!(report.bandwidth.received.iter().all(|bytes| *bytes > 0));
+
+    // Each shard commits on its own chain.
+    let mut sim = scenario.build_sharded_simulator();
+    sim.run_until(GlobalTime(1000), &StopConditions::default());
+    let node = sim.replicas()[3].node();
+    let commits = node.instance_commits();
+    assert_eq!(commits.len(), 2);
+    assert_ne!(commits[0].1[0].block, commits[1].1[0].block);
+    assert!(sim.replicas()[0].node().shard(1).is_none());
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use bft_simulator_runtime::bandwidth::PayloadSizes;
 
 #[test]
 fn test_happened_before() {
@@ -9,29 +10,35 @@ fn test_happened_before() {
     let mut s2 = SimulatedLedgerState::new();
     assert!(!s1.happened_just_before(&s2));
     s1.execute(
-        Command {
+        Some(Command {
             proposer: Author(0),
             index: 0,
-        },
+        }),
         NodeTime(1),
     );
     assert!(!s1.happened_just_before(&s2));
     assert!(s2.happened_just_before(&s1));
     s1.execute(
-        Command {
+        Some(Command {
             proposer: Author(1),
             index: 0,
-        },
+        }),
         NodeTime(1),
     );
     s2.execute(
-        Command {
+        Some(Command {
             proposer: Author(1),
             index: 0,
-        },
+        }),
         NodeTime(1),
     );
     assert!(!s2.happened_just_before(&s1));
+    // Empty blocks are also part of the history.
+    let mut s3 = s1.clone();
+    s3.execute(None, NodeTime(2));
+    assert!(s1.happened_just_before(&s3));
+    assert_ne!(s1.key(), s3.key());
+    assert_eq!(s3.execution_history, s1.execution_history);
 }
 
 #[test]
@@ -47,22 +54,22 @@ fn test_simulated_context() {
     let c3 = context.fetch().unwrap();
 
     let s1 = context
-        .compute(&s0, c1, NodeTime(1), None, Vec::new())
+        .compute(&s0, Some(c1), NodeTime(1), None, Vec::new())
         .unwrap();
     assert_eq!(context.read_epoch_id(&s1), EpochId(0));
 
     let s2 = context
-        .compute(&s1, c2, NodeTime(4), None, Vec::new())
+        .compute(&s1, Some(c2), NodeTime(4), None, Vec::new())
         .unwrap();
     assert_eq!(context.read_epoch_id(&s2), EpochId(1));
 
     let s3 = context
-        .compute(&s0, c3, NodeTime(3), None, Vec::new())
+        .compute(&s0, Some(c3), NodeTime(3), None, Vec::new())
         .unwrap();
     assert_eq!(context.read_epoch_id(&s3), EpochId(0));
 
-    context.commit(&s1, None);
-    context.commit(&s2, None);
+    context.commit(&s1, NodeTime(1), None);
+    context.commit(&s2, NodeTime(2), None);
     context.discard(&s3);
 
     assert_eq!(
@@ -85,3 +92,283 @@ fn test_simulated_context() {
         ]
     )
 }
+
+#[test]
+fn test_submit() {
+    let mut context = SimulatedContext::new(Author(2), 4, 10);
+    let command = context.submit();
+    assert_eq!(context.mempool_depth(), 1);
+    assert_eq!(context.fetch(), Some(command));
+    assert_eq!(context.fetch().unwrap().index, 1);
+}
+
+#[test]
+fn test_fetch_excluding_clients() {
+    let mut context = SimulatedContext::new(Author(0), 4, 10);
+    context.set_clients(2);
+    let censored = vec![1].into_iter().collect();
+    assert_eq!(context.fetch_excluding_clients(&censored).unwrap().index, 0);
+    // The next command arriving is from client 1.
+    assert_eq!(context.fetch_excluding_clients(&censored), None);
+    assert_eq!(context.fetch_excluding_clients(&censored).unwrap().index, 2);
+    let pending: Vec<_> = context.pending_commands().map(|c| c.index).collect();
+    assert_eq!(pending, vec![1]);
+    assert_eq!(context.fetch().unwrap().index, 1);
+}
+
+#[test]
+fn test_fetch_with_limits() {
+    let mut context = SimulatedContext::new(Author(0), 4, 10);
+    context.set_arrival_rate(0);
+    context.set_payload_sizes(PayloadSizes::Uniform { min: 0, max: 1000 });
+    let commands: Vec<_> = (0..10).map(|_| context.submit()).collect();
+    let small = commands
+        .iter()
+        .find(|command| context.payload_size(command) <= 300)
+        .unwrap()
+        .clone();
+    assert_eq!(
+        context.fetch_with_limits(Priority::Low, Some(300)),
+        Some(small.clone())
+    );
+    // Larger payloads stay in the mempool.
+    assert_eq!(context.mempool_depth(), 9);
+    assert_eq!(context.fetch_with_limits(Priority::Low, Some(0)), None);
+    assert_eq!(context.fetch(), commands.into_iter().find(|c| *c != small));
+}
+
+fn make_block(index: usize, time: i64) -> Block {
+    Block {
+        command: Some(Command {
+            proposer: Author(0),
+            index,
+        }),
+        time: NodeTime(time),
+        previous_quorum_certificate_hash: QuorumCertificateHash(0),
+        round: Round(index),
+        author: Author(0),
+        signature: Signature(0),
+    }
+}
+
+#[test]
+fn test_rollback() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    let s1 = context
+        .speculate(BlockHash(1), &make_block(1, 1), &s0, None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .speculate(BlockHash(2), &make_block(2, 2), &s0, None, Vec::new())
+        .unwrap();
+    context
+        .speculate(BlockHash(3), &make_block(3, 3), &s2, None, Vec::new())
+        .unwrap();
+    context.commit(&s1, NodeTime(1), None);
+    // Blocks that were never executed are ignored.
+    context.rollback(&[BlockHash(2), BlockHash(3), BlockHash(4)]);
+    assert_eq!(context.rolled_back_states(), 2);
+    assert_eq!(context.pending_states(), 0);
+}
+
+#[test]
+#[should_panic(expected = "consistent with the committed ledger")]
+fn test_rollback_of_committed_branch() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    let s1 = context
+        .speculate(BlockHash(1), &make_block(1, 1), &s0, None, Vec::new())
+        .unwrap();
+    context
+        .speculate(BlockHash(2), &make_block(2, 2), &s1, None, Vec::new())
+        .unwrap();
+    context.commit(&s1, NodeTime(1), None);
+    context.rollback(&[BlockHash(2)]);
+}
+
+#[test]
+fn test_prune() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    let s1 = context
+        .speculate(BlockHash(1), &make_block(1, 1), &s0, None, Vec::new())
+        .unwrap();
+    context
+        .speculate(BlockHash(2), &make_block(2, 2), &s0, None, Vec::new())
+        .unwrap();
+    context
+        .speculate(BlockHash(3), &make_block(3, 3), &s1, None, Vec::new())
+        .unwrap();
+    context.commit(&s1, NodeTime(1), None);
+    // Pruning up to an older commit does nothing.
+    context.prune(&s0);
+    assert_eq!(context.pending_states(), 2);
+    // Only the state extending the commit is kept.
+    context.prune(&s1);
+    assert_eq!(context.pruned_states(), 1);
+    assert_eq!(context.pending_states(), 1);
+    // Pruned blocks are not rolled back.
+    context.rollback(&[BlockHash(2)]);
+    assert_eq!(context.rolled_back_states(), 0);
+}
+
+#[test]
+fn test_snapshots() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    let s1 = context
+        .speculate(BlockHash(1), &make_block(1, 1), &s0, None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .speculate(BlockHash(2), &make_block(2, 2), &s1, None, Vec::new())
+        .unwrap();
+    context.commit(&s1, NodeTime(1), None);
+    context.commit(&s2, NodeTime(2), None);
+    let snapshot = context.export_snapshot(&s2).unwrap();
+    assert_eq!(context.export_snapshot(&s1), None);
+
+    let epoch = EpochChange::new(context.read_epoch_id(&s2), &context.configuration(&s2));
+
+    let mut other = SimulatedContext::new(Author(1), 1, 10);
+    // Snapshots must decode to the expected state.
+    assert!(!other.import_snapshot(&s1, &epoch, &snapshot));
+    assert!(!other.import_snapshot(&s2, &epoch, &snapshot[1..]));
+    // Snapshots must start the announced epoch.
+    let other_epoch = EpochChange::new(EpochId(1), &context.configuration(&s2));
+    assert!(!other.import_snapshot(&s2, &other_epoch, &snapshot));
+    assert_eq!(other.last_committed_state(), s0);
+    assert!(other.import_snapshot(&s2, &epoch, &snapshot));
+    assert_eq!(other.last_committed_state(), s2);
+    assert_eq!(other.committed_history(), context.committed_history());
+    // Snapshots must extend the committed ledger.
+    assert!(!other.import_snapshot(&s2, &epoch, &snapshot));
+}
+
+#[test]
+#[should_panic(expected = "Committing a state that was pruned")]
+fn test_commit_of_pruned_state() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    let s1 = context
+        .speculate(BlockHash(1), &make_block(1, 1), &s0, None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .speculate(BlockHash(2), &make_block(2, 2), &s0, None, Vec::new())
+        .unwrap();
+    context.commit(&s1, NodeTime(1), None);
+    context.prune(&s1);
+    context.commit(&s2, NodeTime(2), None);
+}
+
+#[test]
+fn test_processing_time() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    context
+        .compute(&s0, None, NodeTime(1), None, Vec::new())
+        .unwrap();
+    context.verified_signatures(4);
+    // CPU work is free by default.
+    assert_eq!(context.take_processing_time(), 0);
+    context.set_cpu_costs(2, 5);
+    context
+        .compute(&s0, None, NodeTime(1), None, Vec::new())
+        .unwrap();
+    context.verified_signatures(4);
+    context
+        .speculate(BlockHash(1), &make_block(1, 1), &s0, None, Vec::new())
+        .unwrap();
+    // Empty blocks have nothing to execute.
+    assert_eq!(context.take_processing_time(), 2 * 4 + 5);
+    assert_eq!(context.take_processing_time(), 0);
+}
+
+#[test]
+fn test_committed_times_agree() {
+    let scenario = scenario::Scenario::from_toml("nodes = 4\nmax_clock = 1000\nseed = 2").unwrap();
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(bft_simulator_runtime::simulator::GlobalTime(1000), None);
+    let mut times = HashMap::new();
+    for index in 0..4 {
+        let context = sim.simulated_node(Author(index)).context();
+        let time = context.last_committed_time().unwrap();
+        assert!(time > NodeTime(0) && time <= NodeTime(1000));
+        // Committed times only depend on the committed blocks.
+        let state = context.last_committed_state();
+        assert_eq!(*times.entry(state).or_insert(time), time);
+    }
+}
+
+#[test]
+fn test_validate_payload() {
+    let mut context = SimulatedContext::new(Author(0), 4, usize::MAX);
+    let s0 = context.last_committed_state();
+    let command = context.fetch().unwrap();
+    assert!(context.validate_payload(&s0, Some(&command)));
+    assert!(context.validate_payload(&s0, None));
+    let s1 = context
+        .compute(&s0, Some(command.clone()), NodeTime(1), None, Vec::new())
+        .unwrap();
+    // Executing the same command again would be a double spend.
+    assert!(!context.validate_payload(&s1, Some(&command)));
+    assert!(context.validate_payload(&s1, context.clone().fetch().as_ref()));
+    assert!(context.validate_payload(&s1, None));
+    // Without the parent state, execution decides.
+    assert!(context.validate_payload(&State(0), Some(&command)));
+}
+
+#[test]
+fn test_cross_shard_transactions() {
+    let operation =
+        |transaction, phase| Some(CrossShardOperation { transaction, phase }.command(Author(0)));
+    let mut state = SimulatedLedgerState::new();
+    state.execute(operation(0, TransactionPhase::Prepare), NodeTime(1));
+    state.execute(operation(1, TransactionPhase::Prepare), NodeTime(2));
+    state.execute(operation(2, TransactionPhase::Abort), NodeTime(3));
+    state.execute(operation(2, TransactionPhase::Prepare), NodeTime(4));
+    let statuses = state.transactions(None);
+    assert_eq!(statuses[&0], TransactionStatus::Prepared);
+    assert_eq!(statuses[&1], TransactionStatus::Prepared);
+    assert_eq!(statuses[&2], TransactionStatus::Aborted);
+
+    // With a single key, the second transaction waits for the lock of the first one.
+    let statuses = state.transactions(Some(1));
+    assert_eq!(statuses[&1], TransactionStatus::Refused);
+    state.execute(operation(0, TransactionPhase::Commit), NodeTime(5));
+    state.execute(operation(1, TransactionPhase::Abort), NodeTime(6));
+    state.execute(operation(3, TransactionPhase::Prepare), NodeTime(7));
+    // Duplicates change nothing.
+    state.execute(operation(0, TransactionPhase::Abort), NodeTime(8));
+    let statuses = state.transactions(Some(1));
+    assert_eq!(statuses[&0], TransactionStatus::Committed);
+    assert_eq!(statuses[&1], TransactionStatus::Aborted);
+    assert_eq!(statuses[&3], TransactionStatus::Prepared);
+}
+
+#[test]
+fn test_submit_operation() {
+    let mut context = SimulatedContext::new(Author(0), 1, 10);
+    let s0 = context.last_committed_state();
+    let operation = context.submit_operation(CrossShardOperation {
+        transaction: 0,
+        phase: TransactionPhase::Prepare,
+    });
+    assert_eq!(context.fetch(), Some(operation.clone()));
+    // Operations stay in the mempool until they are committed, but are not proposed again
+    // while a pending block carries them.
+    let s1 = context
+        .compute(&s0, Some(operation.clone()), NodeTime(1), None, Vec::new())
+        .unwrap();
+    assert_eq!(context.fetch().unwrap().index, 0);
+    context.discard(&s1);
+    assert_eq!(context.fetch(), Some(operation.clone()));
+    let s1 = context
+        .compute(&s0, Some(operation.clone()), NodeTime(1), None, Vec::new())
+        .unwrap();
+    context.commit(&s1, NodeTime(1), None);
+    assert_eq!(context.fetch().unwrap().index, 1);
+    assert!(context
+        .pending_commands()
+        .all(|command| *command != operation));
+    assert_eq!(context.transactions()[&0], TransactionStatus::Prepared);
+}
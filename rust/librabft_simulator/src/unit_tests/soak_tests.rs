@@ -0,0 +1,64 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+fn parameters() -> SoakParameters {
+    SoakParameters {
+        epochs: 20,
+        reconfiguration_period: 3,
+        ..SoakParameters::default()
+    }
+}
+
+#[test]
+fn test_soak_reaches_the_target_epoch() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 1000").unwrap();
+    let report = soak(&base, 5, &parameters()).unwrap();
+    assert_eq!(report.violation, None);
+    assert!(!report.stalled);
+    assert_eq!(report.epochs.iter().cloned().max(), Some(20));
+    assert_eq!(report.reconfigurations, 6);
+    assert!(report.crashes + report.partitions > 0);
+    assert_eq!(report.max_past_record_stores, 2);
+    assert!(report.commits.iter().all(|commits| *commits >= 150));
+    // Runs are reproducible.
+    assert_eq!(soak(&base, 5, &parameters()).unwrap(), report);
+}
+
+#[test]
+fn test_past_record_stores_are_pruned() {
+    let base = Scenario::from_toml("nodes = 4\nfollowers = 1\nmax_clock = 1000").unwrap();
+    let parameters = SoakParameters {
+        past_epochs_kept: 1,
+        fault_rate: 0.0,
+        ..parameters()
+    };
+    let report = soak(&base, 2, &parameters).unwrap();
+    assert_eq!(report.violation, None);
+    assert_eq!(report.epochs.len(), 5);
+    assert_eq!(report.max_past_record_stores, 1);
+    assert_eq!(report.crashes + report.partitions, 0);
+    assert!(report.lagging.is_empty());
+}
+
+#[test]
+fn test_invalid_parameters() {
+    let base = Scenario::from_toml("nodes = 4\nmax_clock = 1000").unwrap();
+    for parameters in &[
+        SoakParameters {
+            past_epochs_kept: 0,
+            ..parameters()
+        },
+        SoakParameters {
+            fault_rate: 1.5,
+            ..parameters()
+        },
+        SoakParameters {
+            reconfiguration_period: 0,
+            ..parameters()
+        },
+    ] {
+        assert!(soak(&base, 1, parameters).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use campaign;
+use node::NodeState;
+use scenario::Scenario;
+
+fn new_node(author: Author, num_nodes: usize) -> (NodeState, SimulatedContext) {
+    let context = SimulatedContext::new(author, num_nodes, 30000);
+    let node = NodeState::new(
+        author,
+        context.last_committed_state(),
+        NodeTime(0),
+        100_000,
+        20,
+        2.0,
+        0.5,
+        &context,
+    );
+    (node, context)
+}
+
+#[test]
+fn test_execute_rounds() {
+    let all = (0..4).map(Author).collect::<Vec<_>>();
+    let outcomes = vec![
+        RoundOutcome::Proposal {
+            voters: all.clone(),
+        },
+        RoundOutcome::Proposal {
+            voters: vec![Author(0)],
+        },
+        RoundOutcome::Timeout {
+            authors: Vec::new(),
+        },
+        RoundOutcome::Proposal { voters: all },
+    ];
+    let records = execute_rounds(4, &outcomes);
+    let count = |f: fn(&Record) -> bool| records.iter().filter(|r| f(r)).count();
+    assert_eq!(count(|r| matches!(r, Record::Block(_))), 3);
+    assert_eq!(count(|r| matches!(r, Record::Vote(_))), 9);
+    assert_eq!(count(|r| matches!(r, Record::QuorumCertificate(_))), 2);
+    // Rounds 2 and 3 both end with a timeout certificate.
+    assert_eq!(count(|r| matches!(r, Record::Timeout(_))), 6);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn prop_valid_records_are_accepted(records in arb_valid_records(4, 8)) {
+        let (mut node, mut context) = new_node(Author(0), 4);
+        for record in records.clone() {
+            let _ = node.insert_network_record(EpochId(0), record, &mut context);
+        }
+        let highest_qc_round = records
+            .iter()
+            .filter_map(|record| match record {
+                Record::QuorumCertificate(qc) => Some(qc.round),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(Round(0));
+        prop_assert_eq!(
+            node.record_store().highest_quorum_certificate_round(),
+            highest_qc_round
+        );
+    }
+
+    #[test]
+    fn prop_nodes_never_commit_conflicting_states(
+        schedule in arb_near_valid_records(4, 8, 4)
+            .prop_flat_map(|records| arb_network_schedule(4, records))
+    ) {
+        let mut committed = BTreeMap::new();
+        for (index, records) in schedule.into_iter().enumerate() {
+            let (mut node, mut context) = new_node(Author(index), 4);
+            for record in records {
+                let _ = node.insert_network_record(EpochId(0), record, &mut context);
+                node.process_commits(NodeTime(0), &mut context);
+                // Commits are delivered only once, as in `update_node`.
+                node.update_tracker(NodeTime(0));
+            }
+            for (round, state) in node.record_store().committed_states_after(Round(0)) {
+                let expected = committed.entry(round).or_insert_with(|| state.clone());
+                prop_assert_eq!(&*expected, &state);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_simulations_are_safe(
+        pacemaker in arb_pacemaker_parameters(),
+        network in arb_network_parameters(),
+        faults in arb_crash_faults(4, 500, 3),
+        seed in any::<u64>(),
+    ) {
+        let scenario = Scenario {
+            pacemaker,
+            network,
+            faults,
+            ..Scenario::from_toml("nodes = 4\nmax_clock = 500").unwrap()
+        };
+        prop_assert!(campaign::run(&scenario, Some(seed)).consistent);
+    }
+}
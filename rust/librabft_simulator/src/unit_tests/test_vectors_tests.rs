@@ -0,0 +1,27 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+// Regenerate with `cargo run --bin librabft_simulator -- --generate_test_vectors
+// rust/librabft_simulator/test_vectors/records.json` after an intended format change.
+const RECORDS: &str = include_str!("../../test_vectors/records.json");
+
+#[test]
+fn test_canonical_test_vectors() {
+    let vectors: Vec<TestVector> = serde_json::from_str(RECORDS).unwrap();
+    for vector in &vectors {
+        assert!(vector.check().is_ok(), "{:?}", vector.check());
+    }
+    assert_eq!(vectors, canonical_test_vectors());
+}
+
+#[test]
+fn test_tampered_test_vector() {
+    let mut vector = canonical_test_vectors().pop().unwrap();
+    match &mut vector.record {
+        Record::Timeout(timeout) => timeout.round = Round(5),
+        _ => unreachable!(),
+    }
+    assert!(vector.check().is_err());
+}
@@ -0,0 +1,69 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use campaign::run;
+use scenario::Scenario;
+
+// Two byzantine identities out of 4: each side of the partition forms a quorum.
+const DIVERGENCE: &str = "nodes = 4\nmax_clock = 2000\ntwins = [0, 1]\nlock_violators = [0, 1]\n\
+                          seed = 0\n[stop]\non_violation = true\n\
+                          [[partitions]]\ngroups = [[0, 1, 2], [4, 5, 3]]\nstart = 0\nend = 1000";
+
+#[test]
+fn test_triage_bundle() {
+    let scenario = Scenario::from_toml(DIVERGENCE).unwrap();
+    let report = run(&scenario, None);
+    let triage = report.triage.unwrap();
+    let round = &triage["round"];
+    assert!(triage["violation"]
+        .as_str()
+        .unwrap()
+        .starts_with("Execution diverged"));
+    let replicas = triage["replicas"].as_array().unwrap();
+    // Validators first, then the twins of validators 0 and 1.
+    assert_eq!(replicas.len(), 6);
+    let honest: Vec<_> = replicas
+        .iter()
+        .filter(|replica| replica["honest"] == true)
+        .collect();
+    assert_eq!(honest.len(), 2);
+    // The honest validators committed different blocks at the round, and each knows a QC
+    // for the block that it committed.
+    let commits: Vec<_> = honest
+        .iter()
+        .map(|replica| &replica["summary"]["commits"])
+        .collect();
+    assert_ne!(commits[0], commits[1]);
+    for replica in honest {
+        let summary = &replica["summary"];
+        assert_eq!(summary["records_available"], true);
+        let committed_block = &summary["commits"][0][0];
+        assert!(summary["quorum_certificates"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|qc| &qc["certified_block_hash"] == committed_block));
+        assert!(!summary["blocks"].as_array().unwrap().is_empty());
+        assert!(summary["highest_committed_round"].as_u64() >= round.as_u64());
+    }
+}
+
+#[test]
+fn test_triage_dir() {
+    let dir = std::env::temp_dir().join(format!("triage_tests_{}", std::process::id()));
+    let mut scenario = Scenario::from_toml(DIVERGENCE).unwrap();
+    scenario.triage_dir = Some(dir.to_string_lossy().into_owned());
+    let report = run(&scenario, None);
+    let path = dir.join("divergence_0.json");
+    let bundle: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(Some(bundle), report.triage);
+    match report.outcome.reason {
+        bft_simulator_runtime::simulator::StopReason::Violation(violation) => {
+            assert!(violation.ends_with(&format!("(triage bundle in {})", path.display())))
+        }
+        reason => panic!("Unexpected outcome {:?}", reason),
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,56 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::simulator::GlobalTime;
+
+const CRASH_ONE_NODE: &str = include_str!("../../scenarios/crash_one_node.toml");
+const DOUBLE_SPENDING: &str = include_str!("../../scenarios/double_spending.toml");
+
+fn run(scenario: &Scenario) -> UnhappyPathReport {
+    let mut sim = scenario.build_simulator();
+    sim.loop_until(GlobalTime(scenario.max_clock), None);
+    analyze_unhappy_path(scenario, &sim)
+}
+
+#[test]
+fn test_happy_chain() {
+    let fixture = fixtures::ChainFixture::new(4, 10);
+    let mut report = UnhappyPathReport::default();
+    report.observe_epoch(&fixture.store, fixture.store.configuration(), |_, _| {
+        LeaderFailure::Network
+    });
+    assert_eq!(report, UnhappyPathReport::default());
+    assert_eq!(report.failed_rounds(), 0);
+}
+
+#[test]
+fn test_crashed_leaders() {
+    let mut scenario = Scenario::from_toml(CRASH_ONE_NODE).unwrap();
+    scenario.seed = Some(0);
+    let report = run(&scenario);
+    assert!(report.leader_failures[&LeaderFailure::Crashed] > 0);
+    assert!(!report
+        .leader_failures
+        .contains_key(&LeaderFailure::Byzantine));
+    let streaks: usize = report
+        .failed_round_streaks
+        .iter()
+        .map(|(length, count)| length * count)
+        .sum();
+    assert_eq!(streaks, report.failed_rounds());
+}
+
+#[test]
+fn test_byzantine_leaders() {
+    let scenario = Scenario::from_toml(DOUBLE_SPENDING).unwrap();
+    assert_eq!(
+        byzantine_validators(&scenario)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![0]
+    );
+    let report = run(&scenario);
+    assert!(report.leader_failures[&LeaderFailure::Byzantine] > 0);
+    assert!(!report.leader_failures.contains_key(&LeaderFailure::Crashed));
+}